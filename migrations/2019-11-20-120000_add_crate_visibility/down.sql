@@ -0,0 +1,2 @@
+DROP TABLE crate_acls;
+ALTER TABLE crates DROP COLUMN visibility;
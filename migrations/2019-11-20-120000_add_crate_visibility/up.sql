@@ -0,0 +1,10 @@
+ALTER TABLE crates ADD COLUMN visibility INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE crate_acls (
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (crate_id, user_id)
+);
+
+CREATE INDEX crate_acls_user_id ON crate_acls (user_id);
@@ -0,0 +1,3 @@
+ALTER TABLE versions DROP COLUMN reproducible;
+DROP TABLE build_verifications;
+DROP TABLE build_verifiers;
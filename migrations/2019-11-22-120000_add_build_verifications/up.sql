@@ -0,0 +1,23 @@
+CREATE TABLE build_verifiers (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    secret VARCHAR NOT NULL DEFAULT random_string(32),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE build_verifications (
+    version_id INTEGER NOT NULL REFERENCES versions (id) ON DELETE CASCADE,
+    verifier_id INTEGER NOT NULL REFERENCES build_verifiers (id) ON DELETE CASCADE,
+    reproducible BOOLEAN NOT NULL,
+    checksum VARCHAR NOT NULL,
+    log_url VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (version_id, verifier_id)
+);
+
+CREATE TRIGGER trigger_build_verifications_set_updated_at BEFORE UPDATE
+ON build_verifications
+FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
+
+ALTER TABLE versions ADD COLUMN reproducible BOOLEAN;
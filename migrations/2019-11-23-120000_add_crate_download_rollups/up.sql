@@ -0,0 +1,7 @@
+CREATE TABLE crate_download_rollups (
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    period VARCHAR NOT NULL CHECK (period IN ('week', 'month')),
+    period_start DATE NOT NULL,
+    downloads BIGINT NOT NULL,
+    PRIMARY KEY (crate_id, period, period_start)
+);
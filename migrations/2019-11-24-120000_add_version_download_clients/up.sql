@@ -0,0 +1,8 @@
+CREATE TABLE version_download_clients (
+    version_id INTEGER NOT NULL REFERENCES versions (id) ON DELETE CASCADE,
+    date DATE NOT NULL DEFAULT CURRENT_DATE,
+    cargo_version VARCHAR NOT NULL,
+    target VARCHAR NOT NULL,
+    downloads INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (version_id, date, cargo_version, target)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE dependency_graphs (
+    version_id INTEGER NOT NULL REFERENCES versions (id) ON DELETE CASCADE,
+    depth INTEGER NOT NULL,
+    graph JSONB NOT NULL,
+    resolved_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (version_id, depth)
+);
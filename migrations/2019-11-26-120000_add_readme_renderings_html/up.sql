@@ -0,0 +1 @@
+ALTER TABLE readme_renderings ADD COLUMN html TEXT;
@@ -0,0 +1 @@
+ALTER TABLE versions ADD COLUMN docs_built BOOLEAN;
@@ -0,0 +1,6 @@
+CREATE TABLE version_files (
+    version_id INTEGER NOT NULL REFERENCES versions (id) ON DELETE CASCADE,
+    path VARCHAR NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY (version_id, path)
+);
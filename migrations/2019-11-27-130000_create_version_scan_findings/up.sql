@@ -0,0 +1,11 @@
+CREATE TABLE version_scan_findings (
+    id SERIAL PRIMARY KEY,
+    version_id INTEGER NOT NULL REFERENCES versions (id) ON DELETE CASCADE,
+    path VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    detail VARCHAR NOT NULL,
+    reviewed BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX version_scan_findings_unreviewed ON version_scan_findings (created_at) WHERE NOT reviewed;
@@ -0,0 +1,3 @@
+DROP TABLE admin_actions;
+ALTER TABLE crates DROP COLUMN locked;
+ALTER TABLE users DROP COLUMN is_admin, DROP COLUMN is_banned;
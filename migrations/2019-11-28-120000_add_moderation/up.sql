@@ -0,0 +1,15 @@
+ALTER TABLE users
+    ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN is_banned BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE crates ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE admin_actions (
+    id SERIAL PRIMARY KEY,
+    admin_id INTEGER NOT NULL REFERENCES users (id),
+    action INTEGER NOT NULL,
+    -- Crates can be deleted, so they are referenced by name
+    crate_name VARCHAR,
+    user_id INTEGER REFERENCES users (id),
+    time TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,12 @@
+CREATE TABLE crate_reports (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    reporter_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category INTEGER NOT NULL,
+    description VARCHAR NOT NULL,
+    resolved BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX crate_reports_reporter_id_created_at ON crate_reports (reporter_id, created_at);
+CREATE INDEX crate_reports_unresolved ON crate_reports (created_at) WHERE NOT resolved;
@@ -0,0 +1,3 @@
+DROP TABLE approved_crate_names;
+DELETE FROM crate_reports WHERE reporter_id IS NULL;
+ALTER TABLE crate_reports ALTER COLUMN reporter_id SET NOT NULL;
@@ -0,0 +1,9 @@
+-- Reports filed by the registry itself, such as for typosquatting, have no reporter
+ALTER TABLE crate_reports ALTER COLUMN reporter_id DROP NOT NULL;
+
+CREATE TABLE approved_crate_names (
+    -- The canonical name, as returned by `canon_crate_name`
+    name VARCHAR PRIMARY KEY,
+    approved_by INTEGER NOT NULL REFERENCES users (id),
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
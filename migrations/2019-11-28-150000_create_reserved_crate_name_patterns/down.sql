@@ -0,0 +1,2 @@
+ALTER TABLE admin_actions DROP COLUMN detail;
+DROP TABLE reserved_crate_name_patterns;
@@ -0,0 +1,9 @@
+CREATE TABLE reserved_crate_name_patterns (
+    id SERIAL PRIMARY KEY,
+    -- 0 for prefixes, 1 for regular expressions
+    kind INTEGER NOT NULL,
+    pattern VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+ALTER TABLE admin_actions ADD COLUMN detail VARCHAR;
@@ -0,0 +1,14 @@
+CREATE TABLE name_claims (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    claimant_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reason VARCHAR NOT NULL,
+    -- 0 pending, 1 released, 2 contested, 3 approved, 4 rejected
+    status INTEGER NOT NULL DEFAULT 0,
+    response VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    responded_at TIMESTAMP
+);
+
+CREATE INDEX name_claims_crate_id ON name_claims (crate_id);
+CREATE INDEX name_claims_open ON name_claims (created_at) WHERE status < 3;
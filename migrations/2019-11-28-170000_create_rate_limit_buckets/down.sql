@@ -0,0 +1,12 @@
+CREATE TABLE publish_limit_buckets(
+  user_id INTEGER PRIMARY KEY NOT NULL REFERENCES users,
+  tokens INTEGER NOT NULL,
+  last_refill TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO publish_limit_buckets (user_id, tokens, last_refill)
+    SELECT CAST(substring(owner FROM 6) AS INTEGER), tokens, last_refill
+    FROM rate_limit_buckets
+    WHERE action = 0 AND owner LIKE 'user:%';
+
+DROP TABLE rate_limit_buckets;
@@ -0,0 +1,14 @@
+CREATE TABLE rate_limit_buckets (
+    -- 0 publishing new crates, 1 searches, 2 downloads, 3 other changes
+    action INTEGER NOT NULL,
+    -- `user:<id>`, `token:<hash>` or `ip:<address>`
+    owner VARCHAR NOT NULL,
+    tokens INTEGER NOT NULL,
+    last_refill TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (action, owner)
+);
+
+INSERT INTO rate_limit_buckets (action, owner, tokens, last_refill)
+    SELECT 0, 'user:' || user_id, tokens, last_refill FROM publish_limit_buckets;
+
+DROP TABLE publish_limit_buckets;
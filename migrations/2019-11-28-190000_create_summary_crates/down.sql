@@ -0,0 +1,2 @@
+DROP FUNCTION refresh_summary_crates();
+DROP MATERIALIZED VIEW summary_crates;
@@ -0,0 +1,44 @@
+DROP MATERIALIZED VIEW summary_crates;
+CREATE MATERIALIZED VIEW summary_crates (section, rank, crate_id) AS
+  (SELECT 'new'::TEXT, (ROW_NUMBER() OVER (ORDER BY created_at DESC))::INTEGER, id
+    FROM crates
+    ORDER BY created_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'just_updated'::TEXT, (ROW_NUMBER() OVER (ORDER BY updated_at DESC))::INTEGER, id
+    FROM crates
+    WHERE updated_at <> created_at
+    ORDER BY updated_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_downloaded'::TEXT, (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, id
+    FROM crates
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_recently_downloaded'::TEXT,
+      (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, crate_id
+    FROM recent_crate_downloads
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'trending'::TEXT,
+      (ROW_NUMBER() OVER (ORDER BY last_week / (earlier / 12.0 + 1) DESC))::INTEGER, crate_id
+    FROM (
+      SELECT versions.crate_id,
+        SUM(version_downloads.downloads)
+          FILTER (WHERE version_downloads.date > CURRENT_DATE - 7) AS last_week,
+        COALESCE(SUM(version_downloads.downloads)
+          FILTER (WHERE version_downloads.date <= CURRENT_DATE - 7), 0) AS earlier
+      FROM version_downloads
+        INNER JOIN versions
+          ON version_downloads.version_id = versions.id
+      WHERE version_downloads.date > CURRENT_DATE - 91
+      GROUP BY versions.crate_id
+    ) AS weekly_downloads
+    WHERE last_week >= 100
+    ORDER BY last_week / (earlier / 12.0 + 1) DESC
+    LIMIT 10);
+CREATE UNIQUE INDEX summary_crates_section_rank ON summary_crates (section, rank);
+
+DROP TABLE trending_crates;
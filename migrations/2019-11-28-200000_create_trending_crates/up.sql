@@ -0,0 +1,41 @@
+-- Computed by the `update_trending_crates` background job
+CREATE TABLE trending_crates (
+  crate_id INTEGER PRIMARY KEY REFERENCES crates (id) ON DELETE CASCADE,
+  rank INTEGER NOT NULL,
+  last_week_downloads BIGINT NOT NULL,
+  previous_week_downloads BIGINT NOT NULL,
+  growth DOUBLE PRECISION NOT NULL,
+  computed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX trending_crates_rank ON trending_crates (rank);
+
+-- The trending section of the summary is now taken from `trending_crates`
+DROP MATERIALIZED VIEW summary_crates;
+CREATE MATERIALIZED VIEW summary_crates (section, rank, crate_id) AS
+  (SELECT 'new'::TEXT, (ROW_NUMBER() OVER (ORDER BY created_at DESC))::INTEGER, id
+    FROM crates
+    ORDER BY created_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'just_updated'::TEXT, (ROW_NUMBER() OVER (ORDER BY updated_at DESC))::INTEGER, id
+    FROM crates
+    WHERE updated_at <> created_at
+    ORDER BY updated_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_downloaded'::TEXT, (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, id
+    FROM crates
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_recently_downloaded'::TEXT,
+      (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, crate_id
+    FROM recent_crate_downloads
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'trending'::TEXT, rank, crate_id
+    FROM trending_crates
+    ORDER BY rank
+    LIMIT 10);
+CREATE UNIQUE INDEX summary_crates_section_rank ON summary_crates (section, rank);
@@ -0,0 +1,7 @@
+-- The old slugs of renamed and merged categories
+CREATE TABLE category_redirects (
+  slug VARCHAR PRIMARY KEY,
+  category_id INTEGER NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX category_redirects_category_id ON category_redirects (category_id);
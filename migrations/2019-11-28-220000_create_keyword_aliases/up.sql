@@ -0,0 +1,7 @@
+-- Alternative spellings of keywords, replaced with `keyword` on publish
+CREATE TABLE keyword_aliases (
+  alias VARCHAR PRIMARY KEY,
+  keyword VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX keyword_aliases_keyword ON keyword_aliases (keyword);
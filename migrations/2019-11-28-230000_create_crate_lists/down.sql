@@ -0,0 +1,3 @@
+DROP TABLE crate_list_follows;
+DROP TABLE crate_list_items;
+DROP TABLE crate_lists;
@@ -0,0 +1,3 @@
+DROP FUNCTION followed_crates(INTEGER);
+DROP TABLE update_digest_subscriptions;
+DROP TABLE topic_follows;
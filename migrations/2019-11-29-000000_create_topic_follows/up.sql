@@ -0,0 +1,49 @@
+-- Follows of all crates with a keyword (kind 0) or in a category (kind 1).
+-- `target_id` is the id of the keyword or category.
+CREATE TABLE topic_follows (
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  kind INTEGER NOT NULL,
+  target_id INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, kind, target_id)
+);
+
+-- Users who get a weekly email with the new releases of the crates they follow
+CREATE TABLE update_digest_subscriptions (
+  user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The crates a user follows, directly or through a keyword or a category
+-- including its subcategories. Only releases made after a topic was followed
+-- are of interest, so `since` is the time of the earliest follow of each crate.
+-- Crates followed through topics have to be public.
+CREATE FUNCTION followed_crates(INTEGER)
+RETURNS TABLE (crate_id INTEGER, since TIMESTAMP) AS $$
+  SELECT crate_id, MIN(since) FROM (
+    SELECT follows.crate_id, '-infinity'::TIMESTAMP AS since
+    FROM follows
+    WHERE follows.user_id = $1
+    UNION ALL
+    SELECT crates_keywords.crate_id, topic_follows.created_at
+    FROM topic_follows
+    INNER JOIN crates_keywords ON crates_keywords.keyword_id = topic_follows.target_id
+    INNER JOIN crates ON crates.id = crates_keywords.crate_id
+    WHERE topic_follows.user_id = $1
+    AND topic_follows.kind = 0
+    AND crates.visibility = 0
+    UNION ALL
+    SELECT crates_categories.crate_id, topic_follows.created_at
+    FROM topic_follows
+    INNER JOIN categories ON categories.id = topic_follows.target_id
+    INNER JOIN categories subcategories
+      ON subcategories.slug = categories.slug
+      OR subcategories.slug LIKE categories.slug || '::%'
+    INNER JOIN crates_categories ON crates_categories.category_id = subcategories.id
+    INNER JOIN crates ON crates.id = crates_categories.crate_id
+    WHERE topic_follows.user_id = $1
+    AND topic_follows.kind = 1
+    AND crates.visibility = 0
+  ) followed
+  GROUP BY crate_id
+$$ LANGUAGE SQL STABLE;
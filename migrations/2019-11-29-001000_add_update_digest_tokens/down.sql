@@ -0,0 +1,3 @@
+ALTER TABLE update_digest_subscriptions
+  DROP COLUMN unsubscribe_token,
+  DROP COLUMN last_sent_at;
@@ -0,0 +1,5 @@
+-- The token in the unsubscribe link of the digest emails, and when the last
+-- digest was sent so a retried job doesn't email a subscriber twice
+ALTER TABLE update_digest_subscriptions
+  ADD COLUMN unsubscribe_token TEXT NOT NULL UNIQUE DEFAULT random_string(32),
+  ADD COLUMN last_sent_at TIMESTAMP;
@@ -0,0 +1,8 @@
+DROP TRIGGER bury_background_job ON background_jobs;
+DROP TRIGGER back_off_background_job ON background_jobs;
+DROP TRIGGER prioritize_background_job ON background_jobs;
+DROP FUNCTION bury_background_job();
+DROP FUNCTION back_off_background_job();
+DROP FUNCTION prioritize_background_job();
+DROP TABLE background_job_dead_letters;
+DROP TABLE background_job_policies;
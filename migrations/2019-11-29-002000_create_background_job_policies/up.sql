@@ -0,0 +1,93 @@
+-- How the jobs of a type are run. swirl picks the retriable job with the
+-- lowest id and waits `1 minute * 2 ^ retries` after a failure, so the
+-- triggers below implement priorities and backoff through the `id` and
+-- `last_retry` columns. Job types without a policy keep swirl's behavior.
+CREATE TABLE background_job_policies (
+  job_type TEXT PRIMARY KEY,
+  -- Jobs with a higher priority run before older jobs with a lower one
+  priority INTEGER NOT NULL DEFAULT 0 CHECK (priority BETWEEN -1000 AND 1000),
+  -- The failed attempts after which a job is moved to the dead letters, or
+  -- NULL to retry it forever
+  max_attempts INTEGER CHECK (max_attempts > 0),
+  -- The delay before the first retry, doubled after every further failure
+  backoff_seconds INTEGER NOT NULL DEFAULT 120 CHECK (backoff_seconds >= 0)
+);
+
+-- Jobs that failed more often than their policy allows, kept until an admin
+-- retries or discards them
+CREATE TABLE background_job_dead_letters (
+  id BIGSERIAL PRIMARY KEY,
+  job_id BIGINT NOT NULL,
+  job_type TEXT NOT NULL,
+  data JSONB NOT NULL,
+  retries INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  dead_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Offsets the id of new jobs by their priority. The sequence stays far below
+-- the offset of a single priority step, so ids remain unique.
+CREATE FUNCTION prioritize_background_job() RETURNS trigger AS $$
+BEGIN
+  NEW.id := NEW.id - 1000000000000000 * COALESCE(
+    (SELECT priority FROM background_job_policies WHERE job_type = NEW.job_type),
+    0
+  );
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER prioritize_background_job
+  BEFORE INSERT ON background_jobs
+  FOR EACH ROW EXECUTE PROCEDURE prioritize_background_job();
+
+-- Moves `last_retry` of a failed job so that swirl's retry condition holds
+-- once the backoff of its policy has passed.
+CREATE FUNCTION back_off_background_job() RETURNS trigger AS $$
+DECLARE
+  backoff INTERVAL;
+BEGIN
+  SELECT make_interval(secs => backoff_seconds * 2 ^ (NEW.retries - 1)) INTO backoff
+    FROM background_job_policies WHERE job_type = NEW.job_type;
+  IF FOUND THEN
+    NEW.last_retry := NEW.last_retry + backoff - interval '1 minute' * 2 ^ NEW.retries;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER back_off_background_job
+  BEFORE UPDATE OF retries ON background_jobs
+  FOR EACH ROW WHEN (NEW.retries > OLD.retries)
+  EXECUTE PROCEDURE back_off_background_job();
+
+CREATE FUNCTION bury_background_job() RETURNS trigger AS $$
+BEGIN
+  IF NEW.retries >= (SELECT max_attempts FROM background_job_policies WHERE job_type = NEW.job_type) THEN
+    INSERT INTO background_job_dead_letters (job_id, job_type, data, retries, created_at)
+      VALUES (NEW.id, NEW.job_type, NEW.data, NEW.retries, NEW.created_at);
+    DELETE FROM background_jobs WHERE id = NEW.id;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bury_background_job
+  AFTER UPDATE OF retries ON background_jobs
+  FOR EACH ROW WHEN (NEW.retries > OLD.retries)
+  EXECUTE PROCEDURE bury_background_job();
+
+-- Index updates are waited on by the publishing user and must not be lost,
+-- emails are given up on after a few attempts, and the periodic jobs are
+-- enqueued again by the scheduler anyway.
+INSERT INTO background_job_policies (job_type, priority, max_attempts, backoff_seconds) VALUES
+  ('add_crate', 10, NULL, 30),
+  ('yank', 10, NULL, 30),
+  ('delete_crate', 10, NULL, 30),
+  ('render_and_upload_readme', 5, 10, 120),
+  ('scan_crate_file', 5, 10, 120),
+  ('notify_dependents_of_yank', 0, 5, 300),
+  ('send_update_digests', 0, 5, 300),
+  ('update_downloads', -5, 3, 120),
+  ('rollup_downloads', -5, 3, 120),
+  ('dump_db', -10, 3, 600);
@@ -0,0 +1,6 @@
+-- When the background workers last enqueued each scheduled job, so a job is
+-- only enqueued once per scheduled minute when several workers are running
+CREATE TABLE scheduled_jobs (
+  name TEXT PRIMARY KEY,
+  last_enqueued_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE OR REPLACE FUNCTION bury_background_job() RETURNS trigger AS $$
+BEGIN
+  IF NEW.retries >= (SELECT max_attempts FROM background_job_policies WHERE job_type = NEW.job_type) THEN
+    INSERT INTO background_job_dead_letters (job_id, job_type, data, retries, created_at)
+      VALUES (NEW.id, NEW.job_type, NEW.data, NEW.retries, NEW.created_at);
+    DELETE FROM background_jobs WHERE id = NEW.id;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE background_job_dead_letters DROP COLUMN trace_id;
+ALTER TABLE background_jobs DROP COLUMN trace_id;
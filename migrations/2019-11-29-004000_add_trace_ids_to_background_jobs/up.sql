@@ -0,0 +1,16 @@
+-- The trace ID of the request that enqueued a job, taken from the session
+-- setting the server sets for every request
+ALTER TABLE background_jobs
+  ADD COLUMN trace_id TEXT DEFAULT NULLIF(current_setting('crates_io.trace_id', true), '');
+ALTER TABLE background_job_dead_letters ADD COLUMN trace_id TEXT;
+
+CREATE OR REPLACE FUNCTION bury_background_job() RETURNS trigger AS $$
+BEGIN
+  IF NEW.retries >= (SELECT max_attempts FROM background_job_policies WHERE job_type = NEW.job_type) THEN
+    INSERT INTO background_job_dead_letters (job_id, job_type, data, retries, created_at, trace_id)
+      VALUES (NEW.id, NEW.job_type, NEW.data, NEW.retries, NEW.created_at, NEW.trace_id);
+    DELETE FROM background_jobs WHERE id = NEW.id;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,19 @@
+-- Publishes uploaded in chunks, so that interrupted uploads can be resumed
+CREATE TABLE publish_sessions (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  length INTEGER NOT NULL,
+  received INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX publish_sessions_user_id ON publish_sessions (user_id);
+CREATE INDEX publish_sessions_updated_at ON publish_sessions (updated_at);
+SELECT diesel_manage_updated_at('publish_sessions');
+
+CREATE TABLE publish_session_chunks (
+  session_id INTEGER NOT NULL REFERENCES publish_sessions (id) ON DELETE CASCADE,
+  position INTEGER NOT NULL,
+  data BYTEA NOT NULL,
+  PRIMARY KEY (session_id, position)
+);
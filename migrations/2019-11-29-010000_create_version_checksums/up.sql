@@ -0,0 +1,9 @@
+-- The checksums of the crate files of versions, so that mirrors can verify
+-- them without reading the index. The SHA512 checksums, and the checksums of
+-- versions published before this table existed, are filled in by the
+-- `backfill_checksums` background job.
+CREATE TABLE version_checksums (
+    version_id INTEGER PRIMARY KEY REFERENCES versions (id) ON DELETE CASCADE,
+    sha256 VARCHAR NOT NULL,
+    sha512 VARCHAR
+);
@@ -0,0 +1,13 @@
+-- Versions their owners deleted shortly after publishing them. The version
+-- numbers are kept so that they can't be published again with a different
+-- crate file, which would break `Cargo.lock` checksums.
+CREATE TABLE deleted_versions (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    num VARCHAR NOT NULL,
+    published_at TIMESTAMP NOT NULL,
+    downloads INTEGER NOT NULL,
+    deleted_by INTEGER NOT NULL REFERENCES users (id),
+    deleted_at TIMESTAMP NOT NULL DEFAULT now(),
+    UNIQUE (crate_id, num)
+);
@@ -0,0 +1,14 @@
+-- Crates their owners deleted. The names are kept from being published again
+-- until `available_at`, so that nobody can take over a deleted name before
+-- the users of the crate noticed the deletion.
+CREATE TABLE deleted_crates (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    downloads INTEGER NOT NULL,
+    deleted_by INTEGER NOT NULL REFERENCES users (id),
+    deleted_at TIMESTAMP NOT NULL DEFAULT now(),
+    available_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX deleted_crates_canon_crate_name ON deleted_crates (canon_crate_name(name));
@@ -0,0 +1,3 @@
+ALTER TABLE crates
+    DROP COLUMN max_version,
+    DROP COLUMN max_stable_version;
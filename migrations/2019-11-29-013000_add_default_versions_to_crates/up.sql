@@ -0,0 +1,8 @@
+-- The latest version of each crate, stored so that listing crates doesn't
+-- require sorting all of their versions. Both are maintained when versions
+-- are published, yanked, unyanked or deleted. `max_version` is only NULL for
+-- crates published before these columns existed, until the
+-- `backfill_default_versions` background job fills them in.
+ALTER TABLE crates
+    ADD COLUMN max_version VARCHAR,
+    ADD COLUMN max_stable_version VARCHAR;
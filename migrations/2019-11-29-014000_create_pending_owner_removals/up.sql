@@ -0,0 +1,15 @@
+-- Removals of individual crate owners that wait for another owner to confirm
+-- them, or for a delay to pass during which they can be cancelled. Rows are
+-- deleted once the removal takes effect or is cancelled.
+CREATE TABLE pending_owner_removals (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    owner_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    requested_by INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    requested_at TIMESTAMP NOT NULL DEFAULT now(),
+    -- NULL if another owner has to confirm the removal
+    effective_at TIMESTAMP,
+    UNIQUE (crate_id, owner_id)
+);
+
+CREATE INDEX pending_owner_removals_effective_at ON pending_owner_removals (effective_at);
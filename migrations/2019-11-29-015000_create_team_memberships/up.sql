@@ -0,0 +1,13 @@
+-- The GitHub team memberships of users, cached to avoid asking GitHub on
+-- every request by a member of a team that owns crates. `expires_at` is
+-- pushed back whenever the membership is checked again.
+CREATE TABLE team_memberships (
+    team_id INTEGER NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    is_member BOOLEAN NOT NULL,
+    checked_at TIMESTAMP NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX team_memberships_expires_at ON team_memberships (expires_at);
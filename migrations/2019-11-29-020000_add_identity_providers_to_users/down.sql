@@ -0,0 +1,3 @@
+ALTER TABLE users
+    DROP COLUMN provider,
+    DROP COLUMN provider_user_id;
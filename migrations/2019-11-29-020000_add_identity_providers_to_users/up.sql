@@ -0,0 +1,7 @@
+-- Users can log in with GitLab or Bitbucket as well as GitHub. Their logins
+-- are qualified with the name of the provider, and `gh_id` is `-1` for them.
+ALTER TABLE users
+    ADD COLUMN provider VARCHAR NOT NULL DEFAULT 'github',
+    ADD COLUMN provider_user_id VARCHAR;
+
+CREATE UNIQUE INDEX users_provider_user_id ON users (provider, provider_user_id);
@@ -0,0 +1,22 @@
+-- The identities users can log in with. Every user has the identity they
+-- signed up with, and can link identities of other providers to their
+-- account. An identity belongs to at most one user.
+CREATE TABLE identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider VARCHAR NOT NULL,
+    provider_user_id VARCHAR NOT NULL,
+    login VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    UNIQUE (provider, provider_user_id),
+    UNIQUE (user_id, provider)
+);
+
+INSERT INTO identities (user_id, provider, provider_user_id, login)
+    SELECT id, 'github', gh_id::text, gh_login FROM users
+    WHERE provider = 'github' AND gh_id > 0;
+
+INSERT INTO identities (user_id, provider, provider_user_id, login)
+    SELECT id, provider, provider_user_id, substring(gh_login from position(':' in gh_login) + 1)
+    FROM users
+    WHERE provider <> 'github' AND provider_user_id IS NOT NULL;
@@ -0,0 +1,15 @@
+-- The browser sessions of users. The session cookie holds the token of the
+-- session, so that users can see where they are logged in and revoke
+-- sessions.
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token VARCHAR NOT NULL DEFAULT random_string(32) UNIQUE,
+    ip VARCHAR,
+    user_agent VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMP NOT NULL DEFAULT now(),
+    revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
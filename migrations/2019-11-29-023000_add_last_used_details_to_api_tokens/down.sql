@@ -0,0 +1,4 @@
+ALTER TABLE api_tokens
+    DROP COLUMN last_used_ip,
+    DROP COLUMN last_used_user_agent,
+    DROP COLUMN unused_notified_at;
@@ -0,0 +1,8 @@
+-- Where API tokens were last used from, so that users can tell whether a
+-- token leaked. `unused_notified_at` is set once the owner of a token that
+-- wasn't used for a long time was told about it, and cleared when the token
+-- is used again.
+ALTER TABLE api_tokens
+    ADD COLUMN last_used_ip VARCHAR,
+    ADD COLUMN last_used_user_agent VARCHAR,
+    ADD COLUMN unused_notified_at TIMESTAMP;
@@ -0,0 +1,14 @@
+-- The users designated by the owners of crates to take them over once their
+-- sole owner is inactive for `inactive_months`. `warned_at` is when the
+-- owner was last told that the crate is about to be transferred.
+CREATE TABLE crate_successors (
+    crate_id INTEGER PRIMARY KEY REFERENCES crates (id) ON DELETE CASCADE,
+    successor_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    designated_by INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    inactive_months INTEGER NOT NULL,
+    accepted BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    warned_at TIMESTAMP
+);
+
+CREATE INDEX crate_successors_successor_id ON crate_successors (successor_id);
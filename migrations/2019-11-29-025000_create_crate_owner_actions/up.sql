@@ -0,0 +1,13 @@
+-- Changes owners make to their crates outside of publishing, like editing
+-- the metadata of a crate.
+CREATE TABLE crate_owner_actions (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    owner_id INTEGER NOT NULL REFERENCES users (id),
+    action INTEGER NOT NULL,
+    -- Anything else needed to understand the action, e.g. the changed fields
+    detail VARCHAR,
+    time TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX crate_owner_actions_crate_id ON crate_owner_actions (crate_id);
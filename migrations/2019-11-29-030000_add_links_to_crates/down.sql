@@ -0,0 +1 @@
+ALTER TABLE crates DROP COLUMN links;
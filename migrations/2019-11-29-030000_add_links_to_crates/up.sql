@@ -0,0 +1,3 @@
+-- Links to the CI, chat, sponsoring page and changelog of a crate, keyed by
+-- kind. Replaces the badges, which only knew a fixed list of services.
+ALTER TABLE crates ADD COLUMN links JSONB NOT NULL DEFAULT '{}';
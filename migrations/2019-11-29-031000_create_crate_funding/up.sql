@@ -0,0 +1,7 @@
+-- Where to fund the development of a crate, one URL per funding platform.
+CREATE TABLE crate_funding (
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    platform VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    PRIMARY KEY (crate_id, platform)
+);
@@ -0,0 +1,11 @@
+-- The share of the downloads of a crate in the last 90 days that went to each
+-- of its versions, recomputed daily by the `update_version_adoption` job.
+CREATE TABLE version_adoption (
+    version_id INTEGER PRIMARY KEY REFERENCES versions (id) ON DELETE CASCADE,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    downloads BIGINT NOT NULL,
+    share FLOAT8 NOT NULL,
+    computed_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX version_adoption_crate_id ON version_adoption (crate_id);
@@ -0,0 +1,9 @@
+CREATE TABLE version_provenance (
+    version_id INTEGER PRIMARY KEY REFERENCES versions (id) ON DELETE CASCADE,
+    provider VARCHAR NOT NULL,
+    run_url VARCHAR,
+    commit_sha VARCHAR NOT NULL,
+    verified BOOLEAN,
+    verified_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+DROP TRIGGER trigger_crates_unverify_repository ON crates;
+DROP FUNCTION unverify_repository_on_repository_change();
+ALTER TABLE crates DROP COLUMN repository_verified;
@@ -0,0 +1,16 @@
+-- Whether an owner of the crate proved that they control the repository the
+-- crate links to. Changing the repository requires verifying it again.
+ALTER TABLE crates ADD COLUMN repository_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE FUNCTION unverify_repository_on_repository_change() RETURNS trigger AS $$
+  BEGIN
+    IF NEW.repository IS DISTINCT FROM OLD.repository THEN
+      NEW.repository_verified := false;
+    END IF;
+    RETURN NEW;
+  END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_crates_unverify_repository BEFORE UPDATE
+ON crates
+FOR EACH ROW EXECUTE PROCEDURE unverify_repository_on_repository_change();
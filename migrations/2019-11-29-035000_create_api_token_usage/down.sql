@@ -0,0 +1,2 @@
+DROP TABLE api_token_usage;
+ALTER TABLE api_tokens DROP COLUMN daily_quota;
@@ -0,0 +1,10 @@
+-- The number of requests each API token made per day, and the number of
+-- requests a token may make per day if it's limited.
+ALTER TABLE api_tokens ADD COLUMN daily_quota INTEGER;
+
+CREATE TABLE api_token_usage (
+  api_token_id INTEGER NOT NULL REFERENCES api_tokens (id) ON DELETE CASCADE,
+  date DATE NOT NULL DEFAULT CURRENT_DATE,
+  requests INTEGER NOT NULL DEFAULT 1,
+  PRIMARY KEY (api_token_id, date)
+);
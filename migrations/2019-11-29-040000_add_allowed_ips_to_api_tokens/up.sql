@@ -0,0 +1,3 @@
+-- The IP addresses and CIDR ranges a token can be used from. Tokens can be
+-- used from anywhere if the list is empty.
+ALTER TABLE api_tokens ADD COLUMN allowed_ips TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,18 @@
+-- Days on which a crate was downloaded far more often than usual, likely by
+-- bots inflating its download count. Consecutive days form one window.
+-- `expected_downloads` are the downloads of the window at the crate's usual
+-- rate. The downloads above that are subtracted from the crate's download
+-- count while the anomaly is `excluded`.
+CREATE TABLE download_anomalies (
+  id SERIAL PRIMARY KEY,
+  crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+  start_date DATE NOT NULL,
+  end_date DATE NOT NULL,
+  downloads BIGINT NOT NULL,
+  expected_downloads BIGINT NOT NULL,
+  excluded BOOLEAN NOT NULL DEFAULT FALSE,
+  reviewed BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX download_anomalies_crate_id_end_date ON download_anomalies (crate_id, end_date);
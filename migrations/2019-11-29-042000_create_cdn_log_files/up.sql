@@ -0,0 +1,8 @@
+-- The CDN access logs whose downloads were added to `version_downloads` by
+-- the `ingest_cdn_logs` job, so that no log file is counted twice. `path` is
+-- the key of the file in the log bucket.
+CREATE TABLE cdn_log_files (
+  path VARCHAR PRIMARY KEY,
+  downloads INTEGER NOT NULL,
+  processed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
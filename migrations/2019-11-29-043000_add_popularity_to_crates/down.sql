@@ -0,0 +1,7 @@
+DROP TRIGGER trigger_crates_set_updated_at ON crates;
+CREATE TRIGGER trigger_crates_set_updated_at BEFORE UPDATE
+ON crates
+FOR EACH ROW EXECUTE PROCEDURE set_updated_at_ignore_downloads();
+DROP FUNCTION set_crates_updated_at();
+
+ALTER TABLE crates DROP COLUMN popularity;
@@ -0,0 +1,34 @@
+-- How popular a crate is, blending its recent downloads with the number of
+-- crates that depend on it. Computed by the `update_popularity` background
+-- job, and used to sort crates with `sort=popularity`.
+ALTER TABLE crates ADD COLUMN popularity DOUBLE PRECISION NOT NULL DEFAULT 0;
+CREATE INDEX crates_popularity ON crates (popularity DESC);
+
+-- Recomputing the popularity doesn't count as updating the crate, like
+-- counting downloads doesn't. The trigger function of `versions` can't refer
+-- to the new column, so crates get their own.
+CREATE FUNCTION set_crates_updated_at() RETURNS trigger AS $$
+DECLARE
+    new_downloads integer;
+    new_popularity double precision;
+BEGIN
+    new_downloads := NEW.downloads;
+    new_popularity := NEW.popularity;
+    OLD.downloads := NEW.downloads;
+    OLD.popularity := NEW.popularity;
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at
+    ) THEN
+        NEW.updated_at = CURRENT_TIMESTAMP;
+    END IF;
+    NEW.downloads := new_downloads;
+    NEW.popularity := new_popularity;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER trigger_crates_set_updated_at ON crates;
+CREATE TRIGGER trigger_crates_set_updated_at BEFORE UPDATE
+ON crates
+FOR EACH ROW EXECUTE PROCEDURE set_crates_updated_at();
@@ -0,0 +1 @@
+DROP INDEX index_keywords_keyword_trgm;
@@ -0,0 +1,3 @@
+-- Keyword suggestions also match keywords that are spelled similarly to the
+-- query, using the `%` operator of `pg_trgm`.
+CREATE INDEX index_keywords_keyword_trgm ON keywords USING gin (keyword gin_trgm_ops);
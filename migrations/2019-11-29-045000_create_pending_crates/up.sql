@@ -0,0 +1,9 @@
+-- New crates that are waiting for the approval of the registry
+-- administrators, see `Config::new_crate_approval`. Their visibility is
+-- `pending` until then, and `index_entry` is the index entry of their first
+-- version, which is only added to the index once they are approved.
+CREATE TABLE pending_crates (
+  crate_id INTEGER PRIMARY KEY REFERENCES crates (id) ON DELETE CASCADE,
+  index_entry JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- Namespaces for crates with names like `org::name`, see
+-- `Config::crate_namespaces`. Each namespace belongs to a team, whose members
+-- can publish new crates in it.
+CREATE TABLE namespaces (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  team_id INTEGER NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  created_by INTEGER NOT NULL REFERENCES users (id),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX namespaces_name ON namespaces (canon_crate_name(name));
@@ -0,0 +1,11 @@
+-- The old names of renamed crates, which keep resolving to the crate. The
+-- versions published before a rename stay in the index under the old name.
+CREATE TABLE crate_renames (
+  id SERIAL PRIMARY KEY,
+  old_name VARCHAR NOT NULL,
+  crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+  renamed_by INTEGER NOT NULL REFERENCES users (id),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX crate_renames_old_name ON crate_renames (canon_crate_name(old_name));
+CREATE INDEX crate_renames_crate_id ON crate_renames (crate_id);
@@ -0,0 +1,29 @@
+DROP MATERIALIZED VIEW summary_crates;
+CREATE MATERIALIZED VIEW summary_crates (section, rank, crate_id) AS
+  (SELECT 'new'::TEXT, (ROW_NUMBER() OVER (ORDER BY created_at DESC))::INTEGER, id
+    FROM crates
+    ORDER BY created_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'just_updated'::TEXT, (ROW_NUMBER() OVER (ORDER BY updated_at DESC))::INTEGER, id
+    FROM crates
+    WHERE updated_at <> created_at
+    ORDER BY updated_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_downloaded'::TEXT, (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, id
+    FROM crates
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_recently_downloaded'::TEXT,
+      (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, crate_id
+    FROM recent_crate_downloads
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'trending'::TEXT, rank, crate_id
+    FROM trending_crates
+    ORDER BY rank
+    LIMIT 10);
+CREATE UNIQUE INDEX summary_crates_section_rank ON summary_crates (section, rank);
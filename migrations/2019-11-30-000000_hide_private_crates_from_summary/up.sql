@@ -0,0 +1,38 @@
+-- Private and pending crates are left out of every section of the summary
+DROP MATERIALIZED VIEW summary_crates;
+CREATE MATERIALIZED VIEW summary_crates (section, rank, crate_id) AS
+  (SELECT 'new'::TEXT, (ROW_NUMBER() OVER (ORDER BY created_at DESC))::INTEGER, id
+    FROM crates
+    WHERE visibility = 0
+    ORDER BY created_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'just_updated'::TEXT, (ROW_NUMBER() OVER (ORDER BY updated_at DESC))::INTEGER, id
+    FROM crates
+    WHERE updated_at <> created_at AND visibility = 0
+    ORDER BY updated_at DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_downloaded'::TEXT, (ROW_NUMBER() OVER (ORDER BY downloads DESC))::INTEGER, id
+    FROM crates
+    WHERE visibility = 0
+    ORDER BY downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'most_recently_downloaded'::TEXT,
+      (ROW_NUMBER() OVER (ORDER BY recent_crate_downloads.downloads DESC))::INTEGER, crate_id
+    FROM recent_crate_downloads
+      INNER JOIN crates
+        ON recent_crate_downloads.crate_id = crates.id
+    WHERE crates.visibility = 0
+    ORDER BY recent_crate_downloads.downloads DESC
+    LIMIT 10)
+  UNION ALL
+  (SELECT 'trending'::TEXT, (ROW_NUMBER() OVER (ORDER BY rank))::INTEGER, crate_id
+    FROM trending_crates
+      INNER JOIN crates
+        ON trending_crates.crate_id = crates.id
+    WHERE crates.visibility = 0
+    ORDER BY rank
+    LIMIT 10);
+CREATE UNIQUE INDEX summary_crates_section_rank ON summary_crates (section, rank);
@@ -0,0 +1 @@
+ALTER TABLE publish_sessions DROP COLUMN crate_name;
@@ -0,0 +1,4 @@
+-- Sessions started before the crate name was stored can't be checked against
+-- the crate they are finished for, so they have to be started again
+DELETE FROM publish_sessions;
+ALTER TABLE publish_sessions ADD COLUMN crate_name VARCHAR NOT NULL;
@@ -0,0 +1 @@
+DROP TABLE private_index_entries;
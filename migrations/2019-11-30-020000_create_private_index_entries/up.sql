@@ -0,0 +1,12 @@
+-- The index entries of private crates, which are left out of the shared
+-- index, see `models::PrivateIndexEntry`. `name` is the name the version was
+-- published under, which differs from the name of the crate for versions
+-- published before it was renamed.
+CREATE TABLE private_index_entries (
+  id SERIAL PRIMARY KEY,
+  crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  num VARCHAR NOT NULL,
+  entry JSONB NOT NULL,
+  UNIQUE (crate_id, num)
+);
@@ -0,0 +1,29 @@
+CREATE OR REPLACE FUNCTION followed_crates(INTEGER)
+RETURNS TABLE (crate_id INTEGER, since TIMESTAMP) AS $$
+  SELECT crate_id, MIN(since) FROM (
+    SELECT follows.crate_id, '-infinity'::TIMESTAMP AS since
+    FROM follows
+    WHERE follows.user_id = $1
+    UNION ALL
+    SELECT crates_keywords.crate_id, topic_follows.created_at
+    FROM topic_follows
+    INNER JOIN crates_keywords ON crates_keywords.keyword_id = topic_follows.target_id
+    INNER JOIN crates ON crates.id = crates_keywords.crate_id
+    WHERE topic_follows.user_id = $1
+    AND topic_follows.kind = 0
+    AND crates.visibility = 0
+    UNION ALL
+    SELECT crates_categories.crate_id, topic_follows.created_at
+    FROM topic_follows
+    INNER JOIN categories ON categories.id = topic_follows.target_id
+    INNER JOIN categories subcategories
+      ON subcategories.slug = categories.slug
+      OR subcategories.slug LIKE categories.slug || '::%'
+    INNER JOIN crates_categories ON crates_categories.category_id = subcategories.id
+    INNER JOIN crates ON crates.id = crates_categories.crate_id
+    WHERE topic_follows.user_id = $1
+    AND topic_follows.kind = 1
+    AND crates.visibility = 0
+  ) followed
+  GROUP BY crate_id
+$$ LANGUAGE SQL STABLE;
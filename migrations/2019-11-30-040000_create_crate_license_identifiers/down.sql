@@ -0,0 +1 @@
+DROP VIEW crate_license_identifiers;
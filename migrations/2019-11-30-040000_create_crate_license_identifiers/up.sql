@@ -0,0 +1,12 @@
+-- The license identifiers in the license expressions of the non-yanked
+-- versions of each crate, split the same way the `license` search filter
+-- splits them, so that `MIT OR Apache-2.0` yields both `MIT` and `Apache-2.0`.
+-- A crate has a row per version mentioning an identifier.
+CREATE VIEW crate_license_identifiers AS
+SELECT versions.crate_id, identifier AS license
+FROM versions
+CROSS JOIN LATERAL unnest(regexp_split_to_array(versions.license, '[[:space:]/()]+')) identifier
+WHERE NOT versions.yanked
+AND versions.license IS NOT NULL
+AND identifier <> ''
+AND upper(identifier) NOT IN ('AND', 'OR', 'WITH');
@@ -0,0 +1,4 @@
+-- The secrets can't be recovered, so every verifier gets a new one
+ALTER TABLE build_verifiers
+  ADD COLUMN secret VARCHAR NOT NULL DEFAULT random_string(32),
+  DROP COLUMN secret_hash;
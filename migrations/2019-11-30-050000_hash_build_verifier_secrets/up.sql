@@ -0,0 +1,10 @@
+-- Only the signing key derived from the secret of a build verifier is kept,
+-- see `BuildVerifier::signing_key`, so that the secret itself can't be read
+-- from the database or its dumps and backups.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+ALTER TABLE build_verifiers ADD COLUMN secret_hash VARCHAR;
+UPDATE build_verifiers SET secret_hash = encode(digest(secret, 'sha256'), 'hex');
+ALTER TABLE build_verifiers
+  ALTER COLUMN secret_hash SET NOT NULL,
+  DROP COLUMN secret;
@@ -0,0 +1 @@
+DELETE FROM background_job_policies WHERE job_type = 'deliver_email';
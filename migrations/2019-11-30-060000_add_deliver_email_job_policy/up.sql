@@ -0,0 +1,4 @@
+-- Emails are sent by the background worker, and given up on after a few
+-- attempts like the jobs that send notifications
+INSERT INTO background_job_policies (job_type, priority, max_attempts, backoff_seconds) VALUES
+  ('deliver_email', 0, 5, 60);
@@ -1,10 +1,10 @@
 //! Application-wide components in a struct accessible from each request
 
-use crate::{db, Config, Env};
+use crate::identity::{self, IdentityProvider};
+use crate::{db, metrics::Metrics, response_cache::ResponseCache, Config, Env};
 use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use diesel::r2d2;
-use oauth2::basic::BasicClient;
 use reqwest::Client;
 use scheduled_thread_pool::ScheduledThreadPool;
 
@@ -16,8 +16,12 @@ pub struct App {
     /// The database connection pool
     pub diesel_database: db::DieselPool,
 
-    /// The GitHub OAuth2 configuration
-    pub github: BasicClient,
+    /// The connection pool of the read-only replica of the database, if one
+    /// is configured. See `RequestTransaction::read_conn`.
+    pub read_only_replica_database: Option<db::DieselPool>,
+
+    /// The OAuth providers users can log in with, GitHub first
+    pub identity_providers: Vec<Box<dyn IdentityProvider>>,
 
     /// A unique key used with conduit_cookie to generate cookies
     pub session_key: String,
@@ -35,6 +39,12 @@ pub struct App {
     /// this is either None (in which case any attempt to create an outgoing connection
     /// will panic) or a `Client` configured with a per-test replay proxy.
     pub(crate) http_client: Option<Client>,
+
+    /// The cache of responses of frequently requested read endpoints
+    pub response_cache: ResponseCache,
+
+    /// The metrics exported by the `GET /metrics` route
+    pub metrics: Metrics,
 }
 
 impl App {
@@ -42,24 +52,10 @@ impl App {
     ///
     /// Configures and sets up:
     ///
-    /// - GitHub OAuth
-    /// - Database connection pools
+    /// - The OAuth clients of the identity providers
+    /// - Database connection pools, for the primary database and its read-only replica
     /// - A `git2::Repository` instance from the index repo checkout (that server.rs ensures exists)
     pub fn new(config: &Config, http_client: Option<Client>) -> App {
-        use oauth2::prelude::*;
-        use oauth2::{AuthUrl, ClientId, ClientSecret, Scope, TokenUrl};
-        use url::Url;
-
-        let github = BasicClient::new(
-            ClientId::new(config.gh_client_id.clone()),
-            Some(ClientSecret::new(config.gh_client_secret.clone())),
-            AuthUrl::new(Url::parse("https://github.com/login/oauth/authorize").unwrap()),
-            Some(TokenUrl::new(
-                Url::parse("https://github.com/login/oauth/access_token").unwrap(),
-            )),
-        )
-        .add_scope(Scope::new("read:org".to_string()));
-
         let db_pool_size = match (dotenv::var("DB_POOL_SIZE"), config.env) {
             (Ok(num), _) => num.parse().expect("couldn't parse DB_POOL_SIZE"),
             (_, Env::Production) => 10,
@@ -84,31 +80,57 @@ impl App {
             (_, Env::Test) => 1,
             _ => 30,
         };
-        let read_only_mode = dotenv::var("READ_ONLY_MODE").is_ok();
-        let connection_config = db::ConnectionConfig {
-            statement_timeout: db_connection_timeout,
-            read_only: read_only_mode,
+        let db_statement_timeout = match dotenv::var("DB_STATEMENT_TIMEOUT") {
+            Ok(num) => num.parse().expect("couldn't parse DB_STATEMENT_TIMEOUT"),
+            _ => db_connection_timeout,
         };
+        let read_only_mode = dotenv::var("READ_ONLY_MODE").is_ok();
 
         let thread_pool = Arc::new(ScheduledThreadPool::new(db_helper_threads));
 
-        let diesel_db_config = r2d2::Pool::builder()
-            .max_size(db_pool_size)
-            .min_idle(db_min_idle)
-            .connection_timeout(Duration::from_secs(db_connection_timeout))
-            .connection_customizer(Box::new(connection_config))
-            .thread_pool(thread_pool);
+        let diesel_db_config = |read_only: bool| {
+            let connection_config = db::ConnectionConfig {
+                statement_timeout: db_statement_timeout,
+                read_only,
+            };
+            r2d2::Pool::builder()
+                .max_size(db_pool_size)
+                .min_idle(db_min_idle)
+                .connection_timeout(Duration::from_secs(db_connection_timeout))
+                .connection_customizer(Box::new(connection_config))
+                .thread_pool(Arc::clone(&thread_pool))
+        };
+
+        let read_only_replica_database = config
+            .replica_db_url
+            .as_ref()
+            .map(|url| db::diesel_pool(url, config.env, diesel_db_config(true)));
 
         App {
-            diesel_database: db::diesel_pool(&config.db_url, config.env, diesel_db_config),
-            github,
+            diesel_database: db::diesel_pool(
+                &config.db_url,
+                config.env,
+                diesel_db_config(read_only_mode),
+            ),
+            read_only_replica_database,
+            identity_providers: identity::from_config(config),
             session_key: config.session_key.clone(),
             git_repo_checkout: config.git_repo_checkout.clone(),
             config: config.clone(),
             http_client,
+            response_cache: ResponseCache::new(&config.response_cache),
+            metrics: Metrics::default(),
         }
     }
 
+    /// Returns the enabled identity provider with the given name.
+    pub fn identity_provider(&self, name: &str) -> Option<&dyn IdentityProvider> {
+        self.identity_providers
+            .iter()
+            .find(|provider| provider.name() == name)
+            .map(|provider| &**provider)
+    }
+
     /// Returns a client for making HTTP requests to upload crate files.
     ///
     /// The client will go through a proxy if the application was configured via
@@ -0,0 +1,384 @@
+//! The async HTTP layer, serving requests with hyper on a tokio runtime.
+//!
+//! Connections are handled asynchronously, so that slow clients and idle
+//! keep-alive connections don't each tie up a thread like they do with civet.
+//! Routes can be served by async handlers, which run on the runtime itself.
+//! All other requests go through a compatibility shim to the conduit handler
+//! built by `build_handler`, which runs on a fixed pool of threads since the
+//! controllers and the database connections block. Controllers can be ported
+//! to async handlers one at a time.
+//!
+//! Async handlers bypass the conduit middleware, so only routes that don't
+//! need any of it are ported for now.
+
+use conduit::{Handler, Host, Method, Scheme};
+use conduit_hyper::semver;
+use futures::sync::oneshot;
+use futures::{future, stream, Future, Stream};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Chunk, HeaderMap, StatusCode};
+use scheduled_thread_pool::ScheduledThreadPool;
+use std::error::Error;
+use std::io::{self, Cursor, Read};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::controllers::health;
+use crate::logging;
+use crate::App;
+
+pub type ResponseFuture =
+    Box<dyn Future<Item = hyper::Response<Body>, Error = hyper::Error> + Send>;
+
+/// A route served without the conduit shim.
+pub type AsyncHandler = fn(Arc<App>, hyper::Request<Body>) -> ResponseFuture;
+
+/// The routes ported to async handlers. Paths are matched exactly.
+const ASYNC_ROUTES: &[(&str, &str, AsyncHandler)] = &[("GET", "/healthz", health::liveness_async)];
+
+// Can't derive Debug because of App and Handler.
+#[allow(missing_debug_implementations)]
+pub struct Service {
+    app: Arc<App>,
+    handler: Arc<dyn Handler>,
+    threads: Arc<ScheduledThreadPool>,
+}
+
+impl Service {
+    /// Creates a service for `app`, running the conduit `handler` on
+    /// `threads` threads.
+    pub fn new(app: Arc<App>, handler: impl Handler, threads: usize) -> Self {
+        Service {
+            app,
+            handler: Arc::new(handler),
+            threads: Arc::new(ScheduledThreadPool::with_name(
+                "conduit-handler-{}",
+                threads,
+            )),
+        }
+    }
+
+    /// Serves requests on `addr` until `shutdown` completes.
+    pub fn serve(
+        self,
+        addr: &SocketAddr,
+        shutdown: impl Future<Item = ()> + Send + 'static,
+    ) -> impl Future<Item = (), Error = hyper::Error> + Send {
+        let service = Arc::new(self);
+        hyper::Server::bind(addr)
+            .serve(make_service_fn(move |conn: &AddrStream| {
+                let service = Arc::clone(&service);
+                let remote_addr = conn.remote_addr();
+                service_fn(move |req| service.call(req, remote_addr))
+            }))
+            .with_graceful_shutdown(shutdown)
+    }
+
+    fn call(&self, req: hyper::Request<Body>, remote_addr: SocketAddr) -> ResponseFuture {
+        let route = ASYNC_ROUTES.iter().find(|(method, path, _)| {
+            req.method().as_str() == *method && req.uri().path() == *path
+        });
+        match route {
+            Some((_, _, handler)) => handler(Arc::clone(&self.app), req),
+            None => self.call_conduit(req, remote_addr),
+        }
+    }
+
+    /// Runs the conduit handler on the thread pool. The body is streamed to
+    /// the handler as it is received, so that large uploads aren't buffered.
+    fn call_conduit(&self, req: hyper::Request<Body>, remote_addr: SocketAddr) -> ResponseFuture {
+        let method = match conduit_method(req.method()) {
+            Some(method) => method,
+            None => return Box::new(future::ok(status(StatusCode::METHOD_NOT_ALLOWED))),
+        };
+        let handler = Arc::clone(&self.handler);
+        let (head, body) = req.into_parts();
+        let head = hyper::Request::from_parts(head, ());
+
+        let (tx, rx) = oneshot::channel();
+        self.threads.execute(move || {
+            let mut req = ConduitRequest::new(head, method, body, remote_addr);
+            let _ = tx.send(from_conduit(handler.call(&mut req)));
+        });
+        // The sender is dropped without a response if the handler panics
+        Box::new(rx.then(|response| {
+            Ok(response.unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR)))
+        }))
+    }
+}
+
+/// Converts the result of a conduit handler to a hyper response.
+pub fn from_conduit(
+    response: Result<conduit::Response, Box<dyn Error + Send>>,
+) -> hyper::Response<Body> {
+    let mut response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            logging::error("unhandled error", json!({ "error": e.to_string() }));
+            return status(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut body = Vec::new();
+    if let Err(e) = response.body.write_body(&mut body) {
+        logging::error(
+            "failed to write a response body",
+            json!({ "error": e.to_string() }),
+        );
+        return status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let mut builder = hyper::Response::builder();
+    builder.status(response.status.0 as u16);
+    for (name, values) in &response.headers {
+        for value in values {
+            builder.header(name.as_str(), value.as_str());
+        }
+    }
+    builder
+        .body(Body::from(body))
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status(status: StatusCode) -> hyper::Response<Body> {
+    let mut response = hyper::Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn conduit_method(method: &hyper::Method) -> Option<Method> {
+    match *method {
+        hyper::Method::GET => Some(Method::Get),
+        hyper::Method::HEAD => Some(Method::Head),
+        hyper::Method::POST => Some(Method::Post),
+        hyper::Method::PUT => Some(Method::Put),
+        hyper::Method::DELETE => Some(Method::Delete),
+        hyper::Method::PATCH => Some(Method::Patch),
+        hyper::Method::OPTIONS => Some(Method::Options),
+        _ => None,
+    }
+}
+
+/// A request passed on to the conduit handler.
+struct ConduitRequest {
+    head: hyper::Request<()>,
+    method: Method,
+    headers: Headers,
+    body: BodyReader,
+    remote_addr: SocketAddr,
+    extensions: conduit::Extensions,
+}
+
+impl ConduitRequest {
+    fn new(
+        mut head: hyper::Request<()>,
+        method: Method,
+        body: Body,
+        remote_addr: SocketAddr,
+    ) -> Self {
+        let headers = Headers(std::mem::replace(head.headers_mut(), HeaderMap::new()));
+        ConduitRequest {
+            head,
+            method,
+            headers,
+            body: BodyReader {
+                chunks: body.wait(),
+                chunk: Cursor::new(Chunk::default()),
+            },
+            remote_addr,
+            extensions: conduit::Extensions::new(),
+        }
+    }
+}
+
+impl conduit::Request for ConduitRequest {
+    fn http_version(&self) -> semver::Version {
+        let (major, minor) = match self.head.version() {
+            hyper::Version::HTTP_09 => (0, 9),
+            hyper::Version::HTTP_10 => (1, 0),
+            hyper::Version::HTTP_2 => (2, 0),
+            _ => (1, 1),
+        };
+        semver::Version {
+            major,
+            minor,
+            patch: 0,
+            pre: Vec::new(),
+            build: Vec::new(),
+        }
+    }
+
+    fn conduit_version(&self) -> semver::Version {
+        semver::Version {
+            major: 0,
+            minor: 8,
+            patch: 0,
+            pre: Vec::new(),
+            build: Vec::new(),
+        }
+    }
+
+    fn method(&self) -> Method {
+        self.method.clone()
+    }
+
+    /// TLS is terminated by Heroku's router.
+    fn scheme(&self) -> Scheme {
+        Scheme::Http
+    }
+
+    fn host(&self) -> Host<'_> {
+        Host::Name(self.headers.first("Host").unwrap_or(""))
+    }
+
+    fn virtual_root(&self) -> Option<&str> {
+        None
+    }
+
+    fn path(&self) -> &str {
+        self.head.uri().path()
+    }
+
+    fn query_string(&self) -> Option<&str> {
+        self.head.uri().query()
+    }
+
+    fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        self.headers
+            .first("Content-Length")
+            .and_then(|length| length.parse().ok())
+    }
+
+    fn headers(&self) -> &dyn conduit::Headers {
+        &self.headers
+    }
+
+    fn body(&mut self) -> &mut dyn Read {
+        &mut self.body
+    }
+
+    fn extensions(&self) -> &conduit::Extensions {
+        &self.extensions
+    }
+
+    fn mut_extensions(&mut self) -> &mut conduit::Extensions {
+        &mut self.extensions
+    }
+}
+
+/// Reads the body of a request on a handler thread, blocking until the
+/// runtime has received the next chunk.
+struct BodyReader {
+    chunks: stream::Wait<Body>,
+    chunk: Cursor<Chunk>,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.chunk.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.chunks.next() {
+                Some(Ok(chunk)) => self.chunk = Cursor::new(chunk),
+                Some(Err(e)) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// The headers of a request. Values that aren't valid UTF-8 are skipped.
+struct Headers(HeaderMap);
+
+impl Headers {
+    fn first(&self, name: &str) -> Option<&str> {
+        self.0.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+impl conduit::Headers for Headers {
+    fn find(&self, key: &str) -> Option<Vec<&str>> {
+        let values = self
+            .0
+            .get_all(key)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    fn all(&self) -> Vec<(&str, Vec<&str>)> {
+        self.0
+            .keys()
+            .map(|name| (name.as_str(), self.find(name.as_str()).unwrap_or_default()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use conduit::Request;
+
+    #[test]
+    fn requests_are_passed_on_to_conduit() {
+        let head = hyper::Request::get("/api/v1/crates?q=foo")
+            .header("Host", "crates.io")
+            .header("Accept", "text/html")
+            .header("accept", "application/json")
+            .header("Content-Length", "4")
+            .body(())
+            .unwrap();
+        let addr = ([127, 0, 0, 1], 8888).into();
+        let mut req = ConduitRequest::new(head, Method::Get, Body::from("body"), addr);
+
+        assert_eq!(req.path(), "/api/v1/crates");
+        assert_eq!(req.query_string(), Some("q=foo"));
+        match req.host() {
+            Host::Name(name) => assert_eq!(name, "crates.io"),
+            Host::Socket(_) => panic!("expected a host name"),
+        }
+        assert_eq!(
+            req.headers().find("ACCEPT"),
+            Some(vec!["text/html", "application/json"])
+        );
+        assert!(!req.headers().has("Cookie"));
+        assert_eq!(req.content_length(), Some(4));
+        let mut body = String::new();
+        req.body().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "body");
+    }
+
+    #[test]
+    fn conduit_responses_are_converted() {
+        let mut response = crate::util::json_response(&vec![1, 2]);
+        response.status = (404, "Not Found");
+        let response = from_conduit(Ok(response));
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "application/json; charset=utf-8"
+        );
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(&*body, b"[1,2]");
+
+        let error = io::Error::new(io::ErrorKind::Other, "boom");
+        let response = from_conduit(Err(Box::new(error)));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -2,6 +2,7 @@ use std::panic::AssertUnwindSafe;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use swirl::PerformError;
 
+use crate::branding::Branding;
 use crate::db::{DieselPool, DieselPooledConn};
 use crate::git::Repository;
 use crate::uploaders::Uploader;
@@ -26,6 +27,9 @@ pub struct Environment {
     pub connection_pool: AssertUnwindSafe<DieselPool>,
     pub uploader: Uploader,
     http_client: AssertUnwindSafe<reqwest::Client>,
+    /// The key the links in emails are signed with, see `email::Unsubscribe`
+    pub session_key: String,
+    pub branding: Branding,
 }
 
 // FIXME: AssertUnwindSafe should be `Clone`, this can be replaced with
@@ -37,6 +41,8 @@ impl Clone for Environment {
             connection_pool: AssertUnwindSafe(self.connection_pool.0.clone()),
             uploader: self.uploader.clone(),
             http_client: AssertUnwindSafe(self.http_client.0.clone()),
+            session_key: self.session_key.clone(),
+            branding: self.branding.clone(),
         }
     }
 }
@@ -47,12 +53,16 @@ impl Environment {
         connection_pool: DieselPool,
         uploader: Uploader,
         http_client: reqwest::Client,
+        session_key: String,
+        branding: Branding,
     ) -> Self {
         Self {
             index: Arc::new(Mutex::new(index)),
             connection_pool: AssertUnwindSafe(connection_pool),
             uploader,
             http_client: AssertUnwindSafe(http_client),
+            session_key,
+            branding,
         }
     }
 
@@ -68,7 +78,8 @@ impl Environment {
         Ok(repo)
     }
 
-    /// Returns a client for making HTTP requests to upload crate files.
+    /// Returns a client for making HTTP requests, like uploading crate files
+    /// or asking GitHub about team memberships.
     pub(crate) fn http_client(&self) -> &reqwest::Client {
         &self.http_client
     }
@@ -7,21 +7,38 @@
 //! the worker thread), we will rebuild the runner and try again up to 5 times.
 //! After the 5th occurrance, we will panic.
 //!
+//! The priority of a job type, how long to wait before retrying its failed
+//! jobs and after how many attempts to give up on them are configured in the
+//! `background_job_policies` table. Jobs that are given up on are kept in the
+//! `background_job_dead_letters` table until an admin retries or discards them
+//! through the `/admin/jobs` endpoints.
+//!
+//! Recurring jobs are enqueued by a scheduler thread, see the `scheduler`
+//! module for how their schedules are configured.
+//!
+//! The jobs of publishes are traced as part of the trace of the publish
+//! request, see the `telemetry` module for how to export traces.
+//!
 //! Usage:
 //!      cargo run --bin background-worker
 
 #![deny(warnings, clippy::all, rust_2018_idioms)]
 
 use cargo_registry::git::{Repository, RepositoryConfig};
+use cargo_registry::logging;
+use cargo_registry::scheduler::Scheduler;
 use cargo_registry::{background_jobs::*, db};
+use chrono::Utc;
 use diesel::r2d2;
-use std::thread::sleep;
+use serde_json::json;
+use std::thread::{self, sleep};
 use std::time::Duration;
 
 fn main() {
     println!("Booting runner");
 
     let config = cargo_registry::Config::default();
+    cargo_registry::telemetry::init("crates-io-background-worker");
 
     // 2x the thread pool size -- not all our jobs need a DB connection,
     // but we want to always be able to run our jobs in parallel, rather
@@ -47,8 +64,25 @@ fn main() {
         db_pool.clone(),
         config.uploader,
         reqwest::Client::new(),
+        config.session_key,
+        config.branding,
     );
 
+    let mut scheduler = Scheduler::from_env(Utc::now().naive_utc()).expect("Invalid job schedule");
+    let scheduler_pool = db_pool.clone();
+    thread::spawn(move || loop {
+        let result = scheduler_pool
+            .get()
+            .and_then(|conn| scheduler.enqueue_due_jobs(&conn, Utc::now().naive_utc()));
+        if let Err(e) = result {
+            logging::error(
+                "failed to enqueue scheduled jobs",
+                json!({ "error": e.to_string() }),
+            );
+        }
+        sleep(Duration::from_secs(1));
+    });
+
     let build_runner = || {
         swirl::Runner::builder(db_pool.clone(), environment.clone())
             .thread_count(2)
@@ -64,6 +64,7 @@ fn delete(conn: &PgConnection) {
     diesel::delete(versions::table.find(&v.id))
         .execute(conn)
         .unwrap();
+    Crate::update_default_versions(conn, krate.id).unwrap();
 
     print!("commit? [y/N]: ");
     io::stdout().flush().unwrap();
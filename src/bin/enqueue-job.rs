@@ -7,6 +7,25 @@ fn main() -> CargoResult<()> {
     let mut args = std::env::args().skip(1);
     match &*args.next().unwrap_or_default() {
         "update_downloads" => tasks::update_downloads().enqueue(&conn),
+        "rollup_downloads" => tasks::rollup_downloads().enqueue(&conn),
+        "refresh_summary" => tasks::refresh_summary().enqueue(&conn),
+        "update_trending_crates" => tasks::update_trending_crates().enqueue(&conn),
+        "update_version_adoption" => tasks::update_version_adoption().enqueue(&conn),
+        "update_popularity" => tasks::update_popularity().enqueue(&conn),
+        "update_docs_status" => tasks::update_docs_status().enqueue(&conn),
+        "send_update_digests" => tasks::send_update_digests().enqueue(&conn),
+        "backfill_checksums" => tasks::backfill_checksums().enqueue(&conn),
+        "backfill_default_versions" => tasks::backfill_default_versions().enqueue(&conn),
+        "apply_owner_removals" => tasks::apply_owner_removals().enqueue(&conn),
+        "sync_team_memberships" => tasks::sync_team_memberships().enqueue(&conn),
+        "notify_unused_tokens" => tasks::notify_unused_tokens().enqueue(&conn),
+        "transfer_inactive_crates" => tasks::transfer_inactive_crates().enqueue(&conn),
+        "delete_stale_publish_sessions" => tasks::delete_stale_publish_sessions().enqueue(&conn),
+        "ingest_cdn_logs" => tasks::ingest_cdn_logs().enqueue(&conn),
+        "detect_download_anomalies" => {
+            let exclude = args.next().as_ref().map(String::as_str) == Some("exclude");
+            tasks::detect_download_anomalies(exclude).enqueue(&conn)
+        }
         "dump_db" => {
             let database_url = args.next().unwrap_or_else(|| env("DATABASE_URL"));
             let target_name = args
@@ -0,0 +1,28 @@
+// Register an external service that may submit build verification verdicts,
+// and print the secret it must use to sign its requests. The secret is only
+// shown once.
+//
+// Usage:
+//      cargo run --bin register-build-verifier verifier-name
+
+#![deny(warnings, clippy::all, rust_2018_idioms)]
+
+use cargo_registry::{db, models::BuildVerifier};
+use std::env;
+
+fn main() {
+    let conn = db::connect_now().unwrap();
+
+    let name = match env::args().nth(1) {
+        None => {
+            println!("needs a verifier-name argument");
+            return;
+        }
+        Some(s) => s,
+    };
+
+    let (verifier, secret) = BuildVerifier::register(&conn, &name).unwrap();
+
+    println!("registered build verifier `{}`", verifier.name);
+    println!("secret: {}", secret);
+}
@@ -112,7 +112,7 @@ fn main() {
         let mut tasks = Vec::with_capacity(page_size as usize);
         for (version, krate_name) in versions {
             let config = config.clone();
-            Version::record_readme_rendering(version.id, &conn).unwrap_or_else(|_| {
+            Version::record_readme_rendering(version.id, None, &conn).unwrap_or_else(|_| {
                 panic!(
                     "[{}-{}] Couldn't record rendering time",
                     krate_name, version.num
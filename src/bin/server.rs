@@ -8,8 +8,8 @@ use std::{
     time::Duration,
 };
 
+use cargo_registry::async_server::Service as HyperService;
 use civet::Server as CivetServer;
-use conduit_hyper::Service as HyperService;
 use futures::Future;
 use jemalloc_ctl;
 use reqwest::Client;
@@ -24,19 +24,22 @@ use Server::*;
 fn main() {
     let _ = jemalloc_ctl::set_background_thread(true);
 
-    // Initialize logging
+    // Initialize logging and tracing
     env_logger::init();
+    cargo_registry::telemetry::init("crates-io");
 
     let config = cargo_registry::Config::default();
     let client = Client::new();
 
-    let app = App::new(&config, Some(client));
-    let app = cargo_registry::build_handler(Arc::new(app));
+    let app = Arc::new(App::new(&config, Some(client)));
+    let handler = cargo_registry::build_handler(Arc::clone(&app));
 
     // On every server restart, ensure the categories available in the database match
-    // the information in *src/categories.toml*.
-    let categories_toml = include_str!("../boot/categories.toml");
-    boot::categories::sync(categories_toml).unwrap();
+    // the information in *src/categories.toml*, unless this registry manages its own.
+    if !config.branding.custom_categories {
+        let categories_toml = include_str!("../boot/categories.toml");
+        boot::categories::sync(categories_toml).unwrap();
+    }
 
     let heroku = dotenv::var("HEROKU").is_ok();
     let fastboot = dotenv::var("USE_FASTBOOT").is_ok();
@@ -62,12 +65,11 @@ fn main() {
     let server = if dotenv::var("USE_HYPER").is_ok() {
         println!("Booting with a hyper based server");
         let addr = ([127, 0, 0, 1], port).into();
-        let service = HyperService::new(app, threads as usize);
-        let server = hyper::Server::bind(&addr).serve(service);
+        let service = HyperService::new(app, handler, threads as usize);
 
         let (tx, rx) = futures::sync::oneshot::channel::<()>();
-        let server = server
-            .with_graceful_shutdown(rx)
+        let server = service
+            .serve(&addr, rx)
             .map_err(|e| log::error!("Server error: {}", e));
 
         ctrlc_handler(move || tx.send(()).unwrap_or(()));
@@ -93,7 +95,7 @@ fn main() {
         println!("Booting with a civet based server");
         let mut cfg = civet::Config::new();
         cfg.port(port).threads(threads).keep_alive(true);
-        Civet(CivetServer::start(cfg, app).unwrap())
+        Civet(CivetServer::start(cfg, handler).unwrap())
     };
 
     println!("listening on port {}", port);
@@ -0,0 +1,82 @@
+//! The identity and policies of a registry deployment
+//!
+//! Private deployments of this codebase can set these to present their own
+//! name, support contact and policies in API responses and error messages,
+//! instead of the ones of crates.io.
+
+/// Configuration describing who runs this registry, exposed to the frontend
+/// through the `/registry` endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct Branding {
+    /// The name of the registry shown to users.
+    pub display_name: String,
+    /// The base URL of the registry frontend, without a trailing slash.
+    pub url: String,
+    /// The email address users can contact for help.
+    pub support_email: String,
+    /// The URL of the usage policies of the registry.
+    pub policies_url: String,
+    /// The URL of the policies for crawlers and other bots.
+    pub crawler_policy_url: String,
+    /// Where users can open issues about the registry, if anywhere.
+    pub issue_tracker_url: Option<String>,
+    /// Whether the operator manages the available categories themselves,
+    /// instead of having the crates.io categories synced on boot.
+    pub custom_categories: bool,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            display_name: String::from("crates.io"),
+            url: String::from("https://crates.io"),
+            support_email: String::from("help@crates.io"),
+            policies_url: String::from("https://crates.io/policies"),
+            crawler_policy_url: String::from("https://crates.io/policies#crawlers"),
+            issue_tracker_url: Some(String::from("https://github.com/rust-lang/crates.io")),
+            custom_categories: false,
+        }
+    }
+}
+
+impl Branding {
+    /// Reads the branding from the environment, falling back to the values of
+    /// crates.io for anything that isn't set.
+    ///
+    /// Pulls values from the following environment variables:
+    ///
+    /// - `REGISTRY_NAME`: The name of the registry shown to users.
+    /// - `REGISTRY_URL`: The base URL of the registry frontend.
+    /// - `REGISTRY_SUPPORT_EMAIL`: The email address users can contact for help.
+    /// - `REGISTRY_POLICIES_URL`: The URL of the usage policies. Defaults to
+    ///   `/policies` on `REGISTRY_URL`.
+    /// - `REGISTRY_CRAWLER_POLICY_URL`: The URL of the crawler policy. Defaults
+    ///   to the `#crawlers` section of `REGISTRY_POLICIES_URL`.
+    /// - `REGISTRY_ISSUE_TRACKER_URL`: Where users can open issues. Defaults to
+    ///   the crates.io repository, set it to an empty value to not point users
+    ///   to any issue tracker.
+    /// - `REGISTRY_CUSTOM_CATEGORIES`: If set, the crates.io categories are not
+    ///   synced to the database on boot.
+    pub fn from_environment() -> Self {
+        let default = Self::default();
+        let url = dotenv::var("REGISTRY_URL")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or(default.url);
+        let policies_url =
+            dotenv::var("REGISTRY_POLICIES_URL").unwrap_or_else(|_| format!("{}/policies", url));
+        let crawler_policy_url = dotenv::var("REGISTRY_CRAWLER_POLICY_URL")
+            .unwrap_or_else(|_| format!("{}#crawlers", policies_url));
+
+        Self {
+            display_name: dotenv::var("REGISTRY_NAME").unwrap_or(default.display_name),
+            url,
+            support_email: dotenv::var("REGISTRY_SUPPORT_EMAIL").unwrap_or(default.support_email),
+            policies_url,
+            crawler_policy_url,
+            issue_tracker_url: dotenv::var("REGISTRY_ISSUE_TRACKER_URL")
+                .map(|url| Some(url).filter(|url| !url.is_empty()))
+                .unwrap_or(default.issue_tracker_url),
+            custom_categories: dotenv::var("REGISTRY_CUSTOM_CATEGORIES").is_ok(),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Purging files from the CDN in front of the storage backend
+//!
+//! Crate files and readmes are served with a long cache lifetime, so a CDN
+//! keeps serving them after they changed upstream. When a version is yanked
+//! or a crate is deleted, the `invalidate_cdn` job purges the affected paths
+//! with one of the backends in this module, selected by
+//! `Config::uploader`.
+//!
+//! The `logs` module counts the downloads in the access logs of the CDN, see
+//! the `ingest_cdn_logs` job.
+
+use std::fmt;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::util::errors::std_error_no_send;
+use crate::util::CargoResult;
+
+mod cloudfront;
+mod fastly;
+pub mod logs;
+
+pub use self::cloudfront::CloudFront;
+pub use self::fastly::Fastly;
+
+pub trait CdnBackend: fmt::Debug + Send + Sync {
+    /// Purges the files at `paths`, which are relative to the root of the
+    /// storage backend, from all edge locations of the CDN.
+    fn invalidate(&self, client: &reqwest::Client, paths: &[String]) -> CargoResult<()>;
+}
+
+/// Purges the files at `paths` from the CDN, if invalidation is configured.
+#[swirl::background_job]
+pub fn invalidate_cdn(env: &Environment, paths: Vec<String>) -> Result<(), PerformError> {
+    env.uploader
+        .invalidate_cdn(env.http_client(), &paths)
+        .map_err(std_error_no_send)
+}
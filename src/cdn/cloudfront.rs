@@ -0,0 +1,189 @@
+use chrono::Utc;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha;
+use openssl::sign::Signer;
+use reqwest::header;
+
+use super::CdnBackend;
+use crate::util::{internal, CargoResult};
+
+const HOST: &str = "cloudfront.amazonaws.com";
+const API_VERSION: &str = "2019-03-26";
+/// CloudFront is a global service, requests to its API are signed for this
+/// region
+const REGION: &str = "us-east-1";
+const SERVICE: &str = "cloudfront";
+const CONTENT_TYPE: &str = "text/xml";
+const SIGNED_HEADERS: &str = "content-type;host;x-amz-date";
+
+/// Creates invalidations in a CloudFront distribution.
+///
+/// Requests are signed with AWS Signature Version 4, using the credentials of
+/// an IAM user allowed to call `cloudfront:CreateInvalidation`.
+#[derive(Clone, Debug)]
+pub struct CloudFront {
+    distribution_id: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl CloudFront {
+    pub fn new(distribution_id: String, access_key: String, secret_key: String) -> Self {
+        CloudFront {
+            distribution_id,
+            access_key,
+            secret_key,
+        }
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "/{}/distribution/{}/invalidation",
+            API_VERSION, self.distribution_id
+        )
+    }
+
+    /// Returns the `Authorization` header of a request creating an
+    /// invalidation with `body`, sent at `amz_date`.
+    fn authorization(&self, amz_date: &str, body: &[u8]) -> String {
+        let date = &amz_date[..8];
+        let canonical_request = format!(
+            "POST\n{}\n\ncontent-type:{}\nhost:{}\nx-amz-date:{}\n\n{}\n{}",
+            self.path(),
+            CONTENT_TYPE,
+            HOST,
+            amz_date,
+            SIGNED_HEADERS,
+            hex::encode(sha::sha256(body)),
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, REGION, SERVICE);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(sha::sha256(canonical_request.as_bytes())),
+        );
+        let key = signing_key(&self.secret_key, date, REGION, SERVICE);
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            SIGNED_HEADERS,
+            hex::encode(hmac(&key, string_to_sign.as_bytes())),
+        )
+    }
+}
+
+impl CdnBackend for CloudFront {
+    fn invalidate(&self, client: &reqwest::Client, paths: &[String]) -> CargoResult<()> {
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let body = invalidation_batch(paths, &amz_date);
+        client
+            .post(&format!("https://{}{}", HOST, self.path()))
+            .header(header::CONTENT_TYPE, CONTENT_TYPE)
+            .header("x-amz-date", amz_date.as_str())
+            .header(
+                header::AUTHORIZATION,
+                self.authorization(&amz_date, body.as_bytes()),
+            )
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| internal(&format_args!("failed to invalidate CloudFront: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Returns the XML body of a request creating an invalidation.
+///
+/// CloudFront treats requests with the same caller reference as one
+/// invalidation, so it is made unique with the time of the request.
+fn invalidation_batch(paths: &[String], amz_date: &str) -> String {
+    let items = paths
+        .iter()
+        .map(|path| format!("<Path>/{}</Path>", path))
+        .collect::<String>();
+    let caller_reference = format!(
+        "{}-{}",
+        amz_date,
+        hex::encode(sha::sha256(paths.join("\n").as_bytes()))
+    );
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <InvalidationBatch xmlns=\"http://cloudfront.amazonaws.com/doc/{}/\">\
+         <Paths><Quantity>{}</Quantity><Items>{}</Items></Paths>\
+         <CallerReference>{}</CallerReference>\
+         </InvalidationBatch>",
+        API_VERSION,
+        paths.len(),
+        items,
+        caller_reference
+    )
+}
+
+/// Derives the key that requests made on `date` are signed with.
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signing_keys_are_derived_like_aws_documents() {
+        // The example from the AWS General Reference
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn invalidation_batches_list_absolute_paths() {
+        let paths = vec![
+            String::from("crates/foo/foo-1.0.0.crate"),
+            String::from("readmes/foo/foo-1.0.0.html"),
+        ];
+        let body = invalidation_batch(&paths, "20191128T000000Z");
+        assert!(body.contains(
+            "<Paths><Quantity>2</Quantity><Items>\
+             <Path>/crates/foo/foo-1.0.0.crate</Path>\
+             <Path>/readmes/foo/foo-1.0.0.html</Path>\
+             </Items></Paths>"
+        ));
+        assert!(body.contains("<CallerReference>20191128T000000Z-"));
+    }
+
+    #[test]
+    fn requests_are_signed_for_the_distribution() {
+        let cloudfront =
+            CloudFront::new("EDFDVBD6EXAMPLE".into(), "access".into(), "secret".into());
+        let authorization = cloudfront.authorization("20191128T000000Z", b"body");
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=access/20191128/us-east-1/cloudfront/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, Signature="
+        ));
+        assert_ne!(
+            authorization,
+            cloudfront.authorization("20191128T000001Z", b"body")
+        );
+    }
+}
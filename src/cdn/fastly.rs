@@ -0,0 +1,50 @@
+use super::CdnBackend;
+use crate::util::{internal, CargoResult};
+
+const API_URL: &str = "https://api.fastly.com";
+
+/// Purges files from a Fastly service, one URL at a time.
+#[derive(Clone, Debug)]
+pub struct Fastly {
+    api_token: String,
+    /// The host the files are served from
+    host: String,
+}
+
+impl Fastly {
+    pub fn new(api_token: String, host: String) -> Self {
+        Fastly { api_token, host }
+    }
+
+    fn purge_url(&self, path: &str) -> String {
+        format!("{}/purge/{}/{}", API_URL, self.host, path)
+    }
+}
+
+impl CdnBackend for Fastly {
+    fn invalidate(&self, client: &reqwest::Client, paths: &[String]) -> CargoResult<()> {
+        for path in paths {
+            client
+                .post(&self.purge_url(path))
+                .header("Fastly-Key", self.api_token.as_str())
+                .send()
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| internal(&format_args!("failed to purge from Fastly: {}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_are_purged_on_the_cdn_host() {
+        let fastly = Fastly::new("token".into(), "static.crates.io".into());
+        assert_eq!(
+            fastly.purge_url("crates/foo/foo-1.0.0.crate"),
+            "https://api.fastly.com/purge/static.crates.io/crates/foo/foo-1.0.0.crate"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Counting crate downloads in the access logs of the CDN
+//!
+//! CloudFront writes its standard logs as gzipped, tab-separated files with a
+//! `#Fields:` header naming the columns. Fastly streams whatever format the
+//! logging endpoint is configured with, which has to be one JSON object per
+//! line with the `date` (or a timestamp starting with it), `method`, `url`
+//! and `status` of each request. Both can be gzipped or not.
+
+use chrono::NaiveDate;
+use flate2::read::MultiGzDecoder;
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::util::{human, CargoResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    CloudFront,
+    Fastly,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cloudfront" => Ok(LogFormat::CloudFront),
+            "fastly" => Ok(LogFormat::Fastly),
+            other => Err(format!(
+                "log format must be either `cloudfront` or `fastly`, got {}",
+                other
+            )),
+        }
+    }
+}
+
+/// A version of a crate downloaded on a day
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Download {
+    pub crate_name: String,
+    pub version: String,
+    pub date: NaiveDate,
+}
+
+/// The number of successful downloads of each crate file in the log file
+/// `contents`. Other requests are ignored.
+pub fn count_downloads(format: LogFormat, contents: &[u8]) -> CargoResult<HashMap<Download, i32>> {
+    let contents = decompress(contents)?;
+    let requests = match format {
+        LogFormat::CloudFront => cloudfront_requests(&contents)?,
+        LogFormat::Fastly => fastly_requests(&contents)?,
+    };
+
+    let mut counts = HashMap::new();
+    for request in requests {
+        if request.method != "GET" || request.status != 200 {
+            continue;
+        }
+        if let Some((crate_name, version)) = crate_file(&request.path) {
+            let download = Download {
+                crate_name,
+                version,
+                date: request.date,
+            };
+            *counts.entry(download).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+struct LogRequest {
+    date: NaiveDate,
+    method: String,
+    path: String,
+    status: u16,
+}
+
+fn decompress(contents: &[u8]) -> CargoResult<String> {
+    if contents.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        MultiGzDecoder::new(contents).read_to_string(&mut text)?;
+        Ok(text)
+    } else {
+        Ok(String::from_utf8(contents.to_vec())?)
+    }
+}
+
+fn cloudfront_requests(contents: &str) -> CargoResult<Vec<LogRequest>> {
+    let mut fields = Vec::new();
+    let mut requests = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with("#Fields:") {
+            fields = line["#Fields:".len()..].split_whitespace().collect();
+            continue;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let values = line.split('\t').collect::<Vec<_>>();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .position(|field| *field == name)
+                .and_then(|idx| values.get(idx).cloned())
+                .ok_or_else(|| human(&format_args!("log line without `{}`: {}", name, line)))
+        };
+        requests.push(LogRequest {
+            date: parse_date(field("date")?)?,
+            method: field("cs-method")?.to_string(),
+            path: field("cs-uri-stem")?.to_string(),
+            status: field("sc-status")?.parse().unwrap_or(0),
+        });
+    }
+    Ok(requests)
+}
+
+fn fastly_requests(contents: &str) -> CargoResult<Vec<LogRequest>> {
+    #[derive(Deserialize)]
+    struct Line {
+        date: String,
+        method: String,
+        url: String,
+        status: u16,
+    }
+
+    let mut requests = Vec::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let line = serde_json::from_str::<Line>(line)
+            .map_err(|e| human(&format_args!("invalid log line: {}", e)))?;
+        let path = line.url.split('?').next().unwrap_or_default();
+        requests.push(LogRequest {
+            date: parse_date(&line.date)?,
+            method: line.method,
+            path: path.to_string(),
+            status: line.status,
+        });
+    }
+    Ok(requests)
+}
+
+fn parse_date(date: &str) -> CargoResult<NaiveDate> {
+    date.get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%F").ok())
+        .ok_or_else(|| human(&format_args!("invalid date in log line: {}", date)))
+}
+
+/// The crate name and version of a crate file, if `path` is the path of one
+/// as stored by `Uploader::crate_path`.
+fn crate_file(path: &str) -> Option<(String, String)> {
+    let path = path.trim_start_matches('/');
+    let mut parts = path.splitn(3, '/');
+    if parts.next() != Some("crates") {
+        return None;
+    }
+    let name = parts.next()?;
+    let file = parts.next()?;
+    if !file.starts_with(name) || !file.ends_with(".crate") {
+        return None;
+    }
+    let version = file.get(name.len()..(file.len() - ".crate".len()))?;
+    if !version.starts_with('-') || version.len() == 1 {
+        return None;
+    }
+    // Build metadata is percent-encoded in the URLs cargo requests
+    let version = version[1..].replace("%2B", "+").replace("%2b", "+");
+    Some((name.to_string(), version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn download(crate_name: &str, version: &str, date: &str) -> Download {
+        Download {
+            crate_name: crate_name.into(),
+            version: version.into(),
+            date: NaiveDate::parse_from_str(date, "%F").unwrap(),
+        }
+    }
+
+    #[test]
+    fn crate_files_are_recognized() {
+        assert_eq!(
+            crate_file("/crates/foo/foo-1.0.0.crate"),
+            Some(("foo".into(), "1.0.0".into()))
+        );
+        assert_eq!(
+            crate_file("crates/foo-bar/foo-bar-0.1.0-beta.1%2Bbuild.crate"),
+            Some(("foo-bar".into(), "0.1.0-beta.1+build".into()))
+        );
+        assert_eq!(crate_file("/readmes/foo/foo-1.0.0.html"), None);
+        assert_eq!(crate_file("/crates/foo/bar-1.0.0.crate"), None);
+        assert_eq!(crate_file("/crates/foo/foo-.crate"), None);
+        assert_eq!(crate_file("/crates/foo/foo-1.0.0.tar.gz"), None);
+    }
+
+    #[test]
+    fn cloudfront_logs_are_counted() {
+        let log = "#Version: 1.0\n\
+                   #Fields: date time x-edge-location sc-bytes c-ip cs-method cs(Host) cs-uri-stem sc-status\n\
+                   2019-11-01\t10:00:00\tFRA2\t100\t192.0.2.1\tGET\tcdn\t/crates/foo/foo-1.0.0.crate\t200\n\
+                   2019-11-01\t10:00:01\tFRA2\t100\t192.0.2.2\tGET\tcdn\t/crates/foo/foo-1.0.0.crate\t200\n\
+                   2019-11-01\t10:00:02\tFRA2\t100\t192.0.2.2\tHEAD\tcdn\t/crates/foo/foo-1.0.0.crate\t200\n\
+                   2019-11-01\t10:00:03\tFRA2\t100\t192.0.2.2\tGET\tcdn\t/crates/foo/foo-2.0.0.crate\t403\n\
+                   2019-11-02\t00:00:00\tFRA2\t100\t192.0.2.3\tGET\tcdn\t/crates/foo/foo-1.0.0.crate\t200\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(log.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let counts = count_downloads(LogFormat::CloudFront, &gzipped).unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&download("foo", "1.0.0", "2019-11-01")], 2);
+        assert_eq!(counts[&download("foo", "1.0.0", "2019-11-02")], 1);
+    }
+
+    #[test]
+    fn fastly_logs_are_counted() {
+        let log = r#"{"date":"2019-11-01T10:00:00Z","method":"GET","url":"/crates/foo/foo-1.0.0.crate?x=1","status":200}
+{"date":"2019-11-01T10:00:01Z","method":"GET","url":"/crates/bar/bar-0.1.0.crate","status":200}
+{"date":"2019-11-01T10:00:02Z","method":"GET","url":"/crates/bar/bar-0.1.0.crate","status":404}
+"#;
+        let counts = count_downloads(LogFormat::Fastly, log.as_bytes()).unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&download("foo", "1.0.0", "2019-11-01")], 1);
+        assert_eq!(counts[&download("bar", "0.1.0", "2019-11-01")], 1);
+
+        assert!(count_downloads(LogFormat::Fastly, b"not json").is_err());
+    }
+}
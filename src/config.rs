@@ -1,22 +1,212 @@
-use crate::publish_rate_limit::PublishRateLimit;
-use crate::{env, uploaders::Uploader, Env, Replica};
+use crate::branding::Branding;
+use crate::cdn::{CdnBackend, CloudFront, Fastly};
+use crate::identity::OAuthClientConfig;
+use crate::logging;
+use crate::rate_limit::RateLimiter;
+use crate::response_cache::ResponseCacheConfig;
+use crate::search_strategy::SearchExperiment;
+use crate::storage::{AzureStorage, GcsStorage, S3Storage};
+use crate::uploaders::{DownloadSigning, Uploader};
+use crate::util::cidr::Cidr;
+use crate::{env, Env, Replica};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub uploader: Uploader,
+    pub download_signing: Option<DownloadSigning>,
+    pub signed_download_ttl: Duration,
+    /// How long the names of deleted crates can't be published again
+    pub deleted_name_cooldown: Duration,
+    /// How long after being requested the removal of an owner takes effect,
+    /// for crates with too few owners to confirm removals
+    pub owner_removal_delay: Duration,
+    /// How long the GitHub team memberships of users are cached, zero to
+    /// check them with GitHub every time
+    pub team_membership_ttl: Duration,
     pub session_key: String,
     pub git_repo_checkout: PathBuf,
     pub gh_client_id: String,
     pub gh_client_secret: String,
+    /// The GitLab application users can log in with, if any
+    pub gitlab: Option<OAuthClientConfig>,
+    /// The Bitbucket application users can log in with, if any
+    pub bitbucket: Option<OAuthClientConfig>,
     pub db_url: String,
+    pub replica_db_url: Option<String>,
+    pub db_request_budget: Option<Duration>,
     pub env: Env,
     pub max_upload_size: u64,
     pub max_unpack_size: u64,
     pub mirror: Replica,
     pub api_protocol: String,
-    pub publish_rate_limit: PublishRateLimit,
+    pub rate_limiter: RateLimiter,
     pub blocked_traffic: Vec<(String, Vec<String>)>,
+    pub branding: Branding,
+    pub search_experiment: SearchExperiment,
+    pub dependency_validation: DependencyValidation,
+    pub tarball_scan: TarballScan,
+    pub typosquat_check: TyposquatCheck,
+    pub response_cache: ResponseCacheConfig,
+    pub metrics_authorization_token: Option<String>,
+    pub cors: CorsConfig,
+    pub download_counting: DownloadCounting,
+    /// The sparse index the download route looks up versions in that aren't
+    /// in the database yet
+    pub index_fallback_url: Option<String>,
+    /// A clone of the index the index file route serves crate files from,
+    /// see `controllers::krate::access::index_file`
+    pub index_repository: Option<PathBuf>,
+    /// Whether new crates are hidden until the registry administrators
+    /// approved them, see `models::PendingCrate`
+    pub new_crate_approval: bool,
+    /// Whether crates can be published with namespaced names like
+    /// `org::name`, see `models::Namespace`
+    pub crate_namespaces: bool,
+    /// The IP addresses and CIDR ranges of the proxies whose `X-Real-Ip`
+    /// header is trusted, see `middleware::current_user::client_ip`
+    pub trusted_proxies: Vec<String>,
+}
+
+/// What to do when publishing a crate with a dependency requirement that no
+/// non-yanked version of the dependency matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DependencyValidation {
+    /// Refuse the upload
+    Reject,
+    /// Accept the upload and return a warning that cargo displays
+    Warn,
+}
+
+impl Default for DependencyValidation {
+    fn default() -> Self {
+        DependencyValidation::Reject
+    }
+}
+
+/// Whether published crate files are scanned for leaked secrets and suspicious
+/// content, see the `scan` module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TarballScan {
+    Off,
+    /// Record findings for review
+    Flag,
+    /// Record findings and yank versions that have any
+    Quarantine,
+}
+
+impl Default for TarballScan {
+    fn default() -> Self {
+        TarballScan::Off
+    }
+}
+
+/// What to do when the name of a new crate is similar to the name of a
+/// popular crate, see the `typosquat` module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TyposquatCheck {
+    Off,
+    /// Accept the upload and return a warning that cargo displays
+    Warn,
+    /// Accept the upload with a warning, and add the crate to the moderation
+    /// queue
+    Review,
+    /// Refuse the upload unless an admin approved the name
+    Approval,
+}
+
+impl Default for TyposquatCheck {
+    fn default() -> Self {
+        TyposquatCheck::Off
+    }
+}
+
+/// How the downloads of crate files are counted in `version_downloads`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DownloadCounting {
+    /// The download route increments the count of the version
+    Database,
+    /// The `ingest_cdn_logs` job adds the downloads in the access logs of the
+    /// CDN, so the download route doesn't write to the database
+    CdnLogs,
+}
+
+impl Default for DownloadCounting {
+    fn default() -> Self {
+        DownloadCounting::Database
+    }
+}
+
+/// Which websites may call the API from browsers, see the `cors` middleware.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// The policy of routes without a policy of their own
+    pub default: CorsPolicy,
+    /// The policies of the routes starting with the given paths. The policy
+    /// with the longest matching path applies.
+    pub routes: Vec<(String, CorsPolicy)>,
+}
+
+impl CorsConfig {
+    /// The policy that applies to requests for `path`.
+    pub fn policy_for(&self, path: &str) -> &CorsPolicy {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(&self.default, |(_, policy)| policy)
+    }
+
+    /// Whether any website may call any route.
+    pub fn is_enabled(&self) -> bool {
+        !self.default.allowed_origins.is_empty()
+            || self
+                .routes
+                .iter()
+                .any(|(_, policy)| !policy.allowed_origins.is_empty())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsPolicy {
+    /// The origins allowed to make requests, like `https://example.com`, or
+    /// `*` for any origin. Cross-origin requests are refused if empty.
+    pub allowed_origins: Vec<String>,
+    /// The methods allowed in cross-origin requests
+    pub allowed_methods: Vec<String>,
+    /// Whether browsers send cookies with cross-origin requests. Can't be
+    /// combined with `*` origins.
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        CorsPolicy {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".into(), "HEAD".into()],
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsPolicy {
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method))
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
 }
 
 impl Default for Config {
@@ -26,23 +216,118 @@ impl Default for Config {
     ///
     /// - `Config::max_upload_size`: 10MiB
     /// - `Config::api_protocol`: `https`
+    /// - `Config::signed_download_ttl`: 5 minutes
+    /// - `Config::deleted_name_cooldown`: 24 hours
+    /// - `Config::owner_removal_delay`: 72 hours
+    /// - `Config::team_membership_ttl`: 1 hour
     ///
     /// Pulls values from the following environment variables:
     ///
     /// - `GIT_REPO_CHECKOUT`: The directory where the registry index was cloned.
     /// - `MIRROR`: Is this instance of cargo_registry a mirror of crates.io.
     /// - `HEROKU`: Is this instance of cargo_registry currently running on Heroku.
-    /// - `S3_BUCKET`: The S3 bucket used to store crate files. If not present during development,
-    /// cargo_registry will fall back to a local uploader.
+    /// - `STORAGE_BACKEND`: Where crate files and readmes are stored, either `s3`, `gcs`, `azure`
+    ///   or `local`. Defaults to `s3` in production, and during development to `s3` if
+    ///   `S3_BUCKET` is set and `local` otherwise. Local files are stored in `local_uploads`.
+    /// - `STORAGE_CDN`: The host files are served from instead of the storage backend. `S3_CDN`
+    ///   is used if unset.
+    /// - `CDN_INVALIDATION`: Either `cloudfront` or `fastly`. Which CDN files are purged from
+    ///   when versions are yanked or crates are deleted. Files aren't purged if unset.
+    /// - `CLOUDFRONT_DISTRIBUTION_ID`, `CLOUDFRONT_ACCESS_KEY`, `CLOUDFRONT_SECRET_KEY`: The
+    ///   CloudFront distribution, and the credentials to create invalidations with. The S3
+    ///   credentials are used if the keys are unset.
+    /// - `FASTLY_API_TOKEN`: The Fastly API token to purge files with. The CDN host has to be
+    ///   set as well.
+    /// - `S3_BUCKET`: The S3 bucket used to store crate files.
     /// - `S3_REGION`: The region in which the bucket was created. Optional if US standard.
     /// - `S3_ACCESS_KEY`: The access key to interact with S3. Optional if running a mirror.
     /// - `S3_SECRET_KEY`: The secret key to interact with S3. Optional if running a mirror.
-    /// - `SESSION_KEY`: The key used to sign and encrypt session cookies.
+    /// - `GCS_BUCKET`, `GCS_ACCESS_KEY`, `GCS_SECRET_KEY`: The Google Cloud Storage bucket, and
+    ///   the HMAC key of a service account that can access it.
+    /// - `AZURE_STORAGE_ACCOUNT`, `AZURE_STORAGE_CONTAINER`, `AZURE_STORAGE_SAS_TOKEN`: The
+    ///   Azure Blob Storage container, and a shared access signature that can read, write and
+    ///   delete its blobs.
+    /// - `SESSION_KEY`: The key used to sign and encrypt session cookies, and to sign the
+    ///   unsubscribe links in notification emails.
     /// - `GH_CLIENT_ID`: The client ID of the associated GitHub application.
     /// - `GH_CLIENT_SECRET`: The client secret of the associated GitHub application.
+    /// - `GITLAB_CLIENT_ID`, `GITLAB_CLIENT_SECRET`, `GITLAB_URL`, `GITLAB_REDIRECT_URL`: The
+    ///   GitLab application users can also log in with. `GITLAB_URL` defaults to
+    ///   `https://gitlab.com`. Logging in with GitLab is disabled if the client ID is unset.
+    /// - `BITBUCKET_CLIENT_ID`, `BITBUCKET_CLIENT_SECRET`, `BITBUCKET_REDIRECT_URL`: The
+    ///   Bitbucket application users can also log in with. Logging in with Bitbucket is disabled
+    ///   if the client ID is unset.
     /// - `DATABASE_URL`: The URL of the postgres database to use.
+    /// - `READ_ONLY_REPLICA_URL`: The URL of a read-only replica of the database. Read-heavy
+    ///   endpoints use it instead of the primary database if set.
+    /// - `DB_REQUEST_BUDGET`: The number of seconds after which requests get no more database
+    ///   connections, and fail with `503 Service Unavailable` instead. Defaults to 25 seconds in
+    ///   production, so that requests fail before Heroku's router times them out, and to no limit
+    ///   otherwise.
     /// - `BLOCKED_TRAFFIC`: A list of headers and environment variables to use for blocking
     ///.  traffic. See the `block_traffic` module for more documentation.
+    /// - `DOWNLOAD_SIGNING`: How download URLs of private crates are signed, either `s3` or
+    ///   `cdn`. Downloads are not signed if unset.
+    /// - `DOWNLOAD_SIGNING_KEY`: The key shared with the CDN to sign download URLs. Required if
+    ///   `DOWNLOAD_SIGNING` is `cdn`.
+    /// - `DOWNLOAD_SIGNING_TTL`: The number of seconds a signed download URL is valid for.
+    /// - `DELETED_NAME_COOLDOWN_HOURS`: The number of hours after which the name of a deleted
+    ///   crate can be published again, unless the administrators release it earlier.
+    /// - `OWNER_REMOVAL_DELAY_HOURS`: The number of hours after which the removal of an owner takes
+    ///   effect, unless another owner confirms or cancels it earlier.
+    /// - `TEAM_MEMBERSHIP_TTL_MINUTES`: The number of minutes GitHub team memberships are cached
+    ///   for. The `sync_team_memberships` job refreshes them in the background.
+    /// - `REGISTRY_*`: The name, support contact and policies of this registry. See
+    ///   `Branding::from_environment` for the full list.
+    /// - `SEARCH_STRATEGY`, `SEARCH_HYBRID_PERCENTAGE`: How search results are ranked. See
+    ///   `SearchExperiment::from_environment`.
+    /// - `DEPENDENCY_VALIDATION`: Either `reject` or `warn`. Whether publishing a crate with a
+    ///   dependency requirement that matches no non-yanked version fails. Defaults to `reject`.
+    /// - `TARBALL_SCAN`: Either `off`, `flag` or `quarantine`. How published crate files are
+    ///   scanned. Defaults to `off`.
+    /// - `TYPOSQUAT_CHECK`: Either `off`, `warn`, `review` or `approval`. What happens to new
+    ///   crates with names similar to popular crates. Defaults to `off`.
+    /// - `RATE_LIMIT_*`: The rate limits of publishing new crates, searches, downloads and other
+    ///   changes. See `RateLimiter::from_environment`.
+    /// - `RESPONSE_CACHE`, `RESPONSE_CACHE_SIZE`, `RESPONSE_CACHE_TTL`, `REDIS_URL`: Where and for
+    ///   how long responses of read endpoints are cached. See
+    ///   `ResponseCacheConfig::from_environment`.
+    /// - `METRICS_AUTHORIZATION_TOKEN`: The token Prometheus authenticates with to scrape
+    ///   `GET /metrics`. The route doesn't exist if unset.
+    /// - `CORS_ALLOWED_ORIGINS`: A comma-separated list of the origins of websites that may call
+    ///   the API from browsers, or `*` for any website. Cross-origin requests are refused if
+    ///   unset.
+    /// - `CORS_ALLOWED_METHODS`: The methods websites may use. Defaults to `GET,HEAD`.
+    /// - `CORS_ALLOW_CREDENTIALS`: Either `true` or `false`. Whether browsers send the user's
+    ///   cookies with cross-origin requests. Defaults to `false`.
+    /// - `CORS_ROUTES`: A comma-separated list of pairs of a path prefix, an equals sign and a
+    ///   prefix of environment variables, like `/api/v1/me=CORS_ME`. Routes starting with the
+    ///   path use the policy read from `CORS_ME_ALLOWED_ORIGINS`, `CORS_ME_ALLOWED_METHODS` and
+    ///   `CORS_ME_ALLOW_CREDENTIALS` instead, with the same defaults.
+    /// - `DOWNLOAD_COUNTING`: Either `database` or `cdn_logs`. Whether downloads are counted by
+    ///   the download route, or by the `ingest_cdn_logs` job from the access logs of the CDN.
+    ///   Defaults to `database`.
+    /// - `INDEX_FALLBACK_URL`: The URL of a sparse index, like `https://index.crates.io`. The
+    ///   download route redirects to the crate files of versions listed there that aren't in the
+    ///   database yet, for mirrors whose database lags behind the index. Such versions are
+    ///   reported as missing if unset.
+    /// - `INDEX_REPOSITORY_PATH`: The path of a clone of the index, or of the bare repository
+    ///   `GIT_REPO_URL` points to, which has to be kept up to date by the registry operator. The
+    ///   `/api/v1/crates/:crate_id/index` route serves the index files of public crates from it.
+    ///   Private crates are left out of the index, and the route serves their index files from
+    ///   the database to the users who can see them. The route reports every public crate as
+    ///   missing if unset.
+    /// - `NEW_CRATE_APPROVAL`: Either `true` or `false`. Whether new crates are only visible to
+    ///   their owners and left out of the index until the registry administrators approved them.
+    ///   Defaults to `false`.
+    /// - `CRATE_NAMESPACES`: Either `true` or `false`. Whether crates can have namespaced names
+    ///   like `org::name`, which cargo doesn't support, so this is only meant for alternative
+    ///   registries with their own tooling. Defaults to `false`.
+    /// - `TRUSTED_PROXIES`: A comma-separated list of the IP addresses and CIDR ranges of the
+    ///   proxies in front of the server, like `10.0.0.0/8`. The address of clients is read from
+    ///   the `X-Real-Ip` header of requests coming from them, and is the address the request
+    ///   came from otherwise. Use `0.0.0.0/0,::/0` if the server is only reachable through a
+    ///   proxy that sets the header, like Heroku's router. Defaults to no proxies.
     fn default() -> Config {
         let checkout = PathBuf::from(env("GIT_REPO_CHECKOUT"));
         let api_protocol = String::from("https");
@@ -57,89 +342,340 @@ impl Default for Config {
         } else {
             Env::Development
         };
-        let uploader = match (cargo_env, mirror) {
-            (Env::Production, Replica::Primary) => {
-                // `env` panics if these vars are not set, and in production for a primary instance,
-                // that's what we want since we don't want to be able to start the server if the
-                // server doesn't know where to upload crates.
-                Uploader::S3 {
-                    bucket: s3::Bucket::new(
-                        env("S3_BUCKET"),
-                        dotenv::var("S3_REGION").ok(),
-                        env("S3_ACCESS_KEY"),
-                        env("S3_SECRET_KEY"),
-                        &api_protocol,
-                    ),
-                    cdn: dotenv::var("S3_CDN").ok(),
-                }
-            }
-            (Env::Production, Replica::ReadOnlyMirror) => {
-                // Read-only mirrors don't need access key or secret key since by definition,
-                // they'll only need to read from a bucket, not upload.
-                //
-                // Read-only mirrors might have access key or secret key, so use them if those
-                // environment variables are set.
-                //
-                // Read-only mirrors definitely need bucket though, so that they know where
-                // to serve crate files from.
-                Uploader::S3 {
-                    bucket: s3::Bucket::new(
-                        env("S3_BUCKET"),
-                        dotenv::var("S3_REGION").ok(),
-                        dotenv::var("S3_ACCESS_KEY").unwrap_or_default(),
-                        dotenv::var("S3_SECRET_KEY").unwrap_or_default(),
-                        &api_protocol,
-                    ),
-                    cdn: dotenv::var("S3_CDN").ok(),
-                }
-            }
-            // In Development mode, either running as a primary instance or a read-only mirror
-            _ => {
-                if dotenv::var("S3_BUCKET").is_ok() {
-                    // If we've set the `S3_BUCKET` variable to any value, use all of the values
-                    // for the related S3 environment variables and configure the app to upload to
-                    // and read from S3 like production does. All values except for bucket are
-                    // optional, like production read-only mirrors.
-                    println!("Using S3 uploader");
-                    Uploader::S3 {
-                        bucket: s3::Bucket::new(
-                            env("S3_BUCKET"),
-                            dotenv::var("S3_REGION").ok(),
-                            dotenv::var("S3_ACCESS_KEY").unwrap_or_default(),
-                            dotenv::var("S3_SECRET_KEY").unwrap_or_default(),
-                            &api_protocol,
-                        ),
-                        cdn: dotenv::var("S3_CDN").ok(),
-                    }
-                } else {
-                    // If we don't set the `S3_BUCKET` variable, we'll use a development-only
-                    // uploader that makes it possible to run and publish to a locally-running
-                    // crates.io instance without needing to set up an account and a bucket in S3.
-                    println!(
-                        "Using local uploader, crate files will be in the local_uploads directory"
-                    );
-                    Uploader::Local
-                }
-            }
-        };
+        let uploader = uploader(cargo_env, mirror, &api_protocol);
+        let signed_download_ttl = dotenv::var("DOWNLOAD_SIGNING_TTL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(5 * 60));
+        let deleted_name_cooldown = dotenv::var("DELETED_NAME_COOLDOWN_HOURS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(|hours| Duration::from_secs(hours * 60 * 60))
+            .unwrap_or_else(|| Duration::from_secs(24 * 60 * 60));
+        let owner_removal_delay = dotenv::var("OWNER_REMOVAL_DELAY_HOURS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(|hours| Duration::from_secs(hours * 60 * 60))
+            .unwrap_or_else(|| Duration::from_secs(72 * 60 * 60));
+        let team_membership_ttl = dotenv::var("TEAM_MEMBERSHIP_TTL_MINUTES")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(|minutes| Duration::from_secs(minutes * 60))
+            .unwrap_or_else(|| Duration::from_secs(60 * 60));
         Config {
             uploader,
+            download_signing: download_signing(),
+            signed_download_ttl,
+            deleted_name_cooldown,
+            owner_removal_delay,
+            team_membership_ttl,
             session_key: env("SESSION_KEY"),
             git_repo_checkout: checkout,
             gh_client_id: env("GH_CLIENT_ID"),
             gh_client_secret: env("GH_CLIENT_SECRET"),
+            gitlab: OAuthClientConfig::from_environment("GITLAB", "https://gitlab.com"),
+            bitbucket: OAuthClientConfig::from_environment("BITBUCKET", "https://bitbucket.org"),
             db_url: env("DATABASE_URL"),
+            replica_db_url: dotenv::var("READ_ONLY_REPLICA_URL").ok(),
+            db_request_budget: db_request_budget(cargo_env),
             env: cargo_env,
             max_upload_size: 10 * 1024 * 1024, // 10 MB default file upload size limit
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
             mirror,
             api_protocol,
-            publish_rate_limit: Default::default(),
+            rate_limiter: RateLimiter::from_environment(),
             blocked_traffic: blocked_traffic(),
+            branding: Branding::from_environment(),
+            search_experiment: SearchExperiment::from_environment(),
+            dependency_validation: dependency_validation(),
+            tarball_scan: tarball_scan(),
+            typosquat_check: typosquat_check(),
+            response_cache: ResponseCacheConfig::from_environment(),
+            metrics_authorization_token: dotenv::var("METRICS_AUTHORIZATION_TOKEN").ok(),
+            cors: cors(),
+            download_counting: download_counting(),
+            index_fallback_url: dotenv::var("INDEX_FALLBACK_URL").ok(),
+            index_repository: dotenv::var("INDEX_REPOSITORY_PATH").ok().map(PathBuf::from),
+            new_crate_approval: bool_var("NEW_CRATE_APPROVAL"),
+            crate_namespaces: bool_var("CRATE_NAMESPACES"),
+            trusted_proxies: trusted_proxies(),
         }
     }
 }
 
+fn db_request_budget(env: Env) -> Option<Duration> {
+    match (dotenv::var("DB_REQUEST_BUDGET"), env) {
+        (Ok(secs), _) => Some(Duration::from_secs(
+            secs.parse().expect("couldn't parse DB_REQUEST_BUDGET"),
+        )),
+        (_, Env::Production) => Some(Duration::from_secs(25)),
+        _ => None,
+    }
+}
+
+/// Reads a flag that is off unless the variable `name` is `true`.
+fn bool_var(name: &str) -> bool {
+    match dotenv::var(name).ok().as_ref().map(String::as_str) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(other) => panic!("{} must be either `true` or `false`, got {}", name, other),
+    }
+}
+
+fn dependency_validation() -> DependencyValidation {
+    match dotenv::var("DEPENDENCY_VALIDATION")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        None | Some("reject") => DependencyValidation::Reject,
+        Some("warn") => DependencyValidation::Warn,
+        Some(other) => panic!(
+            "DEPENDENCY_VALIDATION must be either `reject` or `warn`, got {}",
+            other
+        ),
+    }
+}
+
+/// Selects the storage backend, see `Config::default`.
+fn uploader(cargo_env: Env, mirror: Replica, api_protocol: &str) -> Uploader {
+    let cdn = dotenv::var("STORAGE_CDN")
+        .or_else(|_| dotenv::var("S3_CDN"))
+        .ok();
+    let cdn_invalidation = cdn_invalidation(cdn.as_ref().map(String::as_str));
+    let uploader = match dotenv::var("STORAGE_BACKEND")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        Some("s3") => Uploader::new(
+            S3Storage::new(s3_bucket(cargo_env, mirror, api_protocol)),
+            cdn,
+        ),
+        Some("gcs") => Uploader::new(
+            GcsStorage::new(
+                env("GCS_BUCKET"),
+                dotenv::var("GCS_ACCESS_KEY").unwrap_or_default(),
+                dotenv::var("GCS_SECRET_KEY").unwrap_or_default(),
+                api_protocol,
+            ),
+            cdn,
+        ),
+        Some("azure") => Uploader::new(
+            AzureStorage::new(
+                env("AZURE_STORAGE_ACCOUNT"),
+                env("AZURE_STORAGE_CONTAINER"),
+                &dotenv::var("AZURE_STORAGE_SAS_TOKEN").unwrap_or_default(),
+            ),
+            cdn,
+        ),
+        Some("local") => Uploader::local(),
+        Some(other) => panic!(
+            "STORAGE_BACKEND must be either `s3`, `gcs`, `azure` or `local`, got {}",
+            other
+        ),
+        // Production instances store files in S3 unless configured otherwise
+        None if cargo_env == Env::Production => Uploader::new(
+            S3Storage::new(s3_bucket(cargo_env, mirror, api_protocol)),
+            cdn,
+        ),
+        // If we've set the `S3_BUCKET` variable to any value, use all of the values for the
+        // related S3 environment variables and configure the app to upload to and read from S3
+        // like production does. All values except for bucket are optional, like production
+        // read-only mirrors.
+        None if dotenv::var("S3_BUCKET").is_ok() => {
+            logging::info("using S3 uploader", json!(null));
+            Uploader::new(
+                S3Storage::new(s3_bucket(cargo_env, mirror, api_protocol)),
+                cdn,
+            )
+        }
+        // If we don't set the `S3_BUCKET` variable, we'll use a development-only uploader that
+        // makes it possible to run and publish to a locally-running crates.io instance without
+        // needing to set up an account and a bucket in S3.
+        None => {
+            logging::info(
+                "using local uploader, crate files will be in the local_uploads directory",
+                json!(null),
+            );
+            Uploader::local()
+        }
+    };
+    match cdn_invalidation {
+        Some(backend) => uploader.with_cdn_invalidation(backend),
+        None => uploader,
+    }
+}
+
+/// Selects the CDN files are purged from, see `Config::default`.
+fn cdn_invalidation(cdn: Option<&str>) -> Option<Arc<dyn CdnBackend>> {
+    match dotenv::var("CDN_INVALIDATION").ok()?.as_str() {
+        "cloudfront" => Some(Arc::new(CloudFront::new(
+            env("CLOUDFRONT_DISTRIBUTION_ID"),
+            dotenv::var("CLOUDFRONT_ACCESS_KEY").unwrap_or_else(|_| env("S3_ACCESS_KEY")),
+            dotenv::var("CLOUDFRONT_SECRET_KEY").unwrap_or_else(|_| env("S3_SECRET_KEY")),
+        ))),
+        "fastly" => Some(Arc::new(Fastly::new(
+            env("FASTLY_API_TOKEN"),
+            String::from(cdn.expect("STORAGE_CDN must be set to purge files from Fastly")),
+        ))),
+        other => panic!(
+            "CDN_INVALIDATION must be either `cloudfront` or `fastly`, got {}",
+            other
+        ),
+    }
+}
+
+/// The S3 bucket files are stored in.
+fn s3_bucket(cargo_env: Env, mirror: Replica, api_protocol: &str) -> s3::Bucket {
+    let (access_key, secret_key) = match (cargo_env, mirror) {
+        // `env` panics if these vars are not set, and in production for a primary instance,
+        // that's what we want since we don't want to be able to start the server if the
+        // server doesn't know where to upload crates.
+        (Env::Production, Replica::Primary) => (env("S3_ACCESS_KEY"), env("S3_SECRET_KEY")),
+        // Read-only mirrors don't need access key or secret key since by definition, they'll
+        // only need to read from a bucket, not upload. They might have them though, so use
+        // them if those environment variables are set. The same goes for development.
+        _ => (
+            dotenv::var("S3_ACCESS_KEY").unwrap_or_default(),
+            dotenv::var("S3_SECRET_KEY").unwrap_or_default(),
+        ),
+    };
+    // Bucket is always required, so that we know where to serve crate files from.
+    s3::Bucket::new(
+        env("S3_BUCKET"),
+        dotenv::var("S3_REGION").ok(),
+        access_key,
+        secret_key,
+        api_protocol,
+    )
+}
+
+fn download_signing() -> Option<DownloadSigning> {
+    match dotenv::var("DOWNLOAD_SIGNING").ok()?.as_str() {
+        "s3" => Some(DownloadSigning::S3),
+        "cdn" => Some(DownloadSigning::Cdn {
+            key: env("DOWNLOAD_SIGNING_KEY"),
+        }),
+        other => panic!(
+            "DOWNLOAD_SIGNING must be either `s3` or `cdn`, got {}",
+            other
+        ),
+    }
+}
+
+fn tarball_scan() -> TarballScan {
+    match dotenv::var("TARBALL_SCAN")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        None | Some("off") => TarballScan::Off,
+        Some("flag") => TarballScan::Flag,
+        Some("quarantine") => TarballScan::Quarantine,
+        Some(other) => panic!(
+            "TARBALL_SCAN must be either `off`, `flag` or `quarantine`, got {}",
+            other
+        ),
+    }
+}
+
+fn typosquat_check() -> TyposquatCheck {
+    match dotenv::var("TYPOSQUAT_CHECK")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        None | Some("off") => TyposquatCheck::Off,
+        Some("warn") => TyposquatCheck::Warn,
+        Some("review") => TyposquatCheck::Review,
+        Some("approval") => TyposquatCheck::Approval,
+        Some(other) => panic!(
+            "TYPOSQUAT_CHECK must be either `off`, `warn`, `review` or `approval`, got {}",
+            other
+        ),
+    }
+}
+
+fn download_counting() -> DownloadCounting {
+    match dotenv::var("DOWNLOAD_COUNTING")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        None | Some("database") => DownloadCounting::Database,
+        Some("cdn_logs") => DownloadCounting::CdnLogs,
+        Some(other) => panic!(
+            "DOWNLOAD_COUNTING must be either `database` or `cdn_logs`, got {}",
+            other
+        ),
+    }
+}
+
+fn cors() -> CorsConfig {
+    let route_list = dotenv::var("CORS_ROUTES").unwrap_or_default();
+    let routes = route_list
+        .split_terminator(',')
+        .map(|route| match route.find('=') {
+            Some(idx) => (route[..idx].into(), cors_policy(&route[(idx + 1)..])),
+            None => panic!(
+                "CORS_ROUTES must be in the form PATH=ENV_VAR_PREFIX, got invalid route {}",
+                route
+            ),
+        })
+        .collect();
+    CorsConfig {
+        default: cors_policy("CORS"),
+        routes,
+    }
+}
+
+fn cors_policy(prefix: &str) -> CorsPolicy {
+    let var = |name: &str| {
+        dotenv::var(format!("{}_{}", prefix, name))
+            .ok()
+            .filter(|s| !s.is_empty())
+    };
+    let list = |value: String| value.split(',').map(|s| s.trim().to_string()).collect();
+
+    let mut policy = CorsPolicy::default();
+    if let Some(origins) = var("ALLOWED_ORIGINS") {
+        policy.allowed_origins = list(origins);
+    }
+    if let Some(methods) = var("ALLOWED_METHODS") {
+        policy.allowed_methods = list(methods);
+    }
+    policy.allow_credentials = match var("ALLOW_CREDENTIALS").as_ref().map(String::as_str) {
+        Some("true") => true,
+        Some("false") | None => false,
+        Some(other) => panic!(
+            "{}_ALLOW_CREDENTIALS must be either `true` or `false`, got {}",
+            prefix, other
+        ),
+    };
+    if policy.allow_credentials && policy.allows_any_origin() {
+        panic!(
+            "{}_ALLOW_CREDENTIALS can't be used when any origin is allowed",
+            prefix
+        );
+    }
+    policy
+}
+
+fn trusted_proxies() -> Vec<String> {
+    let proxy_list = dotenv::var("TRUSTED_PROXIES").unwrap_or_default();
+    proxy_list
+        .split_terminator(',')
+        .map(|proxy| proxy.trim())
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| match proxy.parse::<Cidr>() {
+            Ok(_) => proxy.to_string(),
+            Err(_) => panic!("TRUSTED_PROXIES contains an invalid CIDR range: {}", proxy),
+        })
+        .collect()
+}
+
 fn blocked_traffic() -> Vec<(String, Vec<String>)> {
     let pattern_list = dotenv::var("BLOCKED_TRAFFIC").unwrap_or_default();
     parse_traffic_patterns(&pattern_list)
@@ -180,3 +716,28 @@ fn parse_traffic_patterns_splits_on_comma_and_looks_for_equal_sign() {
     let patterns_3 = parse_traffic_patterns(pattern_string_3).collect::<Vec<_>>();
     assert!(patterns_3.is_empty());
 }
+
+#[test]
+fn cors_policies_apply_to_the_longest_matching_route() {
+    let policy = |origin: &str| CorsPolicy {
+        allowed_origins: vec![origin.into()],
+        ..CorsPolicy::default()
+    };
+    let config = CorsConfig {
+        default: policy("https://a.example"),
+        routes: vec![
+            ("/api/v1/me".into(), policy("https://b.example")),
+            ("/api/v1/me/tokens".into(), CorsPolicy::default()),
+        ],
+    };
+
+    assert_eq!(config.policy_for("/api/v1/crates"), &config.default);
+    assert_eq!(config.policy_for("/api/v1/me"), &config.routes[0].1);
+    assert_eq!(config.policy_for("/api/v1/me/updates"), &config.routes[0].1);
+    assert_eq!(
+        config.policy_for("/api/v1/me/tokens/1"),
+        &config.routes[1].1
+    );
+    assert!(config.is_enabled());
+    assert!(!CorsConfig::default().is_enabled());
+}
@@ -6,7 +6,7 @@ mod prelude {
     pub use conduit_router::RequestParams;
 
     pub use crate::db::RequestTransaction;
-    pub use crate::util::{human, CargoResult};
+    pub use crate::util::{human, CargoResult, ErrorCode};
 
     pub use crate::middleware::app::RequestApp;
     pub use crate::middleware::current_user::RequestUser;
@@ -23,6 +23,7 @@ mod prelude {
 
         fn json<T: Serialize>(&self, t: &T) -> Response;
         fn query(&self) -> IndexMap<String, String>;
+        fn query_values(&self, key: &str) -> Vec<String>;
         fn wants_json(&self) -> bool;
         fn query_with_params(&self, params: IndexMap<String, String>) -> String;
     }
@@ -38,6 +39,13 @@ mod prelude {
                 .collect()
         }
 
+        fn query_values(&self, key: &str) -> Vec<String> {
+            url::form_urlencoded::parse(self.query_string().unwrap_or("").as_bytes())
+                .filter(|(k, _)| k == key)
+                .map(|(_, v)| v.into_owned())
+                .collect()
+        }
+
         fn redirect(&self, url: String) -> Response {
             let mut headers = HashMap::new();
             headers.insert("Location".to_string(), vec![url]);
@@ -68,12 +76,20 @@ mod prelude {
 
 pub mod helpers;
 
+pub mod admin;
 pub mod category;
+pub mod crate_list;
 pub mod crate_owner_invitation;
+pub mod github;
+pub mod graphql;
+pub mod health;
 pub mod keyword;
 pub mod krate;
+pub mod metrics;
+pub mod namespace;
 pub mod site_metadata;
 pub mod team;
 pub mod token;
 pub mod user;
+pub mod v2;
 pub mod version;
@@ -0,0 +1,1578 @@
+//! Endpoints for the registry administrators to moderate crates and users, to
+//! approve new crates, and to manage categories, keyword aliases and failed
+//! background jobs
+//!
+//! All endpoints require the `is_admin` flag on the current user, and every
+//! change they make is recorded in the `admin_actions` table.
+
+use chrono::{NaiveDateTime, Utc};
+use serde_json;
+use std::collections::HashMap;
+use swirl::Job;
+
+use crate::cdn;
+use crate::config::TarballScan;
+use crate::controllers::prelude::*;
+use crate::email;
+use crate::git;
+use crate::models::krate::{canon_crate_name, canonical_name};
+use crate::models::name_claim::{self, HOLDING_PERIOD_DAYS};
+use crate::models::{
+    AdminAction, BackgroundJob, Category, ClaimStatus, Crate, CrateRename, CrateReport, DeadLetter,
+    DeletedCrate, DownloadAnomaly, JobPolicy, Keyword, KeywordAlias, NameClaim, NewAdminAction,
+    NewCategory, PendingCrate, ReservedNamePattern, ReservedNamePatternKind, User, Version,
+    Visibility,
+};
+use crate::scan;
+use crate::schema::{
+    approved_crate_names, background_job_dead_letters, background_jobs, categories, crate_reports,
+    crates, download_anomalies, keyword_aliases, name_claims, pending_crates,
+    publish_rate_overrides, reserved_crate_name_patterns, reserved_crate_names, users,
+    version_scan_findings, versions,
+};
+use crate::telemetry;
+use crate::uploaders::Uploader;
+use crate::util::CargoError;
+use crate::views::{
+    EncodableBackgroundJob, EncodableCategory, EncodableCrateReport, EncodableDeadLetter,
+    EncodableDownloadAnomaly, EncodableJobPolicy, EncodableKeywordAlias, EncodableNameClaim,
+    EncodablePendingCrate, EncodableReservedNamePattern, EncodableScanFinding,
+};
+
+/// Returns the current user if they are an admin.
+fn admin(req: &dyn Request) -> CargoResult<&User> {
+    let user = req.user()?;
+    if !user.is_admin {
+        return Err(ErrorCode::AdminRequired.human("must be an admin to use this endpoint"));
+    }
+    Ok(user)
+}
+
+/// Handles the `GET /admin/flagged` route.
+///
+/// Lists the findings of the tarball scans that haven't been reviewed yet and
+/// the crate reports that haven't been resolved yet, oldest first.
+pub fn flagged(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let findings = version_scan_findings::table
+        .inner_join(versions::table.inner_join(crates::table))
+        .filter(version_scan_findings::reviewed.eq(false))
+        .select((
+            version_scan_findings::id,
+            crates::name,
+            versions::num,
+            version_scan_findings::path,
+            version_scan_findings::kind,
+            version_scan_findings::detail,
+            version_scan_findings::created_at,
+        ))
+        .order(version_scan_findings::created_at)
+        .load::<(i32, String, String, String, String, String, NaiveDateTime)>(&*conn)?
+        .into_iter()
+        .map(
+            |(id, krate, version, path, kind, detail, created_at)| EncodableScanFinding {
+                id,
+                krate,
+                version,
+                path,
+                kind,
+                detail,
+                created_at,
+            },
+        )
+        .collect();
+
+    let reports = crate_reports::table
+        .inner_join(crates::table)
+        .left_join(users::table)
+        .filter(crate_reports::resolved.eq(false))
+        .select((
+            crate_reports::all_columns,
+            crates::name,
+            users::gh_login.nullable(),
+        ))
+        .order(crate_reports::created_at)
+        .load::<(CrateReport, String, Option<String>)>(&*conn)?
+        .into_iter()
+        .map(|(report, krate, reporter)| EncodableCrateReport {
+            id: report.id,
+            krate,
+            reporter,
+            category: report.category,
+            description: report.description,
+            created_at: report.created_at,
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        findings: Vec<EncodableScanFinding>,
+        reports: Vec<EncodableCrateReport>,
+    }
+    Ok(req.json(&R { findings, reports }))
+}
+
+/// Handles the `PUT /admin/findings/:finding_id/review` route.
+pub fn review_finding(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let finding_id = req.params()["finding_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid finding id"))?;
+    let conn = req.db_conn()?;
+
+    let crate_name = conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        let crate_name = version_scan_findings::table
+            .find(finding_id)
+            .inner_join(versions::table.inner_join(crates::table))
+            .select(crates::name)
+            .first::<String>(&*conn)?;
+
+        diesel::update(version_scan_findings::table.find(finding_id))
+            .set(version_scan_findings::reviewed.eq(true))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ReviewFinding,
+            crate_name: Some(&crate_name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        Ok(crate_name)
+    })?;
+
+    req.app().response_cache.invalidate_crate(&crate_name);
+    ok_true()
+}
+
+/// Handles the `PUT /admin/reports/:report_id/resolve` route.
+pub fn resolve_report(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let report_id = req.params()["report_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid report id"))?;
+    let conn = req.db_conn()?;
+
+    let crate_name = conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        let crate_name = crate_reports::table
+            .find(report_id)
+            .inner_join(crates::table)
+            .select(crates::name)
+            .first::<String>(&*conn)?;
+
+        diesel::update(crate_reports::table.find(report_id))
+            .set(crate_reports::resolved.eq(true))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ResolveReport,
+            crate_name: Some(&crate_name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        Ok(crate_name)
+    })?;
+
+    req.app().response_cache.invalidate_crate(&crate_name);
+    ok_true()
+}
+
+/// Handles the `GET /admin/download_anomalies` route.
+///
+/// Lists the download anomalies found by the `detect_download_anomalies` job
+/// that haven't been reviewed yet, oldest first.
+pub fn download_anomalies(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let anomalies = download_anomalies::table
+        .inner_join(crates::table)
+        .filter(download_anomalies::reviewed.eq(false))
+        .select((download_anomalies::all_columns, crates::name))
+        .order(download_anomalies::created_at)
+        .load::<(DownloadAnomaly, String)>(&*conn)?
+        .into_iter()
+        .map(|(anomaly, krate)| EncodableDownloadAnomaly {
+            id: anomaly.id,
+            krate,
+            start_date: anomaly.start_date.to_string(),
+            end_date: anomaly.end_date.to_string(),
+            downloads: anomaly.downloads,
+            expected_downloads: anomaly.expected_downloads,
+            excluded: anomaly.excluded,
+            created_at: anomaly.created_at,
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        download_anomalies: Vec<EncodableDownloadAnomaly>,
+    }
+    Ok(req.json(&R {
+        download_anomalies: anomalies,
+    }))
+}
+
+/// Handles the `PUT /admin/download_anomalies/:anomaly_id/review` route.
+///
+/// The request body has the format
+///
+///     {"exclude": true}
+///
+/// Whether the downloads above the crate's usual rate are excluded from its
+/// download count, because they were inflated, or counted, because they
+/// were genuine.
+pub fn review_download_anomaly(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        exclude: bool,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let admin = admin(req)?;
+    let anomaly_id = req.params()["anomaly_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid anomaly id"))?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let (anomaly, crate_name) = download_anomalies::table
+            .find(anomaly_id)
+            .inner_join(crates::table)
+            .select((download_anomalies::all_columns, crates::name))
+            .first::<(DownloadAnomaly, String)>(&*conn)?;
+
+        anomaly.set_excluded(&conn, request.exclude)?;
+        diesel::update(&anomaly)
+            .set(download_anomalies::reviewed.eq(true))
+            .execute(&*conn)?;
+
+        let detail = format!(
+            "{} {} downloads from {} to {}",
+            if request.exclude {
+                "excluded"
+            } else {
+                "counted"
+            },
+            anomaly.excess(),
+            anomaly.start_date,
+            anomaly.end_date
+        );
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ReviewDownloadAnomaly,
+            crate_name: Some(&crate_name),
+            user_id: None,
+            detail: Some(&detail),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /admin/approved_names/:name` route.
+///
+/// Allows publishing a new crate with this name even though it is similar to
+/// the name of a popular crate, see the `typosquat` module.
+pub fn approve_name(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let name = canonical_name(&req.params()["name"]);
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        diesel::insert_into(approved_crate_names::table)
+            .values((
+                approved_crate_names::name.eq(&name),
+                approved_crate_names::approved_by.eq(admin.id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ApproveName,
+            crate_name: Some(&name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `GET /admin/reserved_names` route.
+///
+/// Lists the reserved crate names and the patterns reserving names, see the
+/// `reserved_name` model.
+pub fn reserved_names(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let names = reserved_crate_names::table
+        .select(reserved_crate_names::name)
+        .order(reserved_crate_names::name)
+        .load(&*conn)?;
+    let patterns = reserved_crate_name_patterns::table
+        .order(reserved_crate_name_patterns::id)
+        .load::<ReservedNamePattern>(&*conn)?
+        .into_iter()
+        .map(ReservedNamePattern::encodable)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        names: Vec<String>,
+        patterns: Vec<EncodableReservedNamePattern>,
+    }
+    Ok(req.json(&R { names, patterns }))
+}
+
+/// Handles the `PUT /admin/reserved_names/:name` route.
+///
+/// Only names that aren't in use yet can be reserved.
+pub fn reserve_name(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let name = &req.params()["name"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let in_use =
+            diesel::select(diesel::dsl::exists(Crate::by_name(name))).get_result::<bool>(&*conn)?;
+        if in_use {
+            return Err(ErrorCode::AlreadyExists.human("a crate with this name already exists"));
+        }
+
+        diesel::insert_into(reserved_crate_names::table)
+            .values(reserved_crate_names::name.eq(name))
+            .on_conflict_do_nothing()
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ReserveName,
+            crate_name: Some(name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /admin/reserved_names/:name` route.
+pub fn unreserve_name(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let name = &req.params()["name"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let deleted = diesel::delete(
+            reserved_crate_names::table
+                .filter(canon_crate_name(reserved_crate_names::name).eq(canon_crate_name(name))),
+        )
+        .execute(&*conn)?;
+        if deleted == 0 {
+            return Err(ErrorCode::NotFound.human("this name is not reserved"));
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::UnreserveName,
+            crate_name: Some(name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `POST /admin/reserved_name_patterns` route.
+///
+/// The request body has the format
+///
+///     {"kind": "prefix" | "regex", "pattern": "..."}
+///
+/// Patterns only apply to new crates. Existing crates with matching names
+/// can still be published.
+pub fn add_name_pattern(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        kind: ReservedNamePatternKind,
+        pattern: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    let pattern = ReservedNamePattern::normalize(request.kind, &request.pattern)?;
+    let admin = admin(req)?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let pattern = ReservedNamePattern::create(&conn, request.kind, &pattern)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::AddNamePattern,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&pattern.pattern),
+        }
+        .record(&conn)?;
+
+        #[derive(Serialize)]
+        struct R {
+            pattern: EncodableReservedNamePattern,
+        }
+        Ok(req.json(&R {
+            pattern: pattern.encodable(),
+        }))
+    })
+}
+
+/// Handles the `DELETE /admin/reserved_name_patterns/:pattern_id` route.
+pub fn remove_name_pattern(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let pattern_id = req.params()["pattern_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid pattern id"))?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let pattern = diesel::delete(reserved_crate_name_patterns::table.find(pattern_id))
+            .get_result::<ReservedNamePattern>(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RemoveNamePattern,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&pattern.pattern),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `GET /admin/claims` route.
+///
+/// Lists the name claims that haven't been decided on yet, oldest first.
+pub fn claims(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let claims = name_claims::table
+        .inner_join(crates::table)
+        .inner_join(users::table)
+        // Decided claims have the highest status codes
+        .filter(name_claims::status.lt(ClaimStatus::Approved))
+        .select((name_claims::all_columns, crates::name, users::gh_login))
+        .order(name_claims::created_at)
+        .load::<(NameClaim, String, String)>(&*conn)?
+        .into_iter()
+        .map(|(claim, krate, claimant)| claim.encodable(krate, claimant))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        claims: Vec<EncodableNameClaim>,
+    }
+    Ok(req.json(&R { claims }))
+}
+
+/// Handles the `PUT /admin/claims/:claim_id/approve` route.
+///
+/// Transfers the crate to the claimant. Unless the owners agreed to the
+/// claim, this is only possible after the holding period.
+pub fn approve_claim(req: &mut dyn Request) -> CargoResult<Response> {
+    decide_claim(req, true)
+}
+
+/// Handles the `PUT /admin/claims/:claim_id/reject` route.
+pub fn reject_claim(req: &mut dyn Request) -> CargoResult<Response> {
+    decide_claim(req, false)
+}
+
+fn decide_claim(req: &mut dyn Request, approve: bool) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let claim_id = req.params()["claim_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid claim id"))?;
+    let conn = req.db_conn()?;
+
+    let (claim, crate_name, claimant, owner_emails) = conn.transaction(|| {
+        let (claim, crate_name, claimant) = name_claims::table
+            .find(claim_id)
+            .inner_join(crates::table)
+            .inner_join(users::table)
+            .select((name_claims::all_columns, crates::name, users::gh_login))
+            .first::<(NameClaim, String, String)>(&*conn)?;
+
+        if !claim.is_open() {
+            return Err(ErrorCode::Conflict.human("this claim has already been decided on"));
+        }
+        if approve && !claim.can_be_approved(Utc::now().naive_utc()) {
+            return Err(ErrorCode::Conflict.human(&format_args!(
+                "the owners of the crate have {} days to respond to the claim",
+                HOLDING_PERIOD_DAYS
+            )));
+        }
+
+        // The previous owners are told about the decision as well
+        let owner_emails = name_claim::owner_emails(&conn, claim.crate_id)?;
+        if approve {
+            claim.transfer(&conn, admin.id)?;
+        }
+        let status = if approve {
+            ClaimStatus::Approved
+        } else {
+            ClaimStatus::Rejected
+        };
+        diesel::update(&claim)
+            .set(name_claims::status.eq(status))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: if approve {
+                AdminAction::ApproveClaim
+            } else {
+                AdminAction::RejectClaim
+            },
+            crate_name: Some(&crate_name),
+            user_id: Some(claim.claimant_id),
+            detail: None,
+        }
+        .record(&conn)?;
+
+        Ok((claim, crate_name, claimant, owner_emails))
+    })?;
+
+    let claimant_email = claim.claimant_email(&conn)?;
+    for address in owner_emails.iter().chain(&claimant_email) {
+        let _ =
+            email::send_name_claim_decision_email(&conn, address, &crate_name, &claimant, approve);
+    }
+
+    ok_true()
+}
+
+/// Handles the `GET /admin/pending_crates` route.
+///
+/// Lists the new crates waiting for approval, oldest first, see
+/// `Config::new_crate_approval`.
+pub fn pending_crates(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    // Pending crates can't have more than the version they were published with
+    let pending = pending_crates::table
+        .inner_join(crates::table.inner_join(versions::table.left_join(users::table)))
+        .select((
+            pending_crates::all_columns,
+            crates::name,
+            versions::num,
+            users::gh_login.nullable(),
+        ))
+        .order(pending_crates::created_at)
+        .load::<(PendingCrate, String, String, Option<String>)>(&*conn)?
+        .into_iter()
+        .map(|(pending, name, version, publisher)| pending.encodable(name, version, publisher))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        crates: Vec<EncodablePendingCrate>,
+    }
+    Ok(req.json(&R { crates: pending }))
+}
+
+/// Handles the `PUT /admin/pending_crates/:crate_id/approve` route.
+///
+/// Makes the crate public and adds it to the index.
+pub fn approve_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    decide_pending_crate(req, true)
+}
+
+/// Handles the `PUT /admin/pending_crates/:crate_id/reject` route.
+///
+/// Deletes the crate, so that its name can be published again. The uploaded
+/// crate file is left in place.
+pub fn reject_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    decide_pending_crate(req, false)
+}
+
+fn decide_pending_crate(req: &mut dyn Request, approve: bool) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let config = &req.app().config;
+
+    conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        let pending = PendingCrate::belonging_to(&krate)
+            .first::<PendingCrate>(&*conn)
+            .optional()?
+            .ok_or_else(|| ErrorCode::Conflict.human("this crate isn't waiting for approval"))?;
+
+        if approve {
+            pending.approve(&conn)?;
+            // Scanning was held back until the crate is in the index
+            if config.tarball_scan != TarballScan::Off {
+                let quarantine = config.tarball_scan == TarballScan::Quarantine;
+                let version_ids = versions::table
+                    .filter(versions::crate_id.eq(krate.id))
+                    .select(versions::id)
+                    .load::<i32>(&*conn)?;
+                for version_id in version_ids {
+                    scan::scan_crate_file(version_id, quarantine, telemetry::current())
+                        .enqueue(&conn)
+                        .map_err(|e| CargoError::from_std_error(e))?;
+                }
+            }
+        } else {
+            diesel::delete(&krate).execute(&*conn)?;
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: if approve {
+                AdminAction::ApproveCrate
+            } else {
+                AdminAction::RejectCrate
+            },
+            crate_name: Some(&krate.name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        Ok(())
+    })?;
+
+    req.app().response_cache.invalidate_crate(crate_name);
+    ok_true()
+}
+
+/// Handles the `PUT /admin/crates/:crate_id/lock` route.
+pub fn lock_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_lock(req, true)
+}
+
+/// Handles the `DELETE /admin/crates/:crate_id/lock` route.
+pub fn unlock_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_lock(req, false)
+}
+
+/// Locking a crate blocks publishing new versions of it. Existing versions
+/// are left untouched.
+fn modify_lock(req: &mut dyn Request, locked: bool) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+        diesel::update(&krate)
+            .set(crates::locked.eq(locked))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: if locked {
+                AdminAction::LockCrate
+            } else {
+                AdminAction::UnlockCrate
+            },
+            crate_name: Some(&krate.name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        Ok(())
+    })?;
+
+    req.app().response_cache.invalidate_crate(crate_name);
+    ok_true()
+}
+
+/// Handles the `PUT /admin/crates/:crate_id/max_upload_size` route.
+///
+/// The request body has the format
+///
+///     {"max_upload_size": 52428800}
+///
+/// Sets the maximum size in bytes of the crate files of new versions of the
+/// crate, for crates that legitimately need more than the registry's limit,
+/// e.g. because they bundle assets.
+pub fn set_max_upload_size(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        max_upload_size: i32,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    if request.max_upload_size < 1 {
+        return Err(
+            ErrorCode::InvalidField.human("the maximum upload size must be at least 1 byte")
+        );
+    }
+    modify_max_upload_size(req, Some(request.max_upload_size))
+}
+
+/// Handles the `DELETE /admin/crates/:crate_id/max_upload_size` route.
+///
+/// The crate gets the registry's limit again.
+pub fn remove_max_upload_size(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_max_upload_size(req, None)
+}
+
+fn modify_max_upload_size(
+    req: &mut dyn Request,
+    max_upload_size: Option<i32>,
+) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+        diesel::update(&krate)
+            .set(crates::max_upload_size.eq(max_upload_size))
+            .execute(&*conn)?;
+
+        let detail = max_upload_size.map(|size| format!("{} bytes", size));
+        NewAdminAction {
+            admin_id: admin.id,
+            action: if max_upload_size.is_some() {
+                AdminAction::SetMaxUploadSize
+            } else {
+                AdminAction::RemoveMaxUploadSize
+            },
+            crate_name: Some(&krate.name),
+            user_id: None,
+            detail: detail.as_ref().map(String::as_str),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /admin/crates/:crate_id/rename` route.
+///
+/// The request body has the format
+///
+///     {"name": "new_name"}
+///
+/// Renames the crate, e.g. because of a trademark claim or to correct a typo
+/// in its name. The old name keeps resolving to the crate and can't be
+/// published again, see `CrateRename`. New versions are published under the
+/// new name.
+pub fn rename_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        name: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let admin = admin(req)?;
+    let crate_name = &req.params()["crate_id"];
+    let new_name = &*request.name;
+    if let Some(reason) = Crate::invalid_name_reason(new_name) {
+        return Err(ErrorCode::InvalidField.human(&reason));
+    }
+    let conn = req.db_conn()?;
+
+    let (old_name, new_name) = conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        if krate.visibility == Visibility::Pending {
+            return Err(ErrorCode::Conflict
+                .human("crates waiting for approval can't be renamed, reject them instead"));
+        }
+        // Only the old names of the crate itself can be taken back
+        if canonical_name(new_name) != canonical_name(&krate.name) {
+            let taken = Crate::by_name(new_name)
+                .first::<Crate>(&*conn)
+                .optional()?
+                .is_some();
+            let renamed_from = CrateRename::renamed_crate(&conn, new_name)?;
+            if taken || renamed_from.map_or(false, |(_, other)| other.id != krate.id) {
+                return Err(ErrorCode::AlreadyExists
+                    .human(&format_args!("crate `{}` already exists", new_name)));
+            }
+        }
+
+        let renamed = CrateRename::rename(&conn, &krate, new_name, admin.id)?;
+
+        let detail = format!("renamed from `{}`", krate.name);
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RenameCrate,
+            crate_name: Some(&renamed.name),
+            user_id: None,
+            detail: Some(&detail),
+        }
+        .record(&conn)?;
+
+        Ok((krate.name, renamed.name))
+    })?;
+
+    req.app().response_cache.invalidate_crate(&old_name);
+    req.app().response_cache.invalidate_crate(&new_name);
+    ok_true()
+}
+
+/// Handles the `DELETE /admin/crates/:crate_id` route.
+///
+/// Deletes the crate with all of its versions and removes it from the index.
+/// This breaks every build depending on the crate, so it is only meant for
+/// spam and malware. The uploaded crate files are left in place, but are
+/// purged from the CDN if invalidation is configured. Like crates deleted by
+/// their owners, the name can't be published again until
+/// `Config::deleted_name_cooldown` passed or it is released.
+pub fn delete_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let config = &req.app().config;
+
+    conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+        if config.uploader.invalidates_cdn() {
+            let mut paths = Vec::new();
+            for version in Version::belonging_to(&krate).load::<Version>(&*conn)? {
+                let name = CrateRename::published_name(&conn, &krate, &version)?;
+                paths.extend(Uploader::version_paths(&name, &version.num.to_string()));
+            }
+            cdn::invalidate_cdn(paths)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+        // The versions published before a rename are in the index under the
+        // old names
+        let old_names = CrateRename::old_names(&conn, &krate)?;
+
+        DeletedCrate::delete(&conn, &krate, admin.id, config.deleted_name_cooldown)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::DeleteCrate,
+            crate_name: Some(&krate.name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        for name in old_names.into_iter().chain(Some(krate.name)) {
+            git::delete_crate(name)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /admin/deleted_names/:name` route.
+///
+/// Lets the name of a deleted crate be published again before
+/// `Config::deleted_name_cooldown` passed, e.g. for a new owner the previous
+/// owners handed the name over to.
+pub fn release_deleted_name(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let name = &req.params()["name"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        if !DeletedCrate::release_name(&conn, name)? {
+            return Err(ErrorCode::NotFound.human("no crate with this name was deleted recently"));
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::ReleaseDeletedName,
+            crate_name: Some(name),
+            user_id: None,
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /admin/users/:user_id/ban` route.
+pub fn ban_user(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_ban(req, true)
+}
+
+/// Handles the `DELETE /admin/users/:user_id/ban` route.
+pub fn unban_user(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_ban(req, false)
+}
+
+/// Banned users are treated as logged out, and their API tokens stop working.
+fn modify_ban(req: &mut dyn Request, banned: bool) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let login = &req.params()["user_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let user = find_user(&conn, login)?;
+
+        if user.id == admin.id {
+            return Err(human("admins cannot ban themselves"));
+        }
+
+        diesel::update(&user)
+            .set(users::is_banned.eq(banned))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: if banned {
+                AdminAction::BanUser
+            } else {
+                AdminAction::UnbanUser
+            },
+            crate_name: None,
+            user_id: Some(user.id),
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /admin/rate_limits/:user_id` route.
+///
+/// The request body has the format
+///
+///     {"burst": 100, "days": 7}
+///
+/// Raises how many new crates the user can publish in a short period of time,
+/// e.g. for the initial release of a workspace with many crates. Without
+/// `days` the override doesn't expire.
+pub fn set_rate_limit(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        burst: i32,
+        days: Option<i64>,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    if request.burst < 1 {
+        return Err(ErrorCode::InvalidField.human("the burst must be at least 1"));
+    }
+    if request.days.map_or(false, |days| days < 1) {
+        return Err(ErrorCode::InvalidField.human("overrides must last at least 1 day"));
+    }
+
+    let admin = admin(req)?;
+    let login = &req.params()["user_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let user = find_user(&conn, login)?;
+        let expires_at = request
+            .days
+            .map(|days| Utc::now().naive_utc() + chrono::Duration::days(days));
+
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user.id),
+                publish_rate_overrides::burst.eq(request.burst),
+                publish_rate_overrides::expires_at.eq(expires_at),
+            ))
+            .on_conflict(publish_rate_overrides::user_id)
+            .do_update()
+            .set((
+                publish_rate_overrides::burst.eq(request.burst),
+                publish_rate_overrides::expires_at.eq(expires_at),
+            ))
+            .execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::SetRateLimit,
+            crate_name: None,
+            user_id: Some(user.id),
+            detail: Some(&format!("burst {}", request.burst)),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /admin/rate_limits/:user_id` route.
+pub fn remove_rate_limit(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let login = &req.params()["user_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let user = find_user(&conn, login)?;
+
+        diesel::delete(publish_rate_overrides::table.find(user.id)).execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RemoveRateLimit,
+            crate_name: None,
+            user_id: Some(user.id),
+            detail: None,
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Returns an error unless the operator manages the categories of the
+/// registry. Otherwise they are replaced with the crates.io categories on the
+/// next boot.
+fn custom_categories(req: &dyn Request) -> CargoResult<()> {
+    if !req.app().config.branding.custom_categories {
+        return Err(ErrorCode::Conflict.human(
+            "categories are synced from categories.toml on boot, \
+             set REGISTRY_CUSTOM_CATEGORIES to manage them",
+        ));
+    }
+    Ok(())
+}
+
+/// Handles the `POST /admin/categories` route.
+///
+/// The request body has the format
+///
+///     {"slug": "parsers", "name": "Parsers", "description": "...", "parent": "parsing"}
+///
+/// Creates a top-level category, or a subcategory of `parent`.
+pub fn create_category(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        slug: String,
+        name: String,
+        #[serde(default)]
+        description: String,
+        parent: Option<String>,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    validate_category_slug(&request.slug)?;
+    validate_category_name(&request.name)?;
+
+    let admin = admin(req)?;
+    custom_categories(req)?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let (slug, name) = match &request.parent {
+            Some(parent) => {
+                let parent = Category::by_slug(parent)
+                    .first::<Category>(&*conn)
+                    .optional()?
+                    .ok_or_else(|| {
+                        ErrorCode::NotFound
+                            .human(&format_args!("unknown parent category `{}`", parent))
+                    })?;
+                (
+                    format!("{}::{}", parent.slug, request.slug),
+                    format!("{}::{}", parent.category, request.name),
+                )
+            }
+            None => (request.slug.clone(), request.name.clone()),
+        };
+        let existing = categories::table
+            .filter(
+                categories::slug
+                    .eq(&slug)
+                    .or(categories::category.eq(&name)),
+            )
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if existing > 0 {
+            return Err(
+                ErrorCode::AlreadyExists.human("a category with this slug or name already exists")
+            );
+        }
+
+        let category = NewCategory {
+            category: &name,
+            slug: &slug,
+            description: &request.description,
+        }
+        .create(&conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::CreateCategory,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&slug),
+        }
+        .record(&conn)?;
+
+        #[derive(Serialize)]
+        struct R {
+            category: EncodableCategory,
+        }
+        Ok(req.json(&R {
+            category: category.encodable(),
+        }))
+    })
+}
+
+/// Handles the `PUT /admin/categories/:category_id` route.
+///
+/// The request body has the format
+///
+///     {"slug": "parsers", "name": "Parsers", "description": "..."}
+///
+/// All fields are optional. Renaming a category renames its subcategories as
+/// well, and their old slugs redirect to the new ones.
+pub fn update_category(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        slug: Option<String>,
+        name: Option<String>,
+        description: Option<String>,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    if let Some(slug) = &request.slug {
+        validate_category_slug(slug)?;
+    }
+    if let Some(name) = &request.name {
+        validate_category_name(name)?;
+    }
+
+    let admin = admin(req)?;
+    custom_categories(req)?;
+    let old_slug = &req.params()["category_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let category = Category::by_slug(old_slug).first::<Category>(&*conn)?;
+
+        // Only the last part of the full slug and name of subcategories changes
+        let replace_last = |full: &str, new: Option<&String>| match (full.rfind("::"), new) {
+            (Some(idx), Some(new)) => format!("{}::{}", &full[..idx], new),
+            (None, Some(new)) => new.clone(),
+            (_, None) => full.to_string(),
+        };
+        let slug = replace_last(&category.slug, request.slug.as_ref());
+        let name = replace_last(&category.category, request.name.as_ref());
+
+        if slug != category.slug || name != category.category {
+            let others = categories::table
+                .filter(categories::id.ne(category.id))
+                .filter(
+                    categories::slug
+                        .eq(&slug)
+                        .or(categories::category.eq(&name)),
+                )
+                .count()
+                .get_result::<i64>(&*conn)?;
+            if others > 0 {
+                return Err(ErrorCode::AlreadyExists
+                    .human("a category with this slug or name already exists"));
+            }
+            category.rename(&conn, &slug, &name)?;
+        }
+        if let Some(description) = &request.description {
+            diesel::update(&category)
+                .set(categories::description.eq(description))
+                .execute(&*conn)?;
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::UpdateCategory,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!("{} -> {}", category.slug, slug)),
+        }
+        .record(&conn)?;
+
+        let category = categories::table
+            .find(category.id)
+            .first::<Category>(&*conn)?;
+        #[derive(Serialize)]
+        struct R {
+            category: EncodableCategory,
+        }
+        Ok(req.json(&R {
+            category: category.encodable(),
+        }))
+    })
+}
+
+/// Handles the `PUT /admin/categories/:category_id/merge` route.
+///
+/// The request body has the format
+///
+///     {"into": "parsing::parsers"}
+///
+/// Moves the crates of the category to the `into` category and deletes it.
+/// Its slug redirects to the `into` category afterwards. Subcategories have
+/// to be merged or renamed first.
+pub fn merge_category(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        into: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let admin = admin(req)?;
+    custom_categories(req)?;
+    let slug = &req.params()["category_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let category = Category::by_slug(slug).first::<Category>(&*conn)?;
+        let target = Category::by_slug(&request.into)
+            .first::<Category>(&*conn)
+            .optional()?
+            .ok_or_else(|| {
+                ErrorCode::NotFound.human(&format_args!("unknown category `{}`", request.into))
+            })?;
+        if target.id == category.id {
+            return Err(human("a category cannot be merged into itself"));
+        }
+        let subcategories = categories::table
+            .filter(categories::slug.like(format!("{}::%", category.slug)))
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if subcategories > 0 {
+            return Err(ErrorCode::Conflict
+                .human("the subcategories of the category have to be merged or renamed first"));
+        }
+
+        category.merge_into(&conn, &target)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::MergeCategory,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!("{} -> {}", category.slug, target.slug)),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Checks the last part of the slug of a category.
+fn validate_category_slug(slug: &str) -> CargoResult<()> {
+    let valid = !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(ErrorCode::InvalidField
+            .human("category slugs may only contain lowercase letters, digits and `-`"));
+    }
+    Ok(())
+}
+
+/// Checks the last part of the name of a category.
+fn validate_category_name(name: &str) -> CargoResult<()> {
+    if name.trim().is_empty() || name.contains("::") {
+        return Err(
+            ErrorCode::InvalidField.human("category names must not be empty or contain `::`")
+        );
+    }
+    Ok(())
+}
+
+/// Handles the `GET /admin/keyword_aliases` route.
+pub fn keyword_aliases(req: &mut dyn Request) -> CargoResult<Response> {
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let aliases = keyword_aliases::table
+        .order(keyword_aliases::alias)
+        .load::<KeywordAlias>(&*conn)?
+        .into_iter()
+        .map(KeywordAlias::encodable)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        aliases: Vec<EncodableKeywordAlias>,
+    }
+    Ok(req.json(&R { aliases }))
+}
+
+/// Handles the `PUT /admin/keyword_aliases/:alias` route.
+///
+/// The request body has the format
+///
+///     {"keyword": "async"}
+///
+/// Crates that are published with the alias get the keyword instead, and
+/// the crates that already have the alias are moved to the keyword.
+pub fn add_keyword_alias(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        keyword: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let admin = admin(req)?;
+    let alias = req.params()["alias"].to_lowercase();
+    let keyword = request.keyword.to_lowercase();
+    if !Keyword::valid_name(&alias) || !Keyword::valid_name(&keyword) {
+        return Err(ErrorCode::InvalidField.human("invalid keyword"));
+    }
+    if alias == keyword {
+        return Err(human("a keyword cannot be an alias of itself"));
+    }
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let keyword_is_alias = keyword_aliases::table
+            .find(&keyword)
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if keyword_is_alias > 0 {
+            return Err(ErrorCode::Conflict.human(&format_args!(
+                "`{}` is an alias itself, use the keyword it stands for",
+                keyword
+            )));
+        }
+
+        KeywordAlias::create(&conn, &alias, &keyword)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::AddKeywordAlias,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!("{} -> {}", alias, keyword)),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /admin/keyword_aliases/:alias` route.
+///
+/// Crates that were moved to the keyword keep it.
+pub fn remove_keyword_alias(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let alias = req.params()["alias"].to_lowercase();
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let deleted = diesel::delete(keyword_aliases::table.find(&alias)).execute(&*conn)?;
+        if deleted == 0 {
+            return Err(ErrorCode::NotFound.human("this keyword is not an alias"));
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RemoveKeywordAlias,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&alias),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `GET /admin/jobs` route.
+///
+/// Lists the number of queued jobs of every type, the jobs that have failed
+/// at least once, the dead letters and the policies of the job types, see the
+/// `background_job` model.
+pub fn jobs(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::count_star;
+
+    admin(req)?;
+    let conn = req.db_conn()?;
+
+    let queued = background_jobs::table
+        .group_by(background_jobs::job_type)
+        .select((background_jobs::job_type, count_star()))
+        .load::<(String, i64)>(&*conn)?
+        .into_iter()
+        .collect();
+    let failed = BackgroundJob::failed(&conn)?
+        .into_iter()
+        .map(BackgroundJob::encodable)
+        .collect();
+    let dead_letters = background_job_dead_letters::table
+        .order(background_job_dead_letters::dead_at.desc())
+        .load::<DeadLetter>(&*conn)?
+        .into_iter()
+        .map(DeadLetter::encodable)
+        .collect();
+    let policies = JobPolicy::all(&conn)?
+        .into_iter()
+        .map(JobPolicy::encodable)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        queued: HashMap<String, i64>,
+        failed: Vec<EncodableBackgroundJob>,
+        dead_letters: Vec<EncodableDeadLetter>,
+        policies: Vec<EncodableJobPolicy>,
+    }
+    Ok(req.json(&R {
+        queued,
+        failed,
+        dead_letters,
+        policies,
+    }))
+}
+
+/// Handles the `PUT /admin/jobs/:job_id/retry` route.
+///
+/// Runs a failed job again without waiting for its backoff.
+pub fn retry_job(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let job_id = req.params()["job_id"]
+        .parse::<i64>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid job id"))?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        if !BackgroundJob::retry_now(&conn, job_id)? {
+            return Err(ErrorCode::NotFound.human("no failed job with this id is queued"));
+        }
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RetryJob,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!("job {}", job_id)),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /admin/jobs/dead_letters/:dead_letter_id/retry` route.
+///
+/// Enqueues the job of a dead letter again, starting over with its attempts.
+pub fn retry_dead_letter(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let id = dead_letter_id(req)?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let dead_letter = find_dead_letter(&conn, id)?;
+        dead_letter.requeue(&conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::RetryJob,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!(
+                "{} job {}",
+                dead_letter.job_type, dead_letter.job_id
+            )),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /admin/jobs/dead_letters/:dead_letter_id` route.
+pub fn discard_dead_letter(req: &mut dyn Request) -> CargoResult<Response> {
+    let admin = admin(req)?;
+    let id = dead_letter_id(req)?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let dead_letter = find_dead_letter(&conn, id)?;
+        diesel::delete(&dead_letter).execute(&*conn)?;
+
+        NewAdminAction {
+            admin_id: admin.id,
+            action: AdminAction::DiscardJob,
+            crate_name: None,
+            user_id: None,
+            detail: Some(&format!(
+                "{} job {}",
+                dead_letter.job_type, dead_letter.job_id
+            )),
+        }
+        .record(&conn)?;
+
+        ok_true()
+    })
+}
+
+fn dead_letter_id(req: &dyn Request) -> CargoResult<i64> {
+    req.params()["dead_letter_id"]
+        .parse()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid dead letter id"))
+}
+
+fn find_dead_letter(conn: &PgConnection, id: i64) -> CargoResult<DeadLetter> {
+    background_job_dead_letters::table
+        .find(id)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| ErrorCode::NotFound.human("no dead letter with this id"))
+}
+
+/// Finds the most recently created user with the given GitHub login.
+fn find_user(conn: &PgConnection, login: &str) -> QueryResult<User> {
+    users::table
+        .filter(crate::lower(users::gh_login).eq(login.to_lowercase()))
+        .order(users::id.desc())
+        .first(conn)
+}
@@ -1,8 +1,9 @@
 use super::helpers::pagination::*;
+use super::helpers::top_crates;
 use super::prelude::*;
 
-use crate::models::Category;
-use crate::schema::categories;
+use crate::models::{Category, FollowTarget, TopicFollow, Visibility};
+use crate::schema::{categories, crates, crates_categories, topic_follows};
 use crate::views::{EncodableCategory, EncodableCategoryWithSubcategories};
 
 /// Handles the `GET /categories` route.
@@ -39,11 +40,52 @@ pub fn index(req: &mut dyn Request) -> CargoResult<Response> {
     }))
 }
 
+/// The default number of categories returned by `suggest`.
+const DEFAULT_SUGGESTIONS: i64 = 10;
+
+/// The maximum number of categories returned by `suggest`.
+const MAX_SUGGESTIONS: i64 = 50;
+
+/// Handles the `GET /categories/suggest` route.
+///
+/// Returns the categories matching the `q` query parameter, along with the
+/// number of crates in them, for autocompletion. See
+/// `Category::suggestions` for how they are matched. The number of results
+/// can be adjusted with the `limit` parameter.
+pub fn suggest(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let query = req.query();
+    let q = query.get("q").map(|s| s.trim()).unwrap_or("");
+    let limit = query
+        .get("limit")
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_SUGGESTIONS)
+        .max(1)
+        .min(MAX_SUGGESTIONS);
+
+    let categories = if q.is_empty() {
+        Vec::new()
+    } else {
+        Category::suggestions(&conn, q, limit)?
+            .into_iter()
+            .map(Category::encodable)
+            .collect()
+    };
+
+    #[derive(Serialize)]
+    struct R {
+        categories: Vec<EncodableCategory>,
+    }
+    Ok(req.json(&R { categories }))
+}
+
 /// Handles the `GET /categories/:category_id` route.
+///
+/// Renamed and merged categories are also found by their old slugs.
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     let slug = &req.params()["category_id"];
     let conn = req.db_conn()?;
-    let cat = Category::by_slug(slug).first::<Category>(&*conn)?;
+    let cat = Category::find_by_slug(&conn, slug)?;
     let subcats = cat
         .subcategories(&conn)?
         .into_iter()
@@ -76,6 +118,31 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     }))
 }
 
+/// Handles the `GET /categories/:category_id/top` route.
+///
+/// Returns the public crates of the category and its subcategories that had
+/// the most downloads in the last 12 weeks, and the ones that were updated
+/// most recently.
+pub fn top(req: &mut dyn Request) -> CargoResult<Response> {
+    let slug = &req.params()["category_id"];
+    let conn = req.db_conn()?;
+    let cat = Category::find_by_slug(&conn, slug)?;
+    let crate_ids = crates_categories::table
+        .inner_join(categories::table)
+        .inner_join(crates::table)
+        .filter(
+            categories::slug
+                .eq(&cat.slug)
+                .or(categories::slug.like(format!("{}::%", cat.slug))),
+        )
+        .filter(crates::visibility.eq(Visibility::Public))
+        .select(crates_categories::crate_id)
+        .distinct()
+        .load::<i32>(&*conn)?;
+
+    Ok(req.json(&top_crates(&conn, &crate_ids)?))
+}
+
 /// Handles the `GET /category_slugs` route.
 pub fn slugs(req: &mut dyn Request) -> CargoResult<Response> {
     let conn = req.db_conn()?;
@@ -99,3 +166,51 @@ pub fn slugs(req: &mut dyn Request) -> CargoResult<Response> {
         category_slugs: slugs,
     }))
 }
+
+fn follow_target(req: &dyn Request, conn: &PgConnection) -> CargoResult<TopicFollow> {
+    let user = req.user()?;
+    let target = Category::find_by_slug(conn, &req.params()["category_id"])?;
+    Ok(TopicFollow {
+        user_id: user.id,
+        kind: FollowTarget::Category,
+        target_id: target.id,
+    })
+}
+
+/// Handles the `PUT /categories/:category_id/follow` route.
+pub fn follow(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    diesel::insert_into(topic_follows::table)
+        .values(&follow)
+        .on_conflict_do_nothing()
+        .execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `DELETE /categories/:category_id/follow` route.
+pub fn unfollow(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    diesel::delete(&follow).execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `GET /categories/:category_id/following` route.
+pub fn following(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::associations::Identifiable;
+    use diesel::dsl::exists;
+
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    let following =
+        diesel::select(exists(topic_follows::table.find(follow.id()))).get_result(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        following: bool,
+    }
+    Ok(req.json(&R { following }))
+}
@@ -0,0 +1,219 @@
+//! Endpoints for the public crate lists curated by users
+
+use diesel::dsl::now;
+use serde_json;
+
+use crate::controllers::prelude::*;
+use crate::db::DieselPooledConn;
+use crate::models::{Crate, CrateList, NewCrateList, User, Visibility};
+use crate::schema::{crate_list_follows, crate_list_items, crate_lists, users};
+use crate::util::errors::NotFound;
+use crate::views::EncodableCrateList;
+
+/// The maximum length of the name of a list, in characters.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// The maximum length of the description of a list, in characters.
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+/// Handles the `GET /me/lists` route.
+pub fn mine(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let lists = CrateList::belonging_to(user)
+        .order(crate_lists::name)
+        .load(&*conn)?;
+    lists_response(req, &conn, lists)
+}
+
+/// Handles the `GET /users/:user_id/lists` route.
+pub fn for_user(req: &mut dyn Request) -> CargoResult<Response> {
+    let login = req.params()["user_id"].to_lowercase();
+    let conn = req.db_conn()?;
+    let user = users::table
+        .filter(crate::lower(users::gh_login).eq(login))
+        .order(users::id.desc())
+        .first::<User>(&*conn)?;
+    let lists = CrateList::belonging_to(&user)
+        .order(crate_lists::name)
+        .load(&*conn)?;
+    lists_response(req, &conn, lists)
+}
+
+/// Handles the `GET /lists/:list_id` route.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let list = find_list(req, &conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        list: EncodableCrateList,
+    }
+    Ok(req.json(&R {
+        list: CrateList::encode_all(&conn, vec![list])?.remove(0),
+    }))
+}
+
+/// Handles the `POST /me/lists` route.
+///
+/// The request body has the format
+///
+///     {"name": "Web services", "description": "..."}
+///
+/// The names of the lists of a user are unique, ignoring case.
+pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        name: String,
+        #[serde(default)]
+        description: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "the name of a list must have between 1 and {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    let description = request.description.trim();
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "the description must not be longer than {} characters",
+            MAX_DESCRIPTION_LENGTH
+        )));
+    }
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let list = NewCrateList {
+        user_id: user.id,
+        name,
+        description,
+    }
+    .create(&conn)?
+    .ok_or_else(|| ErrorCode::AlreadyExists.human("you already have a list with this name"))?;
+
+    #[derive(Serialize)]
+    struct R {
+        list: EncodableCrateList,
+    }
+    Ok(req.json(&R {
+        list: CrateList::encode_all(&conn, vec![list])?.remove(0),
+    }))
+}
+
+/// Handles the `DELETE /me/lists/:list_id` route.
+pub fn delete(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let list = own_list(req, &conn)?;
+    diesel::delete(&list).execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `PUT /me/lists/:list_id/crates/:crate_id` route.
+///
+/// Only public crates can be added to lists.
+pub fn add_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let list = own_list(req, &conn)?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if krate.visibility != Visibility::Public {
+        return Err(Box::new(NotFound));
+    }
+
+    conn.transaction(|| {
+        diesel::insert_into(crate_list_items::table)
+            .values((
+                crate_list_items::list_id.eq(list.id),
+                crate_list_items::crate_id.eq(krate.id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&*conn)?;
+        diesel::update(&list)
+            .set(crate_lists::updated_at.eq(now))
+            .execute(&*conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /me/lists/:list_id/crates/:crate_id` route.
+pub fn remove_crate(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let list = own_list(req, &conn)?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+    conn.transaction(|| {
+        diesel::delete(crate_list_items::table.find((list.id, krate.id))).execute(&*conn)?;
+        diesel::update(&list)
+            .set(crate_lists::updated_at.eq(now))
+            .execute(&*conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /lists/:list_id/follow` route.
+pub fn follow(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let list = find_list(req, &conn)?;
+    diesel::insert_into(crate_list_follows::table)
+        .values((
+            crate_list_follows::list_id.eq(list.id),
+            crate_list_follows::user_id.eq(user.id),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `DELETE /lists/:list_id/follow` route.
+pub fn unfollow(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let list = find_list(req, &conn)?;
+    diesel::delete(crate_list_follows::table.find((list.id, user.id))).execute(&*conn)?;
+
+    ok_true()
+}
+
+fn find_list(req: &dyn Request, conn: &DieselPooledConn<'_>) -> CargoResult<CrateList> {
+    let id = req.params()["list_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid list id"))?;
+    Ok(crate_lists::table.find(id).first(&**conn)?)
+}
+
+/// Finds the list in the route, which has to belong to the current user.
+fn own_list(req: &dyn Request, conn: &DieselPooledConn<'_>) -> CargoResult<CrateList> {
+    let user = req.user()?;
+    let list = find_list(req, conn)?;
+    if list.user_id != user.id {
+        return Err(Box::new(NotFound));
+    }
+    Ok(list)
+}
+
+fn lists_response(
+    req: &dyn Request,
+    conn: &PgConnection,
+    lists: Vec<CrateList>,
+) -> CargoResult<Response> {
+    #[derive(Serialize)]
+    struct R {
+        lists: Vec<EncodableCrateList>,
+    }
+    Ok(req.json(&R {
+        lists: CrateList::encode_all(conn, lists)?,
+    }))
+}
@@ -37,8 +37,8 @@ pub fn handle_invite(req: &mut dyn Request) -> CargoResult<Response> {
 
     let conn = &*req.db_conn()?;
 
-    let crate_invite: OwnerInvitation =
-        serde_json::from_str(&body).map_err(|_| human("invalid json request"))?;
+    let crate_invite: OwnerInvitation = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
 
     let crate_invite = crate_invite.crate_owner_invite;
 
@@ -0,0 +1,301 @@
+//! Endpoint for GitHub's secret scanning partner program
+//!
+//! GitHub scans public repositories for strings that look like API tokens of
+//! the registry, and reports them to this endpoint. Reported tokens that
+//! exist are revoked right away, and their owners are told about it.
+//!
+//! See <https://developer.github.com/partnerships/secret-scanning/>
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Verifier;
+use reqwest::header;
+
+use crate::app::App;
+use crate::controllers::prelude::*;
+use crate::email;
+use crate::logging;
+use crate::models::ApiToken;
+use crate::schema::{api_tokens, emails, users};
+use crate::util::errors::Unauthorized;
+use crate::util::{internal, CargoError};
+
+/// How long the public keys GitHub signs its reports with are cached. Reports
+/// signed with a key that isn't cached fetch the keys again.
+const PUBLIC_KEYS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to wait before fetching the keys again, so that reports signed
+/// with unknown keys can't make every request fetch them.
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+lazy_static! {
+    static ref PUBLIC_KEYS: Mutex<PublicKeyCache> = Mutex::new(PublicKeyCache::default());
+}
+
+#[derive(Default)]
+struct PublicKeyCache {
+    keys: Vec<PublicKey>,
+    fetched_at: Option<Instant>,
+    /// When the keys were last fetched, or tried to be
+    attempted_at: Option<Instant>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct PublicKey {
+    key_identifier: String,
+    /// The PEM encoded ECDSA public key
+    key: String,
+}
+
+/// A token GitHub found in a public repository.
+#[derive(Deserialize, Debug)]
+struct Report {
+    token: String,
+    #[serde(rename = "type")]
+    token_type: String,
+    /// Where the token was found
+    url: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Verdict {
+    token_raw: String,
+    token_type: String,
+    /// `true_positive` if the token was a valid token of the registry
+    label: &'static str,
+}
+
+/// Handles the `POST /api/github/secret-scanning/verify` route.
+///
+/// The request body is a JSON array of reports like
+///
+///     [{"token": "...", "type": "crates_io_api_token", "url": "https://...", "source": "content"}]
+///
+/// signed by GitHub. The `GITHUB-PUBLIC-KEY-IDENTIFIER` header names the key
+/// of GitHub that signed the body, and the `GITHUB-PUBLIC-KEY-SIGNATURE`
+/// header contains the base64 encoded ECDSA signature. The response tells
+/// GitHub which of the reported tokens were valid.
+pub fn verify(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = Vec::new();
+    req.body().read_to_end(&mut body)?;
+
+    let header = |name: &str| {
+        req.headers()
+            .find(name)
+            .and_then(|values| values.first().map(|s| s.to_string()))
+            .ok_or_else(|| Box::new(Unauthorized) as Box<dyn CargoError>)
+    };
+    let key_identifier = header("GITHUB-PUBLIC-KEY-IDENTIFIER")?;
+    let signature = header("GITHUB-PUBLIC-KEY-SIGNATURE")?;
+
+    let app = req.app();
+    let key = public_key(app, &key_identifier)?
+        .ok_or_else(|| Box::new(Unauthorized) as Box<dyn CargoError>)?;
+    if !signature_matches(&key, &body, &signature) {
+        return Err(Box::new(Unauthorized));
+    }
+
+    let reports: Vec<Report> = serde_json::from_slice(&body)
+        .map_err(|_| ErrorCode::InvalidJson.bad_request("invalid json request"))?;
+    let conn = req.db_conn()?;
+    let verdicts = reports
+        .into_iter()
+        .map(|report| handle_report(&conn, report))
+        .collect::<QueryResult<Vec<_>>>()?;
+
+    Ok(req.json(&verdicts))
+}
+
+/// Revokes the reported token if it exists, and tells its owner unless it
+/// was revoked already.
+fn handle_report(conn: &PgConnection, report: Report) -> QueryResult<Verdict> {
+    let token = api_tokens::table
+        .filter(api_tokens::token.eq(&report.token))
+        .first::<ApiToken>(conn)
+        .optional()?;
+
+    let label = match token {
+        Some(token) => {
+            let revoked = diesel::update(
+                api_tokens::table
+                    .find(token.id)
+                    .filter(api_tokens::revoked.eq(false)),
+            )
+            .set(api_tokens::revoked.eq(true))
+            .execute(conn)?;
+            // Tokens that are reported again, e.g. when found in another
+            // repository, were already revoked and their owner told about it
+            if revoked > 0 {
+                logging::info(
+                    "revoked api token found by github secret scanning",
+                    json!({ "token_id": token.id, "user_id": token.user_id, "url": &report.url }),
+                );
+                notify_owner(conn, &token, report.url.as_ref().map(|s| &s[..]))?;
+            }
+            "true_positive"
+        }
+        None => "false_positive",
+    };
+
+    Ok(Verdict {
+        token_raw: report.token,
+        token_type: report.token_type,
+        label,
+    })
+}
+
+fn notify_owner(conn: &PgConnection, token: &ApiToken, url: Option<&str>) -> QueryResult<()> {
+    let owner = users::table
+        .find(token.user_id)
+        .inner_join(emails::table)
+        .filter(emails::verified.eq(true))
+        .select((emails::email, users::gh_login))
+        .first::<(String, String)>(conn)
+        .optional()?;
+    if let Some((email, user_name)) = owner {
+        // The token is revoked either way, so a failed email shouldn't make
+        // GitHub report it again
+        let _ = email::send_token_exposed_email(conn, &email, &user_name, &token.name, url);
+    }
+    Ok(())
+}
+
+/// Returns the public key of GitHub with the given identifier, fetching the
+/// keys from GitHub if they aren't cached or don't contain it, at most once
+/// every `MIN_REFETCH_INTERVAL`. Expired keys are used until then.
+fn public_key(app: &App, key_identifier: &str) -> CargoResult<Option<PublicKey>> {
+    let find = |keys: &[PublicKey]| {
+        keys.iter()
+            .find(|key| key.key_identifier == key_identifier)
+            .cloned()
+    };
+    let recently = |instant: Option<Instant>, duration| {
+        instant.map_or(false, |instant: Instant| instant.elapsed() < duration)
+    };
+
+    {
+        let mut cache = PUBLIC_KEYS.lock().unwrap();
+        let key = find(&cache.keys[..]);
+        if key.is_some() && recently(cache.fetched_at, PUBLIC_KEYS_TTL) {
+            return Ok(key);
+        }
+        if recently(cache.attempted_at, MIN_REFETCH_INTERVAL) {
+            return Ok(key);
+        }
+        cache.attempted_at = Some(Instant::now());
+    }
+
+    // The lock isn't held while fetching, so that a slow response from GitHub
+    // doesn't hold up reports signed with cached keys
+    let keys = fetch_public_keys(app)?;
+    let key = find(&keys[..]);
+    let mut cache = PUBLIC_KEYS.lock().unwrap();
+    cache.keys = keys;
+    cache.fetched_at = Some(Instant::now());
+    Ok(key)
+}
+
+fn fetch_public_keys(app: &App) -> CargoResult<Vec<PublicKey>> {
+    #[derive(Deserialize)]
+    struct PublicKeys {
+        public_keys: Vec<PublicKey>,
+    }
+
+    let url = format!(
+        "{}://api.github.com/meta/public_keys/secret_scanning",
+        app.config.api_protocol
+    );
+    let keys = app
+        .http_client()
+        .get(&url)
+        .header(header::ACCEPT, "application/vnd.github.v3+json")
+        .send()?
+        .error_for_status()
+        .map_err(|e| internal(&format_args!("couldn't fetch github's public keys: {}", e)))?
+        .json::<PublicKeys>()?;
+    Ok(keys.public_keys)
+}
+
+/// Whether `signature`, a base64 encoded DER ECDSA signature, is the
+/// signature of `body` by `key`.
+fn signature_matches(key: &PublicKey, body: &[u8], signature: &str) -> bool {
+    let verify = || -> Result<bool, Box<dyn std::error::Error>> {
+        let signature = base64::decode(signature.trim())?;
+        let key = PKey::public_key_from_pem(key.key.as_bytes())?;
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+        verifier.update(body)?;
+        Ok(verifier.verify(&signature)?)
+    };
+    verify().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_fixtures::user, test_util::pg_connection};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+
+    fn key_pair() -> (PKey<Private>, PublicKey) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let private = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let public = PublicKey {
+            key_identifier: "abc".into(),
+            key: String::from_utf8(private.public_key_to_pem().unwrap()).unwrap(),
+        };
+        (private, public)
+    }
+
+    fn sign(key: &PKey<Private>, body: &[u8]) -> String {
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer.update(body).unwrap();
+        base64::encode(&signer.sign_to_vec().unwrap())
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let (private, public) = key_pair();
+        let body = br#"[{"token":"abc","type":"crates_io_api_token"}]"#;
+        let signature = sign(&private, body);
+
+        assert!(signature_matches(&public, body, &signature));
+        assert!(!signature_matches(&public, b"[]", &signature));
+        assert!(!signature_matches(&public, body, "not base64!"));
+
+        let (_, other) = key_pair();
+        assert!(!signature_matches(&other, body, &signature));
+    }
+
+    #[test]
+    fn reported_tokens_are_revoked() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let token = ApiToken::insert(&conn, user.id, "laptop").unwrap();
+
+        let report = |token: &str| Report {
+            token: token.into(),
+            token_type: "crates_io_api_token".into(),
+            url: None,
+        };
+        let verdict = handle_report(&conn, report(&token.token)).unwrap();
+        assert_eq!(verdict.label, "true_positive");
+        assert_eq!(verdict.token_raw, token.token);
+        let revoked = api_tokens::table
+            .find(token.id)
+            .select(api_tokens::revoked)
+            .first::<bool>(&conn)
+            .unwrap();
+        assert!(revoked);
+
+        // Reports of revoked tokens are still confirmed
+        let verdict = handle_report(&conn, report(&token.token)).unwrap();
+        assert_eq!(verdict.label, "true_positive");
+
+        let verdict = handle_report(&conn, report("unknown")).unwrap();
+        assert_eq!(verdict.label, "false_positive");
+    }
+}
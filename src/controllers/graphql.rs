@@ -0,0 +1,514 @@
+//! A GraphQL endpoint exposing crates, versions, owners and users
+//!
+//! This is an alternative to the REST endpoints for clients that want to fetch
+//! exactly the fields they need in a single round trip. The same authorization
+//! rules apply: private crates are only visible to users that can see them
+//! through the REST API, and email addresses are only exposed to the user
+//! they belong to.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use juniper::{EmptyMutation, FieldResult, RootNode};
+use serde_json;
+
+use crate::app::App;
+use crate::controllers::prelude::*;
+use crate::db::DieselPooledConn;
+use crate::models::{Crate, CrateOwner, OwnerKind, User, Version, VersionDownload};
+use crate::schema::{crate_owners, crates, users, version_downloads};
+use crate::views::EncodableOwner;
+
+use crate::models::krate::ALL_COLUMNS;
+
+/// The maximum number of crates that can be requested through `crates(names:)`.
+const MAX_CRATES_PER_QUERY: usize = 100;
+/// The deepest selection sets can be nested in a query, see `check_query_size`.
+const MAX_QUERY_DEPTH: usize = 8;
+/// The most fields a query can select, see `check_query_size`.
+const MAX_QUERY_FIELDS: usize = 200;
+
+pub type Schema = RootNode<'static, Query, EmptyMutation<Context>>;
+
+pub fn schema() -> Schema {
+    Schema::new(Query, EmptyMutation::new())
+}
+
+/// Handles the `POST /graphql` route.
+///
+/// The request body has the standard GraphQL over HTTP format
+///
+///     {"query": "...", "operationName": "...", "variables": {...}}
+pub fn execute(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: GraphQLRequest = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.bad_request("invalid json request"))?;
+    check_query_size(&request.query)?;
+
+    // Tests share a single connection, which every resolver can lock again
+    let conn = match req.db_conn()? {
+        DieselPooledConn::Pool(conn) => Some(conn),
+        DieselPooledConn::Test(_) => None,
+    };
+    let context = Context {
+        app: Arc::clone(req.app()),
+        user: req.user().ok().cloned(),
+        conn,
+    };
+    let request = juniper::http::GraphQLRequest::new(
+        request.query,
+        request.operation_name,
+        request.variables,
+    );
+    let response = request.execute(&schema(), &context);
+
+    let mut resp = req.json(&response);
+    if !response.is_ok() {
+        resp.status = (400, "Bad Request");
+    }
+    Ok(resp)
+}
+
+/// The body of a GraphQL request. This is parsed separately from
+/// `juniper::http::GraphQLRequest`, which doesn't expose the query, so that
+/// the query can be checked before it is executed.
+#[derive(Deserialize)]
+struct GraphQLRequest {
+    query: String,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<juniper::InputValue>,
+}
+
+/// Rejects queries that nest selection sets deeper than `MAX_QUERY_DEPTH` or
+/// select more than `MAX_QUERY_FIELDS` fields, before they are executed.
+/// Juniper doesn't limit either, and every field of a list is resolved with
+/// its own database queries.
+///
+/// This only tokenizes the query instead of parsing it. Fragments are counted
+/// as if they were all nested in each other, which overestimates the depth of
+/// queries using several fragments but can't be bypassed by spreading them.
+fn check_query_size(query: &str) -> CargoResult<()> {
+    let mut chars = query.chars().peekable();
+    let (mut depth, mut parens, mut fields) = (0, 0, 0);
+    let (mut definition_depth, mut operations_depth, mut fragments_depth) = (0, 0, 0);
+    let mut in_fragment = false;
+    // Whether the next name is a fragment, type or directive name rather than a field
+    let mut skip_name = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '"' => skip_string(&mut chars),
+            '(' => parens += 1,
+            ')' => parens -= 1,
+            '{' if parens == 0 => {
+                depth += 1;
+                definition_depth = definition_depth.max(depth);
+            }
+            '}' if parens == 0 => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    if in_fragment {
+                        fragments_depth += definition_depth;
+                    } else {
+                        operations_depth = operations_depth.max(definition_depth);
+                    }
+                    definition_depth = 0;
+                    in_fragment = false;
+                }
+            }
+            '.' | '@' => skip_name = true,
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c != '_' && !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                while chars
+                    .peek()
+                    .map_or(false, |c| c.is_whitespace() || *c == ',')
+                {
+                    chars.next();
+                }
+                let is_alias = chars.peek() == Some(&':');
+
+                if depth == 0 {
+                    in_fragment |= parens == 0 && name == "fragment";
+                } else if parens == 0 && name == "on" && !is_alias {
+                    // The type condition of an inline fragment
+                    skip_name = true;
+                    continue;
+                } else if parens == 0 && !skip_name && !is_alias {
+                    fields += 1;
+                }
+                skip_name = false;
+            }
+            _ => {}
+        }
+    }
+
+    if operations_depth + fragments_depth > MAX_QUERY_DEPTH {
+        return Err(ErrorCode::InvalidRequest.bad_request(&format_args!(
+            "the query is nested more than {} levels deep",
+            MAX_QUERY_DEPTH
+        )));
+    }
+    if fields > MAX_QUERY_FIELDS {
+        return Err(ErrorCode::InvalidRequest.bad_request(&format_args!(
+            "the query selects more than {} fields",
+            MAX_QUERY_FIELDS
+        )));
+    }
+    Ok(())
+}
+
+/// Skips a string literal, the opening quote of which was just consumed.
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    let mut rest = chars.clone();
+    if rest.next() == Some('"') && rest.next() == Some('"') {
+        // A block string, which ends at the next unescaped `"""`
+        chars.next();
+        chars.next();
+        let mut quotes = 0;
+        let mut escaped = false;
+        for c in chars {
+            match c {
+                '"' if !escaped => {
+                    quotes += 1;
+                    if quotes == 3 {
+                        return;
+                    }
+                }
+                _ => quotes = 0,
+            }
+            escaped = c == '\\';
+        }
+    } else {
+        let mut escaped = false;
+        for c in chars {
+            match c {
+                '"' if !escaped => return,
+                '\\' => escaped = !escaped,
+                _ => escaped = false,
+            }
+        }
+    }
+}
+
+pub struct Context {
+    app: Arc<App>,
+    user: Option<User>,
+    /// The connection all resolvers of a request share, so that a query only
+    /// ever holds one connection of the pool
+    conn: Option<PooledConnection<ConnectionManager<PgConnection>>>,
+}
+
+impl juniper::Context for Context {}
+
+/// A connection used by a resolver, either the one shared by the request or
+/// one checked out of the pool when there is none
+enum ContextConn<'a> {
+    Shared(&'a PgConnection),
+    Checkout(DieselPooledConn<'a>),
+}
+
+impl Deref for ContextConn<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        match self {
+            ContextConn::Shared(conn) => conn,
+            ContextConn::Checkout(conn) => conn,
+        }
+    }
+}
+
+impl Context {
+    fn conn(&self) -> CargoResult<ContextConn<'_>> {
+        match self.conn {
+            Some(ref conn) => Ok(ContextConn::Shared(&**conn)),
+            None => Ok(ContextConn::Checkout(self.app.diesel_database.get()?)),
+        }
+    }
+
+    /// Returns the crate if the current user is allowed to see it
+    fn visible(&self, conn: &PgConnection, krate: Crate) -> CargoResult<Option<CrateObject>> {
+        if krate.is_visible_to(&self.app, conn, self.user.as_ref())? {
+            Ok(Some(CrateObject(krate)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub struct Query;
+
+#[juniper::object(Context = Context)]
+impl Query {
+    /// Look up a single crate by name
+    #[graphql(name = "crate")]
+    fn krate(context: &Context, name: String) -> FieldResult<Option<CrateObject>> {
+        let conn = context.conn()?;
+        let krate = Crate::by_name(&name).first::<Crate>(&*conn).optional()?;
+        match krate {
+            Some(krate) => Ok(context.visible(&conn, krate)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up several crates by name at once. Crates that don't exist are
+    /// left out of the result.
+    fn crates(context: &Context, names: Vec<String>) -> FieldResult<Vec<CrateObject>> {
+        if names.len() > MAX_CRATES_PER_QUERY {
+            return Err(format!(
+                "cannot request more than {} crates at once",
+                MAX_CRATES_PER_QUERY
+            )
+            .into());
+        }
+
+        let conn = context.conn()?;
+        let names = names
+            .iter()
+            .map(|name| name.to_lowercase().replace('-', "_"))
+            .collect::<Vec<_>>();
+        let mut result = Vec::new();
+        for krate in crates::table
+            .select(ALL_COLUMNS)
+            .filter(crate::models::krate::canon_crate_name(crates::name).eq_any(names))
+            .order(crates::name)
+            .load::<Crate>(&*conn)?
+        {
+            result.extend(context.visible(&conn, krate)?);
+        }
+        Ok(result)
+    }
+
+    /// Look up a user by their login
+    fn user(context: &Context, login: String) -> FieldResult<Option<UserObject>> {
+        let conn = context.conn()?;
+        let user = users::table
+            .filter(crate::lower(users::gh_login).eq(login.to_lowercase()))
+            .order(users::id.desc())
+            .first::<User>(&*conn)
+            .optional()?;
+        Ok(user.map(UserObject))
+    }
+
+    /// The currently authenticated user
+    fn me(context: &Context) -> Option<UserObject> {
+        context.user.clone().map(UserObject)
+    }
+}
+
+pub struct CrateObject(Crate);
+
+#[juniper::object(Context = Context, name = "Crate")]
+impl CrateObject {
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.0.description.as_ref().map(|s| &**s)
+    }
+
+    fn homepage(&self) -> Option<&str> {
+        self.0.homepage.as_ref().map(|s| &**s)
+    }
+
+    fn documentation(&self) -> Option<&str> {
+        self.0.documentation.as_ref().map(|s| &**s)
+    }
+
+    fn repository(&self) -> Option<&str> {
+        self.0.repository.as_ref().map(|s| &**s)
+    }
+
+    /// The total number of downloads of all versions of this crate
+    fn downloads(&self) -> i32 {
+        self.0.downloads
+    }
+
+    fn created_at(&self) -> String {
+        rfc3339(self.0.created_at)
+    }
+
+    fn updated_at(&self) -> String {
+        rfc3339(self.0.updated_at)
+    }
+
+    /// The highest version that hasn't been yanked
+    fn max_version(&self) -> String {
+        self.0
+            .max_version
+            .clone()
+            .unwrap_or_else(|| String::from("0.0.0"))
+    }
+
+    /// The highest non-prerelease version that hasn't been yanked
+    fn max_stable_version(&self) -> Option<String> {
+        self.0.max_stable_version.clone()
+    }
+
+    /// All versions of this crate, newest first
+    fn versions(&self, context: &Context) -> FieldResult<Vec<VersionObject>> {
+        let conn = context.conn()?;
+        let mut versions = Version::belonging_to(&self.0).load::<Version>(&*conn)?;
+        versions.sort_by(|a, b| b.num.cmp(&a.num));
+        Ok(versions.into_iter().map(VersionObject).collect())
+    }
+
+    fn owners(&self, context: &Context) -> FieldResult<Vec<OwnerObject>> {
+        let conn = context.conn()?;
+        Ok(self
+            .0
+            .owners(&conn)?
+            .into_iter()
+            .map(|owner| OwnerObject(owner.encodable()))
+            .collect())
+    }
+}
+
+pub struct VersionObject(Version);
+
+#[juniper::object(Context = Context, name = "Version")]
+impl VersionObject {
+    fn num(&self) -> String {
+        self.0.num.to_string()
+    }
+
+    fn downloads(&self) -> i32 {
+        self.0.downloads
+    }
+
+    fn yanked(&self) -> bool {
+        self.0.yanked
+    }
+
+    fn license(&self) -> Option<&str> {
+        self.0.license.as_ref().map(|s| &**s)
+    }
+
+    fn crate_size(&self) -> Option<i32> {
+        self.0.crate_size
+    }
+
+    fn advisory(&self) -> Option<&str> {
+        self.0.advisory.as_ref().map(|s| &**s)
+    }
+
+    fn created_at(&self) -> String {
+        rfc3339(self.0.created_at)
+    }
+
+    /// The daily download counts of the last 90 days
+    fn daily_downloads(&self, context: &Context) -> FieldResult<Vec<DailyDownloads>> {
+        use diesel::dsl::*;
+
+        let conn = context.conn()?;
+        Ok(VersionDownload::belonging_to(&self.0)
+            .filter(version_downloads::date.gt(date(now - 90.days())))
+            .order(version_downloads::date)
+            .load::<VersionDownload>(&*conn)?
+            .into_iter()
+            .map(|download| DailyDownloads {
+                date: download.date.to_string(),
+                downloads: download.downloads,
+            })
+            .collect())
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct DailyDownloads {
+    date: String,
+    downloads: i32,
+}
+
+pub struct OwnerObject(EncodableOwner);
+
+#[juniper::object(Context = Context, name = "Owner")]
+impl OwnerObject {
+    fn login(&self) -> &str {
+        &self.0.login
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.0.name.as_ref().map(|s| &**s)
+    }
+
+    fn avatar(&self) -> Option<&str> {
+        self.0.avatar.as_ref().map(|s| &**s)
+    }
+
+    fn url(&self) -> Option<&str> {
+        self.0.url.as_ref().map(|s| &**s)
+    }
+
+    /// Either `user` or `team`
+    fn kind(&self) -> &str {
+        &self.0.kind
+    }
+}
+
+pub struct UserObject(User);
+
+#[juniper::object(Context = Context, name = "User")]
+impl UserObject {
+    fn login(&self) -> &str {
+        &self.0.gh_login
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.0.name.as_ref().map(|s| &**s)
+    }
+
+    fn avatar(&self) -> Option<&str> {
+        self.0.gh_avatar.as_ref().map(|s| &**s)
+    }
+
+    fn url(&self) -> String {
+        format!("https://github.com/{}", self.0.gh_login)
+    }
+
+    /// The email address of the user, only visible to the user themselves
+    fn email(&self, context: &Context) -> Option<&str> {
+        match context.user {
+            Some(ref user) if user.id == self.0.id => self.0.email.as_ref().map(|s| &**s),
+            _ => None,
+        }
+    }
+
+    /// The crates this user is an owner of
+    fn crates(&self, context: &Context) -> FieldResult<Vec<CrateObject>> {
+        let conn = context.conn()?;
+        let owned = CrateOwner::by_owner_kind(OwnerKind::User)
+            .select(crate_owners::crate_id)
+            .filter(crate_owners::owner_id.eq(self.0.id));
+        let mut result = Vec::new();
+        for krate in crates::table
+            .select(ALL_COLUMNS)
+            .filter(crates::id.eq_any(owned))
+            .order(crates::name)
+            .load::<Crate>(&*conn)?
+        {
+            result.extend(context.visible(&conn, krate)?);
+        }
+        Ok(result)
+    }
+}
+
+fn rfc3339(time: chrono::NaiveDateTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc).to_rfc3339()
+}
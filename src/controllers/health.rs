@@ -0,0 +1,216 @@
+//! Endpoints for load balancers and deployment tooling to check whether the
+//! server is up and able to handle requests
+
+use chrono::Utc;
+use futures::future;
+use hyper::Body;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::prelude::*;
+use crate::async_server::{self, ResponseFuture};
+use crate::db::DieselPool;
+use crate::schema::background_jobs;
+use crate::util::json_response;
+use crate::App;
+
+/// Jobs waiting longer than this to be run for the first time are reported,
+/// as the background worker is down or can't keep up.
+const MAX_QUEUE_LAG_SECONDS: i64 = 10 * 60;
+
+/// How long the result of the storage check is reused, so that frequent
+/// probes of every server don't each make a request to the storage service.
+const STORAGE_CHECK_TTL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref STORAGE_CHECK: Mutex<Option<(Instant, Result<(), String>)>> = Mutex::new(None);
+}
+
+/// The jobs that update the index repository.
+const INDEX_JOB_TYPES: &[&str] = &[
+    "add_crate",
+    "yank",
+    "delete_crate",
+    "update_advisory",
+    "sync_index_visibility",
+];
+
+#[derive(Serialize)]
+struct Liveness {
+    status: &'static str,
+}
+
+/// Handles the `GET /healthz` route.
+///
+/// The server is alive if it responds at all, so this doesn't check any of
+/// the services the server depends on.
+pub fn liveness(req: &mut dyn Request) -> CargoResult<Response> {
+    Ok(req.json(&Liveness { status: "ok" }))
+}
+
+/// Handles the `GET /healthz` route when served by the async HTTP layer, so
+/// that probes are answered even when all conduit handler threads are busy.
+pub fn liveness_async(_: Arc<App>, _: hyper::Request<Body>) -> ResponseFuture {
+    let response = json_response(&Liveness { status: "ok" });
+    Box::new(future::ok(async_server::from_conduit(Ok(response))))
+}
+
+/// Handles the `GET /readyz` route.
+///
+/// Checks the services the server depends on, and responds with `503
+/// Service Unavailable` if the database or the storage of crate files fails.
+///
+/// The state of the background worker is reported as well, but doesn't make
+/// the server unready: it's shared by all servers, so taking them all out of
+/// rotation wouldn't help. The index repository is only accessed by the
+/// background worker, so its check fails if index updates are being retried
+/// rather than connecting to it from every server.
+pub fn readiness(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Serialize)]
+    struct Checks {
+        database: Check,
+        storage: Check,
+        index: Check,
+        background_queue: Check,
+    }
+    #[derive(Serialize)]
+    struct R {
+        status: &'static str,
+        checks: Checks,
+    }
+
+    let app = req.app().clone();
+    let database = Check::run(|| {
+        let conn = req.db_conn()?;
+        diesel::sql_query("SELECT 1").execute(&*conn)?;
+        // Tests share a single connection instead of a pool
+        match &app.diesel_database {
+            DieselPool::Pool(pool) => {
+                let state = pool.state();
+                Ok(Some(json!({
+                    "connections": state.connections,
+                    "idle_connections": state.idle_connections,
+                    "max_size": pool.max_size(),
+                })))
+            }
+            DieselPool::Test(_) => Ok(None),
+        }
+    });
+    let storage = match &app.http_client {
+        Some(client) => Check::run(|| {
+            let mut cached = STORAGE_CHECK.lock().unwrap();
+            let result = match &*cached {
+                Some((checked_at, result)) if checked_at.elapsed() < STORAGE_CHECK_TTL => {
+                    result.clone()
+                }
+                _ => {
+                    let result = app
+                        .config
+                        .uploader
+                        .check_access(client)
+                        .map_err(|e| e.to_string());
+                    *cached = Some((Instant::now(), result.clone()));
+                    result
+                }
+            };
+            result.map_err(|e| human(&e))?;
+            Ok(None)
+        }),
+        // Tests that don't record HTTP requests have no client
+        None => Check::skipped(),
+    };
+    let index = Check::run(|| {
+        let conn = req.db_conn()?;
+        let failing = background_jobs::table
+            .filter(background_jobs::job_type.eq_any(INDEX_JOB_TYPES.to_vec()))
+            .filter(background_jobs::retries.gt(0))
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if failing > 0 {
+            return Err(human(&format_args!(
+                "index updates are failing, failed jobs: {}",
+                failing
+            )));
+        }
+        Ok(None)
+    });
+    let background_queue = Check::run(|| {
+        let conn = req.db_conn()?;
+        let oldest = background_jobs::table
+            .filter(background_jobs::retries.eq(0))
+            .select(diesel::dsl::min(background_jobs::created_at))
+            .first::<Option<chrono::NaiveDateTime>>(&*conn)?;
+        let lag = oldest.map_or(0, |oldest| {
+            (Utc::now().naive_utc() - oldest).num_seconds().max(0)
+        });
+        if lag > MAX_QUEUE_LAG_SECONDS {
+            return Err(human(&format_args!(
+                "the oldest job has waited for {} seconds",
+                lag
+            )));
+        }
+        Ok(Some(json!({ "lag_seconds": lag })))
+    });
+
+    let checks = Checks {
+        database,
+        storage,
+        index,
+        background_queue,
+    };
+    let ready = [&checks.database, &checks.storage]
+        .iter()
+        .all(|check| check.status != "error");
+
+    let mut resp = req.json(&R {
+        status: if ready { "ok" } else { "unavailable" },
+        checks,
+    });
+    if !ready {
+        resp.status = (503, "Service Unavailable");
+    }
+    Ok(resp)
+}
+
+#[derive(Serialize)]
+struct Check {
+    /// `ok`, `error` or `skipped`
+    status: &'static str,
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+impl Check {
+    fn run(f: impl FnOnce() -> CargoResult<Option<serde_json::Value>>) -> Self {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        let duration_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        match result {
+            Ok(details) => Check {
+                status: "ok",
+                duration_ms,
+                error: None,
+                details,
+            },
+            Err(e) => Check {
+                status: "error",
+                duration_ms,
+                error: Some(e.to_string()),
+                details: None,
+            },
+        }
+    }
+
+    fn skipped() -> Self {
+        Check {
+            status: "skipped",
+            duration_ms: 0,
+            error: None,
+            details: None,
+        }
+    }
+}
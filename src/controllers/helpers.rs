@@ -1,9 +1,15 @@
 use crate::util::{json_response, CargoResult};
 use conduit::Response;
 
+pub(crate) mod conditional;
+pub(crate) mod owned_crates;
 pub(crate) mod pagination;
+pub(crate) mod top_crates;
 
+pub(crate) use self::conditional::CacheValidator;
+pub(crate) use self::owned_crates::owned_crates;
 pub(crate) use self::pagination::Paginate;
+pub(crate) use self::top_crates::top_crates;
 
 pub fn ok_true() -> CargoResult<Response> {
     #[derive(Serialize)]
@@ -0,0 +1,134 @@
+//! Conditional requests for read endpoints
+//!
+//! Endpoints compute a `CacheValidator` from the `updated_at` columns of the
+//! rows they show before running any expensive queries. If the client already
+//! has the current response, it gets an empty `304 Not Modified` response
+//! instead.
+
+use chrono::NaiveDateTime;
+use conduit::{Request, Response};
+use std::collections::HashMap;
+use std::io;
+
+use crate::util::request_header;
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone)]
+pub(crate) struct CacheValidator {
+    etag: String,
+    /// `None` if the response can change without `updated_at` changing
+    last_modified: Option<NaiveDateTime>,
+}
+
+impl CacheValidator {
+    pub fn new(updated_at: NaiveDateTime) -> Self {
+        Self {
+            etag: format!("W/\"{}\"", updated_at.timestamp_nanos()),
+            last_modified: Some(updated_at),
+        }
+    }
+
+    /// Adds a value that changes the response without touching `updated_at`,
+    /// like a download count. Responses with such values only have an ETag,
+    /// since their modification time isn't known.
+    pub fn with_state(self, state: i64) -> Self {
+        Self {
+            etag: format!("{}-{}\"", self.etag.trim_end_matches('"'), state),
+            last_modified: None,
+        }
+    }
+
+    /// Returns a `304 Not Modified` response if the client's copy of the
+    /// response is current.
+    pub fn not_modified(&self, req: &dyn Request) -> Option<Response> {
+        if !self.is_fresh(req) {
+            return None;
+        }
+        let mut response = Response {
+            status: (304, "Not Modified"),
+            headers: HashMap::new(),
+            body: Box::new(io::empty()),
+        };
+        self.add_headers(&mut response);
+        Some(response)
+    }
+
+    pub fn add_headers(&self, response: &mut Response) {
+        response
+            .headers
+            .insert("ETag".into(), vec![self.etag.clone()]);
+        if let Some(last_modified) = self.last_modified {
+            response.headers.insert(
+                "Last-Modified".into(),
+                vec![last_modified.format(HTTP_DATE_FORMAT).to_string()],
+            );
+        }
+    }
+
+    /// `If-None-Match` takes precedence over `If-Modified-Since`, as required
+    /// by RFC 7232.
+    fn is_fresh(&self, req: &dyn Request) -> bool {
+        let if_none_match = request_header(req, "If-None-Match");
+        if !if_none_match.is_empty() {
+            return if_none_match
+                .split(',')
+                .any(|etag| etag.trim() == self.etag || etag.trim() == "*");
+        }
+
+        let if_modified_since = request_header(req, "If-Modified-Since");
+        match self.last_modified {
+            Some(last_modified) if !if_modified_since.is_empty() => {
+                NaiveDateTime::parse_from_str(if_modified_since, HTTP_DATE_FORMAT)
+                    // HTTP dates only have a precision of seconds
+                    .map(|since| last_modified.timestamp() <= since.timestamp())
+                    .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use conduit::Method;
+    use conduit_test::MockRequest;
+
+    fn updated_at() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2019, 11, 28).and_hms_micro(12, 30, 15, 250)
+    }
+
+    #[test]
+    fn matching_etags_are_not_modified() {
+        let validator = CacheValidator::new(updated_at()).with_state(42);
+        let mut req = MockRequest::new(Method::Get, "/");
+        assert!(validator.not_modified(&req).is_none());
+
+        req.header("If-None-Match", &validator.etag);
+        let response = validator.not_modified(&req).unwrap();
+        assert_eq!(response.status.0, 304);
+
+        let other = CacheValidator::new(updated_at()).with_state(43);
+        assert!(other.not_modified(&req).is_none());
+    }
+
+    #[test]
+    fn if_modified_since_is_only_used_without_state() {
+        let mut req = MockRequest::new(Method::Get, "/");
+        req.header("If-Modified-Since", "Thu, 28 Nov 2019 12:30:15 GMT");
+        assert!(CacheValidator::new(updated_at())
+            .not_modified(&req)
+            .is_some());
+        assert!(CacheValidator::new(updated_at())
+            .with_state(42)
+            .not_modified(&req)
+            .is_none());
+
+        let mut req = MockRequest::new(Method::Get, "/");
+        req.header("If-Modified-Since", "Thu, 28 Nov 2019 12:30:14 GMT");
+        assert!(CacheValidator::new(updated_at())
+            .not_modified(&req)
+            .is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::{any, sql};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+
+use crate::models::OwnerKind;
+use crate::schema::{crate_owners, crates, recent_crate_downloads, versions};
+use crate::views::EncodableDashboardCrate;
+
+/// Returns the crates a user or team owns, sorted by name, along with their
+/// latest version, downloads and number of versions with advisories.
+pub(crate) fn owned_crates(
+    conn: &PgConnection,
+    owner_id: i32,
+    owner_kind: OwnerKind,
+) -> QueryResult<Vec<EncodableDashboardCrate>> {
+    let crates = crate_owners::table
+        .inner_join(crates::table.left_join(recent_crate_downloads::table))
+        .filter(crate_owners::owner_id.eq(owner_id))
+        .filter(crate_owners::owner_kind.eq(owner_kind as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .select((
+            crates::id,
+            crates::name,
+            crates::max_version,
+            crates::downloads,
+            recent_crate_downloads::downloads.nullable(),
+            crates::updated_at,
+        ))
+        .order(crates::name.asc())
+        .load::<(i32, String, Option<String>, i32, Option<i64>, NaiveDateTime)>(conn)?;
+
+    let crate_ids = crates.iter().map(|krate| krate.0).collect::<Vec<_>>();
+    let advisories = versions::table
+        .filter(versions::crate_id.eq(any(&crate_ids)))
+        .filter(versions::advisory.is_not_null())
+        .filter(versions::yanked.eq(false))
+        .group_by(versions::crate_id)
+        .select((versions::crate_id, sql::<BigInt>("COUNT(*)")))
+        .load::<(i32, i64)>(conn)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    Ok(crates
+        .into_iter()
+        .map(
+            |(id, name, max_version, downloads, recent_downloads, updated_at)| {
+                EncodableDashboardCrate {
+                    id,
+                    name,
+                    max_version,
+                    downloads,
+                    recent_downloads: recent_downloads.unwrap_or(0),
+                    advisories: advisories.get(&id).cloned().unwrap_or(0),
+                    updated_at,
+                }
+            },
+        )
+        .collect())
+}
@@ -6,19 +6,36 @@ use diesel::query_builder::*;
 use diesel::query_dsl::LoadQuery;
 use diesel::sql_types::BigInt;
 use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) enum Page {
     Numeric(u32),
+    /// Keyset pagination, continuing after the record the cursor was created
+    /// from. An empty cursor starts at the first record.
+    Seek(String),
     Unspecified,
 }
 
 impl Page {
-    fn new(params: &IndexMap<String, String>) -> CargoResult<Self> {
+    fn new(params: &IndexMap<String, String>, enable_seek: bool) -> CargoResult<Self> {
+        if let Some(cursor) = params.get("seek") {
+            if !enable_seek {
+                return Err(ErrorCode::InvalidParameter
+                    .human("seek pagination is not supported by this endpoint"));
+            }
+            if params.contains_key("page") {
+                return Err(
+                    ErrorCode::InvalidParameter.human("only one of `page` and `seek` can be given")
+                );
+            }
+            return Ok(Page::Seek(cursor.clone()));
+        }
+
         if let Some(s) = params.get("page") {
             let numeric_page = s.parse()?;
             if numeric_page < 1 {
-                return Err(human(&format_args!(
+                return Err(ErrorCode::InvalidParameter.human(&format_args!(
                     "page indexing starts from 1, page {} is invalid",
                     numeric_page,
                 )));
@@ -31,7 +48,7 @@ impl Page {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) struct PaginationOptions {
     page: Page,
     pub(crate) per_page: u32,
@@ -39,6 +56,16 @@ pub(crate) struct PaginationOptions {
 
 impl PaginationOptions {
     pub(crate) fn new(params: &IndexMap<String, String>) -> CargoResult<Self> {
+        Self::build(params, false)
+    }
+
+    /// Like `new`, but also accepts a `seek` cursor instead of a page number.
+    /// Callers are responsible for filtering their query with `seek_after`.
+    pub(crate) fn new_with_seek(params: &IndexMap<String, String>) -> CargoResult<Self> {
+        Self::build(params, true)
+    }
+
+    fn build(params: &IndexMap<String, String>, enable_seek: bool) -> CargoResult<Self> {
         const DEFAULT_PER_PAGE: u32 = 10;
         const MAX_PER_PAGE: u32 = 100;
 
@@ -48,18 +75,61 @@ impl PaginationOptions {
             .unwrap_or(Ok(DEFAULT_PER_PAGE))?;
 
         if per_page > MAX_PER_PAGE {
-            return Err(human(&format_args!(
+            return Err(ErrorCode::InvalidParameter.human(&format_args!(
                 "cannot request more than {} items",
                 MAX_PER_PAGE,
             )));
         }
 
         Ok(Self {
-            page: Page::new(params)?,
+            page: Page::new(params, enable_seek)?,
             per_page,
         })
     }
 
+    pub(crate) fn is_seek(&self) -> bool {
+        match self.page {
+            Page::Seek(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Decodes the `seek` cursor into the sort key of the last record of the
+    /// previous page. Returns `None` if seek pagination wasn't requested, or
+    /// if this is the first page.
+    pub(crate) fn seek_after<K: DeserializeOwned>(&self) -> CargoResult<Option<K>> {
+        match self.page {
+            Page::Seek(ref cursor) if !cursor.is_empty() => {
+                let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+                    .map_err(|_| ErrorCode::InvalidParameter.human("invalid seek cursor"))?;
+                let key = serde_json::from_slice(&json)
+                    .map_err(|_| ErrorCode::InvalidParameter.human("invalid seek cursor"))?;
+                Ok(Some(key))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the query parameters for the page after the current one, given
+    /// the number of records on the current page and the sort key of the last
+    /// one. Returns `None` if this is the last page.
+    pub(crate) fn next_seek_params<K: Serialize>(
+        &self,
+        records: usize,
+        last_key: Option<K>,
+    ) -> Option<IndexMap<String, String>> {
+        if records < self.per_page as usize {
+            return None;
+        }
+        let json = serde_json::to_vec(&last_key?).ok()?;
+        let mut opts = IndexMap::new();
+        opts.insert(
+            "seek".into(),
+            base64::encode_config(&json, base64::URL_SAFE_NO_PAD),
+        );
+        Some(opts)
+    }
+
     pub(crate) fn offset(&self) -> Option<u32> {
         if let Page::Numeric(p) = self.page {
             Some((p - 1) * self.per_page)
@@ -76,6 +146,16 @@ pub(crate) trait Paginate: Sized {
             options: PaginationOptions::new(params)?,
         })
     }
+
+    /// Like `paginate`, but also accepts a `seek` cursor. The query must
+    /// already be filtered to the records after
+    /// `PaginationOptions::seek_after`.
+    fn paginate_with_options(self, options: PaginationOptions) -> PaginatedQuery<Self> {
+        PaginatedQuery {
+            query: self,
+            options,
+        }
+    }
 }
 
 impl<T> Paginate for T {}
@@ -86,7 +166,12 @@ pub struct Paginated<T> {
 }
 
 impl<T> Paginated<T> {
+    /// The total number of records. This isn't known when using seek
+    /// pagination, since the query only includes records after the cursor.
     pub(crate) fn total(&self) -> Option<i64> {
+        if self.options.is_seek() {
+            return None;
+        }
         Some(
             self.records_and_total
                 .get(0)
@@ -95,7 +180,13 @@ impl<T> Paginated<T> {
         )
     }
 
-    pub(crate) fn next_page_params(&self) -> Option<IndexMap<String, String>> {
+    /// Returns the query parameters of the next page. With seek pagination,
+    /// `seek_key` is used to build the cursor from the last record.
+    pub(crate) fn next_page_params<K, F>(&self, seek_key: F) -> Option<IndexMap<String, String>>
+    where
+        K: Serialize,
+        F: FnOnce(&T) -> K,
+    {
         if self.records_and_total.len() < self.options.per_page as usize {
             return None;
         }
@@ -104,19 +195,30 @@ impl<T> Paginated<T> {
         match self.options.page {
             Page::Numeric(n) => opts.insert("page".into(), (n + 1).to_string()),
             Page::Unspecified => opts.insert("page".into(), 2.to_string()),
+            Page::Seek(_) => {
+                let last = self
+                    .records_and_total
+                    .last()
+                    .map(|row| seek_key(&row.record));
+                return self
+                    .options
+                    .next_seek_params(self.records_and_total.len(), last);
+            }
         };
         Some(opts)
     }
 
+    /// Returns the query parameters of the previous page. Seek pagination
+    /// only goes forward, so this is always `None` for it.
     pub(crate) fn prev_page_params(&self) -> Option<IndexMap<String, String>> {
-        if let Page::Numeric(1) | Page::Unspecified = self.options.page {
+        if let Page::Numeric(1) | Page::Unspecified | Page::Seek(_) = self.options.page {
             return None;
         }
 
         let mut opts = IndexMap::new();
         match self.options.page {
             Page::Numeric(n) => opts.insert("page".into(), (n - 1).to_string()),
-            Page::Unspecified => unreachable!(),
+            Page::Unspecified | Page::Seek(_) => unreachable!(),
         };
         Some(opts)
     }
@@ -146,7 +248,7 @@ impl<T> PaginatedQuery<T> {
     where
         Self: LoadQuery<PgConnection, WithCount<U>>,
     {
-        let options = self.options;
+        let options = self.options.clone();
         let records_and_total = self.internal_load(conn)?;
         Ok(Paginated {
             records_and_total,
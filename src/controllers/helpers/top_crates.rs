@@ -0,0 +1,65 @@
+use chrono::{Datelike, Duration, Utc};
+use diesel::dsl::any;
+use diesel::prelude::*;
+
+use crate::models::krate::ALL_COLUMNS;
+use crate::models::{Crate, CrateDownloadRollup};
+use crate::schema::crates;
+use crate::util::CargoResult;
+use crate::views::{EncodableCrate, EncodableTopCrate, EncodableTopCrates};
+
+/// The number of crates in each list.
+const TOP_CRATES: i64 = 10;
+/// The number of weeks, including the current one, whose downloads are
+/// counted.
+const DOWNLOAD_WEEKS: i64 = 12;
+
+/// Returns the most downloaded and most recently updated of the given crates.
+///
+/// Downloads are taken from the weekly totals of the `rollup_downloads`
+/// background job, so the current week may lag behind the daily counts.
+pub(crate) fn top_crates(
+    conn: &PgConnection,
+    crate_ids: &[i32],
+) -> CargoResult<EncodableTopCrates> {
+    let today = Utc::today().naive_utc();
+    let since = today
+        - Duration::days(i64::from(today.weekday().num_days_from_monday()))
+        - Duration::weeks(DOWNLOAD_WEEKS - 1);
+
+    let most_downloaded = CrateDownloadRollup::most_downloaded(conn, crate_ids, since, TOP_CRATES)?;
+    let ids = most_downloaded
+        .iter()
+        .map(|&(id, _)| id)
+        .collect::<Vec<_>>();
+    let mut krates = crates::table
+        .filter(crates::id.eq(any(&ids)))
+        .select(ALL_COLUMNS)
+        .load::<Crate>(conn)?;
+    krates.sort_by_key(|krate| ids.iter().position(|&id| id == krate.id));
+    let most_downloaded = encode_crates(krates)
+        .into_iter()
+        .zip(most_downloaded)
+        .map(|(krate, (_, downloads))| EncodableTopCrate { krate, downloads })
+        .collect();
+
+    let recently_updated = crates::table
+        .filter(crates::id.eq(any(crate_ids)))
+        .order(crates::updated_at.desc())
+        .select(ALL_COLUMNS)
+        .limit(TOP_CRATES)
+        .load::<Crate>(conn)?;
+
+    Ok(EncodableTopCrates {
+        most_downloaded,
+        recently_updated: encode_crates(recently_updated),
+        downloads_since: since.to_string(),
+    })
+}
+
+fn encode_crates(krates: Vec<Crate>) -> Vec<EncodableCrate> {
+    krates
+        .into_iter()
+        .map(|krate| krate.minimal_encodable(None, false, None))
+        .collect()
+}
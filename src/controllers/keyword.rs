@@ -1,7 +1,8 @@
 use super::prelude::*;
 
-use crate::controllers::helpers::Paginate;
-use crate::models::Keyword;
+use crate::controllers::helpers::{top_crates, Paginate};
+use crate::models::{FollowTarget, Keyword, TopicFollow, Visibility};
+use crate::schema::topic_follows;
 use crate::views::EncodableKeyword;
 
 /// Handles the `GET /keywords` route.
@@ -40,7 +41,48 @@ pub fn index(req: &mut dyn Request) -> CargoResult<Response> {
     }))
 }
 
+/// The default number of keywords returned by `suggest`.
+const DEFAULT_SUGGESTIONS: i64 = 10;
+
+/// The maximum number of keywords returned by `suggest`.
+const MAX_SUGGESTIONS: i64 = 50;
+
+/// Handles the `GET /keywords/suggest` route.
+///
+/// Returns existing keywords starting with the `q` query parameter, ordered by
+/// the number of crates using them, followed by keywords that are spelled
+/// similarly. The number of results can be adjusted with the `limit`
+/// parameter.
+pub fn suggest(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let query = req.query();
+    let prefix = query.get("q").map(|s| s.trim()).unwrap_or("");
+    let limit = query
+        .get("limit")
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_SUGGESTIONS)
+        .max(1)
+        .min(MAX_SUGGESTIONS);
+
+    let keywords = if prefix.is_empty() {
+        Vec::new()
+    } else {
+        Keyword::suggestions(&conn, prefix, limit)?
+            .into_iter()
+            .map(Keyword::encodable)
+            .collect()
+    };
+
+    #[derive(Serialize)]
+    struct R {
+        keywords: Vec<EncodableKeyword>,
+    }
+    Ok(req.json(&R { keywords }))
+}
+
 /// Handles the `GET /keywords/:keyword_id` route.
+///
+/// Aliases are resolved to the keyword they stand for.
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     let name = &req.params()["keyword_id"];
     let conn = req.db_conn()?;
@@ -55,3 +97,72 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
         keyword: kw.encodable(),
     }))
 }
+
+/// Handles the `GET /keywords/:keyword_id/top` route.
+///
+/// Returns the public crates with the keyword that had the most downloads in
+/// the last 12 weeks, and the ones that were updated most recently.
+pub fn top(req: &mut dyn Request) -> CargoResult<Response> {
+    use crate::schema::{crates, crates_keywords};
+
+    let name = &req.params()["keyword_id"];
+    let conn = req.db_conn()?;
+
+    let kw = Keyword::find_by_keyword(&conn, name)?;
+    let crate_ids = crates_keywords::table
+        .inner_join(crates::table)
+        .filter(crates_keywords::keyword_id.eq(kw.id))
+        .filter(crates::visibility.eq(Visibility::Public))
+        .select(crates_keywords::crate_id)
+        .load::<i32>(&*conn)?;
+
+    Ok(req.json(&top_crates(&conn, &crate_ids)?))
+}
+
+fn follow_target(req: &dyn Request, conn: &PgConnection) -> CargoResult<TopicFollow> {
+    let user = req.user()?;
+    let target = Keyword::find_by_keyword(conn, &req.params()["keyword_id"])?;
+    Ok(TopicFollow {
+        user_id: user.id,
+        kind: FollowTarget::Keyword,
+        target_id: target.id,
+    })
+}
+
+/// Handles the `PUT /keywords/:keyword_id/follow` route.
+pub fn follow(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    diesel::insert_into(topic_follows::table)
+        .values(&follow)
+        .on_conflict_do_nothing()
+        .execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `DELETE /keywords/:keyword_id/follow` route.
+pub fn unfollow(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    diesel::delete(&follow).execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `GET /keywords/:keyword_id/following` route.
+pub fn following(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::associations::Identifiable;
+    use diesel::dsl::exists;
+
+    let conn = req.db_conn()?;
+    let follow = follow_target(req, &conn)?;
+    let following =
+        diesel::select(exists(topic_follows::table.find(follow.id()))).get_result(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        following: bool,
+    }
+    Ok(req.json(&R { following }))
+}
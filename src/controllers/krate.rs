@@ -1,6 +1,14 @@
+pub mod access;
+pub mod availability;
+pub mod claim;
+pub mod delete;
 pub mod downloads;
 pub mod follow;
 pub mod metadata;
 pub mod owners;
 pub mod publish;
+pub mod publish_session;
+pub mod report;
+pub mod repository;
 pub mod search;
+pub mod successor;
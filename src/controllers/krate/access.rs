@@ -0,0 +1,244 @@
+//! Endpoints for managing the visibility of a crate and who can access it
+//!
+//! Private crates are hidden from search and return 404s for their detail and
+//! download endpoints unless the requesting user is an owner of the crate or
+//! has been granted access. The git index is shared by everyone and cannot be
+//! filtered per token, so private crates are left out of it, see
+//! `PrivateIndexEntry`. Their index files are only served through
+//! `index_file`, which applies the same rules to the user the API token
+//! belongs to.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use serde_json;
+use swirl::Job;
+
+use crate::app::App;
+use crate::controllers::prelude::*;
+use crate::git;
+use crate::models::{Crate, CrateAcl, PrivateIndexEntry, Rights, User, Visibility};
+use crate::schema::{crate_acls, crates, users};
+use crate::util::errors::NotFound;
+use crate::util::CargoError;
+use crate::views::EncodablePublicUser;
+
+/// Handles the `GET /crates/:crate_id/access` route.
+pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+    if user.rights(app, &conn, &krate.owners(&conn)?)? == Rights::None {
+        return Err(
+            ErrorCode::OwnerMismatch.human("only owners can view who has access to a crate")
+        );
+    }
+
+    let users = CrateAcl::belonging_to(&krate)
+        .inner_join(users::table)
+        .select(users::all_columns)
+        .order(users::gh_login)
+        .load::<User>(&*conn)?
+        .into_iter()
+        .map(User::encodable_public)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        visibility: Visibility,
+        users: Vec<EncodablePublicUser>,
+    }
+    Ok(req.json(&R {
+        visibility: krate.visibility,
+        users,
+    }))
+}
+
+/// Handles the `PUT /crates/:crate_id/access` route.
+pub fn grant(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_access(req, true)
+}
+
+/// Handles the `DELETE /crates/:crate_id/access` route.
+pub fn revoke(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_access(req, false)
+}
+
+/// Handles the `PUT /crates/:crate_id/visibility` route.
+///
+/// The request body has the format `{"visibility": "public" | "private"}`.
+///
+/// Crates waiting for the approval of the registry administrators keep
+/// their visibility until they are approved.
+pub fn update_visibility(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        visibility: Visibility,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    if request.visibility == Visibility::Pending {
+        return Err(ErrorCode::InvalidField.human("the visibility must be `public` or `private`"));
+    }
+
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        ensure_full_rights(app, &conn, user, &krate)?;
+        if krate.visibility == Visibility::Pending {
+            return Err(ErrorCode::ApprovalRequired
+                .human("the crate is waiting for the approval of the registry administrators"));
+        }
+
+        diesel::update(&krate)
+            .set(crates::visibility.eq(request.visibility))
+            .execute(&*conn)?;
+        if krate.visibility != request.visibility {
+            git::sync_index_visibility(krate.id)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+
+        Ok(())
+    })?;
+
+    // Responses of a public crate are cached for everyone
+    app.response_cache.invalidate_crate(crate_name);
+    ok_true()
+}
+
+/// Handles the `GET /crates/:crate_id/index` route.
+///
+/// Returns the index file of the crate, as found in `Config::index_repository`
+/// or in `PrivateIndexEntry` for private crates, if the crate is visible to
+/// the user the request is authenticated as. Crates that aren't are reported
+/// as missing, like everywhere else.
+pub fn index_file(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let content = if krate.visibility == Visibility::Private {
+        PrivateIndexEntry::index_file(&conn, &krate)?.into_bytes()
+    } else {
+        let repository = match req.app().config.index_repository {
+            Some(ref path) => git2::Repository::open(path)?,
+            None => return Err(Box::new(NotFound)),
+        };
+        let path = git::index_file_path(&krate.name);
+        match repository
+            .head()?
+            .peel_to_tree()?
+            .get_path(Path::new(&path))
+        {
+            Ok(entry) => entry
+                .to_object(&repository)?
+                .peel_to_blob()?
+                .content()
+                .to_vec(),
+            Err(_) => Vec::new(),
+        }
+    };
+    // Crates without any version in the index don't have a file yet
+    if content.is_empty() {
+        return Err(Box::new(NotFound));
+    }
+
+    let mut headers = HashMap::new();
+    headers.insert(
+        "Content-Type".to_string(),
+        vec!["text/plain; charset=utf-8".to_string()],
+    );
+    headers.insert(
+        "Content-Length".to_string(),
+        vec![content.len().to_string()],
+    );
+    // Visibility depends on who is asking, so shared caches mustn't keep it
+    headers.insert("Cache-Control".to_string(), vec!["private".to_string()]);
+    Ok(Response {
+        status: (200, "OK"),
+        headers,
+        body: Box::new(io::Cursor::new(content)),
+    })
+}
+
+/// Parse the JSON request body of requests to modify the access list of a
+/// crate. The format is
+///
+///     {"users": ["username", ...]}
+fn parse_access_request(req: &mut dyn Request) -> CargoResult<Vec<String>> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        users: Vec<String>,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    Ok(request.users)
+}
+
+fn modify_access(req: &mut dyn Request, grant: bool) -> CargoResult<Response> {
+    let logins = parse_access_request(req)?;
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        ensure_full_rights(app, &conn, user, &krate)?;
+
+        for login in &logins {
+            let target = users::table
+                .filter(users::gh_login.eq(login))
+                .first::<User>(&*conn)
+                .map_err(|_| {
+                    ErrorCode::NotFound
+                        .human(&format_args!("could not find user with login `{}`", login))
+                })?;
+
+            if grant {
+                diesel::insert_into(crate_acls::table)
+                    .values(&CrateAcl {
+                        crate_id: krate.id,
+                        user_id: target.id,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(&*conn)?;
+            } else {
+                diesel::delete(crate_acls::table.find((krate.id, target.id))).execute(&*conn)?;
+            }
+        }
+
+        ok_true()
+    })
+}
+
+fn ensure_full_rights(
+    app: &App,
+    conn: &PgConnection,
+    user: &User,
+    krate: &Crate,
+) -> CargoResult<()> {
+    match user.rights(app, conn, &krate.owners(conn)?)? {
+        Rights::Full => Ok(()),
+        Rights::Publish => Err(ErrorCode::OwnerMismatch
+            .human("team members don't have permission to modify access to a crate")),
+        Rights::None => Err(ErrorCode::OwnerMismatch
+            .human("only owners have permission to modify access to a crate")),
+    }
+}
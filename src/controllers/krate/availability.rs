@@ -0,0 +1,89 @@
+//! Endpoint for checking whether a crate name can be published
+//!
+//! This lets tooling find out whether `cargo publish` would be rejected
+//! because of the name before building and uploading the crate.
+
+use chrono::NaiveDateTime;
+
+use crate::app::App;
+use crate::controllers::prelude::*;
+use crate::models::{deleted_crate, reserved_name, Crate, CrateRename, Namespace};
+use crate::util::rfc3339;
+
+/// Handles the `GET /crates/:crate_id/availability` route.
+///
+/// The name is checked against the rules for crate names, the names of
+/// existing crates and the old names of renamed crates, reserved names and the
+/// tombstones of deleted crates, in that order. Namespaced names are invalid
+/// unless namespaces are enabled and the namespace exists. Names that only
+/// differ from another one in case or in `-` and `_` count as the same name.
+/// The first check that fails is reported in `reason`, which is one of
+/// `invalid`, `taken`, `reserved` or `tombstoned`.
+pub fn availability(req: &mut dyn Request) -> CargoResult<Response> {
+    let name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let mut detail = None;
+    let mut available_at = None;
+    let invalid = match Crate::invalid_name_reason(name) {
+        Some(rule) => Some(rule),
+        None => unusable_namespace(req.app(), &conn, name)?,
+    };
+    let reason = if let Some(rule) = invalid {
+        detail = Some(rule);
+        Some("invalid")
+    } else if Crate::by_name(name)
+        .first::<Crate>(&*conn)
+        .optional()?
+        .is_some()
+        || CrateRename::renamed_crate(&conn, name)?.is_some()
+    {
+        Some("taken")
+    } else if reserved_name::is_reserved(&conn, name)? {
+        Some("reserved")
+    } else if let Some(at) = deleted_crate::tombstoned_until(&conn, name)? {
+        available_at = Some(at);
+        Some("tombstoned")
+    } else {
+        None
+    };
+
+    #[derive(Serialize)]
+    struct R<'a> {
+        name: &'a str,
+        available: bool,
+        reason: Option<&'static str>,
+        detail: Option<String>,
+        #[serde(with = "rfc3339::option")]
+        available_at: Option<NaiveDateTime>,
+    }
+    Ok(req.json(&R {
+        name,
+        available: reason.is_none(),
+        reason,
+        detail,
+        available_at,
+    }))
+}
+
+/// Why the namespace of a namespaced crate name can't be published in, or
+/// `None` if it can or the name isn't namespaced.
+fn unusable_namespace(app: &App, conn: &PgConnection, name: &str) -> QueryResult<Option<String>> {
+    use diesel::dsl::exists;
+
+    let namespace = match Crate::split_namespace(name) {
+        Some((namespace, _)) => namespace,
+        None => return Ok(None),
+    };
+    if !app.config.crate_namespaces {
+        return Ok(Some(
+            "namespaced crate names aren't enabled on this registry".into(),
+        ));
+    }
+    let found = diesel::select(exists(Namespace::by_name(namespace))).get_result::<bool>(conn)?;
+    if found {
+        Ok(None)
+    } else {
+        Ok(Some(format!("namespace `{}` doesn't exist", namespace)))
+    }
+}
@@ -0,0 +1,196 @@
+//! Endpoints for users to claim the names of abandoned or squatted crates
+//!
+//! The owners of the crate are emailed about the claim and can agree to give
+//! up the crate or object to the claim. The registry administrators decide on
+//! claims at `PUT /admin/claims/:claim_id/approve` and
+//! `PUT /admin/claims/:claim_id/reject`.
+
+use serde_json;
+
+use crate::controllers::prelude::*;
+use crate::email;
+use crate::models::name_claim::{self, HOLDING_PERIOD_DAYS};
+use crate::models::{ClaimStatus, Crate, NameClaim, NewNameClaim, Rights};
+use crate::schema::{name_claims, users};
+use crate::views::EncodableNameClaim;
+
+/// The maximum length of the reason for a claim and of responses, in
+/// characters.
+const MAX_TEXT_LENGTH: usize = 5000;
+
+fn validate_text<'a>(text: &'a str, field: &str) -> CargoResult<&'a str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ErrorCode::InvalidField.human(&format_args!("a {} is required", field)));
+    }
+    if text.chars().count() > MAX_TEXT_LENGTH {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "the {} must not be longer than {} characters",
+            field, MAX_TEXT_LENGTH
+        )));
+    }
+    Ok(text)
+}
+
+/// Handles the `POST /crates/:crate_id/claims` route.
+///
+/// The request body has the format
+///
+///     {"reason": "..."}
+pub fn claim(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        reason: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    let reason = validate_text(&request.reason, "reason for the claim")?;
+
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let (krate, claim) = conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        if user.rights(app, &conn, &krate.owners(&conn)?)? > Rights::None {
+            return Err(ErrorCode::AlreadyExists.human("you already own this crate"));
+        }
+
+        let open_claim = NameClaim::belonging_to(&krate)
+            .filter(name_claims::claimant_id.eq(user.id))
+            .load::<NameClaim>(&*conn)?
+            .iter()
+            .any(NameClaim::is_open);
+        if open_claim {
+            return Err(ErrorCode::AlreadyExists.human("you have already claimed this crate"));
+        }
+
+        let claim = NewNameClaim {
+            crate_id: krate.id,
+            claimant_id: user.id,
+            reason,
+        }
+        .create(&conn)?;
+        Ok((krate, claim))
+    })?;
+
+    // Failing to email one of the owners shouldn't fail the claim
+    for owner_email in name_claim::owner_emails(&conn, krate.id)? {
+        let _ = email::send_name_claim_email(
+            &conn,
+            &owner_email,
+            &krate.name,
+            &user.gh_login,
+            &claim.reason,
+            HOLDING_PERIOD_DAYS,
+        );
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        claim: EncodableNameClaim,
+    }
+    Ok(req.json(&R {
+        claim: claim.encodable(krate.name, user.gh_login.clone()),
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/claims` route.
+///
+/// Only the owners of the crate can see the claims for it.
+pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if user.rights(app, &conn, &krate.owners(&conn)?)? < Rights::Full {
+        return Err(ErrorCode::OwnerMismatch.human("only owners can see the claims for a crate"));
+    }
+
+    let claims = NameClaim::belonging_to(&krate)
+        .inner_join(users::table)
+        .select((name_claims::all_columns, users::gh_login))
+        .order(name_claims::created_at.desc())
+        .load::<(NameClaim, String)>(&*conn)?
+        .into_iter()
+        .map(|(claim, claimant)| claim.encodable(krate.name.clone(), claimant))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        claims: Vec<EncodableNameClaim>,
+    }
+    Ok(req.json(&R { claims }))
+}
+
+/// Handles the `PUT /crates/:crate_id/claims/:claim_id` route.
+///
+/// The request body has the format
+///
+///     {"release": true | false, "response": "..."}
+///
+/// Owners can change their response until the claim has been decided on.
+pub fn respond(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        release: bool,
+        response: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+    let response = validate_text(&request.response, "response")?;
+
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let claim_id = req.params()["claim_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid claim id"))?;
+    let conn = req.db_conn()?;
+
+    let (krate, claim) = conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        if user.rights(app, &conn, &krate.owners(&conn)?)? < Rights::Full {
+            return Err(ErrorCode::OwnerMismatch.human("only owners can respond to claims"));
+        }
+
+        let claim = NameClaim::belonging_to(&krate)
+            .filter(name_claims::id.eq(claim_id))
+            .first::<NameClaim>(&*conn)?;
+        if !claim.is_open() {
+            return Err(ErrorCode::Conflict.human("this claim has already been decided on"));
+        }
+
+        let status = if request.release {
+            ClaimStatus::Released
+        } else {
+            ClaimStatus::Contested
+        };
+        let claim = diesel::update(&claim)
+            .set((
+                name_claims::status.eq(status),
+                name_claims::response.eq(response),
+                name_claims::responded_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .get_result::<NameClaim>(&*conn)?;
+        Ok((krate, claim))
+    })?;
+
+    if let Some(claimant_email) = claim.claimant_email(&conn)? {
+        let _ = email::send_name_claim_response_email(
+            &conn,
+            &claimant_email,
+            &krate.name,
+            request.release,
+        );
+    }
+
+    ok_true()
+}
@@ -0,0 +1,98 @@
+//! Endpoint for owners to delete their crates
+
+use diesel::dsl::exists;
+use swirl::Job;
+
+use crate::controllers::prelude::*;
+use crate::git;
+use crate::models::deleted_crate::MAX_DOWNLOADS;
+use crate::models::{Crate, CrateRename, DeletedCrate, Rights, Version};
+use crate::schema::{crates, dependencies, versions};
+use crate::storage;
+use crate::util::CargoError;
+
+/// Handles the `DELETE /crates/:crate_id` route.
+///
+/// Deletes the crate with all of its versions, removes it from the index and
+/// deletes its files. This is only allowed for crates that no other crate
+/// depends on, with at most `MAX_DOWNLOADS` downloads. The name can be
+/// published again after `Config::deleted_name_cooldown`.
+pub fn delete(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let app = req.app();
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+    let owners = krate.owners(&conn)?;
+    match user.rights(app, &conn, &owners)? {
+        Rights::Full => {}
+        Rights::Publish => {
+            return Err(ErrorCode::OwnerMismatch
+                .human("team members don't have permission to delete crates"));
+        }
+        Rights::None => {
+            return Err(ErrorCode::OwnerMismatch.human("must already be an owner to delete a crate"))
+        }
+    }
+
+    if krate.downloads > MAX_DOWNLOADS {
+        return Err(ErrorCode::Conflict.human(&format_args!(
+            "crates with more than {} downloads can't be deleted, \
+             yank their versions instead",
+            MAX_DOWNLOADS
+        )));
+    }
+
+    conn.transaction(|| {
+        // Locking the crate blocks dependencies on it from being inserted
+        // until the crate is deleted, so that a dependent published in the
+        // meantime isn't missed. Yanked versions of other crates count as
+        // well, since they can still be in lock files.
+        crates::table
+            .find(krate.id)
+            .select(crates::id)
+            .for_update()
+            .first::<i32>(&*conn)?;
+        let has_dependents = diesel::select(exists(
+            dependencies::table
+                .inner_join(versions::table)
+                .filter(dependencies::crate_id.eq(krate.id))
+                .filter(versions::crate_id.ne(krate.id)),
+        ))
+        .get_result::<bool>(&*conn)?;
+        if has_dependents {
+            return Err(ErrorCode::Conflict.human(
+                "crates that other crates depend on can't be deleted, \
+                 yank their versions instead",
+            ));
+        }
+
+        // The versions published before a rename are stored under the old
+        // names of the crate
+        let mut files = Vec::new();
+        for version in Version::belonging_to(&krate).load::<Version>(&*conn)? {
+            let name = CrateRename::published_name(&conn, &krate, &version)?;
+            files.push((name, version.num.to_string()));
+        }
+        let old_names = CrateRename::old_names(&conn, &krate)?;
+
+        DeletedCrate::delete(&conn, &krate, user.id, app.config.deleted_name_cooldown)?;
+
+        for name in old_names.into_iter().chain(Some(krate.name.clone())) {
+            git::delete_crate(name)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+        for (name, num) in files {
+            storage::delete_version_files(name, num)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+        Ok(())
+    })?;
+
+    app.response_cache.invalidate_crate(&krate.name);
+
+    ok_true()
+}
@@ -3,13 +3,24 @@
 //! The enpoints for download a crate and exposing version specific
 //! download counts are located in `krate::downloads`.
 
+use chrono::{Datelike, NaiveDateTime, Utc};
 use std::cmp;
 
+use crate::controllers::helpers::CacheValidator;
 use crate::controllers::prelude::*;
 
-use crate::models::{Crate, CrateVersions, Version, VersionDownload};
-use crate::schema::version_downloads;
-use crate::views::EncodableVersionDownload;
+use crate::models::{
+    Crate, CrateDownloadRollup, CrateVersions, Version, VersionAdoption, VersionDownload,
+};
+use crate::schema::{
+    crate_download_rollups, version_adoption, version_download_clients, version_downloads, versions,
+};
+use crate::util::errors::NotFound;
+use crate::util::rfc3339;
+use crate::views::{
+    EncodableDownloadRollup, EncodableSeriesAdoption, EncodableVersionAdoption,
+    EncodableVersionDownload,
+};
 
 use crate::models::krate::to_char;
 
@@ -19,8 +30,19 @@ pub fn downloads(req: &mut dyn Request) -> CargoResult<Response> {
     use diesel::sql_types::BigInt;
 
     let crate_name = &req.params()["crate_id"];
-    let conn = req.db_conn()?;
+    let conn = req.read_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    // The last 90 days are shown, so the response changes every day
+    let validator = CacheValidator::new(krate.updated_at)
+        .with_state(krate.downloads.into())
+        .with_state(Utc::today().num_days_from_ce().into());
+    if let Some(response) = validator.not_modified(req) {
+        return Ok(response);
+    }
 
     let mut versions = krate.all_versions().load::<Version>(&*conn)?;
     versions.sort_by(|a, b| b.num.cmp(&a.num));
@@ -62,8 +84,194 @@ pub fn downloads(req: &mut dyn Request) -> CargoResult<Response> {
     let meta = Meta {
         extra_downloads: extra,
     };
-    Ok(req.json(&R {
+    let mut response = req.json(&R {
         version_downloads: downloads,
         meta,
+    });
+    validator.add_headers(&mut response);
+    Ok(response)
+}
+
+/// Handles the `GET /crates/:crate_id/downloads/summary` route.
+///
+/// Returns the download totals of the last 52 weeks and the last 24 months,
+/// oldest first. These are computed by the `rollup_downloads` background job,
+/// so the current week and month may lag behind the daily counts.
+pub fn summary(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.read_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let rollups = |period: &str, limit: i64| -> CargoResult<Vec<EncodableDownloadRollup>> {
+        let mut rows = CrateDownloadRollup::belonging_to(&krate)
+            .filter(crate_download_rollups::period.eq(period))
+            .order(crate_download_rollups::period_start.desc())
+            .limit(limit)
+            .load::<CrateDownloadRollup>(&*conn)?;
+        rows.reverse();
+        Ok(rows
+            .into_iter()
+            .map(CrateDownloadRollup::encodable)
+            .collect())
+    };
+
+    #[derive(Serialize)]
+    struct R {
+        weekly: Vec<EncodableDownloadRollup>,
+        monthly: Vec<EncodableDownloadRollup>,
+    }
+    Ok(req.json(&R {
+        weekly: rollups("week", 52)?,
+        monthly: rollups("month", 24)?,
     }))
 }
+
+/// Handles the `GET /crates/:crate_id/downloads/clients` route.
+///
+/// Returns the downloads of the last 90 days broken down by the minor version
+/// of cargo and by the target they were made from, most downloads first.
+pub fn clients(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::*;
+    use diesel::sql_types::BigInt;
+
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.read_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let sum_downloads = sql::<BigInt>("SUM(version_download_clients.downloads)");
+    let recent_downloads = version_download_clients::table
+        .inner_join(versions::table)
+        .filter(versions::crate_id.eq(krate.id))
+        .filter(version_download_clients::date.gt(date(now - 90.days())));
+
+    let cargo_versions = recent_downloads
+        .clone()
+        .select((
+            version_download_clients::cargo_version,
+            sum_downloads.clone(),
+        ))
+        .group_by(version_download_clients::cargo_version)
+        .order((
+            sum_downloads.clone().desc(),
+            version_download_clients::cargo_version,
+        ))
+        .load::<ClientDownloads>(&*conn)?;
+
+    let targets = recent_downloads
+        .select((version_download_clients::target, sum_downloads.clone()))
+        .group_by(version_download_clients::target)
+        .order((sum_downloads.desc(), version_download_clients::target))
+        .load::<ClientDownloads>(&*conn)?;
+
+    #[derive(Serialize, Queryable)]
+    struct ClientDownloads {
+        name: String,
+        downloads: i64,
+    }
+    #[derive(Serialize)]
+    struct R {
+        cargo_versions: Vec<ClientDownloads>,
+        targets: Vec<ClientDownloads>,
+    }
+    Ok(req.json(&R {
+        cargo_versions,
+        targets,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/version_adoption` route.
+///
+/// Returns the downloads of the last 90 days broken down by version and by
+/// semver compatible series, newest first, so that maintainers can tell how
+/// many users are still on old versions before dropping support for them.
+/// These are computed daily by the `update_version_adoption` background job.
+pub fn version_adoption(req: &mut dyn Request) -> CargoResult<Response> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.read_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let mut rows = VersionAdoption::belonging_to(&krate)
+        .inner_join(versions::table)
+        .select((version_adoption::all_columns, versions::num))
+        .load::<(VersionAdoption, String)>(&*conn)?
+        .into_iter()
+        .filter_map(|(adoption, num)| {
+            semver::Version::parse(&num)
+                .ok()
+                .map(|version| (version, adoption, num))
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let computed_at = rows.iter().map(|row| row.1.computed_at).max();
+    let mut series = Vec::<EncodableSeriesAdoption>::new();
+    for (version, adoption, _) in &rows {
+        let name = compatible_series(version);
+        match series.last_mut() {
+            Some(last) if last.series == name => {
+                last.downloads += adoption.downloads;
+                last.share += adoption.share;
+            }
+            _ => series.push(EncodableSeriesAdoption {
+                series: name,
+                downloads: adoption.downloads,
+                share: adoption.share,
+            }),
+        }
+    }
+    let versions = rows
+        .into_iter()
+        .map(|(_, adoption, num)| adoption.encodable(num))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        versions: Vec<EncodableVersionAdoption>,
+        series: Vec<EncodableSeriesAdoption>,
+        #[serde(with = "rfc3339::option")]
+        computed_at: Option<NaiveDateTime>,
+    }
+    Ok(req.json(&R {
+        versions,
+        series,
+        computed_at,
+    }))
+}
+
+/// The semver compatible series of a version: its major version, or the
+/// leading `0.x` or `0.0.x` of versions before 1.0.
+fn compatible_series(version: &semver::Version) -> String {
+    if version.major > 0 {
+        version.major.to_string()
+    } else if version.minor > 0 {
+        format!("0.{}", version.minor)
+    } else {
+        format!("0.0.{}", version.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compatible_series;
+
+    fn series(num: &str) -> String {
+        compatible_series(&semver::Version::parse(num).unwrap())
+    }
+
+    #[test]
+    fn versions_are_grouped_by_semver_compatibility() {
+        assert_eq!(series("2.1.3"), "2");
+        assert_eq!(series("1.0.0-beta.1"), "1");
+        assert_eq!(series("0.3.7"), "0.3");
+        assert_eq!(series("0.0.4"), "0.0.4");
+    }
+}
@@ -6,16 +6,18 @@ use crate::controllers::prelude::*;
 use crate::db::DieselPooledConn;
 use crate::models::{Crate, Follow};
 use crate::schema::*;
+use crate::util::errors::NotFound;
 
 fn follow_target(req: &dyn Request, conn: &DieselPooledConn<'_>) -> CargoResult<Follow> {
     let user = req.user()?;
     let crate_name = &req.params()["crate_id"];
-    let crate_id = Crate::by_name(crate_name)
-        .select(crates::id)
-        .first(&**conn)?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&**conn)?;
+    if !krate.is_visible_to(req.app(), conn, Some(user))? {
+        return Err(Box::new(NotFound));
+    }
     Ok(Follow {
         user_id: user.id,
-        crate_id,
+        crate_id: krate.id,
     })
 }
 
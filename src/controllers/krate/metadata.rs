@@ -2,66 +2,70 @@
 //!
 //! These endpoints provide data that could be obtained direclty from the
 //! index or cached metadata which was extracted (client side) from the
-//! `Cargo.toml` file.
+//! `Cargo.toml` file. Owners can also update the crate level metadata
+//! without publishing a new version.
 
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use swirl::Job;
+
+use crate::controllers::helpers::pagination::PaginationOptions;
+use crate::controllers::helpers::CacheValidator;
 use crate::controllers::prelude::*;
 use crate::models::{
-    Category, Crate, CrateCategory, CrateKeyword, CrateVersions, Keyword, RecentCrateDownloads,
-    User, Version,
+    Category, Crate, CrateAction, CrateCategory, CrateFunding, CrateKeyword, CrateLinks,
+    CrateMetadata, CrateVersions, Keyword, NewCrateOwnerAction, RecentCrateDownloads,
+    ReverseDependency, Rights, User, Version, Visibility,
 };
+use crate::render;
+use crate::response_cache::ResponseCache;
 use crate::schema::*;
+use crate::telemetry;
+use crate::util::errors::NotFound;
+use crate::util::CargoError;
+use crate::views::krate_publish::{EncodableCategoryList, EncodableKeywordList};
 use crate::views::{
-    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
+    EncodableCategory, EncodableCrate, EncodableCrateFunding, EncodableDependency,
+    EncodableKeyword, EncodableVersion, GoodCrate, PublishWarnings,
 };
 
 use crate::models::krate::ALL_COLUMNS;
 
 /// Handles the `GET /summary` route.
+///
+/// The crates listed in each section are taken from the `summary_crates`
+/// materialized view, which is refreshed by the `refresh_summary` background
+/// job. Only public crates are counted and listed.
 pub fn summary(req: &mut dyn Request) -> CargoResult<Response> {
-    use crate::schema::crates::dsl::*;
+    let cache = &req.app().response_cache;
+    if let Some(response) = cache.get(&ResponseCache::summary_key()) {
+        return Ok(response);
+    }
 
     let conn = req.db_conn()?;
-    let num_crates = crates.count().get_result(&*conn)?;
+    let num_crates = crates::table
+        .filter(crates::visibility.eq(Visibility::Public))
+        .count()
+        .get_result(&*conn)?;
     let num_downloads = metadata::table
         .select(metadata::total_downloads)
         .get_result(&*conn)?;
 
-    let encode_crates = |krates: Vec<Crate>| -> CargoResult<Vec<_>> {
-        let versions = krates.versions().load::<Version>(&*conn)?;
-        versions
-            .grouped_by(&krates)
-            .into_iter()
-            .map(|versions| Version::max(versions.into_iter().map(|v| v.num)))
-            .zip(krates)
-            .map(|(max_version, krate)| {
-                Ok(krate.minimal_encodable(&max_version, None, false, None))
-            })
-            .collect()
-    };
-
-    let new_crates = crates
-        .order(created_at.desc())
-        .select(ALL_COLUMNS)
-        .limit(10)
-        .load(&*conn)?;
-    let just_updated = crates
-        .filter(updated_at.ne(created_at))
-        .order(updated_at.desc())
-        .select(ALL_COLUMNS)
-        .limit(10)
-        .load(&*conn)?;
-    let most_downloaded = crates
-        .order(downloads.desc())
-        .select(ALL_COLUMNS)
-        .limit(10)
-        .load(&*conn)?;
+    let krates = summary_crates::table
+        .inner_join(crates::table)
+        .order((summary_crates::section, summary_crates::rank))
+        .select((summary_crates::section, ALL_COLUMNS))
+        .load::<(String, Crate)>(&*conn)?;
 
-    let most_recently_downloaded = crates
-        .inner_join(recent_crate_downloads::table)
-        .order(recent_crate_downloads::downloads.desc())
-        .select(ALL_COLUMNS)
-        .limit(10)
-        .load(&*conn)?;
+    let mut crates_by_section = HashMap::<_, Vec<_>>::new();
+    for (section, krate) in krates {
+        crates_by_section
+            .entry(section)
+            .or_default()
+            .push(krate.minimal_encodable(None, false, None));
+    }
+    let mut section = |name: &str| crates_by_section.remove(name).unwrap_or_default();
 
     let popular_keywords = keywords::table
         .order(keywords::crates_cnt.desc())
@@ -84,26 +88,58 @@ pub fn summary(req: &mut dyn Request) -> CargoResult<Response> {
         most_downloaded: Vec<EncodableCrate>,
         most_recently_downloaded: Vec<EncodableCrate>,
         just_updated: Vec<EncodableCrate>,
+        trending: Vec<EncodableCrate>,
         popular_keywords: Vec<EncodableKeyword>,
         popular_categories: Vec<EncodableCategory>,
     }
-    Ok(req.json(&R {
-        num_downloads,
-        num_crates,
-        new_crates: encode_crates(new_crates)?,
-        most_downloaded: encode_crates(most_downloaded)?,
-        most_recently_downloaded: encode_crates(most_recently_downloaded)?,
-        just_updated: encode_crates(just_updated)?,
-        popular_keywords,
-        popular_categories,
-    }))
+    Ok(cache.insert(
+        &ResponseCache::summary_key(),
+        &R {
+            num_downloads,
+            num_crates,
+            new_crates: section("new"),
+            most_downloaded: section("most_downloaded"),
+            most_recently_downloaded: section("most_recently_downloaded"),
+            just_updated: section("just_updated"),
+            trending: section("trending"),
+            popular_keywords,
+            popular_categories,
+        },
+    ))
 }
 
 /// Handles the `GET /crates/:crate_id` route.
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     let name = &req.params()["crate_id"];
-    let conn = req.db_conn()?;
+    let conn = req.read_conn()?;
     let krate = Crate::by_name(name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let recent_downloads = RecentCrateDownloads::belonging_to(&krate)
+        .select(recent_crate_downloads::downloads)
+        .get_result::<i64>(&*conn)
+        .optional()?;
+    let validator = CacheValidator::new(krate.updated_at)
+        .with_state(krate.downloads.into())
+        .with_state(recent_downloads.unwrap_or(0));
+    if let Some(response) = validator.not_modified(req) {
+        return Ok(response);
+    }
+
+    // Private crates are shown to their owners only, so they aren't cached
+    let cache = &req.app().response_cache;
+    let cache_key = if krate.visibility == Visibility::Public {
+        Some(ResponseCache::crate_key(&krate.name))
+    } else {
+        None
+    };
+    // Cached responses may be older than the validator, so they are served
+    // without it
+    if let Some(response) = cache_key.as_ref().and_then(|key| cache.get(key)) {
+        return Ok(response);
+    }
 
     let mut versions_and_publishers: Vec<(Version, Option<User>)> = krate
         .all_versions()
@@ -121,16 +157,12 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
         .inner_join(categories::table)
         .select(categories::all_columns)
         .load(&*conn)?;
-    let recent_downloads = RecentCrateDownloads::belonging_to(&krate)
-        .select(recent_crate_downloads::downloads)
-        .get_result(&*conn)
-        .optional()?;
-
     let badges = badges::table
         .filter(badges::crate_id.eq(krate.id))
         .load(&*conn)?;
-    let max_version = krate.max_version(&conn)?;
-
+    let funding = CrateFunding::belonging_to(&krate)
+        .order(crate_funding::platform)
+        .load::<CrateFunding>(&*conn)?;
     #[derive(Serialize)]
     struct R {
         #[serde(rename = "crate")]
@@ -138,10 +170,10 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
         versions: Vec<EncodableVersion>,
         keywords: Vec<EncodableKeyword>,
         categories: Vec<EncodableCategory>,
+        funding: Vec<EncodableCrateFunding>,
     }
-    Ok(req.json(&R {
+    let body = R {
         krate: krate.clone().encodable(
-            &max_version,
             Some(ids),
             Some(&kws),
             Some(&cats),
@@ -155,10 +187,21 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
             .collect(),
         keywords: kws.into_iter().map(Keyword::encodable).collect(),
         categories: cats.into_iter().map(Category::encodable).collect(),
-    }))
+        funding: funding.into_iter().map(CrateFunding::encodable).collect(),
+    };
+    let mut response = match cache_key {
+        Some(key) => cache.insert(&key, &body),
+        None => req.json(&body),
+    };
+    validator.add_headers(&mut response);
+    Ok(response)
 }
 
 /// Handles the `GET /crates/:crate_id/:version/readme` route.
+///
+/// Readmes whose rendered HTML is stored in the database are served directly,
+/// with an `ETag` that clients can use to revalidate their copy. Other readmes
+/// are redirected to the uploader.
 pub fn readme(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let version = &req.params()["version"];
@@ -174,10 +217,53 @@ pub fn readme(req: &mut dyn Request) -> CargoResult<Response> {
         struct R {
             url: String,
         }
-        Ok(req.json(&R { url: redirect_url }))
-    } else {
-        Ok(req.redirect(redirect_url))
+        return Ok(req.json(&R { url: redirect_url }));
+    }
+
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
     }
+
+    let html = readme_renderings::table
+        .inner_join(versions::table)
+        .filter(versions::crate_id.eq(krate.id))
+        .filter(versions::num.eq(version))
+        .select(readme_renderings::html)
+        .first::<Option<String>>(&*conn)
+        .optional()?;
+    let html = match html {
+        Some(Some(html)) => html,
+        _ => return Ok(req.redirect(redirect_url)),
+    };
+
+    let etag = format!("\"{}\"", hex::encode(openssl::sha::sha256(html.as_bytes())));
+    let mut headers = HashMap::new();
+    headers.insert("ETag".to_string(), vec![etag.clone()]);
+
+    let not_modified = req
+        .headers()
+        .find("If-None-Match")
+        .map_or(false, |values| values.iter().any(|value| *value == etag));
+    if not_modified {
+        return Ok(Response {
+            status: (304, "Not Modified"),
+            headers,
+            body: Box::new(io::empty()),
+        });
+    }
+
+    headers.insert(
+        "Content-Type".to_string(),
+        vec!["text/html; charset=utf-8".to_string()],
+    );
+    headers.insert("Content-Length".to_string(), vec![html.len().to_string()]);
+    Ok(Response {
+        status: (200, "OK"),
+        headers,
+        body: Box::new(io::Cursor::new(html.into_bytes())),
+    })
 }
 
 /// Handles the `GET /crates/:crate_id/versions` route.
@@ -187,6 +273,9 @@ pub fn versions(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
     let mut versions_and_publishers: Vec<(Version, Option<User>)> = krate
         .all_versions()
         .left_outer_join(users::table)
@@ -206,13 +295,42 @@ pub fn versions(req: &mut dyn Request) -> CargoResult<Response> {
 }
 
 /// Handles the `GET /crates/:crate_id/reverse_dependencies` route.
+///
+/// Supports both `page` and `seek` pagination. With `seek`, the cursor of the
+/// next page is returned in `meta.next_page`.
+///
+/// Only the latest non-yanked version of each dependent crate is considered.
+/// Passing `matching=1.2.3` only returns the dependents whose version
+/// requirement is satisfied by version `1.2.3` of this crate.
 pub fn reverse_dependencies(req: &mut dyn Request) -> CargoResult<Response> {
     use diesel::dsl::any;
 
     let name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(name).first::<Crate>(&*conn)?;
-    let (rev_deps, total) = krate.reverse_dependencies(&*conn, &req.query())?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+    let params = req.query();
+    let options = PaginationOptions::new_with_seek(&params)?;
+    let matching = match params.get("matching") {
+        Some(version) => Some(semver::Version::parse(version).map_err(|_| {
+            ErrorCode::InvalidVersion
+                .bad_request(&format_args!("invalid version `{}` in `matching`", version))
+        })?),
+        None => None,
+    };
+    let (rev_deps, total) = krate.reverse_dependencies(&*conn, &options, matching.as_ref())?;
+    let next_page = if options.is_seek() {
+        options
+            .next_seek_params(
+                rev_deps.len(),
+                rev_deps.last().map(ReverseDependency::seek_key),
+            )
+            .map(|p| req.query_with_params(p))
+    } else {
+        None
+    };
     let rev_deps: Vec<_> = rev_deps
         .into_iter()
         .map(|dep| dep.encodable(&krate.name))
@@ -243,10 +361,160 @@ pub fn reverse_dependencies(req: &mut dyn Request) -> CargoResult<Response> {
     #[derive(Serialize)]
     struct Meta {
         total: i64,
+        next_page: Option<String>,
     }
     Ok(req.json(&R {
         dependencies: rev_deps,
         versions,
-        meta: Meta { total },
+        meta: Meta { total, next_page },
+    }))
+}
+
+/// Deserializes a field that is present in the request, to tell a `null`
+/// value apart from a missing field.
+fn present<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    T::deserialize(d).map(Some)
+}
+
+/// Handles the `PATCH /crates/:crate_id` route.
+///
+/// Updates the metadata of a crate without publishing a new version, so that
+/// fixing a typo doesn't require a release. The request body is like
+///
+///     {"crate": {"description": "...", "repository": null, "keywords": ["cli"]}}
+///
+/// Fields that are left out aren't changed, and `null` clears a URL. The
+/// `links` object, with optional `ci`, `chat`, `sponsor` and `changelog`
+/// URLs, replaces all links of the crate, and the `funding` object, mapping
+/// funding platforms to URLs, replaces all funding of the crate. The
+/// fields are validated like those of published versions. A new `readme`
+/// (with an optional `readme_file` name) is rendered for the newest version
+/// of the crate. Every update is recorded in the `crate_owner_actions` table.
+pub fn update(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Changes {
+        description: Option<String>,
+        #[serde(default, deserialize_with = "present")]
+        homepage: Option<Option<String>>,
+        #[serde(default, deserialize_with = "present")]
+        documentation: Option<Option<String>>,
+        #[serde(default, deserialize_with = "present")]
+        repository: Option<Option<String>>,
+        readme: Option<String>,
+        readme_file: Option<String>,
+        keywords: Option<EncodableKeywordList>,
+        categories: Option<EncodableCategoryList>,
+        links: Option<CrateLinks>,
+        funding: Option<BTreeMap<String, String>>,
+    }
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(rename = "crate")]
+        krate: Changes,
+    }
+    let changes = serde_json::from_str::<Request>(&body)
+        .map_err(|e| ErrorCode::InvalidJson.human(&format_args!("invalid update request: {}", e)))?
+        .krate;
+
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let url =
+        |url: &Option<Option<String>>| url.as_ref().map(|url| url.as_ref().map(String::as_str));
+    let metadata = CrateMetadata {
+        description: changes.description.as_ref().map(String::as_str),
+        homepage: url(&changes.homepage),
+        documentation: url(&changes.documentation),
+        repository: url(&changes.repository),
+        readme: changes.readme.as_ref().map(String::as_str),
+        links: changes.links.as_ref(),
+    };
+
+    let (krate, invalid_categories) = conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        if user.rights(app, &conn, &krate.owners(&conn)?)? < Rights::Publish {
+            return Err(
+                ErrorCode::OwnerMismatch.human("only owners can update the metadata of a crate")
+            );
+        }
+        if krate.is_locked(&conn)? {
+            return Err(ErrorCode::CrateLocked.human(&format_args!(
+                "crate `{}` has been locked by the registry administrators, \
+                 its metadata cannot be changed",
+                krate.name
+            )));
+        }
+
+        let mut changed = metadata.changed_fields();
+        let krate = metadata.update(&conn, krate.id)?;
+        if let Some(ref keywords) = changes.keywords {
+            let keywords = keywords.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            Keyword::update_crate(&conn, &krate, &keywords)?;
+            changed.push("keywords");
+        }
+        let mut invalid_categories = Vec::new();
+        if let Some(ref categories) = changes.categories {
+            let categories = categories.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            invalid_categories = Category::update_crate(&conn, &krate, &categories)?;
+            changed.push("categories");
+        }
+        if let Some(ref funding) = changes.funding {
+            CrateFunding::update_crate(&conn, &krate, funding)?;
+            changed.push("funding");
+        }
+        if changed.is_empty() {
+            return Err(human("no metadata to update"));
+        }
+
+        if let Some(ref readme) = changes.readme {
+            let version_id = versions::table
+                .filter(versions::crate_id.eq(krate.id))
+                .filter(versions::num.nullable().eq(&krate.max_version))
+                .select(versions::id)
+                .first::<i32>(&*conn)
+                .optional()?;
+            if let Some(version_id) = version_id {
+                let readme_file = changes
+                    .readme_file
+                    .clone()
+                    .unwrap_or_else(|| String::from("README.md"));
+                render::render_and_upload_readme(
+                    version_id,
+                    readme.clone(),
+                    readme_file,
+                    krate.repository.clone(),
+                    telemetry::current(),
+                )
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+            }
+        }
+
+        NewCrateOwnerAction {
+            crate_id: krate.id,
+            owner_id: user.id,
+            action: CrateAction::UpdateMetadata,
+            detail: Some(&changed.join(", ")),
+        }
+        .record(&conn)?;
+        Ok((krate, invalid_categories))
+    })?;
+
+    app.response_cache.invalidate_crate(&krate.name);
+    Ok(req.json(&GoodCrate {
+        krate: krate.minimal_encodable(None, false, None),
+        warnings: PublishWarnings {
+            invalid_categories,
+            invalid_badges: Vec::new(),
+            other: Vec::new(),
+        },
     }))
 }
@@ -1,16 +1,32 @@
 //! All routes related to managing owners of a crate
+//!
+//! Owners can remove themselves and teams right away. Removing another user
+//! has to be confirmed by a third owner for crates with more than
+//! `CONFIRMATION_THRESHOLD` individual owners, and otherwise takes effect
+//! after `Config::owner_removal_delay`. Either way, the owners are emailed
+//! and any of them can cancel the removal until it takes effect.
 
+use chrono::{Duration, Utc};
 use serde_json;
 
 use crate::controllers::prelude::*;
-use crate::models::{Crate, Owner, Rights, Team, User};
-use crate::views::EncodableOwner;
+use crate::email;
+use crate::models::name_claim;
+use crate::models::pending_owner_removal::CONFIRMATION_THRESHOLD;
+use crate::models::{Crate, Owner, PendingOwnerRemoval, Rights, Team, User};
+use crate::schema::pending_owner_removals;
+use crate::util::errors::NotFound;
+use crate::util::CargoError;
+use crate::views::{EncodableOwner, EncodablePendingOwnerRemoval};
 
 /// Handles the `GET /crates/:crate_id/owners` route.
 pub fn owners(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
     let owners = krate
         .owners(&conn)?
         .into_iter()
@@ -29,6 +45,9 @@ pub fn owner_team(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
     let owners = Team::owning(&krate, &conn)?
         .into_iter()
         .map(Owner::encodable)
@@ -46,6 +65,9 @@ pub fn owner_user(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
     let owners = User::owning(&krate, &conn)?
         .into_iter()
         .map(Owner::encodable)
@@ -81,12 +103,12 @@ fn parse_owners_request(req: &mut dyn Request) -> CargoResult<Vec<String>> {
         users: Option<Vec<String>>,
         owners: Option<Vec<String>>,
     }
-    let request: Request =
-        serde_json::from_str(&body).map_err(|_| human("invalid json request"))?;
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
     request
         .owners
         .or(request.users)
-        .ok_or_else(|| human("invalid json request"))
+        .ok_or_else(|| ErrorCode::InvalidJson.human("invalid json request"))
 }
 
 fn modify_owners(req: &mut dyn Request, add: bool) -> CargoResult<Response> {
@@ -96,45 +118,104 @@ fn modify_owners(req: &mut dyn Request, add: bool) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
 
-    conn.transaction(|| {
+    let (response, krate, requested) = conn.transaction(|| {
         let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
         let owners = krate.owners(&conn)?;
 
-        match user.rights(app, &owners)? {
+        match user.rights(app, &conn, &owners)? {
             Rights::Full => {}
             // Yes!
             Rights::Publish => {
-                return Err(human("team members don't have permission to modify owners"));
+                return Err(ErrorCode::OwnerMismatch
+                    .human("team members don't have permission to modify owners"));
             }
             Rights::None => {
-                return Err(human("only owners have permission to modify owners"));
+                return Err(
+                    ErrorCode::OwnerMismatch.human("only owners have permission to modify owners")
+                );
             }
         }
 
+        let mut requested = Vec::new();
         let comma_sep_msg = if add {
             let mut msgs = Vec::new();
             for login in &logins {
                 let login_test =
                     |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
                 if owners.iter().any(login_test) {
-                    return Err(human(&format_args!("`{}` is already an owner", login)));
+                    return Err(ErrorCode::AlreadyExists
+                        .human(&format_args!("`{}` is already an owner", login)));
                 }
                 let msg = krate.owner_add(app, &conn, user, login)?;
                 msgs.push(msg);
             }
             msgs.join(",")
         } else {
+            let user_owners = User::owning(&krate, &conn)?;
+            let effective_at = if user_owners.len() > CONFIRMATION_THRESHOLD {
+                None
+            } else {
+                let delay = Duration::from_std(app.config.owner_removal_delay)
+                    .expect("the owner removal delay is out of range");
+                Some(Utc::now().naive_utc() + delay)
+            };
+            let mut removed = PendingOwnerRemoval::belonging_to(&krate)
+                .select(pending_owner_removals::owner_id)
+                .load::<i32>(&*conn)?;
+
+            let mut msgs = Vec::new();
             for login in &logins {
-                krate.owner_remove(app, &conn, user, login)?;
+                let owner = Owner::find_or_create_by_login(app, &conn, user, login)?;
+                match owner {
+                    Owner::User(ref other_user) if other_user.id != user.id => {
+                        if !user_owners.iter().any(|o| o.id() == other_user.id) {
+                            return Err(ErrorCode::NotFound
+                                .human(&format_args!("`{}` is not an owner", login)));
+                        }
+                        let removal = PendingOwnerRemoval::request(
+                            &conn,
+                            krate.id,
+                            other_user.id,
+                            user.id,
+                            effective_at,
+                        )?
+                        .ok_or_else(|| {
+                            ErrorCode::AlreadyExists.human(&format_args!(
+                                "the removal of `{}` is already pending",
+                                login
+                            ))
+                        })?;
+                        msgs.push(match removal.effective_at {
+                            Some(effective_at) => format!(
+                                "{} will be removed as an owner on {} UTC \
+                                 unless the removal is cancelled",
+                                login,
+                                effective_at.format("%Y-%m-%d %H:%M")
+                            ),
+                            None => format!(
+                                "the removal of {} has to be confirmed by another owner",
+                                login
+                            ),
+                        });
+                        removed.push(other_user.id);
+                        requested.push((other_user.gh_login.clone(), removal));
+                    }
+                    _ => krate.remove_owner(&conn, &owner)?,
+                }
             }
-            if User::owning(&krate, &conn)?.is_empty() {
-                return Err(human(
-                    "cannot remove all individual owners of a crate. \
-                     Team member don't have permission to modify owners, so \
-                     at least one individual owner is required.",
-                ));
+
+            let remaining = User::owning(&krate, &conn)?
+                .iter()
+                .filter(|o| !removed.contains(&o.id()))
+                .count();
+            if remaining == 0 {
+                return Err(cannot_remove_all_owners());
+            }
+            if msgs.is_empty() {
+                "owners successfully removed".to_owned()
+            } else {
+                msgs.join(",")
             }
-            "owners successfully removed".to_owned()
         };
 
         #[derive(Serialize)]
@@ -142,9 +223,123 @@ fn modify_owners(req: &mut dyn Request, add: bool) -> CargoResult<Response> {
             ok: bool,
             msg: String,
         }
-        Ok(req.json(&R {
+        let response = req.json(&R {
             ok: true,
             msg: comma_sep_msg,
-        }))
-    })
+        });
+        Ok((response, krate, requested))
+    })?;
+
+    if !requested.is_empty() {
+        let emails = name_claim::owner_emails(&conn, krate.id).unwrap_or_default();
+        for (login, removal) in requested {
+            let effective_at = removal
+                .effective_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string());
+            for address in &emails {
+                let _ = email::send_owner_removal_email(
+                    &conn,
+                    address,
+                    &krate.name,
+                    &user.gh_login,
+                    &login,
+                    effective_at.as_ref().map(String::as_str),
+                );
+            }
+        }
+    }
+
+    app.response_cache.invalidate_crate(crate_name);
+    Ok(response)
+}
+
+fn cannot_remove_all_owners() -> Box<dyn CargoError> {
+    ErrorCode::Conflict.human(
+        "cannot remove all individual owners of a crate. \
+         Team member don't have permission to modify owners, so \
+         at least one individual owner is required.",
+    )
+}
+
+/// Handles the `GET /crates/:crate_id/owner_removals` route.
+pub fn owner_removals(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if user.rights(req.app(), &conn, &krate.owners(&conn)?)? < Rights::Full {
+        return Err(ErrorCode::OwnerMismatch.human("only owners can see pending owner removals"));
+    }
+
+    let removals = PendingOwnerRemoval::belonging_to(&krate)
+        .order(pending_owner_removals::id)
+        .load::<PendingOwnerRemoval>(&*conn)?
+        .into_iter()
+        .map(|removal| {
+            let (owner, requested_by) = removal.logins(&conn)?;
+            Ok(removal.encodable(owner, requested_by))
+        })
+        .collect::<QueryResult<_>>()?;
+
+    #[derive(Serialize)]
+    struct R {
+        owner_removals: Vec<EncodablePendingOwnerRemoval>,
+    }
+    Ok(req.json(&R {
+        owner_removals: removals,
+    }))
+}
+
+/// Handles the `PUT /crates/:crate_id/owner_removals/:removal_id/confirm`
+/// route.
+///
+/// Removals can be confirmed by any owner other than the one who requested
+/// them, including the owner being removed, and take effect right away.
+pub fn confirm_owner_removal(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_owner_removal(req, true)
+}
+
+/// Handles the `DELETE /crates/:crate_id/owner_removals/:removal_id` route.
+pub fn cancel_owner_removal(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_owner_removal(req, false)
+}
+
+fn modify_owner_removal(req: &mut dyn Request, confirm: bool) -> CargoResult<Response> {
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let removal_id = req.params()["removal_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid removal id"))?;
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        if user.rights(app, &conn, &krate.owners(&conn)?)? < Rights::Full {
+            return Err(
+                ErrorCode::OwnerMismatch.human("only owners can confirm or cancel owner removals")
+            );
+        }
+        let removal = PendingOwnerRemoval::belonging_to(&krate)
+            .filter(pending_owner_removals::id.eq(removal_id))
+            .first::<PendingOwnerRemoval>(&*conn)?;
+
+        if confirm {
+            if removal.requested_by == user.id {
+                return Err(
+                    ErrorCode::Conflict.human("removals have to be confirmed by another owner")
+                );
+            }
+            if !removal.leaves_owners(&conn)? {
+                return Err(cannot_remove_all_owners());
+            }
+            removal.apply(&conn)?;
+        } else {
+            removal.cancel(&conn)?;
+        }
+        Ok(())
+    })?;
+
+    app.response_cache.invalidate_crate(crate_name);
+    ok_true()
 }
@@ -4,19 +4,47 @@ use hex::ToHex;
 use std::sync::Arc;
 use swirl::Job;
 
+use crate::config::{DependencyValidation, TarballScan, TyposquatCheck};
 use crate::controllers::prelude::*;
+use crate::db;
 use crate::git;
 use crate::models::dependency;
-use crate::models::{Badge, Category, Keyword, NewCrate, NewVersion, Rights, User};
+use crate::models::{deleted_crate, reserved_name};
+use crate::models::{
+    Badge, Category, Crate, CrateFunding, CrateRename, Keyword, Namespace, NewCrate, NewVersion,
+    PendingCrate, Rights, User, VersionChecksum, VersionFile, VersionProvenance, Visibility,
+};
 use crate::render;
-use crate::util::{read_fill, read_le_u32};
+use crate::scan;
+use crate::tasks;
+use crate::telemetry::{self, Span, SpanKind};
+use crate::typosquat;
+use crate::uploaders::Uploader;
+use crate::util::{read_fill, read_le_u32, request_header};
 use crate::util::{CargoError, ChainError, Maximums};
-use crate::views::{EncodableCrateUpload, GoodCrate, PublishWarnings};
+use crate::views::{EncodableCrateUpload, GoodCrate, PublishDryRun, PublishWarnings};
 
 /// Handles the `PUT /crates/new` route.
 /// Used by `cargo publish` to publish a new crate or to publish a new version of an
 /// existing crate.
 ///
+/// CI systems can state where the crate was published from with the
+/// `X-CI-Provider`, `X-CI-Commit-SHA` and optional `X-CI-Run-URL` headers.
+/// They are recorded as the provenance of the version, and the
+/// `verify_provenance` job checks that the commit exists in the repository
+/// of the crate.
+///
+/// New crates with namespaced names like `org::name` are owned by the team of
+/// the namespace as well, see `Namespace`.
+///
+/// If `Config::new_crate_approval` is set, new crates are held back from the
+/// index and only visible to their owners until the registry administrators
+/// approved them, see `PendingCrate`.
+///
+/// With `?dry_run=true`, the crate is validated as if it was published and
+/// the entry it would get in the index is returned, but nothing is persisted
+/// or uploaded.
+///
 /// Currently blocks the HTTP thread, perhaps some function calls can spawn new
 /// threads and return completion or error through other methods  a `cargo publish
 /// --status` command, via crates.io's front end, or email.
@@ -36,21 +64,30 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
     // - Then the .crate tarball length is passed to the upload_crate function where the actual
     //   file is read and uploaded.
 
+    let dry_run = req.query().get("dry_run").map(String::as_str) == Some("true");
+    let provenance = parse_ci_headers(req)?;
     let (new_crate, user) = parse_new_headers(req)?;
 
     let conn = app.diesel_database.get()?;
+    db::set_trace_id(&conn)?;
 
     let verified_email_address = user.verified_email(&conn)?;
+    let branding = &app.config.branding;
     let verified_email_address = verified_email_address.ok_or_else(|| {
-        human(
-            "A verified email address is required to publish crates to crates.io. \
-             Visit https://crates.io/me to set and verify your email address.",
-        )
+        ErrorCode::EmailUnverified.human(&format_args!(
+            "A verified email address is required to publish crates to {}. \
+             Visit {}/me to set and verify your email address.",
+            branding.display_name, branding.url
+        ))
     })?;
 
+    let crate_name = new_crate.name.to_string();
+
     // Create a transaction on the database, if there are no errors,
-    // commit the transactions to record a new or updated crate.
-    conn.transaction(|| {
+    // commit the transactions to record a new or updated crate. Dry runs
+    // always roll it back.
+    let mut span = Span::start("db.transaction publish", SpanKind::Client);
+    let publish = || -> CargoResult<Response> {
         let name = new_crate.name;
         let vers = &*new_crate.vers;
         let links = new_crate.links;
@@ -82,13 +119,79 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
             max_upload_size: None,
         };
 
+        let is_new_crate = Crate::by_name(&name)
+            .first::<Crate>(&*conn)
+            .optional()?
+            .is_none();
+        if is_new_crate {
+            reserved_name::ensure_name_not_reserved(&conn, &name)?;
+            deleted_crate::ensure_name_not_tombstoned(&conn, &name)?;
+            // The old names of renamed crates keep resolving to them
+            if let Some((old_name, renamed)) = CrateRename::renamed_crate(&conn, &name)? {
+                return Err(ErrorCode::AlreadyExists.human(&format_args!(
+                    "crate `{}` was renamed to `{}`, new versions have to be \
+                     published under the new name",
+                    old_name, renamed.name
+                )));
+            }
+        }
+
+        // Only members of the team a namespace belongs to can publish new
+        // crates in it
+        let namespace = match Crate::split_namespace(&name) {
+            Some(_) if !app.config.crate_namespaces => {
+                return Err(ErrorCode::InvalidField
+                    .human("namespaced crate names aren't enabled on this registry"));
+            }
+            Some((namespace, _)) => Some(
+                Namespace::by_name(namespace)
+                    .first::<Namespace>(&*conn)
+                    .optional()?
+                    .ok_or_else(|| {
+                        ErrorCode::NotFound
+                            .human(&format_args!("namespace `{}` doesn't exist", namespace))
+                    })?,
+            ),
+            None => None,
+        };
+        if let (true, Some(namespace)) = (is_new_crate, &namespace) {
+            let team = namespace.team(&conn)?;
+            if !team.contains_user(req.app(), &conn, &user)? {
+                return Err(ErrorCode::TeamMembershipRequired.human(&format_args!(
+                    "only members of {} can publish new crates in namespace `{}`",
+                    team.login, namespace.name
+                )));
+            }
+        }
+
+        // Only the names of new crates are compared to popular crates
+        let typosquat_check = app.config.typosquat_check;
+        let similar_crates = if is_new_crate && typosquat_check != TyposquatCheck::Off {
+            typosquat::similar_popular_crates(&conn, &name)?
+        } else {
+            Vec::new()
+        };
+        if !similar_crates.is_empty()
+            && typosquat_check == TyposquatCheck::Approval
+            && !typosquat::is_approved(&conn, &name)?
+        {
+            return Err(ErrorCode::ApprovalRequired.human(&format_args!(
+                "{}. Publishing it requires the approval of the registry \
+                 administrators, please contact {}.",
+                typosquat::warning(&name, &similar_crates),
+                branding.support_email
+            )));
+        }
+
         let license_file = new_crate.license_file.as_ref().map(|s| &**s);
-        let krate =
-            persist.create_or_update(&conn, user.id, Some(&app.config.publish_rate_limit))?;
+        let krate = persist.create_or_update(&conn, user.id, Some(&app.config.rate_limiter))?;
+        if let (true, Some(namespace)) = (is_new_crate, &namespace) {
+            namespace.add_team_as_owner(&conn, &krate, user.id)?;
+        }
 
         let owners = krate.owners(&conn)?;
-        if user.rights(req.app(), &owners)? < Rights::Publish {
-            return Err(human(
+        if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+            return Err(ErrorCode::OwnerMismatch.human(
                 "this crate exists but you don't seem to be an owner. \
                  If you believe this is a mistake, perhaps you need \
                  to accept an invitation to be an owner before \
@@ -97,11 +200,26 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
         }
 
         if krate.name != *name {
-            return Err(human(&format_args!(
-                "crate was previously named `{}`",
+            return Err(ErrorCode::Conflict
+                .human(&format_args!("crate was previously named `{}`", krate.name)));
+        }
+
+        if krate.is_locked(&conn)? {
+            return Err(ErrorCode::CrateLocked.human(&format_args!(
+                "crate `{}` has been locked by the registry administrators, \
+                 new versions cannot be published",
+                krate.name
+            )));
+        }
+
+        if krate.visibility == Visibility::Pending {
+            return Err(ErrorCode::ApprovalRequired.human(&format_args!(
+                "crate `{}` is waiting for the approval of the registry administrators, \
+                 new versions can be published once it is approved",
                 krate.name
             )));
         }
+        let hold_for_approval = is_new_crate && app.config.new_crate_approval;
 
         // Length of the .crate tarball, which appears after the metadata in the request body.
         // TODO: Not sure why we're using the total content length (metadata + .crate file length)
@@ -111,7 +229,7 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
 
         let content_length = req
             .content_length()
-            .chain_error(|| human("missing header: Content-Length"))?;
+            .chain_error(|| ErrorCode::InvalidUpload.human("missing header: Content-Length"))?;
 
         let maximums = Maximums::new(
             krate.max_upload_size,
@@ -120,7 +238,7 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
         );
 
         if content_length > maximums.max_upload_size {
-            return Err(human(&format_args!(
+            return Err(ErrorCode::UploadTooLarge.human(&format_args!(
                 "max upload size is: {}",
                 maximums.max_upload_size
             )));
@@ -140,11 +258,46 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
             // to get here, and max upload sizes are way less than i32 max
             file_length as i32,
             user.id,
+            new_crate.rust_version.clone(),
         )?
         .save(&conn, &new_crate.authors, &verified_email_address)?;
 
         // Link this new version to all dependencies
-        let git_deps = dependency::add_dependencies(&conn, &new_crate.deps, version.id)?;
+        let git_deps = dependency::add_dependencies(
+            &conn,
+            &new_crate.deps,
+            version.id,
+            &branding.display_name,
+        )?;
+        let mut other_warnings = dependency::advisory_warnings(&conn, &new_crate.deps)?;
+
+        let unmatched_requirements = dependency::unmatched_requirements(&conn, &new_crate.deps)?;
+        if !unmatched_requirements.is_empty() {
+            match app.config.dependency_validation {
+                DependencyValidation::Reject => {
+                    return Err(
+                        ErrorCode::InvalidDependency.human(&unmatched_requirements.join(", "))
+                    );
+                }
+                DependencyValidation::Warn => other_warnings.extend(unmatched_requirements),
+            }
+        }
+
+        if !similar_crates.is_empty() {
+            if typosquat_check == TyposquatCheck::Review {
+                typosquat::file_report(&conn, &krate, &similar_crates)?;
+            }
+            if typosquat_check != TyposquatCheck::Approval {
+                other_warnings.push(typosquat::warning(&name, &similar_crates));
+            }
+        }
+        if hold_for_approval {
+            other_warnings.push(String::from(
+                "new crates have to be approved by the registry administrators, \
+                 until then the crate is only visible to its owners and can't be \
+                 used as a dependency",
+            ));
+        }
 
         // Update all keywords for this crate
         Keyword::update_crate(&conn, &krate, &keywords)?;
@@ -156,28 +309,49 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
         // Update all badges for this crate, collecting any invalid badges in
         // order to be able to warn about them
         let ignored_invalid_badges = Badge::update_crate(&conn, &krate, new_crate.badges.as_ref())?;
-        let max_version = krate.max_version(&conn)?;
+
+        if let Some(ref funding) = new_crate.funding {
+            CrateFunding::update_crate(&conn, &krate, funding)?;
+        }
+        let krate = Crate::update_default_versions(&conn, krate.id)?;
 
         if let Some(readme) = new_crate.readme {
-            render::render_and_upload_readme(
-                version.id,
-                readme,
-                new_crate
-                    .readme_file
-                    .unwrap_or_else(|| String::from("README.md")),
-                repo,
-            )
-            .enqueue(&conn)
-            .map_err(|e| CargoError::from_std_error(e))?;
+            let readme_file = new_crate
+                .readme_file
+                .unwrap_or_else(|| String::from("README.md"));
+            if dry_run {
+                // The job would be rolled back, so the readme is rendered
+                // right away instead
+                render::readme_to_html(&readme, &readme_file, repo.as_ref().map(String::as_str));
+            } else {
+                render::render_and_upload_readme(
+                    version.id,
+                    readme,
+                    readme_file,
+                    repo,
+                    telemetry::current(),
+                )
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+            }
         }
 
-        let cksum = app
-            .config
-            .uploader
-            .upload_crate(req, &krate, maximums, vers)?;
+        let (cksum, files) = if dry_run {
+            Uploader::verify_crate(req, &krate, maximums, vers, u64::from(file_length))?
+        } else {
+            app.config
+                .uploader
+                .upload_crate(req, &krate, maximums, vers, u64::from(file_length))?
+        };
+        VersionFile::insert(&conn, version.id, &files)?;
 
         let mut hex_cksum = String::new();
         cksum.write_hex(&mut hex_cksum)?;
+        VersionChecksum::insert(&conn, version.id, &hex_cksum)?;
+        if let Some((ref provider, ref run_url, ref commit_sha)) = provenance {
+            let run_url = run_url.as_ref().map(String::as_str);
+            VersionProvenance::insert(&conn, version.id, provider, run_url, commit_sha)?;
+        }
 
         // Register this crate in our local git repo.
         let git_crate = git::Crate {
@@ -188,25 +362,72 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
             deps: git_deps,
             yanked: Some(false),
             links,
+            advisory: None,
         };
-        git::add_crate(git_crate)
-            .enqueue(&conn)
-            .map_err(|e| CargoError::from_std_error(e))?;
 
-        // The `other` field on `PublishWarnings` was introduced to handle a temporary warning
-        // that is no longer needed. As such, crates.io currently does not return any `other`
-        // warnings at this time, but if we need to, the field is available.
+        // The `other` field on `PublishWarnings` is displayed by cargo as is, so we use it to
+        // point out dependencies that resolve to versions with an advisory, or that don't
+        // resolve at all.
         let warnings = PublishWarnings {
             invalid_categories: ignored_invalid_categories,
             invalid_badges: ignored_invalid_badges,
-            other: vec![],
+            other: other_warnings,
         };
+        let krate_json = krate.minimal_encodable(None, false, None);
+
+        if dry_run {
+            return Ok(req.json(&PublishDryRun {
+                krate: krate_json,
+                index_entry: git_crate,
+                warnings,
+            }));
+        }
+
+        if hold_for_approval {
+            // The crate is added to the index and scanned once it's approved
+            PendingCrate::hold(&conn, &krate, &git_crate)?;
+        } else {
+            git::add_crate(git_crate, telemetry::current())
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+        tasks::notify_owners_of_publish(version.id)
+            .enqueue(&conn)
+            .map_err(|e| CargoError::from_std_error(e))?;
+        if provenance.is_some() {
+            tasks::verify_provenance(version.id)
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
+
+        // Scanning happens after the index was updated, so that quarantining a
+        // version yanks it rather than racing with its publication.
+        if app.config.tarball_scan != TarballScan::Off && !hold_for_approval {
+            let quarantine = app.config.tarball_scan == TarballScan::Quarantine;
+            scan::scan_crate_file(version.id, quarantine, telemetry::current())
+                .enqueue(&conn)
+                .map_err(|e| CargoError::from_std_error(e))?;
+        }
 
         Ok(req.json(&GoodCrate {
-            krate: krate.minimal_encodable(&max_version, None, false, None),
+            krate: krate_json,
             warnings,
         }))
-    })
+    };
+    let response = if dry_run {
+        db::rolled_back_transaction(&conn, publish)
+    } else {
+        conn.transaction(publish)
+    };
+    let response = span.record(response)?;
+    drop(span);
+
+    if dry_run {
+        return Ok(response);
+    }
+    app.response_cache.invalidate_crate(&crate_name);
+    app.metrics.record_publish();
+    Ok(response)
 }
 
 /// Used by the `krate::new` function.
@@ -221,13 +442,15 @@ fn parse_new_headers(req: &mut dyn Request) -> CargoResult<(EncodableCrateUpload
 
     let max = req.app().config.max_upload_size;
     if metadata_length > max {
-        return Err(human(&format_args!("max upload size is: {}", max)));
+        return Err(ErrorCode::UploadTooLarge.human(&format_args!("max upload size is: {}", max)));
     }
     let mut json = vec![0; metadata_length as usize];
     read_fill(req.body(), &mut json)?;
-    let json = String::from_utf8(json).map_err(|_| human("json body was not valid utf-8"))?;
-    let new: EncodableCrateUpload = serde_json::from_str(&json)
-        .map_err(|e| human(&format_args!("invalid upload request: {}", e)))?;
+    let json = String::from_utf8(json)
+        .map_err(|_| ErrorCode::InvalidJson.human("json body was not valid utf-8"))?;
+    let new: EncodableCrateUpload = serde_json::from_str(&json).map_err(|e| {
+        ErrorCode::InvalidJson.human(&format_args!("invalid upload request: {}", e))
+    })?;
 
     // Make sure required fields are provided
     fn empty(s: Option<&String>) -> bool {
@@ -245,7 +468,7 @@ fn parse_new_headers(req: &mut dyn Request) -> CargoResult<(EncodableCrateUpload
         missing.push("authors");
     }
     if !missing.is_empty() {
-        return Err(human(&format_args!(
+        return Err(ErrorCode::InvalidUpload.human(&format_args!(
             "missing or empty metadata fields: {}. Please \
              see https://doc.rust-lang.org/cargo/reference/manifest.html for \
              how to upload metadata",
@@ -256,3 +479,27 @@ fn parse_new_headers(req: &mut dyn Request) -> CargoResult<(EncodableCrateUpload
     let user = req.user()?;
     Ok((new, user.clone()))
 }
+
+/// Reads the CI metadata headers of a publish, returning the provider, the
+/// URL of the run and the commit, or `None` if none of the headers were sent.
+fn parse_ci_headers(req: &dyn Request) -> CargoResult<Option<(String, Option<String>, String)>> {
+    let provider = request_header(req, "X-CI-Provider");
+    let run_url = request_header(req, "X-CI-Run-URL");
+    let commit_sha = request_header(req, "X-CI-Commit-SHA");
+    if provider.is_empty() && run_url.is_empty() && commit_sha.is_empty() {
+        return Ok(None);
+    }
+    if provider.is_empty() || commit_sha.is_empty() {
+        return Err(human(
+            "the X-CI-Provider and X-CI-Commit-SHA headers have to be sent together",
+        ));
+    }
+
+    let run_url = Some(run_url).filter(|s| !s.is_empty());
+    VersionProvenance::validate(provider, run_url, commit_sha)?;
+    Ok(Some((
+        provider.to_string(),
+        run_url.map(String::from),
+        commit_sha.to_string(),
+    )))
+}
@@ -0,0 +1,242 @@
+//! Publishing in chunks, so that uploads that were interrupted, e.g. by a
+//! flaky connection of a CI service, can be resumed rather than restarted.
+//!
+//! A session is started with the length of the request body that would be
+//! sent to `PUT /crates/new`. The body is then uploaded in chunks of any size,
+//! in order, and published once it is complete. After an interruption, the
+//! session tells how much was received, and the upload continues from there.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom};
+use tempdir::TempDir;
+
+use super::publish;
+use crate::controllers::prelude::*;
+use crate::models::{Crate, NewPublishSession, PublishSession, Rights};
+use crate::util::{read_le_u32, Maximums, RequestProxy};
+use crate::views::EncodablePublishSession;
+
+/// How many unfinished sessions a user can have at a time.
+const MAX_OPEN_SESSIONS: i64 = 5;
+
+/// Handles the `POST /crates/new/session` route.
+///
+/// The request body has the format
+///
+///     {"crate": "foo", "length": 12345}
+///
+/// where `length` is the length of the whole publish request body. It is
+/// checked against the maximum upload size of the crate up front, so that a
+/// large upload isn't rejected only after it was completed. The limit the
+/// registry administrators raised for a crate only applies to users who can
+/// publish it, and the session can only be finished for that crate.
+pub fn start(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(rename = "crate")]
+        krate: String,
+        length: i32,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let app = req.app();
+
+    let krate = Crate::by_name(&request.krate)
+        .first::<Crate>(&*conn)
+        .optional()?;
+    let crate_max_upload_size = match krate {
+        Some(ref krate) if krate.max_upload_size.is_some() => {
+            let owners = krate.owners(&conn)?;
+            if user.rights(app, &conn, &owners)? >= Rights::Publish {
+                krate.max_upload_size
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    let maximums = Maximums::new(
+        crate_max_upload_size,
+        app.config.max_upload_size,
+        app.config.max_unpack_size,
+    );
+    if request.length < 1 {
+        return Err(
+            ErrorCode::InvalidUpload.human("the length of the upload must be at least 1 byte")
+        );
+    }
+    if request.length as u64 > maximums.max_upload_size {
+        return Err(ErrorCode::UploadTooLarge.human(&format_args!(
+            "max upload size is: {}",
+            maximums.max_upload_size
+        )));
+    }
+
+    if PublishSession::open_count(&conn, user.id)? >= MAX_OPEN_SESSIONS {
+        return Err(ErrorCode::Conflict.human(&format_args!(
+            "you can't have more than {} unfinished uploads at a time, \
+             please finish or delete one of them first",
+            MAX_OPEN_SESSIONS
+        )));
+    }
+
+    let session = NewPublishSession {
+        user_id: user.id,
+        length: request.length,
+        crate_name: &request.krate,
+    }
+    .create(&conn)?;
+    session_response(req, session)
+}
+
+/// Handles the `GET /crates/new/session/:session_id` route.
+///
+/// Tells where to resume an interrupted upload.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let session = find_session(req, &conn)?;
+    session_response(req, session)
+}
+
+/// Handles the `PUT /crates/new/session/:session_id/chunk` route.
+///
+/// The request body is the next chunk, and the `offset` query parameter has to
+/// be the number of bytes received so far.
+pub fn upload_chunk(req: &mut dyn Request) -> CargoResult<Response> {
+    let offset = req
+        .query()
+        .get("offset")
+        .and_then(|offset| offset.parse::<i32>().ok())
+        .ok_or_else(|| {
+            ErrorCode::InvalidParameter.human("missing or invalid `offset` query parameter")
+        })?;
+
+    let session = {
+        let conn = req.db_conn()?;
+        find_session(req, &conn)?
+    };
+    if offset != session.received {
+        return Err(ErrorCode::Conflict.human(&format_args!(
+            "expected a chunk at offset {}",
+            session.received
+        )));
+    }
+
+    // Anything beyond the announced length is read as well, to reject it
+    let remaining = (session.length - session.received) as u64;
+    let mut chunk = Vec::new();
+    req.body().take(remaining + 1).read_to_end(&mut chunk)?;
+    if chunk.is_empty() {
+        return Err(ErrorCode::InvalidUpload.human("the chunk is empty"));
+    }
+    if chunk.len() as u64 > remaining {
+        return Err(ErrorCode::InvalidUpload.human(&format_args!(
+            "the chunk exceeds the length of the upload, only {} more bytes are expected",
+            remaining
+        )));
+    }
+
+    let conn = req.db_conn()?;
+    let session = session
+        .append_chunk(&conn, offset, &chunk)?
+        .ok_or_else(|| {
+            ErrorCode::Conflict.human("another chunk was received at the same offset, please retry")
+        })?;
+    session_response(req, session)
+}
+
+/// Handles the `POST /crates/new/session/:session_id/finish` route.
+///
+/// Publishes the uploaded body like `PUT /crates/new` would, and responds the
+/// same way. The session is deleted once the crate was published, and kept
+/// otherwise, so that temporary failures can be retried.
+pub fn finish(req: &mut dyn Request) -> CargoResult<Response> {
+    let spool = TempDir::new("publish-session")?;
+    let mut body = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(spool.path().join("body"))?;
+
+    let session = {
+        let conn = req.db_conn()?;
+        let session = find_session(req, &conn)?;
+        if session.received < session.length {
+            return Err(ErrorCode::Conflict.human(&format_args!(
+                "the upload is incomplete, {} of {} bytes were received",
+                session.received, session.length
+            )));
+        }
+        session.write_body(&conn, &mut body)?;
+        session
+    };
+    body.seek(SeekFrom::Start(0))?;
+    let crate_name = uploaded_crate_name(&mut body)?;
+    if crate_name != session.crate_name {
+        return Err(ErrorCode::InvalidUpload.human(&format_args!(
+            "the upload is for crate `{}`, but the session was started for `{}`",
+            crate_name, session.crate_name
+        )));
+    }
+    body.seek(SeekFrom::Start(0))?;
+
+    let response = publish::publish(&mut RequestProxy::replace_body(
+        req,
+        &mut body,
+        session.length as u64,
+    ))?;
+
+    let conn = req.db_conn()?;
+    session.delete(&conn)?;
+    Ok(response)
+}
+
+/// Handles the `DELETE /crates/new/session/:session_id` route.
+pub fn delete(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let session = find_session(req, &conn)?;
+    session.delete(&conn)?;
+
+    ok_true()
+}
+
+/// Reads the name of the crate from the metadata at the start of an uploaded
+/// publish request body.
+fn uploaded_crate_name(body: &mut dyn Read) -> CargoResult<String> {
+    #[derive(Deserialize)]
+    struct Metadata {
+        name: String,
+    }
+    let metadata_length = read_le_u32(body)?;
+    let mut json = Vec::new();
+    body.take(u64::from(metadata_length))
+        .read_to_end(&mut json)?;
+    let metadata: Metadata = serde_json::from_slice(&json).map_err(|e| {
+        ErrorCode::InvalidJson.human(&format_args!("invalid upload request: {}", e))
+    })?;
+    Ok(metadata.name)
+}
+
+/// Finds the session in the route, which has to belong to the current user.
+fn find_session(req: &dyn Request, conn: &PgConnection) -> CargoResult<PublishSession> {
+    let user = req.user()?;
+    let id = req.params()["session_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid session id"))?;
+    Ok(PublishSession::find(conn, id, user.id)?)
+}
+
+fn session_response(req: &dyn Request, session: PublishSession) -> CargoResult<Response> {
+    #[derive(Serialize)]
+    struct R {
+        session: EncodablePublishSession,
+    }
+    Ok(req.json(&R {
+        session: session.encodable(),
+    }))
+}
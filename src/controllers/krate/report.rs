@@ -0,0 +1,86 @@
+//! Endpoint for reporting crates to the registry administrators
+
+use chrono::NaiveDateTime;
+use serde_json;
+
+use crate::controllers::prelude::*;
+use crate::models::{Crate, NewCrateReport, ReportCategory};
+use crate::schema::crate_reports;
+use crate::util::errors::{NotFound, TooManyRequests};
+
+/// The maximum number of reports a user can file within 24 hours.
+const MAX_REPORTS_PER_DAY: usize = 10;
+
+/// The maximum length of the description of a report, in characters.
+const MAX_DESCRIPTION_LENGTH: usize = 5000;
+
+/// Handles the `POST /crates/:crate_id/report` route.
+///
+/// The request body has the format
+///
+///     {"category": "spam" | "malware" | "typosquat" | "trademark", "description": "..."}
+///
+/// Reports show up in the moderation queue at `GET /admin/flagged`.
+pub fn report(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        category: ReportCategory,
+        description: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let description = request.description.trim();
+    if description.is_empty() {
+        return Err(ErrorCode::InvalidField.human("a description of the problem is required"));
+    }
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "the description must not be longer than {} characters",
+            MAX_DESCRIPTION_LENGTH
+        )));
+    }
+
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, Some(user))? {
+        return Err(Box::new(NotFound));
+    }
+
+    conn.transaction(|| {
+        check_rate_limit(&conn, user.id)?;
+
+        NewCrateReport {
+            crate_id: krate.id,
+            reporter_id: Some(user.id),
+            category: request.category,
+            description,
+        }
+        .create(&conn)?;
+
+        ok_true()
+    })
+}
+
+fn check_rate_limit(conn: &PgConnection, user_id: i32) -> CargoResult<()> {
+    use diesel::dsl::*;
+
+    let recent = crate_reports::table
+        .filter(crate_reports::reporter_id.eq(user_id))
+        .filter(crate_reports::created_at.gt(now - 1.day()))
+        .select(crate_reports::created_at)
+        .order(crate_reports::created_at)
+        .load::<NaiveDateTime>(conn)?;
+
+    if recent.len() >= MAX_REPORTS_PER_DAY {
+        return Err(Box::new(TooManyRequests {
+            action: "reported too many crates",
+            retry_after: recent[0] + chrono::Duration::days(1),
+        }));
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Verification of the repository a crate links to
+//!
+//! Anyone can publish a crate that claims to come from a popular repository.
+//! Owners prove that the `repository` of their crate is theirs by showing
+//! that their GitHub account has admin rights on it, which sets
+//! `repository_verified`. Changing the repository of the crate resets it, see
+//! the `trigger_crates_unverify_repository` trigger.
+
+use oauth2::{prelude::*, AccessToken};
+
+use crate::app::App;
+use crate::controllers::prelude::*;
+use crate::github::{self, github_api};
+use crate::models::{Crate, Rights, User};
+use crate::schema::crates;
+use crate::util::errors::NotFound;
+
+/// Handles the `PUT /crates/:crate_id/repository_verification` route.
+///
+/// Only repositories on GitHub can be verified, by an owner whose GitHub
+/// account has admin rights on the repository.
+pub fn verify(req: &mut dyn Request) -> CargoResult<Response> {
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if user.rights(app, &conn, &krate.owners(&conn)?)? < Rights::Full {
+        return Err(
+            ErrorCode::OwnerMismatch.human("only owners can verify the repository of a crate")
+        );
+    }
+
+    let repository = krate
+        .repository
+        .as_ref()
+        .ok_or_else(|| ErrorCode::Conflict.human("the crate has no repository to verify"))?;
+    let (owner, repo) = github::repository_from_url(repository).ok_or_else(|| {
+        ErrorCode::InvalidField.human("only repositories on GitHub can be verified")
+    })?;
+    if !is_repository_admin(app, &owner, &repo, user)? {
+        return Err(ErrorCode::GithubAccessDenied.human(&format_args!(
+            "your GitHub account needs admin rights on `{}/{}` to verify it",
+            owner, repo
+        )));
+    }
+
+    // The repository could have changed since it was checked
+    diesel::update(&krate)
+        .filter(crates::repository.eq(repository))
+        .set(crates::repository_verified.eq(true))
+        .execute(&*conn)?;
+    app.response_cache.invalidate_crate(&krate.name);
+    ok_true()
+}
+
+/// Whether the GitHub account of `user` has admin rights on the repository.
+fn is_repository_admin(app: &App, owner: &str, repo: &str, user: &User) -> CargoResult<bool> {
+    #[derive(Deserialize)]
+    struct Permissions {
+        admin: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct Repository {
+        /// Missing when the request isn't authenticated
+        permissions: Option<Permissions>,
+    }
+
+    let url = format!("/repos/{}/{}", owner, repo);
+    let token = AccessToken::new(user.gh_access_token.clone());
+    match github_api::<Repository>(app, &url, &token) {
+        Err(ref e) if e.is::<NotFound>() => Err(ErrorCode::NotFound.human(&format_args!(
+            "could not find the GitHub repository `{}/{}`",
+            owner, repo
+        ))),
+        repository => Ok(repository?.permissions.map_or(false, |p| p.admin)),
+    }
+}
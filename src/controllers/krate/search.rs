@@ -1,22 +1,52 @@
 //! Endpoint for searching and discovery functionality
 
 use diesel::dsl::*;
+use diesel::pg::Pg;
+use diesel::sql_types::{Bool, Double, Integer, Nullable, Text};
 use diesel_full_text_search::*;
+use indexmap::IndexMap;
 
+use crate::controllers::helpers::pagination::PaginationOptions;
 use crate::controllers::helpers::Paginate;
 use crate::controllers::prelude::*;
-use crate::models::{Crate, CrateBadge, CrateOwner, CrateVersions, OwnerKind, Version};
+use crate::middleware::current_user::client_ip;
+use crate::models::{Crate, CrateBadge, CrateOwner, OwnerKind, Version, Visibility};
 use crate::schema::*;
+use crate::search_strategy::SearchStrategy;
 use crate::views::EncodableCrate;
 
 use crate::models::krate::{canon_crate_name, ALL_COLUMNS};
 
+/// The maximum number of crates that can be requested through `ids[]`.
+const MAX_BULK_IDS: usize = 100;
+
 /// Handles the `GET /crates` route.
 /// Returns a list of crates. Called in a variety of scenarios in the
 /// front end, including:
 /// - Alphabetical listing of crates
 /// - List of crates under a specific owner
 /// - Listing a user's followed crates
+/// - Fetching the metadata of up to 100 crates at once, by passing their
+///   names as `ids[]`
+///
+/// When listing crates by name without a search query, `seek` pagination can
+/// be used instead of `page`. Pass an empty `seek` to get the first page along
+/// with a cursor for the next one in `meta.next_page`.
+///
+/// With `sort=trending`, crates are listed by their rank in `trending_crates`,
+/// see the `update_trending_crates` background job. With `sort=popularity`,
+/// they are listed by `crates.popularity`, which blends recent downloads with
+/// the number of dependent crates, see the `update_popularity` background job.
+///
+/// The `category`, `keyword`, `license` and `max_rust_version` filters can be
+/// combined with each other and with a search query `q`. `max_rust_version`
+/// (also accepted as `rust_version`) only lists crates with a non-yanked
+/// version that declares a minimum supported Rust version of at most the given
+/// one. The `keyword` filter also accepts aliases of keywords. The applied
+/// filters are returned in `meta.filters`.
+///
+/// Searches with few results also return the names of crates that are
+/// spelled similarly to the query in `meta.suggestions`.
 ///
 /// Notes:
 /// The different use cases this function covers is handled through passing
@@ -33,51 +63,242 @@ use crate::models::krate::{canon_crate_name, ALL_COLUMNS};
 /// function out to cover the different use cases, and create unit tests
 /// for them.
 pub fn search(req: &mut dyn Request) -> CargoResult<Response> {
-    use diesel::sql_types::{Bool, Text};
-
-    let conn = req.db_conn()?;
-    let params = req.query();
+    let conn = req.read_conn()?;
+    let mut params = req.query();
+    let ids = req.query_values("ids[]");
+    if ids.len() > MAX_BULK_IDS {
+        return Err(ErrorCode::InvalidParameter.bad_request(&format_args!(
+            "cannot request more than {} crates at once",
+            MAX_BULK_IDS
+        )));
+    }
+    if !ids.is_empty() {
+        // Return all of the requested crates in a single page
+        params.insert("per_page".into(), ids.len().to_string());
+    }
     let sort = params.get("sort").map(|s| &**s);
-    let include_yanked = params
-        .get("include_yanked")
-        .map(|s| s == "yes")
-        .unwrap_or(true);
+    let options = PaginationOptions::new_with_seek(&params)?;
+    let sorted_by_name = sort.map(|s| s == "alpha").unwrap_or(true);
+    if options.is_seek() && (params.contains_key("q") || !sorted_by_name) {
+        return Err(ErrorCode::InvalidParameter
+            .bad_request("seek pagination is only supported when listing crates by name"));
+    }
+    let q_string = params.get("q").filter(|q| !q.is_empty());
+    let strategy = q_string.map(|_| search_strategy(req));
+
+    // Results depend on the current user, so only anonymous searches are
+    // cached
+    let cache = &req.app().response_cache;
+    let cache_key = if req.user().is_err() {
+        Some(cache.search_key(&format!(
+            "{}?{}",
+            strategy.map_or("", SearchStrategy::as_str),
+            req.query_string().unwrap_or_default()
+        )))
+    } else {
+        None
+    };
+    if let Some(response) = cache_key.as_ref().and_then(|key| cache.get(key)) {
+        return Ok(response);
+    }
 
     let selection = (
         ALL_COLUMNS,
         false.into_sql::<Bool>(),
         recent_crate_downloads::downloads.nullable(),
     );
+    let mut query = filtered_crates(req, &params, &ids, strategy)?.select(selection);
+
+    if let (Some(q_string), Some(strategy)) = (q_string, strategy) {
+        let sort = params.get("sort").map(|s| &**s).unwrap_or("relevance");
+
+        query = query.select((
+            ALL_COLUMNS,
+            Crate::with_name(q_string),
+            recent_crate_downloads::downloads.nullable(),
+        ));
+        query = query.order(Crate::with_name(q_string).desc());
+
+        if sort == "relevance" {
+            query = match strategy {
+                SearchStrategy::TsRank => {
+                    let q = sql::<TsQuery>("plainto_tsquery('english', ")
+                        .bind::<Text, _>(q_string)
+                        .sql(")");
+                    let rank = ts_rank_cd(crates::textsearchable_index_col, q);
+                    query.then_order_by(rank.desc())
+                }
+                SearchStrategy::Hybrid => query.then_order_by(hybrid_rank(q_string).desc()),
+            };
+        }
+    }
+
+    if let Some(name) = options.seek_after::<String>()? {
+        query = query.filter(crates::name.gt(name));
+    }
+
+    if sort == Some("downloads") {
+        query = query.then_order_by(crates::downloads.desc())
+    } else if sort == Some("recent-downloads") {
+        query = query.then_order_by(recent_crate_downloads::downloads.desc().nulls_last())
+    } else if sort == Some("popularity") {
+        query = query
+            .then_order_by(crates::popularity.desc())
+            .then_order_by(crates::name.asc())
+    } else if sort == Some("recent-updates") {
+        query = query.order(crates::updated_at.desc());
+    } else if sort == Some("trending") {
+        // Crates that aren't trending are listed by name after the others
+        let trending_rank = sql::<Nullable<Integer>>(
+            "(SELECT rank FROM trending_crates WHERE trending_crates.crate_id = crates.id)",
+        );
+        query = query
+            .then_order_by(trending_rank.asc().nulls_last())
+            .then_order_by(crates::name.asc())
+    } else {
+        query = query.then_order_by(crates::name.asc())
+    }
+
+    let data = query
+        .paginate_with_options(options)
+        .load::<(Crate, bool, Option<i64>)>(&*conn)?;
+    let total = data.total();
+
+    let next_page = data
+        .next_page_params(|(krate, _, _)| krate.name.clone())
+        .map(|p| req.query_with_params(p));
+    let prev_page = data.prev_page_params().map(|p| req.query_with_params(p));
+
+    let perfect_matches = data.iter().map(|&(_, b, _)| b).collect::<Vec<_>>();
+    let recent_downloads = data
+        .iter()
+        .map(|&(_, _, s)| s.unwrap_or(0))
+        .collect::<Vec<_>>();
+    let crates = data.into_iter().map(|(c, _, _)| c).collect::<Vec<_>>();
+
+    let badges = CrateBadge::belonging_to(&crates)
+        .select((badges::crate_id, badges::all_columns))
+        .load::<CrateBadge>(&*conn)?
+        .grouped_by(&crates)
+        .into_iter()
+        .map(|badges| badges.into_iter().map(|cb| cb.badge).collect());
+
+    let crates = crates
+        .into_iter()
+        .zip(perfect_matches)
+        .zip(recent_downloads)
+        .zip(badges)
+        .map(|(((krate, perfect_match), recent_downloads), badges)| {
+            krate.minimal_encodable(Some(badges), perfect_match, Some(recent_downloads))
+        })
+        .collect();
+
+    let suggestions = match (q_string, total) {
+        (Some(q_string), Some(total)) if total < SUGGESTION_THRESHOLD => {
+            Some(suggestions(&conn, q_string)?)
+        }
+        _ => None,
+    };
+
+    // Facets describe all matching crates, not just the ones on this page
+    let facets = match q_string {
+        Some(_) => {
+            let crate_ids = || -> CargoResult<_> {
+                Ok(filtered_crates(req, &params, &ids, strategy)?.select(crates::id))
+            };
+            Some(Facets::load(&conn, crate_ids)?)
+        }
+        None => None,
+    };
+
+    let filters = Filters {
+        category: params.get("category").cloned(),
+        keyword: params.get("keyword").cloned(),
+        license: params.get("license").cloned(),
+        rust_version: max_rust_version(&params).cloned(),
+    };
+
+    #[derive(Serialize)]
+    struct R {
+        crates: Vec<EncodableCrate>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        total: Option<i64>,
+        next_page: Option<String>,
+        prev_page: Option<String>,
+        search_strategy: Option<&'static str>,
+        filters: Filters,
+        facets: Option<Facets>,
+        suggestions: Option<Vec<String>>,
+    }
+    #[derive(Serialize)]
+    struct Filters {
+        category: Option<String>,
+        keyword: Option<String>,
+        license: Option<String>,
+        rust_version: Option<String>,
+    }
+
+    let body = R {
+        crates,
+        meta: Meta {
+            total,
+            next_page,
+            prev_page,
+            search_strategy: strategy.map(SearchStrategy::as_str),
+            filters,
+            facets,
+            suggestions,
+        },
+    };
+    Ok(match cache_key {
+        Some(key) => cache.insert(&key, &body),
+        None => req.json(&body),
+    })
+}
+
+type BoxedCrates<'a> = IntoBoxed<'a, LeftJoin<crates::table, recent_crate_downloads::table>, Pg>;
+type BoxedCrateIds<'a> = Select<BoxedCrates<'a>, crates::id>;
+
+/// Builds the query for all crates matching the search query and the filters
+/// of the request that the current user is allowed to see, without any
+/// ordering or pagination.
+fn filtered_crates<'a>(
+    req: &dyn Request,
+    params: &'a IndexMap<String, String>,
+    ids: &[String],
+    strategy: Option<SearchStrategy>,
+) -> CargoResult<BoxedCrates<'a>> {
+    let include_yanked = params
+        .get("include_yanked")
+        .map(|s| s == "yes")
+        .unwrap_or(true);
+
     let mut query = crates::table
         .left_join(recent_crate_downloads::table)
-        .select(selection)
         .into_boxed();
 
-    if let Some(q_string) = params.get("q") {
-        if !q_string.is_empty() {
-            let sort = params.get("sort").map(|s| &**s).unwrap_or("relevance");
-
-            let q = sql::<TsQuery>("plainto_tsquery('english', ")
-                .bind::<Text, _>(q_string)
-                .sql(")");
-            query = query.filter(
-                q.clone()
-                    .matches(crates::textsearchable_index_col)
-                    .or(Crate::loosly_matches_name(&q_string)),
-            );
-
-            query = query.select((
-                ALL_COLUMNS,
-                Crate::with_name(q_string),
-                recent_crate_downloads::downloads.nullable(),
-            ));
-            query = query.order(Crate::with_name(q_string).desc());
-
-            if sort == "relevance" {
-                let rank = ts_rank_cd(crates::textsearchable_index_col, q);
-                query = query.then_order_by(rank.desc())
-            }
-        }
+    if let (Some(q_string), Some(strategy)) = (params.get("q"), strategy) {
+        let q = sql::<TsQuery>("plainto_tsquery('english', ")
+            .bind::<Text, _>(q_string)
+            .sql(")");
+        let matches = q
+            .matches(crates::textsearchable_index_col)
+            .or(Crate::loosly_matches_name(q_string));
+        query = match strategy {
+            SearchStrategy::TsRank => query.filter(matches),
+            SearchStrategy::Hybrid => query.filter(matches.or(has_similar_name(q_string))),
+        };
+    }
+
+    if !ids.is_empty() {
+        let names = ids
+            .iter()
+            .map(|name| name.to_lowercase().replace('-', "_"))
+            .collect::<Vec<_>>();
+        query = query.filter(canon_crate_name(crates::name).eq_any(names));
     }
 
     if let Some(cat) = params.get("category") {
@@ -101,10 +322,44 @@ pub fn search(req: &mut dyn Request) -> CargoResult<Response> {
                 crates_keywords::table
                     .select(crates_keywords::crate_id)
                     .inner_join(keywords::table)
-                    .filter(crate::lower(keywords::keyword).eq(crate::lower(kw))),
+                    .filter(
+                        crate::lower(keywords::keyword)
+                            .eq(crate::lower(kw))
+                            .or(keywords::keyword.eq_any(
+                                keyword_aliases::table
+                                    .filter(keyword_aliases::alias.eq(crate::lower(kw)))
+                                    .select(keyword_aliases::keyword),
+                            )),
+                    ),
             ),
         );
-    } else if let Some(letter) = params.get("letter") {
+    }
+
+    if let Some(license) = params.get("license") {
+        query = query.filter(exists(
+            versions::table
+                .filter(versions::crate_id.eq(crates::id))
+                .filter(versions::yanked.eq(false))
+                .filter(has_license(license)),
+        ));
+    }
+
+    if let Some(rust_version) = max_rust_version(params) {
+        if !Version::valid_rust_version(rust_version) {
+            return Err(ErrorCode::InvalidParameter.bad_request(&format_args!(
+                "invalid rust_version `{}`, expected a version like `1.40` or `1.40.0`",
+                rust_version
+            )));
+        }
+        query = query.filter(exists(
+            versions::table
+                .filter(versions::crate_id.eq(crates::id))
+                .filter(versions::yanked.eq(false))
+                .filter(supports_rust_version(rust_version)),
+        ));
+    }
+
+    if let Some(letter) = params.get("letter") {
         let pattern = format!(
             "{}%",
             letter
@@ -141,6 +396,31 @@ pub fn search(req: &mut dyn Request) -> CargoResult<Response> {
         );
     }
 
+    // Private crates are only listed for their individual owners and for users
+    // who have been granted access. Checking team membership would require a
+    // request to GitHub per crate, so members of owning teams need an explicit
+    // grant to see a private crate here. Pending crates are only listed for
+    // their individual owners.
+    query = match req.user() {
+        Ok(user) => query.filter(
+            crates::visibility
+                .eq(Visibility::Public)
+                .or(crates::visibility.eq(Visibility::Private).and(
+                    crates::id.eq_any(
+                        crate_acls::table
+                            .select(crate_acls::crate_id)
+                            .filter(crate_acls::user_id.eq(user.id)),
+                    ),
+                ))
+                .or(crates::id.eq_any(
+                    CrateOwner::by_owner_kind(OwnerKind::User)
+                        .select(crate_owners::crate_id)
+                        .filter(crate_owners::owner_id.eq(user.id)),
+                )),
+        ),
+        Err(_) => query.filter(crates::visibility.eq(Visibility::Public)),
+    };
+
     if !include_yanked {
         query = query.filter(exists(
             versions::table
@@ -149,80 +429,190 @@ pub fn search(req: &mut dyn Request) -> CargoResult<Response> {
         ));
     }
 
-    if sort == Some("downloads") {
-        query = query.then_order_by(crates::downloads.desc())
-    } else if sort == Some("recent-downloads") {
-        query = query.then_order_by(recent_crate_downloads::downloads.desc().nulls_last())
-    } else if sort == Some("recent-updates") {
-        query = query.order(crates::updated_at.desc());
-    } else {
-        query = query.then_order_by(crates::name.asc())
-    }
+    Ok(query)
+}
 
-    let data = query
-        .paginate(&req.query())?
-        .load::<(Crate, bool, Option<i64>)>(&*conn)?;
-    let total = data.total();
+/// Searches with fewer results than this get "did you mean" suggestions.
+const SUGGESTION_THRESHOLD: i64 = 3;
+/// The maximum number of suggestions returned for a search.
+const MAX_SUGGESTIONS: i64 = 5;
 
-    let next_page = data.next_page_params().map(|p| req.query_with_params(p));
-    let prev_page = data.prev_page_params().map(|p| req.query_with_params(p));
+/// The names of public crates that are closest to the query by trigram
+/// similarity, to help users that misspelled a crate name. The crate named
+/// exactly like the query is left out, since it would be the first result.
+fn suggestions(conn: &PgConnection, q_string: &str) -> QueryResult<Vec<String>> {
+    use diesel::sql_types::Float;
 
-    let perfect_matches = data.iter().map(|&(_, b, _)| b).collect::<Vec<_>>();
-    let recent_downloads = data
-        .iter()
-        .map(|&(_, _, s)| s.unwrap_or(0))
-        .collect::<Vec<_>>();
-    let crates = data.into_iter().map(|(c, _, _)| c).collect::<Vec<_>>();
+    let similarity = sql::<Float>("similarity(canon_crate_name(crates.name), canon_crate_name(")
+        .bind::<Text, _>(q_string)
+        .sql("))");
+    crates::table
+        .select(crates::name)
+        .into_boxed()
+        .filter(crates::visibility.eq(Visibility::Public))
+        .filter(has_similar_name(q_string))
+        .filter(not(Crate::with_name(q_string)))
+        .order((similarity.desc(), crates::name))
+        .limit(MAX_SUGGESTIONS)
+        .load(conn)
+}
 
-    let versions = crates
-        .versions()
-        .load::<Version>(&*conn)?
-        .grouped_by(&crates)
-        .into_iter()
-        .map(|versions| Version::max(versions.into_iter().map(|v| v.num)));
+/// The number of values returned for each facet.
+const FACET_LIMIT: i64 = 10;
 
-    let badges = CrateBadge::belonging_to(&crates)
-        .select((badges::crate_id, badges::all_columns))
-        .load::<CrateBadge>(&*conn)?
-        .grouped_by(&crates)
-        .into_iter()
-        .map(|badges| badges.into_iter().map(|cb| cb.badge).collect());
+/// Counts of the matching crates by category, keyword and license, so that
+/// the frontend can offer filters for a search.
+#[derive(Serialize)]
+struct Facets {
+    categories: Vec<FacetCount>,
+    keywords: Vec<FacetCount>,
+    licenses: Vec<FacetCount>,
+}
 
-    let crates = versions
-        .zip(crates)
-        .zip(perfect_matches)
-        .zip(recent_downloads)
-        .zip(badges)
-        .map(
-            |((((max_version, krate), perfect_match), recent_downloads), badges)| {
-                krate.minimal_encodable(
-                    &max_version,
-                    Some(badges),
-                    perfect_match,
-                    Some(recent_downloads),
-                )
-            },
-        )
-        .collect();
+#[derive(Serialize, Queryable)]
+struct FacetCount {
+    name: String,
+    count: i64,
+}
 
-    #[derive(Serialize)]
-    struct R {
-        crates: Vec<EncodableCrate>,
-        meta: Meta,
-    }
-    #[derive(Serialize)]
-    struct Meta {
-        total: Option<i64>,
-        next_page: Option<String>,
-        prev_page: Option<String>,
+impl Facets {
+    /// Counts the facets of the crates selected by the query `crate_ids`
+    /// returns, which is run as a subquery of each count, so that the ids
+    /// of all matching crates don't have to be sent back and forth.
+    fn load<'a, F>(conn: &PgConnection, crate_ids: F) -> CargoResult<Self>
+    where
+        F: Fn() -> CargoResult<BoxedCrateIds<'a>>,
+    {
+        use diesel::sql_types::BigInt;
+
+        let count = sql::<BigInt>("COUNT(*)");
+        let categories = crates_categories::table
+            .inner_join(categories::table)
+            .filter(crates_categories::crate_id.eq_any(crate_ids()?))
+            .select((categories::slug, count.clone()))
+            .group_by(categories::slug)
+            .order((count.clone().desc(), categories::slug))
+            .limit(FACET_LIMIT)
+            .load(conn)?;
+
+        let keywords = crates_keywords::table
+            .inner_join(keywords::table)
+            .filter(crates_keywords::crate_id.eq_any(crate_ids()?))
+            .select((keywords::keyword, count.clone()))
+            .group_by(keywords::keyword)
+            .order((count.desc(), keywords::keyword))
+            .limit(FACET_LIMIT)
+            .load(conn)?;
+
+        // A crate is counted once for every license identifier in the
+        // license expressions of its non-yanked versions, so that
+        // `MIT OR Apache-2.0` counts towards both `MIT` and `Apache-2.0`
+        let identifier = sql::<Text>("lower(crate_license_identifiers.license)");
+        let crate_count = sql::<BigInt>("COUNT(DISTINCT crate_license_identifiers.crate_id)");
+        let licenses = crate_license_identifiers::table
+            .filter(crate_license_identifiers::crate_id.eq_any(crate_ids()?))
+            .select((
+                sql::<Text>("min(crate_license_identifiers.license)"),
+                crate_count.clone(),
+            ))
+            .group_by(identifier.clone())
+            .order((crate_count.desc(), identifier))
+            .limit(FACET_LIMIT)
+            .load(conn)?;
+
+        Ok(Facets {
+            categories,
+            keywords,
+            licenses,
+        })
     }
+}
 
-    Ok(req.json(&R {
-        crates,
-        meta: Meta {
-            total,
-            next_page,
-            prev_page,
-        },
-    }))
+/// Picks the ranking for a search request. Searchers are identified by their
+/// user id, or by their IP address if they aren't logged in.
+fn search_strategy(req: &dyn Request) -> SearchStrategy {
+    let key = match req.user() {
+        Ok(user) => format!("user:{}", user.id),
+        Err(_) => client_ip(req),
+    };
+    req.app().config.search_experiment.strategy_for(&key)
+}
+
+/// Whether the name of the crate is similar to the query according to
+/// `pg_trgm`, which catches typos that neither the full text search nor the
+/// substring match of the name would find.
+fn has_similar_name<'a, QS: 'a>(
+    q_string: &'a str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Bool> + 'a> {
+    Box::new(
+        sql::<Bool>("canon_crate_name(crates.name) % canon_crate_name(")
+            .bind::<Text, _>(q_string)
+            .sql(")"),
+    )
+}
+
+/// Whether the license expression of the version mentions the given license
+/// identifier, ignoring case.
+fn has_license<'a, QS: 'a>(
+    license: &'a str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Bool> + 'a> {
+    Box::new(
+        sql::<Bool>("lower(")
+            .bind::<Text, _>(license)
+            .sql(") = ANY(regexp_split_to_array(lower(versions.license), '[[:space:]/()]+'))"),
+    )
+}
+
+/// The Rust version to filter by, from `max_rust_version` or its alias
+/// `rust_version`.
+fn max_rust_version(params: &IndexMap<String, String>) -> Option<&String> {
+    params
+        .get("max_rust_version")
+        .or_else(|| params.get("rust_version"))
+}
+
+/// Whether the minimum supported Rust version of the version is at most the
+/// given one. Versions that don't declare one never match.
+///
+/// Both versions are compared as `[major, minor, patch]` arrays, appending
+/// `.0` first so that `1.40` and `1.40.0` are equal.
+fn supports_rust_version<'a, QS: 'a>(
+    rust_version: &'a str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Bool> + 'a> {
+    Box::new(
+        sql::<Bool>(
+            "(string_to_array(versions.rust_version || '.0', '.')::int[])[1:3] \
+             <= (string_to_array(",
+        )
+        .bind::<Text, _>(rust_version)
+        .sql(" || '.0', '.')::int[])[1:3]"),
+    )
+}
+
+/// The weights of the different signals of the hybrid rank. Recent downloads
+/// are scaled logarithmically so that they only break ties between otherwise
+/// similarly relevant crates.
+const NAME_SIMILARITY_WEIGHT: f64 = 2.0;
+const TEXT_RANK_WEIGHT: f64 = 1.0;
+const RECENT_DOWNLOADS_WEIGHT: f64 = 0.1;
+
+fn hybrid_rank<'a, QS: 'a>(
+    q_string: &'a str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Double> + 'a> {
+    Box::new(
+        sql::<Double>(&format!(
+            "({} * similarity(canon_crate_name(crates.name), canon_crate_name(",
+            NAME_SIMILARITY_WEIGHT
+        ))
+        .bind::<Text, _>(q_string)
+        .sql(&format!(
+            ")) + {} * ts_rank_cd(crates.textsearchable_index_col, plainto_tsquery('english', ",
+            TEXT_RANK_WEIGHT
+        ))
+        .bind::<Text, _>(q_string)
+        .sql(&format!(
+            ")) + {} * ln(1 + COALESCE(recent_crate_downloads.downloads, 0)))::float8",
+            RECENT_DOWNLOADS_WEIGHT
+        )),
+    )
 }
@@ -0,0 +1,232 @@
+//! All routes related to the successor of a crate
+//!
+//! Owners can designate a user who becomes the owner of the crate once its
+//! sole owner is inactive for a number of months, see `models::CrateSuccessor`.
+//! The successor has to accept the designation before it takes effect, and
+//! the `transfer_inactive_crates` job warns the owner before transferring the
+//! crate.
+
+use crate::controllers::prelude::*;
+use crate::email;
+use crate::models::crate_successor::{MAX_INACTIVE_MONTHS, MIN_INACTIVE_MONTHS};
+use crate::models::{Crate, CrateSuccessor, Rights, User};
+use crate::schema::{crate_successors, crates, users};
+use crate::views::EncodableCrateSuccessor;
+
+/// Handles the `GET /crates/:crate_id/successor` route.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if user.rights(req.app(), &conn, &krate.owners(&conn)?)? < Rights::Full {
+        return Err(ErrorCode::OwnerMismatch.human("only owners can see the successor of a crate"));
+    }
+
+    let successor = CrateSuccessor::belonging_to(&krate)
+        .first::<CrateSuccessor>(&*conn)
+        .optional()?
+        .map(|successor| encodable(&conn, successor))
+        .transpose()?;
+
+    #[derive(Serialize)]
+    struct R {
+        successor: Option<EncodableCrateSuccessor>,
+    }
+    Ok(req.json(&R { successor }))
+}
+
+/// Handles the `PUT /crates/:crate_id/successor` route.
+///
+/// The request body is like
+///
+///     {"successor": {"login": "username", "inactive_months": 12}}
+///
+/// Replaces any previous successor. The successor is emailed, and has to
+/// accept the designation before it takes effect.
+pub fn designate(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Designation {
+        login: String,
+        inactive_months: i32,
+    }
+    #[derive(Deserialize)]
+    struct Request {
+        successor: Designation,
+    }
+    let designation = serde_json::from_str::<Request>(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?
+        .successor;
+    let months = designation.inactive_months;
+    if months < MIN_INACTIVE_MONTHS || months > MAX_INACTIVE_MONTHS {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "the inactivity before the transfer has to be between {} and {} months",
+            MIN_INACTIVE_MONTHS, MAX_INACTIVE_MONTHS
+        )));
+    }
+
+    let app = req.app();
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let (krate, successor, designated) = conn.transaction(|| {
+        let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+        let owners = krate.owners(&conn)?;
+        if user.rights(app, &conn, &owners)? < Rights::Full {
+            return Err(ErrorCode::OwnerMismatch
+                .human("only owners can designate the successor of a crate"));
+        }
+
+        let login = &designation.login;
+        let successor = users::table
+            .filter(users::gh_login.eq(login))
+            .first::<User>(&*conn)
+            .optional()?
+            .ok_or_else(|| {
+                ErrorCode::NotFound
+                    .human(&format_args!("could not find user with login `{}`", login))
+            })?;
+        if owners
+            .iter()
+            .any(|owner| owner.login() == successor.gh_login)
+        {
+            return Err(ErrorCode::AlreadyExists.human(&format_args!(
+                "`{}` is already an owner, successors have to be other users",
+                login
+            )));
+        }
+
+        let designated = CrateSuccessor::designate(&conn, krate.id, successor.id, user.id, months)?;
+        Ok((krate, successor, designated))
+    })?;
+
+    if let Ok(Some(address)) = successor.verified_email(&conn) {
+        let _ = email::send_successor_invitation_email(
+            &conn,
+            &address,
+            &krate.name,
+            &user.gh_login,
+            months,
+        );
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        successor: EncodableCrateSuccessor,
+    }
+    Ok(req.json(&R {
+        successor: designated.encodable(krate.name, successor.gh_login, user.gh_login.clone()),
+    }))
+}
+
+/// Handles the `DELETE /crates/:crate_id/successor` route.
+pub fn remove(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if user.rights(req.app(), &conn, &krate.owners(&conn)?)? < Rights::Full {
+        return Err(
+            ErrorCode::OwnerMismatch.human("only owners can remove the successor of a crate")
+        );
+    }
+
+    let successor = CrateSuccessor::belonging_to(&krate)
+        .first::<CrateSuccessor>(&*conn)
+        .optional()?
+        .ok_or_else(|| ErrorCode::NotFound.human("the crate has no successor"))?;
+    successor.remove(&conn)?;
+    ok_true()
+}
+
+/// Handles the `GET /me/crate_successions` route.
+///
+/// Lists the crates the current user was designated as the successor of,
+/// whether they accepted the designation yet or not.
+pub fn successions(req: &mut dyn Request) -> CargoResult<Response> {
+    let user_id = req.user()?.id;
+    let conn = req.db_conn()?;
+    let successions = CrateSuccessor::for_successor(&conn, user_id)?
+        .into_iter()
+        .map(|successor| encodable(&conn, successor))
+        .collect::<QueryResult<_>>()?;
+
+    #[derive(Serialize)]
+    struct R {
+        crate_successions: Vec<EncodableCrateSuccessor>,
+    }
+    Ok(req.json(&R {
+        crate_successions: successions,
+    }))
+}
+
+/// Handles the `PUT /me/crate_successions/:crate_id` route.
+///
+/// The request body is `{"accepted": true}` to accept being the successor of
+/// the crate, or `{"accepted": false}` to decline, which removes the
+/// designation. Either way, the owner who designated the successor is
+/// emailed.
+pub fn handle_succession(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        accepted: bool,
+    }
+    let accepted = serde_json::from_str::<Request>(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?
+        .accepted;
+
+    let user = req.user()?;
+    let crate_id = req.params()["crate_id"]
+        .parse::<i32>()
+        .map_err(|_| ErrorCode::InvalidParameter.human("invalid crate id"))?;
+    let conn = req.db_conn()?;
+
+    let successor = crate_successors::table
+        .find(crate_id)
+        .filter(crate_successors::successor_id.eq(user.id))
+        .first::<CrateSuccessor>(&*conn)
+        .optional()?
+        .ok_or_else(|| {
+            ErrorCode::UserMismatch.human("you weren't designated as the successor of this crate")
+        })?;
+    if accepted {
+        successor.accept(&conn)?;
+    } else {
+        successor.remove(&conn)?;
+    }
+
+    let crate_name = crates::table
+        .find(crate_id)
+        .select(crates::name)
+        .first::<String>(&*conn)?;
+    let owner = users::table
+        .find(successor.designated_by)
+        .first::<User>(&*conn)?;
+    if let Ok(Some(address)) = owner.verified_email(&conn) {
+        let _ = email::send_successor_response_email(
+            &conn,
+            &address,
+            &crate_name,
+            &user.gh_login,
+            accepted,
+        );
+    }
+    ok_true()
+}
+
+fn encodable(
+    conn: &PgConnection,
+    successor: CrateSuccessor,
+) -> QueryResult<EncodableCrateSuccessor> {
+    let crate_name = crates::table
+        .find(successor.crate_id)
+        .select(crates::name)
+        .first(conn)?;
+    let (login, designated_by) = successor.logins(conn)?;
+    Ok(successor.encodable(crate_name, login, designated_by))
+}
@@ -0,0 +1,108 @@
+//! Exports the metrics of the server in the Prometheus text format, see the
+//! `metrics` module
+
+use std::collections::HashMap;
+use std::io;
+
+use super::prelude::*;
+use crate::db::DieselPool;
+use crate::metrics::Exposition;
+use crate::schema::{background_job_dead_letters, background_jobs};
+use crate::util::errors::{NotFound, Unauthorized};
+use crate::util::request_header;
+
+/// Handles the `GET /metrics` route.
+///
+/// Scrapers authenticate with `Authorization: Bearer <token>`, where the
+/// token is set with the `METRICS_AUTHORIZATION_TOKEN` environment variable.
+/// The route doesn't exist if no token is set.
+pub fn prometheus(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::count_star;
+
+    let app = req.app();
+    let token = match &app.config.metrics_authorization_token {
+        Some(token) => format!("Bearer {}", token),
+        None => return Err(Box::new(NotFound)),
+    };
+    let authorization = request_header(req, "Authorization");
+    if authorization.len() != token.len()
+        || !openssl::memcmp::eq(authorization.as_bytes(), token.as_bytes())
+    {
+        return Err(Box::new(Unauthorized));
+    }
+
+    let mut out = Exposition::default();
+    app.metrics.write_to(&mut out);
+
+    // Tests share a single connection instead of a pool
+    if let DieselPool::Pool(pool) = &app.diesel_database {
+        let state = pool.state();
+        out.header(
+            "database_connections",
+            "gauge",
+            "Connections of the database pool by state",
+        );
+        let in_use = state.connections - state.idle_connections;
+        out.sample("database_connections", &[("state", "in_use")], in_use);
+        out.sample(
+            "database_connections",
+            &[("state", "idle")],
+            state.idle_connections,
+        );
+        out.header(
+            "database_pool_size",
+            "gauge",
+            "The maximum number of connections of the database pool",
+        );
+        out.sample("database_pool_size", &[], pool.max_size());
+    }
+
+    let conn = req.db_conn()?;
+    let queued = background_jobs::table
+        .group_by(background_jobs::job_type)
+        .select((background_jobs::job_type, count_star()))
+        .order(background_jobs::job_type)
+        .load::<(String, i64)>(&*conn)?;
+    let failed = background_jobs::table
+        .filter(background_jobs::retries.gt(0))
+        .group_by(background_jobs::job_type)
+        .select((background_jobs::job_type, count_star()))
+        .order(background_jobs::job_type)
+        .load::<(String, i64)>(&*conn)?;
+    let dead_letters = background_job_dead_letters::table
+        .group_by(background_job_dead_letters::job_type)
+        .select((background_job_dead_letters::job_type, count_star()))
+        .order(background_job_dead_letters::job_type)
+        .load::<(String, i64)>(&*conn)?;
+    for (name, help, counts) in &[
+        ("background_jobs", "Jobs in the queue by type", queued),
+        (
+            "background_jobs_failed",
+            "Jobs in the queue that failed at least once by type",
+            failed,
+        ),
+        (
+            "background_job_dead_letters",
+            "Jobs that were given up on by type",
+            dead_letters,
+        ),
+    ] {
+        out.header(name, "gauge", help);
+        for (job_type, count) in counts {
+            out.sample(name, &[("job_type", job_type.as_str())], count);
+        }
+    }
+
+    let body = out.into_string();
+    let mut headers = HashMap::new();
+    headers.insert(
+        "Content-Type".to_string(),
+        vec!["text/plain; version=0.0.4".to_string()],
+    );
+    headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+    Ok(Response {
+        status: (200, "OK"),
+        headers,
+        body: Box::new(io::Cursor::new(body.into_bytes())),
+    })
+}
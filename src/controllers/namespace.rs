@@ -0,0 +1,100 @@
+//! Endpoints for namespaces of crates with names like `org::name`
+//!
+//! Namespaces are only available if `Config::crate_namespaces` is set. See
+//! `models::Namespace` for who can publish crates in a namespace.
+
+use serde_json;
+
+use crate::controllers::prelude::*;
+use crate::models::krate::{canon_crate_name, canonical_name, NAMESPACE_SEPARATOR};
+use crate::models::{Crate, Namespace, Team, Visibility};
+use crate::schema::{crates, teams};
+use crate::util::escape_like;
+use crate::views::EncodableNamespace;
+
+/// Handles the `GET /namespaces/:namespace_id` route.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    let name = &req.params()["namespace_id"];
+    let conn = req.db_conn()?;
+    let namespace = Namespace::by_name(name).first::<Namespace>(&*conn)?;
+    let team = namespace.team(&conn)?;
+
+    let pattern = format!(
+        "{}{}%",
+        escape_like(&canonical_name(&namespace.name)),
+        NAMESPACE_SEPARATOR
+    );
+    let crates = crates::table
+        .filter(canon_crate_name(crates::name).like(pattern))
+        .filter(crates::visibility.eq(Visibility::Public))
+        .count()
+        .get_result(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        namespace: EncodableNamespace,
+    }
+    Ok(req.json(&R {
+        namespace: namespace.encodable(&team, crates),
+    }))
+}
+
+/// Handles the `PUT /namespaces/:namespace_id` route.
+///
+/// The request body has the format
+///
+///     {"team": "github:org:team"}
+///
+/// Only members of the team can create a namespace for it.
+pub fn create(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        team: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let app = req.app();
+    if !app.config.crate_namespaces {
+        return Err(ErrorCode::InvalidRequest.human("namespaces aren't enabled on this registry"));
+    }
+    let user = req.user()?;
+    let name = &req.params()["namespace_id"];
+    if let Some(reason) = Crate::invalid_namespace_reason(name) {
+        return Err(ErrorCode::InvalidParameter.human(&reason));
+    }
+    let conn = req.db_conn()?;
+
+    // Teams that are already known don't need to be looked up on GitHub
+    let login = request.team.to_lowercase();
+    let team = match teams::table
+        .filter(teams::login.eq(&login))
+        .first::<Team>(&*conn)
+        .optional()?
+    {
+        Some(team) => team,
+        None if login.contains(':') => Team::create_or_update(app, &conn, &login, user)?,
+        None => {
+            return Err(ErrorCode::InvalidField
+                .human("namespaces belong to a team, like `github:org:team`"))
+        }
+    };
+    if !team.contains_user(app, &conn, user)? {
+        return Err(ErrorCode::TeamMembershipRequired
+            .human("only members of a team can create a namespace for it"));
+    }
+
+    let namespace = Namespace::create(&conn, name, &team, user.id)?.ok_or_else(|| {
+        ErrorCode::AlreadyExists.human(&format_args!("namespace `{}` already exists", name))
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        namespace: EncodableNamespace,
+    }
+    Ok(req.json(&R {
+        namespace: namespace.encodable(&team, 0),
+    }))
+}
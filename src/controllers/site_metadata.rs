@@ -18,3 +18,19 @@ pub fn show_deployed_sha(req: &mut dyn Request) -> CargoResult<Response> {
         commit: &deployed_sha[..],
     }))
 }
+
+/// Handles the `GET /registry` route.
+///
+/// Returns the name, support contact and policies of this registry, so that
+/// the frontend and other clients don't have to hard-code them.
+pub fn show_registry(req: &mut dyn Request) -> CargoResult<Response> {
+    use crate::branding::Branding;
+
+    #[derive(Serialize)]
+    struct R<'a> {
+        registry: &'a Branding,
+    }
+    Ok(req.json(&R {
+        registry: &req.app().config.branding,
+    }))
+}
@@ -1,8 +1,9 @@
+use crate::controllers::helpers::owned_crates;
 use crate::controllers::prelude::*;
 
-use crate::models::Team;
+use crate::models::{OwnerKind, Team, TeamMembership};
 use crate::schema::teams;
-use crate::views::EncodableTeam;
+use crate::views::{EncodableDashboardCrate, EncodableTeam};
 
 /// Handles the `GET /teams/:team_id` route.
 pub fn show_team(req: &mut dyn Request) -> CargoResult<Response> {
@@ -20,3 +21,67 @@ pub fn show_team(req: &mut dyn Request) -> CargoResult<Response> {
         team: team.encodable(),
     }))
 }
+
+/// Handles the `POST /teams/:team_id/resync` route.
+///
+/// Drops the cached GitHub memberships of the team, so that changes to the
+/// team on GitHub, like removed members, take effect right away instead of
+/// after `Config::team_membership_ttl`. Only members of the team can do this.
+pub fn resync_team(req: &mut dyn Request) -> CargoResult<Response> {
+    use self::teams::dsl::{login, teams};
+
+    let name = &req.params()["team_id"];
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let team = teams.filter(login.eq(name)).first::<Team>(&*conn)?;
+    if !team.contains_user(req.app(), &conn, user)? {
+        return Err(ErrorCode::TeamMembershipRequired.human("only members of a team can resync it"));
+    }
+
+    TeamMembership::expire_team(&conn, team.id)?;
+    ok_true()
+}
+
+/// Handles the `GET /teams/:org/:team/crates` route.
+///
+/// Lists the crates the GitHub team `org/team` owns, with their latest
+/// version and downloads, and the download totals of all of them. Only
+/// members of the team can see this, and their membership is checked against
+/// the cached memberships before asking GitHub.
+pub fn crates(req: &mut dyn Request) -> CargoResult<Response> {
+    let login = format!("github:{}:{}", req.params()["org"], req.params()["team"]).to_lowercase();
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let team = teams::table
+        .filter(teams::login.eq(&login))
+        .first::<Team>(&*conn)?;
+    if !team.contains_user(req.app(), &conn, user)? {
+        return Err(
+            ErrorCode::TeamMembershipRequired.human("only members of a team can see its crates")
+        );
+    }
+
+    let crates = owned_crates(&conn, team.id, OwnerKind::Team)?;
+    let downloads = crates.iter().map(|krate| i64::from(krate.downloads)).sum();
+    let recent_downloads = crates.iter().map(|krate| krate.recent_downloads).sum();
+
+    #[derive(Serialize)]
+    struct R {
+        team: EncodableTeam,
+        crates: Vec<EncodableDashboardCrate>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        downloads: i64,
+        recent_downloads: i64,
+    }
+    Ok(req.json(&R {
+        team: team.encodable(),
+        crates,
+        meta: Meta {
+            downloads,
+            recent_downloads,
+        },
+    }))
+}
@@ -1,11 +1,13 @@
 use super::prelude::*;
 
 use crate::middleware::current_user::AuthenticationSource;
-use crate::models::ApiToken;
-use crate::schema::api_tokens;
+use crate::models::{ApiToken, ApiTokenUsage};
+use crate::schema::{api_token_usage, api_tokens};
+use crate::util::cidr::Cidr;
 use crate::util::{bad_request, read_fill, ChainError};
-use crate::views::EncodableApiTokenWithToken;
+use crate::views::{EncodableApiTokenUsage, EncodableApiTokenWithToken};
 
+use chrono::{Duration, Utc};
 use serde_json as json;
 
 /// Handles the `GET /me/tokens` route.
@@ -27,6 +29,13 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
     #[derive(Deserialize, Serialize)]
     struct NewApiToken {
         name: String,
+        /// How many requests the token can make per day, unlimited if unset
+        #[serde(default)]
+        daily_quota: Option<i32>,
+        /// The IP addresses and CIDR ranges the token can be used from,
+        /// anywhere if empty
+        #[serde(default)]
+        allowed_ips: Vec<String>,
     }
 
     /// The incoming serialization format for the `ApiToken` model.
@@ -35,11 +44,7 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
         api_token: NewApiToken,
     }
 
-    if req.authentication_source()? != AuthenticationSource::SessionCookie {
-        return Err(bad_request(
-            "cannot use an API token to create a new API token",
-        ));
-    }
+    ensure_session_cookie(req)?;
 
     let max_size = 2000;
     let length = req
@@ -53,17 +58,66 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
     let mut json = vec![0; length as usize];
     read_fill(req.body(), &mut json)?;
 
-    let json =
-        String::from_utf8(json).map_err(|_| bad_request(&"json body was not valid utf-8"))?;
+    let json = String::from_utf8(json)
+        .map_err(|_| ErrorCode::InvalidJson.bad_request(&"json body was not valid utf-8"))?;
 
-    let new: NewApiTokenRequest = json::from_str(&json)
-        .map_err(|e| bad_request(&format!("invalid new token request: {:?}", e)))?;
+    let new: NewApiTokenRequest = json::from_str(&json).map_err(|e| {
+        ErrorCode::InvalidJson.bad_request(&format!("invalid new token request: {:?}", e))
+    })?;
 
     let name = &new.api_token.name;
     if name.is_empty() {
-        return Err(bad_request("name must have a value"));
+        return Err(ErrorCode::InvalidField.bad_request("name must have a value"));
     }
+    let daily_quota = new.api_token.daily_quota;
+    if daily_quota.map_or(false, |quota| quota < 1) {
+        return Err(ErrorCode::InvalidField.bad_request("daily_quota must be positive"));
+    }
+
+    let allowed_ips = new
+        .api_token
+        .allowed_ips
+        .iter()
+        .map(|range| range.parse::<Cidr>().map(|cidr| cidr.to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorCode::InvalidField.bad_request(&e))?;
+
+    insert_token(req, name, daily_quota, &allowed_ips)
+}
 
+/// Handles the `PUT /me/tokens/cargo_login` route.
+///
+/// `cargo login` asks the user to paste a token found on the registry's `/me`
+/// page. This creates a token for that purpose without requiring the user to
+/// pick a name, naming it after `cargo login` and the time it was created.
+pub fn cargo_login(req: &mut dyn Request) -> CargoResult<Response> {
+    ensure_session_cookie(req)?;
+
+    let name = format!(
+        "cargo login ({})",
+        Utc::now().naive_utc().format("%Y-%m-%d %H:%M:%S")
+    );
+    insert_token(req, &name, None, &[])
+}
+
+/// API tokens can only be created from a browser session, never by another token.
+fn ensure_session_cookie(req: &dyn Request) -> CargoResult<()> {
+    if req.authentication_source()? != AuthenticationSource::SessionCookie {
+        return Err(bad_request(
+            "cannot use an API token to create a new API token",
+        ));
+    }
+    Ok(())
+}
+
+/// Creates a new token with the given name and restrictions for the current
+/// user and returns it, including its secret value, as the response.
+fn insert_token(
+    req: &dyn Request,
+    name: &str,
+    daily_quota: Option<i32>,
+    allowed_ips: &[String],
+) -> CargoResult<Response> {
     let user = req.user()?;
     let conn = req.db_conn()?;
 
@@ -78,7 +132,7 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
         )));
     }
 
-    let api_token = ApiToken::insert(&*conn, user.id, name)?;
+    let api_token = ApiToken::insert_restricted(&*conn, user.id, name, daily_quota, allowed_ips)?;
 
     #[derive(Serialize)]
     struct R {
@@ -91,9 +145,7 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
 
 /// Handles the `DELETE /me/tokens/:id` route.
 pub fn revoke(req: &mut dyn Request) -> CargoResult<Response> {
-    let id = req.params()["id"]
-        .parse::<i32>()
-        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+    let id = token_id(req)?;
 
     diesel::update(ApiToken::belonging_to(req.user()?).find(id))
         .set(api_tokens::revoked.eq(true))
@@ -103,3 +155,46 @@ pub fn revoke(req: &mut dyn Request) -> CargoResult<Response> {
     struct R {}
     Ok(req.json(&R {}))
 }
+
+/// Handles the `GET /me/tokens/:id/usage` route.
+///
+/// Lists how many requests the token made on each of the last 30 days it was
+/// used on, oldest first.
+pub fn usage(req: &mut dyn Request) -> CargoResult<Response> {
+    let id = token_id(req)?;
+    let conn = req.db_conn()?;
+    let token = ApiToken::belonging_to(req.user()?)
+        .find(id)
+        .first::<ApiToken>(&*conn)?;
+
+    let cutoff_date = Utc::today().naive_utc() - Duration::days(29);
+    let usage = ApiTokenUsage::belonging_to(&token)
+        .filter(api_token_usage::date.ge(cutoff_date))
+        .order(api_token_usage::date)
+        .load::<ApiTokenUsage>(&*conn)?
+        .into_iter()
+        .map(ApiTokenUsage::encodable)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        api_token_usage: Vec<EncodableApiTokenUsage>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        daily_quota: Option<i32>,
+    }
+    Ok(req.json(&R {
+        api_token_usage: usage,
+        meta: Meta {
+            daily_quota: token.daily_quota,
+        },
+    }))
+}
+
+fn token_id(req: &dyn Request) -> CargoResult<i32> {
+    req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| ErrorCode::InvalidParameter.bad_request(&format!("invalid token id: {:?}", e)))
+}
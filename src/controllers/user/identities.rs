@@ -0,0 +1,77 @@
+//! Endpoints for linking the identities of several providers to one account,
+//! so that users can log in with any of them, see `models::Identity`
+
+use super::session::{authenticate, begin_login};
+use crate::controllers::prelude::*;
+use crate::models::Identity;
+use crate::views::EncodableIdentity;
+
+/// Handles the `GET /me/identities` route.
+pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let identities = Identity::for_user(&conn, user.id)?
+        .into_iter()
+        .map(Identity::encodable)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        identities: Vec<EncodableIdentity>,
+    }
+    Ok(req.json(&R { identities }))
+}
+
+/// Handles the `GET /me/identities/:provider/authorize_url` route.
+///
+/// Like `GET /authorize_url/:provider`, but starts linking the identity the
+/// user authorizes to the account of the current user instead of logging in.
+pub fn authorize_url(req: &mut dyn Request) -> CargoResult<Response> {
+    req.user()?;
+    let provider = req.params()["provider"].to_string();
+    begin_login(req, &provider, state_key(&provider))
+}
+
+/// Handles the `PUT /me/identities/:provider` route.
+///
+/// Called with the `code` and `state` query parameters the provider sent the
+/// user back with, like `GET /authorize/:provider`. Fails if the identity is
+/// already linked to another account, or if the account is already linked to
+/// another identity of the provider.
+pub fn link(req: &mut dyn Request) -> CargoResult<Response> {
+    req.user()?;
+    let provider = req.params()["provider"].to_string();
+    let (provider_user, _) = authenticate(req, &provider, &state_key(&provider))?;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let identity = Identity::link(
+        &conn,
+        user,
+        &provider,
+        &provider_user.id,
+        &provider_user.login,
+    )?;
+
+    #[derive(Serialize)]
+    struct R {
+        identity: EncodableIdentity,
+    }
+    Ok(req.json(&R {
+        identity: identity.encodable(),
+    }))
+}
+
+/// Handles the `DELETE /me/identities/:provider` route.
+pub fn unlink(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    Identity::unlink(&conn, user, &req.params()["provider"])?;
+    ok_true()
+}
+
+/// The session key of the OAuth state while linking an identity, which is
+/// kept apart from the state of logging in.
+fn state_key(provider: &str) -> String {
+    format!("{}_link_state", provider)
+}
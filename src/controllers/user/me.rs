@@ -2,14 +2,18 @@ use std::collections::HashMap;
 
 use crate::controllers::prelude::*;
 
+use crate::controllers::helpers::pagination::PaginationOptions;
 use crate::controllers::helpers::*;
-use crate::email;
+use crate::email::{self, Unsubscribe};
 use crate::util::bad_request;
 use crate::util::errors::CargoError;
 
-use crate::models::{CrateOwner, Email, Follow, NewEmail, OwnerKind, User, Version};
-use crate::schema::{crate_owners, crates, emails, follows, users, versions};
-use crate::views::{EncodableMe, EncodableVersion, OwnedCrate};
+use crate::models::{CrateOwner, CrateOwnerInvitation, Email, NewEmail, OwnerKind, User, Version};
+use crate::schema::{
+    crate_owner_invitations, crate_owners, crates, emails, update_digest_subscriptions, users,
+    versions,
+};
+use crate::views::{EncodableDashboard, EncodableMe, EncodableVersion, OwnedCrate};
 
 /// Handles the `GET /me` route.
 pub fn me(req: &mut dyn Request) -> CargoResult<Response> {
@@ -53,6 +57,11 @@ pub fn me(req: &mut dyn Request) -> CargoResult<Response> {
         })
         .collect();
 
+    let update_digest = diesel::select(diesel::dsl::exists(
+        update_digest_subscriptions::table.find(user_id),
+    ))
+    .get_result(&*conn)?;
+
     let verified = verified.unwrap_or(false);
     let verification_sent = verified || verification_sent;
     let user = User { email, ..user };
@@ -60,31 +69,103 @@ pub fn me(req: &mut dyn Request) -> CargoResult<Response> {
     Ok(req.json(&EncodableMe {
         user: user.encodable_private(verified, verification_sent),
         owned_crates,
+        update_digest,
+    }))
+}
+
+/// Handles the `GET /me/dashboard` route.
+///
+/// Returns everything the dashboard of the current user shows in a single
+/// response: the crates they own with their latest version, downloads and
+/// number of versions with advisories, their pending owner invitations and
+/// the state of their email address.
+pub fn dashboard(req: &mut dyn Request) -> CargoResult<Response> {
+    let user_id = req.user()?.id;
+    let conn = req.read_conn()?;
+
+    let (email, verified, verification_sent) = emails::table
+        .filter(emails::user_id.eq(user_id))
+        .select((
+            emails::email,
+            emails::verified,
+            emails::token_generated_at.is_not_null(),
+        ))
+        .first::<(String, bool, bool)>(&*conn)
+        .optional()?
+        .map_or((None, false, false), |(email, verified, sent)| {
+            (Some(email), verified, verified || sent)
+        });
+
+    let crates = owned_crates(&conn, user_id, OwnerKind::User)?;
+
+    let crate_owner_invitations = crate_owner_invitations::table
+        .filter(crate_owner_invitations::invited_user_id.eq(user_id))
+        .load::<CrateOwnerInvitation>(&*conn)?
+        .into_iter()
+        .map(|invitation| invitation.encodable(&conn))
+        .collect();
+
+    Ok(req.json(&EncodableDashboard {
+        crates,
+        crate_owner_invitations,
+        email,
+        email_verified: verified,
+        email_verification_sent: verification_sent,
     }))
 }
 
 /// Handles the `GET /me/updates` route.
+///
+/// Lists the versions of followed crates, and the versions of crates with
+/// followed keywords or in followed categories that were published after the
+/// keyword or category was followed.
+///
+/// Supports both `page` and `seek` pagination, the latter being much faster
+/// for users following many crates. Pass an empty `seek` to get the first page
+/// along with a cursor for the next one in `meta.next_page`.
 pub fn updates(req: &mut dyn Request) -> CargoResult<Response> {
-    use diesel::dsl::any;
+    use chrono::NaiveDateTime;
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Integer};
 
     let user = req.user()?;
-    let conn = req.db_conn()?;
-
-    let followed_crates = Follow::belonging_to(user).select(follows::crate_id);
-    let data = versions::table
+    let conn = req.read_conn()?;
+    let options = PaginationOptions::new_with_seek(&req.query())?;
+
+    let followed = sql::<Bool>("EXISTS (SELECT 1 FROM followed_crates(")
+        .bind::<Integer, _>(user.id)
+        .sql(
+            ") followed WHERE followed.crate_id = crates.id \
+             AND versions.created_at > followed.since)",
+        );
+    let mut query = versions::table
         .inner_join(crates::table)
         .left_outer_join(users::table)
-        .filter(crates::id.eq(any(followed_crates)))
-        .order(versions::created_at.desc())
+        .filter(followed)
+        .order((versions::created_at.desc(), versions::id.desc()))
         .select((
             versions::all_columns,
             crates::name,
             users::all_columns.nullable(),
         ))
-        .paginate(&req.query())?
+        .into_boxed();
+
+    if let Some((created_at, id)) = options.seek_after::<(NaiveDateTime, i32)>()? {
+        query = query.filter(
+            versions::created_at
+                .lt(created_at)
+                .or(versions::created_at.eq(created_at).and(versions::id.lt(id))),
+        );
+    }
+
+    let data = query
+        .paginate_with_options(options)
         .load::<(Version, String, Option<User>)>(&*conn)?;
 
-    let more = data.next_page_params().is_some();
+    let next_page = data
+        .next_page_params(|(version, _, _)| (version.created_at, version.id))
+        .map(|p| req.query_with_params(p));
+    let more = next_page.is_some();
 
     let versions = data
         .into_iter()
@@ -99,10 +180,11 @@ pub fn updates(req: &mut dyn Request) -> CargoResult<Response> {
     #[derive(Serialize)]
     struct Meta {
         more: bool,
+        next_page: Option<String>,
     }
     Ok(req.json(&R {
         versions,
-        meta: Meta { more },
+        meta: Meta { more, next_page },
     }))
 }
 
@@ -120,7 +202,7 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
 
     // need to check if current user matches user to be updated
     if &user.id.to_string() != name {
-        return Err(human("current user does not match requested user"));
+        return Err(ErrorCode::UserMismatch.human("current user does not match requested user"));
     }
 
     #[derive(Deserialize)]
@@ -133,18 +215,18 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
         email: Option<String>,
     }
 
-    let user_update: UserUpdate =
-        serde_json::from_str(&body).map_err(|_| human("invalid json request"))?;
+    let user_update: UserUpdate = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
 
     if user_update.user.email.is_none() {
-        return Err(human("empty email rejected"));
+        return Err(ErrorCode::InvalidEmail.human("empty email rejected"));
     }
 
     let user_email = user_update.user.email.unwrap();
     let user_email = user_email.trim();
 
     if user_email == "" {
-        return Err(human("empty email rejected"));
+        return Err(ErrorCode::InvalidEmail.human("empty email rejected"));
     }
 
     conn.transaction::<_, Box<dyn CargoError>, _>(|| {
@@ -166,7 +248,7 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
             .get_result::<String>(&*conn)
             .map_err(|_| human("Error in creating token"))?;
 
-        crate::email::send_user_confirm_email(user_email, &user.gh_login, &token);
+        crate::email::send_user_confirm_email(&conn, user_email, &user.gh_login, &token);
 
         Ok(())
     })?;
@@ -190,7 +272,7 @@ pub fn confirm_user_email(req: &mut dyn Request) -> CargoResult<Response> {
         .execute(&*conn)?;
 
     if updated_rows == 0 {
-        return Err(bad_request("Email belonging to token not found."));
+        return Err(ErrorCode::NotFound.bad_request("Email belonging to token not found."));
     }
 
     #[derive(Serialize)]
@@ -211,16 +293,16 @@ pub fn regenerate_token_and_send(req: &mut dyn Request) -> CargoResult<Response>
 
     // need to check if current user matches user to be updated
     if &user.id != name {
-        return Err(human("current user does not match requested user"));
+        return Err(ErrorCode::UserMismatch.human("current user does not match requested user"));
     }
 
     conn.transaction(|| {
         let email = update(Email::belonging_to(user))
             .set(emails::token.eq(sql("DEFAULT")))
             .get_result::<Email>(&*conn)
-            .map_err(|_| bad_request("Email could not be found"))?;
+            .map_err(|_| ErrorCode::NotFound.bad_request("Email could not be found"))?;
 
-        email::try_send_user_confirm_email(&email.email, &user.gh_login, &email.token)
+        email::try_send_user_confirm_email(&conn, &email.email, &user.gh_login, &email.token)
             .map_err(|_| bad_request("Error in sending email"))
     })?;
 
@@ -245,7 +327,7 @@ pub fn update_email_notifications(req: &mut dyn Request) -> CargoResult<Response
     let mut body = String::new();
     req.body().read_to_string(&mut body)?;
     let updates: HashMap<i32, bool> = serde_json::from_str::<Vec<CrateEmailNotifications>>(&body)
-        .map_err(|_| bad_request("invalid json request"))?
+        .map_err(|_| ErrorCode::InvalidJson.bad_request("invalid json request"))?
         .iter()
         .map(|c| (c.id, c.email_notifications))
         .collect();
@@ -287,3 +369,79 @@ pub fn update_email_notifications(req: &mut dyn Request) -> CargoResult<Response
     }
     Ok(req.json(&R { ok: true }))
 }
+
+/// Handles the `PUT /me/update_digest` route.
+///
+/// Subscribes to a weekly email with the new releases listed at
+/// `GET /me/updates`. The email is only sent to verified addresses, and
+/// whether the user is subscribed is part of `GET /me`.
+pub fn subscribe_update_digest(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    diesel::insert_into(update_digest_subscriptions::table)
+        .values(update_digest_subscriptions::user_id.eq(user.id))
+        .on_conflict_do_nothing()
+        .execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `DELETE /me/update_digest` route.
+pub fn unsubscribe_update_digest(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    diesel::delete(update_digest_subscriptions::table.find(user.id)).execute(&*conn)?;
+
+    ok_true()
+}
+
+/// Handles the `PUT /unsubscribe/:unsubscribe_token` route.
+///
+/// Unsubscribes from the update digest through the link in digests sent
+/// before they had signed links, see `unsubscribe_email`.
+pub fn unsubscribe_update_digest_by_token(req: &mut dyn Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let token = &req.params()["unsubscribe_token"];
+
+    let deleted_rows = diesel::delete(
+        update_digest_subscriptions::table
+            .filter(update_digest_subscriptions::unsubscribe_token.eq(token)),
+    )
+    .execute(&*conn)?;
+
+    if deleted_rows == 0 {
+        return Err(ErrorCode::NotFound.bad_request("Subscription belonging to token not found."));
+    }
+
+    ok_true()
+}
+
+/// Handles the `GET /email/unsubscribe/:token` and
+/// `POST /email/unsubscribe/:token` routes.
+///
+/// Turns off the notification setting named by the signed token in the link
+/// of a notification email, without having to sign in. Mail clients use the
+/// `POST` route for their one-click unsubscribe button.
+pub fn unsubscribe_email(req: &mut dyn Request) -> CargoResult<Response> {
+    let unsubscribe = Unsubscribe::from_token(&req.params()["token"], &req.app().session_key)
+        .ok_or_else(|| ErrorCode::InvalidParameter.bad_request("invalid unsubscribe token"))?;
+    let conn = req.db_conn()?;
+
+    match unsubscribe {
+        Unsubscribe::UpdateDigest { user_id } => {
+            diesel::delete(update_digest_subscriptions::table.find(user_id)).execute(&*conn)?;
+        }
+        Unsubscribe::CrateNotifications { user_id, crate_ids } => {
+            diesel::update(
+                crate_owners::table
+                    .filter(crate_owners::owner_id.eq(user_id))
+                    .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                    .filter(crate_owners::crate_id.eq_any(crate_ids)),
+            )
+            .set(crate_owners::email_notifications.eq(false))
+            .execute(&*conn)?;
+        }
+    }
+
+    ok_true()
+}
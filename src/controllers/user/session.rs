@@ -1,12 +1,15 @@
 use crate::controllers::prelude::*;
 
-use crate::github;
 use conduit_cookie::RequestSession;
 use oauth2::{prelude::*, AuthorizationCode, TokenResponse};
 
-use crate::models::{NewUser, User};
+use crate::app::App;
+use crate::identity::{self, ProviderUser, GITHUB};
+use crate::middleware::current_user::start_session;
+use crate::models::{Identity, NewUser, Session, User};
 use crate::schema::users;
-use crate::util::errors::{CargoError, ReadOnlyMode};
+use crate::util::errors::{internal, CargoError, NotFound, ReadOnlyMode};
+use crate::views::EncodableSession;
 
 /// Handles the `GET /authorize_url` route.
 ///
@@ -24,13 +27,32 @@ use crate::util::errors::{CargoError, ReadOnlyMode};
 /// }
 /// ```
 pub fn github_authorize(req: &mut dyn Request) -> CargoResult<Response> {
-    let (url, state) = req
-        .app()
-        .github
+    begin_login(req, GITHUB, state_key(GITHUB))
+}
+
+/// Handles the `GET /authorize_url/:provider` route.
+///
+/// Like `GET /authorize_url`, but for any of the identity providers enabled
+/// on this registry, see `identity`.
+pub fn authorize_url(req: &mut dyn Request) -> CargoResult<Response> {
+    let provider = req.params()["provider"].to_string();
+    begin_login(req, &provider, state_key(&provider))
+}
+
+/// Responds with the URL that sends the user to `provider` to authorize the
+/// registry, and keeps the state of the OAuth flow in the session under
+/// `state_key`.
+pub(super) fn begin_login(
+    req: &mut dyn Request,
+    provider: &str,
+    state_key: String,
+) -> CargoResult<Response> {
+    let app = req.app().clone();
+    let (url, state) = identity_provider(&app, provider)?
+        .oauth_client()
         .authorize_url(oauth2::CsrfToken::new_random);
     let state = state.secret().to_string();
-    req.session()
-        .insert("github_oauth_state".to_string(), state.clone());
+    req.session().insert(state_key, state.clone());
 
     #[derive(Serialize)]
     struct R {
@@ -72,6 +94,49 @@ pub fn github_authorize(req: &mut dyn Request) -> CargoResult<Response> {
 /// }
 /// ```
 pub fn github_access_token(req: &mut dyn Request) -> CargoResult<Response> {
+    finish_login(req, GITHUB)
+}
+
+/// Handles the `GET /authorize/:provider` route.
+///
+/// Like `GET /authorize`, but for any of the identity providers enabled on
+/// this registry. The logins of users of providers other than GitHub are
+/// qualified with the name of the provider, like `gitlab:foobar`.
+pub fn access_token(req: &mut dyn Request) -> CargoResult<Response> {
+    let provider = req.params()["provider"].to_string();
+    finish_login(req, &provider)
+}
+
+fn finish_login(req: &mut dyn Request, provider: &str) -> CargoResult<Response> {
+    let (provider_user, access_token) = authenticate(req, provider, &state_key(provider))?;
+    let user = {
+        let conn = req.db_conn()?;
+        let linked = Identity::find(&conn, provider, &provider_user.id)?
+            .map(|identity| users::table.find(identity.user_id).first::<User>(&*conn))
+            .transpose()?;
+        match linked {
+            // Identities linked to an account that signed up with another
+            // provider log into that account without changing its details
+            Some(ref user) if user.provider != provider => user.clone(),
+            _ => save_user(provider, &provider_user, &access_token, &conn)?,
+        }
+    };
+    let session = start_session(req, user.id)?;
+    req.mut_extensions().insert(session);
+    req.mut_extensions().insert(user);
+    super::me::me(req)
+}
+
+/// Finishes the OAuth flow started by `begin_login`, and returns the user
+/// the provider authenticated along with their access token.
+pub(super) fn authenticate(
+    req: &mut dyn Request,
+    provider: &str,
+    state_key: &str,
+) -> CargoResult<(ProviderUser, String)> {
+    let app = req.app().clone();
+    let identity_provider = identity_provider(&app, provider)?;
+
     // Parse the url query
     let mut query = req.query();
     let code = query.remove("code").unwrap_or_default();
@@ -80,73 +145,153 @@ pub fn github_access_token(req: &mut dyn Request) -> CargoResult<Response> {
     // Make sure that the state we just got matches the session state that we
     // should have issued earlier.
     {
-        let session_state = req.session().remove(&"github_oauth_state".to_string());
+        let session_state = req.session().remove(&state_key.to_string());
         let session_state = session_state.as_ref().map(|a| &a[..]);
         if Some(&state[..]) != session_state {
-            return Err(human("invalid state parameter"));
+            return Err(ErrorCode::InvalidParameter.human("invalid state parameter"));
         }
     }
 
-    // Fetch the access token from github using the code we just got
+    // Fetch the access token from the provider using the code we just got
 
     let code = AuthorizationCode::new(code);
-    let token = req
-        .app()
-        .github
+    let token = identity_provider
+        .oauth_client()
         .exchange_code(code)
         .map_err(|s| human(&s))?;
     let token = token.access_token();
-    let ghuser = github::github_api::<GithubUser>(req.app(), "/user", token)?;
-    let user = ghuser.save_to_database(&token.secret(), &*req.db_conn()?)?;
-    req.session()
-        .insert("user_id".to_string(), user.id.to_string());
-    req.mut_extensions().insert(user);
-    super::me::me(req)
+    let provider_user = identity_provider.user(app.http_client(), token)?;
+    Ok((provider_user, token.secret().to_string()))
+}
+
+fn identity_provider<'a>(
+    app: &'a App,
+    provider: &str,
+) -> CargoResult<&'a dyn identity::IdentityProvider> {
+    app.identity_provider(provider).ok_or_else(|| {
+        ErrorCode::InvalidParameter.human(&format_args!(
+            "logging in with `{}` isn't supported by this registry",
+            provider
+        ))
+    })
 }
 
-#[derive(Deserialize)]
-struct GithubUser {
-    email: Option<String>,
-    name: Option<String>,
-    login: String,
-    id: i32,
-    avatar_url: Option<String>,
+/// The session key of the OAuth state, `github_oauth_state` for GitHub.
+fn state_key(provider: &str) -> String {
+    format!("{}_oauth_state", provider)
 }
 
-impl GithubUser {
-    fn save_to_database(&self, access_token: &str, conn: &PgConnection) -> CargoResult<User> {
-        NewUser::new(
-            self.id,
-            &self.login,
-            self.email.as_ref().map(|s| &s[..]),
-            self.name.as_ref().map(|s| &s[..]),
-            self.avatar_url.as_ref().map(|s| &s[..]),
-            access_token,
-        )
-        .create_or_update(conn)
+fn save_user(
+    provider: &str,
+    provider_user: &ProviderUser,
+    access_token: &str,
+    conn: &PgConnection,
+) -> CargoResult<User> {
+    let login = identity::qualified_login(provider, &provider_user.login);
+    let gh_id = if provider == GITHUB {
+        provider_user
+            .id
+            .parse::<i32>()
+            .map_err(|e| internal(&format_args!("invalid GitHub user ID: {}", e)))?
+    } else {
+        -1
+    };
+    let new_user = NewUser::new(
+        gh_id,
+        &login,
+        provider_user.email.as_ref().map(|s| &s[..]),
+        provider_user.name.as_ref().map(|s| &s[..]),
+        provider_user.avatar.as_ref().map(|s| &s[..]),
+        access_token,
+    );
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let user = if provider == GITHUB {
+            new_user.create_or_update(conn)?
+        } else {
+            new_user.create_or_update_for_provider(conn, provider, &provider_user.id)?
+        };
+        Identity::record(
+            conn,
+            user.id,
+            provider,
+            &provider_user.id,
+            &provider_user.login,
+        )?;
+        Ok(user)
+    });
+
+    result
         .map_err(Into::into)
         .or_else(|e: Box<dyn CargoError>| {
             // If we're in read only mode, we can't update their details
             // just look for an existing user
             if e.is::<ReadOnlyMode>() {
-                users::table
-                    .filter(users::gh_id.eq(self.id))
-                    .first(conn)
-                    .optional()?
-                    .ok_or(e)
+                let existing = if provider == GITHUB {
+                    users::table.filter(users::gh_id.eq(gh_id)).first(conn)
+                } else {
+                    users::table
+                        .filter(users::provider.eq(provider))
+                        .filter(users::provider_user_id.eq(&provider_user.id))
+                        .first(conn)
+                };
+                existing.optional()?.ok_or(e)
             } else {
                 Err(e)
             }
         })
-    }
 }
 
 /// Handles the `GET /logout` route.
+///
+/// Revokes the current session, so that the session cookie can't be used
+/// anymore even if it was copied.
 pub fn logout(req: &mut dyn Request) -> CargoResult<Response> {
+    if let Some(session) = req.extensions().find::<Session>() {
+        Session::revoke(&*req.db_conn()?, session.user_id, session.id)?;
+    }
+    req.session().remove(&"session_token".to_string());
     req.session().remove(&"user_id".to_string());
     Ok(req.json(&true))
 }
 
+/// Handles the `GET /me/sessions` route.
+///
+/// Lists the browser sessions the current user is logged in with, along with
+/// the IP address and user agent they logged in from.
+pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let current = req.extensions().find::<Session>().map(|session| session.id);
+    let sessions = Session::for_user(&conn, user.id)?
+        .into_iter()
+        .map(|session| {
+            let is_current = Some(session.id) == current;
+            session.encodable(is_current)
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        sessions: Vec<EncodableSession>,
+    }
+    Ok(req.json(&R { sessions }))
+}
+
+/// Handles the `DELETE /me/sessions/:id` route.
+///
+/// Revokes a session of the current user, which logs out the browser that
+/// uses it. Revoking the current session logs the user out.
+pub fn revoke(req: &mut dyn Request) -> CargoResult<Response> {
+    let id = req.params()["id"].parse::<i32>().map_err(|e| {
+        ErrorCode::InvalidParameter.bad_request(&format!("invalid session id: {:?}", e))
+    })?;
+
+    if !Session::revoke(&*req.db_conn()?, req.user()?.id, id)? {
+        return Err(Box::new(NotFound));
+    }
+    ok_true()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,14 +305,14 @@ mod tests {
     #[test]
     fn gh_user_with_invalid_email_doesnt_fail() {
         let conn = pg_connection();
-        let gh_user = GithubUser {
+        let gh_user = ProviderUser {
             email: Some("String.Format(\"{0}.{1}@live.com\", FirstName, LastName)".into()),
             name: Some("My Name".into()),
             login: "github_user".into(),
-            id: -1,
-            avatar_url: None,
+            id: "-1".into(),
+            avatar: None,
         };
-        let result = gh_user.save_to_database("arbitrary_token", &conn);
+        let result = save_user(GITHUB, &gh_user, "arbitrary_token", &conn);
 
         assert!(
             result.is_ok(),
@@ -0,0 +1,273 @@
+//! The `/api/v2` routes
+//!
+//! The v2 API serves the same data as v1 in a uniform format: successful
+//! responses are `views::v2::Envelope`s, collections are paginated with the
+//! same `page` and `per_page` parameters and `meta`, and every error is an
+//! RFC 7807 `application/problem+json` response with an HTTP status that
+//! matches the error. The v1 routes are left as they are, since cargo
+//! depends on their format.
+//!
+//! Controllers report errors like the v1 controllers do, and `problem`
+//! converts them when the response is sent. Errors about a single query
+//! parameter are reported with `invalid_parameter`, so that the problem
+//! points at the parameter.
+
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::helpers::pagination::Paginated;
+use super::prelude::*;
+use crate::logging;
+use crate::util::errors::{CargoErrToStdErr, CargoError};
+use crate::util::json_response;
+use crate::views::v2::{Envelope, PageMeta, Problem, ProblemError};
+
+pub mod krate;
+
+const DEFAULT_PER_PAGE: u32 = 10;
+const MAX_PER_PAGE: u32 = 100;
+
+/// Returns `data` in an envelope without pagination.
+pub fn data<T: Serialize>(data: T) -> CargoResult<Response> {
+    Ok(json_response(&Envelope { data, meta: None }))
+}
+
+/// The `page` and `per_page` parameters of a request for a collection.
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    page: u32,
+    per_page: u32,
+}
+
+impl Pagination {
+    pub fn from_request(req: &dyn Request) -> CargoResult<Self> {
+        let params = req.query();
+        let number = |name: &'static str, default: u32| match params.get(name) {
+            Some(value) => value
+                .parse::<u32>()
+                .map_err(|_| invalid_parameter(name, &format_args!("`{}` must be a number", name))),
+            None => Ok(default),
+        };
+        let page = number("page", 1)?;
+        let per_page = number("per_page", DEFAULT_PER_PAGE)?;
+        if page < 1 {
+            return Err(invalid_parameter("page", "pages are numbered from 1"));
+        }
+        if per_page < 1 || per_page > MAX_PER_PAGE {
+            return Err(invalid_parameter(
+                "per_page",
+                &format_args!("`per_page` must be between 1 and {}", MAX_PER_PAGE),
+            ));
+        }
+        Ok(Pagination { page, per_page })
+    }
+
+    /// The parameters understood by `Paginate::paginate`.
+    pub fn params(self) -> IndexMap<String, String> {
+        let mut params = IndexMap::new();
+        params.insert("page".into(), self.page.to_string());
+        params.insert("per_page".into(), self.per_page.to_string());
+        params
+    }
+
+    /// Returns a page of a collection in an envelope, converting its records
+    /// with `f`.
+    pub fn respond<T, U, F>(
+        self,
+        req: &dyn Request,
+        records: Paginated<T>,
+        f: F,
+    ) -> CargoResult<Response>
+    where
+        T: 'static,
+        U: Serialize,
+        F: FnMut(T) -> U,
+    {
+        let meta = PageMeta {
+            total: records.total().unwrap_or_default(),
+            page: self.page,
+            per_page: self.per_page,
+            next_page: records
+                .next_page_params(|_| ())
+                .map(|p| req.query_with_params(p)),
+            prev_page: records.prev_page_params().map(|p| req.query_with_params(p)),
+        };
+        let data = records.into_iter().map(f).collect::<Vec<_>>();
+        Ok(json_response(&Envelope {
+            data,
+            meta: Some(meta),
+        }))
+    }
+}
+
+/// An invalid query parameter.
+#[derive(Debug)]
+struct InvalidParameter {
+    parameter: &'static str,
+    detail: String,
+}
+
+/// Returns an error about the query parameter `parameter`, which `problem`
+/// reports as a 400 pointing at the parameter.
+pub fn invalid_parameter<S: ToString + ?Sized>(
+    parameter: &'static str,
+    detail: &S,
+) -> Box<dyn CargoError> {
+    Box::new(InvalidParameter {
+        parameter,
+        detail: detail.to_string(),
+    })
+}
+
+impl CargoError for InvalidParameter {
+    fn description(&self) -> &str {
+        &self.detail
+    }
+
+    fn response(&self) -> Option<Response> {
+        let error = ProblemError {
+            code: ErrorCode::InvalidParameter.as_str().into(),
+            detail: self.detail.clone(),
+            pointer: None,
+            parameter: Some(self.parameter.into()),
+        };
+        Some(problem_response(400, vec![error]))
+    }
+
+    fn human(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for InvalidParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.detail.fmt(f)
+    }
+}
+
+/// Converts an error of a controller into a problem response.
+///
+/// Errors with a response of their own are converted from the v1 error
+/// format, keeping their status, headers and codes. Human errors, which v1
+/// returns with a 200 for cargo's sake, become 422s. Any other error is
+/// logged and becomes a 500, without details.
+pub fn problem(error: Box<dyn CargoError>) -> Response {
+    if error.is::<InvalidParameter>() {
+        return error
+            .response()
+            .expect("invalid parameters have a response");
+    }
+
+    let mut response = match error.response() {
+        Some(response) => response,
+        None => {
+            let error = CargoErrToStdErr(error).to_string();
+            logging::error("v2 request failed", json!({ "error": error }));
+            let error = ProblemError {
+                code: "internal_error".into(),
+                detail: "an internal error occurred".into(),
+                pointer: None,
+                parameter: None,
+            };
+            return problem_response(500, vec![error]);
+        }
+    };
+
+    #[derive(Deserialize)]
+    struct V1Error {
+        detail: String,
+        code: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct V1Errors {
+        errors: Vec<V1Error>,
+    }
+    let mut body = Vec::new();
+    let details = response
+        .body
+        .write_body(&mut body)
+        .ok()
+        .and_then(|_| serde_json::from_slice::<V1Errors>(&body).ok())
+        .map(|v1| v1.errors)
+        .unwrap_or_default();
+
+    let status = match response.status.0 {
+        200 => 422,
+        status => status,
+    };
+    let code = code(status);
+    let mut errors = details
+        .into_iter()
+        .map(|error| ProblemError {
+            code: error.code.unwrap_or_else(|| code.into()),
+            detail: error.detail,
+            pointer: None,
+            parameter: None,
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        errors.push(ProblemError {
+            code: code.into(),
+            detail: error.description().into(),
+            pointer: None,
+            parameter: None,
+        });
+    }
+
+    let mut problem = problem_response(status, errors);
+    // Like `Retry-After`
+    for (name, values) in response.headers.drain() {
+        if name != "Content-Type" && name != "Content-Length" {
+            problem.headers.insert(name, values);
+        }
+    }
+    problem
+}
+
+fn problem_response(status: u32, errors: Vec<ProblemError>) -> Response {
+    let title = reason(status);
+    let problem = Problem {
+        problem_type: "about:blank".into(),
+        title: title.into(),
+        status,
+        code: errors[0].code.clone(),
+        detail: errors[0].detail.clone(),
+        errors,
+    };
+    let mut response = json_response(&problem);
+    response.status = (status, title);
+    response.headers.insert(
+        "Content-Type".into(),
+        vec!["application/problem+json; charset=utf-8".into()],
+    );
+    response
+}
+
+/// The `code` of problems with the given HTTP status, for responses without
+/// codes of their own.
+fn code(status: u32) -> &'static str {
+    match status {
+        400 => "bad_request",
+        403 => "forbidden",
+        404 => "not_found",
+        422 => "invalid_request",
+        429 => "too_many_requests",
+        503 => "service_unavailable",
+        _ => "error",
+    }
+}
+
+fn reason(status: u32) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
@@ -0,0 +1,128 @@
+//! The `/api/v2` routes about crates
+
+use super::{data, Pagination};
+use crate::controllers::helpers::Paginate;
+use crate::controllers::prelude::*;
+use crate::models::{Crate, Owner, Version, Visibility};
+use crate::schema::{crates, users, versions};
+use crate::util::errors::NotFound;
+use crate::views::{self, v2};
+
+/// Handles the `GET /api/v2/crates` route.
+///
+/// Lists the public crates by name, or by downloads with `sort=downloads`.
+/// Only crates whose name loosely matches the `q` parameter are listed if it
+/// is given.
+pub fn index(req: &mut dyn Request) -> CargoResult<Response> {
+    let pagination = Pagination::from_request(req)?;
+    let params = req.query();
+    let conn = req.db_conn()?;
+
+    let mut query = crates::table
+        .select(crate::models::krate::ALL_COLUMNS)
+        .filter(crates::visibility.eq(Visibility::Public))
+        .into_boxed();
+    if let Some(q) = params.get("q") {
+        query = query.filter(Crate::loosly_matches_name(q));
+    }
+    query = match params.get("sort").map(String::as_str) {
+        None | Some("name") => query.order(crates::name.asc()),
+        Some("downloads") => query.order((crates::downloads.desc(), crates::name.asc())),
+        Some(_) => {
+            return Err(super::invalid_parameter(
+                "sort",
+                "`sort` must be `name` or `downloads`",
+            ))
+        }
+    };
+
+    let crates = query
+        .paginate(&pagination.params())?
+        .load::<Crate>(&*conn)?;
+    pagination.respond(req, crates, encodable_crate)
+}
+
+/// Handles the `GET /api/v2/crates/:crate_id` route.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    let krate = visible_crate(req)?;
+    data(encodable_crate(krate))
+}
+
+/// Handles the `GET /api/v2/crates/:crate_id/versions` route.
+///
+/// Lists the versions of the crate, most recently published first.
+pub fn versions(req: &mut dyn Request) -> CargoResult<Response> {
+    let pagination = Pagination::from_request(req)?;
+    let krate = visible_crate(req)?;
+    let conn = req.db_conn()?;
+
+    let versions = versions::table
+        .left_outer_join(users::table)
+        .filter(versions::crate_id.eq(krate.id))
+        .select((versions::all_columns, users::gh_login.nullable()))
+        .order((versions::created_at.desc(), versions::id.desc()))
+        .paginate(&pagination.params())?
+        .load::<(Version, Option<String>)>(&*conn)?;
+    pagination.respond(req, versions, |(version, published_by)| {
+        v2::EncodableVersion {
+            num: version.num.to_string(),
+            yanked: version.yanked,
+            license: version.license,
+            crate_size: version.crate_size,
+            rust_version: version.rust_version,
+            downloads: version.downloads,
+            published_by,
+            created_at: version.created_at,
+        }
+    })
+}
+
+/// Handles the `GET /api/v2/crates/:crate_id/owners` route.
+///
+/// Lists the users and teams owning the crate. The owners of a crate aren't
+/// paginated, since crates only have a few.
+pub fn owners(req: &mut dyn Request) -> CargoResult<Response> {
+    let krate = visible_crate(req)?;
+    let conn = req.db_conn()?;
+    let owners = krate
+        .owners(&conn)?
+        .into_iter()
+        .map(Owner::encodable)
+        .map(|owner: views::EncodableOwner| v2::EncodableOwner {
+            login: owner.login,
+            kind: owner.kind,
+            name: owner.name,
+            url: owner.url,
+            avatar: owner.avatar,
+        })
+        .collect::<Vec<_>>();
+    data(owners)
+}
+
+/// The crate named by the `crate_id` parameter, if the current user can see
+/// it.
+fn visible_crate(req: &mut dyn Request) -> CargoResult<Crate> {
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+    Ok(krate)
+}
+
+fn encodable_crate(krate: Crate) -> v2::EncodableCrate {
+    v2::EncodableCrate {
+        name: krate.name,
+        description: krate.description,
+        homepage: krate.homepage,
+        documentation: krate.documentation,
+        repository: krate.repository,
+        repository_verified: krate.repository_verified,
+        max_version: krate.max_version,
+        max_stable_version: krate.max_stable_version,
+        downloads: krate.downloads,
+        created_at: krate.created_at,
+        updated_at: krate.updated_at,
+    }
+}
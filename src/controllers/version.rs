@@ -1,4 +1,8 @@
+pub mod advisory;
+pub mod build_verification;
+pub mod delete;
 pub mod deprecated;
+pub mod diff;
 pub mod downloads;
 pub mod metadata;
 pub mod yank;
@@ -12,19 +16,23 @@ fn version_and_crate(req: &mut dyn Request) -> CargoResult<(Version, Crate)> {
     let crate_name = &req.params()["crate_id"];
     let semver = &req.params()["version"];
     if semver::Version::parse(semver).is_err() {
-        return Err(human(&format_args!("invalid semver: {}", semver)));
+        return Err(ErrorCode::InvalidVersion.human(&format_args!("invalid semver: {}", semver)));
     };
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
-    let version = krate
+    let version = version_of_crate(&conn, &krate, semver)?;
+    Ok((version, krate))
+}
+
+fn version_of_crate(conn: &PgConnection, krate: &Crate, num: &str) -> CargoResult<Version> {
+    krate
         .all_versions()
-        .filter(versions::num.eq(semver))
-        .first(&*conn)
+        .filter(versions::num.eq(num))
+        .first(conn)
         .map_err(|_| {
-            human(&format_args!(
+            ErrorCode::NotFound.human(&format_args!(
                 "crate `{}` does not have a version `{}`",
-                crate_name, semver
+                krate.name, num
             ))
-        })?;
-    Ok((version, krate))
+        })
 }
@@ -0,0 +1,67 @@
+//! Endpoints for setting and clearing advisories on specific versions of crates
+
+use serde_json;
+use swirl::Job;
+
+use super::version_and_crate;
+use crate::controllers::prelude::*;
+use crate::git;
+use crate::models::{CrateRename, Rights};
+use crate::util::CargoError;
+
+/// The maximum length of an advisory message, in characters.
+const MAX_ADVISORY_LENGTH: usize = 256;
+
+/// Handles the `PUT /crates/:crate_id/:version/advisory` route.
+///
+/// An advisory is a short message such as "known broken on Windows" or
+/// "superseded by 2.x". Unlike yanking, the version remains available for
+/// resolution, the message is only surfaced in the API and the index.
+///
+/// The request body has the format `{"advisory": "message"}`.
+pub fn set(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    #[derive(Deserialize)]
+    struct Request {
+        advisory: String,
+    }
+    let request: Request = serde_json::from_str(&body)
+        .map_err(|_| ErrorCode::InvalidJson.human("invalid json request"))?;
+
+    let advisory = request.advisory.trim();
+    if advisory.is_empty() {
+        return Err(ErrorCode::InvalidField.human("advisory message must not be empty"));
+    }
+    if advisory.chars().count() > MAX_ADVISORY_LENGTH {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "advisory message must not be longer than {} characters",
+            MAX_ADVISORY_LENGTH
+        )));
+    }
+
+    modify_advisory(req, Some(advisory.to_string()))
+}
+
+/// Handles the `DELETE /crates/:crate_id/:version/advisory` route.
+pub fn clear(req: &mut dyn Request) -> CargoResult<Response> {
+    modify_advisory(req, None)
+}
+
+/// Changes the `advisory` message on a crate version record
+fn modify_advisory(req: &mut dyn Request, advisory: Option<String>) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let owners = krate.owners(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+        return Err(ErrorCode::OwnerMismatch.human("must already be an owner to change advisories"));
+    }
+
+    let name = CrateRename::published_name(&conn, &krate, &version)?;
+    git::update_advisory(name, version, advisory)
+        .enqueue(&conn)
+        .map_err(|e| CargoError::from_std_error(e))?;
+
+    ok_true()
+}
@@ -0,0 +1,134 @@
+//! Endpoints for external services verifying that versions build reproducibly
+
+use chrono::{NaiveDateTime, Utc};
+use serde_json;
+
+use super::version_and_crate;
+use crate::controllers::prelude::*;
+use crate::models::{BuildVerification, BuildVerifier, NewBuildVerification, VersionChecksum};
+use crate::schema::{build_verifications, build_verifiers, version_checksums};
+use crate::util::errors::{NotFound, Unauthorized};
+use crate::util::CargoError;
+use crate::views::EncodableBuildVerification;
+
+/// How long after being signed build verification requests are accepted.
+const MAX_SIGNATURE_AGE_SECS: i64 = 5 * 60;
+
+/// Handles the `PUT /crates/:crate_id/:version/build_verification` route.
+///
+/// Called by registered build verification services to submit whether
+/// rebuilding the version from its stated source produced a bit-identical
+/// tarball. The `X-Verifier` header names the service, the
+/// `X-Verifier-Timestamp` header contains the time the request was signed at
+/// in seconds since the Unix epoch, and the `X-Verifier-Signature` header
+/// contains the hex encoded HMAC-SHA256 of `BuildVerifier::signed_message`,
+/// signed with the hex encoded SHA-256 of the secret of the service. Requests
+/// signed more than `MAX_SIGNATURE_AGE_SECS` ago (or ahead) are refused.
+///
+/// The request body has the format
+///
+///     {"reproducible": true, "checksum": "<sha256>", "log_url": "https://..."}
+///
+/// The checksum is the one of the tarball the service rebuilt, or downloaded
+/// to compare against. Verdicts about any other tarball than the published
+/// one are refused.
+pub fn submit(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = Vec::new();
+    req.body().read_to_end(&mut body)?;
+    let (version, _) = version_and_crate(req)?;
+
+    let header = |name: &str| {
+        req.headers()
+            .find(name)
+            .and_then(|values| values.first().map(|s| s.to_string()))
+            .ok_or_else(|| Box::new(Unauthorized) as Box<dyn CargoError>)
+    };
+    let verifier_name = header("X-Verifier")?;
+    let timestamp = header("X-Verifier-Timestamp")?;
+    let signature = header("X-Verifier-Signature")?;
+
+    let signed_at = timestamp
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| NaiveDateTime::from_timestamp_opt(secs, 0))
+        .ok_or_else(|| Box::new(Unauthorized) as Box<dyn CargoError>)?;
+    let age = Utc::now().naive_utc() - signed_at;
+    if age.num_seconds().abs() > MAX_SIGNATURE_AGE_SECS {
+        return Err(Box::new(Unauthorized));
+    }
+    // The path is signed as requested, before the API router stripped the
+    // `/api/v1` prefix
+    let path = format!("/api/v1{}", req.path());
+    let message =
+        BuildVerifier::signed_message(&req.method().to_string(), &path, &timestamp, &body);
+
+    let conn = req.db_conn()?;
+    let verifier = BuildVerifier::find_by_name(&conn, &verifier_name)
+        .optional()?
+        .filter(|verifier| verifier.signature_matches(&message, &signature))
+        .ok_or_else(|| Box::new(Unauthorized) as Box<dyn CargoError>)?;
+
+    #[derive(Deserialize)]
+    struct Statement {
+        reproducible: bool,
+        checksum: String,
+        log_url: Option<String>,
+    }
+    let statement: Statement = serde_json::from_slice(&body)
+        .map_err(|_| ErrorCode::InvalidJson.bad_request("invalid json request"))?;
+
+    let published = VersionChecksum::belonging_to(&version)
+        .select(version_checksums::sha256)
+        .first::<String>(&*conn)
+        .optional()?;
+    match published {
+        Some(ref checksum) if checksum.eq_ignore_ascii_case(&statement.checksum) => {}
+        Some(_) => {
+            return Err(ErrorCode::InvalidField
+                .bad_request("the checksum doesn't match the published crate file"));
+        }
+        None => {
+            return Err(ErrorCode::InvalidField
+                .bad_request("the checksum of the published crate file isn't known yet"));
+        }
+    }
+
+    NewBuildVerification {
+        version_id: version.id,
+        verifier_id: verifier.id,
+        reproducible: statement.reproducible,
+        checksum: &statement.checksum,
+        log_url: statement.log_url.as_ref().map(|s| &**s),
+    }
+    .save(&conn)?;
+
+    ok_true()
+}
+
+/// Handles the `GET /crates/:crate_id/:version/build_verifications` route.
+pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.db_conn()?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let verifications = BuildVerification::belonging_to(&version)
+        .inner_join(build_verifiers::table)
+        .select((build_verifications::all_columns, build_verifiers::name))
+        .order(build_verifiers::name)
+        .load::<(BuildVerification, String)>(&*conn)?
+        .into_iter()
+        .map(|(verification, verifier)| verification.encodable(verifier))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        reproducible: Option<bool>,
+        verifications: Vec<EncodableBuildVerification>,
+    }
+    Ok(req.json(&R {
+        reproducible: version.reproducible,
+        verifications,
+    }))
+}
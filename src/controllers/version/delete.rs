@@ -0,0 +1,83 @@
+//! Endpoint for deleting versions shortly after they were published
+
+use chrono::{Duration, Utc};
+use swirl::Job;
+
+use super::version_and_crate;
+use crate::controllers::prelude::*;
+use crate::git;
+use crate::models::deleted_version::{GRACE_PERIOD_HOURS, MAX_DOWNLOADS};
+use crate::models::{CrateRename, DeletedVersion, Rights};
+use crate::schema::versions;
+use crate::storage;
+use crate::util::CargoError;
+
+/// Handles the `DELETE /crates/:crate_id/:version` route.
+///
+/// Unlike yanking, this removes the version from the index and deletes its
+/// crate file, so it is only allowed for versions that were published by
+/// mistake and nobody depends on yet: within `GRACE_PERIOD_HOURS` of being
+/// published, with at most `MAX_DOWNLOADS` downloads, and without dependents.
+/// The version number can't be published again.
+pub fn delete(req: &mut dyn Request) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let owners = krate.owners(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+        return Err(ErrorCode::OwnerMismatch.human("must already be an owner to delete a version"));
+    }
+
+    if Utc::now().naive_utc() > version.created_at + Duration::hours(GRACE_PERIOD_HOURS) {
+        return Err(ErrorCode::Conflict.human(&format_args!(
+            "versions can only be deleted within {} hours of being published, \
+             yank the version instead",
+            GRACE_PERIOD_HOURS
+        )));
+    }
+    if version.downloads > MAX_DOWNLOADS {
+        return Err(ErrorCode::Conflict.human(&format_args!(
+            "versions with more than {} downloads can't be deleted, \
+             yank the version instead",
+            MAX_DOWNLOADS
+        )));
+    }
+
+    conn.transaction(|| {
+        // Checked in the transaction, so that a dependent published in the
+        // meantime isn't missed
+        let dependents = krate.all_dependents_of_version(&conn, &version.num)?;
+        if !dependents.is_empty() {
+            return Err(ErrorCode::Conflict.human(&format_args!(
+                "version `{}` can't be deleted because other crates depend on it: {}",
+                version.num,
+                dependents.join(", ")
+            )));
+        }
+
+        let other_versions = versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .filter(versions::id.ne(version.id))
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if other_versions == 0 {
+            return Err(ErrorCode::Conflict.human("the only version of a crate can't be deleted"));
+        }
+
+        DeletedVersion::delete(&conn, &version, user.id)?;
+
+        let name = CrateRename::published_name(&conn, &krate, &version)?;
+        let num = version.num.to_string();
+        git::delete_version(name.clone(), num.clone())
+            .enqueue(&conn)
+            .map_err(|e| CargoError::from_std_error(e))?;
+        storage::delete_version_files(name, num)
+            .enqueue(&conn)
+            .map_err(|e| CargoError::from_std_error(e))?;
+        Ok(())
+    })?;
+
+    req.app().response_cache.invalidate_crate(&krate.name);
+
+    ok_true()
+}
@@ -5,10 +5,13 @@
 //! period of time to ensure there are no external users of an endpoint before
 //! it is removed.
 
+use std::collections::HashMap;
+
 use crate::controllers::prelude::*;
 
 use crate::models::{Crate, User, Version};
 use crate::schema::*;
+use crate::util::errors::NotFound;
 use crate::views::EncodableVersion;
 
 /// Handles the `GET /versions` route.
@@ -22,19 +25,35 @@ pub fn index(req: &mut dyn Request) -> CargoResult<Response> {
         .filter_map(|(ref a, ref b)| if *a == "ids[]" { b.parse().ok() } else { None })
         .collect::<Vec<i32>>();
 
-    let versions = versions::table
+    let rows = versions::table
         .inner_join(crates::table)
         .left_outer_join(users::table)
         .select((
             versions::all_columns,
-            crates::name,
+            crate::models::krate::ALL_COLUMNS,
             users::all_columns.nullable(),
         ))
         .filter(versions::id.eq(any(ids)))
-        .load::<(Version, String, Option<User>)>(&*conn)?
-        .into_iter()
-        .map(|(version, crate_name, published_by)| version.encodable(&crate_name, published_by))
-        .collect();
+        .load::<(Version, Crate, Option<User>)>(&*conn)?;
+
+    // Versions of crates the user can't see are left out, as if they didn't
+    // exist
+    let user = req.user().ok();
+    let mut visible = HashMap::new();
+    let mut versions = Vec::with_capacity(rows.len());
+    for (version, krate, published_by) in rows {
+        let is_visible = match visible.get(&krate.id) {
+            Some(&is_visible) => is_visible,
+            None => {
+                let is_visible = krate.is_visible_to(req.app(), &conn, user)?;
+                visible.insert(krate.id, is_visible);
+                is_visible
+            }
+        };
+        if is_visible {
+            versions.push(version.encodable(&krate.name, published_by));
+        }
+    }
 
     #[derive(Serialize)]
     struct R {
@@ -61,6 +80,10 @@ pub fn show_by_id(req: &mut dyn Request) -> CargoResult<Response> {
         ))
         .first(&*conn)?;
 
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
     #[derive(Serialize)]
     struct R {
         version: EncodableVersion,
@@ -0,0 +1,167 @@
+//! Endpoint comparing two versions of a crate
+
+use std::collections::BTreeMap;
+
+use super::{version_and_crate, version_of_crate};
+use crate::controllers::prelude::*;
+use crate::models::{Dependency, Version, VersionFile};
+use crate::util::errors::NotFound;
+use crate::views::{
+    EncodableDependencyChange, EncodableDependencyDiff, EncodableFeatureChange,
+    EncodableFeatureDiff, EncodableFileChange, EncodableFileDiff, EncodableVersionDiff,
+    EncodableVersionFile,
+};
+
+/// Handles the `GET /crates/:crate_id/:version/diff/:other_version` route.
+///
+/// Lists the dependencies, features and files that were added, removed or
+/// changed going from `version` to `other_version`, so that upgrades can be
+/// audited without downloading both crate files. Versions published before
+/// files were recorded have no files, so their file lists can't be compared.
+pub fn diff(req: &mut dyn Request) -> CargoResult<Response> {
+    let (from, krate) = version_and_crate(req)?;
+    let other_num = &req.params()["other_version"];
+    if semver::Version::parse(other_num).is_err() {
+        return Err(ErrorCode::InvalidVersion.human(&format_args!("invalid semver: {}", other_num)));
+    }
+    let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+    let to = version_of_crate(&conn, &krate, other_num)?;
+
+    let dependencies = diff_dependencies(from.dependencies(&conn)?, to.dependencies(&conn)?);
+    let features = diff_features(features(&from), features(&to));
+    let files = diff_files(files(&conn, &from)?, files(&conn, &to)?);
+
+    #[derive(Serialize)]
+    struct R {
+        diff: EncodableVersionDiff,
+    }
+    Ok(req.json(&R {
+        diff: EncodableVersionDiff {
+            from: from.num.to_string(),
+            to: to.num.to_string(),
+            dependencies,
+            features,
+            files,
+        },
+    }))
+}
+
+/// Dependencies are matched by the crate they depend on, their kind and their
+/// target, since a crate can be depended on more than once that way.
+fn diff_dependencies(
+    from: Vec<(Dependency, String)>,
+    to: Vec<(Dependency, String)>,
+) -> EncodableDependencyDiff {
+    fn key(dep: &Dependency) -> (i32, u32, Option<String>) {
+        (dep.crate_id, dep.kind as u32, dep.target.clone())
+    }
+
+    let mut removed = from
+        .into_iter()
+        .map(|(dep, name)| (key(&dep), (dep, name)))
+        .collect::<BTreeMap<_, _>>();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (dep, name) in to {
+        match removed.remove(&key(&dep)) {
+            Some((old, old_name)) => {
+                if old.req.to_string() != dep.req.to_string()
+                    || old.optional != dep.optional
+                    || old.default_features != dep.default_features
+                    || old.features != dep.features
+                {
+                    changed.push(EncodableDependencyChange {
+                        from: old.encodable(&old_name, None),
+                        to: dep.encodable(&name, None),
+                    });
+                }
+            }
+            None => added.push(dep.encodable(&name, None)),
+        }
+    }
+
+    EncodableDependencyDiff {
+        added,
+        removed: removed
+            .into_iter()
+            .map(|(_, (dep, name))| dep.encodable(&name, None))
+            .collect(),
+        changed,
+    }
+}
+
+fn features(version: &Version) -> BTreeMap<String, Vec<String>> {
+    serde_json::from_value(version.features.clone()).unwrap_or_default()
+}
+
+fn diff_features(
+    mut from: BTreeMap<String, Vec<String>>,
+    to: BTreeMap<String, Vec<String>>,
+) -> EncodableFeatureDiff {
+    let mut added = BTreeMap::new();
+    let mut changed = BTreeMap::new();
+    for (name, enables) in to {
+        match from.remove(&name) {
+            Some(old) => {
+                if old != enables {
+                    changed.insert(
+                        name,
+                        EncodableFeatureChange {
+                            from: old,
+                            to: enables,
+                        },
+                    );
+                }
+            }
+            None => {
+                added.insert(name, enables);
+            }
+        }
+    }
+
+    EncodableFeatureDiff {
+        added,
+        removed: from,
+        changed,
+    }
+}
+
+fn files(conn: &PgConnection, version: &Version) -> QueryResult<BTreeMap<String, i64>> {
+    Ok(VersionFile::belonging_to(version)
+        .load::<VersionFile>(conn)?
+        .into_iter()
+        .map(|file| (file.path, file.size))
+        .collect())
+}
+
+fn diff_files(mut from: BTreeMap<String, i64>, to: BTreeMap<String, i64>) -> EncodableFileDiff {
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (path, size) in to {
+        match from.remove(&path) {
+            Some(from_size) => {
+                if from_size != size {
+                    changed.push(EncodableFileChange {
+                        path,
+                        from_size,
+                        to_size: size,
+                    });
+                }
+            }
+            None => added.push(EncodableVersionFile { path, size }),
+        }
+    }
+
+    EncodableFileDiff {
+        added,
+        removed: from
+            .into_iter()
+            .map(|(path, size)| EncodableVersionFile { path, size })
+            .collect(),
+        changed,
+    }
+}
@@ -2,29 +2,61 @@
 //!
 //! Crate level functionality is located in `krate::downloads`.
 
+use crate::controllers::helpers::CacheValidator;
 use crate::controllers::prelude::*;
 
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
 
-use crate::models::{Crate, VersionDownload};
+use crate::config::DownloadCounting;
+use crate::git;
+use crate::logging;
+use crate::models::krate::ALL_COLUMNS;
+use crate::models::{Crate, CrateRename, DownloadClient, Version, VersionDownload, Visibility};
 use crate::schema::*;
+use crate::util::errors::NotFound;
+use crate::util::request_header;
 use crate::views::EncodableVersionDownload;
 
 use super::version_and_crate;
 
 /// Handles the `GET /crates/:crate_id/:version/download` route.
 /// This returns a URL to the location where the crate is stored.
+///
+/// If download signing is configured, private crates are served from a
+/// short-lived signed URL instead, so that the location can't be shared.
+///
+/// Versions that aren't in the database are looked up in the sparse index at
+/// `Config::index_fallback_url`, if set. Their downloads aren't counted.
+///
+/// The crate files of versions published before their crate was renamed are
+/// served from where they were uploaded, under the old name.
 pub fn download(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
     let version = &req.params()["version"];
 
-    let crate_name = increment_download_counts(req, crate_name, version)?;
+    let (name, visibility) = match increment_download_counts(req, crate_name, version)? {
+        Some((found, krate)) => {
+            let conn = req.db_conn()?;
+            let name = CrateRename::published_name(&conn, &krate, &found)?;
+            (name, krate.visibility)
+        }
+        None => match find_in_index(req, crate_name, version) {
+            Some(name) => (name, Visibility::Public),
+            None => return Err(Box::new(NotFound)),
+        },
+    };
+    req.app().metrics.record_download();
 
-    let redirect_url = req
-        .app()
-        .config
-        .uploader
-        .crate_location(&crate_name, version);
+    let config = &req.app().config;
+    let redirect_url = match (&config.download_signing, visibility) {
+        (Some(signing), Visibility::Private) => {
+            let expires = Utc::now().timestamp() + config.signed_download_ttl.as_secs() as i64;
+            config
+                .uploader
+                .signed_crate_location(&name, version, signing, expires)
+        }
+        _ => config.uploader.crate_location(&name, version),
+    };
 
     if req.wants_json() {
         #[derive(Serialize)]
@@ -39,8 +71,13 @@ pub fn download(req: &mut dyn Request) -> CargoResult<Response> {
 
 /// Increment the download counts for a given crate version.
 ///
-/// Returns the crate name as stored in the database, or an error if we could
-/// not load the version ID from the database.
+/// Returns the version and its crate as stored in the database, `None` if the
+/// version isn't in the database, or an error if the crate is private and the
+/// requesting user doesn't have access to it.
+///
+/// Downloads made by cargo are additionally counted per cargo version and
+/// target, see `DownloadClient`. If `Config::download_counting` is
+/// `CdnLogs`, only those counts are updated here.
 ///
 /// This ignores any errors that occur updating the download count. Failure is
 /// expected if the application is in read only mode, or for API-only mirrors.
@@ -50,33 +87,131 @@ fn increment_download_counts(
     req: &dyn Request,
     crate_name: &str,
     version: &str,
-) -> CargoResult<String> {
-    use self::versions::dsl::*;
-
+) -> CargoResult<Option<(Version, Crate)>> {
     let conn = req.db_conn()?;
-    let (version_id, crate_name) = versions
+    let (version, krate) = match versions::table
         .inner_join(crates::table)
-        .select((id, crates::name))
+        .select((versions::all_columns, ALL_COLUMNS))
         .filter(Crate::with_name(crate_name))
-        .filter(num.eq(version))
-        .first(&*conn)?;
+        .filter(versions::num.eq(version))
+        .first::<(Version, Crate)>(&*conn)
+        .optional()?
+    {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    // Private crates are reported as missing so that we don't leak their
+    // existence to users without access
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
 
     // Wrap in a transaction so we don't poison the outer transaction if this
-    // fails
-    let _ = conn.transaction(|| VersionDownload::create_or_increment(version_id, &conn));
-    Ok(crate_name)
+    // fails. Downloads counted from the CDN logs are added by the
+    // `ingest_cdn_logs` job instead.
+    if req.app().config.download_counting == DownloadCounting::Database {
+        let _ = conn.transaction(|| VersionDownload::create_or_increment(version.id, &conn));
+    }
+    if let Some(client) = DownloadClient::from_user_agent(request_header(req, "User-Agent")) {
+        let _ = conn.transaction(|| client.record(version.id, &conn));
+    }
+    Ok(Some((version, krate)))
 }
 
+/// Looks up a version that isn't in the database yet in the sparse index at
+/// `Config::index_fallback_url`, and returns the name of its crate as listed
+/// there. Versions are reported as missing if the index can't be reached.
+fn find_in_index(req: &dyn Request, crate_name: &str, version: &str) -> Option<String> {
+    let index_url = req.app().config.index_fallback_url.as_ref()?;
+    let url = format!(
+        "{}/{}",
+        index_url.trim_end_matches('/'),
+        git::index_file_path(crate_name)
+    );
+    let lines = req
+        .app()
+        .http_client()
+        .get(&url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|mut response| response.text());
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(e) => {
+            if e.status() != Some(reqwest::StatusCode::NOT_FOUND) {
+                logging::warn(
+                    "failed to look up a version in the index",
+                    json!({ "url": url, "error": e.to_string() }),
+                );
+            }
+            return None;
+        }
+    };
+
+    lines
+        .lines()
+        .filter_map(|line| serde_json::from_str::<git::Crate>(line).ok())
+        .find(|krate| krate.vers == version)
+        .map(|krate| krate.name)
+}
+
+/// The largest number of days that can be requested from the version
+/// downloads endpoint at once.
+const MAX_DOWNLOADS_RANGE_DAYS: i64 = 366;
+
 /// Handles the `GET /crates/:crate_id/:version/downloads` route.
+///
+/// Returns the daily download counts between the `from` and `to` query
+/// parameters (inclusive, formatted as `YYYY-MM-DD`). `to` defaults to today,
+/// or to `before_date` for older clients, and `from` defaults to 89 days
+/// before `to`.
 pub fn downloads(req: &mut dyn Request) -> CargoResult<Response> {
-    let (version, _) = version_and_crate(req)?;
-    let conn = req.db_conn()?;
-    let cutoff_end_date = req
-        .query()
-        .get("before_date")
-        .and_then(|d| NaiveDate::parse_from_str(d, "%F").ok())
-        .unwrap_or_else(|| Utc::today().naive_utc());
-    let cutoff_start_date = cutoff_end_date - Duration::days(89);
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.read_conn()?;
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+    let query = req.query();
+    let parse_date = |name: &str| {
+        query
+            .get(name)
+            .map(|d| {
+                NaiveDate::parse_from_str(d, "%F").map_err(|_| {
+                    ErrorCode::InvalidParameter
+                        .bad_request(&format_args!("invalid `{}` date: {}", name, d))
+                })
+            })
+            .transpose()
+    };
+
+    let cutoff_end_date = match parse_date("to")? {
+        Some(date) => date,
+        None => query
+            .get("before_date")
+            .and_then(|d| NaiveDate::parse_from_str(d, "%F").ok())
+            .unwrap_or_else(|| Utc::today().naive_utc()),
+    };
+    let cutoff_start_date =
+        parse_date("from")?.unwrap_or_else(|| cutoff_end_date - Duration::days(89));
+
+    if cutoff_start_date > cutoff_end_date {
+        return Err(ErrorCode::InvalidParameter.bad_request("`from` must not be after `to`"));
+    }
+    if cutoff_end_date - cutoff_start_date >= Duration::days(MAX_DOWNLOADS_RANGE_DAYS) {
+        return Err(ErrorCode::InvalidParameter.bad_request(&format_args!(
+            "cannot request more than {} days of downloads at once",
+            MAX_DOWNLOADS_RANGE_DAYS
+        )));
+    }
+
+    // The default range moves with the current date
+    let validator = CacheValidator::new(version.updated_at)
+        .with_state(version.downloads.into())
+        .with_state(cutoff_end_date.num_days_from_ce().into());
+    if let Some(response) = validator.not_modified(req) {
+        return Ok(response);
+    }
 
     let downloads = VersionDownload::belonging_to(&version)
         .filter(version_downloads::date.between(cutoff_start_date, cutoff_end_date))
@@ -90,7 +225,9 @@ pub fn downloads(req: &mut dyn Request) -> CargoResult<Response> {
     struct R {
         version_downloads: Vec<EncodableVersionDownload>,
     }
-    Ok(req.json(&R {
+    let mut response = req.json(&R {
         version_downloads: downloads,
-    }))
+    });
+    validator.add_headers(&mut response);
+    Ok(response)
 }
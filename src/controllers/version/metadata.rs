@@ -4,10 +4,16 @@
 //! index or cached metadata which was extracted (client side) from the
 //! `Cargo.toml` file.
 
+use crate::controllers::helpers::CacheValidator;
 use crate::controllers::prelude::*;
 
+use crate::models::{dependency_graph, VersionChecksum, VersionFile, VersionProvenance};
 use crate::schema::*;
-use crate::views::{EncodableDependency, EncodablePublicUser, EncodableVersion};
+use crate::util::errors::NotFound;
+use crate::views::{
+    EncodableDependency, EncodableDependencyGraph, EncodablePublicUser, EncodableVersion,
+    EncodableVersionChecksums, EncodableVersionFile, EncodableVersionProvenance,
+};
 
 use super::version_and_crate;
 
@@ -19,8 +25,18 @@ use super::version_and_crate;
 /// fields for `id`, `version_id`, and `downloads` (which appears to always
 /// be 0)
 pub fn dependencies(req: &mut dyn Request) -> CargoResult<Response> {
-    let (version, _) = version_and_crate(req)?;
+    let (version, krate) = version_and_crate(req)?;
     let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    // The dependencies of a version never change after it was published
+    let validator = CacheValidator::new(version.updated_at);
+    if let Some(response) = validator.not_modified(req) {
+        return Ok(response);
+    }
     let deps = version.dependencies(&*conn)?;
     let deps = deps
         .into_iter()
@@ -31,13 +47,155 @@ pub fn dependencies(req: &mut dyn Request) -> CargoResult<Response> {
     struct R {
         dependencies: Vec<EncodableDependency>,
     }
-    Ok(req.json(&R { dependencies: deps }))
+    let mut response = req.json(&R { dependencies: deps });
+    validator.add_headers(&mut response);
+    Ok(response)
+}
+
+/// The depth of the dependency graph if none is requested.
+const DEFAULT_GRAPH_DEPTH: u32 = 3;
+/// The deepest dependency graph that can be requested.
+const MAX_GRAPH_DEPTH: u32 = 10;
+
+/// Handles the `GET /crates/:crate_id/:version/dependency_graph` route.
+///
+/// Resolves the transitive dependencies of the version, up to `depth` levels
+/// deep, to the latest versions matching their requirements. Resolved graphs
+/// are cached for a while, so they might not include versions that were
+/// published in the meantime.
+pub fn dependency_graph(req: &mut dyn Request) -> CargoResult<Response> {
+    let depth = match req.query().get("depth") {
+        Some(depth) => depth
+            .parse::<u32>()
+            .ok()
+            .filter(|depth| *depth >= 1 && *depth <= MAX_GRAPH_DEPTH)
+            .ok_or_else(|| {
+                ErrorCode::InvalidParameter.bad_request(&format_args!(
+                    "depth must be a number between 1 and {}",
+                    MAX_GRAPH_DEPTH
+                ))
+            })?,
+        None => DEFAULT_GRAPH_DEPTH,
+    };
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.db_conn()?;
+
+    // Private crates are reported as missing so that we don't leak their
+    // existence to users without access
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let graph = dependency_graph::load(&conn, &version, &krate.name, depth)?;
+
+    #[derive(Serialize)]
+    struct R {
+        dependency_graph: EncodableDependencyGraph,
+    }
+    Ok(req.json(&R {
+        dependency_graph: graph,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/:version/files` route.
+///
+/// Lists the files in the uploaded crate file, along with their uncompressed
+/// size. Versions published before files were recorded have no files.
+pub fn files(req: &mut dyn Request) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let files = VersionFile::belonging_to(&version)
+        .order(version_files::path)
+        .load::<VersionFile>(&*conn)?;
+    let total_size = files.iter().map(|file| file.size).sum();
+    let file_count = files.len();
+
+    #[derive(Serialize)]
+    struct R {
+        files: Vec<EncodableVersionFile>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        file_count: usize,
+        total_size: i64,
+    }
+    Ok(req.json(&R {
+        files: files.into_iter().map(VersionFile::encodable).collect(),
+        meta: Meta {
+            file_count,
+            total_size,
+        },
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/:version/checksums` route.
+///
+/// Returns the checksums of the uploaded crate file, so that mirrors and
+/// vendoring tools can verify it without reading the index. Checksums that
+/// haven't been computed yet by the `backfill_checksums` job are `null`.
+pub fn checksums(req: &mut dyn Request) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let checksums = VersionChecksum::belonging_to(&version)
+        .first::<VersionChecksum>(&*conn)
+        .optional()?
+        .map(VersionChecksum::encodable)
+        .unwrap_or(EncodableVersionChecksums {
+            sha256: None,
+            sha512: None,
+        });
+
+    #[derive(Serialize)]
+    struct R {
+        checksums: EncodableVersionChecksums,
+    }
+    Ok(req.json(&R { checksums }))
+}
+
+/// Handles the `GET /crates/:crate_id/:version/provenance` route.
+///
+/// Returns the CI run the version was published from, or `null` if it
+/// wasn't published from a CI system that stated it.
+pub fn provenance(req: &mut dyn Request) -> CargoResult<Response> {
+    let (version, krate) = version_and_crate(req)?;
+    let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let provenance = VersionProvenance::belonging_to(&version)
+        .first::<VersionProvenance>(&*conn)
+        .optional()?
+        .map(VersionProvenance::encodable);
+
+    #[derive(Serialize)]
+    struct R {
+        provenance: Option<EncodableVersionProvenance>,
+    }
+    Ok(req.json(&R { provenance }))
 }
 
 /// Handles the `GET /crates/:crate_id/:version/authors` route.
 pub fn authors(req: &mut dyn Request) -> CargoResult<Response> {
-    let (version, _) = version_and_crate(req)?;
+    let (version, krate) = version_and_crate(req)?;
     let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
     let names = version_authors::table
         .filter(version_authors::version_id.eq(version.id))
         .select(version_authors::name)
@@ -69,13 +227,24 @@ pub fn authors(req: &mut dyn Request) -> CargoResult<Response> {
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     let (version, krate) = version_and_crate(req)?;
     let conn = req.db_conn()?;
+
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Err(Box::new(NotFound));
+    }
+
+    let validator = CacheValidator::new(version.updated_at).with_state(version.downloads.into());
+    if let Some(response) = validator.not_modified(req) {
+        return Ok(response);
+    }
     let published_by = version.published_by(&conn);
 
     #[derive(Serialize)]
     struct R {
         version: EncodableVersion,
     }
-    Ok(req.json(&R {
+    let mut response = req.json(&R {
         version: version.encodable(&krate.name, published_by),
-    }))
+    });
+    validator.add_headers(&mut response);
+    Ok(response)
 }
@@ -5,7 +5,7 @@ use swirl::Job;
 use super::version_and_crate;
 use crate::controllers::prelude::*;
 use crate::git;
-use crate::models::Rights;
+use crate::models::{CrateRename, Rights};
 use crate::util::CargoError;
 
 /// Handles the `DELETE /crates/:crate_id/:version/yank` route.
@@ -32,11 +32,16 @@ fn modify_yank(req: &mut dyn Request, yanked: bool) -> CargoResult<Response> {
     let user = req.user()?;
     let conn = req.db_conn()?;
     let owners = krate.owners(&conn)?;
-    if user.rights(req.app(), &owners)? < Rights::Publish {
-        return Err(human("must already be an owner to yank or unyank"));
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+        return Err(ErrorCode::OwnerMismatch.human("must already be an owner to yank or unyank"));
     }
 
-    git::yank(krate.name, version, yanked)
+    // The version is only yanked once the job ran, so responses cached
+    // before then are stale until they expire
+    req.app().response_cache.invalidate_crate(&krate.name);
+
+    let name = CrateRename::published_name(&conn, &krate, &version)?;
+    git::yank(name, version, yanked)
         .enqueue(&conn)
         .map_err(|e| CargoError::from_std_error(e))?;
 
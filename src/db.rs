@@ -1,12 +1,17 @@
 use conduit::Request;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+use diesel::sql_types::Text;
 use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+use std::cmp;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
 use crate::middleware::app::RequestApp;
+use crate::telemetry::{Span, SpanKind};
+use crate::util::errors::DatabaseUnavailable;
 use crate::util::CargoResult;
 use crate::Env;
 
@@ -18,9 +23,48 @@ pub enum DieselPool {
 }
 
 impl DieselPool {
+    /// Checks out a connection. Diesel can't trace the queries made with it,
+    /// so the time spent waiting for it is traced instead.
     pub fn get(&self) -> CargoResult<DieselPooledConn<'_>> {
+        self.get_before(None)
+    }
+
+    /// Checks out a connection, waiting for one until `deadline` at the
+    /// latest if the pool is exhausted. Fails with `DatabaseUnavailable`
+    /// instead of waiting if the deadline has already passed.
+    pub fn get_before(&self, deadline: Option<Instant>) -> CargoResult<DieselPooledConn<'_>> {
+        let mut span = Span::start("db.checkout", SpanKind::Client);
+        span.set_attribute("db.system", "postgresql");
+        let remaining = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if remaining > Duration::from_secs(0) => Some(remaining),
+                _ => {
+                    span.set_error(&"the request exceeded its database time budget");
+                    return Err(Box::new(DatabaseUnavailable));
+                }
+            },
+            None => None,
+        };
         match self {
-            DieselPool::Pool(pool) => Ok(DieselPooledConn::Pool(pool.get()?)),
+            DieselPool::Pool(pool) => {
+                let timeout = remaining.map_or(pool.connection_timeout(), |remaining| {
+                    cmp::min(remaining, pool.connection_timeout())
+                });
+                match pool.get_timeout(timeout) {
+                    Ok(conn) => Ok(DieselPooledConn::Pool(conn)),
+                    Err(e) => {
+                        span.set_error(&e);
+                        crate::logging::warn(
+                            "no database connection available",
+                            json!({
+                                "error": e.to_string(),
+                                "state": format!("{:?}", pool.state()),
+                            }),
+                        );
+                        Err(Box::new(DatabaseUnavailable))
+                    }
+                }
+            }
             DieselPool::Test(conn) => Ok(DieselPooledConn::Test(conn.lock())),
         }
     }
@@ -84,18 +128,96 @@ pub fn diesel_pool(
     }
 }
 
+/// The time by which the database connections of a request have to be
+/// checked out, so that requests fail fast with `503 Service Unavailable`
+/// instead of piling up when the database pool is exhausted. Added by the
+/// `AppMiddleware` if `Config::db_request_budget` is set.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseDeadline(pub Instant);
+
 pub trait RequestTransaction {
     /// Return the lazily initialized postgres connection for this request.
     ///
     /// The connection will live for the lifetime of the request.
     // FIXME: This description does not match the implementation below.
     fn db_conn(&self) -> CargoResult<DieselPooledConn<'_>>;
+
+    /// Return a connection to the read-only replica of the database if one
+    /// is configured and available, and to the primary database otherwise.
+    ///
+    /// Only use this for endpoints that don't write anything, and that can
+    /// serve data that lags slightly behind the primary database.
+    fn read_conn(&self) -> CargoResult<DieselPooledConn<'_>>;
 }
 
 impl<T: Request + ?Sized> RequestTransaction for T {
     fn db_conn(&self) -> CargoResult<DieselPooledConn<'_>> {
-        self.app().diesel_database.get().map_err(Into::into)
+        checkout(self, &self.app().diesel_database)
+    }
+
+    fn read_conn(&self) -> CargoResult<DieselPooledConn<'_>> {
+        let replica = match &self.app().read_only_replica_database {
+            Some(replica) => replica,
+            None => return self.db_conn(),
+        };
+        match checkout(self, replica) {
+            Ok(conn) => Ok(conn),
+            Err(e) => {
+                crate::logging::warn(
+                    "falling back to the primary database",
+                    json!({ "error": e.to_string() }),
+                );
+                self.db_conn()
+            }
+        }
+    }
+}
+
+/// Checks out a connection for a request before its `DatabaseDeadline`, and
+/// records how long that took.
+fn checkout<'a, T: Request + ?Sized>(
+    req: &'a T,
+    pool: &'a DieselPool,
+) -> CargoResult<DieselPooledConn<'a>> {
+    let deadline = req.extensions().find::<DatabaseDeadline>().map(|d| d.0);
+    let start = Instant::now();
+    let conn = pool.get_before(deadline);
+    req.app()
+        .metrics
+        .record_db_checkout(start.elapsed(), conn.is_ok());
+    let conn = conn?;
+    set_trace_id(&conn)?;
+    Ok(conn)
+}
+
+/// Tags the session with the trace ID of the request being handled, if any.
+/// Postgres logs it as the `application_name`, and background jobs enqueued
+/// in the session store it in their `trace_id` column.
+pub fn set_trace_id(conn: &PgConnection) -> QueryResult<()> {
+    if let Some(trace_id) = crate::logging::trace_id() {
+        diesel::sql_query(
+            "SELECT set_config('application_name', $1, false), \
+             set_config('crates_io.trace_id', $1, false)",
+        )
+        .bind::<Text, _>(trace_id)
+        .execute(conn)?;
     }
+    Ok(())
+}
+
+/// Runs `f` in a transaction that is rolled back even if `f` succeeds, so
+/// that changes can be checked against the database without keeping them.
+pub fn rolled_back_transaction<T, F>(conn: &PgConnection, f: F) -> CargoResult<T>
+where
+    F: FnOnce() -> CargoResult<T>,
+{
+    use diesel::connection::TransactionManager;
+
+    let transaction_manager = conn.transaction_manager();
+    transaction_manager.begin_transaction(conn)?;
+    let result = f();
+    transaction_manager.rollback_transaction(conn)?;
+    result
 }
 
 #[derive(Debug, Clone, Copy)]
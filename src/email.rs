@@ -1,56 +1,45 @@
-use std::path::Path;
+use diesel::PgConnection;
+use failure::Fail;
+use lettre::{EmailAddress, SendableEmail};
+use lettre_email::Email;
+use serde::Serialize;
+use swirl::{Job, PerformError};
 
-use crate::util::{bad_request, CargoResult};
+use crate::background_jobs::Environment;
+use crate::util::errors::std_error_no_send;
+use crate::util::{bad_request, CargoError, CargoResult};
 
-use failure::Fail;
-use lettre::file::FileTransport;
-use lettre::smtp::authentication::{Credentials, Mechanism};
-use lettre::smtp::SmtpClient;
-use lettre::{SendableEmail, Transport};
+mod backend;
+mod ses;
+mod templates;
+mod unsubscribe;
 
-use lettre_email::Email;
+use self::templates::{render, Rendered, DEFAULT_LOCALE};
 
-#[derive(Debug)]
-pub struct MailgunConfigVars {
-    pub smtp_login: String,
-    pub smtp_password: String,
-    pub smtp_server: String,
-}
-
-pub fn init_config_vars() -> Option<MailgunConfigVars> {
-    match (
-        dotenv::var("MAILGUN_SMTP_LOGIN"),
-        dotenv::var("MAILGUN_SMTP_PASSWORD"),
-        dotenv::var("MAILGUN_SMTP_SERVER"),
-    ) {
-        (Ok(login), Ok(password), Ok(server)) => Some(MailgunConfigVars {
-            smtp_login: login,
-            smtp_password: password,
-            smtp_server: server,
-        }),
-        _ => None,
-    }
-}
+pub use self::backend::{EmailBackend, FileBackend, LogBackend, SmtpBackend};
+pub use self::ses::SesBackend;
+pub use self::unsubscribe::Unsubscribe;
 
 fn build_email(
     recipient: &str,
-    subject: &str,
-    body: &str,
-    mailgun_config: &Option<MailgunConfigVars>,
+    email: &Rendered,
+    unsubscribe_url: Option<&str>,
+    sender: &str,
 ) -> CargoResult<SendableEmail> {
-    let sender = mailgun_config
-        .as_ref()
-        .map(|s| s.smtp_login.as_str())
-        .unwrap_or("test@localhost");
-
-    #[allow(clippy::redundant_closure)]
-    let email = Email::builder()
+    let mut builder = Email::builder()
         .to(recipient)
         .from(sender)
-        .subject(subject)
-        .body(body)
-        .build()
-        .map_err(|e| e.compat())?;
+        .subject(email.subject.as_str())
+        .alternative(email.html.as_str(), email.text.as_str());
+    if let Some(unsubscribe_url) = unsubscribe_url {
+        // Lets mail clients offer a one-click unsubscribe button (RFC 8058)
+        builder = builder
+            .header(("List-Unsubscribe", format!("<{}>", unsubscribe_url)))
+            .header(("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"));
+    }
+
+    #[allow(clippy::redundant_closure)]
+    let email = builder.build().map_err(|e| e.compat())?;
 
     Ok(email.into())
 }
@@ -61,8 +50,8 @@ fn build_email(
 /// have an invalid email set in their GitHub profile, and we should let them sign in even though
 /// we're trying to silently use their invalid address during signup and can't send them an email.
 /// Use `try_send_user_confirm_email` when the user is directly trying to set their email.
-pub fn send_user_confirm_email(email: &str, user_name: &str, token: &str) {
-    let _ = try_send_user_confirm_email(email, user_name, token);
+pub fn send_user_confirm_email(conn: &PgConnection, email: &str, user_name: &str, token: &str) {
+    let _ = try_send_user_confirm_email(conn, email, user_name, token);
 }
 
 /// Attempts to send a confirmation email and returns errors.
@@ -70,67 +59,690 @@ pub fn send_user_confirm_email(email: &str, user_name: &str, token: &str) {
 /// For use in cases where we want to fail if an email is bad because the user is directly trying
 /// to set their email correctly, as opposed to us silently trying to use the email from their
 /// GitHub profile during signup.
-pub fn try_send_user_confirm_email(email: &str, user_name: &str, token: &str) -> CargoResult<()> {
+pub fn try_send_user_confirm_email(
+    conn: &PgConnection,
+    email: &str,
+    user_name: &str,
+    token: &str,
+) -> CargoResult<()> {
     // Create a URL with token string as path to send to user
     // If user clicks on path, look email/user up in database,
     // make sure tokens match
 
-    let subject = "Please confirm your email address";
-    let body = format!(
-        "Hello {}! Welcome to Crates.io. Please click the
-link below to verify your email address. Thank you!\n
-https://crates.io/confirm/{}",
-        user_name, token
-    );
-
-    send_email(email, subject, &body)
-}
-
-fn send_email(recipient: &str, subject: &str, body: &str) -> CargoResult<()> {
-    let mailgun_config = init_config_vars();
-    let email = build_email(recipient, subject, body, &mailgun_config)?;
-
-    match mailgun_config {
-        Some(mailgun_config) => {
-            let mut transport = SmtpClient::new_simple(&mailgun_config.smtp_server)?
-                .credentials(Credentials::new(
-                    mailgun_config.smtp_login,
-                    mailgun_config.smtp_password,
-                ))
-                .smtp_utf8(true)
-                .authentication_mechanism(Mechanism::Plain)
-                .transport();
-
-            let result = transport.send(email);
-            result.map_err(|_| bad_request("Error in sending email"))?;
-        }
-        None => {
-            let mut sender = FileTransport::new(Path::new("/tmp"));
-            let result = sender.send(email);
-            result.map_err(|_| bad_request("Email file could not be generated"))?;
-        }
-    }
+    let context = ConfirmEmail { user_name, token };
+    send_email(conn, email, "confirm_email", &context)
+}
+
+#[derive(Serialize)]
+struct ConfirmEmail<'a> {
+    user_name: &'a str,
+    token: &'a str,
+}
+
+/// Tells the owner of some crates that a version their crates depend on was
+/// yanked, so they can update their requirements before users run into it.
+pub fn send_yanked_dependency_email(
+    conn: &PgConnection,
+    email: &str,
+    yanked_crate: &str,
+    yanked_version: &str,
+    dependents: &[String],
+    unsubscribe: &Unsubscribe,
+) -> CargoResult<()> {
+    let context = YankedDependency {
+        krate: yanked_crate,
+        version: yanked_version,
+        dependents,
+    };
+    send_notification_email(conn, email, "yanked_dependency", &context, unsubscribe)
+}
+
+#[derive(Serialize)]
+struct YankedDependency<'a> {
+    krate: &'a str,
+    version: &'a str,
+    dependents: &'a [String],
+}
+
+/// Tells an owner of a crate that another owner published a new version of
+/// it, so that they notice releases they didn't expect.
+pub fn send_version_published_email(
+    conn: &PgConnection,
+    email: &str,
+    krate: &str,
+    version: &str,
+    publisher: &str,
+    checksum: &str,
+    unsubscribe: &Unsubscribe,
+) -> CargoResult<()> {
+    let context = VersionPublished {
+        krate,
+        version,
+        publisher,
+        checksum,
+    };
+    send_notification_email(conn, email, "version_published", &context, unsubscribe)
+}
+
+#[derive(Serialize)]
+struct VersionPublished<'a> {
+    krate: &'a str,
+    version: &'a str,
+    publisher: &'a str,
+    checksum: &'a str,
+}
+
+/// Sends a user the new releases of the crates they follow, directly or
+/// through keywords and categories.
+pub fn send_update_digest_email(
+    conn: &PgConnection,
+    email: &str,
+    releases: &[(String, String)],
+    unsubscribe: &Unsubscribe,
+) -> CargoResult<()> {
+    let context = UpdateDigest {
+        releases: releases
+            .iter()
+            .map(|(krate, version)| Release { krate, version })
+            .collect(),
+    };
+    send_notification_email(conn, email, "update_digest", &context, unsubscribe)
+}
+
+#[derive(Serialize)]
+struct UpdateDigest<'a> {
+    releases: Vec<Release<'a>>,
+}
+
+#[derive(Serialize)]
+struct Release<'a> {
+    krate: &'a str,
+    version: &'a str,
+}
+
+/// Tells an owner of a crate that another user wants to take it over.
+pub fn send_name_claim_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    claimant: &str,
+    reason: &str,
+    holding_period_days: i64,
+) -> CargoResult<()> {
+    let context = NameClaim {
+        krate: crate_name,
+        claimant,
+        reason,
+        days: holding_period_days,
+    };
+    send_email(conn, email, "name_claim", &context)
+}
+
+#[derive(Serialize)]
+struct NameClaim<'a> {
+    krate: &'a str,
+    claimant: &'a str,
+    reason: &'a str,
+    days: i64,
+}
+
+/// Tells the user who claimed a crate how its owners responded.
+pub fn send_name_claim_response_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    released: bool,
+) -> CargoResult<()> {
+    let context = NameClaimResponse {
+        krate: crate_name,
+        released,
+    };
+    send_email(conn, email, "name_claim_response", &context)
+}
+
+#[derive(Serialize)]
+struct NameClaimResponse<'a> {
+    krate: &'a str,
+    released: bool,
+}
 
+/// Tells the claimant and the previous owners of a crate about the decision
+/// on a claim.
+pub fn send_name_claim_decision_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    claimant: &str,
+    approved: bool,
+) -> CargoResult<()> {
+    let context = NameClaimDecision {
+        krate: crate_name,
+        claimant,
+        approved,
+    };
+    send_email(conn, email, "name_claim_decision", &context)
+}
+
+#[derive(Serialize)]
+struct NameClaimDecision<'a> {
+    krate: &'a str,
+    claimant: &'a str,
+    approved: bool,
+}
+
+/// Tells the owners of a crate that one of them wants to remove another,
+/// and when the removal takes effect unless it is cancelled. `effective_at`
+/// is `None` if the removal has to be confirmed by another owner instead.
+pub fn send_owner_removal_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    requested_by: &str,
+    owner: &str,
+    effective_at: Option<&str>,
+) -> CargoResult<()> {
+    let context = OwnerRemoval {
+        krate: crate_name,
+        requested_by,
+        owner,
+        effective_at,
+    };
+    send_email(conn, email, "owner_removal", &context)
+}
+
+#[derive(Serialize)]
+struct OwnerRemoval<'a> {
+    krate: &'a str,
+    requested_by: &'a str,
+    owner: &'a str,
+    effective_at: Option<&'a str>,
+}
+
+/// Asks a user to accept becoming the owner of a crate once its sole owner is
+/// inactive for `months` months.
+pub fn send_successor_invitation_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    owner: &str,
+    months: i32,
+) -> CargoResult<()> {
+    let context = SuccessorInvitation {
+        krate: crate_name,
+        owner,
+        months,
+    };
+    send_email(conn, email, "successor_invitation", &context)
+}
+
+#[derive(Serialize)]
+struct SuccessorInvitation<'a> {
+    krate: &'a str,
+    owner: &'a str,
+    months: i32,
+}
+
+/// Tells the owner who designated the successor of a crate how the successor
+/// responded.
+pub fn send_successor_response_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    successor: &str,
+    accepted: bool,
+) -> CargoResult<()> {
+    let context = SuccessorResponse {
+        krate: crate_name,
+        successor,
+        accepted,
+    };
+    send_email(conn, email, "successor_response", &context)
+}
+
+#[derive(Serialize)]
+struct SuccessorResponse<'a> {
+    krate: &'a str,
+    successor: &'a str,
+    accepted: bool,
+}
+
+/// Warns the sole owner of a crate that it is transferred to its successor on
+/// `transfer_on` unless they use the site before then.
+pub fn send_successor_warning_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    successor: &str,
+    months: i32,
+    transfer_on: &str,
+) -> CargoResult<()> {
+    let context = SuccessorWarning {
+        krate: crate_name,
+        successor,
+        months,
+        transfer_on,
+    };
+    send_email(conn, email, "successor_warning", &context)
+}
+
+#[derive(Serialize)]
+struct SuccessorWarning<'a> {
+    krate: &'a str,
+    successor: &'a str,
+    months: i32,
+    transfer_on: &'a str,
+}
+
+/// Tells the previous owner and the successor of a crate that it was
+/// transferred to the successor.
+pub fn send_successor_transfer_email(
+    conn: &PgConnection,
+    email: &str,
+    crate_name: &str,
+    owner: &str,
+    successor: &str,
+) -> CargoResult<()> {
+    let context = SuccessorTransfer {
+        krate: crate_name,
+        owner,
+        successor,
+    };
+    send_email(conn, email, "successor_transfer", &context)
+}
+
+#[derive(Serialize)]
+struct SuccessorTransfer<'a> {
+    krate: &'a str,
+    owner: &'a str,
+    successor: &'a str,
+}
+
+/// Tells a user that one of their API tokens was found in public, and was
+/// revoked. `url` is where the token was found, if GitHub knows it.
+pub fn send_token_exposed_email(
+    conn: &PgConnection,
+    email: &str,
+    user_name: &str,
+    token_name: &str,
+    url: Option<&str>,
+) -> CargoResult<()> {
+    let context = TokenExposed {
+        user_name,
+        token_name,
+        url,
+    };
+    send_email(conn, email, "token_exposed", &context)
+}
+
+#[derive(Serialize)]
+struct TokenExposed<'a> {
+    user_name: &'a str,
+    token_name: &'a str,
+    url: Option<&'a str>,
+}
+
+/// Tells a user that some of their API tokens weren't used for a long time,
+/// and suggests revoking them in case they leaked. `tokens` are the names of
+/// the tokens along with when they were last used, `None` if never.
+pub fn send_unused_tokens_email(
+    conn: &PgConnection,
+    email: &str,
+    user_name: &str,
+    tokens: &[(String, Option<String>)],
+) -> CargoResult<()> {
+    let context = UnusedTokens {
+        user_name,
+        tokens: tokens
+            .iter()
+            .map(|(name, last_used)| UnusedToken {
+                name,
+                last_used: last_used.as_ref().map(|s| &s[..]),
+            })
+            .collect(),
+    };
+    send_email(conn, email, "unused_tokens", &context)
+}
+
+#[derive(Serialize)]
+struct UnusedTokens<'a> {
+    user_name: &'a str,
+    tokens: Vec<UnusedToken<'a>>,
+}
+
+#[derive(Serialize)]
+struct UnusedToken<'a> {
+    name: &'a str,
+    last_used: Option<&'a str>,
+}
+
+fn send_email<T: Serialize>(
+    conn: &PgConnection,
+    recipient: &str,
+    kind: &str,
+    context: &T,
+) -> CargoResult<()> {
+    enqueue(conn, recipient, kind, context, None)
+}
+
+/// Sends an email that the recipient can opt out of, with the unsubscribe
+/// link of its template also in the `List-Unsubscribe` header. Emails that
+/// answer something the recipient did, like confirming their address, use
+/// `send_email` instead.
+fn send_notification_email<T: Serialize>(
+    conn: &PgConnection,
+    recipient: &str,
+    kind: &str,
+    context: &T,
+    unsubscribe: &Unsubscribe,
+) -> CargoResult<()> {
+    enqueue(conn, recipient, kind, context, Some(unsubscribe))
+}
+
+/// Enqueues a `deliver_email` job, so that requests don't wait for the
+/// backend. Addresses that can't be sent to are rejected right away, since
+/// retrying the job wouldn't help.
+fn enqueue<T: Serialize>(
+    conn: &PgConnection,
+    recipient: &str,
+    kind: &str,
+    context: &T,
+    unsubscribe: Option<&Unsubscribe>,
+) -> CargoResult<()> {
+    EmailAddress::new(recipient.to_string()).map_err(|_| bad_request("invalid email address"))?;
+    deliver_email(
+        recipient.to_string(),
+        kind.to_string(),
+        serde_json::to_value(context)?,
+        unsubscribe.cloned(),
+    )
+    .enqueue(conn)
+    .map_err(|e| CargoError::from_std_error(e))?;
+    Ok(())
+}
+
+/// Renders an email of the given kind and sends it with the backend. The
+/// unsubscribe link of notification emails is added to the `context` here,
+/// since it is signed with the session key. Failed attempts are retried with
+/// the backoff of the job's policy, and moved to the dead letters of the job
+/// queue once it gives up, see `models::JobPolicy`.
+#[swirl::background_job]
+pub fn deliver_email(
+    env: &Environment,
+    recipient: String,
+    kind: String,
+    context: serde_json::Value,
+    unsubscribe: Option<Unsubscribe>,
+) -> Result<(), PerformError> {
+    let unsubscribe_url =
+        unsubscribe.map(|unsubscribe| unsubscribe.url(&env.branding.url, &env.session_key));
+    let mut context = context;
+    if let Some(unsubscribe_url) = &unsubscribe_url {
+        context["unsubscribe_url"] = json!(unsubscribe_url);
+    }
+    let email =
+        render(&kind, DEFAULT_LOCALE, &env.branding, &context).map_err(std_error_no_send)?;
+    let backend = backend::from_env();
+    let email = build_email(
+        &recipient,
+        &email,
+        unsubscribe_url.as_ref().map(String::as_str),
+        backend.sender(),
+    )
+    .map_err(std_error_no_send)?;
+    backend.send(email).map_err(std_error_no_send)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{branding::Branding, schema::background_jobs, test_util::pg_connection};
+    use diesel::prelude::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn test_email() -> Rendered {
+        Rendered {
+            subject: String::from("test"),
+            text: String::from("test"),
+            html: String::from("<p>test</p>"),
+        }
+    }
 
     #[test]
     fn sending_to_invalid_email_fails() {
+        let conn = pg_connection();
+        let context = ConfirmEmail {
+            user_name: "ferris",
+            token: "abcdef",
+        };
         let result = send_email(
+            &conn,
             "String.Format(\"{0}.{1}@live.com\", FirstName, LastName)",
-            "test",
-            "test",
+            "confirm_email",
+            &context,
         );
         assert!(result.is_err());
     }
 
     #[test]
-    fn sending_to_valid_email_succeeds() {
-        let result = send_email("someone@example.com", "test", "test");
-        assert!(result.is_ok());
+    fn emails_are_sent_by_the_background_worker() {
+        let conn = pg_connection();
+        let context = ConfirmEmail {
+            user_name: "ferris",
+            token: "abcdef",
+        };
+        send_email(&conn, "someone@example.com", "confirm_email", &context).unwrap();
+        let jobs = background_jobs::table
+            .filter(background_jobs::job_type.eq("deliver_email"))
+            .count()
+            .get_result::<i64>(&conn)
+            .unwrap();
+        assert_eq!(jobs, 1);
+    }
+
+    #[test]
+    fn notification_emails_can_be_unsubscribed_from() {
+        let email = build_email(
+            "someone@example.com",
+            &test_email(),
+            Some(UNSUBSCRIBE_URL),
+            "test@localhost",
+        )
+        .unwrap();
+        let message = email.message_to_string().unwrap();
+        assert!(message.contains("List-Unsubscribe:"));
+        assert!(message.contains(&format!("<{}>", UNSUBSCRIBE_URL)));
+        assert!(message.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    }
+
+    const UNSUBSCRIBE_URL: &str = "https://crates.io/api/v1/email/unsubscribe/token";
+
+    fn fixture<T: Serialize>(kind: &'static str, context: T) -> (&'static str, Rendered) {
+        (
+            kind,
+            render(kind, DEFAULT_LOCALE, &Branding::default(), &context).unwrap(),
+        )
+    }
+
+    /// Adds the unsubscribe link that `deliver_email` adds to the context.
+    fn notification_fixture<T: Serialize>(
+        kind: &'static str,
+        context: T,
+    ) -> (&'static str, Rendered) {
+        let mut context = serde_json::to_value(context).unwrap();
+        context["unsubscribe_url"] = json!(UNSUBSCRIBE_URL);
+        fixture(kind, context)
+    }
+
+    /// Renders every kind of email with example data.
+    fn fixtures() -> Vec<(&'static str, Rendered)> {
+        vec![
+            fixture(
+                "confirm_email",
+                ConfirmEmail {
+                    user_name: "ferris",
+                    token: "abcdef",
+                },
+            ),
+            fixture(
+                "name_claim",
+                NameClaim {
+                    krate: "foo",
+                    claimant: "ferris",
+                    reason: "The crate has been abandoned for years.",
+                    days: 30,
+                },
+            ),
+            fixture(
+                "name_claim_decision",
+                NameClaimDecision {
+                    krate: "foo",
+                    claimant: "ferris",
+                    approved: true,
+                },
+            ),
+            fixture(
+                "name_claim_response",
+                NameClaimResponse {
+                    krate: "foo",
+                    released: false,
+                },
+            ),
+            fixture(
+                "owner_removal",
+                OwnerRemoval {
+                    krate: "foo",
+                    requested_by: "ferris",
+                    owner: "corro",
+                    effective_at: Some("2019-12-01 12:00"),
+                },
+            ),
+            fixture(
+                "successor_invitation",
+                SuccessorInvitation {
+                    krate: "foo",
+                    owner: "ferris",
+                    months: 12,
+                },
+            ),
+            fixture(
+                "successor_response",
+                SuccessorResponse {
+                    krate: "foo",
+                    successor: "corro",
+                    accepted: true,
+                },
+            ),
+            fixture(
+                "successor_transfer",
+                SuccessorTransfer {
+                    krate: "foo",
+                    owner: "ferris",
+                    successor: "corro",
+                },
+            ),
+            fixture(
+                "successor_warning",
+                SuccessorWarning {
+                    krate: "foo",
+                    successor: "corro",
+                    months: 12,
+                    transfer_on: "2020-12-01",
+                },
+            ),
+            fixture(
+                "token_exposed",
+                TokenExposed {
+                    user_name: "ferris",
+                    token_name: "laptop",
+                    url: Some("https://github.com/ferris/foo/blob/master/.cargo/credentials"),
+                },
+            ),
+            fixture(
+                "unused_tokens",
+                UnusedTokens {
+                    user_name: "ferris",
+                    tokens: vec![
+                        UnusedToken {
+                            name: "laptop",
+                            last_used: Some("2019-04-02"),
+                        },
+                        UnusedToken {
+                            name: "ci",
+                            last_used: None,
+                        },
+                    ],
+                },
+            ),
+            notification_fixture(
+                "update_digest",
+                UpdateDigest {
+                    releases: vec![
+                        Release {
+                            krate: "serde",
+                            version: "1.0.103",
+                        },
+                        Release {
+                            krate: "tokio",
+                            version: "0.2.2",
+                        },
+                    ],
+                    unsubscribe_url: UNSUBSCRIBE_URL,
+                },
+            ),
+            notification_fixture(
+                "version_published",
+                VersionPublished {
+                    krate: "libc",
+                    version: "0.2.66",
+                    publisher: "alexcrichton",
+                    checksum: "d44e80633f007889c7eff624b709ab43c92d708caad982295768a7b13ca3b5eb",
+                    unsubscribe_url: UNSUBSCRIBE_URL,
+                },
+            ),
+            notification_fixture(
+                "yanked_dependency",
+                YankedDependency {
+                    krate: "libc",
+                    version: "0.2.65",
+                    dependents: &[String::from("bar"), String::from("foo")],
+                    unsubscribe_url: UNSUBSCRIBE_URL,
+                },
+            ),
+        ]
+    }
+
+    /// Compares the rendered emails with the snapshots in `src/email/snapshots`.
+    /// Run the tests with `UPDATE_EMAIL_SNAPSHOTS=1` to update the snapshots
+    /// after changing a template.
+    #[test]
+    fn rendered_emails_match_snapshots() {
+        let fixtures = fixtures();
+        let kinds = fixtures.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            templates::kinds(),
+            "every kind of email needs a fixture"
+        );
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/email/snapshots");
+        for (kind, email) in fixtures {
+            let snapshot = format!(
+                "Subject: {}\n\n{}\n---\n\n{}",
+                email.subject, email.text, email.html
+            );
+            let path = dir.join(format!("{}.snap", kind));
+            if dotenv::var("UPDATE_EMAIL_SNAPSHOTS").is_ok() {
+                fs::write(&path, &snapshot).unwrap();
+            }
+            let expected = fs::read_to_string(&path).unwrap_or_default();
+            assert_eq!(
+                snapshot,
+                expected,
+                "{} is outdated, run the tests with UPDATE_EMAIL_SNAPSHOTS=1 to update it",
+                path.display()
+            );
+        }
     }
 }
@@ -0,0 +1,173 @@
+//! The services that deliver emails, selected with the `EMAIL_BACKEND`
+//! environment variable.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use lettre::file::FileTransport;
+use lettre::smtp::authentication::{Credentials, Mechanism};
+use lettre::smtp::SmtpClient;
+use lettre::{SendableEmail, Transport};
+
+use super::ses::SesBackend;
+use crate::env;
+use crate::logging;
+use crate::util::{bad_request, CargoResult};
+
+/// Something that delivers emails.
+pub trait EmailBackend: fmt::Debug {
+    /// The address emails are sent from.
+    fn sender(&self) -> &str;
+
+    /// Whether emails reach their recipients, as opposed to being written to
+    /// files or logs during development.
+    fn delivers(&self) -> bool {
+        true
+    }
+
+    fn send(&self, email: SendableEmail) -> CargoResult<()>;
+}
+
+/// Returns the backend configured by the environment:
+///
+/// - `EMAIL_BACKEND`: One of `smtp`, `mailgun`, `ses`, `file` or `log`. If
+///   unset, emails are sent with Mailgun if its variables are set, and written
+///   to files in `/tmp` otherwise.
+/// - `EMAIL_FROM`: The address emails are sent from. Defaults to the SMTP
+///   login, and is required for SES.
+/// - `SMTP_SERVER`, `SMTP_LOGIN`, `SMTP_PASSWORD`: The SMTP server and its
+///   credentials.
+/// - `MAILGUN_SMTP_SERVER`, `MAILGUN_SMTP_LOGIN`, `MAILGUN_SMTP_PASSWORD`: The
+///   same for Mailgun, as set by its Heroku add-on.
+/// - `SES_REGION`, `SES_ACCESS_KEY`, `SES_SECRET_KEY`: The AWS region of the
+///   Amazon SES API and the credentials to call it with.
+/// - `EMAIL_FILE_DIR`: Where the `file` backend writes emails to.
+pub fn from_env() -> Box<dyn EmailBackend> {
+    match dotenv::var("EMAIL_BACKEND")
+        .ok()
+        .as_ref()
+        .map(String::as_str)
+    {
+        Some("smtp") => Box::new(SmtpBackend::from_env("SMTP")),
+        Some("mailgun") => Box::new(SmtpBackend::from_env("MAILGUN_SMTP")),
+        Some("ses") => Box::new(SesBackend {
+            region: env("SES_REGION"),
+            access_key: env("SES_ACCESS_KEY"),
+            secret_key: env("SES_SECRET_KEY"),
+            sender: env("EMAIL_FROM"),
+        }),
+        Some("file") => Box::new(FileBackend {
+            dir: dotenv::var("EMAIL_FILE_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("/tmp")),
+        }),
+        Some("log") => Box::new(LogBackend),
+        Some(other) => panic!(
+            "EMAIL_BACKEND must be one of `smtp`, `mailgun`, `ses`, `file` or `log`, got {}",
+            other
+        ),
+        None if dotenv::var("MAILGUN_SMTP_SERVER").is_ok() => {
+            Box::new(SmtpBackend::from_env("MAILGUN_SMTP"))
+        }
+        None => Box::new(FileBackend {
+            dir: PathBuf::from("/tmp"),
+        }),
+    }
+}
+
+/// Sends emails through an SMTP server, e.g. Mailgun's.
+#[derive(Debug)]
+pub struct SmtpBackend {
+    pub server: String,
+    pub login: String,
+    pub password: String,
+    pub sender: String,
+}
+
+impl SmtpBackend {
+    /// Reads the `<prefix>_SERVER`, `<prefix>_LOGIN` and `<prefix>_PASSWORD`
+    /// variables.
+    fn from_env(prefix: &str) -> Self {
+        let login = env(&format!("{}_LOGIN", prefix));
+        SmtpBackend {
+            server: env(&format!("{}_SERVER", prefix)),
+            password: env(&format!("{}_PASSWORD", prefix)),
+            sender: dotenv::var("EMAIL_FROM").unwrap_or_else(|_| login.clone()),
+            login,
+        }
+    }
+}
+
+impl EmailBackend for SmtpBackend {
+    fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    fn send(&self, email: SendableEmail) -> CargoResult<()> {
+        let mut transport = SmtpClient::new_simple(&self.server)?
+            .credentials(Credentials::new(self.login.clone(), self.password.clone()))
+            .smtp_utf8(true)
+            .authentication_mechanism(Mechanism::Plain)
+            .transport();
+
+        let result = transport.send(email);
+        result.map_err(|_| bad_request("Error in sending email"))?;
+        Ok(())
+    }
+}
+
+/// Writes emails to JSON files in a directory, for development.
+#[derive(Debug)]
+pub struct FileBackend {
+    pub dir: PathBuf,
+}
+
+impl EmailBackend for FileBackend {
+    fn sender(&self) -> &str {
+        "test@localhost"
+    }
+
+    fn delivers(&self) -> bool {
+        false
+    }
+
+    fn send(&self, email: SendableEmail) -> CargoResult<()> {
+        let mut sender = FileTransport::new(&self.dir);
+        let result = sender.send(email);
+        result.map_err(|_| bad_request("Email file could not be generated"))?;
+        Ok(())
+    }
+}
+
+/// Prints emails to the log, for development.
+#[derive(Debug)]
+pub struct LogBackend;
+
+impl EmailBackend for LogBackend {
+    fn sender(&self) -> &str {
+        "test@localhost"
+    }
+
+    fn delivers(&self) -> bool {
+        false
+    }
+
+    fn send(&self, email: SendableEmail) -> CargoResult<()> {
+        let recipients = email
+            .envelope()
+            .to()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        logging::info(
+            "email",
+            json!({
+                "to": recipients,
+                "message_id": email.message_id(),
+                "message": email.message_to_string()?,
+            }),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Sending emails with the `SendRawEmail` action of the Amazon SES API.
+
+use chrono::{DateTime, Utc};
+use lettre::SendableEmail;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::header;
+use url::form_urlencoded;
+
+use super::backend::EmailBackend;
+use crate::util::CargoResult;
+
+#[derive(Debug)]
+pub struct SesBackend {
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub sender: String,
+}
+
+impl EmailBackend for SesBackend {
+    fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    fn send(&self, email: SendableEmail) -> CargoResult<()> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("Action", "SendRawEmail")
+            .append_pair("Version", "2010-12-01")
+            .append_pair(
+                "RawMessage.Data",
+                &base64::encode(email.message_to_string()?.as_bytes()),
+            )
+            .finish();
+
+        let host = format!("email.{}.amazonaws.com", self.region);
+        let now = Utc::now();
+        let authorization = self.authorization(&host, &now, &body);
+        reqwest::Client::new()
+            .post(&format!("https://{}/", host))
+            .header(header::CONTENT_TYPE, CONTENT_TYPE)
+            .header("X-Amz-Date", amz_date(&now))
+            .header(header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+impl SesBackend {
+    /// Returns the `Authorization` header for a request, signed with AWS
+    /// Signature Version 4.
+    fn authorization(&self, host: &str, now: &DateTime<Utc>, body: &str) -> String {
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/ses/aws4_request", date, self.region);
+        let signed_headers = "content-type;host;x-amz-date";
+        let canonical_request = format!(
+            "POST\n/\n\ncontent-type:{}\nhost:{}\nx-amz-date:{}\n\n{}\n{}",
+            CONTENT_TYPE,
+            host,
+            amz_date(now),
+            signed_headers,
+            sha256_hex(body.as_bytes()),
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date(now),
+            scope,
+            sha256_hex(canonical_request.as_bytes()),
+        );
+
+        let key = format!("AWS4{}", self.secret_key);
+        let key = hmac(key.as_bytes(), date.as_bytes());
+        let key = hmac(&key, self.region.as_bytes());
+        let key = hmac(&key, b"ses");
+        let key = hmac(&key, b"aws4_request");
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+fn amz_date(now: &DateTime<Utc>) -> String {
+    now.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(hash(MessageDigest::sha256(), data).unwrap())
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn requests_are_signed_for_the_region_and_day() {
+        let backend = SesBackend {
+            region: String::from("us-east-1"),
+            access_key: String::from("access"),
+            secret_key: String::from("secret"),
+            sender: String::from("noreply@example.com"),
+        };
+        let now = Utc.ymd(2019, 11, 28).and_hms(12, 0, 0);
+        let host = "email.us-east-1.amazonaws.com";
+
+        let authorization = backend.authorization(host, &now, "Action=SendRawEmail");
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=access/20191128/us-east-1/ses/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, Signature="
+        ));
+        assert_eq!(
+            authorization,
+            backend.authorization(host, &now, "Action=SendRawEmail")
+        );
+        assert_ne!(
+            authorization,
+            backend.authorization(host, &now, "Action=SendEmail")
+        );
+    }
+}
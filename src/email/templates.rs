@@ -0,0 +1,189 @@
+//! The Handlebars templates of the emails in the `templates` directory. Each
+//! kind of email has a subject, a plain text and an HTML template for every
+//! locale it has been translated to. Besides the variables of their kind of
+//! email, templates can use `branding.name` and `branding.url` to refer to
+//! the registry, see `Branding`.
+
+use handlebars::Handlebars;
+use serde::Serialize;
+
+use crate::branding::Branding;
+use crate::util::{internal, CargoResult};
+
+/// The locale used for kinds of emails that haven't been translated to the
+/// recipient's locale.
+pub const DEFAULT_LOCALE: &str = "en";
+
+struct Template {
+    locale: &'static str,
+    kind: &'static str,
+    subject: &'static str,
+    text: &'static str,
+    html: &'static str,
+}
+
+macro_rules! template {
+    ($locale:literal, $kind:literal) => {
+        Template {
+            locale: $locale,
+            kind: $kind,
+            subject: include_str!(concat!("templates/", $locale, "/", $kind, ".subject.hbs")),
+            text: include_str!(concat!("templates/", $locale, "/", $kind, ".txt.hbs")),
+            html: include_str!(concat!("templates/", $locale, "/", $kind, ".html.hbs")),
+        }
+    };
+}
+
+static TEMPLATES: &[Template] = &[
+    template!("en", "confirm_email"),
+    template!("en", "name_claim"),
+    template!("en", "name_claim_decision"),
+    template!("en", "name_claim_response"),
+    template!("en", "owner_removal"),
+    template!("en", "successor_invitation"),
+    template!("en", "successor_response"),
+    template!("en", "successor_transfer"),
+    template!("en", "successor_warning"),
+    template!("en", "token_exposed"),
+    template!("en", "unused_tokens"),
+    template!("en", "update_digest"),
+    template!("en", "version_published"),
+    template!("en", "yanked_dependency"),
+];
+
+/// An email rendered from its templates.
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Renders the templates of an email in the given locale, falling back to
+/// the default locale. Rendering fails if the context misses a variable used
+/// by the templates.
+pub fn render<T: Serialize>(
+    kind: &str,
+    locale: &str,
+    branding: &Branding,
+    context: &T,
+) -> CargoResult<Rendered> {
+    let find = |locale: &str| {
+        TEMPLATES
+            .iter()
+            .find(|template| template.kind == kind && template.locale == locale)
+    };
+    let template = find(locale)
+        .or_else(|| find(DEFAULT_LOCALE))
+        .ok_or_else(|| internal(&format_args!("no templates for {} emails", kind)))?;
+
+    let mut context = serde_json::to_value(context)?;
+    context["branding"] = json!({ "name": branding.display_name, "url": branding.url });
+
+    let mut text = Handlebars::new();
+    text.set_strict_mode(true);
+    text.register_escape_fn(handlebars::no_escape);
+    let mut html = Handlebars::new();
+    html.set_strict_mode(true);
+
+    Ok(Rendered {
+        subject: text
+            .render_template(template.subject, &context)?
+            .trim()
+            .to_string(),
+        text: text.render_template(template.text, &context)?,
+        html: html.render_template(template.html, &context)?,
+    })
+}
+
+/// Returns the kinds of emails that have templates.
+#[cfg(test)]
+pub fn kinds() -> Vec<&'static str> {
+    let mut kinds = TEMPLATES
+        .iter()
+        .map(|template| template.kind)
+        .collect::<Vec<_>>();
+    kinds.sort();
+    kinds.dedup();
+    kinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Context {
+        user_name: &'static str,
+        token: &'static str,
+    }
+
+    #[test]
+    fn untranslated_emails_fall_back_to_the_default_locale() {
+        let branding = Branding::default();
+        let context = Context {
+            user_name: "foo",
+            token: "bar",
+        };
+        assert_eq!(
+            render("confirm_email", "xx", &branding, &context).unwrap(),
+            render("confirm_email", DEFAULT_LOCALE, &branding, &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_variables_are_errors() {
+        #[derive(Serialize)]
+        struct Incomplete {
+            user_name: &'static str,
+        }
+        let branding = Branding::default();
+        assert!(render(
+            "confirm_email",
+            "en",
+            &branding,
+            &Incomplete { user_name: "foo" }
+        )
+        .is_err());
+        assert!(render(
+            "unknown",
+            "en",
+            &branding,
+            &Context {
+                user_name: "foo",
+                token: "bar"
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn html_is_escaped_but_text_is_not() {
+        let context = Context {
+            user_name: "<b>",
+            token: "bar",
+        };
+        let rendered = render("confirm_email", "en", &Branding::default(), &context).unwrap();
+        assert!(rendered.text.starts_with("Hello <b>!"));
+        assert!(rendered.html.starts_with("<p>Hello &lt;b&gt;!"));
+    }
+
+    #[test]
+    fn emails_refer_to_the_configured_registry() {
+        let branding = Branding {
+            display_name: String::from("Acme Crates"),
+            url: String::from("https://crates.acme.example"),
+            ..Branding::default()
+        };
+        let context = Context {
+            user_name: "foo",
+            token: "bar",
+        };
+        let rendered = render("confirm_email", "en", &branding, &context).unwrap();
+        assert!(rendered.text.contains("Welcome to Acme Crates."));
+        assert!(rendered
+            .html
+            .contains("https://crates.acme.example/confirm/bar"));
+        assert!(!rendered.html.contains("crates.io"));
+    }
+}
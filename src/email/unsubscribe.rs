@@ -0,0 +1,138 @@
+//! Signed tokens for the one-click unsubscribe links in notification emails.
+//!
+//! A token names the notification setting to turn off and carries an
+//! HMAC-SHA256 signature, so the `GET /email/unsubscribe/:token` route can
+//! apply it without the user signing in. Tokens are signed with a key derived
+//! from the session key, so that they can't be used as signatures of
+//! anything else signed with it.
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+/// A notification setting that the link in an email turns off.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unsubscribe {
+    /// The weekly digest of new releases of followed crates
+    UpdateDigest { user_id: i32 },
+    /// The notifications a user gets as an owner of these crates
+    CrateNotifications { user_id: i32, crate_ids: Vec<i32> },
+}
+
+impl Unsubscribe {
+    /// Returns the token for the link, which is the setting followed by a
+    /// hex encoded signature, e.g. `digest.42.<signature>`.
+    pub fn token(&self, session_key: &str) -> String {
+        let payload = self.payload();
+        format!("{}.{}", payload, hex::encode(sign(session_key, &payload)))
+    }
+
+    /// Returns the setting a token turns off, or `None` if the token is
+    /// malformed or its signature doesn't match.
+    pub fn from_token(token: &str, session_key: &str) -> Option<Self> {
+        let mut parts = token.rsplitn(2, '.');
+        let signature = hex::decode(parts.next()?).ok()?;
+        let payload = parts.next()?;
+        let expected = sign(session_key, payload);
+        if expected.len() != signature.len() || !openssl::memcmp::eq(&expected, &signature) {
+            return None;
+        }
+
+        let mut fields = payload.split('.');
+        let unsubscribe = match (fields.next()?, fields.next()?.parse().ok()?) {
+            ("digest", user_id) => Unsubscribe::UpdateDigest { user_id },
+            ("crates", user_id) => Unsubscribe::CrateNotifications {
+                user_id,
+                crate_ids: fields
+                    .next()?
+                    .split('-')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .ok()?,
+            },
+            _ => return None,
+        };
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(unsubscribe)
+    }
+
+    /// Returns the URL of the one-click unsubscribe link on the registry at
+    /// `base_url`, see `Branding::url`.
+    pub fn url(&self, base_url: &str, session_key: &str) -> String {
+        format!(
+            "{}/api/v1/email/unsubscribe/{}",
+            base_url,
+            self.token(session_key)
+        )
+    }
+
+    fn payload(&self) -> String {
+        match self {
+            Unsubscribe::UpdateDigest { user_id } => format!("digest.{}", user_id),
+            Unsubscribe::CrateNotifications { user_id, crate_ids } => format!(
+                "crates.{}.{}",
+                user_id,
+                crate_ids
+                    .iter()
+                    .map(i32::to_string)
+                    .collect::<Vec<_>>()
+                    .join("-")
+            ),
+        }
+    }
+}
+
+/// Signs the payload of a token with a key derived from the session key.
+fn sign(session_key: &str, payload: &str) -> Vec<u8> {
+    let key = hmac(session_key.as_bytes(), b"email-unsubscribe");
+    hmac(&key, payload.as_bytes())
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "test this has to be over 32 bytes long";
+
+    #[test]
+    fn tokens_round_trip() {
+        let digest = Unsubscribe::UpdateDigest { user_id: 42 };
+        assert_eq!(
+            Unsubscribe::from_token(&digest.token(KEY), KEY),
+            Some(digest)
+        );
+
+        let crates = Unsubscribe::CrateNotifications {
+            user_id: 42,
+            crate_ids: vec![1, 2, 3],
+        };
+        assert!(crates.token(KEY).starts_with("crates.42.1-2-3."));
+        assert_eq!(
+            Unsubscribe::from_token(&crates.token(KEY), KEY),
+            Some(crates)
+        );
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let token = Unsubscribe::UpdateDigest { user_id: 42 }.token(KEY);
+        let signature = token.rsplitn(2, '.').next().unwrap();
+        assert_eq!(
+            Unsubscribe::from_token(&format!("digest.43.{}", signature), KEY),
+            None
+        );
+        assert_eq!(Unsubscribe::from_token(&token, "another key"), None);
+        assert_eq!(Unsubscribe::from_token("digest.42", KEY), None);
+        assert_eq!(Unsubscribe::from_token("digest.42.00", KEY), None);
+        assert_eq!(Unsubscribe::from_token("", KEY), None);
+    }
+}
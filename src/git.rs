@@ -4,12 +4,16 @@ use std::collections::HashMap;
 use std::fs::{self, OpenOptions};
 use std::path::{Path, PathBuf};
 use swirl::errors::PerformError;
+use swirl::Job;
 use tempdir::TempDir;
 use url::Url;
 
 use crate::background_jobs::Environment;
-use crate::models::{DependencyKind, Version};
-use crate::schema::versions;
+use crate::logging;
+use crate::models::{self, DependencyKind, PrivateIndexEntry, Version};
+use crate::schema::{crates, private_index_entries, versions};
+use crate::telemetry::{self, Span, SpanContext, SpanKind};
+use crate::uploaders::Uploader;
 use crate::util::errors::{std_error_no_send, CargoResult};
 
 static DEFAULT_GIT_SSH_USERNAME: &str = "git";
@@ -65,6 +69,8 @@ pub struct Crate {
     pub yanked: Option<bool>,
     #[serde(default)]
     pub links: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advisory: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -97,10 +103,11 @@ impl RepositoryConfig {
         match (username, password, http_url, ssh_key, ssh_url) {
             (extra_user, extra_pass, extra_http_url, Ok(encoded_key), Ok(ssh_url)) => {
                 if let (Ok(_), Ok(_), Ok(_)) = (extra_user, extra_pass, extra_http_url) {
-                    println!(
-                        "warning: both http and ssh credentials to authenticate with git are set"
+                    logging::warn(
+                        "both http and ssh credentials to authenticate with git are set, \
+                         the ssh credentials take precedence",
+                        json!(null),
                     );
-                    println!("note: ssh credentials will take precedence over the http ones");
                 }
 
                 let index_location =
@@ -178,19 +185,22 @@ impl Repository {
     }
 
     fn relative_index_file(&self, name: &str) -> PathBuf {
-        let name = name.to_lowercase();
-        match name.len() {
-            1 => Path::new("1").join(&name),
-            2 => Path::new("2").join(&name),
-            3 => Path::new("3").join(&name[..1]).join(&name),
-            _ => Path::new(&name[0..2]).join(&name[2..4]).join(&name),
-        }
+        PathBuf::from(index_file_path(name))
     }
 
     fn commit_and_push(&self, msg: &str, modified_file: &Path) -> Result<(), PerformError> {
-        // git add $file
+        let mut span = Span::start("git.commit_and_push", SpanKind::Client);
+        span.record(self.try_commit_and_push(msg, modified_file))
+    }
+
+    fn try_commit_and_push(&self, msg: &str, modified_file: &Path) -> Result<(), PerformError> {
+        // git add $file, or git rm $file if it was deleted
         let mut index = self.repository.index()?;
-        index.add_path(modified_file)?;
+        if self.checkout_path.path().join(modified_file).exists() {
+            index.add_path(modified_file)?;
+        } else {
+            index.remove_path(modified_file)?;
+        }
         index.write()?;
         let tree_id = index.write_tree()?;
         let tree = self.repository.find_tree(tree_id)?;
@@ -248,22 +258,114 @@ impl Repository {
     }
 }
 
+/// The path of the index file listing the versions of the crate `name`,
+/// relative to the root of the index. This is the same for the git index
+/// and for sparse indexes served over HTTP.
+///
+/// The files of namespaced crates are in a directory per namespace, named
+/// like the namespace with a leading `@`. Crate names can't contain `@`, so
+/// these don't collide with the directories of other crates.
+pub fn index_file_path(name: &str) -> String {
+    if let Some((namespace, name)) = models::Crate::split_namespace(name) {
+        return format!("@{}/{}", namespace.to_lowercase(), index_file_path(name));
+    }
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[0..2], &name[2..4], name),
+    }
+}
+
 #[swirl::background_job]
-pub fn add_crate(env: &Environment, krate: Crate) -> Result<(), PerformError> {
-    use std::io::prelude::*;
+pub fn add_crate(
+    env: &Environment,
+    krate: Crate,
+    trace: Option<SpanContext>,
+) -> Result<(), PerformError> {
+    telemetry::in_job_span("add_crate", trace, || {
+        use std::io::prelude::*;
+
+        let repo = env.lock_index().map_err(std_error_no_send)?;
+        let conn = env.connection()?;
+        if let Some(private) = private_crate(&conn, &krate.name)? {
+            PrivateIndexEntry::insert(&conn, private.id, &krate).map_err(std_error_no_send)?;
+            return Ok(());
+        }
+        let dst = repo.index_file(&krate.name);
+
+        // Add the crate to its relevant file
+        fs::create_dir_all(dst.parent().unwrap())?;
+        let mut file = OpenOptions::new().append(true).create(true).open(&dst)?;
+        serde_json::to_writer(&mut file, &krate)?;
+        file.write_all(b"\n")?;
 
+        repo.commit_and_push(
+            &format!("Updating crate `{}#{}`", krate.name, krate.vers),
+            &repo.relative_index_file(&krate.name),
+        )
+    })
+}
+
+/// Removes all versions of a crate from the index. This is only meant for
+/// crates that are deleted by the registry administrators, such as spam.
+#[swirl::background_job]
+pub fn delete_crate(env: &Environment, krate: String) -> Result<(), PerformError> {
     let repo = env.lock_index().map_err(std_error_no_send)?;
-    let dst = repo.index_file(&krate.name);
+    let dst = repo.index_file(&krate);
+    if !dst.exists() {
+        return Ok(());
+    }
 
-    // Add the crate to its relevant file
-    fs::create_dir_all(dst.parent().unwrap())?;
-    let mut file = OpenOptions::new().append(true).create(true).open(&dst)?;
-    serde_json::to_writer(&mut file, &krate)?;
-    file.write_all(b"\n")?;
+    fs::remove_file(&dst)?;
+    repo.commit_and_push(
+        &format!("Deleting crate `{}`", krate),
+        &repo.relative_index_file(&krate),
+    )
+}
 
+/// Removes a version from the index. This is only meant for versions that
+/// their owners deleted shortly after publishing them.
+#[swirl::background_job]
+pub fn delete_version(
+    env: &Environment,
+    krate: String,
+    version: String,
+) -> Result<(), PerformError> {
+    let repo = env.lock_index().map_err(std_error_no_send)?;
+    let conn = env.connection()?;
+    if let Some(private) = private_crate(&conn, &krate)? {
+        PrivateIndexEntry::delete(&conn, private.id, &version)?;
+        return Ok(());
+    }
+    let dst = repo.index_file(&krate);
+    if !dst.exists() {
+        return Ok(());
+    }
+
+    let prev = fs::read_to_string(&dst)?;
+    let mut new = String::new();
+    let mut removed = false;
+    for line in prev.lines() {
+        let git_crate = serde_json::from_str::<Crate>(line)
+            .map_err(|_| format!("couldn't decode: `{}`", line))?;
+        if git_crate.vers == version {
+            removed = true;
+        } else {
+            new.push_str(line);
+            new.push('\n');
+        }
+    }
+    if !removed {
+        // The version was already removed
+        return Ok(());
+    }
+
+    fs::write(&dst, new.as_bytes())?;
     repo.commit_and_push(
-        &format!("Updating crate `{}#{}`", krate.name, krate.vers),
-        &repo.relative_index_file(&krate.name),
+        &format!("Deleting crate `{}#{}`", krate, version),
+        &repo.relative_index_file(&krate),
     )
 }
 
@@ -297,37 +399,245 @@ pub fn yank(
             return Ok(());
         }
 
-        let prev = fs::read_to_string(&dst)?;
-        let version_num = version.num.to_string();
-        let new = prev
-            .lines()
-            .map(|line| {
-                let mut git_crate = serde_json::from_str::<Crate>(line)
-                    .map_err(|_| format!("couldn't decode: `{}`", line))?;
-                if git_crate.name != krate || git_crate.vers != version_num {
-                    return Ok(line.to_string());
-                }
+        if let Some(private) = private_crate(&conn, &krate)? {
+            PrivateIndexEntry::update(&conn, private.id, &version.num.to_string(), |git_crate| {
                 git_crate.yanked = Some(yanked);
-                Ok(serde_json::to_string(&git_crate)?)
             })
-            .collect::<Result<Vec<_>, PerformError>>();
-        let new = new?.join("\n") + "\n";
-        fs::write(&dst, new.as_bytes())?;
-
-        repo.commit_and_push(
-            &format!(
-                "{} crate `{}#{}`",
-                if yanked { "Yanking" } else { "Unyanking" },
-                krate,
-                version.num
-            ),
-            &repo.relative_index_file(&krate),
-        )?;
+            .map_err(std_error_no_send)?;
+        } else {
+            update_index_line(&dst, &krate, &version, |git_crate| {
+                git_crate.yanked = Some(yanked);
+            })?;
+
+            repo.commit_and_push(
+                &format!(
+                    "{} crate `{}#{}`",
+                    if yanked { "Yanking" } else { "Unyanking" },
+                    krate,
+                    version.num
+                ),
+                &repo.relative_index_file(&krate),
+            )?;
+        }
 
         diesel::update(&version)
             .set(versions::yanked.eq(yanked))
             .execute(&*conn)?;
+        crate::models::Crate::update_default_versions(&conn, version.crate_id)?;
+
+        if yanked {
+            crate::tasks::notify_dependents_of_yank(version.id).enqueue(&*conn)?;
+            if env.uploader.invalidates_cdn() {
+                let paths = Uploader::version_paths(&krate, &version.num.to_string());
+                crate::cdn::invalidate_cdn(paths).enqueue(&*conn)?;
+            }
+        }
 
         Ok(())
     })
 }
+
+/// Sets or clears the advisory message of a crate version. Unlike yanking,
+/// this doesn't change whether the version is considered during resolution,
+/// the message is only surfaced to users of the version.
+#[swirl::background_job]
+pub fn update_advisory(
+    env: &Environment,
+    krate: String,
+    version: Version,
+    advisory: Option<String>,
+) -> Result<(), PerformError> {
+    use diesel::prelude::*;
+
+    let repo = env.lock_index().map_err(std_error_no_send)?;
+    let dst = repo.index_file(&krate);
+
+    let conn = env.connection()?;
+
+    conn.transaction(|| {
+        let advisory_in_db = versions::table
+            .find(version.id)
+            .select(versions::advisory)
+            .for_update()
+            .first::<Option<String>>(&*conn)?;
+
+        if advisory_in_db == advisory {
+            // The version already has the requested advisory, nothing to do
+            return Ok(());
+        }
+
+        if let Some(private) = private_crate(&conn, &krate)? {
+            PrivateIndexEntry::update(&conn, private.id, &version.num.to_string(), |git_crate| {
+                git_crate.advisory = advisory.clone();
+            })
+            .map_err(std_error_no_send)?;
+        } else {
+            update_index_line(&dst, &krate, &version, |git_crate| {
+                git_crate.advisory = advisory.clone();
+            })?;
+
+            repo.commit_and_push(
+                &format!(
+                    "{} advisory for crate `{}#{}`",
+                    if advisory.is_some() {
+                        "Setting"
+                    } else {
+                        "Removing"
+                    },
+                    krate,
+                    version.num
+                ),
+                &repo.relative_index_file(&krate),
+            )?;
+        }
+
+        diesel::update(&version)
+            .set(versions::advisory.eq(&advisory))
+            .execute(&*conn)?;
+
+        Ok(())
+    })
+}
+
+/// Moves the index entries of a crate out of the index when it was made
+/// private, and back into it when it was made public again, see
+/// `PrivateIndexEntry`. This includes the entries of versions published under
+/// old names of the crate.
+#[swirl::background_job]
+pub fn sync_index_visibility(env: &Environment, crate_id: i32) -> Result<(), PerformError> {
+    use diesel::prelude::*;
+
+    let repo = env.lock_index().map_err(std_error_no_send)?;
+    let conn = env.connection()?;
+
+    let krate = match crates::table
+        .find(crate_id)
+        .select(models::krate::ALL_COLUMNS)
+        .first::<models::Crate>(&*conn)
+        .optional()?
+    {
+        Some(krate) => krate,
+        None => return Ok(()),
+    };
+    let mut names = models::CrateRename::old_names(&conn, &krate)?;
+    names.push(krate.name.clone());
+
+    match krate.visibility {
+        models::Visibility::Private => {
+            for name in &names {
+                let dst = repo.index_file(name);
+                if !dst.exists() {
+                    continue;
+                }
+                // The entries are stored before they are removed from the
+                // index, so that retrying the job after a failed push doesn't
+                // lose any
+                conn.transaction(|| {
+                    for line in fs::read_to_string(&dst)?.lines() {
+                        let git_crate = serde_json::from_str::<Crate>(line)
+                            .map_err(|_| format!("couldn't decode: `{}`", line))?;
+                        PrivateIndexEntry::insert(&conn, krate.id, &git_crate)
+                            .map_err(std_error_no_send)?;
+                    }
+                    Ok::<_, PerformError>(())
+                })?;
+                fs::remove_file(&dst)?;
+                repo.commit_and_push(
+                    &format!("Hiding crate `{}`", name),
+                    &repo.relative_index_file(name),
+                )?;
+            }
+        }
+        models::Visibility::Public => {
+            let entries = PrivateIndexEntry::of_crate(&conn, krate.id)?;
+            for name in &names {
+                let dst = repo.index_file(name);
+                let prev = if dst.exists() {
+                    fs::read_to_string(&dst)?
+                } else {
+                    String::new()
+                };
+                // Entries that are already in the index were added by a
+                // previous attempt whose rows couldn't be deleted
+                let mut new = prev.clone();
+                for row in entries.iter().filter(|row| &row.name == name) {
+                    let git_crate = serde_json::from_value::<Crate>(row.entry.clone())?;
+                    let present = prev.lines().any(|line| {
+                        serde_json::from_str::<Crate>(line)
+                            .map(|line| line.vers == git_crate.vers)
+                            .unwrap_or(false)
+                    });
+                    if !present {
+                        new.push_str(&serde_json::to_string(&git_crate)?);
+                        new.push('\n');
+                    }
+                }
+                if new == prev {
+                    continue;
+                }
+                fs::create_dir_all(dst.parent().unwrap())?;
+                fs::write(&dst, new.as_bytes())?;
+                repo.commit_and_push(
+                    &format!("Publishing crate `{}`", name),
+                    &repo.relative_index_file(name),
+                )?;
+            }
+            diesel::delete(
+                private_index_entries::table.filter(private_index_entries::crate_id.eq(krate.id)),
+            )
+            .execute(&*conn)?;
+        }
+        // Pending crates aren't in the index yet, and their first version is
+        // held back in `PendingCrate`
+        models::Visibility::Pending => {}
+    }
+    Ok(())
+}
+
+/// The private crate that is or was named `name`, whose index entries are
+/// kept out of the index, see `PrivateIndexEntry`.
+fn private_crate(
+    conn: &diesel::PgConnection,
+    name: &str,
+) -> Result<Option<models::Crate>, PerformError> {
+    use diesel::prelude::*;
+
+    let krate = match models::Crate::by_name(name)
+        .first::<models::Crate>(conn)
+        .optional()?
+    {
+        Some(krate) => Some(krate),
+        None => models::CrateRename::renamed_crate(conn, name)?.map(|(_, krate)| krate),
+    };
+    Ok(krate.filter(|krate| krate.visibility == models::Visibility::Private))
+}
+
+/// Rewrites the line for the given crate version in the index file at `dst`,
+/// leaving all other lines untouched.
+fn update_index_line<F>(
+    dst: &Path,
+    krate: &str,
+    version: &Version,
+    mut f: F,
+) -> Result<(), PerformError>
+where
+    F: FnMut(&mut Crate),
+{
+    let prev = fs::read_to_string(dst)?;
+    let version_num = version.num.to_string();
+    let new = prev
+        .lines()
+        .map(|line| {
+            let mut git_crate = serde_json::from_str::<Crate>(line)
+                .map_err(|_| format!("couldn't decode: `{}`", line))?;
+            if git_crate.name != krate || git_crate.vers != version_num {
+                return Ok(line.to_string());
+            }
+            f(&mut git_crate);
+            Ok(serde_json::to_string(&git_crate)?)
+        })
+        .collect::<Result<Vec<_>, PerformError>>();
+    let new = new?.join("\n") + "\n";
+    fs::write(dst, new.as_bytes())?;
+    Ok(())
+}
@@ -8,7 +8,7 @@ use serde::de::DeserializeOwned;
 use std::str;
 
 use crate::app::App;
-use crate::util::{errors::NotFound, human, internal, CargoError, CargoResult};
+use crate::util::{errors::NotFound, internal, CargoError, CargoResult, ErrorCode};
 
 /// Does all the nonsense for sending a GET to Github. Doesn't handle parsing
 /// because custom error-code handling may be desirable. Use
@@ -17,10 +17,23 @@ pub fn github_api<T>(app: &App, url: &str, auth: &AccessToken) -> CargoResult<T>
 where
     T: DeserializeOwned,
 {
-    let url = format!("{}://api.github.com{}", app.config.api_protocol, url);
+    github_api_with_client(app.http_client(), &app.config.api_protocol, url, auth)
+}
+
+/// Like `github_api`, for background jobs, which don't have an `App`.
+pub fn github_api_with_client<T>(
+    client: &reqwest::Client,
+    api_protocol: &str,
+    url: &str,
+    auth: &AccessToken,
+) -> CargoResult<T>
+where
+    T: DeserializeOwned,
+{
+    let url = format!("{}://api.github.com{}", api_protocol, url);
     info!("GITHUB HTTP: {}", url);
 
-    app.http_client()
+    client
         .get(&url)
         .header(header::ACCEPT, "application/vnd.github.v3+json")
         .header(header::AUTHORIZATION, format!("token {}", auth.secret()))
@@ -35,15 +48,16 @@ fn handle_error_response(error: &reqwest::Error) -> Box<dyn CargoError> {
     use reqwest::StatusCode as Status;
 
     match error.status() {
-        Some(Status::UNAUTHORIZED) | Some(Status::FORBIDDEN) => human(
-            "It looks like you don't have permission \
+        Some(Status::UNAUTHORIZED) | Some(Status::FORBIDDEN) => ErrorCode::GithubAccessDenied
+            .human(
+                "It looks like you don't have permission \
              to query a necessary property from Github \
              to complete this request. \
              You may need to re-authenticate on \
              crates.io to grant permission to read \
              github org memberships. Just go to \
              https://crates.io/login",
-        ),
+            ),
         Some(Status::NOT_FOUND) => Box::new(NotFound),
         _ => internal(&format_args!(
             "didn't get a 200 result from github: {}",
@@ -60,3 +74,60 @@ pub fn team_url(login: &str) -> String {
         login_pieces.next().expect("org failed"),
     )
 }
+
+/// The owner and name of a repository on GitHub, from a repository URL like
+/// `https://github.com/rust-lang/cargo.git`. Returns `None` for repositories
+/// hosted elsewhere.
+pub fn repository_from_url(url: &str) -> Option<(String, String)> {
+    let path = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.");
+    if !path.starts_with("github.com/") {
+        return None;
+    }
+    let mut segments = path["github.com/".len()..].split('/');
+    let owner = segments.next().filter(|s| !s.is_empty())?;
+    let repo = segments.next()?.trim_end_matches(".git");
+    if repo.is_empty() {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(owner: &str, repo: &str) -> Option<(String, String)> {
+        Some((owner.into(), repo.into()))
+    }
+
+    #[test]
+    fn github_repositories_are_recognized() {
+        let cargo = repository("rust-lang", "cargo");
+        assert_eq!(
+            repository_from_url("https://github.com/rust-lang/cargo"),
+            cargo
+        );
+        assert_eq!(
+            repository_from_url("https://github.com/rust-lang/cargo.git"),
+            cargo
+        );
+        assert_eq!(
+            repository_from_url("http://www.github.com/rust-lang/cargo/"),
+            cargo
+        );
+        assert_eq!(
+            repository_from_url("https://github.com/rust-lang/cargo/tree/master/crates"),
+            cargo
+        );
+
+        assert_eq!(repository_from_url("https://github.com/rust-lang"), None);
+        assert_eq!(
+            repository_from_url("https://gitlab.com/rust-lang/cargo"),
+            None
+        );
+        assert_eq!(repository_from_url("https://github.com.evil.com/a/b"), None);
+    }
+}
@@ -0,0 +1,332 @@
+//! The OAuth providers users can log in with, see `controllers::user::session`
+//!
+//! GitHub is always available. Private deployments that aren't on GitHub can
+//! also let users log in with GitLab, including self-hosted instances, and
+//! Bitbucket. The logins of their users are qualified with the name of the
+//! provider, like `gitlab:ferris`, so that they can't be confused with GitHub
+//! users. GitHub teams can only be used by GitHub users.
+
+use oauth2::basic::BasicClient;
+use oauth2::prelude::*;
+use oauth2::{AccessToken, AuthUrl, ClientId, ClientSecret, RedirectUrl, Scope, TokenUrl};
+use reqwest::{header, Client};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::github;
+use crate::util::{human, internal, CargoResult};
+use crate::Config;
+
+pub const GITHUB: &str = "github";
+pub const GITLAB: &str = "gitlab";
+pub const BITBUCKET: &str = "bitbucket";
+
+/// The application registered with GitLab or Bitbucket to log users in with.
+#[derive(Clone, Debug)]
+pub struct OAuthClientConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// The base URL of the provider, without a trailing slash
+    pub url: String,
+    /// Where the provider sends users back to after they authorized the
+    /// application, if it isn't registered with the application
+    pub redirect_url: Option<String>,
+}
+
+impl OAuthClientConfig {
+    /// Reads the application from the environment variables
+    /// `{prefix}_CLIENT_ID`, `{prefix}_CLIENT_SECRET`, `{prefix}_URL` and
+    /// `{prefix}_REDIRECT_URL`. Returns `None` if the client ID isn't set.
+    pub fn from_environment(prefix: &str, default_url: &str) -> Option<Self> {
+        let var = |name: &str| dotenv::var(format!("{}_{}", prefix, name)).ok();
+        Some(Self {
+            client_id: var("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET").unwrap_or_default(),
+            url: var("URL")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| String::from(default_url)),
+            redirect_url: var("REDIRECT_URL"),
+        })
+    }
+
+    fn oauth_client(&self, authorize_path: &str, token_path: &str) -> BasicClient {
+        let url = |path: &str| {
+            Url::parse(&format!("{}{}", self.url, path)).expect("invalid identity provider URL")
+        };
+        let client = BasicClient::new(
+            ClientId::new(self.client_id.clone()),
+            Some(ClientSecret::new(self.client_secret.clone())),
+            AuthUrl::new(url(authorize_path)),
+            Some(TokenUrl::new(url(token_path))),
+        );
+        match self.redirect_url {
+            Some(ref redirect_url) => client.set_redirect_url(RedirectUrl::new(
+                Url::parse(redirect_url).expect("invalid identity provider redirect URL"),
+            )),
+            None => client,
+        }
+    }
+}
+
+/// A user as the identity provider knows them.
+#[derive(Debug)]
+pub struct ProviderUser {
+    /// The ID of the user, which stays the same when they rename their account
+    pub id: String,
+    /// The login of the user, not qualified with the name of the provider
+    pub login: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+pub trait IdentityProvider: Send + Sync {
+    /// The name of the provider in routes and in the logins of its users
+    fn name(&self) -> &'static str;
+
+    /// The client of the application registered with the provider
+    fn oauth_client(&self) -> &BasicClient;
+
+    /// Asks the provider which user an access token belongs to.
+    fn user(&self, http_client: &Client, token: &AccessToken) -> CargoResult<ProviderUser>;
+}
+
+/// Returns the login of a user of `provider` in the `users` table.
+pub fn qualified_login(provider: &str, login: &str) -> String {
+    if provider == GITHUB {
+        login.to_string()
+    } else {
+        format!("{}:{}", provider, login)
+    }
+}
+
+/// Returns whether `login` is the login of a user of a provider other than
+/// GitHub, as opposed to the name of a GitHub team.
+pub fn is_qualified_user_login(login: &str) -> bool {
+    [GITLAB, BITBUCKET]
+        .iter()
+        .any(|provider| login.starts_with(&format!("{}:", provider)))
+}
+
+/// Returns the identity providers enabled by the config, GitHub first.
+pub fn from_config(config: &Config) -> Vec<Box<dyn IdentityProvider>> {
+    let mut providers: Vec<Box<dyn IdentityProvider>> = vec![Box::new(GitHub::new(config))];
+    if let Some(ref gitlab) = config.gitlab {
+        providers.push(Box::new(GitLab::new(gitlab)));
+    }
+    if let Some(ref bitbucket) = config.bitbucket {
+        providers.push(Box::new(Bitbucket::new(bitbucket)));
+    }
+    providers
+}
+
+// The oauth types don't implement debug.
+#[allow(missing_debug_implementations)]
+pub struct GitHub {
+    client: BasicClient,
+    api_protocol: String,
+}
+
+impl GitHub {
+    pub fn new(config: &Config) -> Self {
+        let client = BasicClient::new(
+            ClientId::new(config.gh_client_id.clone()),
+            Some(ClientSecret::new(config.gh_client_secret.clone())),
+            AuthUrl::new(Url::parse("https://github.com/login/oauth/authorize").unwrap()),
+            Some(TokenUrl::new(
+                Url::parse("https://github.com/login/oauth/access_token").unwrap(),
+            )),
+        )
+        .add_scope(Scope::new("read:org".to_string()));
+
+        Self {
+            client,
+            api_protocol: config.api_protocol.clone(),
+        }
+    }
+}
+
+impl IdentityProvider for GitHub {
+    fn name(&self) -> &'static str {
+        GITHUB
+    }
+
+    fn oauth_client(&self) -> &BasicClient {
+        &self.client
+    }
+
+    fn user(&self, http_client: &Client, token: &AccessToken) -> CargoResult<ProviderUser> {
+        #[derive(Deserialize)]
+        struct GithubUser {
+            email: Option<String>,
+            name: Option<String>,
+            login: String,
+            id: i32,
+            avatar_url: Option<String>,
+        }
+
+        let user = github::github_api_with_client::<GithubUser>(
+            http_client,
+            &self.api_protocol,
+            "/user",
+            token,
+        )?;
+        Ok(ProviderUser {
+            id: user.id.to_string(),
+            login: user.login,
+            email: user.email,
+            name: user.name,
+            avatar: user.avatar_url,
+        })
+    }
+}
+
+// The oauth types don't implement debug.
+#[allow(missing_debug_implementations)]
+pub struct GitLab {
+    client: BasicClient,
+    url: String,
+}
+
+impl GitLab {
+    pub fn new(config: &OAuthClientConfig) -> Self {
+        let client = config
+            .oauth_client("/oauth/authorize", "/oauth/token")
+            .add_scope(Scope::new("read_user".to_string()));
+        Self {
+            client,
+            url: config.url.clone(),
+        }
+    }
+}
+
+impl IdentityProvider for GitLab {
+    fn name(&self) -> &'static str {
+        GITLAB
+    }
+
+    fn oauth_client(&self) -> &BasicClient {
+        &self.client
+    }
+
+    fn user(&self, http_client: &Client, token: &AccessToken) -> CargoResult<ProviderUser> {
+        #[derive(Deserialize)]
+        struct GitLabUser {
+            id: i64,
+            username: String,
+            name: Option<String>,
+            email: Option<String>,
+            avatar_url: Option<String>,
+        }
+
+        let url = format!("{}/api/v4/user", self.url);
+        let user = get_json::<GitLabUser>(http_client, &url, token)?;
+        Ok(ProviderUser {
+            id: user.id.to_string(),
+            login: user.username,
+            email: user.email,
+            name: user.name,
+            avatar: user.avatar_url,
+        })
+    }
+}
+
+// The oauth types don't implement debug.
+#[allow(missing_debug_implementations)]
+pub struct Bitbucket {
+    client: BasicClient,
+}
+
+impl Bitbucket {
+    pub fn new(config: &OAuthClientConfig) -> Self {
+        // The scopes are configured with the application on Bitbucket
+        let client = config.oauth_client("/site/oauth2/authorize", "/site/oauth2/access_token");
+        Self { client }
+    }
+}
+
+impl IdentityProvider for Bitbucket {
+    fn name(&self) -> &'static str {
+        BITBUCKET
+    }
+
+    fn oauth_client(&self) -> &BasicClient {
+        &self.client
+    }
+
+    fn user(&self, http_client: &Client, token: &AccessToken) -> CargoResult<ProviderUser> {
+        #[derive(Deserialize)]
+        struct BitbucketUser {
+            uuid: String,
+            nickname: String,
+            display_name: Option<String>,
+            links: Links,
+        }
+        #[derive(Deserialize)]
+        struct Links {
+            avatar: Option<Link>,
+        }
+        #[derive(Deserialize)]
+        struct Link {
+            href: String,
+        }
+        #[derive(Deserialize)]
+        struct Emails {
+            values: Vec<Email>,
+        }
+        #[derive(Deserialize)]
+        struct Email {
+            email: String,
+            is_primary: bool,
+            is_confirmed: bool,
+        }
+
+        let user =
+            get_json::<BitbucketUser>(http_client, "https://api.bitbucket.org/2.0/user", token)?;
+        let emails = get_json::<Emails>(
+            http_client,
+            "https://api.bitbucket.org/2.0/user/emails",
+            token,
+        )?;
+        Ok(ProviderUser {
+            id: user.uuid,
+            login: user.nickname,
+            email: emails
+                .values
+                .into_iter()
+                .find(|email| email.is_primary && email.is_confirmed)
+                .map(|email| email.email),
+            name: user.display_name,
+            avatar: user.links.avatar.map(|link| link.href),
+        })
+    }
+}
+
+fn get_json<T: DeserializeOwned>(
+    http_client: &Client,
+    url: &str,
+    token: &AccessToken,
+) -> CargoResult<T> {
+    info!("IDENTITY PROVIDER HTTP: {}", url);
+
+    let response = http_client
+        .get(url)
+        .header(header::ACCEPT, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token.secret()))
+        .send()?;
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(human(
+            "the identity provider didn't accept the access token",
+        ));
+    }
+    response
+        .error_for_status()
+        .map_err(|e| {
+            internal(&format_args!(
+                "request to the identity provider failed: {}",
+                e
+            ))
+        })?
+        .json()
+        .map_err(Into::into)
+}
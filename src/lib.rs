@@ -13,13 +13,22 @@ extern crate derive_deref;
 #[macro_use]
 extern crate diesel;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 #[macro_use]
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
 
-pub use crate::{app::App, config::Config, uploaders::Uploader};
+pub use crate::{
+    app::App,
+    config::{
+        Config, CorsConfig, CorsPolicy, DependencyValidation, DownloadCounting, TarballScan,
+        TyposquatCheck,
+    },
+    uploaders::Uploader,
+};
 use std::sync::Arc;
 
 use conduit_middleware::MiddlewareBuilder;
@@ -29,19 +38,34 @@ use jemallocator::Jemalloc;
 static ALLOC: Jemalloc = Jemalloc;
 
 mod app;
+pub mod async_server;
 pub mod background_jobs;
 pub mod boot;
+pub mod branding;
+pub mod cdn;
 mod config;
 pub mod db;
 pub mod email;
 pub mod git;
 pub mod github;
+pub mod identity;
+pub mod logging;
+pub mod metrics;
 pub mod middleware;
-mod publish_rate_limit;
+pub mod openapi;
+pub mod rate_limit;
 pub mod render;
+pub mod response_cache;
+pub mod scan;
+pub mod scheduler;
 pub mod schema;
+pub mod search_strategy;
+pub mod storage;
 pub mod tasks;
+pub mod telemetry;
+mod test_fixtures;
 mod test_util;
+pub mod typosquat;
 pub mod uploaders;
 pub mod util;
 
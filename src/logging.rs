@@ -0,0 +1,105 @@
+//! Structured logs, written to stdout as one JSON object per line.
+//!
+//! Every line carries the trace ID of the request being handled by the
+//! current thread, if any. The `RequestIds` middleware sets it from the
+//! `X-Request-Id` header, and it is also passed on to the database sessions
+//! and the background jobs of the request, so that all of their logs can be
+//! correlated.
+
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+
+/// The longest trace ID accepted from a request header, like Heroku's router.
+const MAX_TRACE_ID_LENGTH: usize = 200;
+
+thread_local! {
+    static TRACE_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Runs `f` with `trace_id` as the trace ID of the current thread.
+pub fn with_trace_id<T>(trace_id: &str, f: impl FnOnce() -> T) -> T {
+    let previous = TRACE_ID.with(|id| id.replace(Some(trace_id.to_string())));
+    let result = f();
+    TRACE_ID.with(|id| *id.borrow_mut() = previous);
+    result
+}
+
+/// Returns the trace ID of the current thread.
+pub fn trace_id() -> Option<String> {
+    TRACE_ID.with(|id| id.borrow().clone())
+}
+
+/// Generates a random trace ID for requests that don't come with one.
+pub fn new_trace_id() -> String {
+    format!(
+        "{:016x}{:016x}",
+        rand::random::<u64>(),
+        rand::random::<u64>()
+    )
+}
+
+/// Whether a trace ID from a request header can be logged and passed on as
+/// is.
+pub fn is_valid_trace_id(trace_id: &str) -> bool {
+    !trace_id.is_empty()
+        && trace_id.len() <= MAX_TRACE_ID_LENGTH
+        && trace_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+pub fn info(message: &str, fields: Value) {
+    println!("{}", line("info", message, fields));
+}
+
+pub fn warn(message: &str, fields: Value) {
+    println!("{}", line("warn", message, fields));
+}
+
+pub fn error(message: &str, fields: Value) {
+    println!("{}", line("error", message, fields));
+}
+
+/// Formats a log line. `fields` is an object of additional values, or
+/// `null`.
+fn line(level: &str, message: &str, fields: Value) -> String {
+    let mut object = match fields {
+        Value::Object(object) => object,
+        Value::Null => Map::new(),
+        other => panic!("log fields must be an object, got {}", other),
+    };
+    object.insert("at".into(), level.into());
+    object.insert("msg".into(), message.into());
+    if let Some(trace_id) = trace_id() {
+        object.insert("trace_id".into(), trace_id.into());
+    }
+    Value::Object(object).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_carry_the_trace_id() {
+        let fields = json!({ "rows": 3 });
+        let without = line("info", "updated", fields.clone());
+        assert_eq!(without, r#"{"at":"info","msg":"updated","rows":3}"#);
+
+        let with = with_trace_id("abcd", || line("error", "updated", fields));
+        assert_eq!(
+            with,
+            r#"{"at":"error","msg":"updated","rows":3,"trace_id":"abcd"}"#
+        );
+        assert_eq!(trace_id(), None);
+    }
+
+    #[test]
+    fn header_trace_ids_are_validated() {
+        assert!(is_valid_trace_id("4b0b9a3e-1c2d-4f5a-9e8b-0123456789ab"));
+        assert!(is_valid_trace_id(&new_trace_id()));
+        assert!(!is_valid_trace_id(""));
+        assert!(!is_valid_trace_id("a\"b"));
+        assert!(!is_valid_trace_id(&"a".repeat(201)));
+    }
+}
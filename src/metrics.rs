@@ -0,0 +1,240 @@
+//! Metrics of the server process, exported in the Prometheus text format by
+//! the `GET /metrics` route.
+//!
+//! Requests are counted by the `RecordMetrics` middleware, and published and
+//! downloaded crates by their controllers. The state of the database pool and
+//! of the background job queue is read when the metrics are requested.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// The upper bounds of the buckets of the request duration histogram, in
+/// seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, &'static str), RequestStats>>,
+    publishes: AtomicU64,
+    downloads: AtomicU64,
+    db_checkouts: AtomicU64,
+    db_checkout_timeouts: AtomicU64,
+    db_checkout_micros: AtomicU64,
+}
+
+/// The requests of a method and route.
+#[derive(Debug, Default)]
+struct RequestStats {
+    /// The number of responses by status code
+    statuses: BTreeMap<u16, u64>,
+    /// The number of requests that took at most the upper bound of each of
+    /// the `DURATION_BUCKETS`
+    buckets: Vec<u64>,
+    seconds: f64,
+}
+
+impl Metrics {
+    /// Records a request. `route` is the pattern of the route that handled
+    /// the request, so that paths with different parameters count as one.
+    pub fn record_request(&self, method: &str, route: &'static str, status: u16, time: Duration) {
+        let seconds = time.as_secs() as f64 + f64::from(time.subsec_nanos()) / 1e9;
+
+        let mut requests = self.requests.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = requests.entry((method.to_string(), route)).or_default();
+        *stats.statuses.entry(status).or_insert(0) += 1;
+        stats.buckets.resize(DURATION_BUCKETS.len(), 0);
+        for (bucket, upper_bound) in stats.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= *upper_bound {
+                *bucket += 1;
+            }
+        }
+        stats.seconds += seconds;
+    }
+
+    pub fn record_publish(&self) {
+        self.publishes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_download(&self) {
+        self.downloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the checkout of a database connection by a request, and
+    /// whether one was available in time.
+    pub fn record_db_checkout(&self, wait: Duration, available: bool) {
+        let micros = wait.as_secs() * 1_000_000 + u64::from(wait.subsec_micros());
+        self.db_checkouts.fetch_add(1, Ordering::Relaxed);
+        self.db_checkout_micros.fetch_add(micros, Ordering::Relaxed);
+        if !available {
+            self.db_checkout_timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn write_to(&self, out: &mut Exposition) {
+        let requests = self.requests.lock().unwrap_or_else(PoisonError::into_inner);
+
+        out.header(
+            "http_requests_total",
+            "counter",
+            "Requests by method, route and status code",
+        );
+        for ((method, route), stats) in requests.iter() {
+            for (status, count) in &stats.statuses {
+                let status = status.to_string();
+                let labels = [
+                    ("method", method.as_str()),
+                    ("route", *route),
+                    ("status", status.as_str()),
+                ];
+                out.sample("http_requests_total", &labels, count);
+            }
+        }
+
+        out.header(
+            "http_request_duration_seconds",
+            "histogram",
+            "Time taken to respond to requests by method and route",
+        );
+        for ((method, route), stats) in requests.iter() {
+            let labels = [("method", method.as_str()), ("route", *route)];
+            for (count, upper_bound) in stats.buckets.iter().zip(DURATION_BUCKETS) {
+                let upper_bound = upper_bound.to_string();
+                let labels = [labels[0], labels[1], ("le", upper_bound.as_str())];
+                out.sample("http_request_duration_seconds_bucket", &labels, count);
+            }
+            let count = stats.statuses.values().sum::<u64>();
+            let labels = [labels[0], labels[1], ("le", "+Inf")];
+            out.sample("http_request_duration_seconds_bucket", &labels, count);
+            out.sample(
+                "http_request_duration_seconds_sum",
+                &labels[..2],
+                stats.seconds,
+            );
+            out.sample("http_request_duration_seconds_count", &labels[..2], count);
+        }
+
+        out.header("crate_publishes_total", "counter", "Published versions");
+        out.sample(
+            "crate_publishes_total",
+            &[],
+            self.publishes.load(Ordering::Relaxed),
+        );
+        out.header("crate_downloads_total", "counter", "Downloaded versions");
+        out.sample(
+            "crate_downloads_total",
+            &[],
+            self.downloads.load(Ordering::Relaxed),
+        );
+
+        out.header(
+            "database_checkouts_total",
+            "counter",
+            "Database connections checked out by requests",
+        );
+        out.sample(
+            "database_checkouts_total",
+            &[],
+            self.db_checkouts.load(Ordering::Relaxed),
+        );
+        out.header(
+            "database_checkout_timeouts_total",
+            "counter",
+            "Requests that failed because no database connection was available in time",
+        );
+        out.sample(
+            "database_checkout_timeouts_total",
+            &[],
+            self.db_checkout_timeouts.load(Ordering::Relaxed),
+        );
+        out.header(
+            "database_checkout_seconds_total",
+            "counter",
+            "Time requests spent waiting for database connections",
+        );
+        out.sample(
+            "database_checkout_seconds_total",
+            &[],
+            self.db_checkout_micros.load(Ordering::Relaxed) as f64 / 1e6,
+        );
+    }
+}
+
+/// Metrics in the Prometheus text format. Names are prefixed with
+/// `cargo_registry_`.
+#[derive(Debug, Default)]
+pub struct Exposition(String);
+
+impl Exposition {
+    /// Starts a metric. All its samples have to follow.
+    pub fn header(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.0, "# HELP cargo_registry_{} {}", name, help).unwrap();
+        writeln!(self.0, "# TYPE cargo_registry_{} {}", name, kind).unwrap();
+    }
+
+    pub fn sample<T: Display>(&mut self, name: &str, labels: &[(&str, &str)], value: T) {
+        write!(self.0, "cargo_registry_{}", name).unwrap();
+        for (i, (label, value)) in labels.iter().enumerate() {
+            let separator = if i == 0 { '{' } else { ',' };
+            write!(self.0, "{}{}=\"{}\"", separator, label, escape(value)).unwrap();
+        }
+        if !labels.is_empty() {
+            self.0.push('}');
+        }
+        writeln!(self.0, " {}", value).unwrap();
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_counted_per_route() {
+        let metrics = Metrics::default();
+        let route = "/crates/:crate_id";
+        metrics.record_request("GET", route, 200, Duration::from_millis(20));
+        metrics.record_request("GET", route, 200, Duration::from_millis(300));
+        metrics.record_request("GET", route, 404, Duration::from_secs(20));
+        metrics.record_download();
+
+        let mut out = Exposition::default();
+        metrics.write_to(&mut out);
+        let out = out.into_string();
+        for line in &[
+            "cargo_registry_http_requests_total{method=\"GET\",route=\"/crates/:crate_id\",status=\"200\"} 2",
+            "cargo_registry_http_requests_total{method=\"GET\",route=\"/crates/:crate_id\",status=\"404\"} 1",
+            "cargo_registry_http_request_duration_seconds_bucket{method=\"GET\",route=\"/crates/:crate_id\",le=\"0.01\"} 0",
+            "cargo_registry_http_request_duration_seconds_bucket{method=\"GET\",route=\"/crates/:crate_id\",le=\"0.025\"} 1",
+            "cargo_registry_http_request_duration_seconds_bucket{method=\"GET\",route=\"/crates/:crate_id\",le=\"0.5\"} 2",
+            "cargo_registry_http_request_duration_seconds_bucket{method=\"GET\",route=\"/crates/:crate_id\",le=\"+Inf\"} 3",
+            "cargo_registry_http_request_duration_seconds_count{method=\"GET\",route=\"/crates/:crate_id\"} 3",
+            "cargo_registry_crate_publishes_total 0",
+            "cargo_registry_crate_downloads_total 1",
+        ] {
+            assert!(out.lines().any(|l| l == *line), "missing `{}` in\n{}", line, out);
+        }
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut out = Exposition::default();
+        out.sample("x", &[("a", "\"\\\n")], 1);
+        assert_eq!(out.into_string(), "cargo_registry_x{a=\"\\\"\\\\\\n\"} 1\n");
+    }
+}
@@ -10,11 +10,13 @@ pub use self::debug::*;
 pub use self::ember_index_rewrite::EmberIndexRewrite;
 pub use self::head::Head;
 use self::log_connection_pool_status::LogConnectionPoolStatus;
+pub use self::request_id::{request_id, RequestId};
 pub use self::security_headers::SecurityHeaders;
 pub use self::static_or_continue::StaticOrContinue;
 
 pub mod app;
 mod block_traffic;
+mod cors;
 pub mod current_user;
 mod debug;
 mod ember_index_rewrite;
@@ -22,9 +24,14 @@ mod ensure_well_formed_500;
 mod head;
 mod log_connection_pool_status;
 mod log_request;
+mod rate_limit;
+mod record_metrics;
+mod request_id;
 mod require_user_agent;
 mod security_headers;
 mod static_or_continue;
+mod token_usage;
+mod trace_requests;
 
 use conduit_conditional_get::ConditionalGet;
 use conduit_cookie::{Middleware as Cookie, SessionMiddleware};
@@ -40,6 +47,7 @@ pub fn build_middleware(app: Arc<App>, endpoints: R404) -> MiddlewareBuilder {
     let mut m = MiddlewareBuilder::new(endpoints);
     let config = app.config.clone();
     let env = config.env;
+    let record_metrics = record_metrics::RecordMetrics::new(&app);
 
     if env != Env::Test {
         m.add(ensure_well_formed_500::EnsureWellFormed500);
@@ -86,17 +94,37 @@ pub fn build_middleware(app: Arc<App>, endpoints: R404) -> MiddlewareBuilder {
         // Note: around middleware is run from bottom to top, so the rewrite occurs first
     }
 
+    m.around(token_usage::TokenUsage::default());
+    m.around(rate_limit::RateLimit::new(config.rate_limiter.clone()));
     m.around(Head::default());
 
     for (header, blocked_values) in config.blocked_traffic {
-        m.around(block_traffic::BlockTraffic::new(header, blocked_values));
+        m.around(block_traffic::BlockTraffic::new(
+            header,
+            blocked_values,
+            config.branding.clone(),
+        ));
     }
 
-    m.around(require_user_agent::RequireUserAgent::default());
+    m.around(require_user_agent::RequireUserAgent::new(
+        config.branding.support_email.clone(),
+    ));
+
+    // Answers preflight requests before they are rate limited or need a user agent
+    if config.cors.is_enabled() {
+        m.around(cors::Cors::new(config.cors.clone()));
+    }
+
+    m.around(record_metrics);
 
     if env != Env::Test {
         m.around(log_request::LogRequests::default());
     }
 
+    m.around(trace_requests::TraceRequests::default());
+
+    // Added last so that the ID is available to all other middleware
+    m.around(request_id::RequestIds::default());
+
     m
 }
@@ -1,9 +1,13 @@
 use super::prelude::*;
 
+use crate::db::DatabaseDeadline;
+use crate::util::errors::std_error_response;
 use crate::App;
 use std::sync::Arc;
+use std::time::Instant;
 
-/// Middleware that injects the `App` instance into the `Request` extensions
+/// Middleware that injects the `App` instance into the `Request` extensions,
+/// along with the `DatabaseDeadline` of the request
 // Can't derive Debug because `App` can't.
 #[allow(missing_debug_implementations)]
 pub struct AppMiddleware {
@@ -19,6 +23,10 @@ impl AppMiddleware {
 impl Middleware for AppMiddleware {
     fn before(&self, req: &mut dyn Request) -> Result<(), Box<dyn Error + Send>> {
         req.mut_extensions().insert(Arc::clone(&self.app));
+        if let Some(budget) = self.app.config.db_request_budget {
+            req.mut_extensions()
+                .insert(DatabaseDeadline(Instant::now() + budget));
+        }
         Ok(())
     }
 
@@ -28,7 +36,9 @@ impl Middleware for AppMiddleware {
         res: Result<Response, Box<dyn Error + Send>>,
     ) -> Result<Response, Box<dyn Error + Send>> {
         req.mut_extensions().pop::<Arc<App>>().unwrap();
-        res
+        // The following middleware, like `CurrentUser`, can fail to check out
+        // a database connection
+        res.or_else(|e| std_error_response(&*e).ok_or(e))
     }
 }
 
@@ -10,6 +10,9 @@
 
 use super::prelude::*;
 
+use super::request_id;
+use crate::branding::Branding;
+
 use std::collections::HashMap;
 use std::io::Cursor;
 
@@ -19,14 +22,16 @@ use std::io::Cursor;
 pub struct BlockTraffic {
     header_name: String,
     blocked_values: Vec<String>,
+    branding: Branding,
     handler: Option<Box<dyn Handler>>,
 }
 
 impl BlockTraffic {
-    pub fn new(header_name: String, blocked_values: Vec<String>) -> Self {
+    pub fn new(header_name: String, blocked_values: Vec<String>, branding: Branding) -> Self {
         Self {
             header_name,
             blocked_values,
+            branding,
             handler: None,
         }
     }
@@ -47,14 +52,22 @@ impl Handler for BlockTraffic {
             .iter()
             .any(|value| self.blocked_values.iter().any(|v| v == value));
         if has_blocked_value {
+            let contact = match self.branding.issue_tracker_url {
+                Some(ref url) => format!(
+                    "Please open an issue at {} or email {}",
+                    url, self.branding.support_email
+                ),
+                None => format!("Please email {}", self.branding.support_email),
+            };
             let body = format!(
                 "We are unable to process your request at this time. \
                  This usually means that you are in violation of our crawler \
-                 policy (https://crates.io/policies#crawlers). \
-                 Please open an issue at https://github.com/rust-lang/crates.io \
-                 or email help@crates.io \
+                 policy ({}). \
+                 {} \
                  and provide the request id {}",
-                req.headers().find("X-Request-Id").unwrap()[0]
+                self.branding.crawler_policy_url,
+                contact,
+                request_id(req)
             );
             let mut headers = HashMap::new();
             headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
@@ -0,0 +1,128 @@
+//! Middleware that lets other websites call the API from browsers
+//!
+//! Browsers only let websites read the responses of requests to other origins if the responses
+//! allow it with `Access-Control-*` headers, and ask before sending requests that aren't simple
+//! `GET`s with a preflight `OPTIONS` request. Both are answered according to the policy in
+//! `Config::cors` of the requested route. Requests from origins the policy doesn't allow are
+//! handled as usual but without these headers, so browsers refuse to show the responses.
+
+use super::prelude::*;
+
+use crate::config::{CorsConfig, CorsPolicy};
+
+use conduit::Method;
+use std::collections::HashMap;
+use std::io;
+
+/// How long browsers may cache the answer to a preflight request
+const MAX_AGE_SECONDS: u32 = 60 * 60;
+
+/// The request headers clients may send besides the ones browsers always allow
+const ALLOWED_HEADERS: &str = "Authorization, Content-Type";
+
+/// The response headers clients may read besides the ones browsers always expose
+const EXPOSED_HEADERS: &str = "Retry-After, X-RateLimit-Limit, X-RateLimit-Remaining, \
+                               X-RateLimit-Reset, X-Request-Id";
+
+// Can't derive debug because of Handler.
+#[allow(missing_debug_implementations)]
+pub struct Cors {
+    config: CorsConfig,
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl Cors {
+    pub fn new(config: CorsConfig) -> Self {
+        Self {
+            config,
+            handler: None,
+        }
+    }
+}
+
+impl AroundMiddleware for Cors {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for Cors {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let handler = self.handler.as_ref().unwrap();
+        let origin = match header(req, "Origin") {
+            Some(origin) => origin,
+            None => return handler.call(req),
+        };
+        let policy = self.config.policy_for(req.path());
+        if !policy.allows_origin(&origin) {
+            return handler.call(req);
+        }
+
+        if req.method() == Method::Options {
+            if let Some(method) = header(req, "Access-Control-Request-Method") {
+                if !policy.allows_method(&method) {
+                    return handler.call(req);
+                }
+                let mut headers = HashMap::new();
+                allow_origin(&mut headers, policy, origin);
+                headers.insert(
+                    "Access-Control-Allow-Methods".into(),
+                    vec![policy.allowed_methods.join(", ")],
+                );
+                headers.insert(
+                    "Access-Control-Allow-Headers".into(),
+                    vec![ALLOWED_HEADERS.into()],
+                );
+                headers.insert(
+                    "Access-Control-Max-Age".into(),
+                    vec![MAX_AGE_SECONDS.to_string()],
+                );
+                return Ok(Response {
+                    status: (204, "No Content"),
+                    headers,
+                    body: Box::new(io::empty()),
+                });
+            }
+        }
+
+        let allowed = policy.allows_method(&req.method().to_string());
+        let mut res = handler.call(req);
+        if let Ok(ref mut response) = res {
+            if allowed {
+                allow_origin(&mut response.headers, policy, origin);
+                response.headers.insert(
+                    "Access-Control-Expose-Headers".into(),
+                    vec![EXPOSED_HEADERS.into()],
+                );
+            }
+        }
+        res
+    }
+}
+
+fn header(req: &dyn Request, name: &str) -> Option<String> {
+    req.headers()
+        .find(name)
+        .and_then(|values| values.first().map(|value| value.to_string()))
+}
+
+fn allow_origin(headers: &mut HashMap<String, Vec<String>>, policy: &CorsPolicy, origin: String) {
+    // Responses for `*` policies are the same for every origin, credentialed responses have to
+    // name the origin
+    let origin = if policy.allows_any_origin() && !policy.allow_credentials {
+        "*".into()
+    } else {
+        origin
+    };
+    headers.insert("Access-Control-Allow-Origin".into(), vec![origin]);
+    headers
+        .entry("Vary".into())
+        .or_insert_with(Vec::new)
+        .push("Origin".into());
+    if policy.allow_credentials {
+        headers.insert(
+            "Access-Control-Allow-Credentials".into(),
+            vec!["true".into()],
+        );
+    }
+}
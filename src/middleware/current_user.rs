@@ -4,9 +4,12 @@ use conduit_cookie::RequestSession;
 use diesel::prelude::*;
 
 use crate::db::RequestTransaction;
-use crate::util::errors::{std_error, CargoResult, ChainError, Unauthorized};
+use crate::middleware::app::RequestApp;
+use crate::util::cidr;
+use crate::util::errors::{std_error, CargoResult, ChainError, ErrorCode, Unauthorized};
+use crate::util::request_header;
 
-use crate::models::User;
+use crate::models::{Session, User};
 use crate::schema::users;
 
 #[derive(Debug, Clone, Copy)]
@@ -18,44 +21,85 @@ pub enum AuthenticationSource {
     ApiToken { auth_header: String },
 }
 
+/// The IP address an API token was used from, if the token can't be used from
+/// there. The request isn't authenticated.
+#[derive(Debug, Clone)]
+struct IpNotAllowed(String);
+
 impl Middleware for CurrentUser {
     fn before(&self, req: &mut dyn Request) -> Result<(), Box<dyn Error + Send>> {
-        // Check if the request has a session cookie with a `user_id` property inside
-        let id = {
-            req.session()
-                .get("user_id")
-                .and_then(|s| s.parse::<i32>().ok())
+        // Check if the request has a session cookie with a `session_token` property inside
+        let (token, legacy_user_id) = {
+            let session = req.session();
+            (
+                session.get("session_token").cloned(),
+                session.get("user_id").and_then(|s| s.parse::<i32>().ok()),
+            )
+        };
+        let token = match (token, legacy_user_id) {
+            // Cookies from before sessions were stored in the database hold
+            // the id of the user instead. They're moved to a stored session,
+            // so that they can be listed and revoked.
+            (None, Some(user_id)) => start_session(req, user_id)
+                .ok()
+                .map(|session| session.token),
+            (token, _) => token,
         };
 
         let conn = req.db_conn().map_err(std_error)?;
 
-        if let Some(id) = id {
-            // If it did, look for a user in the database with the given `user_id`
-            let maybe_user = users::table.find(id).first::<User>(&*conn);
+        if let Some(token) = token {
+            // If it did, look for an unrevoked session with the given token,
+            // and the user in the database it belongs to
+            let maybe_session = Session::find_by_token(&conn, &token).and_then(|session| {
+                let user = users::table
+                    .find(session.user_id)
+                    .filter(users::is_banned.eq(false))
+                    .first::<User>(&*conn)?;
+                Ok((session, user))
+            });
             drop(conn);
-            if let Ok(user) = maybe_user {
-                // Attach the `User` model from the database to the request
+            if let Ok((session, user)) = maybe_session {
+                // Attach the `User` and `Session` models from the database to the request
                 req.mut_extensions().insert(user);
+                req.mut_extensions().insert(session);
                 req.mut_extensions()
                     .insert(AuthenticationSource::SessionCookie);
             }
         } else {
             // Otherwise, look for an `Authorization` header on the request
             // and try to find a user in the database with a matching API token
+            let mut ip_not_allowed = None;
             let user_auth = if let Some(headers) = req.headers().find("Authorization") {
                 let auth_header = headers[0].to_string();
+                let token = api_token_from_header(&auth_header);
 
-                User::find_by_api_token(&conn, &auth_header)
-                    .map(|user| (AuthenticationSource::ApiToken { auth_header }, user))
+                let ip = client_ip(req);
+                let user = User::find_by_api_token(&conn, token, Some(&ip[..]), user_agent(req))
                     .optional()
-                    .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?
+                    .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+                match user {
+                    Some(Some(user)) => {
+                        Some((AuthenticationSource::ApiToken { auth_header }, user))
+                    }
+                    Some(None) => {
+                        ip_not_allowed = Some(IpNotAllowed(ip));
+                        None
+                    }
+                    None => None,
+                }
             } else {
                 None
             };
 
             drop(conn);
 
-            if let Some((api_token, user)) = user_auth {
+            if let Some(ip_not_allowed) = ip_not_allowed {
+                req.mut_extensions().insert(ip_not_allowed);
+            }
+
+            // Banned users are treated as if they weren't authenticated at all
+            if let Some((api_token, user)) = user_auth.filter(|(_, user)| !user.is_banned) {
                 // Attach the `User` model from the database and the API token to the request
                 req.mut_extensions().insert(user);
                 req.mut_extensions().insert(api_token);
@@ -66,6 +110,51 @@ impl Middleware for CurrentUser {
     }
 }
 
+/// Starts a stored session for a user, and sets the session cookie to its
+/// token.
+pub fn start_session(req: &mut dyn Request, user_id: i32) -> CargoResult<Session> {
+    let session = {
+        let conn = req.db_conn()?;
+        let ip = client_ip(req);
+        Session::create(&conn, user_id, Some(&ip[..]), user_agent(req))?
+    };
+    req.session().remove(&"user_id".to_string());
+    req.session()
+        .insert("session_token".to_string(), session.token.clone());
+    Ok(session)
+}
+
+/// The IP address of the client. The `X-Real-Ip` header is only used if the
+/// request came from one of the `trusted_proxies`, since anyone else could set
+/// it to any address.
+pub fn client_ip(req: &dyn Request) -> String {
+    let remote_ip = req.remote_addr().ip().to_string();
+    let trusted_proxies = &req.app().config.trusted_proxies;
+    if trusted_proxies.is_empty() || !cidr::is_allowed(trusted_proxies, &remote_ip) {
+        return remote_ip;
+    }
+    match request_header(req, "X-Real-Ip") {
+        "" => remote_ip,
+        ip => ip.to_string(),
+    }
+}
+
+fn user_agent(req: &dyn Request) -> Option<&str> {
+    Some(request_header(req, "User-Agent")).filter(|ua| !ua.is_empty())
+}
+
+/// Extracts the API token from the value of an `Authorization` header.
+///
+/// `cargo` sends the raw token as the header value, but clients of alternative
+/// registries commonly use the `Bearer` scheme, so both forms are accepted.
+pub fn api_token_from_header(value: &str) -> &str {
+    let value = value.trim();
+    match value.find(' ') {
+        Some(idx) if value[..idx].eq_ignore_ascii_case("bearer") => value[idx..].trim_start(),
+        _ => value,
+    }
+}
+
 pub trait RequestUser {
     fn user(&self) -> CargoResult<&User>;
     fn authentication_source(&self) -> CargoResult<AuthenticationSource>;
@@ -73,6 +162,10 @@ pub trait RequestUser {
 
 impl<'a> RequestUser for dyn Request + 'a {
     fn user(&self) -> CargoResult<&User> {
+        if let Some(IpNotAllowed(ip)) = self.extensions().find::<IpNotAllowed>() {
+            return Err(ErrorCode::Unauthorized
+                .human(&format_args!("this API token can't be used from {}", ip)));
+        }
         self.extensions()
             .find::<User>()
             .chain_error(|| Unauthorized)
@@ -85,3 +178,21 @@ impl<'a> RequestUser for dyn Request + 'a {
             .chain_error(|| Unauthorized)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::api_token_from_header;
+
+    #[test]
+    fn raw_tokens_are_used_as_is() {
+        assert_eq!(api_token_from_header("abc123"), "abc123");
+        assert_eq!(api_token_from_header(" abc123 "), "abc123");
+    }
+
+    #[test]
+    fn bearer_scheme_is_stripped() {
+        assert_eq!(api_token_from_header("Bearer abc123"), "abc123");
+        assert_eq!(api_token_from_header("bearer  abc123"), "abc123");
+        assert_eq!(api_token_from_header("Basic abc123"), "Basic abc123");
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::prelude::*;
 use crate::app::App;
+use crate::logging;
 
 use conduit::Request;
 use std::sync::{
@@ -36,10 +37,12 @@ impl Middleware for LogConnectionPoolStatus {
         let in_flight_requests = self.in_flight_requests.fetch_add(1, Ordering::SeqCst);
         if last_log_time.elapsed() >= Duration::from_secs(60) {
             *last_log_time = Instant::now();
-            println!(
-                "connection_pool_status=\"{:?}\" in_flight_requests={}",
-                self.app.diesel_database.state(),
-                in_flight_requests
+            logging::info(
+                "connection pool status",
+                json!({
+                    "connection_pool_status": format!("{:?}", self.app.diesel_database.state()),
+                    "in_flight_requests": in_flight_requests,
+                }),
             );
         }
         Ok(())
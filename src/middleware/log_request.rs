@@ -1,7 +1,8 @@
-//! Log all requests in a format similar to Heroku's router, but with additional
-//! information that we care about like User-Agent
+//! Log all requests with information similar to Heroku's router logs, plus
+//! additional information that we care about like User-Agent
 
 use super::prelude::*;
+use crate::logging;
 use crate::util::request_header;
 use conduit::Request;
 use std::fmt;
@@ -23,44 +24,37 @@ impl Handler for LogRequests {
     fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
         let request_start = Instant::now();
         let res = self.handler.as_ref().unwrap().call(req);
-        let (level, response_code) = match res {
-            Ok(ref r) => ("info", r.status.0),
-            Err(_) => ("error", 500),
+        let response_code = match res {
+            Ok(ref r) => r.status.0,
+            Err(_) => 500,
         };
         let response_time = request_start.elapsed();
         let response_time =
             response_time.as_secs() * 1000 + u64::from(response_time.subsec_nanos()) / 1_000_000;
 
-        let metadata_length = req
-            .extensions()
-            .find::<u64>()
-            .map_or(String::new(), |l| format!(" metadata_length={}", l));
-
-        print!(
-            "at={level} method={method} path=\"{path}\" \
-             request_id={request_id} fwd=\"{ip}\" service={time_ms}ms \
-             status={status} user_agent=\"{user_agent}\"\
-             {metadata_length}",
-            level = level,
-            method = req.method(),
-            path = FullPath(req),
-            ip = request_header(req, "X-Real-Ip"),
-            time_ms = response_time,
-            user_agent = request_header(req, "User-Agent"),
-            request_id = request_header(req, "X-Request-Id"),
-            status = response_code,
-            metadata_length = metadata_length,
-        );
-
+        let mut fields = json!({
+            "method": req.method().to_string(),
+            "path": FullPath(req).to_string(),
+            "fwd": request_header(req, "X-Real-Ip"),
+            "service_ms": response_time,
+            "status": response_code,
+            "user_agent": request_header(req, "User-Agent"),
+        });
+        if let Some(metadata_length) = req.extensions().find::<u64>() {
+            fields["metadata_length"] = json!(metadata_length);
+        }
         if let Err(ref e) = res {
-            print!(" error=\"{}\"", e.description());
+            fields["error"] = json!(e.description());
         }
-
         if response_time > 1000 {
-            print!(" SLOW REQUEST");
+            fields["slow"] = json!(true);
         }
 
-        println!();
+        if res.is_ok() {
+            logging::info("request", fields);
+        } else {
+            logging::error("request", fields);
+        }
 
         res
     }
@@ -0,0 +1,86 @@
+//! Middleware that limits how often clients can make requests to the API
+//!
+//! See the `rate_limit` module for how the limits work, and
+//! `LimitedAction::for_request` for which requests are limited.
+
+use super::prelude::*;
+
+use crate::db::RequestTransaction;
+use crate::middleware::current_user::{api_token_from_header, client_ip, AuthenticationSource};
+use crate::models::User;
+use crate::rate_limit::{BucketOwner, LimitedAction, RateLimiter};
+use crate::util::errors::CargoError;
+
+// Can't derive debug because of Handler.
+#[allow(missing_debug_implementations)]
+pub struct RateLimit {
+    limiter: RateLimiter,
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self {
+            limiter,
+            handler: None,
+        }
+    }
+}
+
+impl AroundMiddleware for RateLimit {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for RateLimit {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let action = if req.path().starts_with("/api/v1/") {
+            LimitedAction::for_request(&req.method(), &req.path()["/api/v1".len()..])
+        } else {
+            None
+        };
+        let handler = self.handler.as_ref().unwrap();
+        let action = match action {
+            Some(action) => action,
+            None => return handler.call(req),
+        };
+
+        // Requests aren't limited if the bucket can't be updated, for example
+        // when the database is in read only mode
+        let status = req.db_conn().ok().and_then(|conn| {
+            self.limiter
+                .take_token(action, &bucket_owner(req), &conn)
+                .ok()
+                .and_then(|status| status)
+        });
+        let status = match status {
+            Some(status) => status,
+            None => return handler.call(req),
+        };
+
+        let mut response = if status.is_exceeded() {
+            status.error().response().unwrap()
+        } else {
+            handler.call(req)?
+        };
+        for (name, value) in status.headers() {
+            response.headers.insert(name, vec![value]);
+        }
+        Ok(response)
+    }
+}
+
+fn bucket_owner(req: &dyn Request) -> BucketOwner {
+    let extensions = req.extensions();
+    match (
+        extensions.find::<User>(),
+        extensions.find::<AuthenticationSource>(),
+    ) {
+        (Some(_), Some(AuthenticationSource::ApiToken { auth_header })) => {
+            BucketOwner::token(api_token_from_header(auth_header))
+        }
+        (Some(user), _) => BucketOwner::User(user.id),
+        (None, _) => BucketOwner::Ip(client_ip(req)),
+    }
+}
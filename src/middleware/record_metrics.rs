@@ -0,0 +1,53 @@
+//! Count requests and their duration per route in `App::metrics`
+
+use super::prelude::*;
+use crate::app::App;
+use crate::router::RoutePattern;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+#[allow(missing_debug_implementations)] // We can't
+pub struct RecordMetrics {
+    app: Arc<App>,
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl RecordMetrics {
+    pub fn new(app: &Arc<App>) -> Self {
+        Self {
+            app: app.clone(),
+            handler: None,
+        }
+    }
+}
+
+impl AroundMiddleware for RecordMetrics {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for RecordMetrics {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let request_start = Instant::now();
+        let res = self.handler.as_ref().unwrap().call(req);
+        let status = match res {
+            Ok(ref r) => r.status.0 as u16,
+            Err(_) => 500,
+        };
+        // Requests that no route handled, e.g. for static files
+        let route = req
+            .extensions()
+            .find::<RoutePattern>()
+            .map_or("unmatched", |pattern| pattern.0);
+
+        self.app.metrics.record_request(
+            &req.method().to_string(),
+            route,
+            status,
+            request_start.elapsed(),
+        );
+        res
+    }
+}
@@ -0,0 +1,50 @@
+//! Assigns every request an ID, which is logged with everything done for the
+//! request and returned in the `X-Request-Id` header so that users can refer
+//! to it when contacting support.
+
+use super::prelude::*;
+use crate::logging;
+use crate::util::request_header;
+
+/// The ID of a request. It is taken from the `X-Request-Id` header set by
+/// Heroku's router if that is valid, and generated otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+#[allow(missing_debug_implementations)] // We can't
+#[derive(Default)]
+pub struct RequestIds {
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl AroundMiddleware for RequestIds {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for RequestIds {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let header = request_header(req, "X-Request-Id");
+        let id = if logging::is_valid_trace_id(header) {
+            header.to_string()
+        } else {
+            logging::new_trace_id()
+        };
+        req.mut_extensions().insert(RequestId(id.clone()));
+
+        let handler = self.handler.as_ref().unwrap();
+        let mut res = logging::with_trace_id(&id, || handler.call(req));
+        if let Ok(ref mut res) = res {
+            res.headers.insert("X-Request-Id".to_string(), vec![id]);
+        }
+        res
+    }
+}
+
+/// Returns the ID of the request, see `RequestId`.
+pub fn request_id(req: &dyn Request) -> &str {
+    req.extensions()
+        .find::<RequestId>()
+        .map_or("", |id| id.0.as_str())
+}
@@ -2,17 +2,27 @@
 
 use super::prelude::*;
 
+use super::request_id;
 use crate::util::request_header;
 use std::collections::HashMap;
 use std::io::Cursor;
 
 // Can't derive debug because of Handler.
 #[allow(missing_debug_implementations)]
-#[derive(Default)]
 pub struct RequireUserAgent {
+    support_email: String,
     handler: Option<Box<dyn Handler>>,
 }
 
+impl RequireUserAgent {
+    pub fn new(support_email: String) -> Self {
+        Self {
+            support_email,
+            handler: None,
+        }
+    }
+}
+
 impl AroundMiddleware for RequireUserAgent {
     fn with_handler(&mut self, handler: Box<dyn Handler>) {
         self.handler = Some(handler);
@@ -23,10 +33,13 @@ impl Handler for RequireUserAgent {
     fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
         let has_user_agent = request_header(req, "User-Agent") != "";
         let is_download = req.path().ends_with("download");
-        if !has_user_agent && !is_download {
+        // Probes of load balancers don't necessarily send one
+        let is_health_check = req.path() == "/healthz" || req.path() == "/readyz";
+        if !has_user_agent && !is_download && !is_health_check {
             let body = format!(
                 include_str!("no_user_agent_message.txt"),
-                request_header(req, "X-Request-Id"),
+                support_email = self.support_email,
+                request_id = request_id(req),
             );
             let mut headers = HashMap::new();
             headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
@@ -19,20 +19,8 @@ impl SecurityHeaders {
 
         headers.insert("X-XSS-Protection".into(), vec!["1; mode=block".into()]);
 
-        let s3_host = match *uploader {
-            Uploader::S3 {
-                ref bucket,
-                ref cdn,
-                ..
-            } => match *cdn {
-                Some(ref s) => s.clone(),
-                None => bucket.host(),
-            },
-            _ => unreachable!(
-                "This middleware should only be used in the production environment, \
-                 which should also require an S3 uploader, QED"
-            ),
-        };
+        // Files stored locally are served by the server itself
+        let storage_url = uploader.public_url().unwrap_or_default();
 
         // It would be better if we didn't have to have 'unsafe-eval' in the `script-src`
         // policy, but google charts (used for the download graph on crate pages) uses `eval`
@@ -43,12 +31,12 @@ impl SecurityHeaders {
             "Content-Security-Policy".into(),
             vec![format!(
                 "default-src 'self'; \
-                 connect-src 'self' https://docs.rs https://{}; \
+                 connect-src 'self' https://docs.rs {}; \
                  script-src 'self' 'unsafe-eval' https://www.google.com; \
                  style-src 'self' https://www.google.com https://ajax.googleapis.com; \
                  img-src *; \
                  object-src 'none'",
-                s3_host
+                storage_url
             )],
         );
 
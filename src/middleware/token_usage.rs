@@ -0,0 +1,61 @@
+//! Middleware that counts the requests made with each API token per day
+//!
+//! The counts are listed by `GET /me/tokens/:id/usage`. Requests made with tokens that have a
+//! daily quota are refused once the token made more requests today than the quota allows.
+
+use super::prelude::*;
+
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::db::RequestTransaction;
+use crate::middleware::current_user::{api_token_from_header, AuthenticationSource};
+use crate::models::{ApiToken, ApiTokenUsage};
+use crate::schema::api_tokens;
+use crate::util::errors::{CargoError, QuotaExceeded};
+
+// Can't derive debug because of Handler.
+#[allow(missing_debug_implementations)]
+#[derive(Default)]
+pub struct TokenUsage {
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl AroundMiddleware for TokenUsage {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for TokenUsage {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let handler = self.handler.as_ref().unwrap();
+        let auth_header = match req.extensions().find::<AuthenticationSource>() {
+            Some(AuthenticationSource::ApiToken { auth_header }) => auth_header.clone(),
+            _ => return handler.call(req),
+        };
+
+        // Requests aren't counted if the usage can't be updated, for example
+        // when the database is in read only mode
+        let usage = req.db_conn().ok().and_then(|conn| {
+            let token = api_tokens::table
+                .filter(api_tokens::token.eq(api_token_from_header(&auth_header)))
+                .filter(api_tokens::revoked.eq(false))
+                .first::<ApiToken>(&*conn)
+                .ok()?;
+            let requests = ApiTokenUsage::record_request(&conn, token.id).ok()?;
+            Some((token.daily_quota, requests))
+        });
+
+        match usage {
+            Some((Some(daily_quota), requests)) if requests > daily_quota => {
+                let error = QuotaExceeded {
+                    daily_quota,
+                    retry_after: Utc::today().succ().and_hms(0, 0, 0).naive_utc(),
+                };
+                Ok(error.response().unwrap())
+            }
+            _ => handler.call(req),
+        }
+    }
+}
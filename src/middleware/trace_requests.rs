@@ -0,0 +1,58 @@
+//! Handle every request in a span, continuing the trace of the caller if it
+//! sent a `traceparent` header
+
+use super::prelude::*;
+use super::RequestId;
+use crate::router::RoutePattern;
+use crate::telemetry::{self, Span, SpanContext, SpanKind};
+use crate::util::request_header;
+
+#[allow(missing_debug_implementations)] // We can't
+#[derive(Default)]
+pub struct TraceRequests {
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl AroundMiddleware for TraceRequests {
+    fn with_handler(&mut self, handler: Box<dyn Handler>) {
+        self.handler = Some(handler);
+    }
+}
+
+impl Handler for TraceRequests {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let method = req.method().to_string();
+        let parent = SpanContext::from_traceparent(request_header(req, "traceparent"));
+        let trace_id = req
+            .extensions()
+            .find::<RequestId>()
+            .and_then(|id| telemetry::trace_id_from_request_id(&id.0));
+        let mut span = match (parent, trace_id) {
+            (Some(parent), _) => Span::with_parent(method.clone(), SpanKind::Server, &parent),
+            (None, Some(trace_id)) => Span::in_trace(method.clone(), SpanKind::Server, trace_id),
+            (None, None) => Span::start(method.clone(), SpanKind::Server),
+        };
+        span.set_attribute("http.method", method.clone());
+        span.set_attribute("http.target", req.path());
+
+        let res = self.handler.as_ref().unwrap().call(req);
+
+        if let Some(route) = req.extensions().find::<RoutePattern>() {
+            span.set_name(format!("{} {}", method, route.0));
+            span.set_attribute("http.route", route.0);
+        }
+        match res {
+            Ok(ref res) => {
+                span.set_attribute("http.status_code", res.status.0);
+                if res.status.0 >= 500 {
+                    span.set_error(&res.status.1);
+                }
+            }
+            Err(ref e) => {
+                span.set_attribute("http.status_code", 500);
+                span.set_error(e);
+            }
+        }
+        res
+    }
+}
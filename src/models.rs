@@ -1,35 +1,89 @@
-pub use self::action::{VersionAction, VersionOwnerAction};
+pub use self::action::{
+    AdminAction, CrateAction, NewAdminAction, NewCrateOwnerAction, VersionAction,
+    VersionOwnerAction,
+};
+pub use self::background_job::{BackgroundJob, DeadLetter, JobPolicy};
 pub use self::badge::{Badge, CrateBadge, MaintenanceStatus};
+pub use self::build_verification::{BuildVerification, BuildVerifier, NewBuildVerification};
 pub use self::category::{Category, CrateCategory, NewCategory};
+pub use self::crate_acl::CrateAcl;
+pub use self::crate_funding::CrateFunding;
+pub use self::crate_list::{CrateList, NewCrateList};
 pub use self::crate_owner_invitation::{CrateOwnerInvitation, NewCrateOwnerInvitation};
+pub use self::crate_rename::CrateRename;
+pub use self::crate_report::{CrateReport, NewCrateReport, ReportCategory};
+pub use self::crate_successor::CrateSuccessor;
+pub use self::deleted_crate::DeletedCrate;
+pub use self::deleted_version::DeletedVersion;
 pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
-pub use self::download::VersionDownload;
+pub use self::download::{
+    CrateDownloadRollup, DownloadAnomaly, DownloadClient, VersionAdoption, VersionDownload,
+};
 pub use self::email::{Email, NewEmail};
-pub use self::follow::Follow;
-pub use self::keyword::{CrateKeyword, Keyword};
-pub use self::krate::{Crate, CrateVersions, NewCrate, RecentCrateDownloads};
+pub use self::follow::{Follow, FollowTarget, TopicFollow};
+pub use self::identity::Identity;
+pub use self::keyword::{CrateKeyword, Keyword, KeywordAlias};
+pub use self::krate::{
+    Crate, CrateLinks, CrateMetadata, CrateVersions, NewCrate, RecentCrateDownloads, Visibility,
+};
+pub use self::name_claim::{ClaimStatus, NameClaim, NewNameClaim};
+pub use self::namespace::Namespace;
 pub use self::owner::{CrateOwner, Owner, OwnerKind};
+pub use self::pending_crate::PendingCrate;
+pub use self::pending_owner_removal::PendingOwnerRemoval;
+pub use self::private_index_entry::PrivateIndexEntry;
+pub use self::publish_session::{NewPublishSession, PublishSession};
+pub use self::reserved_name::{ReservedNamePattern, ReservedNamePatternKind};
 pub use self::rights::Rights;
+pub use self::session::Session;
 pub use self::team::{NewTeam, Team};
-pub use self::token::ApiToken;
+pub use self::team_membership::TeamMembership;
+pub use self::token::{ApiToken, ApiTokenUsage};
 pub use self::user::{NewUser, User};
 pub use self::version::{NewVersion, Version};
+pub use self::version_checksum::VersionChecksum;
+pub use self::version_file::VersionFile;
+pub use self::version_provenance::VersionProvenance;
 
 pub mod helpers;
 
 mod action;
+mod background_job;
 mod badge;
+mod build_verification;
 pub mod category;
+mod crate_acl;
+pub mod crate_funding;
+mod crate_list;
 mod crate_owner_invitation;
+mod crate_rename;
+mod crate_report;
+pub mod crate_successor;
+pub mod deleted_crate;
+pub mod deleted_version;
 pub mod dependency;
+pub mod dependency_graph;
 mod download;
 mod email;
 mod follow;
+mod identity;
 mod keyword;
 pub mod krate;
+pub mod name_claim;
+mod namespace;
 mod owner;
+mod pending_crate;
+pub mod pending_owner_removal;
+mod private_index_entry;
+mod publish_session;
+pub mod reserved_name;
 mod rights;
-mod team;
+mod session;
+pub mod team;
+mod team_membership;
 mod token;
 mod user;
 mod version;
+mod version_checksum;
+mod version_file;
+mod version_provenance;
@@ -1,4 +1,10 @@
 use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
 
 use crate::models::{ApiToken, User, Version};
 use crate::schema::*;
@@ -24,3 +30,150 @@ pub struct VersionOwnerAction {
     pub action: VersionAction,
     pub time: NaiveDateTime,
 }
+
+/// Changes owners make to their crates outside of publishing. Every action is
+/// recorded in the `crate_owner_actions` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum CrateAction {
+    UpdateMetadata = 0,
+    // if you add an action here, be sure to update `from_sql` below.
+}
+
+#[derive(Debug, Clone, Copy, Insertable)]
+#[table_name = "crate_owner_actions"]
+pub struct NewCrateOwnerAction<'a> {
+    pub crate_id: i32,
+    pub owner_id: i32,
+    pub action: CrateAction,
+    /// Anything else needed to understand the action, e.g. the changed fields
+    pub detail: Option<&'a str>,
+}
+
+impl<'a> NewCrateOwnerAction<'a> {
+    pub fn record(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::insert_into(crate_owner_actions::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+impl FromSql<Integer, Pg> for CrateAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(CrateAction::UpdateMetadata),
+            n => Err(format!("unknown crate action: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for CrateAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+/// Moderation actions taken by admins. Every action is recorded in the
+/// `admin_actions` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum AdminAction {
+    LockCrate = 0,
+    UnlockCrate = 1,
+    DeleteCrate = 2,
+    BanUser = 3,
+    UnbanUser = 4,
+    ReviewFinding = 5,
+    ResolveReport = 6,
+    ApproveName = 7,
+    ReserveName = 8,
+    UnreserveName = 9,
+    AddNamePattern = 10,
+    RemoveNamePattern = 11,
+    ApproveClaim = 12,
+    RejectClaim = 13,
+    SetRateLimit = 14,
+    RemoveRateLimit = 15,
+    CreateCategory = 16,
+    UpdateCategory = 17,
+    MergeCategory = 18,
+    AddKeywordAlias = 19,
+    RemoveKeywordAlias = 20,
+    RetryJob = 21,
+    DiscardJob = 22,
+    SetMaxUploadSize = 23,
+    RemoveMaxUploadSize = 24,
+    ReleaseDeletedName = 25,
+    ReviewDownloadAnomaly = 26,
+    ApproveCrate = 27,
+    RejectCrate = 28,
+    RenameCrate = 29,
+    // if you add an action here, be sure to update `from_sql` below.
+}
+
+#[derive(Debug, Clone, Copy, Insertable)]
+#[table_name = "admin_actions"]
+pub struct NewAdminAction<'a> {
+    pub admin_id: i32,
+    pub action: AdminAction,
+    pub crate_name: Option<&'a str>,
+    pub user_id: Option<i32>,
+    /// Anything else needed to understand the action, e.g. a name pattern
+    pub detail: Option<&'a str>,
+}
+
+impl<'a> NewAdminAction<'a> {
+    pub fn record(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::insert_into(admin_actions::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+impl FromSql<Integer, Pg> for AdminAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(AdminAction::LockCrate),
+            1 => Ok(AdminAction::UnlockCrate),
+            2 => Ok(AdminAction::DeleteCrate),
+            3 => Ok(AdminAction::BanUser),
+            4 => Ok(AdminAction::UnbanUser),
+            5 => Ok(AdminAction::ReviewFinding),
+            6 => Ok(AdminAction::ResolveReport),
+            7 => Ok(AdminAction::ApproveName),
+            8 => Ok(AdminAction::ReserveName),
+            9 => Ok(AdminAction::UnreserveName),
+            10 => Ok(AdminAction::AddNamePattern),
+            11 => Ok(AdminAction::RemoveNamePattern),
+            12 => Ok(AdminAction::ApproveClaim),
+            13 => Ok(AdminAction::RejectClaim),
+            14 => Ok(AdminAction::SetRateLimit),
+            15 => Ok(AdminAction::RemoveRateLimit),
+            16 => Ok(AdminAction::CreateCategory),
+            17 => Ok(AdminAction::UpdateCategory),
+            18 => Ok(AdminAction::MergeCategory),
+            19 => Ok(AdminAction::AddKeywordAlias),
+            20 => Ok(AdminAction::RemoveKeywordAlias),
+            21 => Ok(AdminAction::RetryJob),
+            22 => Ok(AdminAction::DiscardJob),
+            23 => Ok(AdminAction::SetMaxUploadSize),
+            24 => Ok(AdminAction::RemoveMaxUploadSize),
+            25 => Ok(AdminAction::ReleaseDeletedName),
+            26 => Ok(AdminAction::ReviewDownloadAnomaly),
+            27 => Ok(AdminAction::ApproveCrate),
+            28 => Ok(AdminAction::RejectCrate),
+            29 => Ok(AdminAction::RenameCrate),
+            n => Err(format!("unknown admin action: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for AdminAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
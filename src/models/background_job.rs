@@ -0,0 +1,140 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use std::cmp;
+
+use crate::schema::{background_job_dead_letters, background_job_policies, background_jobs};
+use crate::views::{EncodableBackgroundJob, EncodableDeadLetter, EncodableJobPolicy};
+
+/// A job in the queue of the background worker. Jobs stay in the queue until
+/// they succeed or their policy moves them to the dead letters.
+#[derive(Clone, Identifiable, Queryable, Debug)]
+pub struct BackgroundJob {
+    pub id: i64,
+    pub job_type: String,
+    pub data: serde_json::Value,
+    /// The number of failed attempts
+    pub retries: i32,
+    pub last_retry: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    /// The ID of the request that enqueued the job
+    pub trace_id: Option<String>,
+}
+
+/// A job that failed more often than its policy allows.
+#[derive(Clone, Identifiable, Queryable, Debug)]
+#[table_name = "background_job_dead_letters"]
+pub struct DeadLetter {
+    pub id: i64,
+    /// The id the job had in the queue
+    pub job_id: i64,
+    pub job_type: String,
+    pub data: serde_json::Value,
+    pub retries: i32,
+    pub created_at: NaiveDateTime,
+    pub dead_at: NaiveDateTime,
+    pub trace_id: Option<String>,
+}
+
+/// How the jobs of a type are prioritized and retried, applied by the
+/// triggers on the `background_jobs` table.
+#[derive(Clone, Queryable, Debug)]
+pub struct JobPolicy {
+    pub job_type: String,
+    /// Jobs with a higher priority run before older jobs with a lower one
+    pub priority: i32,
+    /// `None` for jobs that are retried until they succeed
+    pub max_attempts: Option<i32>,
+    /// The delay before the first retry, doubled after every further failure
+    pub backoff_seconds: i32,
+}
+
+impl BackgroundJob {
+    /// Returns the jobs that have failed at least once, oldest first.
+    pub fn failed(conn: &PgConnection) -> QueryResult<Vec<BackgroundJob>> {
+        background_jobs::table
+            .filter(background_jobs::retries.gt(0))
+            .order(background_jobs::created_at)
+            .load(conn)
+    }
+
+    /// Returns when the worker runs the job again. swirl waits
+    /// `1 minute * 2 ^ retries` after `last_retry`, which the triggers move
+    /// to apply the backoff of the job's policy.
+    pub fn next_attempt_at(&self) -> NaiveDateTime {
+        let exponent = cmp::min(self.retries, 30) as u32;
+        self.last_retry + Duration::minutes(2i64.pow(exponent))
+    }
+
+    /// Makes a failed job run again as soon as a worker is free, without
+    /// waiting for its backoff. Returns `false` if there is no such job.
+    pub fn retry_now(conn: &PgConnection, id: i64) -> QueryResult<bool> {
+        let updated = diesel::update(
+            background_jobs::table
+                .find(id)
+                .filter(background_jobs::retries.gt(0)),
+        )
+        .set(background_jobs::last_retry.eq(NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0)))
+        .execute(conn)?;
+        Ok(updated > 0)
+    }
+
+    pub fn encodable(self) -> EncodableBackgroundJob {
+        EncodableBackgroundJob {
+            next_attempt_at: self.next_attempt_at(),
+            id: self.id,
+            job_type: self.job_type,
+            data: self.data,
+            retries: self.retries,
+            created_at: self.created_at,
+            trace_id: self.trace_id,
+        }
+    }
+}
+
+impl DeadLetter {
+    /// Enqueues the job again with no failed attempts and its original trace
+    /// ID, and removes its dead letter.
+    pub fn requeue(&self, conn: &PgConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(background_jobs::table)
+                .values((
+                    background_jobs::job_type.eq(&self.job_type),
+                    background_jobs::data.eq(&self.data),
+                    background_jobs::trace_id.eq(&self.trace_id),
+                ))
+                .execute(conn)?;
+            diesel::delete(self).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn encodable(self) -> EncodableDeadLetter {
+        EncodableDeadLetter {
+            id: self.id,
+            job_id: self.job_id,
+            job_type: self.job_type,
+            data: self.data,
+            retries: self.retries,
+            created_at: self.created_at,
+            dead_at: self.dead_at,
+            trace_id: self.trace_id,
+        }
+    }
+}
+
+impl JobPolicy {
+    pub fn all(conn: &PgConnection) -> QueryResult<Vec<JobPolicy>> {
+        background_job_policies::table
+            .order(background_job_policies::job_type)
+            .load(conn)
+    }
+
+    pub fn encodable(self) -> EncodableJobPolicy {
+        EncodableJobPolicy {
+            job_type: self.job_type,
+            priority: self.priority,
+            max_attempts: self.max_attempts,
+            backoff_seconds: self.backoff_seconds,
+        }
+    }
+}
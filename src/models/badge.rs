@@ -20,6 +20,8 @@ pub struct CrateBadge {
     pub badge: Badge,
 }
 
+/// Deprecated: badges only support a fixed list of services, crates should set
+/// their `CrateLinks` instead. Badges from manifests are still accepted.
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case", tag = "badge_type", content = "attributes")]
 pub enum Badge {
@@ -0,0 +1,186 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use crate::models::Version;
+use crate::schema::{build_verifications, build_verifiers, versions};
+use crate::views::EncodableBuildVerification;
+
+/// An external service that is allowed to submit build verification verdicts.
+///
+/// Verdicts are authenticated with an HMAC-SHA256 signature of the request,
+/// see `BuildVerifier::signed_message`, using the signing key derived from
+/// the secret the service was given when it was registered. The secret
+/// itself isn't stored.
+#[derive(Clone, Debug, Identifiable, Queryable)]
+pub struct BuildVerifier {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    /// The signing key, see `BuildVerifier::signing_key`
+    pub secret_hash: String,
+}
+
+/// The verdict of a `BuildVerifier` on whether rebuilding a version from its
+/// stated source produced a bit-identical tarball.
+#[derive(Clone, Debug, Identifiable, Queryable, Associations)]
+#[belongs_to(Version)]
+#[belongs_to(BuildVerifier, foreign_key = "verifier_id")]
+#[primary_key(version_id, verifier_id)]
+pub struct BuildVerification {
+    pub version_id: i32,
+    pub verifier_id: i32,
+    pub reproducible: bool,
+    pub checksum: String,
+    pub log_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "build_verifications"]
+#[changeset_options(treat_none_as_null = "true")]
+#[primary_key(version_id, verifier_id)]
+pub struct NewBuildVerification<'a> {
+    pub version_id: i32,
+    pub verifier_id: i32,
+    pub reproducible: bool,
+    pub checksum: &'a str,
+    pub log_url: Option<&'a str>,
+}
+
+impl BuildVerifier {
+    /// Registers a new verifier, and returns it along with its secret, which
+    /// can't be recovered later.
+    pub fn register(conn: &PgConnection, name: &str) -> QueryResult<(BuildVerifier, String)> {
+        let secret = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .collect::<String>();
+        let verifier = diesel::insert_into(build_verifiers::table)
+            .values((
+                build_verifiers::name.eq(name),
+                build_verifiers::secret_hash.eq(Self::signing_key(&secret)),
+            ))
+            .get_result(conn)?;
+        Ok((verifier, secret))
+    }
+
+    /// The key requests are signed with: the hex encoded SHA-256 of the
+    /// secret.
+    pub fn signing_key(secret: &str) -> String {
+        hex::encode(sha256(secret.as_bytes()))
+    }
+
+    pub fn find_by_name(conn: &PgConnection, name: &str) -> QueryResult<BuildVerifier> {
+        build_verifiers::table
+            .filter(build_verifiers::name.eq(name))
+            .first(conn)
+    }
+
+    /// The message verifiers sign for a request: its method, path and
+    /// timestamp, each followed by a newline, and then its body. Signing all
+    /// of these means that a signed verdict can't be replayed for another
+    /// version, or once the timestamp is too old.
+    pub fn signed_message(method: &str, path: &str, timestamp: &str, body: &[u8]) -> Vec<u8> {
+        let mut message = format!("{}\n{}\n{}\n", method, path, timestamp).into_bytes();
+        message.extend_from_slice(body);
+        message
+    }
+
+    /// Returns whether `signature` is the hex encoded HMAC-SHA256 of
+    /// `message` using this verifier's signing key.
+    pub fn signature_matches(&self, message: &[u8], signature: &str) -> bool {
+        let signature = match hex::decode(signature) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        let expected = self.sign(message);
+        expected.len() == signature.len() && openssl::memcmp::eq(&expected, &signature)
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let key = PKey::hmac(self.secret_hash.as_bytes()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(message).unwrap();
+        signer.sign_to_vec().unwrap()
+    }
+}
+
+impl BuildVerification {
+    pub fn encodable(self, verifier: String) -> EncodableBuildVerification {
+        EncodableBuildVerification {
+            verifier,
+            reproducible: self.reproducible,
+            checksum: self.checksum,
+            log_url: self.log_url,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+impl<'a> NewBuildVerification<'a> {
+    /// Inserts or replaces the verdict of this verifier, and updates the
+    /// `reproducible` flag of the version.
+    ///
+    /// A version is only considered reproducible if all verifiers agree.
+    pub fn save(&self, conn: &PgConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(build_verifications::table)
+                .values(self)
+                .on_conflict((
+                    build_verifications::version_id,
+                    build_verifications::verifier_id,
+                ))
+                .do_update()
+                .set(self)
+                .execute(conn)?;
+
+            let verdicts = build_verifications::table
+                .filter(build_verifications::version_id.eq(self.version_id))
+                .select(build_verifications::reproducible)
+                .load::<bool>(conn)?;
+            diesel::update(versions::table.find(self.version_id))
+                .set(versions::reproducible.eq(verdicts.iter().all(|&r| r)))
+                .execute(conn)?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn verifier() -> BuildVerifier {
+        BuildVerifier {
+            id: 1,
+            name: String::from("rebuilderd"),
+            created_at: NaiveDate::from_ymd(2019, 11, 22).and_hms(12, 0, 0),
+            secret_hash: BuildVerifier::signing_key("secret"),
+        }
+    }
+
+    #[test]
+    fn signed_message_covers_the_request() {
+        let message = BuildVerifier::signed_message("PUT", "/path", "1574424000", b"body");
+        assert_eq!(message, b"PUT\n/path\n1574424000\nbody".to_vec());
+    }
+
+    #[test]
+    fn signature_matches_body() {
+        let verifier = verifier();
+        let signature = hex::encode(verifier.sign(b"body"));
+        assert!(verifier.signature_matches(b"body", &signature));
+        assert!(!verifier.signature_matches(b"other body", &signature));
+        assert!(!verifier.signature_matches(b"body", "not hex"));
+        assert!(!verifier.signature_matches(b"body", ""));
+    }
+}
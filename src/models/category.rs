@@ -3,6 +3,7 @@ use diesel::{self, *};
 
 use crate::models::Crate;
 use crate::schema::*;
+use crate::util::escape_like;
 use crate::views::EncodableCategory;
 
 #[derive(Clone, Identifiable, Queryable, QueryableByName, Debug)]
@@ -76,19 +77,52 @@ impl Category {
         }
     }
 
+    /// Finds the category with the given slug, following the redirects of
+    /// renamed and merged categories.
+    pub fn find_by_slug(conn: &PgConnection, slug: &str) -> QueryResult<Category> {
+        if let Some(category) = Category::by_slug(slug).first(conn).optional()? {
+            return Ok(category);
+        }
+        category_redirects::table
+            .inner_join(categories::table)
+            .filter(category_redirects::slug.eq(crate::lower(slug)))
+            .select(categories::all_columns)
+            .first(conn)
+    }
+
     pub fn update_crate(
         conn: &PgConnection,
         krate: &Crate,
         slugs: &[&str],
     ) -> QueryResult<Vec<String>> {
+        use diesel::dsl::any;
+
         conn.transaction(|| {
-            let categories = Category::by_slugs_case_sensitive(slugs).load::<Category>(conn)?;
-            let invalid_categories = slugs
+            let mut categories = Category::by_slugs_case_sensitive(slugs).load::<Category>(conn)?;
+            let missing = slugs
                 .iter()
                 .cloned()
                 .filter(|s| !categories.iter().any(|c| c.slug == *s))
+                .collect::<Vec<_>>();
+
+            // Crates published with the old slug of a renamed category end up
+            // in the renamed one
+            let redirected = category_redirects::table
+                .inner_join(categories::table)
+                .filter(category_redirects::slug.eq(any(&missing)))
+                .select((category_redirects::slug, categories::all_columns))
+                .load::<(String, Category)>(conn)?;
+            let invalid_categories = missing
+                .iter()
+                .filter(|s| !redirected.iter().any(|(slug, _)| slug == *s))
                 .map(ToString::to_string)
                 .collect();
+            for (_, category) in redirected {
+                if !categories.iter().any(|c| c.id == category.id) {
+                    categories.push(category);
+                }
+            }
+
             let crate_categories = categories
                 .iter()
                 .map(|c| CrateCategory {
@@ -105,6 +139,39 @@ impl Category {
         })
     }
 
+    /// Returns the categories whose name or slug contains `query`, or whose
+    /// name is spelled similarly. Categories starting with `query` come
+    /// first, and then the ones with the most crates.
+    pub fn suggestions(conn: &PgConnection, query: &str, limit: i64) -> QueryResult<Vec<Category>> {
+        use crate::lower;
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Text};
+
+        let query = query.to_lowercase();
+        let escaped = escape_like(&query);
+        let contains = format!("%{}%", escaped);
+        let prefix = format!("{}%", escaped);
+        let is_similar = sql::<Bool>("categories.category % ").bind::<Text, _>(&query);
+
+        categories::table
+            .filter(
+                lower(categories::category)
+                    .like(&contains)
+                    .or(categories::slug.like(&contains))
+                    .or(is_similar),
+            )
+            .order((
+                lower(categories::category)
+                    .like(&prefix)
+                    .or(categories::slug.like(&prefix))
+                    .desc(),
+                categories::crates_cnt.desc(),
+                categories::category.asc(),
+            ))
+            .limit(limit)
+            .load(conn)
+    }
+
     pub fn count_toplevel(conn: &PgConnection) -> QueryResult<i64> {
         use self::categories::dsl::*;
 
@@ -154,10 +221,53 @@ impl Category {
             .bind::<Text, _>(&self.slug)
             .load(conn)
     }
+
+    /// Changes the full slug and name of this category and its subcategories.
+    /// The old slugs redirect to the renamed categories.
+    pub fn rename(&self, conn: &PgConnection, slug: &str, name: &str) -> QueryResult<()> {
+        use diesel::sql_types::Text;
+
+        conn.transaction(|| {
+            sql_query(include_str!("rename_category.sql"))
+                .bind::<Text, _>(&self.slug)
+                .bind::<Text, _>(slug)
+                .bind::<Text, _>(&self.category)
+                .bind::<Text, _>(name)
+                .execute(conn)?;
+            delete_shadowed_redirects(conn)
+        })
+    }
+
+    /// Moves the crates of this category to `target` and deletes this
+    /// category. Its slug redirects to `target` afterwards.
+    pub fn merge_into(&self, conn: &PgConnection, target: &Category) -> QueryResult<()> {
+        use diesel::sql_types::Integer;
+
+        conn.transaction(|| {
+            sql_query(include_str!("merge_categories.sql"))
+                .bind::<Integer, _>(self.id)
+                .bind::<Integer, _>(target.id)
+                .execute(conn)?;
+            update(category_redirects::table.filter(category_redirects::category_id.eq(self.id)))
+                .set(category_redirects::category_id.eq(target.id))
+                .execute(conn)?;
+            delete(self).execute(conn)?;
+            insert_into(category_redirects::table)
+                .values((
+                    category_redirects::slug.eq(&self.slug),
+                    category_redirects::category_id.eq(target.id),
+                ))
+                .on_conflict(category_redirects::slug)
+                .do_update()
+                .set(category_redirects::category_id.eq(target.id))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
 }
 
-/// Struct for inserting categories; only used in tests. Actual categories are inserted
-/// in src/boot/categories.rs.
+/// Struct for inserting categories from the admin endpoints and tests. The
+/// crates.io categories are inserted in src/boot/categories.rs.
 #[derive(Insertable, AsChangeset, Default, Debug)]
 #[table_name = "categories"]
 pub struct NewCategory<'a> {
@@ -167,6 +277,18 @@ pub struct NewCategory<'a> {
 }
 
 impl<'a> NewCategory<'a> {
+    /// Inserts a new category into the database. Redirects from its slug to
+    /// other categories are removed.
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<Category> {
+        conn.transaction(|| {
+            let category = insert_into(categories::table)
+                .values(self)
+                .get_result(conn)?;
+            delete_shadowed_redirects(conn)?;
+            Ok(category)
+        })
+    }
+
     /// Inserts the category into the database, or updates an existing one.
     pub fn create_or_update(&self, conn: &PgConnection) -> QueryResult<Category> {
         use crate::schema::categories::dsl::*;
@@ -180,6 +302,16 @@ impl<'a> NewCategory<'a> {
     }
 }
 
+/// Deletes the redirects whose slug belongs to an existing category.
+fn delete_shadowed_redirects(conn: &PgConnection) -> QueryResult<()> {
+    delete(
+        category_redirects::table
+            .filter(category_redirects::slug.eq_any(categories::table.select(categories::slug))),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,13 @@
+use crate::models::{Crate, User};
+use crate::schema::crate_acls;
+
+/// Grants a user read access to a private crate.
+#[derive(Insertable, Identifiable, Associations, Clone, Copy, Debug)]
+#[belongs_to(Crate)]
+#[belongs_to(User)]
+#[primary_key(crate_id, user_id)]
+#[table_name = "crate_acls"]
+pub struct CrateAcl {
+    pub crate_id: i32,
+    pub user_id: i32,
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+
+use crate::models::krate::validate_url;
+use crate::models::Crate;
+use crate::schema::crate_funding;
+use crate::util::{CargoResult, ErrorCode};
+use crate::views::EncodableCrateFunding;
+
+/// The platforms a crate can be funded through. `custom` is for any other
+/// page that explains how to fund the crate.
+pub const PLATFORMS: &[&str] = &[
+    "custom",
+    "github",
+    "issuehunt",
+    "ko_fi",
+    "liberapay",
+    "open_collective",
+    "patreon",
+    "tidelift",
+];
+
+/// Where to fund the development of a crate, set by its owners when
+/// publishing or through `PATCH /crates/:crate_id`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[primary_key(crate_id, platform)]
+#[table_name = "crate_funding"]
+pub struct CrateFunding {
+    pub crate_id: i32,
+    pub platform: String,
+    pub url: String,
+}
+
+impl CrateFunding {
+    /// Checks that the platforms are known and the URLs are http(s) URLs.
+    pub fn validate(funding: &BTreeMap<String, String>) -> CargoResult<()> {
+        for (platform, url) in funding {
+            if !PLATFORMS.contains(&&platform[..]) {
+                return Err(ErrorCode::InvalidField.human(&format_args!(
+                    "unknown funding platform `{}`, expected one of: {}",
+                    platform,
+                    PLATFORMS.join(", ")
+                )));
+            }
+            validate_url(Some(url.as_str()), &format!("funding.{}", platform))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the funding of a crate, mapping platforms to URLs.
+    pub fn update_crate(
+        conn: &PgConnection,
+        krate: &Crate,
+        funding: &BTreeMap<String, String>,
+    ) -> CargoResult<()> {
+        Self::validate(funding)?;
+        let new_funding = funding
+            .iter()
+            .map(|(platform, url)| {
+                (
+                    crate_funding::crate_id.eq(krate.id),
+                    crate_funding::platform.eq(platform),
+                    crate_funding::url.eq(url),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        conn.transaction(|| {
+            diesel::delete(CrateFunding::belonging_to(krate)).execute(conn)?;
+            diesel::insert_into(crate_funding::table)
+                .values(&new_funding)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn encodable(self) -> EncodableCrateFunding {
+        EncodableCrateFunding {
+            platform: self.platform,
+            url: self.url,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::any;
+use diesel::prelude::*;
+
+use crate::models::{User, Visibility};
+use crate::schema::{crate_list_follows, crate_list_items, crate_lists, crates, users};
+use crate::views::EncodableCrateList;
+
+/// A public, named list of crates that a user curates, e.g. to share the
+/// crates they build web services with.
+#[derive(Queryable, Identifiable, Associations, Clone, Debug)]
+#[belongs_to(User)]
+#[table_name = "crate_lists"]
+pub struct CrateList {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "crate_lists"]
+pub struct NewCrateList<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub description: &'a str,
+}
+
+impl<'a> NewCrateList<'a> {
+    /// Inserts the list, or returns `None` if the user already has a list
+    /// with the same name.
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<Option<CrateList>> {
+        diesel::insert_into(crate_lists::table)
+            .values(self)
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()
+    }
+}
+
+impl CrateList {
+    /// Encodes the lists with the names of their crates, their number of
+    /// followers and the login of their owner. Crates that were made private
+    /// after being added are left out.
+    pub fn encode_all(
+        conn: &PgConnection,
+        lists: Vec<CrateList>,
+    ) -> QueryResult<Vec<EncodableCrateList>> {
+        use diesel::dsl::count_star;
+
+        let ids = lists.iter().map(|list| list.id).collect::<Vec<_>>();
+        let items = crate_list_items::table
+            .inner_join(crates::table)
+            .filter(crate_list_items::list_id.eq(any(&ids)))
+            .filter(crates::visibility.eq(Visibility::Public))
+            .select((crate_list_items::list_id, crates::name))
+            .order((crate_list_items::list_id, crates::name))
+            .load::<(i32, String)>(conn)?;
+        let followers = crate_list_follows::table
+            .filter(crate_list_follows::list_id.eq(any(&ids)))
+            .group_by(crate_list_follows::list_id)
+            .select((crate_list_follows::list_id, count_star()))
+            .load::<(i32, i64)>(conn)?;
+        let user_ids = lists.iter().map(|list| list.user_id).collect::<Vec<_>>();
+        let logins = users::table
+            .filter(users::id.eq(any(&user_ids)))
+            .select((users::id, users::gh_login))
+            .load::<(i32, String)>(conn)?;
+
+        Ok(lists
+            .into_iter()
+            .map(|list| {
+                let crates = items
+                    .iter()
+                    .filter(|(list_id, _)| *list_id == list.id)
+                    .map(|(_, name)| name.clone())
+                    .collect();
+                let followers = followers
+                    .iter()
+                    .find(|(list_id, _)| *list_id == list.id)
+                    .map_or(0, |(_, count)| *count);
+                let user = logins
+                    .iter()
+                    .find(|(id, _)| *id == list.user_id)
+                    .map(|(_, login)| login.clone())
+                    .unwrap_or_default();
+                EncodableCrateList {
+                    id: list.id,
+                    name: list.name,
+                    description: list.description,
+                    user,
+                    crates,
+                    followers,
+                    created_at: list.created_at,
+                    updated_at: list.updated_at,
+                }
+            })
+            .collect())
+    }
+}
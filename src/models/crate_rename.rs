@@ -0,0 +1,89 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::krate::{canon_crate_name, ALL_COLUMNS};
+use crate::models::{Crate, Version};
+use crate::schema::{crate_renames, crates};
+
+/// An old name of a crate that was renamed by the registry administrators,
+/// see `controllers::admin::rename_crate`.
+///
+/// Old names keep resolving to the crate. The API redirects requests for them
+/// to the new name, and the versions published before the rename stay in the
+/// index under the old name, so that crates depending on it can still be
+/// built. Their tarballs stay where they were uploaded as well, see
+/// `CrateRename::published_name`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+pub struct CrateRename {
+    pub id: i32,
+    pub old_name: String,
+    pub crate_id: i32,
+    pub renamed_by: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateRename {
+    /// Renames `krate` to `new_name`, and returns the renamed crate.
+    pub fn rename(
+        conn: &PgConnection,
+        krate: &Crate,
+        new_name: &str,
+        renamed_by: i32,
+    ) -> QueryResult<Crate> {
+        conn.transaction(|| {
+            diesel::insert_into(crate_renames::table)
+                .values((
+                    crate_renames::old_name.eq(&krate.name),
+                    crate_renames::crate_id.eq(krate.id),
+                    crate_renames::renamed_by.eq(renamed_by),
+                    crate_renames::created_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(conn)?;
+            diesel::update(krate)
+                .set(crates::name.eq(new_name))
+                .returning(ALL_COLUMNS)
+                .get_result(conn)
+        })
+    }
+
+    /// Finds the crate that used to be named `name`, along with the old name
+    /// as it was spelled. Names are matched like crate names.
+    pub fn renamed_crate(conn: &PgConnection, name: &str) -> QueryResult<Option<(String, Crate)>> {
+        crate_renames::table
+            .inner_join(crates::table)
+            .filter(canon_crate_name(crate_renames::old_name).eq(canon_crate_name(name)))
+            .select((crate_renames::old_name, ALL_COLUMNS))
+            .order(crate_renames::created_at.desc())
+            .first(conn)
+            .optional()
+    }
+
+    /// All the names `krate` had before it was renamed.
+    pub fn old_names(conn: &PgConnection, krate: &Crate) -> QueryResult<Vec<String>> {
+        crate_renames::table
+            .filter(crate_renames::crate_id.eq(krate.id))
+            .select(crate_renames::old_name)
+            .distinct()
+            .load(conn)
+    }
+
+    /// The name `version` of `krate` was published under. Its index entry and
+    /// tarball are stored under that name.
+    pub fn published_name(
+        conn: &PgConnection,
+        krate: &Crate,
+        version: &Version,
+    ) -> QueryResult<String> {
+        // The name is the one the crate had until the first rename after the
+        // version was published
+        let old_name = crate_renames::table
+            .filter(crate_renames::crate_id.eq(krate.id))
+            .filter(crate_renames::created_at.gt(version.created_at))
+            .select(crate_renames::old_name)
+            .order(crate_renames::created_at.asc())
+            .first::<String>(conn)
+            .optional()?;
+        Ok(old_name.unwrap_or_else(|| krate.name.clone()))
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
+
+use crate::models::{Crate, User};
+use crate::schema::crate_reports;
+
+/// A report of a crate by a user, to be looked at by the registry
+/// administrators.
+#[derive(Queryable, Identifiable, Associations, Clone, Debug)]
+#[belongs_to(Crate)]
+#[belongs_to(User, foreign_key = "reporter_id")]
+#[table_name = "crate_reports"]
+pub struct CrateReport {
+    pub id: i32,
+    pub crate_id: i32,
+    /// `None` for reports filed by the registry itself
+    pub reporter_id: Option<i32>,
+    pub category: ReportCategory,
+    pub description: String,
+    pub resolved: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "crate_reports"]
+pub struct NewCrateReport<'a> {
+    pub crate_id: i32,
+    pub reporter_id: Option<i32>,
+    pub category: ReportCategory,
+    pub description: &'a str,
+}
+
+impl<'a> NewCrateReport<'a> {
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<CrateReport> {
+        diesel::insert_into(crate_reports::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+/// What a crate is reported for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum ReportCategory {
+    Spam = 0,
+    Malware = 1,
+    /// A name chosen to be mistaken for a popular crate
+    Typosquat = 2,
+    Trademark = 3,
+    // if you add a category here, be sure to update `from_sql` below.
+}
+
+impl FromSql<Integer, Pg> for ReportCategory {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(ReportCategory::Spam),
+            1 => Ok(ReportCategory::Malware),
+            2 => Ok(ReportCategory::Typosquat),
+            3 => Ok(ReportCategory::Trademark),
+            n => Err(format!("unknown report category: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for ReportCategory {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::dsl::{max, now};
+use diesel::prelude::*;
+
+use crate::models::{Crate, CrateOwner, OwnerKind};
+use crate::schema::{api_tokens, crate_owners, crate_successors, sessions, users};
+use crate::views::EncodableCrateSuccessor;
+
+/// The shortest inactivity, in months, after which a successor can take over
+/// a crate.
+pub const MIN_INACTIVE_MONTHS: i32 = 6;
+
+/// The longest inactivity, in months, after which a successor can take over
+/// a crate.
+pub const MAX_INACTIVE_MONTHS: i32 = 60;
+
+/// How many days before the transfer of a crate its owner is warned. Crates
+/// are only transferred once their owner was warned at least this long ago.
+pub const WARNING_DAYS: i64 = 30;
+
+/// A user designated by an owner of a crate to take it over once the sole
+/// owner of the crate is inactive for `inactive_months`, see
+/// `controllers::krate::successor`. Designations take effect once the
+/// successor accepts them, and are carried out by the
+/// `transfer_inactive_crates` job.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[primary_key(crate_id)]
+pub struct CrateSuccessor {
+    pub crate_id: i32,
+    pub successor_id: i32,
+    pub designated_by: i32,
+    /// A month counts as 30 days
+    pub inactive_months: i32,
+    pub accepted: bool,
+    pub created_at: NaiveDateTime,
+    /// When the owner was told that the crate is about to be transferred
+    pub warned_at: Option<NaiveDateTime>,
+}
+
+/// What the `transfer_inactive_crates` job does about a designation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Wait,
+    /// Tell the owner that the crate will be transferred unless they use the
+    /// site before the transfer
+    Warn,
+    /// The owner used the site after being warned, so the transfer is off
+    /// until they are inactive long enough again
+    Reset,
+    Transfer,
+}
+
+impl CrateSuccessor {
+    /// Designates the successor of a crate, replacing any previous
+    /// designation. The successor has to accept the designation, even if
+    /// they accepted a previous one.
+    pub fn designate(
+        conn: &PgConnection,
+        crate_id: i32,
+        successor_id: i32,
+        designated_by: i32,
+        inactive_months: i32,
+    ) -> QueryResult<CrateSuccessor> {
+        use diesel::pg::upsert::excluded;
+
+        diesel::insert_into(crate_successors::table)
+            .values((
+                crate_successors::crate_id.eq(crate_id),
+                crate_successors::successor_id.eq(successor_id),
+                crate_successors::designated_by.eq(designated_by),
+                crate_successors::inactive_months.eq(inactive_months),
+            ))
+            .on_conflict(crate_successors::crate_id)
+            .do_update()
+            .set((
+                crate_successors::successor_id.eq(excluded(crate_successors::successor_id)),
+                crate_successors::designated_by.eq(excluded(crate_successors::designated_by)),
+                crate_successors::inactive_months.eq(excluded(crate_successors::inactive_months)),
+                crate_successors::accepted.eq(false),
+                crate_successors::created_at.eq(now),
+                crate_successors::warned_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
+    /// The designations of a user as the successor of crates.
+    pub fn for_successor(conn: &PgConnection, user_id: i32) -> QueryResult<Vec<CrateSuccessor>> {
+        crate_successors::table
+            .filter(crate_successors::successor_id.eq(user_id))
+            .order(crate_successors::crate_id)
+            .load(conn)
+    }
+
+    /// The designations the successors accepted.
+    pub fn accepted(conn: &PgConnection) -> QueryResult<Vec<CrateSuccessor>> {
+        crate_successors::table
+            .filter(crate_successors::accepted.eq(true))
+            .order(crate_successors::crate_id)
+            .load(conn)
+    }
+
+    pub fn accept(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::update(self)
+            .set(crate_successors::accepted.eq(true))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(conn)?;
+        Ok(())
+    }
+
+    pub fn set_warned_at(
+        &self,
+        conn: &PgConnection,
+        warned_at: Option<NaiveDateTime>,
+    ) -> QueryResult<()> {
+        diesel::update(self)
+            .set(crate_successors::warned_at.eq(warned_at))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// The id of the only owner of the crate, or `None` if the crate has
+    /// several owners or is owned by a team.
+    pub fn sole_owner(&self, conn: &PgConnection) -> QueryResult<Option<i32>> {
+        let owners = crate_owners::table
+            .filter(crate_owners::crate_id.eq(self.crate_id))
+            .filter(crate_owners::deleted.eq(false))
+            .select((crate_owners::owner_id, crate_owners::owner_kind))
+            .load::<(i32, i32)>(conn)?;
+        if owners.len() == 1 && owners[0].1 == OwnerKind::User as i32 {
+            Ok(Some(owners[0].0))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// When the owner last used the site, with a browser session or an API
+    /// token. Activity before the designation doesn't count, so that a
+    /// designation never transfers a crate right away.
+    pub fn last_active(&self, conn: &PgConnection, owner_id: i32) -> QueryResult<NaiveDateTime> {
+        let session = sessions::table
+            .filter(sessions::user_id.eq(owner_id))
+            .select(max(sessions::last_used_at))
+            .first::<Option<NaiveDateTime>>(conn)?;
+        let token = api_tokens::table
+            .filter(api_tokens::user_id.eq(owner_id))
+            .select(max(api_tokens::last_used_at))
+            .first::<Option<NaiveDateTime>>(conn)?;
+        Ok(session
+            .into_iter()
+            .chain(token)
+            .fold(self.created_at, NaiveDateTime::max))
+    }
+
+    /// When the crate is transferred if the owner stays inactive.
+    pub fn deadline(&self, last_active: NaiveDateTime) -> NaiveDateTime {
+        last_active + Duration::days(30 * i64::from(self.inactive_months))
+    }
+
+    /// What to do about the designation at `now`, given when the owner was
+    /// last active.
+    pub fn next_step(&self, last_active: NaiveDateTime, now: NaiveDateTime) -> Step {
+        let deadline = self.deadline(last_active);
+        let warning = Duration::days(WARNING_DAYS);
+        match self.warned_at {
+            Some(warned_at) if warned_at < last_active => Step::Reset,
+            Some(warned_at) if now >= deadline && now - warned_at >= warning => Step::Transfer,
+            Some(_) => Step::Wait,
+            None if now >= deadline - warning => Step::Warn,
+            None => Step::Wait,
+        }
+    }
+
+    /// Makes the successor an owner of the crate in place of `owner_id`, and
+    /// removes the designation.
+    pub fn transfer(&self, conn: &PgConnection, owner_id: i32) -> QueryResult<()> {
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: self.crate_id,
+                owner_id: self.successor_id,
+                created_by: self.designated_by,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: true,
+            })
+            .on_conflict(crate_owners::table.primary_key())
+            .do_update()
+            .set(crate_owners::deleted.eq(false))
+            .execute(conn)?;
+        let target = crate_owners::table.find((self.crate_id, owner_id, OwnerKind::User as i32));
+        diesel::update(target)
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)?;
+        self.remove(conn)
+    }
+
+    /// The logins of the successor and of the owner who designated them.
+    pub fn logins(&self, conn: &PgConnection) -> QueryResult<(String, String)> {
+        let login = |id| users::table.find(id).select(users::gh_login).first(conn);
+        Ok((login(self.successor_id)?, login(self.designated_by)?))
+    }
+
+    pub fn encodable(
+        self,
+        crate_name: String,
+        successor: String,
+        designated_by: String,
+    ) -> EncodableCrateSuccessor {
+        EncodableCrateSuccessor {
+            crate_id: self.crate_id,
+            crate_name,
+            successor,
+            designated_by,
+            inactive_months: self.inactive_months,
+            accepted: self.accepted,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn day(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0) + Duration::days(i64::from(day))
+    }
+
+    fn successor(warned_at: Option<NaiveDateTime>) -> CrateSuccessor {
+        CrateSuccessor {
+            crate_id: 1,
+            successor_id: 2,
+            designated_by: 3,
+            inactive_months: 6,
+            accepted: true,
+            created_at: day(0),
+            warned_at,
+        }
+    }
+
+    #[test]
+    fn owners_are_warned_before_the_transfer() {
+        let last_active = day(10);
+        // The deadline is 180 days after the owner was last active
+        assert_eq!(successor(None).deadline(last_active), day(190));
+        assert_eq!(successor(None).next_step(last_active, day(159)), Step::Wait);
+        assert_eq!(successor(None).next_step(last_active, day(160)), Step::Warn);
+        // Owners who weren't warned in time are still warned first
+        assert_eq!(successor(None).next_step(last_active, day(400)), Step::Warn);
+
+        let warned = successor(Some(day(160)));
+        assert_eq!(warned.next_step(last_active, day(189)), Step::Wait);
+        assert_eq!(warned.next_step(last_active, day(190)), Step::Transfer);
+
+        let warned_late = successor(Some(day(400)));
+        assert_eq!(warned_late.next_step(last_active, day(429)), Step::Wait);
+        assert_eq!(warned_late.next_step(last_active, day(430)), Step::Transfer);
+    }
+
+    #[test]
+    fn activity_after_the_warning_resets_it() {
+        let warned = successor(Some(day(160)));
+        assert_eq!(warned.next_step(day(170), day(190)), Step::Reset);
+    }
+}
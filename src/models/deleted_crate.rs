@@ -0,0 +1,94 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::time;
+
+use crate::models::krate::canon_crate_name;
+use crate::models::Crate;
+use crate::schema::deleted_crates;
+use crate::util::{CargoResult, ErrorCode};
+
+/// The most downloads a crate, summed over all of its versions, can have and
+/// still be deleted by its owners.
+pub const MAX_DOWNLOADS: i32 = 1000;
+
+/// A crate that was deleted by its owners, see `controllers::krate::delete`,
+/// or by the registry administrators. The record serves as a tombstone that
+/// keeps the name from being published again by someone else right away.
+#[derive(Queryable, Identifiable, Debug, Clone)]
+pub struct DeletedCrate {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub downloads: i32,
+    pub deleted_by: i32,
+    pub deleted_at: NaiveDateTime,
+    /// When the name can be published again
+    pub available_at: NaiveDateTime,
+}
+
+impl DeletedCrate {
+    /// Deletes `krate` along with all of its versions, and keeps its name from
+    /// being published again for `cooldown`, see
+    /// `Config::deleted_name_cooldown`.
+    pub fn delete(
+        conn: &PgConnection,
+        krate: &Crate,
+        deleted_by: i32,
+        cooldown: time::Duration,
+    ) -> QueryResult<()> {
+        let cooldown = Duration::from_std(cooldown).expect("the cooldown is out of range");
+        let available_at = Utc::now().naive_utc() + cooldown;
+        conn.transaction(|| {
+            diesel::insert_into(deleted_crates::table)
+                .values((
+                    deleted_crates::name.eq(&krate.name),
+                    deleted_crates::created_at.eq(krate.created_at),
+                    deleted_crates::downloads.eq(krate.downloads),
+                    deleted_crates::deleted_by.eq(deleted_by),
+                    deleted_crates::available_at.eq(available_at),
+                ))
+                .execute(conn)?;
+            diesel::delete(krate).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Lets the name of a deleted crate be published again right away, and
+    /// returns whether it was still held.
+    pub fn release_name(conn: &PgConnection, name: &str) -> QueryResult<bool> {
+        let now = Utc::now().naive_utc();
+        let released = diesel::update(
+            deleted_crates::table
+                .filter(canon_crate_name(deleted_crates::name).eq(canon_crate_name(name)))
+                .filter(deleted_crates::available_at.gt(now)),
+        )
+        .set(deleted_crates::available_at.eq(now))
+        .execute(conn)?;
+        Ok(released > 0)
+    }
+}
+
+/// When the name of a recently deleted crate named `name` can be published
+/// again, or `None` if it can be published right away.
+pub fn tombstoned_until(conn: &PgConnection, name: &str) -> QueryResult<Option<NaiveDateTime>> {
+    let available_at = deleted_crates::table
+        .filter(canon_crate_name(deleted_crates::name).eq(canon_crate_name(name)))
+        .select(deleted_crates::available_at)
+        .order(deleted_crates::available_at.desc())
+        .first::<NaiveDateTime>(conn)
+        .optional()?;
+    Ok(available_at.filter(|&available_at| available_at > Utc::now().naive_utc()))
+}
+
+/// Returns an error if a crate named `name` was deleted too recently for the
+/// name to be published again. Like reserved names, this is only checked for
+/// new crates.
+pub fn ensure_name_not_tombstoned(conn: &PgConnection, name: &str) -> CargoResult<()> {
+    match tombstoned_until(conn, name)? {
+        Some(available_at) => Err(ErrorCode::NameUnavailable.human(&format_args!(
+            "a crate with this name was deleted recently, the name can be used again after {} UTC",
+            available_at.format("%Y-%m-%d %H:%M")
+        ))),
+        None => Ok(()),
+    }
+}
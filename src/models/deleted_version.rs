@@ -0,0 +1,61 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::exists;
+use diesel::prelude::*;
+
+use crate::models::{Crate, Version};
+use crate::schema::{crates, deleted_versions};
+
+/// How long after publishing a version its owners can delete it.
+pub const GRACE_PERIOD_HOURS: i64 = 72;
+
+/// The most downloads a version can have and still be deleted. Versions that
+/// were downloaded more often are likely in use, and have to be yanked.
+pub const MAX_DOWNLOADS: i32 = 500;
+
+/// A version its owners deleted shortly after publishing it, see
+/// `controllers::version::delete`. Its number can't be published again.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+pub struct DeletedVersion {
+    pub id: i32,
+    pub crate_id: i32,
+    pub num: String,
+    pub published_at: NaiveDateTime,
+    pub downloads: i32,
+    pub deleted_by: i32,
+    pub deleted_at: NaiveDateTime,
+}
+
+impl DeletedVersion {
+    /// Deletes `version` along with everything that references it, and keeps a
+    /// record of the deletion.
+    pub fn delete(conn: &PgConnection, version: &Version, deleted_by: i32) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(deleted_versions::table)
+                .values((
+                    deleted_versions::crate_id.eq(version.crate_id),
+                    deleted_versions::num.eq(version.num.to_string()),
+                    deleted_versions::published_at.eq(version.created_at),
+                    deleted_versions::downloads.eq(version.downloads),
+                    deleted_versions::deleted_by.eq(deleted_by),
+                ))
+                .execute(conn)?;
+            diesel::delete(version).execute(conn)?;
+            diesel::update(crates::table.find(version.crate_id))
+                .set(crates::downloads.eq(crates::downloads - version.downloads))
+                .execute(conn)?;
+            Crate::update_default_versions(conn, version.crate_id)?;
+            Ok(())
+        })
+    }
+
+    /// Returns whether the given version of a crate was deleted.
+    pub fn exists(conn: &PgConnection, crate_id: i32, num: &str) -> QueryResult<bool> {
+        diesel::select(exists(
+            deleted_versions::table
+                .filter(deleted_versions::crate_id.eq(crate_id))
+                .filter(deleted_versions::num.eq(num)),
+        ))
+        .get_result(conn)
+    }
+}
@@ -1,9 +1,9 @@
 use diesel::prelude::*;
 
 use crate::git;
-use crate::util::{human, CargoResult};
+use crate::util::{CargoResult, ErrorCode};
 
-use crate::models::{Crate, Version};
+use crate::models::{Crate, CrateRename, CrateVersions, Version};
 use crate::schema::*;
 use crate::views::{EncodableCrateDependency, EncodableDependency};
 
@@ -67,12 +67,23 @@ impl ReverseDependency {
         self.dependency
             .encodable(crate_name, Some(self.crate_downloads))
     }
+
+    pub fn crate_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The downloads and name of the dependent crate, which reverse
+    /// dependencies are sorted by
+    pub fn seek_key(&self) -> (i32, String) {
+        (self.crate_downloads, self.name.clone())
+    }
 }
 
 pub fn add_dependencies(
     conn: &PgConnection,
     deps: &[EncodableCrateDependency],
     target_version_id: i32,
+    registry_name: &str,
 ) -> CargoResult<Vec<git::Dependency>> {
     use self::dependencies::dsl::*;
     use diesel::insert_into;
@@ -82,16 +93,21 @@ pub fn add_dependencies(
         .map(|dep| {
             if let Some(registry) = &dep.registry {
                 if !registry.is_empty() {
-                    return Err(human(&format_args!("Dependency `{}` is hosted on another registry. Cross-registry dependencies are not permitted on crates.io.", &*dep.name)));
+                    return Err(ErrorCode::InvalidDependency.human(&format_args!("Dependency `{}` is hosted on another registry. Cross-registry dependencies are not permitted on {}.", &*dep.name, registry_name)));
                 }
             }
 
-            // Match only identical names to ensure the index always references the original crate name
-            let krate = Crate::by_exact_name(&dep.name)
-                .first::<Crate>(&*conn)
-                .map_err(|_| human(&format_args!("no known crate named `{}`", &*dep.name)))?;
+            // Match only identical names to ensure the index always references the original crate name.
+            // The old names of renamed crates still resolve in the index, see `CrateRename`.
+            let krate = match Crate::by_exact_name(&dep.name).first::<Crate>(&*conn).optional()? {
+                Some(krate) => Some(krate),
+                None => CrateRename::renamed_crate(conn, &dep.name)?
+                    .filter(|(old_name, _)| *old_name == *dep.name)
+                    .map(|(_, krate)| krate),
+            };
+            let krate = krate.ok_or_else(|| ErrorCode::InvalidDependency.human(&format_args!("no known crate named `{}`", &*dep.name)))?;
             if dep.version_req == semver::VersionReq::parse("*").unwrap() {
-                return Err(human(
+                return Err(ErrorCode::InvalidDependency.human(
                     "wildcard (`*`) dependency constraints are not allowed \
                      on crates.io. See https://doc.rust-lang.org/cargo/faq.html#can-\
                      libraries-use--as-a-version-for-their-dependencies for more \
@@ -144,6 +160,78 @@ pub fn add_dependencies(
     Ok(git_deps)
 }
 
+/// Returns a warning for each dependency whose newest matching version has an
+/// advisory, so that cargo can show it to the user when publishing.
+pub fn advisory_warnings(
+    conn: &PgConnection,
+    deps: &[EncodableCrateDependency],
+) -> CargoResult<Vec<String>> {
+    let mut warnings = Vec::new();
+    for dep in deps {
+        let krate = match Crate::by_exact_name(&dep.name)
+            .first::<Crate>(conn)
+            .optional()?
+        {
+            Some(krate) => krate,
+            None => continue,
+        };
+
+        let newest_match = krate
+            .versions()
+            .select((versions::num, versions::advisory))
+            .load::<(String, Option<String>)>(conn)?
+            .into_iter()
+            .filter_map(|(num, advisory)| {
+                semver::Version::parse(&num).ok().map(|num| (num, advisory))
+            })
+            .filter(|(num, _)| dep.version_req.matches(num))
+            .max_by(|a, b| a.0.cmp(&b.0));
+
+        if let Some((num, Some(advisory))) = newest_match {
+            warnings.push(format!(
+                "dependency `{}` resolves to version {}, which has an advisory: {}",
+                &*dep.name, num, advisory
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+/// Returns a message for each dependency whose version requirement doesn't
+/// match any non-yanked version of the dependency. These are most likely typos,
+/// and would fail to resolve for anyone using the published crate.
+pub fn unmatched_requirements(
+    conn: &PgConnection,
+    deps: &[EncodableCrateDependency],
+) -> CargoResult<Vec<String>> {
+    let mut unmatched = Vec::new();
+    for dep in deps {
+        let krate = match Crate::by_exact_name(&dep.name)
+            .first::<Crate>(conn)
+            .optional()?
+        {
+            Some(krate) => krate,
+            None => continue,
+        };
+
+        let matches_any = krate
+            .versions()
+            .select(versions::num)
+            .load::<String>(conn)?
+            .iter()
+            .filter_map(|num| semver::Version::parse(num).ok())
+            .any(|num| dep.version_req.matches(&num));
+
+        if !matches_any {
+            unmatched.push(format!(
+                "no non-yanked version of dependency `{}` matches the requirement `{}`",
+                &*dep.name, *dep.version_req
+            ));
+        }
+    }
+    Ok(unmatched)
+}
+
 use diesel::deserialize::{self, FromSql};
 use diesel::pg::Pg;
 use diesel::sql_types::Integer;
@@ -0,0 +1,213 @@
+//! Resolution of the transitive dependencies of a version
+//!
+//! Each dependency is resolved to the latest non-yanked version matching its
+//! requirement, which is what a fresh `cargo generate-lockfile` would pick if
+//! there were no other constraints. Dev dependencies are left out since they
+//! aren't built for dependents.
+
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+
+use crate::models::{DependencyKind, Version};
+use crate::schema::{crates, dependencies, dependency_graphs, versions};
+use crate::util::CargoResult;
+use crate::views::{
+    EncodableDependencyGraph, EncodableDependencyGraphEdge, EncodableDependencyGraphNode,
+};
+
+/// How long a resolved graph is served from the cache before it is resolved
+/// again to pick up newly published versions.
+const CACHE_TTL_MINUTES: i32 = 60;
+/// The most versions a graph includes. Dependencies of larger graphs are
+/// left out, and the graph is marked as `truncated`.
+const MAX_NODES: usize = 500;
+
+/// Returns the dependency graph of the version up to the given depth, from
+/// the cache if it was resolved recently.
+pub fn load(
+    conn: &PgConnection,
+    version: &Version,
+    crate_name: &str,
+    depth: u32,
+) -> CargoResult<EncodableDependencyGraph> {
+    use diesel::dsl::*;
+    use diesel::insert_into;
+
+    let cached = dependency_graphs::table
+        .find((version.id, depth as i32))
+        .filter(dependency_graphs::resolved_at.gt(now - CACHE_TTL_MINUTES.minutes()))
+        .select(dependency_graphs::graph)
+        .first::<serde_json::Value>(conn)
+        .optional()?;
+    if let Some(graph) = cached.and_then(|graph| serde_json::from_value(graph).ok()) {
+        return Ok(graph);
+    }
+
+    let graph = resolve(conn, version, crate_name, depth)?;
+    let json = serde_json::to_value(&graph)?;
+
+    // Wrap in a transaction so we don't poison the outer transaction if
+    // caching the graph fails, e.g. because it was cached concurrently
+    let _ = conn.transaction(|| {
+        insert_into(dependency_graphs::table)
+            .values((
+                dependency_graphs::version_id.eq(version.id),
+                dependency_graphs::depth.eq(depth as i32),
+                dependency_graphs::graph.eq(&json),
+            ))
+            .on_conflict((dependency_graphs::version_id, dependency_graphs::depth))
+            .do_update()
+            .set((
+                dependency_graphs::graph.eq(&json),
+                dependency_graphs::resolved_at.eq(now),
+            ))
+            .execute(conn)
+    });
+
+    Ok(graph)
+}
+
+fn node_id(name: &str, num: &semver::Version) -> String {
+    format!("{}@{}", name, num)
+}
+
+/// Resolves the graph level by level, with at most two queries per level.
+/// Versions that were already visited are linked to but not expanded again,
+/// unless they were only reachable through optional dependencies so far.
+///
+/// Nodes only reachable through optional dependencies are marked `optional`,
+/// since they aren't built unless a feature enabling them is.
+fn resolve(
+    conn: &PgConnection,
+    root: &Version,
+    crate_name: &str,
+    depth: u32,
+) -> QueryResult<EncodableDependencyGraph> {
+    let root_id = node_id(crate_name, &root.num);
+    let mut nodes = vec![EncodableDependencyGraphNode {
+        id: root_id.clone(),
+        name: crate_name.to_string(),
+        version: root.num.to_string(),
+        optional: false,
+    }];
+    let mut edges = Vec::new();
+    let mut truncated = false;
+    // The index in `nodes` of every visited version
+    let mut visited = HashMap::new();
+    visited.insert(root.id, 0);
+    let mut linked = HashSet::new();
+    // The versions of every crate depended on so far, by crate id
+    let mut candidates = HashMap::<i32, Vec<(i32, semver::Version)>>::new();
+    // The versions to expand, with their node id and whether they are optional
+    let mut frontier = HashMap::new();
+    frontier.insert(root.id, (root_id, false));
+
+    for _ in 0..depth {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let version_ids = frontier.keys().cloned().collect::<Vec<_>>();
+        let deps = dependencies::table
+            .inner_join(crates::table)
+            .filter(dependencies::version_id.eq_any(&version_ids))
+            .filter(dependencies::kind.ne(DependencyKind::Dev as i32))
+            .select((
+                dependencies::version_id,
+                dependencies::crate_id,
+                crates::name,
+                dependencies::req,
+                dependencies::kind,
+                dependencies::optional,
+            ))
+            .order((dependencies::version_id, crates::name))
+            .load::<(i32, i32, String, String, DependencyKind, bool)>(conn)?;
+
+        // Only load the versions of crates that weren't depended on before
+        let crate_ids = deps
+            .iter()
+            .map(|dep| dep.1)
+            .filter(|crate_id| !candidates.contains_key(crate_id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if !crate_ids.is_empty() {
+            for crate_id in &crate_ids {
+                candidates.insert(*crate_id, Vec::new());
+            }
+            for (id, crate_id, num) in versions::table
+                .filter(versions::crate_id.eq_any(&crate_ids))
+                .filter(versions::yanked.eq(false))
+                .select((versions::id, versions::crate_id, versions::num))
+                .load::<(i32, i32, String)>(conn)?
+            {
+                if let Ok(num) = semver::Version::parse(&num) {
+                    candidates.entry(crate_id).or_default().push((id, num));
+                }
+            }
+        }
+
+        let mut next_frontier = HashMap::new();
+        for (version_id, crate_id, name, req, kind, optional) in deps {
+            let resolved = semver::VersionReq::parse(&req).ok().and_then(|parsed| {
+                candidates
+                    .get(&crate_id)?
+                    .iter()
+                    .filter(|(_, num)| parsed.matches(num))
+                    .max_by(|(_, a), (_, b)| a.cmp(b))
+            });
+            // Requirements that no published version satisfies can't be
+            // resolved, so they are left out of the graph
+            let (dep_version_id, num) = match resolved {
+                Some(resolved) => resolved,
+                None => continue,
+            };
+
+            let (from, from_optional) = &frontier[&version_id];
+            let optional_path = *from_optional || optional;
+            let to = node_id(&name, num);
+            match visited.get(dep_version_id) {
+                Some(&index) => {
+                    let node = &mut nodes[index];
+                    if node.optional && !optional_path {
+                        node.optional = false;
+                        next_frontier.insert(*dep_version_id, (to.clone(), false));
+                    }
+                }
+                None if nodes.len() >= MAX_NODES => {
+                    truncated = true;
+                    continue;
+                }
+                None => {
+                    visited.insert(*dep_version_id, nodes.len());
+                    nodes.push(EncodableDependencyGraphNode {
+                        id: to.clone(),
+                        name,
+                        version: num.to_string(),
+                        optional: optional_path,
+                    });
+                    next_frontier.insert(*dep_version_id, (to.clone(), optional_path));
+                }
+            }
+
+            // Versions expanded again are linked to their dependencies again
+            if linked.insert((from.clone(), to.clone(), kind as u32)) {
+                edges.push(EncodableDependencyGraphEdge {
+                    from: from.clone(),
+                    to,
+                    req,
+                    kind,
+                    optional,
+                });
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    Ok(EncodableDependencyGraph {
+        nodes,
+        edges,
+        truncated,
+    })
+}
@@ -1,9 +1,12 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 
-use crate::models::Version;
-use crate::schema::version_downloads;
-use crate::views::EncodableVersionDownload;
+use crate::models::{Crate, Version};
+use crate::schema::{
+    crate_download_rollups, crates, download_anomalies, metadata, version_adoption,
+    version_download_clients, version_downloads,
+};
+use crate::views::{EncodableDownloadRollup, EncodableVersionAdoption, EncodableVersionDownload};
 
 #[derive(Queryable, Identifiable, Associations, Debug, Clone, Copy)]
 #[belongs_to(Version)]
@@ -32,6 +35,21 @@ impl VersionDownload {
         Ok(())
     }
 
+    /// Adds `amount` downloads on `day`, which are counted towards the
+    /// totals by the next run of `update_downloads` even if the day was
+    /// already processed.
+    pub fn add(conn: &PgConnection, version: i32, day: NaiveDate, amount: i32) -> QueryResult<()> {
+        use self::version_downloads::dsl::*;
+
+        diesel::insert_into(version_downloads)
+            .values((version_id.eq(version), date.eq(day), downloads.eq(amount)))
+            .on_conflict((version_id, date))
+            .do_update()
+            .set((downloads.eq(downloads + amount), processed.eq(false)))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub fn encodable(self) -> EncodableVersionDownload {
         EncodableVersionDownload {
             version: self.version_id,
@@ -40,3 +58,285 @@ impl VersionDownload {
         }
     }
 }
+
+/// The total downloads of all versions of a crate over a week or a month.
+/// These are computed from `version_downloads` by the `rollup_downloads`
+/// background job.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[primary_key(crate_id, period, period_start)]
+pub struct CrateDownloadRollup {
+    pub crate_id: i32,
+    pub period: String,
+    pub period_start: NaiveDate,
+    pub downloads: i64,
+}
+
+impl CrateDownloadRollup {
+    /// Returns the ids and weekly download totals of the `limit` crates of
+    /// `crate_ids` with the most downloads in the weeks starting at or after
+    /// `since`, most downloaded first.
+    pub fn most_downloaded(
+        conn: &PgConnection,
+        crate_ids: &[i32],
+        since: NaiveDate,
+        limit: i64,
+    ) -> QueryResult<Vec<(i32, i64)>> {
+        use diesel::dsl::{any, sql};
+        use diesel::sql_types::BigInt;
+
+        let total = sql::<BigInt>("SUM(crate_download_rollups.downloads)");
+        crate_download_rollups::table
+            .filter(crate_download_rollups::crate_id.eq(any(crate_ids)))
+            .filter(crate_download_rollups::period.eq("week"))
+            .filter(crate_download_rollups::period_start.ge(since))
+            .group_by(crate_download_rollups::crate_id)
+            .select((crate_download_rollups::crate_id, total.clone()))
+            .order(total.desc())
+            .limit(limit)
+            .load(conn)
+    }
+
+    pub fn encodable(self) -> EncodableDownloadRollup {
+        EncodableDownloadRollup {
+            period_start: self.period_start.to_string(),
+            downloads: self.downloads,
+        }
+    }
+}
+
+/// The cargo version and target platform a download was made from, as
+/// reported in the `User-Agent` header. Only the minor version of cargo is
+/// kept and counts are aggregated per day, so the recorded stats can't be
+/// tied back to individual users.
+/// The downloads of a version in the last 90 days and their share of the
+/// downloads of its crate, computed by the `update_version_adoption` job.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[belongs_to(Version)]
+#[primary_key(version_id)]
+#[table_name = "version_adoption"]
+pub struct VersionAdoption {
+    pub version_id: i32,
+    pub crate_id: i32,
+    pub downloads: i64,
+    pub share: f64,
+    pub computed_at: NaiveDateTime,
+}
+
+impl VersionAdoption {
+    pub fn encodable(self, num: String) -> EncodableVersionAdoption {
+        EncodableVersionAdoption {
+            num,
+            downloads: self.downloads,
+            share: self.share,
+        }
+    }
+}
+
+/// A window of days on which a crate was downloaded far more often than
+/// usual, found by the `detect_download_anomalies` job.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[table_name = "download_anomalies"]
+pub struct DownloadAnomaly {
+    pub id: i32,
+    pub crate_id: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub downloads: i64,
+    /// The downloads of the window at the crate's usual rate
+    pub expected_downloads: i64,
+    /// Whether the downloads above the usual rate are subtracted from the
+    /// download count of the crate
+    pub excluded: bool,
+    pub reviewed: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl DownloadAnomaly {
+    /// Records that the crate was downloaded `downloads` times on `date`
+    /// instead of the usual `expected_downloads`.
+    ///
+    /// A day right after a window extends it, and needs to be reviewed again.
+    /// Days that are already part of a window are ignored. New windows are
+    /// excluded right away if `exclude` is set.
+    pub fn record(
+        conn: &PgConnection,
+        crate_id: i32,
+        date: NaiveDate,
+        downloads: i64,
+        expected_downloads: i64,
+        exclude: bool,
+    ) -> QueryResult<()> {
+        let previous = download_anomalies::table
+            .filter(download_anomalies::crate_id.eq(crate_id))
+            .filter(download_anomalies::start_date.le(date))
+            .filter(download_anomalies::end_date.ge(date.pred()))
+            .first::<DownloadAnomaly>(conn)
+            .optional()?;
+
+        match previous {
+            Some(ref anomaly) if anomaly.end_date >= date => Ok(()),
+            Some(anomaly) => {
+                let extended = diesel::update(&anomaly)
+                    .set((
+                        download_anomalies::end_date.eq(date),
+                        download_anomalies::downloads.eq(download_anomalies::downloads + downloads),
+                        download_anomalies::expected_downloads
+                            .eq(download_anomalies::expected_downloads + expected_downloads),
+                        download_anomalies::reviewed.eq(false),
+                    ))
+                    .get_result::<DownloadAnomaly>(conn)?;
+                if extended.excluded {
+                    adjust_downloads(conn, crate_id, anomaly.excess() - extended.excess())?;
+                }
+                Ok(())
+            }
+            None => {
+                let anomaly = diesel::insert_into(download_anomalies::table)
+                    .values((
+                        download_anomalies::crate_id.eq(crate_id),
+                        download_anomalies::start_date.eq(date),
+                        download_anomalies::end_date.eq(date),
+                        download_anomalies::downloads.eq(downloads),
+                        download_anomalies::expected_downloads.eq(expected_downloads),
+                    ))
+                    .get_result::<DownloadAnomaly>(conn)?;
+                if exclude {
+                    anomaly.set_excluded(conn, true)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The downloads above the crate's usual rate
+    pub fn excess(&self) -> i64 {
+        (self.downloads - self.expected_downloads).max(0)
+    }
+
+    /// Subtracts the excess downloads from the download count of the crate
+    /// and the registry, or adds them back.
+    pub fn set_excluded(&self, conn: &PgConnection, excluded: bool) -> QueryResult<()> {
+        if excluded == self.excluded {
+            return Ok(());
+        }
+        diesel::update(self)
+            .set(download_anomalies::excluded.eq(excluded))
+            .execute(conn)?;
+        let delta = if excluded {
+            -self.excess()
+        } else {
+            self.excess()
+        };
+        adjust_downloads(conn, self.crate_id, delta)
+    }
+}
+
+fn adjust_downloads(conn: &PgConnection, crate_id: i32, delta: i64) -> QueryResult<()> {
+    diesel::update(crates::table.find(crate_id))
+        .set(crates::downloads.eq(crates::downloads + delta as i32))
+        .execute(conn)?;
+    diesel::update(metadata::table)
+        .set(metadata::total_downloads.eq(metadata::total_downloads + delta))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadClient {
+    pub cargo_version: String,
+    pub target: String,
+}
+
+impl DownloadClient {
+    /// Parses a cargo `User-Agent` such as `cargo 1.39.0 (1c6ec66d5 2019-09-30)`.
+    ///
+    /// Returns `None` for anything that isn't cargo. Released versions of cargo
+    /// don't include the host triple, so the target is recorded as `unknown`
+    /// unless the user agent contains something that looks like one.
+    pub fn from_user_agent(user_agent: &str) -> Option<Self> {
+        let mut tokens = user_agent
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ';')
+            .filter(|token| !token.is_empty());
+        if tokens.next()? != "cargo" {
+            return None;
+        }
+
+        let mut version = tokens.next()?.split('.');
+        let major = version.next()?.parse::<u32>().ok()?;
+        let minor = version.next()?.parse::<u32>().ok()?;
+        let target = tokens.find(|t| is_target_triple(t)).unwrap_or("unknown");
+
+        Some(DownloadClient {
+            cargo_version: format!("{}.{}", major, minor),
+            target: target.to_string(),
+        })
+    }
+
+    /// Counts a download of the given version by this client for today.
+    pub fn record(&self, version: i32, conn: &PgConnection) -> QueryResult<()> {
+        use self::version_download_clients::dsl::*;
+
+        diesel::insert_into(version_download_clients)
+            .values((
+                version_id.eq(version),
+                cargo_version.eq(&self.cargo_version),
+                target.eq(&self.target),
+            ))
+            .on_conflict((version_id, date, cargo_version, target))
+            .do_update()
+            .set(downloads.eq(downloads + 1))
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+fn is_target_triple(token: &str) -> bool {
+    token.len() <= 64
+        && token.split('-').count() >= 3
+        && token.starts_with(|c: char| c.is_ascii_alphabetic())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownloadClient;
+
+    fn client(cargo_version: &str, target: &str) -> Option<DownloadClient> {
+        Some(DownloadClient {
+            cargo_version: cargo_version.to_string(),
+            target: target.to_string(),
+        })
+    }
+
+    #[test]
+    fn parses_cargo_user_agents() {
+        assert_eq!(
+            DownloadClient::from_user_agent("cargo 1.39.0 (1c6ec66d5 2019-09-30)"),
+            client("1.39", "unknown")
+        );
+        assert_eq!(
+            DownloadClient::from_user_agent("cargo 1.41.0-nightly (626f0f40e 2019-12-03)"),
+            client("1.41", "unknown")
+        );
+        assert_eq!(
+            DownloadClient::from_user_agent(
+                "cargo 1.40.0 (bc8e4c8be 2019-11-22) x86_64-unknown-linux-gnu"
+            ),
+            client("1.40", "x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn ignores_other_user_agents() {
+        assert_eq!(DownloadClient::from_user_agent(""), None);
+        assert_eq!(DownloadClient::from_user_agent("conduit-test"), None);
+        assert_eq!(DownloadClient::from_user_agent("curl/7.54.0"), None);
+        assert_eq!(DownloadClient::from_user_agent("cargo"), None);
+        assert_eq!(DownloadClient::from_user_agent("cargo foo"), None);
+    }
+}
@@ -1,5 +1,11 @@
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
+
 use crate::models::User;
-use crate::schema::follows;
+use crate::schema::{follows, topic_follows};
 
 #[derive(Insertable, Queryable, Identifiable, Associations, Clone, Copy, Debug)]
 #[belongs_to(User)]
@@ -9,3 +15,44 @@ pub struct Follow {
     pub user_id: i32,
     pub crate_id: i32,
 }
+
+/// A follow of all crates with a keyword or in a category. New releases of
+/// these crates show up in `GET /me/updates` like those of followed crates.
+#[derive(Insertable, Identifiable, Associations, Clone, Copy, Debug)]
+#[belongs_to(User)]
+#[primary_key(user_id, kind, target_id)]
+#[table_name = "topic_follows"]
+pub struct TopicFollow {
+    pub user_id: i32,
+    pub kind: FollowTarget,
+    /// The id of the keyword or category
+    pub target_id: i32,
+}
+
+/// What kind of topic is followed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum FollowTarget {
+    Keyword = 0,
+    /// Categories include their subcategories
+    Category = 1,
+    // if you add a target here, be sure to update `from_sql` below and the
+    // `followed_crates` SQL function.
+}
+
+impl FromSql<Integer, Pg> for FollowTarget {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(FollowTarget::Keyword),
+            1 => Ok(FollowTarget::Category),
+            n => Err(format!("unknown follow target: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for FollowTarget {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
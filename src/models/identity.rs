@@ -0,0 +1,141 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::identities;
+use crate::util::{CargoResult, ErrorCode};
+use crate::views::EncodableIdentity;
+
+/// An identity of a user with one of the identity providers, see `identity`.
+/// Users have the identity they signed up with, and can link identities of
+/// other providers to their account to log in with them as well, see
+/// `controllers::user::identities`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[table_name = "identities"]
+#[belongs_to(User)]
+pub struct Identity {
+    pub id: i32,
+    pub user_id: i32,
+    pub provider: String,
+    /// The ID of the user with the provider
+    pub provider_user_id: String,
+    /// The login of the user with the provider, not qualified with its name
+    pub login: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Identity {
+    pub fn find(
+        conn: &PgConnection,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> QueryResult<Option<Identity>> {
+        identities::table
+            .filter(identities::provider.eq(provider))
+            .filter(identities::provider_user_id.eq(provider_user_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn for_user(conn: &PgConnection, user_id: i32) -> QueryResult<Vec<Identity>> {
+        identities::table
+            .filter(identities::user_id.eq(user_id))
+            .order((identities::created_at, identities::id))
+            .load(conn)
+    }
+
+    /// Records the identity a user logged in with, or updates its login if
+    /// it was already recorded.
+    pub fn record(
+        conn: &PgConnection,
+        user_id: i32,
+        provider: &str,
+        provider_user_id: &str,
+        login: &str,
+    ) -> QueryResult<Identity> {
+        use diesel::pg::upsert::excluded;
+
+        diesel::insert_into(identities::table)
+            .values((
+                identities::user_id.eq(user_id),
+                identities::provider.eq(provider),
+                identities::provider_user_id.eq(provider_user_id),
+                identities::login.eq(login),
+            ))
+            .on_conflict((identities::provider, identities::provider_user_id))
+            .do_update()
+            .set(identities::login.eq(excluded(identities::login)))
+            .get_result(conn)
+    }
+
+    /// Links an identity to the account of `user`. Fails if the identity is
+    /// already linked to another account, or if the account is already
+    /// linked to another identity with the same provider.
+    pub fn link(
+        conn: &PgConnection,
+        user: &User,
+        provider: &str,
+        provider_user_id: &str,
+        login: &str,
+    ) -> CargoResult<Identity> {
+        conn.transaction(|| {
+            if let Some(identity) = Identity::find(conn, provider, provider_user_id)? {
+                if identity.user_id != user.id {
+                    return Err(ErrorCode::AlreadyExists.human(&format_args!(
+                        "this {} account is already linked to another account",
+                        provider
+                    )));
+                }
+            } else if Identity::for_user(conn, user.id)?
+                .iter()
+                .any(|identity| identity.provider == provider)
+            {
+                return Err(ErrorCode::AlreadyExists.human(&format_args!(
+                    "your account is already linked to another {} account, \
+                     unlink it first",
+                    provider
+                )));
+            }
+
+            Ok(Identity::record(
+                conn,
+                user.id,
+                provider,
+                provider_user_id,
+                login,
+            )?)
+        })
+    }
+
+    /// Unlinks the identity of `provider` from the account of `user`. The
+    /// identity the account signed up with can't be unlinked, since its
+    /// details are the details of the account.
+    pub fn unlink(conn: &PgConnection, user: &User, provider: &str) -> CargoResult<()> {
+        if provider == user.provider {
+            return Err(ErrorCode::Conflict
+                .human("the identity your account signed up with can't be unlinked"));
+        }
+
+        let deleted = diesel::delete(
+            identities::table
+                .filter(identities::user_id.eq(user.id))
+                .filter(identities::provider.eq(provider)),
+        )
+        .execute(conn)?;
+        if deleted == 0 {
+            return Err(ErrorCode::NotFound.human(&format_args!(
+                "your account isn't linked to a {} account",
+                provider
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn encodable(self) -> EncodableIdentity {
+        EncodableIdentity {
+            provider: self.provider,
+            login: self.login,
+            created_at: self.created_at,
+        }
+    }
+}
@@ -3,7 +3,8 @@ use diesel::prelude::*;
 
 use crate::models::Crate;
 use crate::schema::*;
-use crate::views::EncodableKeyword;
+use crate::util::escape_like;
+use crate::views::{EncodableKeyword, EncodableKeywordAlias};
 
 #[derive(Clone, Identifiable, Queryable, Debug)]
 pub struct Keyword {
@@ -23,13 +24,47 @@ pub struct CrateKeyword {
     keyword_id: i32,
 }
 
+/// An alternative spelling of a keyword, e.g. `async-await` for `async`.
+#[derive(Clone, Queryable, Debug)]
+pub struct KeywordAlias {
+    pub alias: String,
+    pub keyword: String,
+    pub created_at: NaiveDateTime,
+}
+
 impl Keyword {
+    /// Finds the keyword with the given name, following keyword aliases.
     pub fn find_by_keyword(conn: &PgConnection, name: &str) -> QueryResult<Keyword> {
+        let name = Keyword::canonical_names(conn, &[name])?.remove(0);
         keywords::table
-            .filter(keywords::keyword.eq(crate::lower(name)))
+            .filter(keywords::keyword.eq(name))
             .first(&*conn)
     }
 
+    /// Lowercases the names and replaces aliases with the keywords they stand
+    /// for. Duplicates are removed.
+    pub fn canonical_names(conn: &PgConnection, names: &[&str]) -> QueryResult<Vec<String>> {
+        use diesel::dsl::any;
+
+        let lowercase_names: Vec<_> = names.iter().map(|s| s.to_lowercase()).collect();
+        let aliases = keyword_aliases::table
+            .filter(keyword_aliases::alias.eq(any(&lowercase_names)))
+            .select((keyword_aliases::alias, keyword_aliases::keyword))
+            .load::<(String, String)>(conn)?;
+
+        let mut canonical_names = Vec::with_capacity(lowercase_names.len());
+        for name in lowercase_names {
+            let name = aliases
+                .iter()
+                .find(|(alias, _)| *alias == name)
+                .map_or(name, |(_, keyword)| keyword.clone());
+            if !canonical_names.contains(&name) {
+                canonical_names.push(name);
+            }
+        }
+        Ok(canonical_names)
+    }
+
     pub fn find_or_create_all(conn: &PgConnection, names: &[&str]) -> QueryResult<Vec<Keyword>> {
         use diesel::dsl::any;
 
@@ -49,6 +84,31 @@ impl Keyword {
             .load(conn)
     }
 
+    /// Returns the most used keywords starting with `query`, followed by the
+    /// keywords that are spelled similarly, so that publishers can be steered
+    /// towards established keywords.
+    ///
+    /// Keywords that are no longer used by any crate are not suggested.
+    pub fn suggestions(conn: &PgConnection, query: &str, limit: i64) -> QueryResult<Vec<Keyword>> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Text};
+
+        let query = query.to_lowercase();
+        let pattern = format!("{}%", escape_like(&query));
+        let is_similar = sql::<Bool>("keywords.keyword % ").bind::<Text, _>(&query);
+
+        keywords::table
+            .filter(keywords::keyword.like(&pattern).or(is_similar))
+            .filter(keywords::crates_cnt.gt(0))
+            .order((
+                keywords::keyword.like(&pattern).desc(),
+                keywords::crates_cnt.desc(),
+                keywords::keyword.asc(),
+            ))
+            .limit(limit)
+            .load(conn)
+    }
+
     pub fn valid_name(name: &str) -> bool {
         if name.is_empty() {
             return false;
@@ -77,7 +137,9 @@ impl Keyword {
 
     pub fn update_crate(conn: &PgConnection, krate: &Crate, keywords: &[&str]) -> QueryResult<()> {
         conn.transaction(|| {
-            let keywords = Keyword::find_or_create_all(conn, keywords)?;
+            let names = Keyword::canonical_names(conn, keywords)?;
+            let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+            let keywords = Keyword::find_or_create_all(conn, &names)?;
             diesel::delete(CrateKeyword::belonging_to(krate)).execute(conn)?;
             let crate_keywords = keywords
                 .into_iter()
@@ -94,6 +156,47 @@ impl Keyword {
     }
 }
 
+impl KeywordAlias {
+    /// Makes `alias` an alias of `keyword`. Crates with the `alias` keyword
+    /// get the `keyword` keyword instead, and aliases of `alias` become
+    /// aliases of `keyword`.
+    pub fn create(conn: &PgConnection, alias: &str, keyword: &str) -> QueryResult<()> {
+        use diesel::sql_types::Text;
+
+        let alias = alias.to_lowercase();
+        let keyword = keyword.to_lowercase();
+        conn.transaction(|| {
+            diesel::update(keyword_aliases::table.filter(keyword_aliases::keyword.eq(&alias)))
+                .set(keyword_aliases::keyword.eq(&keyword))
+                .execute(conn)?;
+            diesel::insert_into(keyword_aliases::table)
+                .values((
+                    keyword_aliases::alias.eq(&alias),
+                    keyword_aliases::keyword.eq(&keyword),
+                ))
+                .on_conflict(keyword_aliases::alias)
+                .do_update()
+                .set(keyword_aliases::keyword.eq(&keyword))
+                .execute(conn)?;
+
+            Keyword::find_or_create_all(conn, &[&keyword])?;
+            diesel::sql_query(include_str!("merge_keywords.sql"))
+                .bind::<Text, _>(&alias)
+                .bind::<Text, _>(&keyword)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn encodable(self) -> EncodableKeywordAlias {
+        EncodableKeywordAlias {
+            alias: self.alias,
+            keyword: self.keyword,
+            created_at: self.created_at,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,20 +3,20 @@ use diesel::associations::Identifiable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::sql_types::Bool;
-use indexmap::IndexMap;
 use url::Url;
 
 use crate::app::App;
-use crate::util::{human, CargoResult};
+use crate::controllers::helpers::pagination::PaginationOptions;
+use crate::util::{CargoResult, ErrorCode};
 
 use crate::models::{
     Badge, Category, CrateOwner, Keyword, NewCrateOwnerInvitation, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    ReverseDependency, Rights, User, Version,
 };
 use crate::views::{EncodableCrate, EncodableCrateLinks};
 
 use crate::models::helpers::with_count::*;
-use crate::publish_rate_limit::PublishRateLimit;
+use crate::rate_limit::{BucketOwner, LimitedAction, RateLimiter};
 use crate::schema::*;
 
 /// Hosts in this list are known to not be hosting documentation,
@@ -45,6 +45,60 @@ pub struct Crate {
     pub documentation: Option<String>,
     pub repository: Option<String>,
     pub max_upload_size: Option<i32>,
+    pub visibility: Visibility,
+    /// The highest version that isn't yanked, see `update_default_versions`
+    pub max_version: Option<String>,
+    /// The highest version that isn't yanked or a pre-release
+    pub max_stable_version: Option<String>,
+    pub links: CrateLinks,
+    /// Whether an owner proved that they control the repository, see
+    /// `controllers::krate::repository`. Reset when the repository changes.
+    pub repository_verified: bool,
+}
+
+/// Controls who is able to see a crate and download its versions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum Visibility {
+    /// Visible to everyone. This is the default for all crates.
+    Public = 0,
+    /// Only visible to the owners of the crate, and to users who have been
+    /// granted access through the `crate_acls` table.
+    Private = 1,
+    /// Only visible to the owners of the crate until the registry
+    /// administrators approved it, see `PendingCrate`.
+    Pending = 2,
+    // if you add a visibility here, be sure to update `from_sql` below.
+}
+
+/// Links to resources of a crate besides its homepage, documentation and
+/// repository, set by its owners through `PATCH /crates/:crate_id`. These
+/// replace badges, which only supported a fixed list of CI services.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(deny_unknown_fields)]
+#[sql_type = "Jsonb"]
+pub struct CrateLinks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ci: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sponsor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
+}
+
+impl CrateLinks {
+    /// Checks that all links are http(s) URLs.
+    pub fn validate(&self) -> CargoResult<()> {
+        validate_url(self.ci.as_ref().map(|s| &s[..]), "links.ci")?;
+        validate_url(self.chat.as_ref().map(|s| &s[..]), "links.chat")?;
+        validate_url(self.sponsor.as_ref().map(|s| &s[..]), "links.sponsor")?;
+        validate_url(self.changelog.as_ref().map(|s| &s[..]), "links.changelog")?;
+        Ok(())
+    }
 }
 
 /// We literally never want to select `textsearchable_index_col`
@@ -60,6 +114,11 @@ type AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::visibility,
+    crates::max_version,
+    crates::max_stable_version,
+    crates::links,
+    crates::repository_verified,
 );
 
 pub const ALL_COLUMNS: AllColumns = (
@@ -73,10 +132,19 @@ pub const ALL_COLUMNS: AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::visibility,
+    crates::max_version,
+    crates::max_stable_version,
+    crates::links,
+    crates::repository_verified,
 );
 
 pub const MAX_NAME_LENGTH: usize = 64;
 
+/// Separates the namespace from the name in namespaced crate names like
+/// `org::name`, see `Namespace`.
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
 type CanonCrateName<T> = self::canon_crate_name::HelperType<T>;
 type All = diesel::dsl::Select<crates::table, AllColumns>;
 type WithName<'a> = diesel::dsl::Eq<CanonCrateName<crates::name>, CanonCrateName<&'a str>>;
@@ -102,19 +170,22 @@ impl<'a> NewCrate<'a> {
         self,
         conn: &PgConnection,
         uploader: i32,
-        rate_limit: Option<&PublishRateLimit>,
+        rate_limiter: Option<&RateLimiter>,
     ) -> CargoResult<Crate> {
         use diesel::update;
 
         self.validate()?;
-        self.ensure_name_not_reserved(conn)?;
 
         conn.transaction(|| {
             // To avoid race conditions, we try to insert
             // first so we know whether to add an owner
             if let Some(krate) = self.save_new_crate(conn, uploader)? {
-                if let Some(rate_limit) = rate_limit {
-                    rate_limit.check_rate_limit(uploader, conn)?;
+                if let Some(rate_limiter) = rate_limiter {
+                    rate_limiter.check_rate_limit(
+                        LimitedAction::PublishNew,
+                        &BucketOwner::User(uploader),
+                        conn,
+                    )?;
                 }
                 return Ok(krate);
             }
@@ -129,49 +200,12 @@ impl<'a> NewCrate<'a> {
     }
 
     fn validate(&self) -> CargoResult<()> {
-        fn validate_url(url: Option<&str>, field: &str) -> CargoResult<()> {
-            let url = match url {
-                Some(s) => s,
-                None => return Ok(()),
-            };
-
-            // Manually check the string, as `Url::parse` may normalize relative URLs
-            // making it difficult to ensure that both slashes are present.
-            if !url.starts_with("http://") && !url.starts_with("https://") {
-                return Err(human(&format_args!(
-                    "URL for field `{}` must begin with http:// or https:// (url: {})",
-                    field, url
-                )));
-            }
-
-            // Ensure the entire URL parses as well
-            Url::parse(url)
-                .map_err(|_| human(&format_args!("`{}` is not a valid url: `{}`", field, url)))?;
-            Ok(())
-        }
-
         validate_url(self.homepage, "homepage")?;
         validate_url(self.documentation, "documentation")?;
         validate_url(self.repository, "repository")?;
         Ok(())
     }
 
-    fn ensure_name_not_reserved(&self, conn: &PgConnection) -> CargoResult<()> {
-        use crate::schema::reserved_crate_names::dsl::*;
-        use diesel::dsl::exists;
-        use diesel::select;
-
-        let reserved_name = select(exists(
-            reserved_crate_names.filter(canon_crate_name(name).eq(canon_crate_name(self.name))),
-        ))
-        .get_result::<bool>(conn)?;
-        if reserved_name {
-            Err(human("cannot upload a crate with a reserved name"))
-        } else {
-            Ok(())
-        }
-    }
-
     fn save_new_crate(&self, conn: &PgConnection, user_id: i32) -> QueryResult<Option<Crate>> {
         use crate::schema::crates::dsl::*;
 
@@ -201,6 +235,94 @@ impl<'a> NewCrate<'a> {
     }
 }
 
+pub fn validate_url(url: Option<&str>, field: &str) -> CargoResult<()> {
+    let url = match url {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+
+    // Manually check the string, as `Url::parse` may normalize relative URLs
+    // making it difficult to ensure that both slashes are present.
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(ErrorCode::InvalidField.human(&format_args!(
+            "URL for field `{}` must begin with http:// or https:// (url: {})",
+            field, url
+        )));
+    }
+
+    // Ensure the entire URL parses as well
+    Url::parse(url).map_err(|_| {
+        ErrorCode::InvalidField.human(&format_args!("`{}` is not a valid url: `{}`", field, url))
+    })?;
+    Ok(())
+}
+
+/// Changes to the metadata of a crate made without publishing a version, see
+/// `controllers::krate::metadata::update`. Fields that are `None` are left as
+/// they are, and URLs that are `Some(None)` are cleared.
+#[derive(AsChangeset, Default, Debug)]
+#[table_name = "crates"]
+pub struct CrateMetadata<'a> {
+    pub description: Option<&'a str>,
+    pub homepage: Option<Option<&'a str>>,
+    pub documentation: Option<Option<&'a str>>,
+    pub repository: Option<Option<&'a str>>,
+    pub readme: Option<&'a str>,
+    /// Replaces all links of the crate
+    pub links: Option<&'a CrateLinks>,
+}
+
+impl<'a> CrateMetadata<'a> {
+    /// The names of the fields that are changed.
+    pub fn changed_fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.description.is_some() {
+            fields.push("description");
+        }
+        if self.homepage.is_some() {
+            fields.push("homepage");
+        }
+        if self.documentation.is_some() {
+            fields.push("documentation");
+        }
+        if self.repository.is_some() {
+            fields.push("repository");
+        }
+        if self.readme.is_some() {
+            fields.push("readme");
+        }
+        if self.links.is_some() {
+            fields.push("links");
+        }
+        fields
+    }
+
+    /// Validates the changes like the metadata of published versions, and
+    /// applies them to the crate.
+    pub fn update(&self, conn: &PgConnection, crate_id: i32) -> CargoResult<Crate> {
+        if self.description.map_or(false, |s| s.trim().is_empty()) {
+            return Err(ErrorCode::InvalidField.human("the description of a crate can't be empty"));
+        }
+        validate_url(self.homepage.and_then(|url| url), "homepage")?;
+        validate_url(self.documentation.and_then(|url| url), "documentation")?;
+        validate_url(self.repository.and_then(|url| url), "repository")?;
+        if let Some(links) = self.links {
+            links.validate()?;
+        }
+
+        if self.changed_fields().is_empty() {
+            return Ok(crates::table
+                .find(crate_id)
+                .select(ALL_COLUMNS)
+                .first(conn)?);
+        }
+        Ok(diesel::update(crates::table.find(crate_id))
+            .set(self)
+            .returning(ALL_COLUMNS)
+            .get_result(conn)?)
+    }
+}
+
 impl Crate {
     /// SQL filter based on whether the crate's name loosly matches the given
     /// string.
@@ -242,17 +364,35 @@ impl Crate {
     }
 
     pub fn valid_name(name: &str) -> bool {
-        let under_max_length = name.chars().take(MAX_NAME_LENGTH + 1).count() <= MAX_NAME_LENGTH;
-        Crate::valid_ident(name) && under_max_length
+        Crate::invalid_name_reason(name).is_none()
     }
 
-    fn valid_ident(name: &str) -> bool {
-        Self::valid_feature_name(name)
-            && name
-                .chars()
-                .nth(0)
-                .map(char::is_alphabetic)
-                .unwrap_or(false)
+    /// The rule `name` breaks that keeps it from being the name of a crate,
+    /// or `None` if it's a valid name. Both parts of namespaced names have to
+    /// follow the same rules. Whether namespaced names can be published
+    /// depends on `Config::crate_namespaces`.
+    pub fn invalid_name_reason(name: &str) -> Option<String> {
+        match Crate::split_namespace(name) {
+            Some((namespace, name)) => Crate::invalid_namespace_reason(namespace)
+                .or_else(|| invalid_ident_reason("name", name)),
+            None => invalid_ident_reason("name", name),
+        }
+    }
+
+    /// The rule `namespace` breaks that keeps it from being the name of a
+    /// namespace, or `None` if it's a valid name.
+    pub fn invalid_namespace_reason(namespace: &str) -> Option<String> {
+        invalid_ident_reason("namespace", namespace)
+    }
+
+    /// Splits a namespaced crate name like `org::name` into the namespace
+    /// and the name within it, or returns `None` for other names.
+    pub fn split_namespace(name: &str) -> Option<(&str, &str)> {
+        let separator = name.find(NAMESPACE_SEPARATOR)?;
+        Some((
+            &name[..separator],
+            &name[separator + NAMESPACE_SEPARATOR.len()..],
+        ))
     }
 
     pub fn valid_feature_name(name: &str) -> bool {
@@ -279,26 +419,16 @@ impl Crate {
 
     pub fn minimal_encodable(
         self,
-        max_version: &semver::Version,
         badges: Option<Vec<Badge>>,
         exact_match: bool,
         recent_downloads: Option<i64>,
     ) -> EncodableCrate {
-        self.encodable(
-            max_version,
-            None,
-            None,
-            None,
-            badges,
-            exact_match,
-            recent_downloads,
-        )
+        self.encodable(None, None, None, badges, exact_match, recent_downloads)
     }
 
     #[allow(clippy::too_many_arguments)]
     pub fn encodable(
         self,
-        max_version: &semver::Version,
         versions: Option<Vec<i32>>,
         keywords: Option<&[Keyword]>,
         categories: Option<&[Category]>,
@@ -315,6 +445,10 @@ impl Crate {
             homepage,
             documentation,
             repository,
+            max_version,
+            max_stable_version,
+            links,
+            repository_verified,
             ..
         } = self;
         let versions_link = match versions {
@@ -337,12 +471,15 @@ impl Crate {
             keywords: keyword_ids,
             categories: category_ids,
             badges,
-            max_version: max_version.to_string(),
+            max_version: max_version.unwrap_or_else(|| String::from("0.0.0")),
+            max_stable_version,
             documentation,
             homepage,
             exact_match,
             description,
             repository,
+            repository_verified,
+            project_links: links,
             links: EncodableCrateLinks {
                 version_downloads: format!("/api/v1/crates/{}/downloads", name),
                 versions: versions_link,
@@ -382,16 +519,38 @@ impl Crate {
         }
     }
 
-    pub fn max_version(&self, conn: &PgConnection) -> CargoResult<semver::Version> {
-        use crate::schema::versions::dsl::*;
-
-        let vs = self
-            .versions()
-            .select(num)
+    /// Recomputes the highest version of a crate that isn't yanked, and the
+    /// highest one that isn't a pre-release either, and stores them on the
+    /// crate. This has to be done whenever a version is published, yanked,
+    /// unyanked or deleted. Returns the updated crate.
+    pub fn update_default_versions(conn: &PgConnection, crate_id: i32) -> QueryResult<Crate> {
+        let nums = versions::table
+            .filter(versions::crate_id.eq(crate_id))
+            .filter(versions::yanked.eq(false))
+            .select(versions::num)
             .load::<String>(conn)?
             .into_iter()
-            .map(|s| semver::Version::parse(&s).unwrap());
-        Ok(Version::max(vs))
+            .map(|num| semver::Version::parse(&num).unwrap())
+            .collect::<Vec<_>>();
+        let max_stable_version = Version::max_stable(nums.iter().cloned());
+        let max_version = Version::max(nums);
+
+        diesel::update(crates::table.find(crate_id))
+            .set((
+                crates::max_version.eq(max_version.to_string()),
+                crates::max_stable_version.eq(max_stable_version.map(|v| v.to_string())),
+            ))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+    }
+
+    /// Whether the registry administrators have locked the crate, which
+    /// blocks publishing new versions of it.
+    pub fn is_locked(&self, conn: &PgConnection) -> QueryResult<bool> {
+        crates::table
+            .find(self.id)
+            .select(crates::locked)
+            .first(conn)
     }
 
     pub fn owners(&self, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
@@ -473,7 +632,11 @@ impl Crate {
         login: &str,
     ) -> CargoResult<()> {
         let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
+        self.remove_owner(conn, &owner)?;
+        Ok(())
+    }
 
+    pub fn remove_owner(&self, conn: &PgConnection, owner: &Owner) -> QueryResult<()> {
         let target = crate_owners::table.find((self.id(), owner.id(), owner.kind() as i32));
         diesel::update(target)
             .set(crate_owners::deleted.eq(true))
@@ -481,6 +644,39 @@ impl Crate {
         Ok(())
     }
 
+    /// Returns whether the given user (if any) is allowed to see this crate.
+    ///
+    /// Public crates are visible to everyone. Private crates are only visible
+    /// to their owners, members of owning teams, and users listed in the
+    /// crate's access control list. Pending crates are only visible to their
+    /// owners and members of owning teams.
+    pub fn is_visible_to(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        user: Option<&User>,
+    ) -> CargoResult<bool> {
+        use diesel::dsl::exists;
+        use diesel::select;
+
+        if self.visibility == Visibility::Public {
+            return Ok(true);
+        }
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(false),
+        };
+
+        if self.visibility == Visibility::Private {
+            let granted = select(exists(crate_acls::table.find((self.id, user.id))))
+                .get_result::<bool>(conn)?;
+            if granted {
+                return Ok(true);
+            }
+        }
+        Ok(user.rights(app, conn, &self.owners(conn)?)? > Rights::None)
+    }
+
     pub fn badges(&self, conn: &PgConnection) -> QueryResult<Vec<Badge>> {
         badges::table
             .filter(badges::crate_id.eq(self.id))
@@ -488,33 +684,193 @@ impl Crate {
     }
 
     /// Returns (dependency, dependent crate name, dependent crate downloads)
+    ///
+    /// Supports both `page` and `seek` pagination, the seek cursor being the
+    /// `ReverseDependency::seek_key` of the last record of the previous page.
+    ///
+    /// If `matching` is given, only dependents whose version requirement is
+    /// satisfied by that version of this crate are returned.
     pub fn reverse_dependencies(
         &self,
         conn: &PgConnection,
-        params: &IndexMap<String, String>,
+        options: &PaginationOptions,
+        matching: Option<&semver::Version>,
     ) -> CargoResult<(Vec<ReverseDependency>, i64)> {
-        use crate::controllers::helpers::pagination::*;
         use diesel::sql_query;
-        use diesel::sql_types::{BigInt, Integer};
+        use diesel::sql_types::{Array, BigInt, Integer, Nullable, Text};
 
         // FIXME: It'd be great to support this with `.paginate` directly,
         // and get cursor/id pagination for free. But Diesel doesn't currently
         // have great support for abstracting over "Is this using `Queryable`
         // or `QueryableByName` to load things?"
-        let options = PaginationOptions::new(params)?;
         let offset = options.offset().unwrap_or_default();
+        let (seek_downloads, seek_name) = match options.seek_after::<(i32, String)>()? {
+            Some((downloads, name)) => (Some(downloads), Some(name)),
+            None => (None, None),
+        };
+        let matching_reqs = match matching {
+            Some(version) => Some(self.requirements_matching(conn, version)?),
+            None => None,
+        };
         let rows = sql_query(include_str!("krate_reverse_dependencies.sql"))
             .bind::<Integer, _>(self.id)
             .bind::<BigInt, _>(i64::from(offset))
             .bind::<BigInt, _>(i64::from(options.per_page))
+            .bind::<Nullable<Integer>, _>(seek_downloads)
+            .bind::<Nullable<Text>, _>(seek_name)
+            .bind::<Nullable<Array<Text>>, _>(matching_reqs)
             .load::<WithCount<ReverseDependency>>(conn)?;
 
         Ok(rows.records_and_total())
     }
+
+    /// Returns the names of the crates whose latest non-yanked version
+    /// depends on the given version of this crate.
+    pub fn dependents_of_version(
+        &self,
+        conn: &PgConnection,
+        version: &semver::Version,
+    ) -> QueryResult<Vec<String>> {
+        use diesel::sql_query;
+        use diesel::sql_types::{Array, BigInt, Integer, Nullable, Text};
+
+        let rows = sql_query(include_str!("krate_reverse_dependencies.sql"))
+            .bind::<Integer, _>(self.id)
+            .bind::<BigInt, _>(0_i64)
+            .bind::<BigInt, _>(i64::max_value())
+            .bind::<Nullable<Integer>, _>(None::<i32>)
+            .bind::<Nullable<Text>, _>(None::<String>)
+            .bind::<Nullable<Array<Text>>, _>(Some(self.requirements_matching(conn, version)?))
+            .load::<WithCount<ReverseDependency>>(conn)?;
+
+        Ok(rows
+            .records_and_total()
+            .0
+            .iter()
+            .map(|dep| dep.crate_name().to_string())
+            .collect())
+    }
+
+    /// Returns the names of the other crates with any version, including
+    /// yanked ones, that depends on the given version of this crate. Yanked
+    /// versions count since they can still be in lock files.
+    pub fn all_dependents_of_version(
+        &self,
+        conn: &PgConnection,
+        version: &semver::Version,
+    ) -> QueryResult<Vec<String>> {
+        let reqs = self.requirements_matching(conn, version)?;
+        dependencies::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(dependencies::crate_id.eq(self.id))
+            .filter(versions::crate_id.ne(self.id))
+            .filter(dependencies::req.eq_any(reqs))
+            .select(crates::name)
+            .distinct()
+            .order(crates::name)
+            .load(conn)
+    }
+
+    /// Returns the distinct version requirements other crates have on this
+    /// crate which are satisfied by the given version. There are far fewer
+    /// distinct requirements than dependencies, so evaluating them here is
+    /// cheaper than comparing versions in SQL.
+    fn requirements_matching(
+        &self,
+        conn: &PgConnection,
+        version: &semver::Version,
+    ) -> QueryResult<Vec<String>> {
+        let reqs = dependencies::table
+            .filter(dependencies::crate_id.eq(self.id))
+            .select(dependencies::req)
+            .distinct()
+            .load::<String>(conn)?;
+
+        Ok(reqs
+            .into_iter()
+            .filter(|req| {
+                semver::VersionReq::parse(req)
+                    .map(|req| req.matches(version))
+                    .unwrap_or(false)
+            })
+            .collect())
+    }
+}
+
+/// The rule `ident` breaks that keeps it from being the name of a crate or
+/// namespace, described as `what`.
+fn invalid_ident_reason(what: &str, ident: &str) -> Option<String> {
+    if ident.is_empty() {
+        return Some(format!("the {} must not be empty", what));
+    }
+    if let Some(c) = ident
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+    {
+        return Some(format!(
+            "the {} can only contain letters, numbers, hyphens and underscores, \
+             but contains `{}`",
+            what, c
+        ));
+    }
+    if !ident
+        .chars()
+        .nth(0)
+        .map_or(false, |c| c.is_ascii_alphabetic())
+    {
+        return Some(format!("the {} must start with a letter", what));
+    }
+    if ident.len() > MAX_NAME_LENGTH {
+        return Some(format!(
+            "the {} must have at most {} characters",
+            what, MAX_NAME_LENGTH
+        ));
+    }
+    None
+}
+
+use diesel::deserialize::{self, FromSql};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::{Date, Integer, Jsonb, Text};
+use std::io::Write;
+
+impl FromSql<Integer, Pg> for Visibility {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(Visibility::Public),
+            1 => Ok(Visibility::Private),
+            2 => Ok(Visibility::Pending),
+            n => Err(format!("unknown visibility: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for Visibility {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+impl FromSql<Jsonb, Pg> for CrateLinks {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for CrateLinks {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Jsonb, Pg>::to_sql(&serde_json::to_value(self)?, out)
+    }
 }
 
-use diesel::sql_types::{Date, Text};
 sql_function!(fn canon_crate_name(x: Text) -> Text);
+
+/// Canonicalizes a crate name the same way the `canon_crate_name` SQL
+/// function does.
+pub fn canonical_name(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
 sql_function!(fn to_char(a: Date, b: Text) -> Text);
 
 #[cfg(test)]
@@ -535,6 +891,38 @@ mod tests {
         assert!(krate.validate().is_err());
     }
 
+    #[test]
+    fn invalid_names_report_the_broken_rule() {
+        assert_eq!(Crate::invalid_name_reason("foo_bar-2"), None);
+        let reason = |name: &str| Crate::invalid_name_reason(name).unwrap();
+        assert_eq!(reason(""), "the name must not be empty");
+        assert!(reason("foo.bar").ends_with("but contains `.`"));
+        assert!(reason("föo").ends_with("but contains `ö`"));
+        assert_eq!(reason("1foo"), "the name must start with a letter");
+        assert_eq!(reason("_foo"), "the name must start with a letter");
+        assert!(Crate::valid_name(&"a".repeat(64)));
+        assert_eq!(
+            reason(&"a".repeat(65)),
+            "the name must have at most 64 characters"
+        );
+    }
+
+    #[test]
+    fn namespaced_names() {
+        assert_eq!(Crate::split_namespace("foo"), None);
+        assert_eq!(Crate::split_namespace("org::foo"), Some(("org", "foo")));
+        assert_eq!(Crate::invalid_name_reason("org::foo-bar"), None);
+        let reason = |name: &str| Crate::invalid_name_reason(name).unwrap();
+        assert_eq!(reason("::foo"), "the namespace must not be empty");
+        assert_eq!(reason("org::"), "the name must not be empty");
+        assert_eq!(
+            reason("1org::foo"),
+            "the namespace must start with a letter"
+        );
+        assert!(reason("org::foo::bar").ends_with("but contains `:`"));
+        assert!(reason("org:foo").ends_with("but contains `:`"));
+    }
+
     #[test]
     fn documentation_blocked_no_url_provided() {
         assert_eq!(Crate::remove_blocked_documentation_urls(None), None);
@@ -1,4 +1,6 @@
--- Apply pagination to the whole thing
+-- Apply pagination to the whole thing. The total is counted before skipping
+-- the rows before the seek cursor ($4, $5), if there is one.
+SELECT * FROM (
 SELECT *, COUNT(*) OVER () as total FROM (
     -- Multple dependencies can exist, make it distinct
     SELECT DISTINCT ON (crate_downloads, crate_name)
@@ -31,7 +33,14 @@ SELECT *, COUNT(*) OVER () as total FROM (
       ON crates.id = versions.crate_id
     WHERE dependencies.crate_id = $1
       AND rn = 1
-    ORDER BY crate_downloads DESC
+      -- Only the requirements that match the `matching` version, if given
+      AND ($6::text[] IS NULL OR dependencies.req = ANY($6))
+    ORDER BY crate_downloads DESC, crate_name ASC
 ) t
+) t2
+WHERE $4::int4 IS NULL
+   OR crate_downloads < $4
+   OR (crate_downloads = $4 AND crate_name > $5)
+ORDER BY crate_downloads DESC, crate_name ASC
 OFFSET $2
 LIMIT $3
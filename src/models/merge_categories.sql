@@ -0,0 +1,5 @@
+INSERT INTO crates_categories (crate_id, category_id)
+SELECT crate_id, $2
+FROM crates_categories
+WHERE category_id = $1
+ON CONFLICT DO NOTHING
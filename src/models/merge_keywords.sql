@@ -0,0 +1,13 @@
+-- Moves the crates with the keyword $1 to the keyword $2
+WITH moved AS (
+  DELETE FROM crates_keywords
+  USING keywords
+  WHERE crates_keywords.keyword_id = keywords.id
+  AND keywords.keyword = $1
+  RETURNING crates_keywords.crate_id
+)
+INSERT INTO crates_keywords (crate_id, keyword_id)
+SELECT moved.crate_id, keywords.id
+FROM moved, keywords
+WHERE keywords.keyword = $2
+ON CONFLICT DO NOTHING
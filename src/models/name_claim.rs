@@ -0,0 +1,201 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
+
+use crate::models::{Crate, CrateOwner, OwnerKind, User};
+use crate::schema::{crate_owner_invitations, crate_owners, emails, name_claims, users};
+use crate::views::EncodableNameClaim;
+
+/// How long the owners of a crate have to respond to a claim before the
+/// registry administrators can transfer the crate without their consent.
+pub const HOLDING_PERIOD_DAYS: i64 = 28;
+
+/// A request by a user to take over the name of a crate someone else owns,
+/// usually because the crate has been abandoned or squatted.
+#[derive(Queryable, Identifiable, Associations, Clone, Debug)]
+#[belongs_to(Crate)]
+#[belongs_to(User, foreign_key = "claimant_id")]
+#[table_name = "name_claims"]
+pub struct NameClaim {
+    pub id: i32,
+    pub crate_id: i32,
+    pub claimant_id: i32,
+    pub reason: String,
+    pub status: ClaimStatus,
+    /// What the owners wrote when responding to the claim
+    pub response: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub responded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "name_claims"]
+pub struct NewNameClaim<'a> {
+    pub crate_id: i32,
+    pub claimant_id: i32,
+    pub reason: &'a str,
+}
+
+impl<'a> NewNameClaim<'a> {
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<NameClaim> {
+        diesel::insert_into(name_claims::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum ClaimStatus {
+    /// Waiting for the owners to respond
+    Pending = 0,
+    /// The owners agreed to give up the crate
+    Released = 1,
+    /// The owners objected to the claim
+    Contested = 2,
+    /// The crate has been transferred to the claimant
+    Approved = 3,
+    Rejected = 4,
+    // if you add a status here, be sure to update `from_sql` below.
+}
+
+impl NameClaim {
+    /// Whether the claim still needs a decision by the administrators.
+    pub fn is_open(&self) -> bool {
+        match self.status {
+            ClaimStatus::Pending | ClaimStatus::Released | ClaimStatus::Contested => true,
+            ClaimStatus::Approved | ClaimStatus::Rejected => false,
+        }
+    }
+
+    /// Whether the administrators can approve the claim at `now`. Claims the
+    /// owners agreed to can be approved right away, all others only after the
+    /// holding period.
+    pub fn can_be_approved(&self, now: NaiveDateTime) -> bool {
+        match self.status {
+            ClaimStatus::Released => true,
+            ClaimStatus::Pending | ClaimStatus::Contested => {
+                now >= self.created_at + Duration::days(HOLDING_PERIOD_DAYS)
+            }
+            ClaimStatus::Approved | ClaimStatus::Rejected => false,
+        }
+    }
+
+    /// Makes the claimant the only owner of the crate. Owner invitations
+    /// that haven't been accepted yet are dropped.
+    pub fn transfer(&self, conn: &PgConnection, admin_id: i32) -> QueryResult<()> {
+        diesel::update(crate_owners::table.filter(crate_owners::crate_id.eq(self.crate_id)))
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)?;
+        diesel::delete(
+            crate_owner_invitations::table
+                .filter(crate_owner_invitations::crate_id.eq(self.crate_id)),
+        )
+        .execute(conn)?;
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: self.crate_id,
+                owner_id: self.claimant_id,
+                created_by: admin_id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: true,
+            })
+            .on_conflict(crate_owners::table.primary_key())
+            .do_update()
+            .set(crate_owners::deleted.eq(false))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn encodable(self, crate_name: String, claimant: String) -> EncodableNameClaim {
+        EncodableNameClaim {
+            id: self.id,
+            krate: crate_name,
+            claimant,
+            reason: self.reason,
+            status: self.status,
+            response: self.response,
+            created_at: self.created_at,
+            responded_at: self.responded_at,
+        }
+    }
+
+    /// The verified email address of the claimant, if any.
+    pub fn claimant_email(&self, conn: &PgConnection) -> QueryResult<Option<String>> {
+        emails::table
+            .filter(emails::user_id.eq(self.claimant_id))
+            .filter(emails::verified.eq(true))
+            .select(emails::email)
+            .first(conn)
+            .optional()
+    }
+}
+
+/// The verified email addresses of the users owning a crate. Teams aren't
+/// emailed.
+pub fn owner_emails(conn: &PgConnection, crate_id: i32) -> QueryResult<Vec<String>> {
+    crate_owners::table
+        .inner_join(users::table.inner_join(emails::table))
+        .filter(crate_owners::crate_id.eq(crate_id))
+        .filter(crate_owners::deleted.eq(false))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(emails::verified.eq(true))
+        .select(emails::email)
+        .load(conn)
+}
+
+impl FromSql<Integer, Pg> for ClaimStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(ClaimStatus::Pending),
+            1 => Ok(ClaimStatus::Released),
+            2 => Ok(ClaimStatus::Contested),
+            3 => Ok(ClaimStatus::Approved),
+            4 => Ok(ClaimStatus::Rejected),
+            n => Err(format!("unknown claim status: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for ClaimStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(status: ClaimStatus) -> NameClaim {
+        NameClaim {
+            id: 1,
+            crate_id: 1,
+            claimant_id: 1,
+            reason: String::from("abandoned"),
+            status,
+            response: None,
+            created_at: chrono::NaiveDate::from_ymd(2019, 11, 1).and_hms(0, 0, 0),
+            responded_at: None,
+        }
+    }
+
+    #[test]
+    fn claims_can_be_approved_after_the_holding_period() {
+        let early = chrono::NaiveDate::from_ymd(2019, 11, 10).and_hms(0, 0, 0);
+        let late = chrono::NaiveDate::from_ymd(2019, 12, 1).and_hms(0, 0, 0);
+
+        assert!(!claim(ClaimStatus::Pending).can_be_approved(early));
+        assert!(!claim(ClaimStatus::Contested).can_be_approved(early));
+        assert!(claim(ClaimStatus::Released).can_be_approved(early));
+        assert!(claim(ClaimStatus::Pending).can_be_approved(late));
+        assert!(claim(ClaimStatus::Contested).can_be_approved(late));
+        assert!(!claim(ClaimStatus::Rejected).can_be_approved(late));
+    }
+}
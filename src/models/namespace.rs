@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::krate::canon_crate_name;
+use crate::models::{Crate, CrateOwner, OwnerKind, Team};
+use crate::schema::{crate_owners, namespaces, teams};
+use crate::views::EncodableNamespace;
+
+/// A namespace for crates with names like `org::name`, see
+/// `Config::crate_namespaces`. Namespaces belong to a GitHub team. Only its
+/// members can publish new crates in the namespace, and the team becomes an
+/// owner of those crates.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Team)]
+pub struct Namespace {
+    pub id: i32,
+    pub name: String,
+    pub team_id: i32,
+    pub created_by: i32,
+    pub created_at: NaiveDateTime,
+}
+
+type ByName<'a> = diesel::dsl::Filter<
+    namespaces::table,
+    diesel::dsl::Eq<
+        canon_crate_name::HelperType<namespaces::name>,
+        canon_crate_name::HelperType<&'a str>,
+    >,
+>;
+
+impl Namespace {
+    /// Namespaces, like crates, are matched ignoring case and the
+    /// difference between `-` and `_`.
+    pub fn by_name(name: &str) -> ByName<'_> {
+        namespaces::table.filter(canon_crate_name(namespaces::name).eq(canon_crate_name(name)))
+    }
+
+    /// Creates the namespace, or returns `None` if it already exists.
+    pub fn create(
+        conn: &PgConnection,
+        name: &str,
+        team: &Team,
+        created_by: i32,
+    ) -> QueryResult<Option<Namespace>> {
+        diesel::insert_into(namespaces::table)
+            .values((
+                namespaces::name.eq(name),
+                namespaces::team_id.eq(team.id),
+                namespaces::created_by.eq(created_by),
+            ))
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()
+    }
+
+    pub fn team(&self, conn: &PgConnection) -> QueryResult<Team> {
+        teams::table.find(self.team_id).first(conn)
+    }
+
+    /// Adds the team of the namespace as an owner of a new crate in it.
+    pub fn add_team_as_owner(
+        &self,
+        conn: &PgConnection,
+        krate: &Crate,
+        added_by: i32,
+    ) -> QueryResult<()> {
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: self.team_id,
+                created_by: added_by,
+                owner_kind: OwnerKind::Team as i32,
+                email_notifications: true,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn encodable(self, team: &Team, crates: i64) -> EncodableNamespace {
+        EncodableNamespace {
+            name: self.name,
+            team: team.login.clone(),
+            crates,
+            created_at: self.created_at,
+        }
+    }
+}
@@ -3,7 +3,8 @@ use diesel::prelude::*;
 
 use crate::app::App;
 use crate::github;
-use crate::util::{human, CargoResult};
+use crate::identity;
+use crate::util::{CargoResult, ErrorCode};
 
 use crate::models::{Crate, Team, User};
 use crate::schema::{crate_owners, users};
@@ -57,15 +58,16 @@ impl Owner {
     /// up-to-date GitHub ID. Fails out if the user isn't found in the
     /// database, the team isn't found on GitHub, or if the user isn't a member
     /// of the team on GitHub.
-    /// May be a user's GH login or a full team name. This is case
-    /// sensitive.
+    /// May be a user's GH login, the qualified login of a user of another
+    /// identity provider like `gitlab:ferris`, or a full team name. This is
+    /// case sensitive.
     pub fn find_or_create_by_login(
         app: &App,
         conn: &PgConnection,
         req_user: &User,
         name: &str,
     ) -> CargoResult<Owner> {
-        if name.contains(':') {
+        if name.contains(':') && !identity::is_qualified_user_login(name) {
             Ok(Owner::Team(Team::create_or_update(
                 app, conn, name, req_user,
             )?))
@@ -74,7 +76,10 @@ impl Owner {
                 .filter(users::gh_login.eq(name))
                 .first(conn)
                 .map(Owner::User)
-                .map_err(|_| human(&format_args!("could not find user with login `{}`", name)))
+                .map_err(|_| {
+                    ErrorCode::NotFound
+                        .human(&format_args!("could not find user with login `{}`", name))
+                })
         }
     }
 
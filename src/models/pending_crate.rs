@@ -0,0 +1,68 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use swirl::Job;
+
+use crate::git;
+use crate::models::{Crate, Visibility};
+use crate::schema::{crates, pending_crates};
+use crate::telemetry;
+use crate::util::{CargoError, CargoResult};
+use crate::views::EncodablePendingCrate;
+
+/// A new crate that is waiting for the approval of the registry
+/// administrators, see `Config::new_crate_approval`. Until it's approved, the
+/// crate has the `Pending` visibility and its first version is left out of
+/// the index. Rejecting it deletes the crate.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[primary_key(crate_id)]
+pub struct PendingCrate {
+    pub crate_id: i32,
+    /// The `git::Crate` that is added to the index once the crate is approved
+    pub index_entry: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl PendingCrate {
+    /// Hides a crate that was just published until it's approved, holding
+    /// back the index entry of its first version.
+    pub fn hold(conn: &PgConnection, krate: &Crate, index_entry: &git::Crate) -> CargoResult<()> {
+        diesel::update(krate)
+            .set(crates::visibility.eq(Visibility::Pending))
+            .execute(conn)?;
+        diesel::insert_into(pending_crates::table)
+            .values((
+                pending_crates::crate_id.eq(krate.id),
+                pending_crates::index_entry.eq(serde_json::to_value(index_entry)?),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Makes the crate public and adds its first version to the index.
+    pub fn approve(&self, conn: &PgConnection) -> CargoResult<()> {
+        let index_entry = serde_json::from_value::<git::Crate>(self.index_entry.clone())?;
+        diesel::update(crates::table.find(self.crate_id))
+            .set(crates::visibility.eq(Visibility::Public))
+            .execute(conn)?;
+        diesel::delete(self).execute(conn)?;
+        git::add_crate(index_entry, telemetry::current())
+            .enqueue(conn)
+            .map_err(|e| CargoError::from_std_error(e))?;
+        Ok(())
+    }
+
+    pub fn encodable(
+        self,
+        name: String,
+        version: String,
+        publisher: Option<String>,
+    ) -> EncodablePendingCrate {
+        EncodablePendingCrate {
+            name,
+            version,
+            publisher,
+            created_at: self.created_at,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::{Crate, OwnerKind};
+use crate::schema::{crate_owners, pending_owner_removals, users};
+use crate::views::EncodablePendingOwnerRemoval;
+
+/// Crates with more individual owners than this need removals of owners to be
+/// confirmed by a second owner. For crates with fewer owners, removals take
+/// effect after `Config::owner_removal_delay` instead.
+pub const CONFIRMATION_THRESHOLD: usize = 2;
+
+/// The removal of an individual owner of a crate by another owner, see
+/// `controllers::krate::owners`. Until it takes effect, any owner of the crate
+/// can cancel it, and any owner but the one who requested it can confirm it.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+pub struct PendingOwnerRemoval {
+    pub id: i32,
+    pub crate_id: i32,
+    pub owner_id: i32,
+    pub requested_by: i32,
+    pub requested_at: NaiveDateTime,
+    /// When the removal takes effect unless it is cancelled, `None` if it
+    /// waits for another owner to confirm it
+    pub effective_at: Option<NaiveDateTime>,
+}
+
+impl PendingOwnerRemoval {
+    /// Records the removal of an owner, or returns `None` if the removal of
+    /// that owner is already pending.
+    pub fn request(
+        conn: &PgConnection,
+        crate_id: i32,
+        owner_id: i32,
+        requested_by: i32,
+        effective_at: Option<NaiveDateTime>,
+    ) -> QueryResult<Option<PendingOwnerRemoval>> {
+        diesel::insert_into(pending_owner_removals::table)
+            .values((
+                pending_owner_removals::crate_id.eq(crate_id),
+                pending_owner_removals::owner_id.eq(owner_id),
+                pending_owner_removals::requested_by.eq(requested_by),
+                pending_owner_removals::effective_at.eq(effective_at),
+            ))
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()
+    }
+
+    /// The removals whose delay has passed at `now`.
+    pub fn due(conn: &PgConnection, now: NaiveDateTime) -> QueryResult<Vec<PendingOwnerRemoval>> {
+        pending_owner_removals::table
+            .filter(pending_owner_removals::effective_at.le(now))
+            .order(pending_owner_removals::effective_at)
+            .load(conn)
+    }
+
+    /// Whether the crate would still have individual owners once the removal
+    /// takes effect.
+    pub fn leaves_owners(&self, conn: &PgConnection) -> QueryResult<bool> {
+        let remaining = crate_owners::table
+            .filter(crate_owners::crate_id.eq(self.crate_id))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+            .filter(crate_owners::owner_id.ne(self.owner_id))
+            .filter(crate_owners::deleted.eq(false))
+            .count()
+            .get_result::<i64>(conn)?;
+        Ok(remaining > 0)
+    }
+
+    /// Removes the owner from the crate.
+    pub fn apply(&self, conn: &PgConnection) -> QueryResult<()> {
+        let target =
+            crate_owners::table.find((self.crate_id, self.owner_id, OwnerKind::User as i32));
+        diesel::update(target)
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)?;
+        self.cancel(conn)
+    }
+
+    pub fn cancel(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(conn)?;
+        Ok(())
+    }
+
+    /// The logins of the owner being removed and of the owner who requested
+    /// the removal.
+    pub fn logins(&self, conn: &PgConnection) -> QueryResult<(String, String)> {
+        let login = |id| users::table.find(id).select(users::gh_login).first(conn);
+        Ok((login(self.owner_id)?, login(self.requested_by)?))
+    }
+
+    pub fn encodable(self, owner: String, requested_by: String) -> EncodablePendingOwnerRemoval {
+        EncodablePendingOwnerRemoval {
+            id: self.id,
+            owner,
+            requested_by,
+            requested_at: self.requested_at,
+            effective_at: self.effective_at,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use diesel::prelude::*;
+
+use crate::git;
+use crate::models::Crate;
+use crate::schema::private_index_entries;
+use crate::util::CargoResult;
+
+/// The index entry of a version of a private crate. Private crates are left
+/// out of the index, which anyone can clone, and their index files are only
+/// served by `controllers::krate::access::index_file` to the users who can
+/// see them. The entries are moved between the index and this table when the
+/// visibility of the crate changes, see `git::sync_index_visibility`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+pub struct PrivateIndexEntry {
+    pub id: i32,
+    pub crate_id: i32,
+    /// The name the version was published under, see `CrateRename`
+    pub name: String,
+    pub num: String,
+    /// The `git::Crate` of the version
+    pub entry: serde_json::Value,
+}
+
+impl PrivateIndexEntry {
+    /// Stores the index entry of a version of the private crate with the
+    /// given id, unless there already is one.
+    pub fn insert(conn: &PgConnection, crate_id: i32, entry: &git::Crate) -> CargoResult<()> {
+        diesel::insert_into(private_index_entries::table)
+            .values((
+                private_index_entries::crate_id.eq(crate_id),
+                private_index_entries::name.eq(&entry.name),
+                private_index_entries::num.eq(&entry.vers),
+                private_index_entries::entry.eq(serde_json::to_value(entry)?),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Changes the index entry of a version of the private crate with the
+    /// given id, if there is one.
+    pub fn update<F>(conn: &PgConnection, crate_id: i32, num: &str, f: F) -> CargoResult<()>
+    where
+        F: FnOnce(&mut git::Crate),
+    {
+        let row = private_index_entries::table
+            .filter(private_index_entries::crate_id.eq(crate_id))
+            .filter(private_index_entries::num.eq(num))
+            .first::<PrivateIndexEntry>(conn)
+            .optional()?;
+        if let Some(row) = row {
+            let mut entry = serde_json::from_value::<git::Crate>(row.entry.clone())?;
+            f(&mut entry);
+            diesel::update(&row)
+                .set(private_index_entries::entry.eq(serde_json::to_value(&entry)?))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the index entry of a version of the private crate with the
+    /// given id.
+    pub fn delete(conn: &PgConnection, crate_id: i32, num: &str) -> QueryResult<()> {
+        diesel::delete(
+            private_index_entries::table
+                .filter(private_index_entries::crate_id.eq(crate_id))
+                .filter(private_index_entries::num.eq(num)),
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+
+    /// All index entries of the crate, in the order they were added.
+    pub fn of_crate(conn: &PgConnection, crate_id: i32) -> QueryResult<Vec<PrivateIndexEntry>> {
+        private_index_entries::table
+            .filter(private_index_entries::crate_id.eq(crate_id))
+            .order(private_index_entries::id)
+            .load(conn)
+    }
+
+    /// The content of the index file of the crate, which only has the
+    /// versions published under its current name, like in the index.
+    pub fn index_file(conn: &PgConnection, krate: &Crate) -> CargoResult<String> {
+        let mut content = String::new();
+        for row in Self::of_crate(conn, krate.id)? {
+            if row.name == krate.name {
+                let entry = serde_json::from_value::<git::Crate>(row.entry)?;
+                content.push_str(&serde_json::to_string(&entry)?);
+                content.push('\n');
+            }
+        }
+        Ok(content)
+    }
+}
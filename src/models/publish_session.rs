@@ -0,0 +1,145 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use std::io::Write;
+
+use crate::models::User;
+use crate::schema::{publish_session_chunks, publish_sessions};
+use crate::util::CargoResult;
+use crate::views::EncodablePublishSession;
+
+/// How long a session is kept after its last chunk was received.
+pub const SESSION_LIFETIME_HOURS: i32 = 24;
+
+/// A publish uploaded in chunks, see `controllers::krate::publish_session`.
+///
+/// The chunks are stored in `publish_session_chunks` until the session is
+/// finished, and are appended in order, so `received` is also the offset of
+/// the next chunk.
+#[derive(Queryable, Identifiable, Associations, Clone, Debug)]
+#[belongs_to(User)]
+#[table_name = "publish_sessions"]
+pub struct PublishSession {
+    pub id: i32,
+    pub user_id: i32,
+    /// The length of the whole publish request body
+    pub length: i32,
+    pub received: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// The crate the session was started for, which is the only one it can
+    /// publish
+    pub crate_name: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "publish_sessions"]
+pub struct NewPublishSession<'a> {
+    pub user_id: i32,
+    pub length: i32,
+    pub crate_name: &'a str,
+}
+
+impl NewPublishSession<'_> {
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<PublishSession> {
+        diesel::insert_into(publish_sessions::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl PublishSession {
+    /// Finds a session of the given user.
+    pub fn find(conn: &PgConnection, id: i32, user_id: i32) -> QueryResult<PublishSession> {
+        publish_sessions::table
+            .find(id)
+            .filter(publish_sessions::user_id.eq(user_id))
+            .first(conn)
+    }
+
+    /// Returns the number of sessions the user has started that haven't been
+    /// finished or deleted yet.
+    pub fn open_count(conn: &PgConnection, user_id: i32) -> QueryResult<i64> {
+        publish_sessions::table
+            .filter(publish_sessions::user_id.eq(user_id))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Stores a chunk at `position`, and returns the updated session.
+    ///
+    /// Returns `None` if the chunk doesn't start where the data received so
+    /// far ends, which is also the case if another request stored a chunk in
+    /// the meantime.
+    pub fn append_chunk(
+        &self,
+        conn: &PgConnection,
+        position: i32,
+        data: &[u8],
+    ) -> QueryResult<Option<PublishSession>> {
+        conn.transaction(|| {
+            let session = publish_sessions::table
+                .find(self.id)
+                .filter(publish_sessions::received.eq(position));
+            let session = diesel::update(session)
+                .set(publish_sessions::received.eq(position + data.len() as i32))
+                .get_result::<PublishSession>(conn)
+                .optional()?;
+            if session.is_some() {
+                diesel::insert_into(publish_session_chunks::table)
+                    .values((
+                        publish_session_chunks::session_id.eq(self.id),
+                        publish_session_chunks::position.eq(position),
+                        publish_session_chunks::data.eq(data),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(session)
+        })
+    }
+
+    /// Writes the received chunks to `out`, loading them one at a time.
+    pub fn write_body(&self, conn: &PgConnection, out: &mut dyn Write) -> CargoResult<()> {
+        let positions = publish_session_chunks::table
+            .filter(publish_session_chunks::session_id.eq(self.id))
+            .select(publish_session_chunks::position)
+            .order(publish_session_chunks::position)
+            .load::<i32>(conn)?;
+        for position in positions {
+            let data = publish_session_chunks::table
+                .find((self.id, position))
+                .select(publish_session_chunks::data)
+                .first::<Vec<u8>>(conn)?;
+            out.write_all(&data)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the session along with its chunks.
+    pub fn delete(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(conn)?;
+        Ok(())
+    }
+
+    /// Deletes the sessions that haven't received a chunk in
+    /// `SESSION_LIFETIME_HOURS`, and returns how many were deleted.
+    pub fn delete_stale(conn: &PgConnection) -> QueryResult<usize> {
+        use diesel::dsl::*;
+
+        diesel::delete(
+            publish_sessions::table
+                .filter(publish_sessions::updated_at.lt(now - SESSION_LIFETIME_HOURS.hours())),
+        )
+        .execute(conn)
+    }
+
+    pub fn encodable(self) -> EncodablePublishSession {
+        EncodablePublishSession {
+            id: self.id,
+            crate_name: self.crate_name,
+            length: self.length,
+            received: self.received,
+            created_at: self.created_at,
+            expires_at: self.updated_at + Duration::hours(i64::from(SESSION_LIFETIME_HOURS)),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+-- Renames a category and its subcategories, and redirects their old slugs
+-- $1 and $2 are the old and new slug, $3 and $4 the old and new name
+WITH renamed AS (
+  UPDATE categories
+  SET slug = $2 || substr(categories.slug, length($1) + 1),
+      category = $4 || substr(categories.category, length($3) + 1)
+  FROM (
+    SELECT id, slug AS old_slug
+    FROM categories
+    WHERE slug = $1 OR slug LIKE $1 || '::%'
+  ) old
+  WHERE categories.id = old.id
+  RETURNING categories.id, old.old_slug
+)
+INSERT INTO category_redirects (slug, category_id)
+SELECT old_slug, id FROM renamed
+ON CONFLICT (slug) DO UPDATE
+SET category_id = EXCLUDED.category_id, created_at = CURRENT_TIMESTAMP
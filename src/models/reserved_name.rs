@@ -0,0 +1,167 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use regex::Regex;
+use std::io::Write;
+
+use crate::models::krate::{canon_crate_name, canonical_name};
+use crate::schema::{reserved_crate_name_patterns, reserved_crate_names};
+use crate::util::{CargoResult, ErrorCode};
+use crate::views::EncodableReservedNamePattern;
+
+/// A rule reserving every crate name that matches it, in addition to the
+/// exact names in the `reserved_crate_names` table.
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "reserved_crate_name_patterns"]
+pub struct ReservedNamePattern {
+    pub id: i32,
+    pub kind: ReservedNamePatternKind,
+    pub pattern: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum ReservedNamePatternKind {
+    /// Reserves all names starting with the pattern
+    Prefix = 0,
+    /// Reserves all names matching the regular expression somewhere. Use `^`
+    /// and `$` to match the whole name.
+    Regex = 1,
+    // if you add a kind here, be sure to update `from_sql` below.
+}
+
+impl ReservedNamePattern {
+    /// Validates a new pattern, and returns it in the form it is stored in.
+    ///
+    /// Patterns are matched against canonical crate names, so prefixes are
+    /// canonicalized the same way.
+    pub fn normalize(kind: ReservedNamePatternKind, pattern: &str) -> CargoResult<String> {
+        if pattern.is_empty() {
+            return Err(ErrorCode::InvalidField.human("the pattern must not be empty"));
+        }
+        match kind {
+            ReservedNamePatternKind::Prefix => Ok(canonical_name(pattern)),
+            ReservedNamePatternKind::Regex => {
+                Regex::new(pattern).map_err(|e| {
+                    ErrorCode::InvalidField
+                        .human(&format_args!("invalid regular expression: {}", e))
+                })?;
+                Ok(pattern.to_string())
+            }
+        }
+    }
+
+    pub fn create(
+        conn: &PgConnection,
+        kind: ReservedNamePatternKind,
+        pattern: &str,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(reserved_crate_name_patterns::table)
+            .values((
+                reserved_crate_name_patterns::kind.eq(kind),
+                reserved_crate_name_patterns::pattern.eq(pattern),
+            ))
+            .get_result(conn)
+    }
+
+    /// Whether the canonical crate name matches this pattern. Patterns that
+    /// fail to compile never match.
+    fn matches(&self, name: &str) -> bool {
+        match self.kind {
+            ReservedNamePatternKind::Prefix => name.starts_with(&self.pattern),
+            ReservedNamePatternKind::Regex => Regex::new(&self.pattern)
+                .map(|regex| regex.is_match(name))
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn encodable(self) -> EncodableReservedNamePattern {
+        EncodableReservedNamePattern {
+            id: self.id,
+            kind: self.kind,
+            pattern: self.pattern,
+        }
+    }
+}
+
+/// Whether `name` is reserved, either exactly or through a pattern.
+pub fn is_reserved(conn: &PgConnection, name: &str) -> QueryResult<bool> {
+    use diesel::dsl::exists;
+
+    let reserved_name = diesel::select(exists(
+        reserved_crate_names::table
+            .filter(canon_crate_name(reserved_crate_names::name).eq(canon_crate_name(name))),
+    ))
+    .get_result::<bool>(conn)?;
+
+    let canonical = canonical_name(name);
+    let reserved_pattern = reserved_crate_name_patterns::table
+        .load::<ReservedNamePattern>(conn)?
+        .iter()
+        .any(|pattern| pattern.matches(&canonical));
+
+    Ok(reserved_name || reserved_pattern)
+}
+
+/// Returns an error if `name` is reserved. This is only checked for new
+/// crates, since existing crates keep their names when a matching pattern is
+/// added.
+pub fn ensure_name_not_reserved(conn: &PgConnection, name: &str) -> CargoResult<()> {
+    if is_reserved(conn, name)? {
+        Err(ErrorCode::ReservedName.human("cannot upload a crate with a reserved name"))
+    } else {
+        Ok(())
+    }
+}
+
+impl FromSql<Integer, Pg> for ReservedNamePatternKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(ReservedNamePatternKind::Prefix),
+            1 => Ok(ReservedNamePatternKind::Regex),
+            n => Err(format!("unknown reserved name pattern kind: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for ReservedNamePatternKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(kind: ReservedNamePatternKind, pattern: &str) -> ReservedNamePattern {
+        ReservedNamePattern {
+            id: 1,
+            kind,
+            pattern: ReservedNamePattern::normalize(kind, pattern).unwrap(),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn prefixes_match_canonical_names() {
+        let prefix = pattern(ReservedNamePatternKind::Prefix, "Rustc-");
+        assert!(prefix.matches(&canonical_name("rustc_ap_syntax")));
+        assert!(prefix.matches(&canonical_name("RUSTC-driver")));
+        assert!(!prefix.matches(&canonical_name("rustc")));
+    }
+
+    #[test]
+    fn regexes() {
+        let regex = pattern(ReservedNamePatternKind::Regex, "^std_[a-z]+$");
+        assert!(regex.matches(&canonical_name("std-io")));
+        assert!(!regex.matches(&canonical_name("std_io2")));
+        assert!(ReservedNamePattern::normalize(ReservedNamePatternKind::Regex, "std_(").is_err());
+    }
+}
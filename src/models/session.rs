@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::sessions;
+use crate::views::EncodableSession;
+
+/// How often the use of a session is recorded, so that every request made
+/// with it doesn't write to the database.
+const LAST_USED_UPDATE_MINUTES: i32 = 5;
+
+/// A browser session of a user, see `middleware::current_user`. The session
+/// cookie only holds the token of the session, so that users can list where
+/// they are logged in and revoke sessions, see `controllers::user::session`.
+#[derive(Clone, Debug, Queryable, Identifiable, Associations)]
+#[belongs_to(User)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub token: String,
+    /// The IP address the user logged in from
+    pub ip: Option<String>,
+    /// The user agent the user logged in with
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: NaiveDateTime,
+    pub revoked: bool,
+}
+
+impl Session {
+    /// Starts a new session for a user who just logged in.
+    pub fn create(
+        conn: &PgConnection,
+        user_id: i32,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> QueryResult<Session> {
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::user_id.eq(user_id),
+                sessions::ip.eq(ip),
+                sessions::user_agent.eq(user_agent),
+            ))
+            .get_result(conn)
+    }
+
+    /// Queries the database for an unrevoked session with a certain `token`,
+    /// and records that it was used if that wasn't recorded in the last
+    /// `LAST_USED_UPDATE_MINUTES`.
+    pub fn find_by_token(conn: &PgConnection, token: &str) -> QueryResult<Session> {
+        let session = sessions::table
+            .filter(sessions::token.eq(token))
+            .filter(sessions::revoked.eq(false))
+            .first::<Session>(conn)?;
+
+        // If the database is in read only mode, we can't update last_used_at.
+        // Try updating in a new transaction, and ignore it if that fails
+        let _ = conn.transaction(|| {
+            diesel::update(
+                sessions::table
+                    .find(session.id)
+                    .filter(sessions::last_used_at.lt(now - LAST_USED_UPDATE_MINUTES.minutes())),
+            )
+            .set(sessions::last_used_at.eq(now))
+            .execute(conn)
+        });
+        Ok(session)
+    }
+
+    /// The unrevoked sessions of a user, most recently used first.
+    pub fn for_user(conn: &PgConnection, user_id: i32) -> QueryResult<Vec<Session>> {
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::revoked.eq(false))
+            .order(sessions::last_used_at.desc())
+            .load(conn)
+    }
+
+    /// Revokes a session of a user, and returns whether it was found.
+    pub fn revoke(conn: &PgConnection, user_id: i32, id: i32) -> QueryResult<bool> {
+        let revoked = diesel::update(
+            sessions::table
+                .find(id)
+                .filter(sessions::user_id.eq(user_id))
+                .filter(sessions::revoked.eq(false)),
+        )
+        .set(sessions::revoked.eq(true))
+        .execute(conn)?;
+        Ok(revoked > 0)
+    }
+
+    /// `current` marks the session the request was made with.
+    pub fn encodable(self, current: bool) -> EncodableSession {
+        EncodableSession {
+            id: self.id,
+            ip: self.ip,
+            user_agent: self.user_agent,
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+            current,
+        }
+    }
+}
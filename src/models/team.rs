@@ -1,12 +1,13 @@
 use diesel::prelude::*;
 
 use crate::app::App;
-use crate::github::{github_api, team_url};
-use crate::util::{errors::NotFound, human, CargoResult};
+use crate::github::{github_api, github_api_with_client, team_url};
+use crate::identity;
+use crate::util::{errors::NotFound, human, CargoResult, ErrorCode};
 
 use oauth2::{prelude::*, AccessToken};
 
-use crate::models::{Crate, CrateOwner, Owner, OwnerKind, User};
+use crate::models::{Crate, CrateOwner, Owner, OwnerKind, TeamMembership, User};
 use crate::schema::{crate_owners, teams};
 use crate::views::EncodableTeam;
 
@@ -74,6 +75,10 @@ impl Team {
         login: &str,
         req_user: &User,
     ) -> CargoResult<Self> {
+        if req_user.provider != identity::GITHUB {
+            return Err(human("only GitHub users can add GitHub teams as owners"));
+        }
+
         // must look like system:xxxxxxx
         let mut chunks = login.split(':');
         match chunks.next().unwrap() {
@@ -82,7 +87,7 @@ impl Team {
                 // Ok to unwrap since we know one ":" is contained
                 let org = chunks.next().unwrap();
                 let team = chunks.next().ok_or_else(|| {
-                    human(
+                    ErrorCode::InvalidField.human(
                         "missing github team argument; \
                          format is github:org:team",
                     )
@@ -96,7 +101,7 @@ impl Team {
                     req_user,
                 )
             }
-            _ => Err(human(
+            _ => Err(ErrorCode::InvalidField.human(
                 "unknown organization handler, \
                  only 'github:org:team' is supported",
             )),
@@ -126,7 +131,7 @@ impl Team {
         }
 
         if let Some(c) = org_name.chars().find(|c| whitelist(*c)) {
-            return Err(human(&format_args!(
+            return Err(ErrorCode::InvalidField.human(&format_args!(
                 "organization cannot contain special \
                  characters like {}",
                 c
@@ -150,14 +155,15 @@ impl Team {
             .into_iter()
             .find(|team| team.slug.to_lowercase() == team_name.to_lowercase())
             .ok_or_else(|| {
-                human(&format_args!(
+                ErrorCode::NotFound.human(&format_args!(
                     "could not find the github team {}/{}",
                     org_name, team_name
                 ))
             })?;
 
         if !team_with_gh_id_contains_user(app, team.id, req_user)? {
-            return Err(human("only members of a team can add it as an owner"));
+            return Err(ErrorCode::TeamMembershipRequired
+                .human("only members of a team can add it as an owner"));
         }
 
         #[derive(Deserialize)]
@@ -177,8 +183,25 @@ impl Team {
     /// Note that we're assuming that the given user is the one interested in
     /// the answer. If this is not the case, then we could accidentally leak
     /// private membership information here.
-    pub fn contains_user(&self, app: &App, user: &User) -> CargoResult<bool> {
-        team_with_gh_id_contains_user(app, self.github_id, user)
+    ///
+    /// The answer is cached for `Config::team_membership_ttl`.
+    pub fn contains_user(&self, app: &App, conn: &PgConnection, user: &User) -> CargoResult<bool> {
+        // Users of other identity providers aren't members of GitHub teams
+        if user.provider != identity::GITHUB {
+            return Ok(false);
+        }
+        if let Some(is_member) = TeamMembership::cached(conn, self.id, user.id)? {
+            return Ok(is_member);
+        }
+        let is_member = team_with_gh_id_contains_user(app, self.github_id, user)?;
+        TeamMembership::record(
+            conn,
+            self.id,
+            user.id,
+            is_member,
+            app.config.team_membership_ttl,
+        )?;
+        Ok(is_member)
     }
 
     pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
@@ -215,6 +238,17 @@ impl Team {
 }
 
 fn team_with_gh_id_contains_user(app: &App, github_id: i32, user: &User) -> CargoResult<bool> {
+    github_team_contains_user(app.http_client(), &app.config.api_protocol, github_id, user)
+}
+
+/// Like `team_with_gh_id_contains_user`, for background jobs, which don't
+/// have an `App`.
+pub fn github_team_contains_user(
+    client: &reqwest::Client,
+    api_protocol: &str,
+    github_id: i32,
+    user: &User,
+) -> CargoResult<bool> {
     // GET teams/:team_id/memberships/:user_name
     // check that "state": "active"
 
@@ -225,7 +259,8 @@ fn team_with_gh_id_contains_user(app: &App, github_id: i32, user: &User) -> Carg
 
     let url = format!("/teams/{}/memberships/{}", &github_id, &user.gh_login);
     let token = AccessToken::new(user.gh_access_token.clone());
-    let membership = match github_api::<Membership>(app, &url, &token) {
+    let response = github_api_with_client::<Membership>(client, api_protocol, &url, &token);
+    let membership = match response {
         // Officially how `false` is returned
         Err(ref e) if e.is::<NotFound>() => return Ok(false),
         x => x?,
@@ -0,0 +1,113 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::time;
+
+use crate::models::{OwnerKind, Team, User};
+use crate::schema::{crate_owners, team_memberships, teams, users};
+
+/// Whether a user is a member of a GitHub team, as GitHub answered at
+/// `checked_at`. See `Team::contains_user`, and the `sync_team_memberships`
+/// job, which checks memberships again before they expire.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[primary_key(team_id, user_id)]
+#[belongs_to(Team)]
+#[belongs_to(User)]
+pub struct TeamMembership {
+    pub team_id: i32,
+    pub user_id: i32,
+    pub is_member: bool,
+    pub checked_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl TeamMembership {
+    /// Returns whether the user is a member of the team, if that was checked
+    /// recently enough.
+    pub fn cached(conn: &PgConnection, team_id: i32, user_id: i32) -> QueryResult<Option<bool>> {
+        team_memberships::table
+            .find((team_id, user_id))
+            .filter(team_memberships::expires_at.gt(Utc::now().naive_utc()))
+            .select(team_memberships::is_member)
+            .first(conn)
+            .optional()
+    }
+
+    /// Caches whether the user is a member of the team for `ttl`.
+    pub fn record(
+        conn: &PgConnection,
+        team_id: i32,
+        user_id: i32,
+        is_member: bool,
+        ttl: time::Duration,
+    ) -> QueryResult<()> {
+        let checked_at = Utc::now().naive_utc();
+        let expires_at = checked_at + Duration::from_std(ttl).expect("the TTL is out of range");
+        diesel::insert_into(team_memberships::table)
+            .values((
+                team_memberships::team_id.eq(team_id),
+                team_memberships::user_id.eq(user_id),
+                team_memberships::is_member.eq(is_member),
+                team_memberships::checked_at.eq(checked_at),
+                team_memberships::expires_at.eq(expires_at),
+            ))
+            .on_conflict((team_memberships::team_id, team_memberships::user_id))
+            .do_update()
+            .set((
+                team_memberships::is_member.eq(is_member),
+                team_memberships::checked_at.eq(checked_at),
+                team_memberships::expires_at.eq(expires_at),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// How long the membership was cached for when it was last checked.
+    pub fn ttl(&self) -> time::Duration {
+        (self.expires_at - self.checked_at)
+            .to_std()
+            .unwrap_or_else(|_| time::Duration::from_secs(0))
+    }
+
+    /// The memberships that expire before `before`, along with the GitHub ID
+    /// of their team and their user, for teams that still own crates.
+    pub fn expiring(
+        conn: &PgConnection,
+        before: NaiveDateTime,
+    ) -> QueryResult<Vec<(TeamMembership, i32, User)>> {
+        team_memberships::table
+            .inner_join(teams::table)
+            .inner_join(users::table)
+            .filter(team_memberships::expires_at.gt(Utc::now().naive_utc()))
+            .filter(team_memberships::expires_at.lt(before))
+            .filter(
+                team_memberships::team_id.eq_any(
+                    crate_owners::table
+                        .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+                        .filter(crate_owners::deleted.eq(false))
+                        .select(crate_owners::owner_id),
+                ),
+            )
+            .select((
+                team_memberships::all_columns,
+                teams::github_id,
+                users::all_columns,
+            ))
+            .load(conn)
+    }
+
+    /// Deletes the memberships that have expired, and returns how many there
+    /// were.
+    pub fn delete_expired(conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(
+            team_memberships::table.filter(team_memberships::expires_at.le(Utc::now().naive_utc())),
+        )
+        .execute(conn)
+    }
+
+    /// Deletes the cached memberships of a team, so that they are checked
+    /// with GitHub again the next time they are needed.
+    pub fn expire_team(conn: &PgConnection, team_id: i32) -> QueryResult<usize> {
+        diesel::delete(team_memberships::table.filter(team_memberships::team_id.eq(team_id)))
+            .execute(conn)
+    }
+}
@@ -1,10 +1,10 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 
 use crate::models::User;
-use crate::schema::api_tokens;
+use crate::schema::{api_token_usage, api_tokens};
 use crate::util::rfc3339;
-use crate::views::EncodableApiTokenWithToken;
+use crate::views::{EncodableApiTokenUsage, EncodableApiTokenWithToken};
 
 /// The model representing a row in the `api_tokens` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
@@ -22,13 +22,44 @@ pub struct ApiToken {
     pub last_used_at: Option<NaiveDateTime>,
     #[serde(skip)]
     pub revoked: bool,
+    /// The IP address the token was last used from
+    pub last_used_ip: Option<String>,
+    /// The user agent the token was last used with
+    pub last_used_user_agent: Option<String>,
+    /// When the owner was told that the token wasn't used for a long time,
+    /// see the `notify_unused_tokens` job
+    #[serde(skip)]
+    pub unused_notified_at: Option<NaiveDateTime>,
+    /// How many requests the token can make per day, if it's limited
+    pub daily_quota: Option<i32>,
+    /// The IP addresses and CIDR ranges the token can be used from, any if
+    /// empty
+    pub allowed_ips: Vec<String>,
 }
 
 impl ApiToken {
     /// Generates a new named API token for a user
     pub fn insert(conn: &PgConnection, user_id: i32, name: &str) -> QueryResult<ApiToken> {
+        Self::insert_restricted(conn, user_id, name, None, &[])
+    }
+
+    /// Generates a new named API token for a user that can make at most
+    /// `daily_quota` requests per day, and can only be used from the
+    /// `allowed_ips` if any are given
+    pub fn insert_restricted(
+        conn: &PgConnection,
+        user_id: i32,
+        name: &str,
+        daily_quota: Option<i32>,
+        allowed_ips: &[String],
+    ) -> QueryResult<ApiToken> {
         diesel::insert_into(api_tokens::table)
-            .values((api_tokens::user_id.eq(user_id), api_tokens::name.eq(name)))
+            .values((
+                api_tokens::user_id.eq(user_id),
+                api_tokens::name.eq(name),
+                api_tokens::daily_quota.eq(daily_quota),
+                api_tokens::allowed_ips.eq(allowed_ips),
+            ))
             .get_result::<ApiToken>(conn)
     }
 
@@ -44,6 +75,41 @@ impl ApiToken {
             revoked: self.revoked,
             created_at: self.created_at,
             last_used_at: self.last_used_at,
+            daily_quota: self.daily_quota,
+            allowed_ips: self.allowed_ips,
+        }
+    }
+}
+
+/// The number of requests made with an API token on a day, see the
+/// `token_usage` middleware.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations)]
+#[belongs_to(ApiToken)]
+#[table_name = "api_token_usage"]
+#[primary_key(api_token_id, date)]
+pub struct ApiTokenUsage {
+    pub api_token_id: i32,
+    pub date: NaiveDate,
+    pub requests: i32,
+}
+
+impl ApiTokenUsage {
+    /// Counts a request made with the token today, and returns the number of
+    /// requests made with it today.
+    pub fn record_request(conn: &PgConnection, api_token_id: i32) -> QueryResult<i32> {
+        diesel::insert_into(api_token_usage::table)
+            .values(api_token_usage::api_token_id.eq(api_token_id))
+            .on_conflict((api_token_usage::api_token_id, api_token_usage::date))
+            .do_update()
+            .set(api_token_usage::requests.eq(api_token_usage::requests + 1))
+            .returning(api_token_usage::requests)
+            .get_result(conn)
+    }
+
+    pub fn encodable(self) -> EncodableApiTokenUsage {
+        EncodableApiTokenUsage {
+            date: self.date.to_string(),
+            requests: self.requests,
         }
     }
 }
@@ -64,6 +130,11 @@ mod tests {
             name: "".to_string(),
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            last_used_ip: None,
+            last_used_user_agent: None,
+            unused_notified_at: None,
+            daily_quota: None,
+            allowed_ips: Vec::new(),
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert!(json
@@ -85,6 +156,8 @@ mod tests {
             revoked: false,
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            daily_quota: None,
+            allowed_ips: Vec::new(),
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert!(json
@@ -1,14 +1,19 @@
-use diesel::dsl::now;
+use chrono::NaiveDateTime;
+use diesel::dsl::{now, IntervalDsl};
 use diesel::prelude::*;
 use std::borrow::Cow;
 
 use crate::app::App;
-use crate::util::CargoResult;
+use crate::util::{cidr, CargoResult};
 
 use crate::models::{Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, Rights};
 use crate::schema::{crate_owners, emails, users};
 use crate::views::{EncodablePrivateUser, EncodablePublicUser};
 
+/// How often the use of an API token is recorded, so that every request made
+/// with it doesn't write to the database.
+const TOKEN_LAST_USED_UPDATE_MINUTES: i32 = 5;
+
 /// The model representing a row in the `users` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, AsChangeset, Associations)]
 pub struct User {
@@ -19,6 +24,15 @@ pub struct User {
     pub name: Option<String>,
     pub gh_avatar: Option<String>,
     pub gh_id: i32,
+    /// Admins can moderate crates and users through the `/admin` endpoints.
+    pub is_admin: bool,
+    /// Banned users can't log in or use their API tokens.
+    pub is_banned: bool,
+    /// The identity provider the user logs in with, see `identity`
+    pub provider: String,
+    /// The ID of the user with their identity provider, `None` for GitHub
+    /// users since `gh_id` is used for them
+    pub provider_user_id: Option<String>,
 }
 
 #[derive(Insertable, Debug, Default)]
@@ -80,52 +94,124 @@ impl<'a> NewUser<'a> {
                 ))
                 .get_result::<User>(conn)?;
 
-            // To send the user an account verification email...
-            if let Some(user_email) = user.email.as_ref() {
-                let new_email = NewEmail {
-                    user_id: user.id,
-                    email: user_email,
-                };
-
-                let token = insert_into(emails::table)
-                    .values(&new_email)
-                    .on_conflict_do_nothing()
-                    .returning(emails::token)
-                    .get_result::<String>(conn)
-                    .optional()?;
-
-                if let Some(token) = token {
-                    crate::email::send_user_confirm_email(user_email, &user.gh_login, &token);
-                }
-            }
+            send_confirmation_email(conn, &user)?;
 
             Ok(user)
         })
     }
+
+    /// Inserts a user who logs in with an identity provider other than
+    /// GitHub into the database, or updates an existing one. The user is
+    /// identified by their ID with the provider, and `gh_login` has to be
+    /// qualified with the name of the provider, see `identity::qualified_login`.
+    pub fn create_or_update_for_provider(
+        &self,
+        conn: &PgConnection,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> QueryResult<User> {
+        use diesel::insert_into;
+        use diesel::pg::upsert::excluded;
+
+        conn.transaction(|| {
+            let user = insert_into(users::table)
+                .values((
+                    self,
+                    users::provider.eq(provider),
+                    users::provider_user_id.eq(provider_user_id),
+                ))
+                .on_conflict((users::provider, users::provider_user_id))
+                .do_update()
+                .set((
+                    users::gh_login.eq(excluded(users::gh_login)),
+                    users::name.eq(excluded(users::name)),
+                    users::gh_avatar.eq(excluded(users::gh_avatar)),
+                    users::gh_access_token.eq(excluded(users::gh_access_token)),
+                ))
+                .get_result::<User>(conn)?;
+
+            send_confirmation_email(conn, &user)?;
+
+            Ok(user)
+        })
+    }
+}
+
+/// Sends the user an account verification email, unless their email address
+/// was already recorded.
+fn send_confirmation_email(conn: &PgConnection, user: &User) -> QueryResult<()> {
+    if let Some(user_email) = user.email.as_ref() {
+        let new_email = NewEmail {
+            user_id: user.id,
+            email: user_email,
+        };
+
+        let token = diesel::insert_into(emails::table)
+            .values(&new_email)
+            .on_conflict_do_nothing()
+            .returning(emails::token)
+            .get_result::<String>(conn)
+            .optional()?;
+
+        if let Some(token) = token {
+            crate::email::send_user_confirm_email(conn, user_email, &user.gh_login, &token);
+        }
+    }
+    Ok(())
 }
 
 impl User {
-    /// Queries the database for a user with a certain `api_token` value.
-    pub fn find_by_api_token(conn: &PgConnection, token_: &str) -> QueryResult<User> {
-        use crate::schema::api_tokens::dsl::{api_tokens, last_used_at, revoked, token, user_id};
+    /// Queries the database for a user with a certain `api_token` value, and
+    /// records that the token was used from `ip` with `user_agent` if that
+    /// wasn't recorded in the last `TOKEN_LAST_USED_UPDATE_MINUTES`.
+    ///
+    /// Returns `None` without recording anything if the token can't be used
+    /// from `ip`, see `ApiToken::allowed_ips`.
+    pub fn find_by_api_token(
+        conn: &PgConnection,
+        token_: &str,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> QueryResult<Option<User>> {
+        use crate::schema::api_tokens::dsl::{
+            allowed_ips, api_tokens, last_used_at, last_used_ip, last_used_user_agent, revoked,
+            token, unused_notified_at,
+        };
         use diesel::update;
 
         let tokens = api_tokens
             .filter(token.eq(token_))
             .filter(revoked.eq(false));
 
+        let (user, allowed) = tokens
+            .inner_join(users::table)
+            .select((users::all_columns, allowed_ips))
+            .first::<(User, Vec<String>)>(conn)?;
+        if !allowed.is_empty() && !ip.map_or(false, |ip| cidr::is_allowed(&allowed, ip)) {
+            return Ok(None);
+        }
+
         // If the database is in read only mode, we can't update last_used_at.
-        // Try updating in a new transaction, if that fails, fall back to reading
-        let user_id_ = conn
-            .transaction(|| {
-                update(tokens)
-                    .set(last_used_at.eq(now.nullable()))
-                    .returning(user_id)
-                    .get_result::<i32>(conn)
-            })
-            .or_else(|_| tokens.select(user_id).first(conn))?;
-
-        users::table.find(user_id_).first(conn)
+        // Try updating in a new transaction, and ignore it if that fails
+        let _ = conn.transaction(|| {
+            let recorded_before = now - TOKEN_LAST_USED_UPDATE_MINUTES.minutes();
+            update(
+                tokens.filter(
+                    last_used_at
+                        .is_null()
+                        .or(last_used_at.lt(recorded_before.nullable())),
+                ),
+            )
+            .set((
+                last_used_at.eq(now.nullable()),
+                last_used_ip.eq(ip),
+                last_used_user_agent.eq(user_agent),
+                unused_notified_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(conn)
+        });
+
+        Ok(Some(user))
     }
 
     pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
@@ -148,7 +234,7 @@ impl User {
     /// `Publish` as well, but this is a non-obvious invariant so we don't bother.
     /// Sweet free optimization if teams are proving burdensome to check.
     /// More than one team isn't really expected, though.
-    pub fn rights(&self, app: &App, owners: &[Owner]) -> CargoResult<Rights> {
+    pub fn rights(&self, app: &App, conn: &PgConnection, owners: &[Owner]) -> CargoResult<Rights> {
         let mut best = Rights::None;
         for owner in owners {
             match *owner {
@@ -158,7 +244,7 @@ impl User {
                     }
                 }
                 Owner::Team(ref team) => {
-                    if team.contains_user(app, self)? {
+                    if team.contains_user(app, conn, self)? {
                         best = Rights::Publish;
                     }
                 }
@@ -3,9 +3,9 @@ use std::collections::HashMap;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 
-use crate::util::{human, CargoResult};
+use crate::util::{license, CargoResult, ErrorCode};
 
-use crate::models::{Crate, Dependency, User};
+use crate::models::{Crate, DeletedVersion, Dependency, User};
 use crate::schema::*;
 use crate::views::{EncodableVersion, EncodableVersionLinks};
 
@@ -24,6 +24,10 @@ pub struct Version {
     pub license: Option<String>,
     pub crate_size: Option<i32>,
     pub published_by: Option<i32>,
+    pub advisory: Option<String>,
+    pub reproducible: Option<bool>,
+    pub docs_built: Option<bool>,
+    pub rust_version: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -35,6 +39,7 @@ pub struct NewVersion {
     license: Option<String>,
     crate_size: Option<i32>,
     published_by: i32,
+    rust_version: Option<String>,
 }
 
 impl Version {
@@ -49,6 +54,10 @@ impl Version {
             yanked,
             license,
             crate_size,
+            advisory,
+            reproducible,
+            rust_version,
+            docs_built,
             ..
         } = self;
         let num = num.to_string();
@@ -71,6 +80,10 @@ impl Version {
             },
             crate_size,
             published_by: published_by.map(User::encodable_public),
+            advisory,
+            reproducible,
+            rust_version,
+            docs_built,
         }
     }
 
@@ -99,18 +112,43 @@ impl Version {
             })
     }
 
-    pub fn record_readme_rendering(version_id_: i32, conn: &PgConnection) -> QueryResult<usize> {
+    /// Returns the highest of `versions` that isn't a pre-release, if any.
+    pub fn max_stable<T>(versions: T) -> Option<semver::Version>
+    where
+        T: IntoIterator<Item = semver::Version>,
+    {
+        versions.into_iter().filter(|v| !v.is_prerelease()).max()
+    }
+
+    /// Records that the readme of a version was rendered. The rendered HTML is
+    /// stored when given, so that it can be served without going through the
+    /// uploader. Passing `None` clears any previously stored HTML.
+    pub fn record_readme_rendering(
+        version_id_: i32,
+        html_: Option<&str>,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
         use crate::schema::readme_renderings::dsl::*;
         use diesel::dsl::now;
 
         diesel::insert_into(readme_renderings)
-            .values(version_id.eq(version_id_))
+            .values((version_id.eq(version_id_), html.eq(html_)))
             .on_conflict(version_id)
             .do_update()
-            .set(rendered_at.eq(now))
+            .set((rendered_at.eq(now), html.eq(html_)))
             .execute(conn)
     }
 
+    /// Whether the string is a minimum supported Rust version in the
+    /// `major.minor` or `major.minor.patch` format.
+    pub fn valid_rust_version(rust_version: &str) -> bool {
+        let parts = rust_version.split('.').collect::<Vec<_>>();
+        (parts.len() == 2 || parts.len() == 3)
+            && parts
+                .iter()
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    }
+
     /// Gets the User who ran `cargo publish` for this version, if recorded.
     /// Not for use when you have a group of versions you need the publishers for.
     pub fn published_by(&self, conn: &PgConnection) -> Option<User> {
@@ -131,6 +169,7 @@ impl NewVersion {
         license_file: Option<&str>,
         crate_size: i32,
         published_by: i32,
+        rust_version: Option<String>,
     ) -> CargoResult<Self> {
         let features = serde_json::to_value(features)?;
 
@@ -141,9 +180,11 @@ impl NewVersion {
             license,
             crate_size: Some(crate_size),
             published_by,
+            rust_version,
         };
 
         new_version.validate_license(license_file)?;
+        new_version.validate_rust_version()?;
 
         Ok(new_version)
     }
@@ -164,12 +205,18 @@ impl NewVersion {
                 .filter(crate_id.eq(self.crate_id))
                 .filter(num.eq(&self.num));
             if select(exists(already_uploaded)).get_result(conn)? {
-                return Err(human(&format_args!(
+                return Err(ErrorCode::AlreadyExists.human(&format_args!(
                     "crate version `{}` is already \
                      uploaded",
                     self.num
                 )));
             }
+            if DeletedVersion::exists(conn, self.crate_id, &self.num)? {
+                return Err(ErrorCode::Conflict.human(&format_args!(
+                    "crate version `{}` was deleted and can't be published again",
+                    self.num
+                )));
+            }
 
             let version = insert_into(versions)
                 .values(self)
@@ -196,16 +243,15 @@ impl NewVersion {
 
     fn validate_license(&mut self, license_file: Option<&str>) -> CargoResult<()> {
         if let Some(ref license) = self.license {
-            for part in license.split('/') {
-                license_exprs::validate_license_expr(part).map_err(|e| {
-                    human(&format_args!(
-                        "{}; see http://opensource.org/licenses \
-                         for options, and http://spdx.org/licenses/ \
-                         for their identifiers",
-                        e
-                    ))
-                })?;
-            }
+            let normalized = license::normalize(license).map_err(|e| {
+                ErrorCode::InvalidField.human(&format_args!(
+                    "{}; see http://opensource.org/licenses \
+                     for options, and http://spdx.org/licenses/ \
+                     for their identifiers",
+                    e
+                ))
+            })?;
+            self.license = Some(normalized);
         } else if license_file.is_some() {
             // If no license is given, but a license file is given, flag this
             // crate as having a nonstandard license. Note that we don't
@@ -214,4 +260,16 @@ impl NewVersion {
         }
         Ok(())
     }
+
+    fn validate_rust_version(&self) -> CargoResult<()> {
+        match self.rust_version {
+            Some(ref rust_version) if !Version::valid_rust_version(rust_version) => {
+                Err(ErrorCode::InvalidField.human(&format_args!(
+                    "invalid rust_version `{}`, expected a version like `1.40` or `1.40.0`",
+                    rust_version
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
 }
@@ -0,0 +1,36 @@
+use diesel::prelude::*;
+
+use crate::models::Version;
+use crate::schema::version_checksums;
+use crate::views::EncodableVersionChecksums;
+
+/// The hex encoded checksums of the uploaded crate file of a version.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Version)]
+#[primary_key(version_id)]
+pub struct VersionChecksum {
+    pub version_id: i32,
+    pub sha256: String,
+    /// Computed by the `backfill_checksums` background job
+    pub sha512: Option<String>,
+}
+
+impl VersionChecksum {
+    /// Records the checksum of a newly published version.
+    pub fn insert(conn: &PgConnection, version_id: i32, sha256: &str) -> QueryResult<()> {
+        diesel::insert_into(version_checksums::table)
+            .values((
+                version_checksums::version_id.eq(version_id),
+                version_checksums::sha256.eq(sha256),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn encodable(self) -> EncodableVersionChecksums {
+        EncodableVersionChecksums {
+            sha256: Some(self.sha256),
+            sha512: self.sha512,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use diesel::prelude::*;
+
+use crate::models::Version;
+use crate::schema::version_files;
+use crate::uploaders::CrateFile;
+use crate::views::EncodableVersionFile;
+
+/// A file contained in the uploaded crate file of a version.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Version)]
+#[primary_key(version_id, path)]
+pub struct VersionFile {
+    pub version_id: i32,
+    pub path: String,
+    pub size: i64,
+}
+
+/// Postgres limits the number of bind parameters of a single query, so the
+/// files of large crates are inserted in chunks.
+const INSERT_CHUNK_SIZE: usize = 10_000;
+
+impl VersionFile {
+    /// Records the files of a newly published version.
+    pub fn insert(conn: &PgConnection, version_id: i32, files: &[CrateFile]) -> QueryResult<()> {
+        for chunk in files.chunks(INSERT_CHUNK_SIZE) {
+            let rows = chunk
+                .iter()
+                .map(|file| {
+                    (
+                        version_files::version_id.eq(version_id),
+                        version_files::path.eq(&file.path),
+                        version_files::size.eq(file.size as i64),
+                    )
+                })
+                .collect::<Vec<_>>();
+            // Tarballs can technically contain the same path twice
+            diesel::insert_into(version_files::table)
+                .values(&rows)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    pub fn encodable(self) -> EncodableVersionFile {
+        EncodableVersionFile {
+            path: self.path,
+            size: self.size,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+use crate::models::krate::validate_url;
+use crate::models::Version;
+use crate::schema::version_provenance;
+use crate::util::{CargoResult, ErrorCode};
+use crate::views::EncodableVersionProvenance;
+
+/// The longest accepted name of a CI provider.
+const MAX_PROVIDER_LENGTH: usize = 64;
+
+/// Where a version was published from, as stated by the CI run that
+/// published it, see `controllers::krate::publish`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Version)]
+#[primary_key(version_id)]
+#[table_name = "version_provenance"]
+pub struct VersionProvenance {
+    pub version_id: i32,
+    /// Like `github-actions` or `gitlab-ci`
+    pub provider: String,
+    pub run_url: Option<String>,
+    pub commit_sha: String,
+    /// Whether the commit exists in the repository of the crate, checked by
+    /// the `verify_provenance` job. `None` until checked, or when the
+    /// repository isn't hosted somewhere it can be checked.
+    pub verified: Option<bool>,
+    pub verified_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl VersionProvenance {
+    /// Checks the CI metadata sent along with a publish.
+    pub fn validate(provider: &str, run_url: Option<&str>, commit_sha: &str) -> CargoResult<()> {
+        let valid_provider = !provider.is_empty()
+            && provider.len() <= MAX_PROVIDER_LENGTH
+            && provider
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_provider {
+            return Err(ErrorCode::InvalidField.human(&format_args!(
+                "invalid CI provider `{}`, expected at most {} letters, digits, `-` or `_`",
+                provider, MAX_PROVIDER_LENGTH
+            )));
+        }
+        if commit_sha.len() != 40 || !commit_sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ErrorCode::InvalidField.human(&format_args!(
+                "invalid commit `{}`, expected a full 40 character hex SHA-1",
+                commit_sha
+            )));
+        }
+        validate_url(run_url, "ci_run_url")
+    }
+
+    /// Records the CI metadata of a newly published version.
+    pub fn insert(
+        conn: &PgConnection,
+        version_id: i32,
+        provider: &str,
+        run_url: Option<&str>,
+        commit_sha: &str,
+    ) -> QueryResult<()> {
+        diesel::insert_into(version_provenance::table)
+            .values((
+                version_provenance::version_id.eq(version_id),
+                version_provenance::provider.eq(provider),
+                version_provenance::run_url.eq(run_url),
+                version_provenance::commit_sha.eq(commit_sha.to_lowercase()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn set_verified(&self, conn: &PgConnection, verified: bool) -> QueryResult<()> {
+        diesel::update(self)
+            .set((
+                version_provenance::verified.eq(verified),
+                version_provenance::verified_at.eq(now.nullable()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn encodable(self) -> EncodableVersionProvenance {
+        EncodableVersionProvenance {
+            provider: self.provider,
+            run_url: self.run_url,
+            commit_sha: self.commit_sha,
+            verified: self.verified,
+            verified_at: self.verified_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn ci_metadata_is_validated() {
+        let run_url = Some("https://github.com/foo/bar/actions/runs/1");
+        assert!(VersionProvenance::validate("github-actions", run_url, SHA).is_ok());
+        assert!(VersionProvenance::validate("gitlab_ci", None, SHA).is_ok());
+
+        assert!(VersionProvenance::validate("", run_url, SHA).is_err());
+        assert!(VersionProvenance::validate("github actions", run_url, SHA).is_err());
+        assert!(VersionProvenance::validate("github-actions", run_url, "0123abc").is_err());
+        assert!(
+            VersionProvenance::validate("github-actions", run_url, &SHA.replace('0', "g")).is_err()
+        );
+        assert!(VersionProvenance::validate("github-actions", Some("ftp://foo"), SHA).is_err());
+    }
+}
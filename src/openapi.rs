@@ -0,0 +1,737 @@
+//! The OpenAPI 3 description of the `/api/v1` routes, served at
+//! `/api/openapi.json`.
+//!
+//! Every route of the API router is annotated with a summary in `OPERATIONS`.
+//! The document is generated from that list, and a test of the router checks
+//! that it contains exactly the routes that are registered, so adding a route
+//! without describing it fails the build.
+
+use serde_json::{Map, Value};
+
+use crate::util::ErrorCode;
+
+/// The method, path and summary of every route under `/api/v1`, with paths as
+/// they are registered with the router.
+pub const OPERATIONS: &[(&str, &str, &str)] = &[
+    // Crates
+    ("get", "/crates", "Search crates"),
+    ("put", "/crates/new", "Publish a new version of a crate"),
+    ("post", "/crates/new/session", "Start a resumable upload"),
+    (
+        "get",
+        "/crates/new/session/:session_id",
+        "Get the state of a resumable upload",
+    ),
+    (
+        "delete",
+        "/crates/new/session/:session_id",
+        "Abandon a resumable upload",
+    ),
+    (
+        "put",
+        "/crates/new/session/:session_id/chunk",
+        "Upload a chunk of a resumable upload",
+    ),
+    (
+        "post",
+        "/crates/new/session/:session_id/finish",
+        "Publish a resumable upload",
+    ),
+    ("get", "/crates/:crate_id", "Get a crate"),
+    (
+        "get",
+        "/crates/:crate_id/availability",
+        "Check whether a crate name is available",
+    ),
+    (
+        "patch",
+        "/crates/:crate_id",
+        "Update the metadata of a crate",
+    ),
+    ("delete", "/crates/:crate_id", "Delete a crate"),
+    (
+        "get",
+        "/crates/:crate_id/owners",
+        "List the owners of a crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/owners",
+        "Invite owners to a crate",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/owners",
+        "Remove owners of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/owner_team",
+        "List the teams owning a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/owner_user",
+        "List the users owning a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/owner_removals",
+        "List the pending owner removals of a crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/owner_removals/:removal_id/confirm",
+        "Confirm an owner removal",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/owner_removals/:removal_id",
+        "Cancel an owner removal",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/successor",
+        "Get the successor of a crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/successor",
+        "Designate the successor of a crate",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/successor",
+        "Remove the successor of a crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/repository_verification",
+        "Verify the repository of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/versions",
+        "List the versions of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/downloads",
+        "Get the recent downloads of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/downloads/summary",
+        "Summarize the downloads of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/downloads/clients",
+        "Get the downloads of a crate by client",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/version_adoption",
+        "Get the adoption of the versions of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/reverse_dependencies",
+        "List the crates depending on a crate",
+    ),
+    ("put", "/crates/:crate_id/follow", "Follow a crate"),
+    ("delete", "/crates/:crate_id/follow", "Unfollow a crate"),
+    (
+        "get",
+        "/crates/:crate_id/following",
+        "Check whether a crate is followed",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/access",
+        "List the users with access to a private crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/access",
+        "Grant users access to a private crate",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/access",
+        "Revoke the access of users to a private crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/visibility",
+        "Change the visibility of a crate",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/index",
+        "Get the index file of a crate visible to the user",
+    ),
+    (
+        "post",
+        "/crates/:crate_id/report",
+        "Report a crate to the administrators",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/claims",
+        "List the name claims for a crate",
+    ),
+    (
+        "post",
+        "/crates/:crate_id/claims",
+        "Claim the name of an abandoned crate",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/claims/:claim_id",
+        "Respond to a name claim",
+    ),
+    // Versions
+    ("get", "/crates/:crate_id/:version", "Get a version"),
+    (
+        "delete",
+        "/crates/:crate_id/:version",
+        "Delete a recently published version",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/:version/yank",
+        "Yank a version",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/:version/unyank",
+        "Unyank a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/download",
+        "Download a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/readme",
+        "Get the rendered readme of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/dependencies",
+        "List the dependencies of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/downloads",
+        "Get the recent downloads of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/authors",
+        "List the authors of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/dependency_graph",
+        "Get the dependency graph of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/files",
+        "List the files of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/checksums",
+        "Get the checksums of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/provenance",
+        "Get the CI provenance of a version",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/diff/:other_version",
+        "Compare two versions",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/:version/advisory",
+        "Set the advisory of a version",
+    ),
+    (
+        "delete",
+        "/crates/:crate_id/:version/advisory",
+        "Clear the advisory of a version",
+    ),
+    (
+        "put",
+        "/crates/:crate_id/:version/build_verification",
+        "Submit a build verification",
+    ),
+    (
+        "get",
+        "/crates/:crate_id/:version/build_verifications",
+        "List the build verifications of a version",
+    ),
+    ("get", "/versions", "List versions by id"),
+    ("get", "/versions/:version_id", "Get a version by id"),
+    ("get", "/summary", "Get the summary of the front page"),
+    // Keywords and categories
+    ("get", "/keywords", "List keywords"),
+    ("get", "/keywords/suggest", "Suggest keywords"),
+    ("get", "/keywords/:keyword_id", "Get a keyword"),
+    (
+        "get",
+        "/keywords/:keyword_id/top",
+        "List the top crates of a keyword",
+    ),
+    ("put", "/keywords/:keyword_id/follow", "Follow a keyword"),
+    (
+        "delete",
+        "/keywords/:keyword_id/follow",
+        "Unfollow a keyword",
+    ),
+    (
+        "get",
+        "/keywords/:keyword_id/following",
+        "Check whether a keyword is followed",
+    ),
+    ("get", "/categories", "List categories"),
+    ("get", "/categories/suggest", "Suggest categories"),
+    ("get", "/categories/:category_id", "Get a category"),
+    (
+        "get",
+        "/categories/:category_id/top",
+        "List the top crates of a category",
+    ),
+    (
+        "put",
+        "/categories/:category_id/follow",
+        "Follow a category",
+    ),
+    (
+        "delete",
+        "/categories/:category_id/follow",
+        "Unfollow a category",
+    ),
+    (
+        "get",
+        "/categories/:category_id/following",
+        "Check whether a category is followed",
+    ),
+    ("get", "/category_slugs", "List the slugs of all categories"),
+    // Users and teams
+    ("get", "/users/:user_id", "Get a user"),
+    (
+        "put",
+        "/users/:user_id",
+        "Update the email address of the current user",
+    ),
+    (
+        "get",
+        "/users/:user_id/stats",
+        "Get the download statistics of a user",
+    ),
+    (
+        "get",
+        "/users/:user_id/lists",
+        "List the public crate lists of a user",
+    ),
+    (
+        "put",
+        "/users/:user_id/resend",
+        "Resend the email address confirmation",
+    ),
+    ("get", "/namespaces/:namespace_id", "Get a namespace"),
+    ("put", "/namespaces/:namespace_id", "Create a namespace"),
+    ("get", "/teams/:team_id", "Get a team"),
+    (
+        "post",
+        "/teams/:team_id/resync",
+        "Resynchronize the members of a team",
+    ),
+    (
+        "get",
+        "/teams/:org/:team/crates",
+        "List the crates of a team",
+    ),
+    ("get", "/me", "Get the current user"),
+    (
+        "get",
+        "/me/updates",
+        "List the new versions of followed crates",
+    ),
+    (
+        "get",
+        "/me/dashboard",
+        "Get the dashboard of the current user",
+    ),
+    ("put", "/me/update_digest", "Subscribe to the update digest"),
+    (
+        "delete",
+        "/me/update_digest",
+        "Unsubscribe from the update digest",
+    ),
+    (
+        "get",
+        "/me/identities",
+        "List the linked accounts of the current user",
+    ),
+    (
+        "get",
+        "/me/identities/:provider/authorize_url",
+        "Start linking an account",
+    ),
+    ("put", "/me/identities/:provider", "Link an account"),
+    ("delete", "/me/identities/:provider", "Unlink an account"),
+    (
+        "get",
+        "/me/sessions",
+        "List the sessions of the current user",
+    ),
+    ("delete", "/me/sessions/:id", "Revoke a session"),
+    (
+        "get",
+        "/me/lists",
+        "List the crate lists of the current user",
+    ),
+    ("post", "/me/lists", "Create a crate list"),
+    ("delete", "/me/lists/:list_id", "Delete a crate list"),
+    (
+        "put",
+        "/me/lists/:list_id/crates/:crate_id",
+        "Add a crate to a list",
+    ),
+    (
+        "delete",
+        "/me/lists/:list_id/crates/:crate_id",
+        "Remove a crate from a list",
+    ),
+    ("get", "/lists/:list_id", "Get a crate list"),
+    ("put", "/lists/:list_id/follow", "Follow a crate list"),
+    ("delete", "/lists/:list_id/follow", "Unfollow a crate list"),
+    (
+        "get",
+        "/me/tokens",
+        "List the API tokens of the current user",
+    ),
+    ("put", "/me/tokens", "Create an API token"),
+    (
+        "put",
+        "/me/tokens/cargo_login",
+        "Create an API token for `cargo login`",
+    ),
+    ("delete", "/me/tokens/:id", "Revoke an API token"),
+    (
+        "get",
+        "/me/tokens/:id/usage",
+        "List the daily requests made with an API token",
+    ),
+    (
+        "get",
+        "/me/crate_owner_invitations",
+        "List the pending owner invitations",
+    ),
+    (
+        "put",
+        "/me/crate_owner_invitations/:crate_id",
+        "Accept or decline an owner invitation",
+    ),
+    (
+        "get",
+        "/me/crate_successions",
+        "List the crates the current user succeeds to",
+    ),
+    (
+        "put",
+        "/me/crate_successions/:crate_id",
+        "Accept or decline a succession",
+    ),
+    (
+        "put",
+        "/me/email_notifications",
+        "Update the email notification settings",
+    ),
+    ("put", "/confirm/:email_token", "Confirm an email address"),
+    (
+        "put",
+        "/unsubscribe/:unsubscribe_token",
+        "Unsubscribe from the update digest by token",
+    ),
+    (
+        "get",
+        "/email/unsubscribe/:token",
+        "Get an email unsubscription",
+    ),
+    (
+        "post",
+        "/email/unsubscribe/:token",
+        "Unsubscribe from emails by token",
+    ),
+    // Registry
+    ("get", "/site_metadata", "Get the deployed revision"),
+    ("get", "/registry", "Get the configuration of the registry"),
+    ("post", "/graphql", "Run a GraphQL query"),
+    // Administration
+    ("get", "/admin/flagged", "List flagged versions and reports"),
+    (
+        "put",
+        "/admin/findings/:finding_id/review",
+        "Review a scan finding",
+    ),
+    (
+        "put",
+        "/admin/reports/:report_id/resolve",
+        "Resolve a crate report",
+    ),
+    (
+        "get",
+        "/admin/download_anomalies",
+        "List the download anomalies that haven't been reviewed",
+    ),
+    (
+        "put",
+        "/admin/download_anomalies/:anomaly_id/review",
+        "Exclude or count the downloads of an anomaly",
+    ),
+    ("put", "/admin/approved_names/:name", "Approve a crate name"),
+    (
+        "get",
+        "/admin/reserved_names",
+        "List the reserved crate names",
+    ),
+    ("put", "/admin/reserved_names/:name", "Reserve a crate name"),
+    (
+        "delete",
+        "/admin/reserved_names/:name",
+        "Release a reserved crate name",
+    ),
+    (
+        "post",
+        "/admin/reserved_name_patterns",
+        "Reserve a crate name pattern",
+    ),
+    (
+        "delete",
+        "/admin/reserved_name_patterns/:pattern_id",
+        "Remove a reserved name pattern",
+    ),
+    ("get", "/admin/claims", "List the name claims"),
+    (
+        "put",
+        "/admin/claims/:claim_id/approve",
+        "Approve a name claim",
+    ),
+    (
+        "put",
+        "/admin/claims/:claim_id/reject",
+        "Reject a name claim",
+    ),
+    (
+        "get",
+        "/admin/pending_crates",
+        "List the new crates waiting for approval",
+    ),
+    (
+        "put",
+        "/admin/pending_crates/:crate_id/approve",
+        "Approve a new crate",
+    ),
+    (
+        "put",
+        "/admin/pending_crates/:crate_id/reject",
+        "Reject a new crate",
+    ),
+    ("put", "/admin/crates/:crate_id/lock", "Lock a crate"),
+    ("delete", "/admin/crates/:crate_id/lock", "Unlock a crate"),
+    (
+        "put",
+        "/admin/crates/:crate_id/max_upload_size",
+        "Set the maximum upload size of a crate",
+    ),
+    (
+        "delete",
+        "/admin/crates/:crate_id/max_upload_size",
+        "Reset the maximum upload size of a crate",
+    ),
+    ("put", "/admin/crates/:crate_id/rename", "Rename a crate"),
+    ("delete", "/admin/crates/:crate_id", "Delete a crate"),
+    (
+        "delete",
+        "/admin/deleted_names/:name",
+        "Release the name of a deleted crate",
+    ),
+    ("put", "/admin/users/:user_id/ban", "Ban a user"),
+    ("delete", "/admin/users/:user_id/ban", "Unban a user"),
+    (
+        "put",
+        "/admin/rate_limits/:user_id",
+        "Override the rate limit of a user",
+    ),
+    (
+        "delete",
+        "/admin/rate_limits/:user_id",
+        "Remove the rate limit override of a user",
+    ),
+    ("post", "/admin/categories", "Create a category"),
+    ("put", "/admin/categories/:category_id", "Update a category"),
+    (
+        "put",
+        "/admin/categories/:category_id/merge",
+        "Merge a category into another",
+    ),
+    ("get", "/admin/keyword_aliases", "List the keyword aliases"),
+    (
+        "put",
+        "/admin/keyword_aliases/:alias",
+        "Add a keyword alias",
+    ),
+    (
+        "delete",
+        "/admin/keyword_aliases/:alias",
+        "Remove a keyword alias",
+    ),
+    ("get", "/admin/jobs", "List the failed background jobs"),
+    (
+        "put",
+        "/admin/jobs/:job_id/retry",
+        "Retry a failed background job",
+    ),
+    (
+        "put",
+        "/admin/jobs/dead_letters/:dead_letter_id/retry",
+        "Retry a dead letter",
+    ),
+    (
+        "delete",
+        "/admin/jobs/dead_letters/:dead_letter_id",
+        "Discard a dead letter",
+    ),
+];
+
+/// Generates the OpenAPI document of the `/api/v1` routes.
+pub fn document() -> Value {
+    let mut paths = Map::new();
+    for &(method, path, summary) in OPERATIONS {
+        let (path, parameters) = openapi_path(path);
+        let tag = path.split('/').nth(1).unwrap_or_default();
+        let operation = json!({
+            "summary": summary,
+            "tags": [tag],
+            "parameters": parameters,
+            "responses": {
+                "200": { "description": "Success" },
+                "default": { "$ref": "#/components/responses/Errors" },
+            },
+        });
+        let item = paths.entry(path).or_insert_with(|| json!({}));
+        item[method] = operation;
+    }
+
+    let codes = ErrorCode::ALL
+        .iter()
+        .map(|code| code.as_str())
+        .collect::<Vec<_>>();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "crates.io",
+            "version": "1",
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "token": { "type": "apiKey", "in": "header", "name": "Authorization" },
+                "session": { "type": "apiKey", "in": "cookie", "name": "cargo_session" },
+            },
+            "responses": {
+                "Errors": {
+                    "description": "An error. Errors meant for cargo have a 200 status.",
+                    "content": {
+                        "application/json": {
+                            "schema": { "$ref": "#/components/schemas/Errors" },
+                        },
+                    },
+                },
+            },
+            "schemas": {
+                "Errors": {
+                    "type": "object",
+                    "required": ["errors"],
+                    "properties": {
+                        "errors": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["detail", "code"],
+                                "properties": {
+                                    "detail": { "type": "string" },
+                                    "code": { "type": "string", "enum": codes },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        },
+        "security": [{ "token": [] }, { "session": [] }, {}],
+    })
+}
+
+/// Converts a router path like `/crates/:crate_id` into an OpenAPI path like
+/// `/crates/{crate_id}`, and returns the parameters of the path.
+fn openapi_path(path: &str) -> (String, Vec<Value>) {
+    let mut parameters = Vec::new();
+    let segments = path
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with(':') {
+                let name = &segment[1..];
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }));
+                format!("{{{}}}", name)
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    (segments.join("/"), parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_parameters_are_converted() {
+        let (path, parameters) = openapi_path("/crates/:crate_id/:version/download");
+        assert_eq!(path, "/crates/{crate_id}/{version}/download");
+        let names = parameters
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["crate_id", "version"]);
+    }
+
+    #[test]
+    fn operations_are_unique() {
+        let mut operations = OPERATIONS
+            .iter()
+            .map(|&(method, path, _)| (method, path))
+            .collect::<Vec<_>>();
+        operations.sort();
+        let count = operations.len();
+        operations.dedup();
+        assert_eq!(operations.len(), count);
+    }
+}
@@ -0,0 +1,720 @@
+//! Token bucket rate limiting of API requests
+//!
+//! Every limited action has its own bucket per user, API token or IP address.
+//! Buckets start out with `burst` tokens and gain a token every `rate`, up to
+//! `burst`. Each request takes a token, and requests are rejected while the
+//! bucket is empty.
+//!
+//! Publishing new crates is limited in the publish controller, so that new
+//! versions of existing crates aren't affected. All other actions are limited
+//! by the `RateLimit` middleware, see `LimitedAction::for_request`.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::data_types::PgInterval;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::schema::{publish_rate_overrides, rate_limit_buckets};
+use crate::util::errors::{CargoResult, TooManyRequests};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromSqlRow, AsExpression)]
+#[sql_type = "Integer"]
+#[repr(u32)]
+pub enum LimitedAction {
+    PublishNew = 0,
+    Search = 1,
+    Download = 2,
+    /// Any other request changing something, like yanking a version or adding
+    /// an owner
+    Mutation = 3,
+    // if you add an action here, be sure to update `from_sql` below.
+}
+
+impl LimitedAction {
+    /// Returns the action a request to the API counts towards, if any.
+    /// `path` is relative to `/api/v1`.
+    pub fn for_request(method: &conduit::Method, path: &str) -> Option<Self> {
+        use conduit::Method::*;
+
+        match *method {
+            Get | Head if path == "/crates" => Some(LimitedAction::Search),
+            Get | Head if path.starts_with("/crates/") && path.ends_with("/download") => {
+                Some(LimitedAction::Download)
+            }
+            Get | Head | Options => None,
+            // Publishing is limited by the controller
+            Put if path == "/crates/new" => None,
+            _ => Some(LimitedAction::Mutation),
+        }
+    }
+
+    /// What the user did too often, as in "You have {action} in a short
+    /// period of time".
+    fn description(self) -> &'static str {
+        match self {
+            LimitedAction::PublishNew => "published too many crates",
+            LimitedAction::Search => "searched too often",
+            LimitedAction::Download => "downloaded too many crates",
+            LimitedAction::Mutation => "made too many changes",
+        }
+    }
+
+    fn env_var(self) -> &'static str {
+        match self {
+            LimitedAction::PublishNew => "RATE_LIMIT_PUBLISH_NEW",
+            LimitedAction::Search => "RATE_LIMIT_SEARCH",
+            LimitedAction::Download => "RATE_LIMIT_DOWNLOAD",
+            LimitedAction::Mutation => "RATE_LIMIT_MUTATION",
+        }
+    }
+}
+
+/// Whose bucket a request takes a token from. Requests authenticated with an
+/// API token use the bucket of the token, so that a single leaked or
+/// misbehaving token can't use up the limit of all tokens of the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketOwner {
+    User(i32),
+    /// The SHA-256 hash of the API token
+    Token(String),
+    Ip(String),
+}
+
+impl BucketOwner {
+    pub fn token(token: &str) -> Self {
+        BucketOwner::Token(hex::encode(openssl::sha::sha256(token.as_bytes())))
+    }
+
+    fn key(&self) -> String {
+        match self {
+            BucketOwner::User(id) => format!("user:{}", id),
+            BucketOwner::Token(hash) => format!("token:{}", hash),
+            BucketOwner::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub rate: Duration,
+    pub burst: i32,
+}
+
+impl RateLimit {
+    /// Parses a limit in the format `<milliseconds per token>,<burst>`.
+    fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split(',');
+        let rate = parts.next()?.trim().parse().ok()?;
+        let burst = parts.next()?.trim().parse().ok()?;
+        if parts.next().is_some() || rate == 0 || burst < 1 {
+            return None;
+        }
+        Some(Self {
+            rate: Duration::from_millis(rate),
+            burst,
+        })
+    }
+}
+
+/// The rate limits of each action. Actions without a limit aren't limited.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limits: HashMap<LimitedAction, RateLimit>,
+}
+
+impl Default for RateLimiter {
+    /// Only publishing new crates is limited by default.
+    fn default() -> Self {
+        let mut limits = HashMap::new();
+        limits.insert(
+            LimitedAction::PublishNew,
+            RateLimit {
+                rate: Duration::from_secs(60) * 10,
+                burst: 30,
+            },
+        );
+        Self { limits }
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone)]
+#[table_name = "rate_limit_buckets"]
+#[allow(dead_code)] // Most fields only read in tests
+struct Bucket {
+    action: LimitedAction,
+    owner: String,
+    tokens: i32,
+    last_refill: NaiveDateTime,
+}
+
+/// The state of a bucket after a request took a token from it, sent to
+/// clients in the `X-RateLimit-*` headers.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    action: LimitedAction,
+    limit: i32,
+    remaining: i32,
+    /// When the next token is added to the bucket
+    reset: NaiveDateTime,
+}
+
+impl RateLimitStatus {
+    pub fn is_exceeded(&self) -> bool {
+        self.remaining < 0
+    }
+
+    pub fn error(&self) -> TooManyRequests {
+        TooManyRequests {
+            action: self.action.description(),
+            retry_after: self.reset,
+        }
+    }
+
+    pub fn headers(&self) -> Vec<(String, String)> {
+        vec![
+            ("X-RateLimit-Limit".into(), self.limit.to_string()),
+            (
+                "X-RateLimit-Remaining".into(),
+                self.remaining.max(0).to_string(),
+            ),
+            (
+                "X-RateLimit-Reset".into(),
+                self.reset.timestamp().to_string(),
+            ),
+        ]
+    }
+}
+
+impl RateLimiter {
+    /// Reads the limits from the `RATE_LIMIT_PUBLISH_NEW`, `RATE_LIMIT_SEARCH`,
+    /// `RATE_LIMIT_DOWNLOAD` and `RATE_LIMIT_MUTATION` environment variables,
+    /// in the format `<milliseconds per token>,<burst>`. Set a variable to
+    /// `off` to turn the limit off.
+    pub fn from_environment() -> Self {
+        let mut limiter = Self::default();
+        for &action in &[
+            LimitedAction::PublishNew,
+            LimitedAction::Search,
+            LimitedAction::Download,
+            LimitedAction::Mutation,
+        ] {
+            match dotenv::var(action.env_var())
+                .ok()
+                .as_ref()
+                .map(String::as_str)
+            {
+                None => {}
+                Some("off") => {
+                    limiter.limits.remove(&action);
+                }
+                Some(value) => {
+                    let limit = RateLimit::parse(value).unwrap_or_else(|| {
+                        panic!(
+                            "{} must be `off` or `<milliseconds per token>,<burst>`, got {}",
+                            action.env_var(),
+                            value
+                        )
+                    });
+                    limiter.limits.insert(action, limit);
+                }
+            }
+        }
+        limiter
+    }
+
+    pub fn limit(&self, action: LimitedAction) -> Option<&RateLimit> {
+        self.limits.get(&action)
+    }
+
+    pub fn set_limit(&mut self, action: LimitedAction, limit: Option<RateLimit>) {
+        match limit {
+            Some(limit) => self.limits.insert(action, limit),
+            None => self.limits.remove(&action),
+        };
+    }
+
+    /// Takes a token from the bucket of `owner`. Returns `None` if the action
+    /// isn't limited.
+    pub fn take_token(
+        &self,
+        action: LimitedAction,
+        owner: &BucketOwner,
+        conn: &PgConnection,
+    ) -> CargoResult<Option<RateLimitStatus>> {
+        let limit = match self.limit(action) {
+            Some(limit) => limit,
+            None => return Ok(None),
+        };
+        let bucket = limit.take_token(action, owner, Utc::now().naive_utc(), conn)?;
+        Ok(Some(RateLimitStatus {
+            action,
+            limit: limit.burst,
+            remaining: bucket.tokens - 1,
+            reset: bucket.last_refill + chrono::Duration::from_std(limit.rate).unwrap(),
+        }))
+    }
+
+    /// Returns an error if `owner` has no tokens left for `action`.
+    pub fn check_rate_limit(
+        &self,
+        action: LimitedAction,
+        owner: &BucketOwner,
+        conn: &PgConnection,
+    ) -> CargoResult<()> {
+        match self.take_token(action, owner, conn)? {
+            Some(status) if status.is_exceeded() => Err(Box::new(status.error())),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl RateLimit {
+    /// Refill a bucket as needed, take a token from it, and returns the
+    /// result.
+    ///
+    /// The number of tokens remaining will always be between 0 and self.burst.
+    /// If the number is 0, the request should be rejected, as the user doesn't
+    /// have a token to take. Technically a "full" bucket would have
+    /// `self.burst + 1` tokens in it, but that value would never be returned
+    /// since we only refill buckets when trying to take a token from it.
+    fn take_token(
+        &self,
+        action: LimitedAction,
+        owner: &BucketOwner,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> CargoResult<Bucket> {
+        use self::rate_limit_buckets::dsl::{last_refill, rate_limit_buckets, tokens};
+        use diesel::sql_types::{Double, Interval, Text, Timestamp};
+
+        sql_function!(fn date_part(x: Text, y: Timestamp) -> Double);
+        sql_function! {
+            #[sql_name = "date_part"]
+            fn interval_part(x: Text, y: Interval) -> Double;
+        }
+        sql_function!(fn floor(x: Double) -> Integer);
+        sql_function!(fn greatest<T>(x: T, y: T) -> T);
+        sql_function!(fn least<T>(x: T, y: T) -> T);
+
+        // Overrides only exist for publishing new crates
+        let burst = match owner {
+            BucketOwner::User(user_id) if action == LimitedAction::PublishNew => {
+                publish_rate_overrides::table
+                    .find(user_id)
+                    .filter(
+                        publish_rate_overrides::expires_at
+                            .is_null()
+                            .or(publish_rate_overrides::expires_at.gt(now)),
+                    )
+                    .select(publish_rate_overrides::burst)
+                    .first::<i32>(conn)
+                    .optional()?
+                    .unwrap_or(self.burst)
+            }
+            _ => self.burst,
+        };
+
+        // Interval division is poorly defined in general (what is 1 month / 30 days?)
+        // However, for the intervals we're dealing with, it is always well
+        // defined, so we convert to an f64 of seconds to represent this.
+        let tokens_to_add = floor(
+            (date_part("epoch", now) - date_part("epoch", last_refill))
+                / interval_part("epoch", self.refill_rate()),
+        );
+
+        diesel::insert_into(rate_limit_buckets)
+            .values(&Bucket {
+                action,
+                owner: owner.key(),
+                tokens: burst,
+                last_refill: now,
+            })
+            .on_conflict(rate_limit_buckets.primary_key())
+            .do_update()
+            .set((
+                tokens.eq(least(burst, greatest(0, tokens - 1) + tokens_to_add)),
+                last_refill
+                    .eq(last_refill + self.refill_rate().into_sql::<Interval>() * tokens_to_add),
+            ))
+            .get_result(conn)
+            .map_err(Into::into)
+    }
+
+    fn refill_rate(&self) -> PgInterval {
+        use diesel::dsl::*;
+        (self.rate.as_millis() as i64).milliseconds()
+    }
+}
+
+impl FromSql<Integer, Pg> for LimitedAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(LimitedAction::PublishNew),
+            1 => Ok(LimitedAction::Search),
+            2 => Ok(LimitedAction::Download),
+            3 => Ok(LimitedAction::Mutation),
+            n => Err(format!("unknown rate limited action: {}", n).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for LimitedAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    const PUBLISH_NEW: LimitedAction = LimitedAction::PublishNew;
+
+    #[test]
+    fn take_token_with_no_bucket_creates_new_one() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let bucket = rate.take_token(
+            PUBLISH_NEW,
+            &BucketOwner::User(new_user(&conn, "user1")?),
+            now,
+            &conn,
+        )?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: bucket.owner.clone(),
+            tokens: 10,
+            last_refill: now,
+        };
+        assert_eq!(expected, bucket);
+
+        let rate = RateLimit {
+            rate: Duration::from_millis(50),
+            burst: 20,
+        };
+        let bucket = rate.take_token(
+            PUBLISH_NEW,
+            &BucketOwner::User(new_user(&conn, "user2")?),
+            now,
+            &conn,
+        )?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: bucket.owner.clone(),
+            tokens: 20,
+            last_refill: now,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn take_token_with_existing_bucket_modifies_existing_bucket() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 5, now)?;
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, now, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 4,
+            last_refill: now,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn take_token_after_delay_refills() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 5, now)?;
+        let refill_time = now + chrono::Duration::seconds(2);
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, refill_time, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 6,
+            last_refill: refill_time,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn refill_subsecond_rate() -> CargoResult<()> {
+        let conn = pg_connection();
+        // Subsecond rates have floating point rounding issues, so use a known
+        // timestamp that rounds fine
+        let now =
+            NaiveDateTime::parse_from_str("2019-03-19T21:11:24.620401", "%Y-%m-%dT%H:%M:%S%.f")?;
+
+        let rate = RateLimit {
+            rate: Duration::from_millis(100),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 5, now)?;
+        let refill_time = now + chrono::Duration::milliseconds(300);
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, refill_time, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 7,
+            last_refill: refill_time,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn last_refill_always_advanced_by_multiple_of_rate() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_millis(100),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 5, now)?;
+        let bucket = rate.take_token(
+            PUBLISH_NEW,
+            &owner,
+            now + chrono::Duration::milliseconds(250),
+            &conn,
+        )?;
+        let expected_refill_time = now + chrono::Duration::milliseconds(200);
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 6,
+            last_refill: expected_refill_time,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_tokens_returned_when_user_has_no_tokens_left() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 1, now)?;
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, now, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 0,
+            last_refill: now,
+        };
+        assert_eq!(expected, bucket);
+
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, now, &conn)?;
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn a_user_with_no_tokens_gets_a_token_after_exactly_rate() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 0, now)?;
+        let refill_time = now + chrono::Duration::seconds(1);
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, refill_time, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 1,
+            last_refill: refill_time,
+        };
+        assert_eq!(expected, bucket);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_never_refill_past_burst() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 8, now)?;
+        let refill_time = now + chrono::Duration::seconds(4);
+        let bucket = rate.take_token(PUBLISH_NEW, &owner, refill_time, &conn)?;
+        let expected = Bucket {
+            action: PUBLISH_NEW,
+            owner: owner.key(),
+            tokens: 10,
+            last_refill: refill_time,
+        };
+        assert_eq!(expected, bucket);
+
+        Ok(())
+    }
+
+    #[test]
+    fn override_is_used_instead_of_global_burst_if_present() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let user_id = new_user(&conn, "user1")?;
+        let other_user_id = new_user(&conn, "user2")?;
+
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user_id),
+                publish_rate_overrides::burst.eq(20),
+            ))
+            .execute(&conn)?;
+
+        let bucket = rate.take_token(PUBLISH_NEW, &BucketOwner::User(user_id), now, &conn)?;
+        let other_bucket =
+            rate.take_token(PUBLISH_NEW, &BucketOwner::User(other_user_id), now, &conn)?;
+
+        assert_eq!(20, bucket.tokens);
+        assert_eq!(10, other_bucket.tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn expired_overrides_are_ignored() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let user_id = new_user(&conn, "user1")?;
+
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user_id),
+                publish_rate_overrides::burst.eq(20),
+                publish_rate_overrides::expires_at.eq(now - chrono::Duration::days(1)),
+            ))
+            .execute(&conn)?;
+
+        let bucket = rate.take_token(PUBLISH_NEW, &BucketOwner::User(user_id), now, &conn)?;
+        assert_eq!(10, bucket.tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn actions_have_separate_buckets() -> CargoResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let rate = RateLimit {
+            rate: Duration::from_secs(1),
+            burst: 10,
+        };
+        let owner = new_user_bucket(&conn, 1, now)?;
+        let bucket = rate.take_token(LimitedAction::Search, &owner, now, &conn)?;
+        assert_eq!(10, bucket.tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn requests_are_limited_by_route_group() {
+        use conduit::Method::*;
+
+        let action = LimitedAction::for_request;
+        assert_eq!(action(&Get, "/crates"), Some(LimitedAction::Search));
+        assert_eq!(
+            action(&Get, "/crates/foo/1.0.0/download"),
+            Some(LimitedAction::Download)
+        );
+        assert_eq!(action(&Get, "/crates/foo"), None);
+        assert_eq!(action(&Put, "/crates/new"), None);
+        assert_eq!(
+            action(&Delete, "/crates/foo/1.0.0/yank"),
+            Some(LimitedAction::Mutation)
+        );
+    }
+
+    #[test]
+    fn parse_limits() {
+        let limit = RateLimit::parse("100, 60").unwrap();
+        assert_eq!(limit.rate, Duration::from_millis(100));
+        assert_eq!(limit.burst, 60);
+        assert!(RateLimit::parse("100").is_none());
+        assert!(RateLimit::parse("0,60").is_none());
+        assert!(RateLimit::parse("100,60,1").is_none());
+    }
+
+    fn new_user(conn: &PgConnection, gh_login: &str) -> CargoResult<i32> {
+        use crate::models::NewUser;
+
+        let user = NewUser {
+            gh_login,
+            ..NewUser::default()
+        }
+        .create_or_update(conn)?;
+        Ok(user.id)
+    }
+
+    fn new_user_bucket(
+        conn: &PgConnection,
+        tokens: i32,
+        now: NaiveDateTime,
+    ) -> CargoResult<BucketOwner> {
+        let owner = BucketOwner::User(new_user(conn, "new_user")?);
+        diesel::insert_into(rate_limit_buckets::table)
+            .values(Bucket {
+                action: PUBLISH_NEW,
+                owner: owner.key(),
+                tokens,
+                last_refill: now,
+            })
+            .execute(conn)?;
+        Ok(owner)
+    }
+
+    /// Strips ns precision from `Utc::now`. PostgreSQL only has microsecond
+    /// precision, but some platforms (notably Linux) provide nanosecond
+    /// precision, meaning that round tripping through the database would
+    /// change the value.
+    fn now() -> NaiveDateTime {
+        let now = Utc::now().naive_utc();
+        let nanos = now.timestamp_subsec_nanos();
+        now - chrono::Duration::nanoseconds(nanos.into())
+    }
+}
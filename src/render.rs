@@ -9,6 +9,7 @@ use url::Url;
 
 use crate::background_jobs::Environment;
 use crate::models::Version;
+use crate::telemetry::{self, SpanContext};
 
 /// Context for markdown to HTML rendering.
 #[allow(missing_debug_implementations)]
@@ -68,7 +69,12 @@ impl<'a> MarkdownRenderer<'a> {
             ..comrak::ComrakOptions::default()
         };
         let rendered = comrak::markdown_to_html(text, &options);
-        self.html_sanitizer.clean(&rendered).to_string()
+        self.sanitize(&rendered)
+    }
+
+    /// Removes anything from the given HTML that isn't in the allowlist.
+    fn sanitize(&self, html: &str) -> String {
+        self.html_sanitizer.clean(html).to_string()
     }
 }
 
@@ -179,6 +185,29 @@ fn markdown_to_html(text: &str, base_url: Option<&str>) -> String {
     renderer.to_html(text)
 }
 
+/// Renders reStructuredText to sanitized HTML with a given `base_url`.
+/// See `readme_to_html` for the interpretation of `base_url`.
+///
+/// Documents that can't be parsed are rendered as plain text.
+fn rst_to_html(text: &str, base_url: Option<&str>) -> String {
+    let mut rendered = Vec::new();
+    let result = rst_parser::parse(text)
+        .and_then(|document| rst_renderer::render_html(&document, &mut rendered, false));
+    match (result, String::from_utf8(rendered)) {
+        (Ok(()), Ok(rendered)) => MarkdownRenderer::new(base_url).sanitize(&rendered),
+        _ => text_to_html(text),
+    }
+}
+
+/// Escapes plain text and keeps its line breaks.
+fn text_to_html(text: &str) -> String {
+    encode_minimal(text).replace("\n", "<br>\n")
+}
+
+/// Any readme with a filename ending in one of these extensions will be rendered as
+/// reStructuredText.
+static RESTRUCTURED_TEXT_EXTENSIONS: [&str; 2] = [".rst", ".rest"];
+
 /// Any readme with a filename ending in one of these extensions will be rendered as Markdown.
 /// Note we also render a readme as Markdown if _no_ extension is on the filename.
 static MARKDOWN_EXTENSIONS: [&str; 7] = [
@@ -192,7 +221,8 @@ static MARKDOWN_EXTENSIONS: [&str; 7] = [
 ];
 
 /// Renders a readme to sanitized HTML.  An appropriate rendering method is chosen depending
-/// on the extension of the supplied `filename`.
+/// on the extension of the supplied `filename`: Markdown, reStructuredText, or plain text
+/// for anything else.
 ///
 /// The returned text will not contain any harmful HTML tag or attribute (such as iframe,
 /// onclick, onmouseover, etc.).
@@ -217,7 +247,14 @@ pub fn readme_to_html(text: &str, filename: &str, base_url: Option<&str>) -> Str
         return markdown_to_html(text, base_url);
     }
 
-    encode_minimal(text).replace("\n", "<br>\n")
+    if RESTRUCTURED_TEXT_EXTENSIONS
+        .iter()
+        .any(|e| filename.ends_with(e))
+    {
+        return rst_to_html(text, base_url);
+    }
+
+    text_to_html(text)
 }
 
 #[swirl::background_job]
@@ -227,25 +264,28 @@ pub fn render_and_upload_readme(
     text: String,
     file_name: String,
     base_url: Option<String>,
+    trace: Option<SpanContext>,
 ) -> Result<(), PerformError> {
-    use crate::schema::*;
-    use crate::util::errors::std_error_no_send;
-    use diesel::prelude::*;
-
-    let rendered = readme_to_html(&text, &file_name, base_url.as_ref().map(String::as_str));
-    let conn = env.connection()?;
-
-    conn.transaction(|| {
-        Version::record_readme_rendering(version_id, &conn)?;
-        let (crate_name, vers) = versions::table
-            .find(version_id)
-            .inner_join(crates::table)
-            .select((crates::name, versions::num))
-            .first::<(String, String)>(&*conn)?;
-        env.uploader
-            .upload_readme(env.http_client(), &crate_name, &vers, rendered)
-            .map_err(std_error_no_send)?;
-        Ok(())
+    telemetry::in_job_span("render_and_upload_readme", trace, || {
+        use crate::schema::*;
+        use crate::util::errors::std_error_no_send;
+        use diesel::prelude::*;
+
+        let rendered = readme_to_html(&text, &file_name, base_url.as_ref().map(String::as_str));
+        let conn = env.connection()?;
+
+        conn.transaction(|| {
+            Version::record_readme_rendering(version_id, Some(&rendered), &conn)?;
+            let (crate_name, vers) = versions::table
+                .find(version_id)
+                .inner_join(crates::table)
+                .select((crates::name, versions::num))
+                .first::<(String, String)>(&*conn)?;
+            env.uploader
+                .upload_readme(env.http_client(), &crate_name, &vers, rendered)
+                .map_err(std_error_no_send)?;
+            Ok(())
+        })
     })
 }
 
@@ -433,6 +473,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn readme_to_html_renders_restructured_text() {
+        let text = "My crate\n========\n\nSome *emphasis*.\n";
+        for f in &["README.rst", "readme.REST"] {
+            let result = readme_to_html(text, f, None);
+            assert!(result.contains("<em>emphasis</em>"), "{}", result);
+        }
+    }
+
     #[test]
     fn header_has_tags() {
         let text = "# My crate\n\nHello, world!\n";
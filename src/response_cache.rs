@@ -0,0 +1,329 @@
+//! A cache for the responses of frequently requested read endpoints
+//!
+//! The crate detail, summary and search endpoints store their serialized
+//! responses in a `CacheStore`, either in an in-memory LRU cache of each
+//! server process or in Redis, which is shared between all of them.
+//!
+//! The controllers that publish crates, yank versions, change owners or the
+//! visibility of crates invalidate the affected entries. Other changes, like download counts or
+//! changes made by background jobs, are only picked up once the entries
+//! expire, so the time to live should be kept short.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use conduit::Response;
+use lru::LruCache;
+use parking_lot::Mutex;
+use redis::Commands;
+use serde::Serialize;
+
+use crate::util::json_body_response;
+
+/// Where cached responses are stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheBackend {
+    Off,
+    /// An LRU cache with room for the given number of responses in each
+    /// server process
+    Memory {
+        capacity: usize,
+    },
+    /// A Redis server shared by all server processes
+    Redis {
+        url: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseCacheConfig {
+    pub backend: CacheBackend,
+    /// How long responses are cached for
+    pub ttl: Duration,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::Off,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    /// Reads the cache configuration from the environment.
+    ///
+    /// Pulls values from the following environment variables:
+    ///
+    /// - `RESPONSE_CACHE`: Either `off`, `memory` or `redis`. Defaults to
+    ///   `off`. The `memory` cache is local to the process, so invalidating
+    ///   it only works if a single process serves the API; deployments with
+    ///   more than one need `redis`.
+    /// - `RESPONSE_CACHE_SIZE`: The number of responses the `memory` cache
+    ///   holds. Defaults to 1000.
+    /// - `RESPONSE_CACHE_TTL`: The number of seconds responses are cached for.
+    ///   Defaults to 60.
+    /// - `REDIS_URL`: The URL of the Redis server. Required for `redis`.
+    pub fn from_environment() -> Self {
+        let var = |name: &str| dotenv::var(name).ok().filter(|s| !s.is_empty());
+        let parse = |name: &str, default| {
+            var(name).map_or(default, |s| {
+                usize::from_str(&s).unwrap_or_else(|_| panic!("couldn't parse {}", name))
+            })
+        };
+
+        let backend = match var("RESPONSE_CACHE").as_ref().map(String::as_str) {
+            Some("off") | None => CacheBackend::Off,
+            Some("memory") => CacheBackend::Memory {
+                capacity: parse("RESPONSE_CACHE_SIZE", 1000),
+            },
+            Some("redis") => CacheBackend::Redis {
+                url: crate::env("REDIS_URL"),
+            },
+            Some(s) => panic!("unknown RESPONSE_CACHE `{}`", s),
+        };
+        Self {
+            backend,
+            ttl: Duration::from_secs(parse("RESPONSE_CACHE_TTL", 60) as u64),
+        }
+    }
+}
+
+/// A key-value store for cached responses. Stores never fail: entries that
+/// can't be read are treated as missing, and entries that can't be written
+/// are dropped.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&self, key: &str, value: &str, ttl: Duration);
+    fn delete(&self, key: &str);
+}
+
+pub struct MemoryStore {
+    entries: Mutex<LruCache<String, (Instant, String)>>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("len", &self.entries.lock().len())
+            .finish()
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock();
+        match entries.get(key) {
+            Some((expires_at, value)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Duration) {
+        self.entries
+            .lock()
+            .put(key.into(), (Instant::now() + ttl, value.into()));
+    }
+
+    fn delete(&self, key: &str) {
+        self.entries.lock().pop(key);
+    }
+}
+
+pub struct RedisStore {
+    client: redis::Client,
+    /// Dropped after errors, and reopened by the next request
+    connection: Mutex<Option<redis::Connection>>,
+}
+
+impl RedisStore {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: redis::Client::open(url).expect("invalid REDIS_URL"),
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> Option<T> {
+        let mut connection = self.connection.lock();
+        if connection.is_none() {
+            *connection = self.client.get_connection().ok();
+        }
+        let result = f(connection.as_mut()?);
+        if let Err(e) = &result {
+            warn!("response cache error: {}", e);
+            *connection = None;
+        }
+        result.ok()
+    }
+}
+
+impl fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStore").finish()
+    }
+}
+
+impl CacheStore for RedisStore {
+    fn get(&self, key: &str) -> Option<String> {
+        self.with_connection(|conn| conn.get(key))
+            .and_then(|value| value)
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Duration) {
+        self.with_connection(|conn| conn.set_ex::<_, _, ()>(key, value, ttl.as_secs() as usize));
+    }
+
+    fn delete(&self, key: &str) {
+        self.with_connection(|conn| conn.del::<_, ()>(key));
+    }
+}
+
+/// The key of the value that is part of the keys of all search responses.
+/// Changing it invalidates all of them at once.
+const SEARCH_GENERATION_KEY: &str = "search-generation";
+
+pub struct ResponseCache {
+    store: Option<Box<dyn CacheStore>>,
+    ttl: Duration,
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("enabled", &self.store.is_some())
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        let store: Option<Box<dyn CacheStore>> = match &config.backend {
+            CacheBackend::Off => None,
+            CacheBackend::Memory { capacity: 0 } => None,
+            CacheBackend::Memory { capacity } => Some(Box::new(MemoryStore::new(*capacity))),
+            CacheBackend::Redis { url } => Some(Box::new(RedisStore::new(url))),
+        };
+        Self {
+            store,
+            ttl: config.ttl,
+        }
+    }
+
+    pub fn crate_key(crate_name: &str) -> String {
+        format!("crate:{}", crate::models::krate::canonical_name(crate_name))
+    }
+
+    pub fn summary_key() -> String {
+        "summary".into()
+    }
+
+    /// The key of a search request, given the parts of the request that the
+    /// response depends on.
+    pub fn search_key(&self, request: &str) -> String {
+        let generation = self.get_raw(SEARCH_GENERATION_KEY).unwrap_or_default();
+        format!("search:{}:{}", generation, request)
+    }
+
+    /// Returns the cached response for the key.
+    pub fn get(&self, key: &str) -> Option<Response> {
+        self.get_raw(key).map(json_body_response)
+    }
+
+    /// Serializes and caches the response for the key.
+    pub fn insert<T: Serialize>(&self, key: &str, response: &T) -> Response {
+        let json = serde_json::to_string(response).unwrap();
+        if let Some(store) = &self.store {
+            store.set(key, &json, self.ttl);
+        }
+        json_body_response(json)
+    }
+
+    /// Invalidates the responses that show the crate, to be called after
+    /// changing it.
+    pub fn invalidate_crate(&self, crate_name: &str) {
+        if let Some(store) = &self.store {
+            store.delete(&Self::crate_key(crate_name));
+            store.delete(&Self::summary_key());
+            let generation = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            store.set(SEARCH_GENERATION_KEY, &generation.to_string(), self.ttl);
+        }
+    }
+
+    fn get_raw(&self, key: &str) -> Option<String> {
+        self.store.as_ref()?.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_cache() -> ResponseCache {
+        ResponseCache::new(&ResponseCacheConfig {
+            backend: CacheBackend::Memory { capacity: 2 },
+            ttl: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn memory_store_evicts_least_recently_used_entries() {
+        let store = MemoryStore::new(2);
+        let ttl = Duration::from_secs(60);
+        store.set("a", "1", ttl);
+        store.set("b", "2", ttl);
+        assert_eq!(store.get("a").as_ref().map(String::as_str), Some("1"));
+        store.set("c", "3", ttl);
+        assert_eq!(store.get("b"), None);
+        assert_eq!(store.get("c").as_ref().map(String::as_str), Some("3"));
+    }
+
+    #[test]
+    fn memory_store_expires_entries() {
+        let store = MemoryStore::new(2);
+        store.set("a", "1", Duration::from_secs(0));
+        assert_eq!(store.get("a"), None);
+    }
+
+    #[test]
+    fn invalidation_changes_search_keys() {
+        let cache = memory_cache();
+        let key = cache.search_key("q=foo");
+        assert_eq!(cache.search_key("q=foo"), key);
+
+        cache.insert(&ResponseCache::crate_key("Foo_Bar"), &"crate");
+        assert!(cache.get(&ResponseCache::crate_key("foo-bar")).is_some());
+
+        cache.invalidate_crate("foo_bar");
+        assert_ne!(cache.search_key("q=foo"), key);
+        assert!(cache.get(&ResponseCache::crate_key("foo-bar")).is_none());
+    }
+
+    #[test]
+    fn disabled_cache_stores_nothing() {
+        let cache = ResponseCache::new(&ResponseCacheConfig::default());
+        cache.insert("summary", &"summary");
+        assert!(cache.get("summary").is_none());
+    }
+}
@@ -1,22 +1,104 @@
+use std::collections::HashMap;
 use std::error::Error;
+use std::io;
 use std::sync::Arc;
 
-use conduit::{Handler, Request, Response};
+use conduit::{Handler, Method, Request, Response};
 use conduit_router::{RequestParams, RouteBuilder};
 
 use crate::controllers::*;
+use crate::db::RequestTransaction;
+use crate::middleware::app::RequestApp;
+use crate::middleware::current_user::RequestUser;
+use crate::models::CrateRename;
 use crate::util::errors::{std_error, CargoError, CargoResult, NotFound};
-use crate::util::RequestProxy;
+use crate::util::{json_response, RequestProxy};
 use crate::{App, Env};
 
 pub fn build_router(app: &App) -> R404 {
-    let mut api_router = RouteBuilder::new();
+    let api_router = Arc::new(R404(api_router().builder));
+
+    // The v2 API, see `controllers::v2`
+    let mut v2_router = Routes::new();
+    v2_router.get("/crates", V2(v2::krate::index));
+    v2_router.get("/crates/:crate_id", V2(v2::krate::show));
+    v2_router.get("/crates/:crate_id/versions", V2(v2::krate::versions));
+    v2_router.get("/crates/:crate_id/owners", V2(v2::krate::owners));
+    let v2_router = Arc::new(V2R404(v2_router.builder));
+
+    let mut router = Routes::new();
+
+    // Mount the router under the /api/v1 path so we're at least somewhat at the
+    // liberty to change things in the future!
+    router.get("/api/v1/*path", FollowRenames(R(Arc::clone(&api_router))));
+    router.put("/api/v1/*path", R(Arc::clone(&api_router)));
+    router.post("/api/v1/*path", R(Arc::clone(&api_router)));
+    router.head("/api/v1/*path", R(Arc::clone(&api_router)));
+    router.patch("/api/v1/*path", R(Arc::clone(&api_router)));
+    router.delete("/api/v1/*path", R(api_router));
+
+    router.get("/api/v2/*path", FollowRenames(R(Arc::clone(&v2_router))));
+    router.put("/api/v2/*path", R(Arc::clone(&v2_router)));
+    router.post("/api/v2/*path", R(Arc::clone(&v2_router)));
+    router.head("/api/v2/*path", R(Arc::clone(&v2_router)));
+    router.patch("/api/v2/*path", R(Arc::clone(&v2_router)));
+    router.delete("/api/v2/*path", R(v2_router));
+
+    router.get("/api/openapi.json", C(openapi));
+    router.get("/authorize_url", C(user::session::github_authorize));
+    router.get("/authorize", C(user::session::github_access_token));
+    router.get("/authorize_url/:provider", C(user::session::authorize_url));
+    router.get("/authorize/:provider", C(user::session::access_token));
+    router.delete("/logout", C(user::session::logout));
+    router.get("/metrics", C(metrics::prometheus));
+    router.get("/healthz", C(health::liveness));
+    router.get("/readyz", C(health::readiness));
+    router.post(
+        "/api/github/secret-scanning/verify",
+        C(github::secret_scanning::verify),
+    );
+
+    // Only serve the local checkout of the git index in development mode.
+    // In production, for crates.io, cargo gets the index from
+    // https://github.com/rust-lang/crates.io-index directly.
+    if app.config.env == Env::Development {
+        let s = conduit_git_http_backend::Serve(app.git_repo_checkout.clone());
+        let s = Arc::new(s);
+        router.get("/git/index/*path", R(Arc::clone(&s)));
+        router.post("/git/index/*path", R(s));
+    }
+
+    R404(router.builder)
+}
+
+/// The routes under `/api/v1`, which have to be described in
+/// `openapi::OPERATIONS`.
+fn api_router() -> Routes {
+    let mut api_router = Routes::new();
 
     // Route used by both `cargo search` and the frontend
     api_router.get("/crates", C(krate::search::search));
 
     // Routes used by `cargo`
     api_router.put("/crates/new", C(krate::publish::publish));
+    api_router.post("/crates/new/session", C(krate::publish_session::start));
+    api_router.get(
+        "/crates/new/session/:session_id",
+        C(krate::publish_session::show),
+    );
+    api_router.delete(
+        "/crates/new/session/:session_id",
+        C(krate::publish_session::delete),
+    );
+    api_router.put(
+        "/crates/new/session/:session_id/chunk",
+        C(krate::publish_session::upload_chunk),
+    );
+    api_router.post(
+        "/crates/new/session/:session_id/finish",
+        C(krate::publish_session::finish),
+    );
+    api_router.delete("/crates/:crate_id", C(krate::delete::delete));
     api_router.get("/crates/:crate_id/owners", C(krate::owners::owners));
     api_router.put("/crates/:crate_id/owners", C(krate::owners::add_owners));
     api_router.delete("/crates/:crate_id/owners", C(krate::owners::remove_owners));
@@ -25,10 +107,25 @@ pub fn build_router(app: &App) -> R404 {
         "/crates/:crate_id/:version/unyank",
         C(version::yank::unyank),
     );
+    api_router.delete("/crates/:crate_id/:version", C(version::delete::delete));
     api_router.get(
         "/crates/:crate_id/:version/download",
         C(version::downloads::download),
     );
+    api_router.put(
+        "/crates/:crate_id/:version/advisory",
+        C(version::advisory::set),
+    );
+    api_router.delete(
+        "/crates/:crate_id/:version/advisory",
+        C(version::advisory::clear),
+    );
+
+    // Routes used by build verification services
+    api_router.put(
+        "/crates/:crate_id/:version/build_verification",
+        C(version::build_verification::submit),
+    );
 
     // Routes that appear to be unused
     api_router.get("/versions", C(version::deprecated::index));
@@ -36,6 +133,7 @@ pub fn build_router(app: &App) -> R404 {
 
     // Routes used by the frontend
     api_router.get("/crates/:crate_id", C(krate::metadata::show));
+    api_router.patch("/crates/:crate_id", C(krate::metadata::update));
     api_router.get("/crates/:crate_id/:version", C(version::metadata::show));
     api_router.get(
         "/crates/:crate_id/:version/readme",
@@ -53,34 +151,154 @@ pub fn build_router(app: &App) -> R404 {
         "/crates/:crate_id/:version/authors",
         C(version::metadata::authors),
     );
+    api_router.get(
+        "/crates/:crate_id/:version/dependency_graph",
+        C(version::metadata::dependency_graph),
+    );
+    api_router.get(
+        "/crates/:crate_id/:version/files",
+        C(version::metadata::files),
+    );
+    api_router.get(
+        "/crates/:crate_id/:version/checksums",
+        C(version::metadata::checksums),
+    );
+    api_router.get(
+        "/crates/:crate_id/:version/provenance",
+        C(version::metadata::provenance),
+    );
+    api_router.get(
+        "/crates/:crate_id/:version/diff/:other_version",
+        C(version::diff::diff),
+    );
+    api_router.get(
+        "/crates/:crate_id/:version/build_verifications",
+        C(version::build_verification::list),
+    );
     api_router.get(
         "/crates/:crate_id/downloads",
         C(krate::downloads::downloads),
     );
+    api_router.get(
+        "/crates/:crate_id/downloads/summary",
+        C(krate::downloads::summary),
+    );
+    api_router.get(
+        "/crates/:crate_id/downloads/clients",
+        C(krate::downloads::clients),
+    );
+    api_router.get(
+        "/crates/:crate_id/version_adoption",
+        C(krate::downloads::version_adoption),
+    );
     api_router.get("/crates/:crate_id/versions", C(krate::metadata::versions));
+    api_router.get(
+        "/crates/:crate_id/availability",
+        C(krate::availability::availability),
+    );
     api_router.put("/crates/:crate_id/follow", C(krate::follow::follow));
     api_router.delete("/crates/:crate_id/follow", C(krate::follow::unfollow));
     api_router.get("/crates/:crate_id/following", C(krate::follow::following));
     api_router.get("/crates/:crate_id/owner_team", C(krate::owners::owner_team));
     api_router.get("/crates/:crate_id/owner_user", C(krate::owners::owner_user));
+    api_router.get(
+        "/crates/:crate_id/owner_removals",
+        C(krate::owners::owner_removals),
+    );
+    api_router.put(
+        "/crates/:crate_id/owner_removals/:removal_id/confirm",
+        C(krate::owners::confirm_owner_removal),
+    );
+    api_router.delete(
+        "/crates/:crate_id/owner_removals/:removal_id",
+        C(krate::owners::cancel_owner_removal),
+    );
+    api_router.get("/crates/:crate_id/successor", C(krate::successor::show));
+    api_router.put(
+        "/crates/:crate_id/successor",
+        C(krate::successor::designate),
+    );
+    api_router.delete("/crates/:crate_id/successor", C(krate::successor::remove));
+    api_router.put(
+        "/crates/:crate_id/repository_verification",
+        C(krate::repository::verify),
+    );
     api_router.get(
         "/crates/:crate_id/reverse_dependencies",
         C(krate::metadata::reverse_dependencies),
     );
+    api_router.get("/crates/:crate_id/access", C(krate::access::list));
+    api_router.put("/crates/:crate_id/access", C(krate::access::grant));
+    api_router.delete("/crates/:crate_id/access", C(krate::access::revoke));
+    api_router.put(
+        "/crates/:crate_id/visibility",
+        C(krate::access::update_visibility),
+    );
+    api_router.get("/crates/:crate_id/index", C(krate::access::index_file));
+    api_router.post("/crates/:crate_id/report", C(krate::report::report));
+    api_router.get("/crates/:crate_id/claims", C(krate::claim::list));
+    api_router.post("/crates/:crate_id/claims", C(krate::claim::claim));
+    api_router.put(
+        "/crates/:crate_id/claims/:claim_id",
+        C(krate::claim::respond),
+    );
     api_router.get("/keywords", C(keyword::index));
+    api_router.get("/keywords/suggest", C(keyword::suggest));
     api_router.get("/keywords/:keyword_id", C(keyword::show));
+    api_router.get("/keywords/:keyword_id/top", C(keyword::top));
+    api_router.put("/keywords/:keyword_id/follow", C(keyword::follow));
+    api_router.delete("/keywords/:keyword_id/follow", C(keyword::unfollow));
+    api_router.get("/keywords/:keyword_id/following", C(keyword::following));
     api_router.get("/categories", C(category::index));
+    api_router.get("/categories/suggest", C(category::suggest));
     api_router.get("/categories/:category_id", C(category::show));
+    api_router.get("/categories/:category_id/top", C(category::top));
+    api_router.put("/categories/:category_id/follow", C(category::follow));
+    api_router.delete("/categories/:category_id/follow", C(category::unfollow));
+    api_router.get("/categories/:category_id/following", C(category::following));
     api_router.get("/category_slugs", C(category::slugs));
     api_router.get("/users/:user_id", C(user::other::show));
     api_router.put("/users/:user_id", C(user::me::update_user));
     api_router.get("/users/:user_id/stats", C(user::other::stats));
+    api_router.get("/users/:user_id/lists", C(crate_list::for_user));
+    api_router.get("/namespaces/:namespace_id", C(namespace::show));
+    api_router.put("/namespaces/:namespace_id", C(namespace::create));
     api_router.get("/teams/:team_id", C(team::show_team));
+    api_router.post("/teams/:team_id/resync", C(team::resync_team));
+    api_router.get("/teams/:org/:team/crates", C(team::crates));
     api_router.get("/me", C(user::me::me));
     api_router.get("/me/updates", C(user::me::updates));
+    api_router.get("/me/dashboard", C(user::me::dashboard));
+    api_router.put("/me/update_digest", C(user::me::subscribe_update_digest));
+    api_router.delete("/me/update_digest", C(user::me::unsubscribe_update_digest));
+    api_router.get("/me/identities", C(user::identities::list));
+    api_router.get(
+        "/me/identities/:provider/authorize_url",
+        C(user::identities::authorize_url),
+    );
+    api_router.put("/me/identities/:provider", C(user::identities::link));
+    api_router.delete("/me/identities/:provider", C(user::identities::unlink));
+    api_router.get("/me/sessions", C(user::session::list));
+    api_router.delete("/me/sessions/:id", C(user::session::revoke));
+    api_router.get("/me/lists", C(crate_list::mine));
+    api_router.post("/me/lists", C(crate_list::new));
+    api_router.delete("/me/lists/:list_id", C(crate_list::delete));
+    api_router.put(
+        "/me/lists/:list_id/crates/:crate_id",
+        C(crate_list::add_crate),
+    );
+    api_router.delete(
+        "/me/lists/:list_id/crates/:crate_id",
+        C(crate_list::remove_crate),
+    );
+    api_router.get("/lists/:list_id", C(crate_list::show));
+    api_router.put("/lists/:list_id/follow", C(crate_list::follow));
+    api_router.delete("/lists/:list_id/follow", C(crate_list::unfollow));
     api_router.get("/me/tokens", C(token::list));
     api_router.put("/me/tokens", C(token::new));
+    api_router.put("/me/tokens/cargo_login", C(token::cargo_login));
     api_router.delete("/me/tokens/:id", C(token::revoke));
+    api_router.get("/me/tokens/:id/usage", C(token::usage));
     api_router.get(
         "/me/crate_owner_invitations",
         C(crate_owner_invitation::list),
@@ -89,44 +307,175 @@ pub fn build_router(app: &App) -> R404 {
         "/me/crate_owner_invitations/:crate_id",
         C(crate_owner_invitation::handle_invite),
     );
+    api_router.get("/me/crate_successions", C(krate::successor::successions));
+    api_router.put(
+        "/me/crate_successions/:crate_id",
+        C(krate::successor::handle_succession),
+    );
     api_router.put(
         "/me/email_notifications",
         C(user::me::update_email_notifications),
     );
     api_router.get("/summary", C(krate::metadata::summary));
     api_router.put("/confirm/:email_token", C(user::me::confirm_user_email));
+    api_router.put(
+        "/unsubscribe/:unsubscribe_token",
+        C(user::me::unsubscribe_update_digest_by_token),
+    );
+    api_router.get("/email/unsubscribe/:token", C(user::me::unsubscribe_email));
+    api_router.post("/email/unsubscribe/:token", C(user::me::unsubscribe_email));
     api_router.put(
         "/users/:user_id/resend",
         C(user::me::regenerate_token_and_send),
     );
     api_router.get("/site_metadata", C(site_metadata::show_deployed_sha));
-    let api_router = Arc::new(R404(api_router));
+    api_router.get("/registry", C(site_metadata::show_registry));
+    api_router.post("/graphql", C(graphql::execute));
+
+    // Routes used by the registry administrators
+    api_router.get("/admin/flagged", C(admin::flagged));
+    api_router.put(
+        "/admin/findings/:finding_id/review",
+        C(admin::review_finding),
+    );
+    api_router.put(
+        "/admin/reports/:report_id/resolve",
+        C(admin::resolve_report),
+    );
+    api_router.get("/admin/download_anomalies", C(admin::download_anomalies));
+    api_router.put(
+        "/admin/download_anomalies/:anomaly_id/review",
+        C(admin::review_download_anomaly),
+    );
+    api_router.put("/admin/approved_names/:name", C(admin::approve_name));
+    api_router.get("/admin/reserved_names", C(admin::reserved_names));
+    api_router.put("/admin/reserved_names/:name", C(admin::reserve_name));
+    api_router.delete("/admin/reserved_names/:name", C(admin::unreserve_name));
+    api_router.post("/admin/reserved_name_patterns", C(admin::add_name_pattern));
+    api_router.delete(
+        "/admin/reserved_name_patterns/:pattern_id",
+        C(admin::remove_name_pattern),
+    );
+    api_router.get("/admin/claims", C(admin::claims));
+    api_router.put("/admin/claims/:claim_id/approve", C(admin::approve_claim));
+    api_router.put("/admin/claims/:claim_id/reject", C(admin::reject_claim));
+    api_router.get("/admin/pending_crates", C(admin::pending_crates));
+    api_router.put(
+        "/admin/pending_crates/:crate_id/approve",
+        C(admin::approve_crate),
+    );
+    api_router.put(
+        "/admin/pending_crates/:crate_id/reject",
+        C(admin::reject_crate),
+    );
+    api_router.put("/admin/crates/:crate_id/lock", C(admin::lock_crate));
+    api_router.delete("/admin/crates/:crate_id/lock", C(admin::unlock_crate));
+    api_router.put(
+        "/admin/crates/:crate_id/max_upload_size",
+        C(admin::set_max_upload_size),
+    );
+    api_router.delete(
+        "/admin/crates/:crate_id/max_upload_size",
+        C(admin::remove_max_upload_size),
+    );
+    api_router.put("/admin/crates/:crate_id/rename", C(admin::rename_crate));
+    api_router.delete("/admin/crates/:crate_id", C(admin::delete_crate));
+    api_router.delete("/admin/deleted_names/:name", C(admin::release_deleted_name));
+    api_router.put("/admin/users/:user_id/ban", C(admin::ban_user));
+    api_router.delete("/admin/users/:user_id/ban", C(admin::unban_user));
+    api_router.put("/admin/rate_limits/:user_id", C(admin::set_rate_limit));
+    api_router.delete("/admin/rate_limits/:user_id", C(admin::remove_rate_limit));
+    api_router.post("/admin/categories", C(admin::create_category));
+    api_router.put("/admin/categories/:category_id", C(admin::update_category));
+    api_router.put(
+        "/admin/categories/:category_id/merge",
+        C(admin::merge_category),
+    );
+    api_router.get("/admin/keyword_aliases", C(admin::keyword_aliases));
+    api_router.put("/admin/keyword_aliases/:alias", C(admin::add_keyword_alias));
+    api_router.delete(
+        "/admin/keyword_aliases/:alias",
+        C(admin::remove_keyword_alias),
+    );
+    api_router.get("/admin/jobs", C(admin::jobs));
+    api_router.put("/admin/jobs/:job_id/retry", C(admin::retry_job));
+    api_router.put(
+        "/admin/jobs/dead_letters/:dead_letter_id/retry",
+        C(admin::retry_dead_letter),
+    );
+    api_router.delete(
+        "/admin/jobs/dead_letters/:dead_letter_id",
+        C(admin::discard_dead_letter),
+    );
+    api_router
+}
 
-    let mut router = RouteBuilder::new();
+/// The pattern of the route that handled a request, e.g. `/crates/:crate_id`
+/// for routes under `/api/v1`. Stored in the request extensions, so requests
+/// can be counted per route.
+#[derive(Clone, Copy, Debug)]
+pub struct RoutePattern(pub &'static str);
 
-    // Mount the router under the /api/v1 path so we're at least somewhat at the
-    // liberty to change things in the future!
-    router.get("/api/v1/*path", R(Arc::clone(&api_router)));
-    router.put("/api/v1/*path", R(Arc::clone(&api_router)));
-    router.post("/api/v1/*path", R(Arc::clone(&api_router)));
-    router.head("/api/v1/*path", R(Arc::clone(&api_router)));
-    router.delete("/api/v1/*path", R(api_router));
+/// A `RouteBuilder` whose routes store their `RoutePattern` in the request.
+struct Routes {
+    builder: RouteBuilder,
+    /// The method and pattern of every route, to compare them with the
+    /// OpenAPI document.
+    routes: Vec<(&'static str, &'static str)>,
+}
 
-    router.get("/authorize_url", C(user::session::github_authorize));
-    router.get("/authorize", C(user::session::github_access_token));
-    router.delete("/logout", C(user::session::logout));
+impl Routes {
+    fn new() -> Self {
+        Routes {
+            builder: RouteBuilder::new(),
+            routes: Vec::new(),
+        }
+    }
 
-    // Only serve the local checkout of the git index in development mode.
-    // In production, for crates.io, cargo gets the index from
-    // https://github.com/rust-lang/crates.io-index directly.
-    if app.config.env == Env::Development {
-        let s = conduit_git_http_backend::Serve(app.git_repo_checkout.clone());
-        let s = Arc::new(s);
-        router.get("/git/index/*path", R(Arc::clone(&s)));
-        router.post("/git/index/*path", R(s));
+    fn get<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("get", pattern));
+        self.builder.get(pattern, Route(pattern, handler));
+    }
+
+    fn put<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("put", pattern));
+        self.builder.put(pattern, Route(pattern, handler));
     }
 
-    R404(router)
+    fn post<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("post", pattern));
+        self.builder.post(pattern, Route(pattern, handler));
+    }
+
+    fn head<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("head", pattern));
+        self.builder.head(pattern, Route(pattern, handler));
+    }
+
+    fn delete<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("delete", pattern));
+        self.builder.delete(pattern, Route(pattern, handler));
+    }
+
+    fn patch<H: Handler>(&mut self, pattern: &'static str, handler: H) {
+        self.routes.push(("patch", pattern));
+        self.builder
+            .map(Method::Patch, pattern, Route(pattern, handler));
+    }
+}
+
+/// Handles the `GET /api/openapi.json` route.
+fn openapi(_req: &mut dyn Request) -> CargoResult<Response> {
+    Ok(json_response(&crate::openapi::document()))
+}
+
+struct Route<H>(&'static str, H);
+
+impl<H: Handler> Handler for Route<H> {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        req.mut_extensions().insert(RoutePattern(self.0));
+        self.1.call(req)
+    }
 }
 
 struct C(pub fn(&mut dyn Request) -> CargoResult<Response>);
@@ -144,6 +493,16 @@ impl Handler for C {
     }
 }
 
+/// A handler of the v2 API, whose errors are all problem responses.
+struct V2(pub fn(&mut dyn Request) -> CargoResult<Response>);
+
+impl Handler for V2 {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let V2(f) = *self;
+        Ok(f(req).unwrap_or_else(v2::problem))
+    }
+}
+
 struct R<H>(pub Arc<H>);
 
 impl<H: Handler> Handler for R<H> {
@@ -154,6 +513,69 @@ impl<H: Handler> Handler for R<H> {
     }
 }
 
+/// Redirects requests for the old name of a renamed crate to its new name, see
+/// `models::CrateRename`. Only `GET` requests that would otherwise be answered
+/// with a 404 are looked up, so that the old names cost nothing otherwise.
+struct FollowRenames<H>(H);
+
+impl<H: Handler> Handler for FollowRenames<H> {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let FollowRenames(ref handler) = *self;
+        let response = handler.call(req)?;
+        if response.status.0 != 404 || req.method() != Method::Get {
+            return Ok(response);
+        }
+        match renamed_location(req) {
+            Ok(Some(location)) => {
+                let mut headers = HashMap::new();
+                headers.insert("Location".to_string(), vec![location]);
+                Ok(Response {
+                    status: (301, "Moved Permanently"),
+                    headers,
+                    body: Box::new(io::empty()),
+                })
+            }
+            Ok(None) => Ok(response),
+            Err(e) => Err(std_error(e)),
+        }
+    }
+}
+
+/// The path of the request with the old crate name replaced by the new one, or
+/// `None` if the route has no crate name or the crate wasn't renamed.
+fn renamed_location(req: &dyn Request) -> CargoResult<Option<String>> {
+    // The parameters are those of the route matched by the nested router
+    let old_name = match req.params().find("crate_id") {
+        Some(name) => name.to_string(),
+        None => return Ok(None),
+    };
+    let conn = req.db_conn()?;
+    let krate = match CrateRename::renamed_crate(&conn, &old_name)? {
+        Some((_, krate)) => krate,
+        None => return Ok(None),
+    };
+    // Private crates are reported as missing under their new name as well
+    if !krate.is_visible_to(req.app(), &conn, req.user().ok())? {
+        return Ok(None);
+    }
+
+    let mut segments = req.path().split('/').collect::<Vec<_>>();
+    let position = segments
+        .windows(2)
+        .position(|pair| pair[0] == "crates" && pair[1] == old_name);
+    let position = match position {
+        Some(position) => position + 1,
+        None => return Ok(None),
+    };
+    segments[position] = &krate.name;
+    let mut location = segments.join("/");
+    if let Some(query) = req.query_string() {
+        location.push('?');
+        location.push_str(query);
+    }
+    Ok(Some(location))
+}
+
 // Can't derive Debug because of RouteBuilder.
 #[allow(missing_debug_implementations)]
 pub struct R404(pub RouteBuilder);
@@ -171,10 +593,27 @@ impl Handler for R404 {
     }
 }
 
+/// Like `R404`, with a problem response for unknown routes.
+#[allow(missing_debug_implementations)]
+struct V2R404(RouteBuilder);
+
+impl Handler for V2R404 {
+    fn call(&self, req: &mut dyn Request) -> Result<Response, Box<dyn Error + Send>> {
+        let V2R404(ref router) = *self;
+        match router.recognize(&req.method(), req.path()) {
+            Ok(m) => {
+                req.mut_extensions().insert(m.params.clone());
+                m.handler.call(req)
+            }
+            Err(..) => Ok(v2::problem(Box::new(NotFound))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::util::errors::{bad_request, human, internal, NotFound, Unauthorized};
+    use crate::util::errors::{bad_request, human, internal, ErrorCode, NotFound, Unauthorized};
 
     use conduit_test::MockRequest;
     use diesel::result::Error as DieselError;
@@ -224,4 +663,38 @@ mod tests {
                 .is_err()
         );
     }
+
+    fn error_code(handler: C) -> String {
+        let mut req = MockRequest::new(::conduit::Method::Get, "/");
+        let mut body = Vec::new();
+        let mut response = handler.call(&mut req).unwrap();
+        response.body.write_body(&mut body).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["errors"][0]["code"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn error_codes_are_sent_with_the_details() {
+        assert_eq!(error_code(C(|_| Err(human("")))), "invalid_request");
+        assert_eq!(
+            error_code(C(|_| Err(ErrorCode::OwnerMismatch.human("")))),
+            "owner_mismatch"
+        );
+        assert_eq!(
+            error_code(C(|_| Err(ErrorCode::InvalidJson.bad_request("")))),
+            "invalid_json"
+        );
+        assert_eq!(error_code(C(|_| err(NotFound))), "not_found");
+    }
+    #[test]
+    fn openapi_document_describes_every_api_route() {
+        let mut registered = api_router().routes;
+        let mut described = crate::openapi::OPERATIONS
+            .iter()
+            .map(|&(method, path, _)| (method, path))
+            .collect::<Vec<_>>();
+        registered.sort();
+        described.sort();
+        assert_eq!(registered, described);
+    }
 }
@@ -19,6 +19,7 @@ pub struct Bucket {
     access_key: String,
     secret_key: String,
     proto: String,
+    endpoint: Option<String>,
 }
 
 impl Bucket {
@@ -35,9 +36,19 @@ impl Bucket {
             access_key,
             secret_key,
             proto: proto.to_string(),
+            endpoint: None,
         }
     }
 
+    /// Uses an S3 compatible service at `endpoint` instead of S3, e.g.
+    /// `storage.googleapis.com` for the XML API of Google Cloud Storage. The
+    /// bucket is addressed as a subdomain of the endpoint, and the region is
+    /// ignored.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Bucket {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+
     pub fn put<R: std::io::Read + Send + 'static>(
         &self,
         client: &reqwest::Client,
@@ -67,6 +78,24 @@ impl Bucket {
             .error_for_status()
     }
 
+    pub fn get(&self, client: &reqwest::Client, path: &str) -> reqwest::Result<reqwest::Response> {
+        let path = if path.starts_with('/') {
+            &path[1..]
+        } else {
+            path
+        };
+        let date = Utc::now().to_rfc2822();
+        let auth = self.auth("GET", &date, path, "", "");
+        let url = self.url(path);
+
+        client
+            .get(&url)
+            .header(header::DATE, date)
+            .header(header::AUTHORIZATION, auth)
+            .send()?
+            .error_for_status()
+    }
+
     pub fn delete(
         &self,
         client: &reqwest::Client,
@@ -89,7 +118,83 @@ impl Bucket {
             .error_for_status()
     }
 
+    /// Checks that the bucket exists and can be accessed with the credentials.
+    pub fn head_bucket(&self, client: &reqwest::Client) -> reqwest::Result<reqwest::Response> {
+        let date = Utc::now().to_rfc2822();
+        let auth = self.auth("HEAD", &date, "", "", "");
+        let url = self.url("");
+
+        client
+            .head(&url)
+            .header(header::DATE, date)
+            .header(header::AUTHORIZATION, auth)
+            .send()?
+            .error_for_status()
+    }
+
+    /// Lists the keys of the objects starting with `prefix` that sort after
+    /// `start_after`, in ascending order. Follows the pagination of the
+    /// listing until all keys were returned.
+    pub fn list(
+        &self,
+        client: &reqwest::Client,
+        prefix: &str,
+        start_after: Option<&str>,
+    ) -> reqwest::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut marker = start_after.map(String::from);
+        loop {
+            let date = Utc::now().to_rfc2822();
+            let auth = self.auth("GET", &date, "", "", "");
+            let mut query = vec![("prefix", prefix.to_string())];
+            if let Some(ref marker) = marker {
+                query.push(("marker", marker.clone()));
+            }
+
+            let body = client
+                .get(&self.url(""))
+                .query(&query)
+                .header(header::DATE, date)
+                .header(header::AUTHORIZATION, auth)
+                .send()?
+                .error_for_status()?
+                .text()?;
+
+            let page = xml_elements(&body, "Key");
+            let truncated = xml_elements(&body, "IsTruncated")
+                .first()
+                .map_or(false, |value| value == "true");
+            marker = page.last().cloned();
+            keys.extend(page);
+            if !truncated || marker.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Returns a URL that allows anyone holding it to `GET` the object at
+    /// `path` until the Unix timestamp `expires`, using query string
+    /// authentication.
+    pub fn presigned_url(&self, path: &str, expires: i64) -> String {
+        let path = if path.starts_with('/') {
+            &path[1..]
+        } else {
+            path
+        };
+        let signature = self.signature("GET", &expires.to_string(), path, "", "");
+        format!(
+            "{}?AWSAccessKeyId={}&Expires={}&Signature={}",
+            self.url(path),
+            self.access_key,
+            expires,
+            encode_query_value(&signature)
+        )
+    }
+
     pub fn host(&self) -> String {
+        if let Some(ref endpoint) = self.endpoint {
+            return format!("{}.{}", self.name, endpoint);
+        }
         format!(
             "{}.s3{}.amazonaws.com",
             self.name,
@@ -102,6 +207,18 @@ impl Bucket {
     }
 
     fn auth(&self, verb: &str, date: &str, path: &str, md5: &str, content_type: &str) -> String {
+        let signature = self.signature(verb, date, path, md5, content_type);
+        format!("AWS {}:{}", self.access_key, signature)
+    }
+
+    fn signature(
+        &self,
+        verb: &str,
+        date: &str,
+        path: &str,
+        md5: &str,
+        content_type: &str,
+    ) -> String {
         let string = format!(
             "{verb}\n{md5}\n{ty}\n{date}\n{headers}{resource}",
             verb = verb,
@@ -111,16 +228,49 @@ impl Bucket {
             headers = "",
             resource = format!("/{}/{}", self.name, path)
         );
-        let signature = {
-            let key = PKey::hmac(self.secret_key.as_bytes()).unwrap();
-            let mut signer = Signer::new(MessageDigest::sha1(), &key).unwrap();
-            signer.update(string.as_bytes()).unwrap();
-            encode(&signer.sign_to_vec().unwrap()[..])
-        };
-        format!("AWS {}:{}", self.access_key, signature)
+        let key = PKey::hmac(self.secret_key.as_bytes()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha1(), &key).unwrap();
+        signer.update(string.as_bytes()).unwrap();
+        encode(&signer.sign_to_vec().unwrap()[..])
     }
 
     fn url(&self, path: &str) -> String {
         format!("{}://{}/{}", self.proto, self.host(), path)
     }
 }
+
+/// The text of the elements named `name` in an XML response. Listings only
+/// contain simple elements, so this doesn't need a full XML parser.
+fn xml_elements(xml: &str, name: &str) -> Vec<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[(start + open.len())..];
+        let end = match rest.find(&close) {
+            Some(end) => end,
+            None => break,
+        };
+        elements.push(unescape_xml(&rest[..end]));
+        rest = &rest[(end + close.len())..];
+    }
+    elements
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Percent-encodes the characters of a base64 string that aren't allowed in a
+/// query string value.
+fn encode_query_value(value: &str) -> String {
+    value
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D")
+}
@@ -0,0 +1,346 @@
+//! Enqueues recurring jobs on cron schedules, so the background worker doesn't
+//! depend on an external scheduler.
+//!
+//! The schedules are read from `scheduler/schedule.toml`, or from the file
+//! named by the `JOB_SCHEDULE_FILE` environment variable. Every worker checks
+//! them, and the `scheduled_jobs` table makes sure a job is only enqueued once
+//! per scheduled minute, however many workers are running.
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use diesel::prelude::*;
+use diesel::sql_types::{Text, Timestamp};
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs;
+use std::str::FromStr;
+use swirl::{Job, PerformError};
+
+use crate::logging;
+use crate::tasks;
+use crate::util::{internal, CargoError, CargoResult};
+
+/// How many missed minutes are caught up on, e.g. after the database couldn't
+/// be reached for a while.
+const MAX_CATCH_UP_MINUTES: i64 = 60;
+
+type EnqueueFn = fn(&PgConnection) -> Result<(), PerformError>;
+
+/// Returns how to enqueue the job with the given name, or `None` if it can't
+/// be scheduled.
+fn enqueue_fn(name: &str) -> Option<EnqueueFn> {
+    let enqueue: EnqueueFn = match name {
+        "update_downloads" => |conn| tasks::update_downloads().enqueue(conn),
+        "ingest_cdn_logs" => |conn| tasks::ingest_cdn_logs().enqueue(conn),
+        "rollup_downloads" => |conn| tasks::rollup_downloads().enqueue(conn),
+        "refresh_summary" => |conn| tasks::refresh_summary().enqueue(conn),
+        "update_trending_crates" => |conn| tasks::update_trending_crates().enqueue(conn),
+        "update_popularity" => |conn| tasks::update_popularity().enqueue(conn),
+        "update_version_adoption" => |conn| tasks::update_version_adoption().enqueue(conn),
+        "update_docs_status" => |conn| tasks::update_docs_status().enqueue(conn),
+        "send_update_digests" => |conn| tasks::send_update_digests().enqueue(conn),
+        "backfill_checksums" => |conn| tasks::backfill_checksums().enqueue(conn),
+        "backfill_default_versions" => |conn| tasks::backfill_default_versions().enqueue(conn),
+        "apply_owner_removals" => |conn| tasks::apply_owner_removals().enqueue(conn),
+        "sync_team_memberships" => |conn| tasks::sync_team_memberships().enqueue(conn),
+        "notify_unused_tokens" => |conn| tasks::notify_unused_tokens().enqueue(conn),
+        "transfer_inactive_crates" => |conn| tasks::transfer_inactive_crates().enqueue(conn),
+        "delete_stale_publish_sessions" => {
+            |conn| tasks::delete_stale_publish_sessions().enqueue(conn)
+        }
+        "dump_db" => |conn| {
+            tasks::dump_db(crate::env("DATABASE_URL"), String::from("db-dump.tar.gz")).enqueue(conn)
+        },
+        _ => return None,
+    };
+    Some(enqueue)
+}
+
+/// The schedules of the recurring jobs.
+#[derive(Debug)]
+pub struct Scheduler {
+    jobs: Vec<(String, Schedule)>,
+    /// The last minute the schedules were checked for
+    checked_until: NaiveDateTime,
+}
+
+impl Scheduler {
+    /// Reads the schedules, see the module documentation.
+    pub fn from_env(now: NaiveDateTime) -> CargoResult<Self> {
+        let config = match dotenv::var("JOB_SCHEDULE_FILE") {
+            Ok(path) => fs::read_to_string(path)?,
+            Err(_) => String::from(include_str!("scheduler/schedule.toml")),
+        };
+        Self::new(&config, now)
+    }
+
+    /// Parses a TOML table mapping job names to cron expressions. The first
+    /// call to `enqueue_due_jobs` starts with the minute of `now`.
+    pub fn new(config: &str, now: NaiveDateTime) -> CargoResult<Self> {
+        let config: BTreeMap<String, String> = toml::from_str(config)?;
+        let jobs = config
+            .into_iter()
+            .map(|(name, expression)| {
+                if enqueue_fn(&name).is_none() {
+                    return Err(internal(&format_args!("`{}` cannot be scheduled", name)));
+                }
+                let schedule = expression.parse().map_err(|e| {
+                    internal(&format_args!("invalid schedule for `{}`: {}", name, e))
+                })?;
+                Ok((name, schedule))
+            })
+            .collect::<CargoResult<_>>()?;
+
+        Ok(Scheduler {
+            jobs,
+            checked_until: start_of_minute(now) - Duration::minutes(1),
+        })
+    }
+
+    /// Enqueues the jobs that were due in the minutes since the last call, up
+    /// to `now`. A job that was due several times is only enqueued once.
+    pub fn enqueue_due_jobs(&mut self, conn: &PgConnection, now: NaiveDateTime) -> CargoResult<()> {
+        let now = start_of_minute(now);
+        let start = cmp::max(
+            self.checked_until,
+            now - Duration::minutes(MAX_CATCH_UP_MINUTES),
+        ) + Duration::minutes(1);
+
+        for (name, schedule) in &self.jobs {
+            if let Some(due) = schedule.last_match(start, now) {
+                enqueue(conn, name, due)?;
+            }
+        }
+        self.checked_until = now;
+        Ok(())
+    }
+}
+
+/// Enqueues a job, unless a worker already did so for the same minute.
+fn enqueue(conn: &PgConnection, name: &str, due: NaiveDateTime) -> CargoResult<()> {
+    conn.transaction(|| {
+        let claimed = diesel::sql_query(
+            "INSERT INTO scheduled_jobs (name, last_enqueued_at) VALUES ($1, $2) \
+             ON CONFLICT (name) DO UPDATE SET last_enqueued_at = excluded.last_enqueued_at \
+             WHERE scheduled_jobs.last_enqueued_at < excluded.last_enqueued_at",
+        )
+        .bind::<Text, _>(name)
+        .bind::<Timestamp, _>(due)
+        .execute(conn)?;
+
+        if claimed == 0 {
+            return Ok(());
+        }
+        if let Some(enqueue) = enqueue_fn(name) {
+            enqueue(conn).map_err(|e| CargoError::from_std_error(e))?;
+            logging::info("enqueued scheduled job", json!({ "job": name, "due": due }));
+        }
+        Ok(())
+    })
+}
+
+fn start_of_minute(time: NaiveDateTime) -> NaiveDateTime {
+    time.date().and_hms(time.hour(), time.minute(), 0)
+}
+
+/// A cron expression with the fields minute, hour, day of month, month and
+/// day of week. Each field is `*`, a number or a range like `1-5`, optionally
+/// followed by a step like `*/10`, or a comma separated list of those. Days of
+/// the week count from Sunday, which is both 0 and 7.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether both day fields are restricted, in which case a day matches if
+    /// either of them does, like in cron
+    either_day: bool,
+}
+
+impl Schedule {
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        let contains = |set: u64, value: u32| set & (1 << value) != 0;
+        let day_of_month = contains(self.days_of_month, time.day());
+        let day_of_week = contains(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = if self.either_day {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+
+        day && contains(self.minutes, time.minute())
+            && contains(self.hours, time.hour())
+            && contains(self.months, time.month())
+    }
+
+    /// Returns the last matching minute from `start` to `end`, both inclusive.
+    fn last_match(&self, start: NaiveDateTime, end: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut minute = end;
+        while minute >= start {
+            if self.matches(minute) {
+                return Some(minute);
+            }
+            minute -= Duration::minutes(1);
+        }
+        None
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, String> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+}
+
+/// Parses a field of a cron expression into the set of values it matches,
+/// with a bit for every value.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |s: &str| {
+        s.parse::<u32>()
+            .map_err(|_| format!("invalid number `{}`", s))
+    };
+
+    let mut values = 0;
+    for part in field.split(',') {
+        let mut split = part.splitn(2, '/');
+        let range = split.next().unwrap();
+        let step = match split.next() {
+            Some(step) => number(step)?,
+            None => 1,
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else {
+            let mut bounds = range.splitn(2, '-');
+            let start = number(bounds.next().unwrap())?;
+            match bounds.next() {
+                Some(end) => (start, number(end)?),
+                None if part.contains('/') => (start, max),
+                None => (start, start),
+            }
+        };
+        if step == 0 || start < min || start > end || end > max {
+            return Err(format!("`{}` is out of range", part));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{schema::background_jobs, test_util::pg_connection};
+    use chrono::NaiveDate;
+
+    fn time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // December 1st 2019 is a Sunday
+        NaiveDate::from_ymd(2019, 12, day).and_hms(hour, minute, 0)
+    }
+
+    fn schedule(expression: &str) -> Schedule {
+        expression.parse().unwrap()
+    }
+
+    #[test]
+    fn fields_match_their_values() {
+        let weekdays = schedule("*/15 9-17 * * 1-5");
+        assert!(weekdays.matches(time(2, 9, 0)));
+        assert!(weekdays.matches(time(2, 17, 45)));
+        assert!(!weekdays.matches(time(2, 9, 20)));
+        assert!(!weekdays.matches(time(2, 18, 0)));
+        assert!(!weekdays.matches(time(7, 9, 0)));
+
+        let sundays = schedule("30 2 * * 7");
+        assert!(sundays.matches(time(1, 2, 30)));
+        assert!(sundays.matches(time(8, 2, 30)));
+        assert!(!sundays.matches(time(2, 2, 30)));
+
+        let lists = schedule("0,5/20 0 * 1,12 *");
+        assert!(lists.matches(time(3, 0, 0)));
+        assert!(lists.matches(time(3, 0, 45)));
+        assert!(!lists.matches(time(3, 0, 20)));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either_day() {
+        let either = schedule("0 0 3 * 0");
+        assert!(either.matches(time(1, 0, 0)));
+        assert!(either.matches(time(3, 0, 0)));
+        assert!(!either.matches(time(2, 0, 0)));
+
+        let day_of_month = schedule("0 0 3 * *");
+        assert!(!day_of_month.matches(time(1, 0, 0)));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expression in &[
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(expression.parse::<Schedule>().is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn schedules_are_validated() {
+        let now = time(1, 0, 0);
+        Scheduler::new(include_str!("scheduler/schedule.toml"), now).unwrap();
+        assert!(Scheduler::new("add_crate = \"* * * * *\"", now).is_err());
+        assert!(Scheduler::new("update_downloads = \"* * *\"", now).is_err());
+    }
+
+    #[test]
+    fn due_jobs_are_enqueued_once() {
+        let conn = pg_connection();
+        let count = || {
+            background_jobs::table
+                .filter(background_jobs::job_type.eq("update_downloads"))
+                .count()
+                .get_result::<i64>(&conn)
+                .unwrap()
+        };
+
+        let config = "update_downloads = \"*/10 * * * *\"";
+        let mut first = Scheduler::new(config, time(2, 10, 5)).unwrap();
+        let mut second = Scheduler::new(config, time(2, 10, 5)).unwrap();
+        first.enqueue_due_jobs(&conn, time(2, 10, 9)).unwrap();
+        assert_eq!(count(), 0);
+
+        first.enqueue_due_jobs(&conn, time(2, 10, 10)).unwrap();
+        second.enqueue_due_jobs(&conn, time(2, 10, 10)).unwrap();
+        assert_eq!(count(), 1);
+
+        // Missed runs are caught up on once
+        first.enqueue_due_jobs(&conn, time(2, 10, 45)).unwrap();
+        assert_eq!(count(), 2);
+    }
+}
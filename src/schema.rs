@@ -1,5 +1,87 @@
 #![allow(unused_imports)]
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `admin_actions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    admin_actions (id) {
+        /// The `id` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `admin_id` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        admin_id -> Int4,
+        /// The `action` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
+        /// The `crate_name` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Nullable<Varchar>,
+        /// The `user_id` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Int4>,
+        /// The `time` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        time -> Timestamp,
+        /// The `detail` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        detail -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `api_token_usage` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    api_token_usage (api_token_id, date) {
+        /// The `api_token_id` column of the `api_token_usage` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Int4,
+        /// The `date` column of the `api_token_usage` table.
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        date -> Date,
+        /// The `requests` column of the `api_token_usage` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requests -> Int4,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -50,6 +132,36 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         revoked -> Bool,
+        /// The `last_used_ip` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_ip -> Nullable<Varchar>,
+        /// The `last_used_user_agent` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_user_agent -> Nullable<Varchar>,
+        /// The `unused_notified_at` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        unused_notified_at -> Nullable<Timestamp>,
+        /// The `daily_quota` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        daily_quota -> Nullable<Int4>,
+        /// The `allowed_ips` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        allowed_ips -> Array<Text>,
     }
 }
 
@@ -57,46 +169,87 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `background_jobs` table.
+    /// Representation of the `approved_crate_names` table.
     ///
     /// (Automatically generated by Diesel.)
-    background_jobs (id) {
-        /// The `id` column of the `background_jobs` table.
+    approved_crate_names (name) {
+        /// The `name` column of the `approved_crate_names` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `approved_by` column of the `approved_crate_names` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        approved_by -> Int4,
+        /// The `created_at` column of the `approved_crate_names` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `background_job_dead_letters` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    background_job_dead_letters (id) {
+        /// The `id` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int8,
-        /// The `job_type` column of the `background_jobs` table.
+        /// The `job_id` column of the `background_job_dead_letters` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        job_id -> Int8,
+        /// The `job_type` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Text`.
         ///
         /// (Automatically generated by Diesel.)
         job_type -> Text,
-        /// The `data` column of the `background_jobs` table.
+        /// The `data` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Jsonb`.
         ///
         /// (Automatically generated by Diesel.)
         data -> Jsonb,
-        /// The `retries` column of the `background_jobs` table.
+        /// The `retries` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         retries -> Int4,
-        /// The `last_retry` column of the `background_jobs` table.
+        /// The `created_at` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        last_retry -> Timestamp,
-        /// The `created_at` column of the `background_jobs` table.
+        created_at -> Timestamp,
+        /// The `dead_at` column of the `background_job_dead_letters` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        created_at -> Timestamp,
+        dead_at -> Timestamp,
+        /// The `trace_id` column of the `background_job_dead_letters` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        trace_id -> Nullable<Text>,
     }
 }
 
@@ -104,28 +257,34 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `badges` table.
+    /// Representation of the `background_job_policies` table.
     ///
     /// (Automatically generated by Diesel.)
-    badges (crate_id, badge_type) {
-        /// The `crate_id` column of the `badges` table.
+    background_job_policies (job_type) {
+        /// The `job_type` column of the `background_job_policies` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        job_type -> Text,
+        /// The `priority` column of the `background_job_policies` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `badge_type` column of the `badges` table.
+        priority -> Int4,
+        /// The `max_attempts` column of the `background_job_policies` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Nullable<Int4>`.
         ///
         /// (Automatically generated by Diesel.)
-        badge_type -> Varchar,
-        /// The `attributes` column of the `badges` table.
+        max_attempts -> Nullable<Int4>,
+        /// The `backoff_seconds` column of the `background_job_policies` table.
         ///
-        /// Its SQL type is `Jsonb`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        attributes -> Jsonb,
+        backoff_seconds -> Int4,
     }
 }
 
@@ -133,46 +292,52 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `categories` table.
+    /// Representation of the `background_jobs` table.
     ///
     /// (Automatically generated by Diesel.)
-    categories (id) {
-        /// The `id` column of the `categories` table.
+    background_jobs (id) {
+        /// The `id` column of the `background_jobs` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `category` column of the `categories` table.
+        id -> Int8,
+        /// The `job_type` column of the `background_jobs` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Text`.
         ///
         /// (Automatically generated by Diesel.)
-        category -> Varchar,
-        /// The `slug` column of the `categories` table.
+        job_type -> Text,
+        /// The `data` column of the `background_jobs` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Jsonb`.
         ///
         /// (Automatically generated by Diesel.)
-        slug -> Varchar,
-        /// The `description` column of the `categories` table.
+        data -> Jsonb,
+        /// The `retries` column of the `background_jobs` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        description -> Varchar,
-        /// The `crates_cnt` column of the `categories` table.
+        retries -> Int4,
+        /// The `last_retry` column of the `background_jobs` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        crates_cnt -> Int4,
-        /// The `created_at` column of the `categories` table.
+        last_retry -> Timestamp,
+        /// The `created_at` column of the `background_jobs` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
+        /// The `trace_id` column of the `background_jobs` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        trace_id -> Nullable<Text>,
     }
 }
 
@@ -180,34 +345,28 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `crate_owner_invitations` table.
+    /// Representation of the `badges` table.
     ///
     /// (Automatically generated by Diesel.)
-    crate_owner_invitations (invited_user_id, crate_id) {
-        /// The `invited_user_id` column of the `crate_owner_invitations` table.
-        ///
-        /// Its SQL type is `Int4`.
-        ///
-        /// (Automatically generated by Diesel.)
-        invited_user_id -> Int4,
-        /// The `invited_by_user_id` column of the `crate_owner_invitations` table.
+    badges (crate_id, badge_type) {
+        /// The `crate_id` column of the `badges` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        invited_by_user_id -> Int4,
-        /// The `crate_id` column of the `crate_owner_invitations` table.
+        crate_id -> Int4,
+        /// The `badge_type` column of the `badges` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `created_at` column of the `crate_owner_invitations` table.
+        badge_type -> Varchar,
+        /// The `attributes` column of the `badges` table.
         ///
-        /// Its SQL type is `Timestamp`.
+        /// Its SQL type is `Jsonb`.
         ///
         /// (Automatically generated by Diesel.)
-        created_at -> Timestamp,
+        attributes -> Jsonb,
     }
 }
 
@@ -215,58 +374,52 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `crate_owners` table.
+    /// Representation of the `build_verifications` table.
     ///
     /// (Automatically generated by Diesel.)
-    crate_owners (crate_id, owner_id, owner_kind) {
-        /// The `crate_id` column of the `crate_owners` table.
+    build_verifications (version_id, verifier_id) {
+        /// The `version_id` column of the `build_verifications` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `owner_id` column of the `crate_owners` table.
+        version_id -> Int4,
+        /// The `verifier_id` column of the `build_verifications` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        owner_id -> Int4,
-        /// The `created_at` column of the `crate_owners` table.
+        verifier_id -> Int4,
+        /// The `reproducible` column of the `build_verifications` table.
         ///
-        /// Its SQL type is `Timestamp`.
+        /// Its SQL type is `Bool`.
         ///
         /// (Automatically generated by Diesel.)
-        created_at -> Timestamp,
-        /// The `created_by` column of the `crate_owners` table.
+        reproducible -> Bool,
+        /// The `checksum` column of the `build_verifications` table.
         ///
-        /// Its SQL type is `Nullable<Int4>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        created_by -> Nullable<Int4>,
-        /// The `deleted` column of the `crate_owners` table.
+        checksum -> Varchar,
+        /// The `log_url` column of the `build_verifications` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        deleted -> Bool,
-        /// The `updated_at` column of the `crate_owners` table.
+        log_url -> Nullable<Varchar>,
+        /// The `created_at` column of the `build_verifications` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        updated_at -> Timestamp,
-        /// The `owner_kind` column of the `crate_owners` table.
-        ///
-        /// Its SQL type is `Int4`.
-        ///
-        /// (Automatically generated by Diesel.)
-        owner_kind -> Int4,
-        /// The `email_notifications` column of the `crate_owners` table.
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `build_verifications` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        email_notifications -> Bool,
+        updated_at -> Timestamp,
     }
 }
 
@@ -274,82 +427,81 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `crates` table.
+    /// Representation of the `build_verifiers` table.
     ///
     /// (Automatically generated by Diesel.)
-    crates (id) {
-        /// The `id` column of the `crates` table.
+    build_verifiers (id) {
+        /// The `id` column of the `build_verifiers` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `name` column of the `crates` table.
+        /// The `name` column of the `build_verifiers` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
         name -> Varchar,
-        /// The `updated_at` column of the `crates` table.
-        ///
-        /// Its SQL type is `Timestamp`.
-        ///
-        /// (Automatically generated by Diesel.)
-        updated_at -> Timestamp,
-        /// The `created_at` column of the `crates` table.
+        /// The `created_at` column of the `build_verifiers` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
-        /// The `downloads` column of the `crates` table.
+        /// The `secret_hash` column of the `build_verifiers` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        downloads -> Int4,
-        /// The `description` column of the `crates` table.
-        ///
-        /// Its SQL type is `Nullable<Varchar>`.
-        ///
-        /// (Automatically generated by Diesel.)
-        description -> Nullable<Varchar>,
-        /// The `homepage` column of the `crates` table.
+        secret_hash -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `categories` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    categories (id) {
+        /// The `id` column of the `categories` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        homepage -> Nullable<Varchar>,
-        /// The `documentation` column of the `crates` table.
+        id -> Int4,
+        /// The `category` column of the `categories` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        documentation -> Nullable<Varchar>,
-        /// The `readme` column of the `crates` table.
+        category -> Varchar,
+        /// The `slug` column of the `categories` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        readme -> Nullable<Varchar>,
-        /// The `textsearchable_index_col` column of the `crates` table.
+        slug -> Varchar,
+        /// The `description` column of the `categories` table.
         ///
-        /// Its SQL type is `Tsvector`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        textsearchable_index_col -> Tsvector,
-        /// The `repository` column of the `crates` table.
+        description -> Varchar,
+        /// The `crates_cnt` column of the `categories` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        repository -> Nullable<Varchar>,
-        /// The `max_upload_size` column of the `crates` table.
+        crates_cnt -> Int4,
+        /// The `created_at` column of the `categories` table.
         ///
-        /// Its SQL type is `Nullable<Int4>`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        max_upload_size -> Nullable<Int4>,
+        created_at -> Timestamp,
     }
 }
 
@@ -357,22 +509,28 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `crates_categories` table.
+    /// Representation of the `category_redirects` table.
     ///
     /// (Automatically generated by Diesel.)
-    crates_categories (crate_id, category_id) {
-        /// The `crate_id` column of the `crates_categories` table.
+    category_redirects (slug) {
+        /// The `slug` column of the `category_redirects` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `category_id` column of the `crates_categories` table.
+        slug -> Varchar,
+        /// The `category_id` column of the `category_redirects` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         category_id -> Int4,
+        /// The `created_at` column of the `category_redirects` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
 
@@ -380,22 +538,28 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `crates_keywords` table.
+    /// Representation of the `cdn_log_files` table.
     ///
     /// (Automatically generated by Diesel.)
-    crates_keywords (crate_id, keyword_id) {
-        /// The `crate_id` column of the `crates_keywords` table.
+    cdn_log_files (path) {
+        /// The `path` column of the `cdn_log_files` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `keyword_id` column of the `crates_keywords` table.
+        path -> Varchar,
+        /// The `downloads` column of the `cdn_log_files` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        keyword_id -> Int4,
+        downloads -> Int4,
+        /// The `processed_at` column of the `cdn_log_files` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        processed_at -> Timestamp,
     }
 }
 
@@ -403,64 +567,109 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `dependencies` table.
+    /// Representation of the `crate_acls` table.
     ///
     /// (Automatically generated by Diesel.)
-    dependencies (id) {
-        /// The `id` column of the `dependencies` table.
+    crate_acls (crate_id, user_id) {
+        /// The `crate_id` column of the `crate_acls` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `version_id` column of the `dependencies` table.
+        crate_id -> Int4,
+        /// The `user_id` column of the `crate_acls` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        version_id -> Int4,
-        /// The `crate_id` column of the `dependencies` table.
+        user_id -> Int4,
+        /// The `created_at` column of the `crate_acls` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_download_rollups` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_download_rollups (crate_id, period, period_start) {
+        /// The `crate_id` column of the `crate_download_rollups` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         crate_id -> Int4,
-        /// The `req` column of the `dependencies` table.
+        /// The `period` column of the `crate_download_rollups` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        req -> Varchar,
-        /// The `optional` column of the `dependencies` table.
+        period -> Varchar,
+        /// The `period_start` column of the `crate_download_rollups` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Date`.
         ///
         /// (Automatically generated by Diesel.)
-        optional -> Bool,
-        /// The `default_features` column of the `dependencies` table.
+        period_start -> Date,
+        /// The `downloads` column of the `crate_download_rollups` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
-        default_features -> Bool,
-        /// The `features` column of the `dependencies` table.
+        downloads -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_funding` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_funding (crate_id, platform) {
+        /// The `crate_id` column of the `crate_funding` table.
         ///
-        /// Its SQL type is `Array<Text>`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        features -> Array<Text>,
-        /// The `target` column of the `dependencies` table.
+        crate_id -> Int4,
+        /// The `platform` column of the `crate_funding` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        target -> Nullable<Varchar>,
-        /// The `kind` column of the `dependencies` table.
+        platform -> Varchar,
+        /// The `url` column of the `crate_funding` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        kind -> Int4,
+        url -> Varchar,
+    }
+}
+
+table! {
+    /// Representation of the `crate_license_identifiers` view.
+    ///
+    /// The license identifiers in the license expressions of the non-yanked
+    /// versions of each crate, with a row per version.
+    crate_license_identifiers (crate_id, license) {
+        /// The `crate_id` column of the `crate_license_identifiers` view.
+        ///
+        /// Its SQL type is `Integer`.
+        crate_id -> Integer,
+        /// The `license` column of the `crate_license_identifiers` view.
+        ///
+        /// Its SQL type is `Text`.
+        license -> Text,
     }
 }
 
@@ -468,46 +677,28 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `emails` table.
+    /// Representation of the `crate_list_follows` table.
     ///
     /// (Automatically generated by Diesel.)
-    emails (id) {
-        /// The `id` column of the `emails` table.
+    crate_list_follows (list_id, user_id) {
+        /// The `list_id` column of the `crate_list_follows` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `user_id` column of the `emails` table.
+        list_id -> Int4,
+        /// The `user_id` column of the `crate_list_follows` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         user_id -> Int4,
-        /// The `email` column of the `emails` table.
-        ///
-        /// Its SQL type is `Varchar`.
-        ///
-        /// (Automatically generated by Diesel.)
-        email -> Varchar,
-        /// The `verified` column of the `emails` table.
-        ///
-        /// Its SQL type is `Bool`.
-        ///
-        /// (Automatically generated by Diesel.)
-        verified -> Bool,
-        /// The `token` column of the `emails` table.
+        /// The `created_at` column of the `crate_list_follows` table.
         ///
-        /// Its SQL type is `Text`.
-        ///
-        /// (Automatically generated by Diesel.)
-        token -> Text,
-        /// The `token_generated_at` column of the `emails` table.
-        ///
-        /// Its SQL type is `Nullable<Timestamp>`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        token_generated_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
     }
 }
 
@@ -515,22 +706,28 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `follows` table.
+    /// Representation of the `crate_list_items` table.
     ///
     /// (Automatically generated by Diesel.)
-    follows (user_id, crate_id) {
-        /// The `user_id` column of the `follows` table.
+    crate_list_items (list_id, crate_id) {
+        /// The `list_id` column of the `crate_list_items` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        user_id -> Int4,
-        /// The `crate_id` column of the `follows` table.
+        list_id -> Int4,
+        /// The `crate_id` column of the `crate_list_items` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         crate_id -> Int4,
+        /// The `created_at` column of the `crate_list_items` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
 
@@ -538,51 +735,46 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `keywords` table.
+    /// Representation of the `crate_lists` table.
     ///
     /// (Automatically generated by Diesel.)
-    keywords (id) {
-        /// The `id` column of the `keywords` table.
+    crate_lists (id) {
+        /// The `id` column of the `crate_lists` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `keyword` column of the `keywords` table.
+        /// The `user_id` column of the `crate_lists` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `name` column of the `crate_lists` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        keyword -> Varchar,
-        /// The `crates_cnt` column of the `keywords` table.
+        name -> Varchar,
+        /// The `description` column of the `crate_lists` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        crates_cnt -> Int4,
-        /// The `created_at` column of the `keywords` table.
+        description -> Varchar,
+        /// The `created_at` column of the `crate_lists` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
-    }
-}
-
-table! {
-    use diesel::sql_types::*;
-    use diesel_full_text_search::{TsVector as Tsvector};
-
-    /// Representation of the `metadata` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    metadata (total_downloads) {
-        /// The `total_downloads` column of the `metadata` table.
+        /// The `updated_at` column of the `crate_lists` table.
         ///
-        /// Its SQL type is `Int8`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        total_downloads -> Int8,
+        updated_at -> Timestamp,
     }
 }
 
@@ -590,51 +782,46 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `publish_limit_buckets` table.
+    /// Representation of the `crate_owner_actions` table.
     ///
     /// (Automatically generated by Diesel.)
-    publish_limit_buckets (user_id) {
-        /// The `user_id` column of the `publish_limit_buckets` table.
+    crate_owner_actions (id) {
+        /// The `id` column of the `crate_owner_actions` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        user_id -> Int4,
-        /// The `tokens` column of the `publish_limit_buckets` table.
+        id -> Int4,
+        /// The `crate_id` column of the `crate_owner_actions` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        tokens -> Int4,
-        /// The `last_refill` column of the `publish_limit_buckets` table.
+        crate_id -> Int4,
+        /// The `owner_id` column of the `crate_owner_actions` table.
         ///
-        /// Its SQL type is `Timestamp`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        last_refill -> Timestamp,
-    }
-}
-
-table! {
-    use diesel::sql_types::*;
-    use diesel_full_text_search::{TsVector as Tsvector};
-
-    /// Representation of the `publish_rate_overrides` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    publish_rate_overrides (user_id) {
-        /// The `user_id` column of the `publish_rate_overrides` table.
+        owner_id -> Int4,
+        /// The `action` column of the `crate_owner_actions` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        user_id -> Int4,
-        /// The `burst` column of the `publish_rate_overrides` table.
+        action -> Int4,
+        /// The `detail` column of the `crate_owner_actions` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        burst -> Int4,
+        detail -> Nullable<Varchar>,
+        /// The `time` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        time -> Timestamp,
     }
 }
 
@@ -642,22 +829,1272 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `readme_renderings` table.
+    /// Representation of the `crate_owner_invitations` table.
     ///
     /// (Automatically generated by Diesel.)
-    readme_renderings (version_id) {
-        /// The `version_id` column of the `readme_renderings` table.
+    crate_owner_invitations (invited_user_id, crate_id) {
+        /// The `invited_user_id` column of the `crate_owner_invitations` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        version_id -> Int4,
-        /// The `rendered_at` column of the `readme_renderings` table.
+        invited_user_id -> Int4,
+        /// The `invited_by_user_id` column of the `crate_owner_invitations` table.
         ///
-        /// Its SQL type is `Timestamp`.
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_by_user_id -> Int4,
+        /// The `crate_id` column of the `crate_owner_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `created_at` column of the `crate_owner_invitations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owners` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owners (crate_id, owner_id, owner_kind) {
+        /// The `crate_id` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `owner_id` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner_id -> Int4,
+        /// The `created_at` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `created_by` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Nullable<Int4>,
+        /// The `deleted` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted -> Bool,
+        /// The `updated_at` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `owner_kind` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner_kind -> Int4,
+        /// The `email_notifications` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email_notifications -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_renames` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_renames (id) {
+        /// The `id` column of the `crate_renames` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `old_name` column of the `crate_renames` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        old_name -> Varchar,
+        /// The `crate_id` column of the `crate_renames` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `renamed_by` column of the `crate_renames` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        renamed_by -> Int4,
+        /// The `created_at` column of the `crate_renames` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_reports` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_reports (id) {
+        /// The `id` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `reporter_id` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reporter_id -> Nullable<Int4>,
+        /// The `category` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category -> Int4,
+        /// The `description` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Varchar,
+        /// The `resolved` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        resolved -> Bool,
+        /// The `created_at` column of the `crate_reports` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_successors` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_successors (crate_id) {
+        /// The `crate_id` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `successor_id` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        successor_id -> Int4,
+        /// The `designated_by` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        designated_by -> Int4,
+        /// The `inactive_months` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        inactive_months -> Int4,
+        /// The `accepted` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        accepted -> Bool,
+        /// The `created_at` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `warned_at` column of the `crate_successors` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        warned_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crates` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crates (id) {
+        /// The `id` column of the `crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `crates` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `updated_at` column of the `crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `created_at` column of the `crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `downloads` column of the `crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int4,
+        /// The `description` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Nullable<Varchar>,
+        /// The `homepage` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        homepage -> Nullable<Varchar>,
+        /// The `documentation` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        documentation -> Nullable<Varchar>,
+        /// The `readme` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        readme -> Nullable<Varchar>,
+        /// The `textsearchable_index_col` column of the `crates` table.
+        ///
+        /// Its SQL type is `Tsvector`.
+        ///
+        /// (Automatically generated by Diesel.)
+        textsearchable_index_col -> Tsvector,
+        /// The `repository` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository -> Nullable<Varchar>,
+        /// The `max_upload_size` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_upload_size -> Nullable<Int4>,
+        /// The `visibility` column of the `crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        visibility -> Int4,
+        /// The `locked` column of the `crates` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locked -> Bool,
+        /// The `max_version` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_version -> Nullable<Varchar>,
+        /// The `max_stable_version` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_stable_version -> Nullable<Varchar>,
+        /// The `links` column of the `crates` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        links -> Jsonb,
+        /// The `repository_verified` column of the `crates` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository_verified -> Bool,
+        /// The `popularity` column of the `crates` table.
+        ///
+        /// Its SQL type is `Float8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        popularity -> Float8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crates_categories` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crates_categories (crate_id, category_id) {
+        /// The `crate_id` column of the `crates_categories` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `category_id` column of the `crates_categories` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crates_keywords` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crates_keywords (crate_id, keyword_id) {
+        /// The `crate_id` column of the `crates_keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `keyword_id` column of the `crates_keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `deleted_crates` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    deleted_crates (id) {
+        /// The `id` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `created_at` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `downloads` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int4,
+        /// The `deleted_by` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_by -> Int4,
+        /// The `deleted_at` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Timestamp,
+        /// The `available_at` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        available_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `deleted_versions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    deleted_versions (id) {
+        /// The `id` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `num` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        num -> Varchar,
+        /// The `published_at` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        published_at -> Timestamp,
+        /// The `downloads` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int4,
+        /// The `deleted_by` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_by -> Int4,
+        /// The `deleted_at` column of the `deleted_versions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `dependencies` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    dependencies (id) {
+        /// The `id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `version_id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `crate_id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `req` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        req -> Varchar,
+        /// The `optional` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        optional -> Bool,
+        /// The `default_features` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        default_features -> Bool,
+        /// The `features` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        features -> Array<Text>,
+        /// The `target` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target -> Nullable<Varchar>,
+        /// The `kind` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `dependency_graphs` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    dependency_graphs (version_id, depth) {
+        /// The `version_id` column of the `dependency_graphs` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `depth` column of the `dependency_graphs` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        depth -> Int4,
+        /// The `graph` column of the `dependency_graphs` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        graph -> Jsonb,
+        /// The `resolved_at` column of the `dependency_graphs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        resolved_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `download_anomalies` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    download_anomalies (id) {
+        /// The `id` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `start_date` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        start_date -> Date,
+        /// The `end_date` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        end_date -> Date,
+        /// The `downloads` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int8,
+        /// The `expected_downloads` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expected_downloads -> Int8,
+        /// The `excluded` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        excluded -> Bool,
+        /// The `reviewed` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reviewed -> Bool,
+        /// The `created_at` column of the `download_anomalies` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `emails` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    emails (id) {
+        /// The `id` column of the `emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `email` column of the `emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Varchar,
+        /// The `verified` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified -> Bool,
+        /// The `token` column of the `emails` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token -> Text,
+        /// The `token_generated_at` column of the `emails` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_generated_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `follows` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    follows (user_id, crate_id) {
+        /// The `user_id` column of the `follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `crate_id` column of the `follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `identities` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    identities (id) {
+        /// The `id` column of the `identities` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `identities` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `provider` column of the `identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider -> Varchar,
+        /// The `provider_user_id` column of the `identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider_user_id -> Varchar,
+        /// The `login` column of the `identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        login -> Varchar,
+        /// The `created_at` column of the `identities` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `keyword_aliases` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    keyword_aliases (alias) {
+        /// The `alias` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        alias -> Varchar,
+        /// The `keyword` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword -> Varchar,
+        /// The `created_at` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `keywords` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    keywords (id) {
+        /// The `id` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `keyword` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword -> Varchar,
+        /// The `crates_cnt` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crates_cnt -> Int4,
+        /// The `created_at` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `metadata` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    metadata (total_downloads) {
+        /// The `total_downloads` column of the `metadata` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        total_downloads -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `name_claims` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    name_claims (id) {
+        /// The `id` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `claimant_id` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claimant_id -> Int4,
+        /// The `reason` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// The `status` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Int4,
+        /// The `response` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response -> Nullable<Varchar>,
+        /// The `created_at` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `responded_at` column of the `name_claims` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        responded_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `namespaces` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    namespaces (id) {
+        /// The `id` column of the `namespaces` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `namespaces` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `team_id` column of the `namespaces` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        team_id -> Int4,
+        /// The `created_by` column of the `namespaces` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Int4,
+        /// The `created_at` column of the `namespaces` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `pending_crates` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    pending_crates (crate_id) {
+        /// The `crate_id` column of the `pending_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `index_entry` column of the `pending_crates` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        index_entry -> Jsonb,
+        /// The `created_at` column of the `pending_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `pending_owner_removals` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    pending_owner_removals (id) {
+        /// The `id` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `owner_id` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner_id -> Int4,
+        /// The `requested_by` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requested_by -> Int4,
+        /// The `requested_at` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requested_at -> Timestamp,
+        /// The `effective_at` column of the `pending_owner_removals` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        effective_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `private_index_entries` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    private_index_entries (id) {
+        /// The `id` column of the `private_index_entries` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `private_index_entries` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `name` column of the `private_index_entries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `num` column of the `private_index_entries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        num -> Varchar,
+        /// The `entry` column of the `private_index_entries` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        entry -> Jsonb,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `publish_rate_overrides` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    publish_rate_overrides (user_id) {
+        /// The `user_id` column of the `publish_rate_overrides` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `burst` column of the `publish_rate_overrides` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        burst -> Int4,
+        /// The `expires_at` column of the `publish_rate_overrides` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `publish_session_chunks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    publish_session_chunks (session_id, position) {
+        /// The `session_id` column of the `publish_session_chunks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        session_id -> Int4,
+        /// The `position` column of the `publish_session_chunks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        position -> Int4,
+        /// The `data` column of the `publish_session_chunks` table.
+        ///
+        /// Its SQL type is `Bytea`.
+        ///
+        /// (Automatically generated by Diesel.)
+        data -> Bytea,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `publish_sessions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    publish_sessions (id) {
+        /// The `id` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `length` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        length -> Int4,
+        /// The `received` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        received -> Int4,
+        /// The `created_at` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `crate_name` column of the `publish_sessions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `rate_limit_buckets` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    rate_limit_buckets (action, owner) {
+        /// The `action` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
+        /// The `owner` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner -> Varchar,
+        /// The `tokens` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tokens -> Int4,
+        /// The `last_refill` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_refill -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `readme_renderings` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    readme_renderings (version_id) {
+        /// The `version_id` column of the `readme_renderings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `rendered_at` column of the `readme_renderings` table.
+        ///
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         rendered_at -> Timestamp,
+        /// The `html` column of the `readme_renderings` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        html -> Nullable<Text>,
     }
 }
 
@@ -674,25 +2111,328 @@ table! {
         crate_id -> Integer,
         /// The `downloads` column of the `recent_crate_downloads` table.
         ///
-        /// Its SQL type is `BigInt`.
-        downloads -> BigInt,
-    }
-}
-
-table! {
-    use diesel::sql_types::*;
-    use diesel_full_text_search::{TsVector as Tsvector};
-
-    /// Representation of the `reserved_crate_names` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    reserved_crate_names (name) {
-        /// The `name` column of the `reserved_crate_names` table.
+        /// Its SQL type is `BigInt`.
+        downloads -> BigInt,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `reserved_crate_name_patterns` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    reserved_crate_name_patterns (id) {
+        /// The `id` column of the `reserved_crate_name_patterns` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `kind` column of the `reserved_crate_name_patterns` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Int4,
+        /// The `pattern` column of the `reserved_crate_name_patterns` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        pattern -> Varchar,
+        /// The `created_at` column of the `reserved_crate_name_patterns` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `reserved_crate_names` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    reserved_crate_names (name) {
+        /// The `name` column of the `reserved_crate_names` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Text,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `scheduled_jobs` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    scheduled_jobs (name) {
+        /// The `name` column of the `scheduled_jobs` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Text,
+        /// The `last_enqueued_at` column of the `scheduled_jobs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_enqueued_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `sessions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sessions (id) {
+        /// The `id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `token` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token -> Varchar,
+        /// The `ip` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip -> Nullable<Varchar>,
+        /// The `user_agent` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent -> Nullable<Varchar>,
+        /// The `created_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `last_used_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_at -> Timestamp,
+        /// The `revoked` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        revoked -> Bool,
+    }
+}
+
+table! {
+    /// Representation of the `summary_crates` view.
+    ///
+    /// The crates listed in each section of the summary, ranked from 1.
+    /// This view does not contain realtime data.
+    /// It is refreshed by the `refresh_summary` background job.
+    summary_crates (section, rank) {
+        /// The `section` column of the `summary_crates` view.
+        ///
+        /// Its SQL type is `Text`.
+        section -> Text,
+        /// The `rank` column of the `summary_crates` view.
+        ///
+        /// Its SQL type is `Integer`.
+        rank -> Integer,
+        /// The `crate_id` column of the `summary_crates` view.
+        ///
+        /// Its SQL type is `Integer`.
+        crate_id -> Integer,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `team_memberships` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    team_memberships (team_id, user_id) {
+        /// The `team_id` column of the `team_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        team_id -> Int4,
+        /// The `user_id` column of the `team_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `is_member` column of the `team_memberships` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_member -> Bool,
+        /// The `checked_at` column of the `team_memberships` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        checked_at -> Timestamp,
+        /// The `expires_at` column of the `team_memberships` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `teams` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    teams (id) {
+        /// The `id` column of the `teams` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `login` column of the `teams` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        login -> Varchar,
+        /// The `github_id` column of the `teams` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        github_id -> Int4,
+        /// The `name` column of the `teams` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Nullable<Varchar>,
+        /// The `avatar` column of the `teams` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        avatar -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `topic_follows` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    topic_follows (user_id, kind, target_id) {
+        /// The `user_id` column of the `topic_follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `kind` column of the `topic_follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Int4,
+        /// The `target_id` column of the `topic_follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target_id -> Int4,
+        /// The `created_at` column of the `topic_follows` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `trending_crates` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    trending_crates (crate_id) {
+        /// The `crate_id` column of the `trending_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `rank` column of the `trending_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        rank -> Int4,
+        /// The `last_week_downloads` column of the `trending_crates` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_week_downloads -> Int8,
+        /// The `previous_week_downloads` column of the `trending_crates` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        previous_week_downloads -> Int8,
+        /// The `growth` column of the `trending_crates` table.
+        ///
+        /// Its SQL type is `Float8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        growth -> Float8,
+        /// The `computed_at` column of the `trending_crates` table.
         ///
-        /// Its SQL type is `Text`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        name -> Text,
+        computed_at -> Timestamp,
     }
 }
 
@@ -700,40 +2440,34 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `teams` table.
+    /// Representation of the `update_digest_subscriptions` table.
     ///
     /// (Automatically generated by Diesel.)
-    teams (id) {
-        /// The `id` column of the `teams` table.
+    update_digest_subscriptions (user_id) {
+        /// The `user_id` column of the `update_digest_subscriptions` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `login` column of the `teams` table.
-        ///
-        /// Its SQL type is `Varchar`.
-        ///
-        /// (Automatically generated by Diesel.)
-        login -> Varchar,
-        /// The `github_id` column of the `teams` table.
+        user_id -> Int4,
+        /// The `created_at` column of the `update_digest_subscriptions` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        github_id -> Int4,
-        /// The `name` column of the `teams` table.
+        created_at -> Timestamp,
+        /// The `unsubscribe_token` column of the `update_digest_subscriptions` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Text`.
         ///
         /// (Automatically generated by Diesel.)
-        name -> Nullable<Varchar>,
-        /// The `avatar` column of the `teams` table.
+        unsubscribe_token -> Text,
+        /// The `last_sent_at` column of the `update_digest_subscriptions` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Nullable<Timestamp>`.
         ///
         /// (Automatically generated by Diesel.)
-        avatar -> Nullable<Varchar>,
+        last_sent_at -> Nullable<Timestamp>,
     }
 }
 
@@ -787,6 +2521,71 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         gh_id -> Int4,
+        /// The `is_admin` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_admin -> Bool,
+        /// The `is_banned` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_banned -> Bool,
+        /// The `provider` column of the `users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider -> Varchar,
+        /// The `provider_user_id` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider_user_id -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_adoption` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_adoption (version_id) {
+        /// The `version_id` column of the `version_adoption` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `crate_id` column of the `version_adoption` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `downloads` column of the `version_adoption` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int8,
+        /// The `share` column of the `version_adoption` table.
+        ///
+        /// Its SQL type is `Float8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        share -> Float8,
+        /// The `computed_at` column of the `version_adoption` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        computed_at -> Timestamp,
     }
 }
 
@@ -825,6 +2624,76 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_checksums` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_checksums (version_id) {
+        /// The `version_id` column of the `version_checksums` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `sha256` column of the `version_checksums` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sha256 -> Varchar,
+        /// The `sha512` column of the `version_checksums` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sha512 -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_download_clients` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_download_clients (version_id, date, cargo_version, target) {
+        /// The `version_id` column of the `version_download_clients` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `date` column of the `version_download_clients` table.
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        date -> Date,
+        /// The `cargo_version` column of the `version_download_clients` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        cargo_version -> Varchar,
+        /// The `target` column of the `version_download_clients` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target -> Varchar,
+        /// The `downloads` column of the `version_download_clients` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int4,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -866,6 +2735,35 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_files` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_files (version_id, path) {
+        /// The `version_id` column of the `version_files` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `path` column of the `version_files` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        path -> Varchar,
+        /// The `size` column of the `version_files` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        size -> Int8,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -913,6 +2811,112 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_provenance` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_provenance (version_id) {
+        /// The `version_id` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `provider` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider -> Varchar,
+        /// The `run_url` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        run_url -> Nullable<Varchar>,
+        /// The `commit_sha` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        commit_sha -> Varchar,
+        /// The `verified` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Nullable<Bool>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified -> Nullable<Bool>,
+        /// The `verified_at` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `version_provenance` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `version_scan_findings` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    version_scan_findings (id) {
+        /// The `id` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `version_id` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `path` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        path -> Varchar,
+        /// The `kind` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `detail` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        detail -> Varchar,
+        /// The `reviewed` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reviewed -> Bool,
+        /// The `created_at` column of the `version_scan_findings` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -987,6 +2991,30 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         published_by -> Nullable<Int4>,
+        /// The `advisory` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        advisory -> Nullable<Varchar>,
+        /// The `reproducible` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Bool>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reproducible -> Nullable<Bool>,
+        /// The `docs_built` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Bool>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        docs_built -> Nullable<Bool>,
+        /// The `rust_version` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        rust_version -> Nullable<Varchar>,
     }
 }
 
@@ -1013,60 +3041,157 @@ table! {
     }
 }
 
+joinable!(api_token_usage -> api_tokens (api_token_id));
 joinable!(api_tokens -> users (user_id));
+joinable!(approved_crate_names -> users (approved_by));
 joinable!(badges -> crates (crate_id));
+joinable!(build_verifications -> build_verifiers (verifier_id));
+joinable!(build_verifications -> versions (version_id));
+joinable!(category_redirects -> categories (category_id));
+joinable!(crate_acls -> crates (crate_id));
+joinable!(crate_acls -> users (user_id));
+joinable!(crate_download_rollups -> crates (crate_id));
+joinable!(crate_funding -> crates (crate_id));
+joinable!(crate_license_identifiers -> crates (crate_id));
+joinable!(crate_list_follows -> crate_lists (list_id));
+joinable!(crate_list_follows -> users (user_id));
+joinable!(crate_list_items -> crate_lists (list_id));
+joinable!(crate_list_items -> crates (crate_id));
+joinable!(crate_lists -> users (user_id));
+joinable!(crate_owner_actions -> crates (crate_id));
+joinable!(crate_owner_actions -> users (owner_id));
 joinable!(crate_owner_invitations -> crates (crate_id));
 joinable!(crate_owners -> crates (crate_id));
 joinable!(crate_owners -> teams (owner_id));
 joinable!(crate_owners -> users (owner_id));
+joinable!(crate_renames -> crates (crate_id));
+joinable!(crate_renames -> users (renamed_by));
+joinable!(crate_reports -> crates (crate_id));
+joinable!(crate_reports -> users (reporter_id));
+joinable!(crate_successors -> crates (crate_id));
 joinable!(crates_categories -> categories (category_id));
 joinable!(crates_categories -> crates (crate_id));
 joinable!(crates_keywords -> crates (crate_id));
 joinable!(crates_keywords -> keywords (keyword_id));
+joinable!(deleted_crates -> users (deleted_by));
+joinable!(deleted_versions -> crates (crate_id));
+joinable!(deleted_versions -> users (deleted_by));
 joinable!(dependencies -> crates (crate_id));
 joinable!(dependencies -> versions (version_id));
+joinable!(dependency_graphs -> versions (version_id));
+joinable!(download_anomalies -> crates (crate_id));
 joinable!(emails -> users (user_id));
 joinable!(follows -> crates (crate_id));
 joinable!(follows -> users (user_id));
-joinable!(publish_limit_buckets -> users (user_id));
+joinable!(identities -> users (user_id));
+joinable!(name_claims -> crates (crate_id));
+joinable!(name_claims -> users (claimant_id));
+joinable!(namespaces -> teams (team_id));
+joinable!(namespaces -> users (created_by));
+joinable!(pending_crates -> crates (crate_id));
+joinable!(pending_owner_removals -> crates (crate_id));
+joinable!(private_index_entries -> crates (crate_id));
 joinable!(publish_rate_overrides -> users (user_id));
+joinable!(publish_session_chunks -> publish_sessions (session_id));
+joinable!(publish_sessions -> users (user_id));
 joinable!(readme_renderings -> versions (version_id));
 joinable!(recent_crate_downloads -> crates (crate_id));
+joinable!(sessions -> users (user_id));
+joinable!(summary_crates -> crates (crate_id));
+joinable!(team_memberships -> teams (team_id));
+joinable!(team_memberships -> users (user_id));
+joinable!(topic_follows -> users (user_id));
+joinable!(trending_crates -> crates (crate_id));
+joinable!(update_digest_subscriptions -> users (user_id));
+joinable!(version_adoption -> crates (crate_id));
+joinable!(version_adoption -> versions (version_id));
 joinable!(version_authors -> users (user_id));
 joinable!(version_authors -> versions (version_id));
+joinable!(version_checksums -> versions (version_id));
+joinable!(version_download_clients -> versions (version_id));
 joinable!(version_downloads -> versions (version_id));
+joinable!(version_files -> versions (version_id));
 joinable!(version_owner_actions -> api_tokens (owner_token_id));
 joinable!(version_owner_actions -> users (owner_id));
 joinable!(version_owner_actions -> versions (version_id));
+joinable!(version_provenance -> versions (version_id));
+joinable!(version_scan_findings -> versions (version_id));
 joinable!(versions -> crates (crate_id));
 joinable!(versions -> users (published_by));
 joinable!(versions_published_by -> versions (version_id));
 
 allow_tables_to_appear_in_same_query!(
+    admin_actions,
+    api_token_usage,
     api_tokens,
+    approved_crate_names,
+    background_job_dead_letters,
+    background_job_policies,
     background_jobs,
     badges,
+    build_verifications,
+    build_verifiers,
     categories,
+    category_redirects,
+    cdn_log_files,
+    crate_acls,
+    crate_download_rollups,
+    crate_funding,
+    crate_license_identifiers,
+    crate_list_follows,
+    crate_list_items,
+    crate_lists,
+    crate_owner_actions,
     crate_owner_invitations,
     crate_owners,
+    crate_renames,
+    crate_reports,
+    crate_successors,
     crates,
     crates_categories,
     crates_keywords,
+    deleted_crates,
+    deleted_versions,
     dependencies,
+    dependency_graphs,
+    download_anomalies,
     emails,
     follows,
+    identities,
+    keyword_aliases,
     keywords,
     metadata,
-    publish_limit_buckets,
+    name_claims,
+    namespaces,
+    pending_crates,
+    pending_owner_removals,
+    private_index_entries,
     publish_rate_overrides,
+    publish_session_chunks,
+    publish_sessions,
+    rate_limit_buckets,
     readme_renderings,
     recent_crate_downloads,
+    reserved_crate_name_patterns,
     reserved_crate_names,
+    scheduled_jobs,
+    sessions,
+    summary_crates,
+    team_memberships,
     teams,
+    topic_follows,
+    trending_crates,
+    update_digest_subscriptions,
     users,
+    version_adoption,
     version_authors,
+    version_checksums,
+    version_download_clients,
     version_downloads,
+    version_files,
     version_owner_actions,
+    version_provenance,
+    version_scan_findings,
     versions,
     versions_published_by,
 );
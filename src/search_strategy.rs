@@ -0,0 +1,140 @@
+//! Selection of the ranking used when searching crates
+//!
+//! Search requests can either be ranked with the full text search rank of the
+//! crate descriptions only, or with a hybrid rank that also takes trigram
+//! similarity of crate names and recent downloads into account. A share of
+//! the searches can be sent to the hybrid ranking to compare the relevance of
+//! both before switching over.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Ranks matches by `ts_rank_cd` of the textsearchable index column.
+    TsRank,
+    /// Ranks matches by a weighted sum of name similarity, `ts_rank_cd` and
+    /// recent downloads. Names that are merely similar to the query match too.
+    Hybrid,
+}
+
+impl SearchStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchStrategy::TsRank => "tsrank",
+            SearchStrategy::Hybrid => "hybrid",
+        }
+    }
+}
+
+impl FromStr for SearchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tsrank" => Ok(SearchStrategy::TsRank),
+            "hybrid" => Ok(SearchStrategy::Hybrid),
+            _ => Err(format!("unknown search strategy `{}`", s)),
+        }
+    }
+}
+
+/// Which search strategy is used for which request.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchExperiment {
+    /// The strategy used for searches outside of the experiment.
+    pub default: SearchStrategy,
+    /// The percentage of searchers, between 0 and 100, that get the hybrid
+    /// strategy regardless of `default`.
+    pub hybrid_percentage: u8,
+}
+
+impl Default for SearchExperiment {
+    fn default() -> Self {
+        Self {
+            default: SearchStrategy::TsRank,
+            hybrid_percentage: 0,
+        }
+    }
+}
+
+impl SearchExperiment {
+    /// Reads the experiment from the environment.
+    ///
+    /// Pulls values from the following environment variables:
+    ///
+    /// - `SEARCH_STRATEGY`: Either `tsrank` or `hybrid`. Defaults to `tsrank`.
+    /// - `SEARCH_HYBRID_PERCENTAGE`: The percentage of searchers that get the
+    ///   hybrid strategy. Defaults to 0.
+    pub fn from_environment() -> Self {
+        let default = dotenv::var("SEARCH_STRATEGY")
+            .map(|s| s.parse().unwrap())
+            .unwrap_or(SearchStrategy::TsRank);
+        let hybrid_percentage = dotenv::var("SEARCH_HYBRID_PERCENTAGE")
+            .map(|s| s.parse::<u8>().unwrap().min(100))
+            .unwrap_or(0);
+
+        Self {
+            default,
+            hybrid_percentage,
+        }
+    }
+
+    /// Picks the strategy for a searcher. The same key always gets the same
+    /// strategy, so a searcher doesn't switch between rankings while
+    /// refining their query.
+    pub fn strategy_for(&self, key: &str) -> SearchStrategy {
+        if self.bucket(key) < self.hybrid_percentage {
+            SearchStrategy::Hybrid
+        } else {
+            self.default
+        }
+    }
+
+    fn bucket(&self, key: &str) -> u8 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % 100) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategy_is_stable_per_key() {
+        let experiment = SearchExperiment {
+            default: SearchStrategy::TsRank,
+            hybrid_percentage: 50,
+        };
+        for key in &["user:1", "user:2", "127.0.0.1"] {
+            assert_eq!(experiment.strategy_for(key), experiment.strategy_for(key));
+        }
+    }
+
+    #[test]
+    fn percentage_bounds() {
+        let none = SearchExperiment {
+            default: SearchStrategy::TsRank,
+            hybrid_percentage: 0,
+        };
+        let all = SearchExperiment {
+            default: SearchStrategy::TsRank,
+            hybrid_percentage: 100,
+        };
+        for i in 0..50 {
+            let key = format!("user:{}", i);
+            assert_eq!(none.strategy_for(&key), SearchStrategy::TsRank);
+            assert_eq!(all.strategy_for(&key), SearchStrategy::Hybrid);
+        }
+    }
+
+    #[test]
+    fn parse_strategy() {
+        assert_eq!("tsrank".parse(), Ok(SearchStrategy::TsRank));
+        assert_eq!("hybrid".parse(), Ok(SearchStrategy::Hybrid));
+        assert!("bm25".parse::<SearchStrategy>().is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! Where crate files, rendered readmes and database dumps are stored
+//!
+//! `Uploader` decides which files go where, and stores them with one of the
+//! backends in this module, selected by `Config::uploader`. Self-hosted
+//! registries can keep their files with their cloud provider, or on the
+//! filesystem of the server.
+
+use reqwest::header;
+use std::fmt;
+use std::io::Read;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::util::errors::std_error_no_send;
+use crate::util::CargoResult;
+
+mod aws;
+mod azure;
+mod gcs;
+mod local;
+
+pub use self::aws::S3Storage;
+pub use self::azure::AzureStorage;
+pub use self::gcs::GcsStorage;
+pub use self::local::LocalStorage;
+
+/// The contents of a file to store.
+pub type Content = Box<dyn Read + Send>;
+
+pub trait StorageBackend: fmt::Debug + Send + Sync {
+    /// Returns the URL files are publicly served from, without a trailing
+    /// slash, or `None` if they are served by the server itself.
+    fn public_url(&self) -> Option<String>;
+
+    /// Stores a file at `path`, and returns where it was stored.
+    ///
+    /// `extra_headers` are passed on to the backend if it has a matching
+    /// property, like `Cache-Control`.
+    fn put(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        content: Content,
+        content_length: u64,
+        content_type: &str,
+        extra_headers: header::HeaderMap,
+    ) -> CargoResult<Option<String>>;
+
+    /// Reads the file at `path`.
+    fn get(&self, client: &reqwest::Client, path: &str) -> CargoResult<Vec<u8>>;
+
+    /// Deletes the file at `path`. Deleting a file that doesn't exist is not
+    /// an error.
+    fn delete(&self, client: &reqwest::Client, path: &str) -> CargoResult<()>;
+
+    /// Checks that files can be accessed, for the readiness checks of the
+    /// server.
+    fn check_access(&self, client: &reqwest::Client) -> CargoResult<()>;
+
+    /// Returns a URL that allows anyone holding it to download the file at
+    /// `path` until the Unix timestamp `expires`, or `None` if the backend
+    /// can't sign URLs.
+    fn presigned_url(&self, _path: &str, _expires: i64) -> Option<String> {
+        None
+    }
+}
+
+/// Deletes the files of a version that its owners deleted, on its own or
+/// along with its crate.
+#[swirl::background_job]
+pub fn delete_version_files(
+    env: &Environment,
+    krate: String,
+    version: String,
+) -> Result<(), PerformError> {
+    env.uploader
+        .delete_version_files(env.http_client(), &krate, &version)
+        .map_err(std_error_no_send)
+}
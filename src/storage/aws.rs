@@ -0,0 +1,72 @@
+use reqwest::header;
+
+use super::{Content, StorageBackend};
+use crate::util::{internal, CargoResult};
+
+/// Stores files in an S3 bucket.
+#[derive(Clone, Debug)]
+pub struct S3Storage {
+    bucket: s3::Bucket,
+}
+
+impl S3Storage {
+    pub fn new(bucket: s3::Bucket) -> Self {
+        S3Storage { bucket }
+    }
+}
+
+impl StorageBackend for S3Storage {
+    fn public_url(&self) -> Option<String> {
+        Some(format!("https://{}", self.bucket.host()))
+    }
+
+    fn put(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        content: Content,
+        content_length: u64,
+        content_type: &str,
+        extra_headers: header::HeaderMap,
+    ) -> CargoResult<Option<String>> {
+        self.bucket
+            .put(
+                client,
+                path,
+                content,
+                content_length,
+                content_type,
+                Some(extra_headers),
+            )
+            .map_err(|e| internal(&format_args!("failed to upload to S3: {}", e)))?;
+        Ok(Some(String::from(path)))
+    }
+
+    fn get(&self, client: &reqwest::Client, path: &str) -> CargoResult<Vec<u8>> {
+        let mut body = Vec::new();
+        self.bucket
+            .get(client, path)
+            .and_then(|mut response| response.copy_to(&mut body))
+            .map_err(|e| internal(&format_args!("failed to download from S3: {}", e)))?;
+        Ok(body)
+    }
+
+    fn delete(&self, client: &reqwest::Client, path: &str) -> CargoResult<()> {
+        // S3 responds with success for objects that don't exist
+        self.bucket
+            .delete(client, path)
+            .map_err(|e| internal(&format_args!("failed to delete from S3: {}", e)))?;
+        Ok(())
+    }
+
+    fn check_access(&self, client: &reqwest::Client) -> CargoResult<()> {
+        self.bucket
+            .head_bucket(client)
+            .map_err(|e| internal(&format_args!("failed to access S3: {}", e)))?;
+        Ok(())
+    }
+
+    fn presigned_url(&self, path: &str, expires: i64) -> Option<String> {
+        Some(self.bucket.presigned_url(path, expires))
+    }
+}
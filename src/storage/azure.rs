@@ -0,0 +1,169 @@
+use reqwest::{header, StatusCode};
+
+use super::{Content, StorageBackend};
+use crate::util::{internal, CargoResult};
+
+/// The version of the Blob service REST API the requests are made against.
+const API_VERSION: &str = "2019-02-02";
+
+/// Stores files in a container of an Azure Blob Storage account.
+///
+/// Requests are authorized with a shared access signature (SAS) token that
+/// has to allow reading, creating, writing and deleting blobs. Files are
+/// served from the container without the token, so it has to allow public
+/// read access to blobs, or be put behind a CDN. Presigned URLs aren't
+/// supported.
+#[derive(Clone, Debug)]
+pub struct AzureStorage {
+    account: String,
+    container: String,
+    sas_token: String,
+}
+
+impl AzureStorage {
+    pub fn new(account: String, container: String, sas_token: &str) -> Self {
+        AzureStorage {
+            account,
+            container,
+            sas_token: sas_token.trim_start_matches('?').to_string(),
+        }
+    }
+
+    fn container_url(&self) -> String {
+        format!(
+            "https://{}.blob.core.windows.net/{}",
+            self.account, self.container
+        )
+    }
+
+    fn blob_url(&self, path: &str) -> String {
+        format!(
+            "{}/{}?{}",
+            self.container_url(),
+            path.trim_start_matches('/'),
+            self.sas_token
+        )
+    }
+}
+
+/// Azure sets the properties of blobs from `x-ms-blob-*` headers rather than
+/// the standard ones.
+fn blob_headers(extra_headers: header::HeaderMap) -> header::HeaderMap {
+    let mut headers = header::HeaderMap::new();
+    for (name, value) in &extra_headers {
+        let property = if *name == header::CACHE_CONTROL {
+            Some("x-ms-blob-cache-control")
+        } else if *name == header::CONTENT_ENCODING {
+            Some("x-ms-blob-content-encoding")
+        } else if *name == header::CONTENT_DISPOSITION {
+            Some("x-ms-blob-content-disposition")
+        } else {
+            None
+        };
+        match property {
+            Some(property) => headers.append(property, value.clone()),
+            None => headers.append(name, value.clone()),
+        };
+    }
+    headers
+}
+
+impl StorageBackend for AzureStorage {
+    fn public_url(&self) -> Option<String> {
+        Some(self.container_url())
+    }
+
+    fn put(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        content: Content,
+        content_length: u64,
+        content_type: &str,
+        extra_headers: header::HeaderMap,
+    ) -> CargoResult<Option<String>> {
+        client
+            .put(&self.blob_url(path))
+            .header("x-ms-version", API_VERSION)
+            .header("x-ms-blob-type", "BlockBlob")
+            .header(header::CONTENT_TYPE, content_type)
+            .headers(blob_headers(extra_headers))
+            .body(reqwest::Body::sized(content, content_length))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| internal(&format_args!("failed to upload to Azure: {}", e)))?;
+        Ok(Some(String::from(path)))
+    }
+
+    fn get(&self, client: &reqwest::Client, path: &str) -> CargoResult<Vec<u8>> {
+        let mut body = Vec::new();
+        client
+            .get(&self.blob_url(path))
+            .header("x-ms-version", API_VERSION)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|mut response| response.copy_to(&mut body))
+            .map_err(|e| internal(&format_args!("failed to download from Azure: {}", e)))?;
+        Ok(body)
+    }
+
+    fn delete(&self, client: &reqwest::Client, path: &str) -> CargoResult<()> {
+        let response = client
+            .delete(&self.blob_url(path))
+            .header("x-ms-version", API_VERSION)
+            .send()
+            .map_err(|e| internal(&format_args!("failed to delete from Azure: {}", e)))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        response
+            .error_for_status()
+            .map_err(|e| internal(&format_args!("failed to delete from Azure: {}", e)))?;
+        Ok(())
+    }
+
+    fn check_access(&self, client: &reqwest::Client) -> CargoResult<()> {
+        let url = format!(
+            "{}?restype=container&{}",
+            self.container_url(),
+            self.sas_token
+        );
+        client
+            .head(&url)
+            .header("x-ms-version", API_VERSION)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| internal(&format_args!("failed to access Azure: {}", e)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blobs_are_addressed_with_the_sas_token() {
+        let storage = AzureStorage::new("account".into(), "crates".into(), "?sv=2019&sig=abc");
+        assert_eq!(
+            storage.blob_url("/crates/foo/foo-1.0.0.crate"),
+            "https://account.blob.core.windows.net/crates/crates/foo/foo-1.0.0.crate\
+             ?sv=2019&sig=abc"
+        );
+        assert_eq!(
+            storage.public_url().unwrap(),
+            "https://account.blob.core.windows.net/crates"
+        );
+    }
+
+    #[test]
+    fn standard_headers_are_mapped_to_blob_properties() {
+        let mut extra_headers = header::HeaderMap::new();
+        extra_headers.insert(header::CACHE_CONTROL, "max-age=60".parse().unwrap());
+        extra_headers.insert("x-custom", "value".parse().unwrap());
+        let headers = blob_headers(extra_headers);
+        assert_eq!(headers["x-ms-blob-cache-control"], "max-age=60");
+        assert_eq!(headers["x-custom"], "value");
+        assert!(!headers.contains_key(header::CACHE_CONTROL));
+    }
+}
@@ -0,0 +1,77 @@
+use reqwest::{header, StatusCode};
+
+use super::{Content, StorageBackend};
+use crate::util::{internal, CargoResult};
+
+const ENDPOINT: &str = "storage.googleapis.com";
+
+/// Stores files in a Google Cloud Storage bucket.
+///
+/// The XML API of Cloud Storage is compatible with S3, and requests are
+/// authenticated with the HMAC key of a service account like they would be
+/// with S3 credentials. Presigned URLs aren't supported.
+#[derive(Clone, Debug)]
+pub struct GcsStorage {
+    bucket: s3::Bucket,
+}
+
+impl GcsStorage {
+    pub fn new(name: String, access_key: String, secret_key: String, proto: &str) -> Self {
+        GcsStorage {
+            bucket: s3::Bucket::new(name, None, access_key, secret_key, proto)
+                .with_endpoint(ENDPOINT),
+        }
+    }
+}
+
+impl StorageBackend for GcsStorage {
+    fn public_url(&self) -> Option<String> {
+        Some(format!("https://{}", self.bucket.host()))
+    }
+
+    fn put(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        content: Content,
+        content_length: u64,
+        content_type: &str,
+        extra_headers: header::HeaderMap,
+    ) -> CargoResult<Option<String>> {
+        self.bucket
+            .put(
+                client,
+                path,
+                content,
+                content_length,
+                content_type,
+                Some(extra_headers),
+            )
+            .map_err(|e| internal(&format_args!("failed to upload to GCS: {}", e)))?;
+        Ok(Some(String::from(path)))
+    }
+
+    fn get(&self, client: &reqwest::Client, path: &str) -> CargoResult<Vec<u8>> {
+        let mut body = Vec::new();
+        self.bucket
+            .get(client, path)
+            .and_then(|mut response| response.copy_to(&mut body))
+            .map_err(|e| internal(&format_args!("failed to download from GCS: {}", e)))?;
+        Ok(body)
+    }
+
+    fn delete(&self, client: &reqwest::Client, path: &str) -> CargoResult<()> {
+        match self.bucket.delete(client, path) {
+            Ok(_) => Ok(()),
+            Err(ref e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(()),
+            Err(e) => Err(internal(&format_args!("failed to delete from GCS: {}", e))),
+        }
+    }
+
+    fn check_access(&self, client: &reqwest::Client) -> CargoResult<()> {
+        self.bucket
+            .head_bucket(client)
+            .map_err(|e| internal(&format_args!("failed to access GCS: {}", e)))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use reqwest::header;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use super::{Content, StorageBackend};
+use crate::util::CargoResult;
+
+/// Stores files in a directory, from where the server serves them as well.
+///
+/// Meant for development, and for self-hosted registries running on a single
+/// server.
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        LocalStorage { root }
+    }
+
+    fn filename(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn public_url(&self) -> Option<String> {
+        None
+    }
+
+    fn put(
+        &self,
+        _client: &reqwest::Client,
+        path: &str,
+        mut content: Content,
+        _content_length: u64,
+        _content_type: &str,
+        _extra_headers: header::HeaderMap,
+    ) -> CargoResult<Option<String>> {
+        let filename = self.filename(path);
+        let dir = filename.parent().unwrap();
+        fs::create_dir_all(dir)?;
+        let mut file = File::create(&filename)?;
+        io::copy(&mut content, &mut file)?;
+        Ok(filename.to_str().map(String::from))
+    }
+
+    fn get(&self, _client: &reqwest::Client, path: &str) -> CargoResult<Vec<u8>> {
+        let mut body = Vec::new();
+        File::open(self.filename(path))?.read_to_end(&mut body)?;
+        Ok(body)
+    }
+
+    fn delete(&self, _client: &reqwest::Client, path: &str) -> CargoResult<()> {
+        match fs::remove_file(self.filename(path)) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    fn check_access(&self, _client: &reqwest::Client) -> CargoResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn stored_files_can_be_read() {
+        let root = TempDir::new("local-storage").unwrap();
+        let storage = LocalStorage::new(root.path().to_path_buf());
+        let client = reqwest::Client::new();
+        let content = Box::new(io::Cursor::new(b"crate".to_vec()));
+        storage
+            .put(
+                &client,
+                "crates/foo/foo-1.0.0.crate",
+                content,
+                5,
+                "application/x-tar",
+                header::HeaderMap::new(),
+            )
+            .unwrap();
+
+        let body = storage.get(&client, "crates/foo/foo-1.0.0.crate").unwrap();
+        assert_eq!(body, b"crate");
+        assert!(root.path().join("crates/foo/foo-1.0.0.crate").exists());
+    }
+
+    #[test]
+    fn deleting_missing_files_succeeds() {
+        let root = TempDir::new("local-storage").unwrap();
+        let storage = LocalStorage::new(root.path().to_path_buf());
+        let client = reqwest::Client::new();
+        let content = Box::new(io::Cursor::new(b"crate".to_vec()));
+        storage
+            .put(
+                &client,
+                "crates/foo/foo-1.0.0.crate",
+                content,
+                5,
+                "application/x-tar",
+                header::HeaderMap::new(),
+            )
+            .unwrap();
+
+        storage
+            .delete(&client, "crates/foo/foo-1.0.0.crate")
+            .unwrap();
+        assert!(!root.path().join("crates/foo/foo-1.0.0.crate").exists());
+        storage
+            .delete(&client, "crates/foo/foo-1.0.0.crate")
+            .unwrap();
+    }
+}
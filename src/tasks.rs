@@ -1,5 +1,43 @@
+mod apply_owner_removals;
+mod backfill_checksums;
+mod backfill_default_versions;
+mod delete_stale_publish_sessions;
+mod detect_download_anomalies;
 pub mod dump_db;
+mod ingest_cdn_logs;
+mod notify_dependents_of_yank;
+mod notify_owners_of_publish;
+mod notify_unused_tokens;
+mod refresh_summary;
+mod rollup_downloads;
+mod send_update_digests;
+mod sync_team_memberships;
+mod transfer_inactive_crates;
+mod update_docs_status;
 mod update_downloads;
+mod update_popularity;
+mod update_trending_crates;
+mod update_version_adoption;
+mod verify_provenance;
 
+pub use apply_owner_removals::apply_owner_removals;
+pub use backfill_checksums::backfill_checksums;
+pub use backfill_default_versions::backfill_default_versions;
+pub use delete_stale_publish_sessions::delete_stale_publish_sessions;
+pub use detect_download_anomalies::detect_download_anomalies;
 pub use dump_db::dump_db;
+pub use ingest_cdn_logs::ingest_cdn_logs;
+pub use notify_dependents_of_yank::notify_dependents_of_yank;
+pub use notify_owners_of_publish::notify_owners_of_publish;
+pub use notify_unused_tokens::notify_unused_tokens;
+pub use refresh_summary::refresh_summary;
+pub use rollup_downloads::rollup_downloads;
+pub use send_update_digests::send_update_digests;
+pub use sync_team_memberships::sync_team_memberships;
+pub use transfer_inactive_crates::transfer_inactive_crates;
+pub use update_docs_status::update_docs_status;
 pub use update_downloads::update_downloads;
+pub use update_popularity::update_popularity;
+pub use update_trending_crates::update_trending_crates;
+pub use update_version_adoption::update_version_adoption;
+pub use verify_provenance::verify_provenance;
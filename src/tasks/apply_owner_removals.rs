@@ -0,0 +1,33 @@
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::{background_jobs::Environment, logging, models::PendingOwnerRemoval};
+
+use swirl::PerformError;
+
+/// Applies the removals of crate owners whose delay has passed, see
+/// `Config::owner_removal_delay`. Removals that would leave a crate without
+/// individual owners are cancelled instead.
+#[swirl::background_job]
+pub fn apply_owner_removals(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let mut applied = 0;
+    let mut cancelled = 0;
+    for removal in PendingOwnerRemoval::due(&conn, Utc::now().naive_utc())? {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            if removal.leaves_owners(&conn)? {
+                removal.apply(&conn)?;
+                applied += 1;
+            } else {
+                removal.cancel(&conn)?;
+                cancelled += 1;
+            }
+            Ok(())
+        })?;
+    }
+    logging::info(
+        "applied owner removals",
+        json!({ "applied": applied, "cancelled": cancelled }),
+    );
+    Ok(())
+}
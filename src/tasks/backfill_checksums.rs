@@ -0,0 +1,67 @@
+use crate::{
+    background_jobs::Environment,
+    logging,
+    schema::{crates, version_checksums, versions},
+};
+
+use diesel::prelude::*;
+use openssl::sha;
+use swirl::PerformError;
+
+/// The number of versions whose crate files are downloaded in a single run of
+/// the job.
+const BATCH_SIZE: i64 = 100;
+
+/// Computes the checksums of the crate files of versions that don't have a
+/// SHA512 checksum yet, which includes versions published before checksums
+/// were recorded. Versions whose crate file can't be downloaded are skipped
+/// and tried again on the next run.
+#[swirl::background_job]
+pub fn backfill_checksums(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let pending = versions::table
+        .inner_join(crates::table)
+        .left_join(version_checksums::table)
+        .filter(version_checksums::sha512.is_null())
+        .select((versions::id, crates::name, versions::num))
+        .order(versions::id)
+        .limit(BATCH_SIZE)
+        .load::<(i32, String, String)>(&*conn)?;
+
+    logging::info(
+        "backfilling checksums",
+        json!({ "versions": pending.len() }),
+    );
+
+    for (version_id, crate_name, num) in pending {
+        let tarball = match env
+            .uploader
+            .download_crate(env.http_client(), &crate_name, &num)
+        {
+            Ok(tarball) => tarball,
+            Err(e) => {
+                logging::warn(
+                    "failed to download a crate file",
+                    json!({ "crate": crate_name, "version": num, "error": e.to_string() }),
+                );
+                continue;
+            }
+        };
+        let sha256 = hex::encode(sha::sha256(&tarball));
+        let sha512 = hex::encode(&sha::sha512(&tarball)[..]);
+
+        // The checksum recorded on publish is the one in the index, so it's
+        // kept even if the file somehow changed since
+        diesel::insert_into(version_checksums::table)
+            .values((
+                version_checksums::version_id.eq(version_id),
+                version_checksums::sha256.eq(&sha256),
+                version_checksums::sha512.eq(&sha512),
+            ))
+            .on_conflict(version_checksums::version_id)
+            .do_update()
+            .set(version_checksums::sha512.eq(&sha512))
+            .execute(&*conn)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use crate::{background_jobs::Environment, logging, models::Crate, schema::crates};
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+/// The number of crates updated in a single run of the job.
+const BATCH_SIZE: i64 = 1000;
+
+/// Fills in the highest versions of crates published before they were stored
+/// on the crate, see `Crate::update_default_versions`.
+#[swirl::background_job]
+pub fn backfill_default_versions(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let pending = crates::table
+        .filter(crates::max_version.is_null())
+        .select(crates::id)
+        .order(crates::id)
+        .limit(BATCH_SIZE)
+        .load::<i32>(&*conn)?;
+
+    logging::info(
+        "backfilling default versions",
+        json!({ "crates": pending.len() }),
+    );
+
+    for crate_id in pending {
+        Crate::update_default_versions(&conn, crate_id)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use crate::{background_jobs::Environment, logging, models::PublishSession};
+
+use swirl::PerformError;
+
+/// Deletes the chunked uploads of publishes that were abandoned, see
+/// `PublishSession::delete_stale`.
+#[swirl::background_job]
+pub fn delete_stale_publish_sessions(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let deleted = PublishSession::delete_stale(&conn)?;
+    logging::info(
+        "deleted stale publish sessions",
+        json!({ "sessions": deleted }),
+    );
+    Ok(())
+}
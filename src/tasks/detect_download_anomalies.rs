@@ -0,0 +1,182 @@
+use crate::background_jobs::Environment;
+use crate::logging;
+use crate::models::DownloadAnomaly;
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Date, Integer};
+use swirl::PerformError;
+
+/// How many of the last complete days are checked. Days that were already
+/// checked are checked again, but only recorded once.
+const DAYS: i32 = 7;
+
+/// The fewest downloads on a day that can be an anomaly, so that crates with
+/// few downloads aren't flagged for every busy day.
+const MIN_DOWNLOADS: i64 = 1000;
+
+/// How many times its usual daily downloads a crate has to be downloaded on a
+/// day for the day to be an anomaly.
+const SPIKE_FACTOR: i64 = 10;
+
+#[derive(QueryableByName, Debug)]
+struct Spike {
+    #[sql_type = "Integer"]
+    crate_id: i32,
+    #[sql_type = "Date"]
+    date: NaiveDate,
+    #[sql_type = "BigInt"]
+    downloads: i64,
+    #[sql_type = "BigInt"]
+    expected_downloads: i64,
+}
+
+/// Flags the days on which crates were downloaded far more often than usual
+/// for review in `GET /admin/download_anomalies`. If `exclude` is set, the
+/// downloads above the usual rate are subtracted from the download counts of
+/// the crates right away, instead of once an admin confirms the anomaly.
+#[swirl::background_job]
+pub fn detect_download_anomalies(env: &Environment, exclude: bool) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    detect(&conn, exclude)?;
+    Ok(())
+}
+
+fn detect(conn: &PgConnection, exclude: bool) -> QueryResult<()> {
+    let spikes = diesel::sql_query(include_str!("detect_download_anomalies.sql"))
+        .bind::<Integer, _>(DAYS)
+        .bind::<BigInt, _>(MIN_DOWNLOADS)
+        .bind::<BigInt, _>(SPIKE_FACTOR)
+        .load::<Spike>(conn)?;
+    for spike in &spikes {
+        conn.transaction(|| {
+            DownloadAnomaly::record(
+                conn,
+                spike.crate_id,
+                spike.date,
+                spike.downloads,
+                spike.expected_downloads,
+                exclude,
+            )
+        })?;
+    }
+    logging::info(
+        "detected download anomalies",
+        json!({ "days": spikes.len(), "exclude": exclude }),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        models::{Crate, Version},
+        schema::{crates, download_anomalies, version_downloads},
+        test_fixtures::{self, user},
+        test_util::pg_connection,
+    };
+    use chrono::{Duration, Utc};
+
+    fn crate_and_version(conn: &PgConnection, user_id: i32) -> (Crate, Version) {
+        let (krate, version) = test_fixtures::crate_and_version(conn, "foo", "1.0.0", user_id);
+
+        let long_ago = Utc::now().naive_utc() - Duration::days(365);
+        diesel::update(crates::table.find(krate.id))
+            .set((
+                crates::created_at.eq(long_ago),
+                crates::downloads.eq(100_000),
+            ))
+            .execute(conn)
+            .unwrap();
+        (krate, version)
+    }
+
+    /// Adds the downloads of the day `days_ago` days before today.
+    fn add_downloads(conn: &PgConnection, version_id: i32, days_ago: i64, downloads: i32) {
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq(Utc::today().naive_utc() - Duration::days(days_ago)),
+                version_downloads::downloads.eq(downloads),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn anomalies(conn: &PgConnection, crate_id: i32) -> Vec<DownloadAnomaly> {
+        download_anomalies::table
+            .filter(download_anomalies::crate_id.eq(crate_id))
+            .load(conn)
+            .unwrap()
+    }
+
+    fn crate_downloads(conn: &PgConnection, crate_id: i32) -> i32 {
+        crates::table
+            .find(crate_id)
+            .select(crates::downloads)
+            .first(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn consecutive_spikes_form_one_window() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, user.id);
+        for days_ago in 4..=40 {
+            add_downloads(&conn, version.id, days_ago, 280);
+        }
+        add_downloads(&conn, version.id, 3, 5000);
+        add_downloads(&conn, version.id, 2, 8000);
+        add_downloads(&conn, version.id, 1, 300);
+
+        detect(&conn, false).unwrap();
+        // Days are only recorded once
+        detect(&conn, false).unwrap();
+
+        let anomalies = anomalies(&conn, krate.id);
+        assert_eq!(anomalies.len(), 1);
+        let today = Utc::today().naive_utc();
+        assert_eq!(anomalies[0].start_date, today - Duration::days(3));
+        assert_eq!(anomalies[0].end_date, today - Duration::days(2));
+        assert_eq!(anomalies[0].downloads, 13_000);
+        assert!(!anomalies[0].excluded);
+        assert_eq!(crate_downloads(&conn, krate.id), 100_000);
+    }
+
+    #[test]
+    fn small_and_usual_download_counts_are_not_anomalies() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, user.id);
+        add_downloads(&conn, version.id, 2, 900);
+        for days_ago in 3..=40 {
+            add_downloads(&conn, version.id, days_ago, 2000);
+        }
+        add_downloads(&conn, version.id, 1, 15_000);
+
+        detect(&conn, false).unwrap();
+
+        assert!(anomalies(&conn, krate.id).is_empty());
+    }
+
+    #[test]
+    fn anomalies_can_be_excluded_right_away() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, user.id);
+        add_downloads(&conn, version.id, 1, 5000);
+
+        detect(&conn, true).unwrap();
+
+        let anomalies = anomalies(&conn, krate.id);
+        assert_eq!(anomalies.len(), 1);
+        assert!(anomalies[0].excluded);
+        assert_eq!(anomalies[0].expected_downloads, 0);
+        assert_eq!(crate_downloads(&conn, krate.id), 95_000);
+
+        anomalies[0].set_excluded(&conn, false).unwrap();
+        assert_eq!(crate_downloads(&conn, krate.id), 100_000);
+    }
+}
@@ -0,0 +1,36 @@
+-- Finds the days among the last $1 complete days on which a crate was
+-- downloaded at least $2 times and more than $3 times as often as on an
+-- average day of the four weeks before. Crates younger than those four weeks
+-- have no usual rate yet and are skipped.
+WITH daily AS (
+  SELECT versions.crate_id,
+         version_downloads.date,
+         SUM(version_downloads.downloads)::bigint AS downloads
+    FROM version_downloads
+   INNER JOIN versions
+      ON versions.id = version_downloads.version_id
+   WHERE version_downloads.date >= CURRENT_DATE - ($1 + 28)
+     AND version_downloads.date < CURRENT_DATE
+   GROUP BY 1, 2
+), rates AS (
+  SELECT recent.crate_id,
+         recent.date,
+         recent.downloads,
+         COALESCE((
+           SELECT SUM(earlier.downloads)
+             FROM daily earlier
+            WHERE earlier.crate_id = recent.crate_id
+              AND earlier.date >= recent.date - 28
+              AND earlier.date < recent.date
+         ), 0)::bigint / 28 AS expected_downloads
+    FROM daily recent
+   INNER JOIN crates
+      ON crates.id = recent.crate_id
+   WHERE recent.date >= CURRENT_DATE - $1
+     AND crates.created_at < recent.date - 28
+)
+SELECT crate_id, date, downloads, expected_downloads
+  FROM rates
+ WHERE downloads >= $2
+   AND downloads > $3 * expected_downloads
+ ORDER BY crate_id, date
@@ -3,7 +3,9 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{background_jobs::Environment, uploaders::Uploader, util::errors::std_error_no_send};
+use crate::{
+    background_jobs::Environment, logging, uploaders::Uploader, util::errors::std_error_no_send,
+};
 
 use swirl::PerformError;
 
@@ -19,7 +21,10 @@ pub fn dump_db(
     directory.populate(&database_url)?;
     let tarball = DumpTarball::create(&directory.export_dir)?;
     tarball.upload(&target_name, &env.uploader)?;
-    println!("Database dump uploaded to {}.", &target_name);
+    logging::info(
+        "database dump uploaded",
+        json!({ "target_name": target_name }),
+    );
     Ok(())
 }
 
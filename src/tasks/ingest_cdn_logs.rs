@@ -0,0 +1,240 @@
+use crate::background_jobs::Environment;
+use crate::cdn::logs::{count_downloads, Download, LogFormat};
+use crate::env;
+use crate::logging;
+use crate::models::{Crate, CrateRename, VersionDownload};
+use crate::schema::{cdn_log_files, crates, versions};
+use crate::util::errors::std_error_no_send;
+
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use swirl::PerformError;
+
+/// The bucket the CDN writes its access logs to
+struct LogSource {
+    bucket: s3::Bucket,
+    prefix: String,
+    format: LogFormat,
+}
+
+impl LogSource {
+    /// Reads the log bucket from the `CDN_LOG_BUCKET`, `CDN_LOG_REGION`,
+    /// `CDN_LOG_ACCESS_KEY`, `CDN_LOG_SECRET_KEY`, `CDN_LOG_PREFIX` and
+    /// `CDN_LOG_FORMAT` environment variables, if the bucket is set. The
+    /// format is either `cloudfront` or `fastly`, and defaults to
+    /// `cloudfront`.
+    fn from_environment() -> Option<Self> {
+        let name = dotenv::var("CDN_LOG_BUCKET").ok()?;
+        let format = dotenv::var("CDN_LOG_FORMAT")
+            .unwrap_or_else(|_| "cloudfront".into())
+            .parse()
+            .unwrap_or_else(|e| panic!("invalid CDN_LOG_FORMAT: {}", e));
+        let bucket = s3::Bucket::new(
+            name,
+            dotenv::var("CDN_LOG_REGION").ok(),
+            env("CDN_LOG_ACCESS_KEY"),
+            env("CDN_LOG_SECRET_KEY"),
+            "https",
+        );
+        Some(LogSource {
+            bucket,
+            prefix: dotenv::var("CDN_LOG_PREFIX").unwrap_or_default(),
+            format,
+        })
+    }
+}
+
+/// How many hours after the files ingested before them the CDN may still
+/// deliver log files. CloudFront and Fastly deliver some files late, under
+/// keys that sort before the ones of files that were already ingested.
+const LATE_DELIVERY_WINDOW_HOURS: i64 = 24;
+
+/// Adds the crate downloads in the access logs the CDN wrote since the last
+/// run to `version_downloads`. Log files are listed in the order of their
+/// keys, which start with the time they were written for both CloudFront and
+/// Fastly, starting after the last one that was ingested more than
+/// `LATE_DELIVERY_WINDOW_HOURS` ago, so that late files are picked up. Each
+/// file is only ingested once.
+#[swirl::background_job]
+pub fn ingest_cdn_logs(env: &Environment) -> Result<(), PerformError> {
+    let source = match LogSource::from_environment() {
+        Some(source) => source,
+        None => {
+            logging::info("no CDN log bucket configured", json!({}));
+            return Ok(());
+        }
+    };
+
+    let conn = env.connection()?;
+    let window_start = Utc::now().naive_utc() - Duration::hours(LATE_DELIVERY_WINDOW_HOURS);
+    let start_after = cdn_log_files::table
+        .filter(cdn_log_files::processed_at.lt(window_start))
+        .select(diesel::dsl::max(cdn_log_files::path))
+        .first::<Option<String>>(&*conn)?;
+    let client = env.http_client();
+    let paths = source.bucket.list(
+        client,
+        &source.prefix,
+        start_after.as_ref().map(String::as_str),
+    )?;
+
+    // Files in the window that were ingested before aren't downloaded again
+    let mut ingested = cdn_log_files::table
+        .select(cdn_log_files::path)
+        .into_boxed();
+    if let Some(ref start_after) = start_after {
+        ingested = ingested.filter(cdn_log_files::path.gt(start_after));
+    }
+    let ingested = ingested.load::<String>(&*conn)?;
+    let ingested = ingested.iter().collect::<HashSet<_>>();
+    let paths = paths.iter().filter(|path| !ingested.contains(path));
+
+    for path in paths {
+        let mut contents = Vec::new();
+        source
+            .bucket
+            .get(client, path)?
+            .read_to_end(&mut contents)?;
+        let counts = count_downloads(source.format, &contents).map_err(std_error_no_send)?;
+        let downloads = ingest(&conn, path, &counts)?;
+        logging::info(
+            "ingested CDN log file",
+            json!({ "path": path, "downloads": downloads }),
+        );
+    }
+    Ok(())
+}
+
+/// Adds the downloads of the log file at `path` to `version_downloads`,
+/// unless the file was ingested before. Downloads of versions that don't
+/// exist are ignored. Returns the number of downloads added.
+fn ingest(conn: &PgConnection, path: &str, counts: &HashMap<Download, i32>) -> QueryResult<i32> {
+    conn.transaction(|| {
+        let mut total = 0;
+        for (download, &count) in counts {
+            if let Some(version_id) = version_id(conn, download)? {
+                VersionDownload::add(conn, version_id, download.date, count)?;
+                total += count;
+            }
+        }
+
+        let inserted = diesel::insert_into(cdn_log_files::table)
+            .values((
+                cdn_log_files::path.eq(path),
+                cdn_log_files::downloads.eq(total),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        if inserted == 0 {
+            // Rolls back the downloads added above
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+        Ok(total)
+    })
+    .or_else(|e| match e {
+        diesel::result::Error::RollbackTransaction => Ok(0),
+        e => Err(e),
+    })
+}
+
+/// The id of the downloaded version. Versions published before their crate
+/// was renamed are downloaded under the old name, see
+/// `CrateRename::published_name`.
+fn version_id(conn: &PgConnection, download: &Download) -> QueryResult<Option<i32>> {
+    let crate_id = match crates::table
+        .filter(Crate::with_name(&download.crate_name))
+        .select(crates::id)
+        .first::<i32>(conn)
+        .optional()?
+    {
+        Some(crate_id) => crate_id,
+        None => match CrateRename::renamed_crate(conn, &download.crate_name)? {
+            Some((_, krate)) => krate.id,
+            None => return Ok(None),
+        },
+    };
+    versions::table
+        .filter(versions::crate_id.eq(crate_id))
+        .filter(versions::num.eq(&download.version))
+        .select(versions::id)
+        .first(conn)
+        .optional()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        schema::version_downloads,
+        test_fixtures::{crate_and_version, user},
+        test_util::pg_connection,
+    };
+    use chrono::NaiveDate;
+
+    fn download(crate_name: &str, version: &str) -> Download {
+        Download {
+            crate_name: crate_name.into(),
+            version: version.into(),
+            date: NaiveDate::from_ymd(2019, 11, 1),
+        }
+    }
+
+    fn downloads(conn: &PgConnection, version_id: i32) -> Vec<(NaiveDate, i32, bool)> {
+        version_downloads::table
+            .filter(version_downloads::version_id.eq(version_id))
+            .select((
+                version_downloads::date,
+                version_downloads::downloads,
+                version_downloads::processed,
+            ))
+            .order(version_downloads::date)
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn log_files_are_only_counted_once() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (_, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
+        let date = NaiveDate::from_ymd(2019, 11, 1);
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version.id),
+                version_downloads::date.eq(date),
+                version_downloads::downloads.eq(2),
+                version_downloads::counted.eq(2),
+                version_downloads::processed.eq(true),
+            ))
+            .execute(&conn)
+            .unwrap();
+
+        let mut counts = HashMap::new();
+        counts.insert(download("foo", "1.0.0"), 5);
+        counts.insert(download("foo", "9.9.9"), 3);
+        counts.insert(download("missing", "1.0.0"), 1);
+
+        assert_eq!(ingest(&conn, "logs/a.gz", &counts).unwrap(), 5);
+        assert_eq!(downloads(&conn, version.id), vec![(date, 7, false)]);
+
+        assert_eq!(ingest(&conn, "logs/a.gz", &counts).unwrap(), 0);
+        assert_eq!(downloads(&conn, version.id), vec![(date, 7, false)]);
+    }
+
+    #[test]
+    fn downloads_under_old_names_are_counted() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
+        CrateRename::rename(&conn, &krate, "bar", user.id).unwrap();
+
+        let mut counts = HashMap::new();
+        counts.insert(download("foo", "1.0.0"), 5);
+
+        assert_eq!(ingest(&conn, "logs/a.gz", &counts).unwrap(), 5);
+        let date = NaiveDate::from_ymd(2019, 11, 1);
+        assert_eq!(downloads(&conn, version.id), vec![(date, 5, false)]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    background_jobs::Environment,
+    email::{self, Unsubscribe},
+    models::{krate::ALL_COLUMNS, Crate, OwnerKind, Version},
+    schema::{crate_owners, crates, emails, users, versions},
+};
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+/// Emails the owners of the crates whose latest version depends on a version
+/// that was just yanked. Owners that turned off email notifications for a
+/// crate, or that haven't verified their email address, are skipped.
+#[swirl::background_job]
+pub fn notify_dependents_of_yank(env: &Environment, version_id: i32) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let (version, krate) = versions::table
+        .find(version_id)
+        .inner_join(crates::table)
+        .select((versions::all_columns, ALL_COLUMNS))
+        .first::<(Version, Crate)>(&*conn)?;
+
+    // The version might have been unyanked before we got to it
+    if !version.yanked {
+        return Ok(());
+    }
+
+    for (email, recipient) in recipients(&conn, &krate, &version)? {
+        let unsubscribe = Unsubscribe::CrateNotifications {
+            user_id: recipient.user_id,
+            crate_ids: recipient.crate_ids,
+        };
+        // A single invalid address shouldn't fail the job, since retrying
+        // it would email everyone else again
+        let _ = email::send_yanked_dependency_email(
+            &conn,
+            &email,
+            &krate.name,
+            &version.num.to_string(),
+            &recipient.crate_names,
+            &unsubscribe,
+        );
+    }
+    Ok(())
+}
+
+/// An owner to notify, with the dependent crates they own.
+#[derive(Debug, Default, PartialEq)]
+struct Recipient {
+    user_id: i32,
+    crate_ids: Vec<i32>,
+    crate_names: Vec<String>,
+}
+
+/// Returns the owner to notify for each email address.
+fn recipients(
+    conn: &PgConnection,
+    krate: &Crate,
+    version: &Version,
+) -> QueryResult<BTreeMap<String, Recipient>> {
+    let dependents = krate.dependents_of_version(conn, &version.num)?;
+
+    let owners = crate_owners::table
+        .inner_join(crates::table)
+        .inner_join(users::table.inner_join(emails::table))
+        .filter(crate_owners::deleted.eq(false))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crates::name.eq_any(&dependents))
+        .filter(crate_owners::email_notifications.eq(true))
+        .filter(emails::verified.eq(true))
+        .select((emails::email, users::id, crates::id, crates::name))
+        .order((emails::email, crates::name))
+        .load::<(String, i32, i32, String)>(conn)?;
+
+    let mut recipients = BTreeMap::<_, Recipient>::new();
+    for (email, user_id, crate_id, crate_name) in owners {
+        let recipient = recipients.entry(email).or_default();
+        recipient.user_id = user_id;
+        recipient.crate_ids.push(crate_id);
+        recipient.crate_names.push(crate_name);
+    }
+    Ok(recipients)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        schema::dependencies,
+        test_fixtures::{crate_and_version, user_with_email},
+        test_util::pg_connection,
+    };
+
+    fn add_dependency(conn: &PgConnection, version: &Version, krate: &Crate, req: &str) {
+        diesel::insert_into(dependencies::table)
+            .values((
+                dependencies::version_id.eq(version.id),
+                dependencies::crate_id.eq(krate.id),
+                dependencies::req.eq(req),
+                dependencies::optional.eq(false),
+                dependencies::default_features.eq(true),
+                dependencies::features.eq(Vec::<String>::new()),
+                dependencies::kind.eq(0),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn notifies_owners_of_matching_dependents() {
+        let conn = pg_connection();
+        let yanker = user_with_email(&conn, 1, "yanker", "yanker@example.com");
+        let owner = user_with_email(&conn, 2, "owner", "owner@example.com");
+        let (krate, version) = crate_and_version(&conn, "yanked", "1.0.0", yanker.id);
+
+        let (dependent, dependent_version) = crate_and_version(&conn, "dep_a", "1.0.0", owner.id);
+        add_dependency(&conn, &dependent_version, &krate, "^1.0");
+        let (_, other_version) = crate_and_version(&conn, "dep_b", "1.0.0", owner.id);
+        add_dependency(&conn, &other_version, &krate, "^2.0");
+        let (muted, muted_version) = crate_and_version(&conn, "dep_c", "1.0.0", owner.id);
+        add_dependency(&conn, &muted_version, &krate, "^1.0");
+        diesel::update(crate_owners::table.filter(crate_owners::crate_id.eq(muted.id)))
+            .set(crate_owners::email_notifications.eq(false))
+            .execute(&conn)
+            .unwrap();
+
+        let recipients = recipients(&conn, &krate, &version).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            String::from("owner@example.com"),
+            Recipient {
+                user_id: owner.id,
+                crate_ids: vec![dependent.id],
+                crate_names: vec![dependent.name],
+            },
+        );
+        assert_eq!(recipients, expected);
+    }
+}
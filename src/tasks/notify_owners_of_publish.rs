@@ -0,0 +1,116 @@
+use crate::{
+    background_jobs::Environment,
+    email::{self, Unsubscribe},
+    models::{krate::ALL_COLUMNS, Crate, OwnerKind, Version},
+    schema::{crate_owners, crates, emails, users, version_checksums, versions},
+};
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+/// Emails the owners of a crate other than the publisher about a new version,
+/// so that they notice releases they didn't expect, e.g. from a compromised
+/// account. Owners that turned off email notifications for the crate, or that
+/// haven't verified their email address, are skipped.
+#[swirl::background_job]
+pub fn notify_owners_of_publish(env: &Environment, version_id: i32) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let (version, krate, checksum) = versions::table
+        .find(version_id)
+        .inner_join(crates::table)
+        .inner_join(version_checksums::table)
+        .select((
+            versions::all_columns,
+            ALL_COLUMNS,
+            version_checksums::sha256,
+        ))
+        .first::<(Version, Crate, String)>(&*conn)?;
+    let publisher = match version.published_by {
+        Some(user_id) => users::table
+            .find(user_id)
+            .select(users::gh_login)
+            .first::<String>(&*conn)?,
+        None => return Ok(()),
+    };
+
+    for (email, user_id) in recipients(&conn, &krate, &version)? {
+        let unsubscribe = Unsubscribe::CrateNotifications {
+            user_id,
+            crate_ids: vec![krate.id],
+        };
+        // A single invalid address shouldn't fail the job, since retrying
+        // it would email the other owners again
+        let _ = email::send_version_published_email(
+            &conn,
+            &email,
+            &krate.name,
+            &version.num.to_string(),
+            &publisher,
+            &checksum,
+            &unsubscribe,
+        );
+    }
+    Ok(())
+}
+
+/// Returns the email address and id of the owners to notify.
+fn recipients(
+    conn: &PgConnection,
+    krate: &Crate,
+    version: &Version,
+) -> QueryResult<Vec<(String, i32)>> {
+    let mut query = crate_owners::table
+        .inner_join(users::table.inner_join(emails::table))
+        .filter(crate_owners::crate_id.eq(krate.id))
+        .filter(crate_owners::deleted.eq(false))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::email_notifications.eq(true))
+        .filter(emails::verified.eq(true))
+        .select((emails::email, users::id))
+        .order(emails::email)
+        .into_boxed();
+    if let Some(publisher) = version.published_by {
+        query = query.filter(users::id.ne(publisher));
+    }
+    query.load(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        models::{CrateOwner, User},
+        test_fixtures::{crate_and_version, user_with_email},
+        test_util::pg_connection,
+    };
+
+    fn add_owner(conn: &PgConnection, krate: &Crate, owner: &User, email_notifications: bool) {
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: owner.id,
+                created_by: owner.id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications,
+            })
+            .execute(conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn notifies_the_other_owners() {
+        let conn = pg_connection();
+        let publisher = user_with_email(&conn, 1, "publisher", "publisher@example.com");
+        let owner = user_with_email(&conn, 2, "owner", "owner@example.com");
+        let muted = user_with_email(&conn, 3, "muted", "muted@example.com");
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", publisher.id);
+        add_owner(&conn, &krate, &owner, true);
+        add_owner(&conn, &krate, &muted, false);
+
+        let recipients = recipients(&conn, &krate, &version).unwrap();
+        assert_eq!(
+            recipients,
+            vec![(String::from("owner@example.com"), owner.id)]
+        );
+    }
+}
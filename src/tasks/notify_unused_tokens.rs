@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::dsl::{any, now};
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::{
+    background_jobs::Environment,
+    email,
+    models::ApiToken,
+    schema::{api_tokens, emails, users},
+};
+
+/// Tokens that weren't used for this many days, about 6 months, are reported
+/// to their owners. Tokens that were never used count from their creation.
+const UNUSED_DAYS: i64 = 183;
+
+/// The unused tokens of a user, and where to tell them about it.
+#[derive(Debug)]
+struct Notification {
+    email: String,
+    user_name: String,
+    tokens: Vec<ApiToken>,
+}
+
+/// Emails users whose API tokens weren't used for `UNUSED_DAYS`, suggesting
+/// that they revoke them. Every token is only reported once, unless it is
+/// used again and then falls unused again. Users without a verified email
+/// address are skipped.
+#[swirl::background_job]
+pub fn notify_unused_tokens(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let cutoff = Utc::now().naive_utc() - Duration::days(UNUSED_DAYS);
+    for notification in unused_tokens(&conn, cutoff)?.values() {
+        let tokens = notification
+            .tokens
+            .iter()
+            .map(|token| {
+                let last_used = token
+                    .last_used_at
+                    .map(|last_used_at| last_used_at.format("%Y-%m-%d").to_string());
+                (token.name.clone(), last_used)
+            })
+            .collect::<Vec<_>>();
+        // A single invalid address shouldn't fail the job, since retrying it
+        // would email the other users again
+        let _ = email::send_unused_tokens_email(
+            &conn,
+            &notification.email,
+            &notification.user_name,
+            &tokens,
+        );
+
+        let ids = notification
+            .tokens
+            .iter()
+            .map(|token| token.id)
+            .collect::<Vec<_>>();
+        diesel::update(api_tokens::table.filter(api_tokens::id.eq(any(ids))))
+            .set(api_tokens::unused_notified_at.eq(now.nullable()))
+            .execute(&conn)?;
+    }
+    Ok(())
+}
+
+/// Returns the tokens that weren't used since `cutoff` and weren't reported
+/// yet, grouped by user id.
+fn unused_tokens(
+    conn: &PgConnection,
+    cutoff: NaiveDateTime,
+) -> QueryResult<BTreeMap<i32, Notification>> {
+    let unused = api_tokens::last_used_at
+        .lt(cutoff)
+        .or(api_tokens::last_used_at
+            .is_null()
+            .and(api_tokens::created_at.lt(cutoff)));
+    let tokens = api_tokens::table
+        .inner_join(users::table.inner_join(emails::table))
+        .filter(api_tokens::revoked.eq(false))
+        .filter(api_tokens::unused_notified_at.is_null())
+        .filter(unused)
+        .filter(users::is_banned.eq(false))
+        .filter(emails::verified.eq(true))
+        .select((api_tokens::all_columns, emails::email, users::gh_login))
+        .order((api_tokens::user_id, api_tokens::id))
+        .load::<(ApiToken, String, String)>(conn)?;
+
+    let mut notifications = BTreeMap::new();
+    for (token, email, user_name) in tokens {
+        notifications
+            .entry(token.user_id)
+            .or_insert_with(|| Notification {
+                email,
+                user_name,
+                tokens: Vec::new(),
+            })
+            .tokens
+            .push(token);
+    }
+    Ok(notifications)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_fixtures::user_with_email, test_util::pg_connection};
+
+    fn token(conn: &PgConnection, user_id: i32, name: &str, days_ago: i64) -> ApiToken {
+        let token = ApiToken::insert(conn, user_id, name).unwrap();
+        let created_at = Utc::now().naive_utc() - Duration::days(days_ago);
+        diesel::update(&token)
+            .set(api_tokens::created_at.eq(created_at))
+            .get_result(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn tokens_unused_for_six_months_are_reported_once() {
+        let conn = pg_connection();
+        let user = user_with_email(&conn, 2, "login", "login@example.com");
+
+        let never_used = token(&conn, user.id, "never used", 200);
+        token(&conn, user.id, "recent", 10);
+        let used_recently = token(&conn, user.id, "used recently", 200);
+        diesel::update(&used_recently)
+            .set(api_tokens::last_used_at.eq(now.nullable()))
+            .execute(&conn)
+            .unwrap();
+        let revoked = token(&conn, user.id, "revoked", 200);
+        diesel::update(&revoked)
+            .set(api_tokens::revoked.eq(true))
+            .execute(&conn)
+            .unwrap();
+
+        let cutoff = Utc::now().naive_utc() - Duration::days(UNUSED_DAYS);
+        let notifications = unused_tokens(&conn, cutoff).unwrap();
+        assert_eq!(notifications.len(), 1);
+        let notification = &notifications[&user.id];
+        assert_eq!(notification.email, "login@example.com");
+        assert_eq!(notification.user_name, "login");
+        let ids = notification
+            .tokens
+            .iter()
+            .map(|token| token.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [never_used.id]);
+
+        // Once reported, the token isn't reported again until it is used
+        diesel::update(&never_used)
+            .set(api_tokens::unused_notified_at.eq(now.nullable()))
+            .execute(&conn)
+            .unwrap();
+        assert!(unused_tokens(&conn, cutoff).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,22 @@
+use crate::background_jobs::Environment;
+
+use diesel::prelude::*;
+use diesel::select;
+use swirl::PerformError;
+
+/// Recomputes the crates listed by `GET /summary`. Since most recently
+/// downloaded and trending crates are taken from `recent_crate_downloads` and
+/// `trending_crates`, this should be scheduled after `update_downloads` and
+/// `update_trending_crates`.
+#[swirl::background_job]
+pub fn refresh_summary(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    refresh(&conn)?;
+    Ok(())
+}
+
+fn refresh(conn: &PgConnection) -> QueryResult<()> {
+    no_arg_sql_function!(refresh_summary_crates, ());
+    select(refresh_summary_crates).execute(conn)?;
+    Ok(())
+}
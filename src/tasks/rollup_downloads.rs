@@ -0,0 +1,108 @@
+use crate::background_jobs::Environment;
+use crate::logging;
+
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use swirl::PerformError;
+
+/// The periods for which download totals are rolled up, named after the
+/// `date_trunc` field they correspond to.
+const PERIODS: &[&str] = &["week", "month"];
+
+#[swirl::background_job]
+pub fn rollup_downloads(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    rollup(&conn)?;
+    Ok(())
+}
+
+fn rollup(conn: &PgConnection) -> QueryResult<()> {
+    for period in PERIODS {
+        let rows = diesel::sql_query(include_str!("rollup_downloads.sql"))
+            .bind::<Text, _>(period)
+            .execute(conn)?;
+        logging::info(
+            "rolled up download totals",
+            json!({ "rows": rows, "period": period }),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        schema::{crate_download_rollups, version_downloads},
+        test_fixtures::{crate_and_version, user},
+        test_util::pg_connection,
+    };
+    use chrono::NaiveDate;
+
+    fn add_downloads(conn: &PgConnection, version_id: i32, date: &str, downloads: i32) {
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq(NaiveDate::parse_from_str(date, "%F").unwrap()),
+                version_downloads::downloads.eq(downloads),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn rollups(conn: &PgConnection, crate_id: i32, period: &str) -> Vec<(String, i64)> {
+        crate_download_rollups::table
+            .filter(crate_download_rollups::crate_id.eq(crate_id))
+            .filter(crate_download_rollups::period.eq(period))
+            .select((
+                crate_download_rollups::period_start,
+                crate_download_rollups::downloads,
+            ))
+            .order(crate_download_rollups::period_start)
+            .load::<(NaiveDate, i64)>(conn)
+            .unwrap()
+            .into_iter()
+            .map(|(start, downloads)| (start.to_string(), downloads))
+            .collect()
+    }
+
+    #[test]
+    fn rolls_up_weeks_and_months() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
+        // 2019-10-28 is a Monday
+        add_downloads(&conn, version.id, "2019-10-28", 1);
+        add_downloads(&conn, version.id, "2019-10-31", 2);
+        add_downloads(&conn, version.id, "2019-11-01", 4);
+        add_downloads(&conn, version.id, "2019-11-04", 8);
+
+        rollup(&conn).unwrap();
+
+        assert_eq!(
+            rollups(&conn, krate.id, "week"),
+            vec![("2019-10-28".into(), 7), ("2019-11-04".into(), 8)]
+        );
+        assert_eq!(
+            rollups(&conn, krate.id, "month"),
+            vec![("2019-10-01".into(), 3), ("2019-11-01".into(), 12)]
+        );
+    }
+
+    #[test]
+    fn recomputes_the_latest_period() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
+        add_downloads(&conn, version.id, "2019-11-04", 1);
+        rollup(&conn).unwrap();
+
+        add_downloads(&conn, version.id, "2019-11-05", 2);
+        rollup(&conn).unwrap();
+
+        assert_eq!(
+            rollups(&conn, krate.id, "week"),
+            vec![("2019-11-04".into(), 3)]
+        );
+    }
+}
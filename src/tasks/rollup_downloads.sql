@@ -0,0 +1,19 @@
+-- Recomputes the weekly or monthly download totals of every crate, starting
+-- from the most recent period that has already been rolled up. Older periods
+-- are complete and will not change any more, so only the first run has to
+-- scan the whole `version_downloads` table.
+INSERT INTO crate_download_rollups (crate_id, period, period_start, downloads)
+SELECT versions.crate_id,
+       $1::text,
+       date_trunc($1::text, version_downloads.date::timestamp)::date,
+       SUM(version_downloads.downloads)
+  FROM version_downloads
+ INNER JOIN versions
+    ON versions.id = version_downloads.version_id
+ WHERE version_downloads.date >= COALESCE(
+         (SELECT MAX(period_start) FROM crate_download_rollups WHERE period = $1::text),
+         '-infinity'::date
+       )
+ GROUP BY 1, 3
+    ON CONFLICT (crate_id, period, period_start)
+    DO UPDATE SET downloads = EXCLUDED.downloads
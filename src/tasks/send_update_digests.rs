@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    background_jobs::Environment,
+    email::{self, Unsubscribe},
+    schema::update_digest_subscriptions,
+};
+
+use diesel::dsl::{any, now};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Text};
+use swirl::PerformError;
+
+/// The number of days covered by a digest. The job is meant to be enqueued
+/// once a week.
+const DIGEST_DAYS: i32 = 7;
+
+/// The number of subscribers whose digests are loaded and sent at once.
+const BATCH_SIZE: i64 = 100;
+
+#[derive(QueryableByName, Debug)]
+struct Release {
+    #[sql_type = "Integer"]
+    user_id: i32,
+    #[sql_type = "Text"]
+    email: String,
+    #[sql_type = "Text"]
+    crate_name: String,
+    #[sql_type = "Text"]
+    num: String,
+}
+
+/// A digest to send to a subscriber, with the crate names and version numbers
+/// of the new releases.
+#[derive(Debug, PartialEq)]
+struct Digest {
+    email: String,
+    releases: Vec<(String, String)>,
+}
+
+/// Emails the subscribers of the update digest the releases of the last week
+/// that show up in their `GET /me/updates`. Subscribers without new releases
+/// or without a verified email address are skipped.
+///
+/// Subscribers are handled in batches, and each batch is marked as sent once
+/// its emails were enqueued. A retried job only emails the remaining subscribers.
+#[swirl::background_job]
+pub fn send_update_digests(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    loop {
+        let batch = due_subscriptions(&conn)?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        for (user_id, digest) in digests(&conn, &batch)? {
+            let unsubscribe = Unsubscribe::UpdateDigest { user_id };
+            // A single invalid address shouldn't fail the job, since retrying
+            // it would email the rest of the batch again
+            let _ = email::send_update_digest_email(
+                &conn,
+                &digest.email,
+                &digest.releases,
+                &unsubscribe,
+            );
+        }
+        mark_sent(&conn, &batch)?;
+    }
+}
+
+/// Returns the user ids of the next batch of subscribers who haven't been
+/// sent a digest in this period yet.
+fn due_subscriptions(conn: &PgConnection) -> QueryResult<Vec<i32>> {
+    use self::update_digest_subscriptions::dsl::*;
+    use diesel::dsl::sql;
+    use diesel::sql_types::Bool;
+
+    // The period is a day shorter than the digest, so that a job enqueued a
+    // little earlier than last week's still finds all subscribers due
+    let due = sql::<Bool>("last_sent_at IS NULL OR last_sent_at < now() - ")
+        .bind::<Integer, _>(DIGEST_DAYS - 1)
+        .sql(" * INTERVAL '1 day'");
+    update_digest_subscriptions
+        .filter(due)
+        .select(user_id)
+        .order(user_id)
+        .limit(BATCH_SIZE)
+        .load(conn)
+}
+
+/// Returns the digests of the given subscribers, by user id.
+fn digests(conn: &PgConnection, user_ids: &[i32]) -> QueryResult<BTreeMap<i32, Digest>> {
+    let releases = diesel::sql_query(include_str!("send_update_digests.sql"))
+        .bind::<Integer, _>(DIGEST_DAYS)
+        .bind::<Array<Integer>, _>(user_ids)
+        .load::<Release>(conn)?;
+
+    let mut digests = BTreeMap::new();
+    for Release {
+        user_id,
+        email,
+        crate_name,
+        num,
+    } in releases
+    {
+        digests
+            .entry(user_id)
+            .or_insert_with(|| Digest {
+                email,
+                releases: Vec::new(),
+            })
+            .releases
+            .push((crate_name, num));
+    }
+    Ok(digests)
+}
+
+fn mark_sent(conn: &PgConnection, user_ids: &[i32]) -> QueryResult<()> {
+    use self::update_digest_subscriptions::dsl::*;
+
+    diesel::update(update_digest_subscriptions.filter(user_id.eq(any(user_ids))))
+        .set(last_sent_at.eq(now.nullable()))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        models::{Crate, FollowTarget, Keyword, TopicFollow, User},
+        schema::{follows, topic_follows, update_digest_subscriptions, versions},
+        test_fixtures::{crate_and_version, user_with_email},
+        test_util::pg_connection,
+    };
+    use chrono::{Duration, Utc};
+
+    fn subscriber(conn: &PgConnection) -> User {
+        let user = user_with_email(conn, 2, "login", "login@example.com");
+        diesel::insert_into(update_digest_subscriptions::table)
+            .values(update_digest_subscriptions::user_id.eq(user.id))
+            .execute(conn)
+            .unwrap();
+        user
+    }
+
+    fn krate(conn: &PgConnection, name: &str, user_id: i32, days_ago: i64) -> Crate {
+        let (krate, version) = crate_and_version(conn, name, "1.0.0", user_id);
+        diesel::update(&version)
+            .set(versions::created_at.eq(Utc::now().naive_utc() - Duration::days(days_ago)))
+            .execute(conn)
+            .unwrap();
+        krate
+    }
+
+    #[test]
+    fn digests_contain_releases_of_followed_crates_and_topics() {
+        let conn = pg_connection();
+        let user = subscriber(&conn);
+        let followed = krate(&conn, "followed", user.id, 1);
+        let old = krate(&conn, "old", user.id, 10);
+        let tagged = krate(&conn, "tagged", user.id, 2);
+        krate(&conn, "unrelated", user.id, 1);
+
+        diesel::insert_into(follows::table)
+            .values(&vec![
+                (
+                    follows::user_id.eq(user.id),
+                    follows::crate_id.eq(followed.id),
+                ),
+                (follows::user_id.eq(user.id), follows::crate_id.eq(old.id)),
+            ])
+            .execute(&conn)
+            .unwrap();
+        Keyword::update_crate(&conn, &tagged, &["digest"]).unwrap();
+        let keyword = Keyword::find_by_keyword(&conn, "digest").unwrap();
+        diesel::insert_into(topic_follows::table)
+            .values(&TopicFollow {
+                user_id: user.id,
+                kind: FollowTarget::Keyword,
+                target_id: keyword.id,
+            })
+            .execute(&conn)
+            .unwrap();
+
+        // Releases from before the keyword was followed are left out
+        let digests_of = |conn: &PgConnection| digests(conn, &[user.id]).unwrap();
+        assert_eq!(digests_of(&conn)[&user.id].releases.len(), 1);
+
+        diesel::update(topic_follows::table)
+            .set(topic_follows::created_at.eq(Utc::now().naive_utc() - Duration::days(3)))
+            .execute(&conn)
+            .unwrap();
+        assert_eq!(
+            digests_of(&conn)[&user.id],
+            Digest {
+                email: "login@example.com".into(),
+                releases: vec![
+                    ("followed".to_string(), "1.0.0".to_string()),
+                    ("tagged".to_string(), "1.0.0".to_string())
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn subscribers_are_only_due_once_per_period() {
+        let conn = pg_connection();
+        let user = subscriber(&conn);
+
+        assert_eq!(due_subscriptions(&conn).unwrap(), [user.id]);
+
+        mark_sent(&conn, &[user.id]).unwrap();
+        assert!(due_subscriptions(&conn).unwrap().is_empty());
+
+        diesel::update(update_digest_subscriptions::table)
+            .set(
+                update_digest_subscriptions::last_sent_at
+                    .eq(Utc::now().naive_utc() - Duration::days(7)),
+            )
+            .execute(&conn)
+            .unwrap();
+        assert_eq!(due_subscriptions(&conn).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,13 @@
+-- The non-yanked versions published in the last $1 days by the crates that
+-- the subscribers with the ids in $2 follow, see `followed_crates`
+SELECT emails.user_id, emails.email, crates.name AS crate_name, versions.num
+FROM emails
+CROSS JOIN LATERAL followed_crates(emails.user_id) followed
+INNER JOIN versions ON versions.crate_id = followed.crate_id
+INNER JOIN crates ON crates.id = versions.crate_id
+WHERE emails.user_id = ANY($2)
+AND emails.verified
+AND NOT versions.yanked
+AND versions.created_at > followed.since
+AND versions.created_at >= now() - $1 * INTERVAL '1 day'
+ORDER BY emails.user_id, crates.name, versions.created_at
@@ -0,0 +1,51 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    background_jobs::Environment,
+    logging,
+    models::{team::github_team_contains_user, TeamMembership},
+};
+
+use swirl::PerformError;
+
+/// How far ahead of their expiry cached team memberships are checked again.
+/// Longer than the interval the job runs at, so that memberships in use are
+/// never checked during a request.
+const REFRESH_WINDOW_MINUTES: i64 = 15;
+
+/// Checks the cached GitHub team memberships that are about to expire again,
+/// see `Team::contains_user`. Memberships that expired, because their team
+/// doesn't own crates anymore or GitHub couldn't be asked, are deleted.
+#[swirl::background_job]
+pub fn sync_team_memberships(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let before = Utc::now().naive_utc() + Duration::minutes(REFRESH_WINDOW_MINUTES);
+
+    let mut refreshed = 0;
+    let mut failed = 0;
+    for (membership, github_id, user) in TeamMembership::expiring(&conn, before)? {
+        // `Config::api_protocol` is only changed in tests
+        match github_team_contains_user(env.http_client(), "https", github_id, &user) {
+            Ok(is_member) => {
+                TeamMembership::record(
+                    &conn,
+                    membership.team_id,
+                    membership.user_id,
+                    is_member,
+                    membership.ttl(),
+                )?;
+                refreshed += 1;
+            }
+            // A revoked token shouldn't fail the job, the membership is
+            // checked during the next request instead
+            Err(_) => failed += 1,
+        }
+    }
+    let deleted = TeamMembership::delete_expired(&conn)?;
+
+    logging::info(
+        "synced team memberships",
+        json!({ "refreshed": refreshed, "failed": failed, "deleted": deleted }),
+    );
+    Ok(())
+}
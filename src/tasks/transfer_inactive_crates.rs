@@ -0,0 +1,101 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::{
+    background_jobs::Environment,
+    email, logging,
+    models::crate_successor::{Step, WARNING_DAYS},
+    models::CrateSuccessor,
+    schema::{crates, emails, users},
+};
+
+/// Carries out the accepted designations of crate successors, see
+/// `models::CrateSuccessor`. The sole owner of a crate is warned before the
+/// crate is transferred, and the crate is only transferred to its successor
+/// if the owner stays inactive for `WARNING_DAYS` after that. Crates with
+/// several owners are left alone.
+#[swirl::background_job]
+pub fn transfer_inactive_crates(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let now = Utc::now().naive_utc();
+    let mut warned = 0;
+    let mut transferred = 0;
+    for successor in CrateSuccessor::accepted(&conn)? {
+        let owner_id = match successor.sole_owner(&conn)? {
+            Some(owner_id) => owner_id,
+            None => continue,
+        };
+        let last_active = successor.last_active(&conn, owner_id)?;
+        let step = successor.next_step(last_active, now);
+        if step == Step::Wait {
+            continue;
+        }
+        if step == Step::Reset {
+            successor.set_warned_at(&conn, None)?;
+            continue;
+        }
+
+        let crate_name = crates::table
+            .find(successor.crate_id)
+            .select(crates::name)
+            .first::<String>(&conn)?;
+        let (owner, owner_email) = contact(&conn, owner_id)?;
+        let (successor_login, successor_email) = contact(&conn, successor.successor_id)?;
+        // A single invalid address shouldn't fail the job, since retrying it
+        // would email the other owners again
+        if step == Step::Warn {
+            successor.set_warned_at(&conn, Some(now))?;
+            let transfer_on = successor
+                .deadline(last_active)
+                .max(now + Duration::days(WARNING_DAYS))
+                .format("%Y-%m-%d")
+                .to_string();
+            if let Some(address) = owner_email {
+                let _ = email::send_successor_warning_email(
+                    &conn,
+                    &address,
+                    &crate_name,
+                    &successor_login,
+                    successor.inactive_months,
+                    &transfer_on,
+                );
+            }
+            warned += 1;
+        } else {
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                successor.transfer(&conn, owner_id)
+            })?;
+            for address in owner_email.iter().chain(&successor_email) {
+                let _ = email::send_successor_transfer_email(
+                    &conn,
+                    address,
+                    &crate_name,
+                    &owner,
+                    &successor_login,
+                );
+            }
+            transferred += 1;
+        }
+    }
+    logging::info(
+        "transferred inactive crates",
+        json!({ "warned": warned, "transferred": transferred }),
+    );
+    Ok(())
+}
+
+/// The login of a user and their verified email address, if any.
+fn contact(conn: &PgConnection, user_id: i32) -> QueryResult<(String, Option<String>)> {
+    let login = users::table
+        .find(user_id)
+        .select(users::gh_login)
+        .first(conn)?;
+    let email = emails::table
+        .filter(emails::user_id.eq(user_id))
+        .filter(emails::verified.eq(true))
+        .select(emails::email)
+        .first(conn)
+        .optional()?;
+    Ok((login, email))
+}
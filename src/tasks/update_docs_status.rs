@@ -0,0 +1,87 @@
+use crate::{
+    background_jobs::Environment,
+    logging,
+    schema::{crates, versions},
+};
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+/// The number of versions checked against docs.rs in a single run of the job.
+const BATCH_SIZE: i64 = 500;
+
+/// How long after publishing docs.rs is asked about a version. Builds that
+/// haven't happened by then most likely never will.
+const CHECK_DAYS: i32 = 30;
+
+static DOCS_RS_URL: &str = "https://docs.rs";
+
+#[derive(Deserialize)]
+struct DocsBuild {
+    build_status: bool,
+}
+
+/// Asks docs.rs whether the documentation of recently published versions was
+/// built, and records the answer in `versions.docs_built`. Versions docs.rs
+/// hasn't built yet are left unknown and checked again on the next run.
+#[swirl::background_job]
+pub fn update_docs_status(env: &Environment) -> Result<(), PerformError> {
+    use diesel::dsl::*;
+
+    let conn = env.connection()?;
+    let pending = versions::table
+        .inner_join(crates::table)
+        .filter(versions::docs_built.is_null())
+        .filter(versions::yanked.eq(false))
+        .filter(versions::created_at.gt(now - CHECK_DAYS.days()))
+        .select((versions::id, crates::name, versions::num))
+        .order(versions::created_at)
+        .limit(BATCH_SIZE)
+        .load::<(i32, String, String)>(&*conn)?;
+
+    logging::info(
+        "checking docs.rs builds",
+        json!({ "versions": pending.len() }),
+    );
+
+    for (version_id, crate_name, num) in pending {
+        let url = format!("{}/crate/{}/{}/builds.json", DOCS_RS_URL, crate_name, num);
+        let mut response = env.http_client().get(&url).send()?;
+        if !response.status().is_success() {
+            // docs.rs doesn't know about versions it hasn't queued yet
+            continue;
+        }
+        let builds = response.json::<Vec<DocsBuild>>()?;
+
+        if let Some(docs_built) = docs_built(&builds) {
+            diesel::update(versions::table.find(version_id))
+                .set(versions::docs_built.eq(docs_built))
+                .execute(&*conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// docs.rs lists the most recent build first, and only that one decides
+/// whether documentation is available.
+fn docs_built(builds: &[DocsBuild]) -> Option<bool> {
+    builds.first().map(|build| build.build_status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latest_build_decides() {
+        let builds: Vec<DocsBuild> = serde_json::from_str(
+            r#"[
+                {"id": 2, "rustc_version": "rustc 1.40.0", "build_status": true},
+                {"id": 1, "rustc_version": "rustc 1.39.0", "build_status": false}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(docs_built(&builds), Some(true));
+        assert_eq!(docs_built(&[]), None);
+    }
+}
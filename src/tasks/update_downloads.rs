@@ -1,5 +1,6 @@
 use crate::{
     background_jobs::Environment,
+    logging,
     models::VersionDownload,
     schema::{crates, metadata, version_downloads, versions},
 };
@@ -42,7 +43,7 @@ fn update(conn: &PgConnection) -> QueryResult<()> {
 fn collect(conn: &PgConnection, rows: &[VersionDownload]) -> QueryResult<()> {
     use diesel::update;
 
-    println!("updating {} versions", rows.len());
+    logging::info("updating downloads", json!({ "versions": rows.len() }));
 
     for download in rows {
         let amt = download.downloads - download.counted;
@@ -81,51 +82,18 @@ fn collect(conn: &PgConnection, rows: &[VersionDownload]) -> QueryResult<()> {
 mod test {
     use super::*;
     use crate::{
-        env,
-        models::{Crate, NewCrate, NewUser, NewVersion, User, Version},
+        models::{Crate, Version},
+        test_fixtures::{crate_and_version, user},
+        test_util::pg_connection,
     };
-    use std::collections::HashMap;
-
-    fn conn() -> PgConnection {
-        let conn = PgConnection::establish(&env("TEST_DATABASE_URL")).unwrap();
-        conn.begin_test_transaction().unwrap();
-        conn
-    }
-
-    fn user(conn: &PgConnection) -> User {
-        NewUser::new(2, "login", None, None, None, "access_token")
-            .create_or_update(conn)
-            .unwrap()
-    }
-
-    fn crate_and_version(conn: &PgConnection, user_id: i32) -> (Crate, Version) {
-        let krate = NewCrate {
-            name: "foo",
-            ..Default::default()
-        }
-        .create_or_update(conn, user_id, None)
-        .unwrap();
-        let version = NewVersion::new(
-            krate.id,
-            &semver::Version::parse("1.0.0").unwrap(),
-            &HashMap::new(),
-            None,
-            None,
-            0,
-            user_id,
-        )
-        .unwrap();
-        let version = version.save(conn, &[], "someone@example.com").unwrap();
-        (krate, version)
-    }
 
     #[test]
     fn increment() {
         use diesel::dsl::*;
 
-        let conn = conn();
+        let conn = pg_connection();
         let user = user(&conn);
-        let (krate, version) = crate_and_version(&conn, user.id);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
         insert_into(version_downloads::table)
             .values(version_downloads::version_id.eq(version.id))
             .execute(&conn)
@@ -162,9 +130,9 @@ mod test {
     fn set_processed_true() {
         use diesel::dsl::*;
 
-        let conn = conn();
+        let conn = pg_connection();
         let user = user(&conn);
-        let (_, version) = crate_and_version(&conn, user.id);
+        let (_, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
         insert_into(version_downloads::table)
             .values((
                 version_downloads::version_id.eq(version.id),
@@ -186,9 +154,9 @@ mod test {
     #[test]
     fn dont_process_recent_row() {
         use diesel::dsl::*;
-        let conn = conn();
+        let conn = pg_connection();
         let user = user(&conn);
-        let (_, version) = crate_and_version(&conn, user.id);
+        let (_, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
         insert_into(version_downloads::table)
             .values((
                 version_downloads::version_id.eq(version.id),
@@ -212,9 +180,9 @@ mod test {
         use diesel::dsl::*;
         use diesel::update;
 
-        let conn = conn();
+        let conn = pg_connection();
         let user = user(&conn);
-        let (krate, version) = crate_and_version(&conn, user.id);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
         update(versions::table)
             .set(versions::updated_at.eq(now - 2.hours()))
             .execute(&conn)
@@ -275,9 +243,9 @@ mod test {
         use diesel::dsl::*;
         use diesel::update;
 
-        let conn = conn();
+        let conn = pg_connection();
         let user = user(&conn);
-        let (_, version) = crate_and_version(&conn, user.id);
+        let (_, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
         update(versions::table)
             .set(versions::updated_at.eq(now - 2.days()))
             .execute(&conn)
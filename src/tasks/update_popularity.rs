@@ -0,0 +1,115 @@
+use crate::background_jobs::Environment;
+use crate::logging;
+
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Integer};
+use swirl::PerformError;
+
+/// After how many days downloads count for half as much towards the
+/// popularity of a crate.
+const HALF_LIFE_DAYS: f64 = 30.0;
+
+/// How many days of downloads are counted. Older downloads would count for
+/// less than 2% of recent ones anyway.
+const DOWNLOAD_DAYS: i32 = 180;
+
+/// How much the number of crates depending on a crate counts compared to its
+/// downloads, on a logarithmic scale.
+const DEPENDENTS_WEIGHT: f64 = 2.0;
+
+/// Recomputes `crates.popularity`, which crates are sorted by with
+/// `sort=popularity`.
+#[swirl::background_job]
+pub fn update_popularity(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    update(&conn)?;
+    Ok(())
+}
+
+fn update(conn: &PgConnection) -> QueryResult<()> {
+    let rows = diesel::sql_query(include_str!("update_popularity.sql"))
+        .bind::<Double, _>(HALF_LIFE_DAYS)
+        .bind::<Integer, _>(DOWNLOAD_DAYS)
+        .bind::<Double, _>(DEPENDENTS_WEIGHT)
+        .execute(conn)?;
+    logging::info("updated crate popularity", json!({ "crates": rows }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        models::Crate,
+        schema::{crates, dependencies, version_downloads},
+        test_fixtures::{crate_and_version, user},
+        test_util::pg_connection,
+    };
+    use chrono::{Duration, Utc};
+
+    fn add_downloads(conn: &PgConnection, version_id: i32, days_ago: i64, downloads: i32) {
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq((Utc::today() - Duration::days(days_ago)).naive_utc()),
+                version_downloads::downloads.eq(downloads),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn add_dependency(conn: &PgConnection, version_id: i32, crate_id: i32) {
+        diesel::insert_into(dependencies::table)
+            .values((
+                dependencies::version_id.eq(version_id),
+                dependencies::crate_id.eq(crate_id),
+                dependencies::req.eq("^1"),
+                dependencies::optional.eq(false),
+                dependencies::default_features.eq(true),
+                dependencies::features.eq(Vec::<String>::new()),
+                dependencies::kind.eq(0),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn popularity(conn: &PgConnection, krate: &Crate) -> f64 {
+        crates::table
+            .find(krate.id)
+            .select(crates::popularity)
+            .first(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn recent_downloads_count_more() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (recent, version) = crate_and_version(&conn, "recent", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 0, 1000);
+        let (old, version) = crate_and_version(&conn, "old", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 60, 1000);
+        let (expired, version) = crate_and_version(&conn, "expired", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 200, 1000);
+
+        update(&conn).unwrap();
+
+        assert!((popularity(&conn, &recent) - 1001f64.ln()).abs() < 1e-9);
+        assert!((popularity(&conn, &old) - 251f64.ln()).abs() < 1e-9);
+        assert_eq!(popularity(&conn, &expired), 0.0);
+    }
+
+    #[test]
+    fn dependents_count_towards_popularity() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (dependency, _) = crate_and_version(&conn, "dependency", "1.0.0", user.id);
+        let (_, version) = crate_and_version(&conn, "dependent", "1.0.0", user.id);
+        add_dependency(&conn, version.id, dependency.id);
+
+        update(&conn).unwrap();
+
+        let expected = DEPENDENTS_WEIGHT * 2f64.ln();
+        assert!((popularity(&conn, &dependency) - expected).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,38 @@
+-- Scores every crate by the logarithms of its recent downloads and of the
+-- number of crates depending on it, so that neither dominates the other.
+-- Downloads count for half as much every $1 days, and downloads older than $2
+-- days aren't counted at all. The dependents are weighted by $3. Only crates
+-- whose score changed are updated.
+WITH decayed_downloads AS (
+  SELECT versions.crate_id,
+         SUM(version_downloads.downloads::float8
+           * POWER(0.5::float8, (CURRENT_DATE - version_downloads.date)::float8 / $1)) AS downloads
+    FROM version_downloads
+   INNER JOIN versions
+      ON versions.id = version_downloads.version_id
+   WHERE version_downloads.date > CURRENT_DATE - $2
+   GROUP BY versions.crate_id
+), dependents AS (
+  SELECT dependencies.crate_id,
+         COUNT(DISTINCT versions.crate_id) AS dependents
+    FROM dependencies
+   INNER JOIN versions
+      ON versions.id = dependencies.version_id
+   WHERE NOT versions.yanked
+     AND versions.crate_id <> dependencies.crate_id
+   GROUP BY dependencies.crate_id
+), scores AS (
+  SELECT crates.id,
+         LN(1 + COALESCE(decayed_downloads.downloads, 0))
+           + $3 * LN(1 + COALESCE(dependents.dependents, 0)) AS popularity
+    FROM crates
+    LEFT JOIN decayed_downloads
+      ON decayed_downloads.crate_id = crates.id
+    LEFT JOIN dependents
+      ON dependents.crate_id = crates.id
+)
+UPDATE crates
+   SET popularity = scores.popularity
+  FROM scores
+ WHERE crates.id = scores.id
+   AND crates.popularity <> scores.popularity
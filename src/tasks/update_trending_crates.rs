@@ -0,0 +1,100 @@
+use crate::background_jobs::Environment;
+use crate::logging;
+use crate::schema::trending_crates;
+
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use swirl::PerformError;
+
+/// The number of downloads in the last week a crate needs to be trending.
+const MIN_WEEKLY_DOWNLOADS: i64 = 100;
+/// The number of trending crates that are kept.
+const MAX_TRENDING_CRATES: i64 = 100;
+
+#[swirl::background_job]
+pub fn update_trending_crates(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    update(&conn)?;
+    Ok(())
+}
+
+fn update(conn: &PgConnection) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(trending_crates::table).execute(conn)?;
+        let rows = diesel::sql_query(include_str!("update_trending_crates.sql"))
+            .bind::<BigInt, _>(MIN_WEEKLY_DOWNLOADS)
+            .bind::<BigInt, _>(MAX_TRENDING_CRATES)
+            .execute(conn)?;
+        logging::info("found trending crates", json!({ "crates": rows }));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        schema::version_downloads,
+        test_fixtures::{crate_and_version, user},
+        test_util::pg_connection,
+    };
+    use chrono::{Duration, Utc};
+
+    fn add_downloads(conn: &PgConnection, version_id: i32, days_ago: i64, downloads: i32) {
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq((Utc::today() - Duration::days(days_ago)).naive_utc()),
+                version_downloads::downloads.eq(downloads),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn trending(conn: &PgConnection) -> Vec<(i32, i32, f64)> {
+        trending_crates::table
+            .select((
+                trending_crates::rank,
+                trending_crates::crate_id,
+                trending_crates::growth,
+            ))
+            .order(trending_crates::rank)
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn ranks_crates_by_week_over_week_growth() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (doubled, version) = crate_and_version(&conn, "doubled", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 10, 100);
+        add_downloads(&conn, version.id, 1, 200);
+        let (steady, version) = crate_and_version(&conn, "steady", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 10, 500);
+        add_downloads(&conn, version.id, 1, 500);
+        let (_, version) = crate_and_version(&conn, "too_few_downloads", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 1, 99);
+
+        update(&conn).unwrap();
+
+        assert_eq!(
+            trending(&conn),
+            vec![(1, doubled.id, 1.0), (2, steady.id, 0.0)]
+        );
+    }
+
+    #[test]
+    fn replaces_previous_results() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let (krate, version) = crate_and_version(&conn, "foo", "1.0.0", user.id);
+        add_downloads(&conn, version.id, 1, 100);
+        update(&conn).unwrap();
+        assert_eq!(trending(&conn), vec![(1, krate.id, 100.0)]);
+
+        add_downloads(&conn, version.id, 0, 100);
+        update(&conn).unwrap();
+        assert_eq!(trending(&conn), vec![(1, krate.id, 200.0)]);
+    }
+}
@@ -0,0 +1,31 @@
+-- Ranks crates by the growth of their downloads in the last 7 days over the 7
+-- days before. Crates with fewer than $1 downloads in the last 7 days are
+-- left out, so that a handful of downloads of an unknown crate doesn't count
+-- as growth. Only the top $2 crates are kept.
+INSERT INTO trending_crates (crate_id, rank, last_week_downloads, previous_week_downloads, growth)
+SELECT crate_id,
+       ROW_NUMBER() OVER (ORDER BY growth DESC, last_week DESC, crate_id),
+       last_week,
+       previous_week,
+       growth
+  FROM (
+    SELECT crate_id,
+           last_week,
+           previous_week,
+           (last_week - previous_week)::float8 / GREATEST(previous_week, 1) AS growth
+      FROM (
+        SELECT versions.crate_id,
+               COALESCE(SUM(version_downloads.downloads)
+                 FILTER (WHERE version_downloads.date > CURRENT_DATE - 7), 0) AS last_week,
+               COALESCE(SUM(version_downloads.downloads)
+                 FILTER (WHERE version_downloads.date <= CURRENT_DATE - 7), 0) AS previous_week
+          FROM version_downloads
+         INNER JOIN versions
+            ON versions.id = version_downloads.version_id
+         WHERE version_downloads.date > CURRENT_DATE - 14
+         GROUP BY versions.crate_id
+      ) AS weekly_downloads
+     WHERE last_week >= $1
+  ) AS weekly_growth
+ ORDER BY growth DESC, last_week DESC, crate_id
+ LIMIT $2
@@ -0,0 +1,106 @@
+use crate::background_jobs::Environment;
+use crate::logging;
+use crate::schema::version_adoption;
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+/// Recomputes the share of the downloads of every crate in the last 90 days
+/// that went to each of its versions, see
+/// `controllers::krate::downloads::version_adoption`.
+#[swirl::background_job]
+pub fn update_version_adoption(env: &Environment) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    update(&conn)?;
+    Ok(())
+}
+
+fn update(conn: &PgConnection) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(version_adoption::table).execute(conn)?;
+        let rows = diesel::sql_query(include_str!("update_version_adoption.sql")).execute(conn)?;
+        logging::info("computed version adoption", json!({ "versions": rows }));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        models::{Crate, NewCrate},
+        schema::version_downloads,
+        test_fixtures::{user, version},
+        test_util::pg_connection,
+    };
+    use chrono::{Duration, Utc};
+
+    fn krate(conn: &PgConnection, user_id: i32) -> Crate {
+        NewCrate {
+            name: "foo",
+            ..Default::default()
+        }
+        .create_or_update(conn, user_id, None)
+        .unwrap()
+    }
+
+    fn add_downloads(conn: &PgConnection, version_id: i32, days_ago: i64, downloads: i32) {
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq((Utc::today() - Duration::days(days_ago)).naive_utc()),
+                version_downloads::downloads.eq(downloads),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn adoption(conn: &PgConnection) -> Vec<(i32, i64, f64)> {
+        version_adoption::table
+            .select((
+                version_adoption::version_id,
+                version_adoption::downloads,
+                version_adoption::share,
+            ))
+            .order(version_adoption::version_id)
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn computes_the_share_of_recent_downloads() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let krate = krate(&conn, user.id);
+        let old = version(&conn, krate.id, "0.9.0", user.id);
+        let current = version(&conn, krate.id, "1.0.0", user.id);
+        let unused = version(&conn, krate.id, "1.0.1", user.id);
+        add_downloads(&conn, old.id, 10, 100);
+        add_downloads(&conn, current.id, 10, 200);
+        add_downloads(&conn, current.id, 1, 100);
+        // Downloads older than 90 days don't count
+        add_downloads(&conn, unused.id, 100, 1000);
+
+        update(&conn).unwrap();
+
+        assert_eq!(
+            adoption(&conn),
+            vec![(old.id, 100, 0.25), (current.id, 300, 0.75)]
+        );
+    }
+
+    #[test]
+    fn replaces_previous_results() {
+        let conn = pg_connection();
+        let user = user(&conn);
+        let krate = krate(&conn, user.id);
+        let version = version(&conn, krate.id, "1.0.0", user.id);
+        add_downloads(&conn, version.id, 1, 100);
+        update(&conn).unwrap();
+        assert_eq!(adoption(&conn), vec![(version.id, 100, 1.0)]);
+
+        add_downloads(&conn, version.id, 0, 100);
+        update(&conn).unwrap();
+        assert_eq!(adoption(&conn), vec![(version.id, 200, 1.0)]);
+    }
+}
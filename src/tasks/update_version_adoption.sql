@@ -0,0 +1,19 @@
+-- Computes the downloads of every version in the last 90 days, and their
+-- share of the downloads of the crate in that time. Versions without
+-- downloads in that time are left out.
+INSERT INTO version_adoption (version_id, crate_id, downloads, share)
+SELECT version_id,
+       crate_id,
+       downloads,
+       downloads::float8 / (SUM(downloads) OVER (PARTITION BY crate_id))::float8
+  FROM (
+    SELECT version_downloads.version_id,
+           versions.crate_id,
+           SUM(version_downloads.downloads) AS downloads
+      FROM version_downloads
+     INNER JOIN versions
+        ON versions.id = version_downloads.version_id
+     WHERE version_downloads.date > CURRENT_DATE - 90
+     GROUP BY 1, 2
+    HAVING SUM(version_downloads.downloads) > 0
+  ) AS recent_downloads
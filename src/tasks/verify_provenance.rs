@@ -0,0 +1,51 @@
+use diesel::prelude::*;
+use reqwest::{header, StatusCode};
+use swirl::PerformError;
+
+use crate::{
+    background_jobs::Environment,
+    github,
+    models::VersionProvenance,
+    schema::{crates, version_provenance, versions},
+};
+
+/// Checks whether the commit a version was published from, as stated by the
+/// CI run that published it, exists in the repository of the crate. Only
+/// repositories on GitHub can be checked, the provenance of other versions
+/// stays unverified.
+#[swirl::background_job]
+pub fn verify_provenance(env: &Environment, version_id: i32) -> Result<(), PerformError> {
+    let conn = env.connection()?;
+    let (provenance, repository) = version_provenance::table
+        .find(version_id)
+        .inner_join(versions::table.inner_join(crates::table))
+        .select((version_provenance::all_columns, crates::repository))
+        .first::<(VersionProvenance, Option<String>)>(&*conn)?;
+
+    let (owner, repo) = match repository
+        .as_ref()
+        .and_then(|r| github::repository_from_url(r))
+    {
+        Some(repository) => repository,
+        None => return Ok(()),
+    };
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/commits/{}",
+        owner, repo, provenance.commit_sha
+    );
+    let response = env
+        .http_client()
+        .get(&url)
+        .header(header::ACCEPT, "application/vnd.github.v3+json")
+        .send()?;
+    let verified = match response.status() {
+        s if s.is_success() => true,
+        // GitHub answers 422 for commits that don't exist in an existing
+        // repository
+        StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY => false,
+        // Rate limits and outages are retried
+        s => return Err(format!("couldn't check the commit on github: {}", s).into()),
+    };
+    provenance.set_verified(&conn, verified)?;
+    Ok(())
+}
@@ -0,0 +1,436 @@
+//! Traces of requests and of the background jobs they enqueue, in the
+//! OpenTelemetry format.
+//!
+//! Spans are exported in batches to the collector at
+//! `OTEL_EXPORTER_OTLP_ENDPOINT`, e.g. `http://localhost:4318`, with OTLP over
+//! HTTP and the JSON encoding. Headers for the collector, e.g. to
+//! authenticate, are read from `OTEL_EXPORTER_OTLP_HEADERS` as comma separated
+//! `name=value` pairs. Without an endpoint, spans are still started so that
+//! trace contexts are passed on, but they aren't exported.
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::logging;
+
+/// How many finished spans are kept while the collector can't keep up.
+/// Further spans are dropped.
+const QUEUE_SIZE: usize = 4096;
+const BATCH_SIZE: usize = 512;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref EXPORTER: Mutex<Option<SyncSender<Value>>> = Mutex::new(None);
+}
+
+thread_local! {
+    /// The spans started on this thread that haven't ended yet
+    static STACK: RefCell<Vec<SpanContext>> = RefCell::new(Vec::new());
+}
+
+/// Starts exporting spans if an endpoint is configured, see the module
+/// documentation. `service_name` identifies the process in the traces.
+pub fn init(service_name: &str) {
+    let endpoint = match dotenv::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => endpoint,
+        Err(_) => return,
+    };
+    let mut headers = HeaderMap::new();
+    if let Ok(pairs) = dotenv::var("OTEL_EXPORTER_OTLP_HEADERS") {
+        for pair in pairs.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let mut split = pair.splitn(2, '=');
+            let name = split.next().unwrap().trim();
+            let value = split.next().unwrap_or_default().trim();
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .expect("Invalid header name in `OTEL_EXPORTER_OTLP_HEADERS`"),
+                HeaderValue::from_str(value)
+                    .expect("Invalid header value in `OTEL_EXPORTER_OTLP_HEADERS`"),
+            );
+        }
+    }
+
+    let exporter = Exporter {
+        url: format!("{}/v1/traces", endpoint.trim_end_matches('/')),
+        headers,
+        service_name: service_name.to_string(),
+    };
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+    thread::spawn(move || exporter.run(receiver));
+    *EXPORTER.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender);
+}
+
+/// The kind of a span, with the values defined by OTLP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal = 1,
+    /// Handling a request
+    Server = 2,
+    /// Calling another service, like the database or S3
+    Client = 3,
+    /// Running a background job
+    Consumer = 5,
+}
+
+/// Identifies a span within a trace. Background jobs take it as an argument,
+/// so that their spans continue the trace of the request that enqueued them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+impl SpanContext {
+    /// Parses a W3C `traceparent` header.
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let parts = header.trim().split('-').collect::<Vec<_>>();
+        match parts.as_slice() {
+            [version, trace_id, span_id, flags]
+                if *version == "00"
+                    && is_id(trace_id, 32)
+                    && is_id(span_id, 16)
+                    && flags.len() == 2 =>
+            {
+                Some(SpanContext {
+                    trace_id: trace_id.to_string(),
+                    span_id: span_id.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the span of the current thread, if any.
+pub fn current() -> Option<SpanContext> {
+    STACK.with(|stack| stack.borrow().last().cloned())
+}
+
+/// Converts the ID of a request into a trace ID if it has the same format,
+/// like the UUIDs set by Heroku's router, so that its logs and traces can be
+/// found with the same ID.
+pub fn trace_id_from_request_id(request_id: &str) -> Option<String> {
+    let id = request_id.replace('-', "").to_lowercase();
+    if is_id(&id, 32) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// Runs a background job in a span that continues the trace of the request
+/// that enqueued it.
+pub fn in_job_span<T, E: Display>(
+    job_type: &str,
+    trace: Option<SpanContext>,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let name = format!("job {}", job_type);
+    let mut span = match trace {
+        Some(parent) => Span::with_parent(name, SpanKind::Consumer, &parent),
+        None => Span::start(name, SpanKind::Consumer),
+    };
+    span.set_attribute("job.type", job_type);
+    span.record(f())
+}
+
+/// A span that ends when it is dropped. Until then it is the parent of the
+/// spans started on the same thread.
+#[derive(Debug)]
+pub struct Span {
+    context: SpanContext,
+    parent_span_id: Option<String>,
+    name: String,
+    kind: SpanKind,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+    error: Option<String>,
+}
+
+impl Span {
+    /// Starts a child of the current span, or a new trace if there is none.
+    pub fn start(name: impl Into<String>, kind: SpanKind) -> Self {
+        match current() {
+            Some(parent) => Self::with_parent(name, kind, &parent),
+            None => Self::new(name.into(), kind, random_id(16), None),
+        }
+    }
+
+    /// Starts a child of a span of another thread or process.
+    pub fn with_parent(name: impl Into<String>, kind: SpanKind, parent: &SpanContext) -> Self {
+        Self::new(
+            name.into(),
+            kind,
+            parent.trace_id.clone(),
+            Some(parent.span_id.clone()),
+        )
+    }
+
+    /// Starts the first span of the trace with the given ID.
+    pub fn in_trace(name: impl Into<String>, kind: SpanKind, trace_id: String) -> Self {
+        Self::new(name.into(), kind, trace_id, None)
+    }
+
+    fn new(name: String, kind: SpanKind, trace_id: String, parent_span_id: Option<String>) -> Self {
+        let context = SpanContext {
+            trace_id,
+            span_id: random_id(8),
+        };
+        STACK.with(|stack| stack.borrow_mut().push(context.clone()));
+        Span {
+            context,
+            parent_span_id,
+            name,
+            kind,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn context(&self) -> &SpanContext {
+        &self.context
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<Value>) {
+        self.attributes.push((key, value.into()));
+    }
+
+    pub fn set_error(&mut self, error: &dyn Display) {
+        self.error = Some(error.to_string());
+    }
+
+    /// Marks the span as failed if `result` is an error, and returns it.
+    pub fn record<T, E: Display>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if let Err(ref e) = result {
+            self.set_error(e);
+        }
+        result
+    }
+
+    /// Returns the span in the OTLP JSON encoding.
+    fn to_otlp(&self, end: SystemTime) -> Value {
+        let attributes = self
+            .attributes
+            .iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect::<Vec<_>>();
+        let status = match self.error {
+            Some(ref message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 0 }),
+        };
+        let mut span = json!({
+            "traceId": self.context.trace_id,
+            "spanId": self.context.span_id,
+            "name": self.name,
+            "kind": self.kind as u8,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(ref parent_span_id) = self.parent_span_id {
+            span["parentSpanId"] = json!(parent_span_id);
+        }
+        span
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(position) = stack.iter().rposition(|c| *c == self.context) {
+                stack.remove(position);
+            }
+        });
+
+        let exporter = EXPORTER.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(ref sender) = *exporter {
+            // Spans are dropped rather than slowing down requests when the
+            // queue is full
+            let _ = sender.try_send(self.to_otlp(SystemTime::now()));
+        }
+    }
+}
+
+struct Exporter {
+    url: String,
+    headers: HeaderMap,
+    service_name: String,
+}
+
+impl Exporter {
+    /// Sends the spans when a batch is full, or at least every
+    /// `EXPORT_INTERVAL`.
+    fn run(self, receiver: Receiver<Value>) {
+        let client = reqwest::Client::new();
+        let mut batch = Vec::new();
+        let mut deadline = Instant::now() + EXPORT_INTERVAL;
+        loop {
+            let now = Instant::now();
+            let timeout = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            match receiver.recv_timeout(timeout) {
+                Ok(span) => {
+                    batch.push(span);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            if !batch.is_empty() {
+                let spans = batch.split_off(0);
+                if let Err(e) = self.send(&client, spans) {
+                    logging::error("exporting spans failed", json!({ "error": e.to_string() }));
+                }
+            }
+            deadline = Instant::now() + EXPORT_INTERVAL;
+        }
+    }
+
+    fn send(&self, client: &reqwest::Client, spans: Vec<Value>) -> reqwest::Result<()> {
+        let body = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [attribute("service.name", &json!(self.service_name))],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "cargo_registry" },
+                    "spans": spans,
+                }],
+            }],
+        });
+        client
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .json(&body)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Returns an attribute in the OTLP JSON encoding, in which integers are
+/// strings.
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_f64() => json!({ "doubleValue": n }),
+        Value::Number(n) => json!({ "intValue": n.to_string() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let nanos =
+        u128::from(since_epoch.as_secs()) * 1_000_000_000 + u128::from(since_epoch.subsec_nanos());
+    nanos.to_string()
+}
+
+/// Whether `id` is a valid trace or span ID of `len` lowercase hex digits.
+/// IDs with only zeros are invalid.
+fn is_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && id.bytes().any(|b| b != b'0')
+}
+
+fn random_id(bytes: usize) -> String {
+    (0..bytes)
+        .map(|_| format!("{:02x}", rand::random::<u8>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_are_nested_per_thread() {
+        let parent = Span::start("parent", SpanKind::Server);
+        {
+            let child = Span::start("child", SpanKind::Internal);
+            assert_eq!(child.context.trace_id, parent.context.trace_id);
+            assert_eq!(child.parent_span_id.as_ref(), Some(&parent.context.span_id));
+            assert_eq!(current().as_ref(), Some(child.context()));
+        }
+        assert_eq!(current().as_ref(), Some(parent.context()));
+        drop(parent);
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn traceparent_headers_are_parsed() {
+        let context = SpanContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id, "00f067aa0ba902b7");
+
+        for header in &[
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert_eq!(SpanContext::from_traceparent(header), None, "{}", header);
+        }
+    }
+
+    #[test]
+    fn request_ids_are_used_as_trace_ids() {
+        assert_eq!(
+            trace_id_from_request_id("4BF92F35-77B3-4DA6-A3CE-929D0E0E4736").unwrap(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(trace_id_from_request_id("abcd"), None);
+    }
+
+    #[test]
+    fn spans_are_encoded_as_otlp_json() {
+        let mut span = Span::in_trace(
+            "GET /crates",
+            SpanKind::Server,
+            "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+        );
+        span.set_attribute("http.status_code", 500);
+        span.set_attribute("http.method", "GET");
+        let _ = span.record(Err::<(), _>("failed"));
+
+        let json = span.to_otlp(SystemTime::now());
+        assert_eq!(json["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(json["kind"], 2);
+        assert!(json.get("parentSpanId").is_none());
+        assert_eq!(
+            json["attributes"],
+            json!([
+                { "key": "http.status_code", "value": { "intValue": "500" } },
+                { "key": "http.method", "value": { "stringValue": "GET" } },
+            ])
+        );
+        assert_eq!(json["status"], json!({ "code": 2, "message": "failed" }));
+    }
+}
@@ -0,0 +1,64 @@
+#![cfg(test)]
+
+//! Records shared by the unit tests that need a database, e.g. of background
+//! jobs. Integration tests use the builders in `src/tests/builders.rs` instead.
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+
+use crate::models::{Crate, NewCrate, NewUser, NewVersion, User, Version};
+use crate::schema::emails;
+
+/// Creates the user `login`, without an email address.
+pub fn user(conn: &PgConnection) -> User {
+    NewUser::new(2, "login", None, None, None, "access_token")
+        .create_or_update(conn)
+        .unwrap()
+}
+
+/// Creates a user with a verified email address.
+pub fn user_with_email(conn: &PgConnection, gh_id: i32, login: &str, email: &str) -> User {
+    let user = NewUser::new(gh_id, login, Some(email), None, None, "access_token")
+        .create_or_update(conn)
+        .unwrap();
+    diesel::update(emails::table.filter(emails::user_id.eq(user.id)))
+        .set(emails::verified.eq(true))
+        .execute(conn)
+        .unwrap();
+    user
+}
+
+/// Publishes a version of the crate, without any dependencies.
+pub fn version(conn: &PgConnection, crate_id: i32, num: &str, user_id: i32) -> Version {
+    NewVersion::new(
+        crate_id,
+        &semver::Version::parse(num).unwrap(),
+        &HashMap::new(),
+        None,
+        None,
+        0,
+        user_id,
+        None,
+    )
+    .unwrap()
+    .save(conn, &[], "someone@example.com")
+    .unwrap()
+}
+
+/// Creates a crate owned by the user, with a single version.
+pub fn crate_and_version(
+    conn: &PgConnection,
+    name: &str,
+    num: &str,
+    user_id: i32,
+) -> (Crate, Version) {
+    let krate = NewCrate {
+        name,
+        ..Default::default()
+    }
+    .create_or_update(conn, user_id, None)
+    .unwrap();
+    let version = version(conn, krate.id, num, user_id);
+    (krate, version)
+}
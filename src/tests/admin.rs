@@ -0,0 +1,1108 @@
+use crate::{
+    builders::{CrateBuilder, DependencyBuilder, PublishBuilder, VersionBuilder},
+    util::{MockCookieUser, RequestHelper, Response},
+    OkBool, TestApp,
+};
+use cargo_registry::{
+    models::{Category, ClaimStatus, Keyword, ReservedNamePatternKind, Version},
+    schema::{
+        admin_actions, background_jobs, categories, crate_owners, crates, crates_categories,
+        crates_keywords, deleted_crates, download_anomalies, name_claims, publish_rate_overrides,
+        users, version_scan_findings, versions,
+    },
+    storage::LocalStorage,
+    uploaders::Uploader,
+    views::{
+        EncodableBackgroundJob, EncodableCategory, EncodableCrateReport, EncodableDeadLetter,
+        EncodableDownloadAnomaly, EncodableJobPolicy, EncodableKeyword, EncodableKeywordAlias,
+        EncodableNameClaim, EncodablePendingCrate, EncodableReservedNamePattern,
+        EncodableScanFinding,
+    },
+    TyposquatCheck,
+};
+use diesel::dsl::*;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tempdir::TempDir;
+
+#[derive(Deserialize)]
+struct FlaggedResponse {
+    findings: Vec<EncodableScanFinding>,
+    reports: Vec<EncodableCrateReport>,
+}
+
+impl MockCookieUser {
+    fn make_admin(&self, app: &TestApp) {
+        app.db(|conn| {
+            diesel::update(users::table.find(self.as_model().id))
+                .set(users::is_admin.eq(true))
+                .execute(conn)
+                .unwrap();
+        });
+    }
+
+    fn lock_crate(&self, krate_name: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/admin/crates/{}/lock", krate_name);
+        self.put(&url, &[])
+    }
+
+    fn report_crate(&self, krate_name: &str, category: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/report", krate_name);
+        let body = json!({ "category": category, "description": "not a real crate" });
+        self.post(&url, body.to_string().as_bytes())
+    }
+
+    fn ban_user(&self, login: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/admin/users/{}/ban", login);
+        self.put(&url, &[])
+    }
+}
+
+fn admin_actions(app: &TestApp) -> i64 {
+    app.db(|conn| admin_actions::table.count().get_result(conn).unwrap())
+}
+
+#[test]
+fn only_admins_can_moderate() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_not_admin", user.as_model().id).expect_build(conn);
+    });
+
+    let json = user.lock_crate("foo_not_admin").bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("must be an admin to use this endpoint"));
+    let json = user
+        .get::<FlaggedResponse>("/api/v1/admin/flagged")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("must be an admin to use this endpoint"));
+    assert_eq!(admin_actions(&app), 0);
+}
+
+#[test]
+fn locked_crates_cannot_be_published() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_locked", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    admin.lock_crate("foo_locked").good();
+
+    let crate_to_publish = PublishBuilder::new("foo_locked").version("1.1.0");
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0].detail.contains("has been locked"),
+        "{:?}",
+        json.errors
+    );
+    assert_eq!(admin_actions(&app), 1);
+}
+
+#[test]
+fn admins_can_override_the_max_upload_size() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_assets", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let files = [("foo_assets-1.1.0/big", &[b'a'; 2000] as &[_])];
+    let crate_to_publish = PublishBuilder::new("foo_assets")
+        .version("1.1.0")
+        .files(&files);
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("too large when decompressed"),
+        "{:?}",
+        json.errors
+    );
+
+    let url = "/api/v1/admin/crates/foo_assets/max_upload_size";
+    let body = json!({ "max_upload_size": 0 });
+    let json = admin
+        .put::<OkBool>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("at least 1 byte"));
+    let body = json!({ "max_upload_size": 2_000_000 });
+    admin.put::<OkBool>(url, body.to_string().as_bytes()).good();
+    let max_upload_size = || {
+        app.db(|conn| {
+            crates::table
+                .filter(crates::name.eq("foo_assets"))
+                .select(crates::max_upload_size)
+                .first::<Option<i32>>(conn)
+                .unwrap()
+        })
+    };
+    assert_eq!(max_upload_size(), Some(2_000_000));
+
+    admin.delete::<OkBool>(url).good();
+    assert_eq!(max_upload_size(), None);
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[test]
+fn delete_crate() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_spam", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    admin
+        .delete::<OkBool>("/api/v1/admin/crates/foo_spam")
+        .good();
+
+    anon.get::<()>("/api/v1/crates/foo_spam").assert_not_found();
+    let remaining = app.db(|conn| {
+        crates::table
+            .filter(crates::name.eq("foo_spam"))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 0);
+    assert_eq!(admin_actions(&app), 1);
+}
+
+#[test]
+fn names_of_deleted_crates_can_be_released() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_spam", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    admin
+        .delete::<OkBool>("/api/v1/admin/crates/foo_spam")
+        .good();
+    let json = user
+        .enqueue_publish(PublishBuilder::new("foo_spam"))
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .starts_with("a crate with this name was deleted recently"));
+
+    admin
+        .delete::<OkBool>("/api/v1/admin/deleted_names/Foo-Spam")
+        .good();
+    let available_at = app.db(|conn| {
+        deleted_crates::table
+            .filter(deleted_crates::name.eq("foo_spam"))
+            .select(deleted_crates::available_at)
+            .first::<chrono::NaiveDateTime>(conn)
+            .unwrap()
+    });
+    assert!(available_at <= chrono::Utc::now().naive_utc());
+
+    let json = admin
+        .delete::<OkBool>("/api/v1/admin/deleted_names/foo_spam")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "no crate with this name was deleted recently"
+    );
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[test]
+fn banned_users_are_logged_out() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    user.get::<()>("/api/v1/me").assert_status(200);
+    admin.ban_user("foo").good();
+    user.get::<()>("/api/v1/me").assert_forbidden();
+
+    admin.delete::<OkBool>("/api/v1/admin/users/foo/ban").good();
+    user.get::<()>("/api/v1/me").assert_status(200);
+    assert_eq!(admin_actions(&app), 2);
+
+    let json = admin.ban_user("admin").bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("admins cannot ban themselves"));
+}
+
+#[test]
+fn flagged_findings_can_be_reviewed() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    let finding_id = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_flagged", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+        let version_id = Version::belonging_to(&krate)
+            .select(versions::id)
+            .first::<i32>(conn)
+            .unwrap();
+        diesel::insert_into(version_scan_findings::table)
+            .values((
+                version_scan_findings::version_id.eq(version_id),
+                version_scan_findings::path.eq("src/lib.rs"),
+                version_scan_findings::kind.eq("secret"),
+                version_scan_findings::detail.eq("AWS access key id"),
+            ))
+            .returning(version_scan_findings::id)
+            .get_result::<i32>(conn)
+            .unwrap()
+    });
+
+    let flagged: FlaggedResponse = admin.get("/api/v1/admin/flagged").good();
+    assert_eq!(flagged.findings.len(), 1);
+    assert_eq!(flagged.findings[0].krate, "foo_flagged");
+    assert_eq!(flagged.findings[0].version, "1.0.0");
+    assert_eq!(flagged.findings[0].kind, "secret");
+
+    let url = format!("/api/v1/admin/findings/{}/review", finding_id);
+    admin.put::<OkBool>(&url, &[]).good();
+
+    let flagged: FlaggedResponse = admin.get("/api/v1/admin/flagged").good();
+    assert!(flagged.findings.is_empty());
+}
+
+#[test]
+fn download_anomalies_can_be_excluded_from_download_counts() {
+    #[derive(Deserialize)]
+    struct AnomaliesResponse {
+        download_anomalies: Vec<EncodableDownloadAnomaly>,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    let anomaly_id = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_inflated", user.as_model().id)
+            .downloads(10_000)
+            .expect_build(conn);
+        let date = chrono::NaiveDate::from_ymd(2019, 11, 1);
+        diesel::insert_into(download_anomalies::table)
+            .values((
+                download_anomalies::crate_id.eq(krate.id),
+                download_anomalies::start_date.eq(date),
+                download_anomalies::end_date.eq(date),
+                download_anomalies::downloads.eq(5000),
+                download_anomalies::expected_downloads.eq(200),
+            ))
+            .returning(download_anomalies::id)
+            .get_result::<i32>(conn)
+            .unwrap()
+    });
+
+    user.get::<AnomaliesResponse>("/api/v1/admin/download_anomalies")
+        .bad_with_status(200);
+    let json: AnomaliesResponse = admin.get("/api/v1/admin/download_anomalies").good();
+    assert_eq!(json.download_anomalies.len(), 1);
+    assert_eq!(json.download_anomalies[0].krate, "foo_inflated");
+    assert_eq!(json.download_anomalies[0].start_date, "2019-11-01");
+
+    let url = format!("/api/v1/admin/download_anomalies/{}/review", anomaly_id);
+    admin.put::<OkBool>(&url, br#"{"exclude": true}"#).good();
+
+    let downloads = |app: &TestApp| {
+        app.db(|conn| {
+            crates::table
+                .filter(crates::name.eq("foo_inflated"))
+                .select(crates::downloads)
+                .first::<i32>(conn)
+                .unwrap()
+        })
+    };
+    assert_eq!(downloads(&app), 5200);
+    let json: AnomaliesResponse = admin.get("/api/v1/admin/download_anomalies").good();
+    assert!(json.download_anomalies.is_empty());
+
+    // Counting the downloads again restores them
+    admin.put::<OkBool>(&url, br#"{"exclude": false}"#).good();
+    assert_eq!(downloads(&app), 10_000);
+}
+
+#[test]
+fn reported_crates_show_up_in_the_moderation_queue() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_reported", user.as_model().id).expect_build(conn);
+    });
+
+    admin.report_crate("foo_reported", "spam").good();
+    let json = user
+        .report_crate("foo_reported", "rude")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid json request"));
+
+    let flagged: FlaggedResponse = admin.get("/api/v1/admin/flagged").good();
+    assert_eq!(flagged.reports.len(), 1);
+    let report = &flagged.reports[0];
+    assert_eq!(report.krate, "foo_reported");
+    assert_eq!(report.reporter.as_ref().unwrap(), "admin");
+    assert_eq!(report.description, "not a real crate");
+
+    let url = format!("/api/v1/admin/reports/{}/resolve", report.id);
+    admin.put::<OkBool>(&url, &[]).good();
+    let flagged: FlaggedResponse = admin.get("/api/v1/admin/flagged").good();
+    assert!(flagged.reports.is_empty());
+}
+
+#[test]
+fn reports_are_rate_limited() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_report_limit", user.as_model().id).expect_build(conn);
+    });
+
+    for _ in 0..10 {
+        user.report_crate("foo_report_limit", "typosquat").good();
+    }
+    let json = user
+        .report_crate("foo_report_limit", "typosquat")
+        .bad_with_status(429);
+    assert!(json.errors[0]
+        .detail
+        .contains("You have reported too many crates"));
+}
+
+#[test]
+fn approved_names_can_be_published() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| config.typosquat_check = TyposquatCheck::Approval)
+        .with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("tokio", user.as_model().id)
+            .downloads(1000)
+            .expect_build(conn);
+    });
+
+    admin
+        .put::<OkBool>("/api/v1/admin/approved_names/Tokiio", &[])
+        .good();
+
+    let json = token.enqueue_publish(PublishBuilder::new("tokiio")).good();
+    assert!(json.warnings.other.is_empty());
+}
+
+#[derive(Deserialize)]
+struct ReservedNamesResponse {
+    names: Vec<String>,
+    patterns: Vec<EncodableReservedNamePattern>,
+}
+
+#[test]
+fn reserved_names_cannot_be_published() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_in_use", user.as_model().id).expect_build(conn);
+    });
+
+    admin
+        .put::<OkBool>("/api/v1/admin/reserved_names/foo_reserved", &[])
+        .good();
+    let json = admin
+        .put::<OkBool>("/api/v1/admin/reserved_names/foo-in-use", &[])
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("a crate with this name already exists"));
+
+    let json = user
+        .enqueue_publish(PublishBuilder::new("foo-reserved"))
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot upload a crate with a reserved name"));
+
+    let json: ReservedNamesResponse = admin.get("/api/v1/admin/reserved_names").good();
+    assert!(json.names.contains(&"foo_reserved".to_string()));
+
+    admin
+        .delete::<OkBool>("/api/v1/admin/reserved_names/foo-reserved")
+        .good();
+    let json: ReservedNamesResponse = admin.get("/api/v1/admin/reserved_names").good();
+    assert!(!json.names.contains(&"foo_reserved".to_string()));
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[derive(Deserialize)]
+struct PatternResponse {
+    pattern: EncodableReservedNamePattern,
+}
+
+#[test]
+fn reserved_name_patterns_block_new_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let body = json!({ "kind": "prefix", "pattern": "Rustc-" });
+    let json: PatternResponse = admin
+        .post(
+            "/api/v1/admin/reserved_name_patterns",
+            body.to_string().as_bytes(),
+        )
+        .good();
+    assert_eq!(json.pattern.kind, ReservedNamePatternKind::Prefix);
+    assert_eq!(json.pattern.pattern, "rustc_");
+    let pattern_id = json.pattern.id;
+
+    let body = json!({ "kind": "regex", "pattern": "^std_(" });
+    let json = admin
+        .post::<PatternResponse>(
+            "/api/v1/admin/reserved_name_patterns",
+            body.to_string().as_bytes(),
+        )
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid regular expression"));
+
+    let json = user
+        .enqueue_publish(PublishBuilder::new("rustc-ap-syntax"))
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot upload a crate with a reserved name"));
+
+    let url = format!("/api/v1/admin/reserved_name_patterns/{}", pattern_id);
+    admin.delete::<OkBool>(&url).good();
+    let json: ReservedNamesResponse = admin.get("/api/v1/admin/reserved_names").good();
+    assert!(json.patterns.is_empty());
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[derive(Deserialize)]
+struct ClaimResponse {
+    claim: EncodableNameClaim,
+}
+
+#[derive(Deserialize)]
+struct ClaimsResponse {
+    claims: Vec<EncodableNameClaim>,
+}
+
+impl MockCookieUser {
+    fn claim_crate(&self, krate_name: &str) -> Response<ClaimResponse> {
+        let url = format!("/api/v1/crates/{}/claims", krate_name);
+        let body = json!({ "reason": "no release in five years" });
+        self.post(&url, body.to_string().as_bytes())
+    }
+}
+
+fn active_owners(app: &TestApp, krate_name: &str) -> Vec<i32> {
+    app.db(|conn| {
+        crate_owners::table
+            .inner_join(crates::table)
+            .filter(crates::name.eq(krate_name))
+            .filter(crate_owners::deleted.eq(false))
+            .select(crate_owners::owner_id)
+            .load(conn)
+            .unwrap()
+    })
+}
+
+#[test]
+fn released_claims_transfer_the_crate() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let claimant = app.db_new_user("claimant");
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_claimed", owner.as_model().id).expect_build(conn);
+    });
+
+    let json = owner.claim_crate("foo_claimed").bad_with_status(200);
+    assert!(json.errors[0].detail.contains("you already own this crate"));
+
+    let claim = claimant.claim_crate("foo_claimed").good().claim;
+    assert_eq!(claim.status, ClaimStatus::Pending);
+    let json = claimant.claim_crate("foo_claimed").bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("you have already claimed this crate"));
+
+    let approve_url = format!("/api/v1/admin/claims/{}/approve", claim.id);
+    let json = admin.put::<OkBool>(&approve_url, &[]).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("days to respond"));
+
+    let json: ClaimsResponse = owner.get("/api/v1/crates/foo_claimed/claims").good();
+    assert_eq!(json.claims.len(), 1);
+    let url = format!("/api/v1/crates/foo_claimed/claims/{}", claim.id);
+    let body = json!({ "release": true, "response": "I don't need it anymore" });
+    owner
+        .put::<OkBool>(&url, body.to_string().as_bytes())
+        .good();
+
+    admin.put::<OkBool>(&approve_url, &[]).good();
+    assert_eq!(
+        active_owners(&app, "foo_claimed"),
+        vec![claimant.as_model().id]
+    );
+    let json: ClaimsResponse = admin.get("/api/v1/admin/claims").good();
+    assert!(json.claims.is_empty());
+    assert_eq!(admin_actions(&app), 1);
+}
+
+#[test]
+fn contested_claims_can_be_approved_after_the_holding_period() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let claimant = app.db_new_user("claimant");
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_contested", owner.as_model().id).expect_build(conn);
+    });
+
+    let claim = claimant.claim_crate("foo_contested").good().claim;
+    let url = format!("/api/v1/crates/foo_contested/claims/{}", claim.id);
+    let body = json!({ "release": false, "response": "still maintained" });
+    owner
+        .put::<OkBool>(&url, body.to_string().as_bytes())
+        .good();
+
+    let json: ClaimsResponse = admin.get("/api/v1/admin/claims").good();
+    assert_eq!(json.claims.len(), 1);
+    assert_eq!(json.claims[0].status, ClaimStatus::Contested);
+    assert_eq!(
+        json.claims[0].response.as_ref().unwrap(),
+        "still maintained"
+    );
+
+    app.db(|conn| {
+        diesel::update(name_claims::table)
+            .set(name_claims::created_at.eq(now - 30.days()))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = format!("/api/v1/admin/claims/{}/approve", claim.id);
+    admin.put::<OkBool>(&url, &[]).good();
+    assert_eq!(
+        active_owners(&app, "foo_contested"),
+        vec![claimant.as_model().id]
+    );
+}
+
+#[test]
+fn rejected_claims_keep_the_owners() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let claimant = app.db_new_user("claimant");
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    app.db(|conn| {
+        CrateBuilder::new("foo_rejected", owner.as_model().id).expect_build(conn);
+    });
+
+    let claim = claimant.claim_crate("foo_rejected").good().claim;
+    let url = format!("/api/v1/admin/claims/{}/reject", claim.id);
+    admin.put::<OkBool>(&url, &[]).good();
+    let json = admin.put::<OkBool>(&url, &[]).bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("this claim has already been decided on"));
+    assert_eq!(
+        active_owners(&app, "foo_rejected"),
+        vec![owner.as_model().id]
+    );
+}
+
+#[derive(Deserialize)]
+struct PendingCratesResponse {
+    crates: Vec<EncodablePendingCrate>,
+}
+
+#[test]
+fn new_crates_are_hidden_until_approved() {
+    let storage = TempDir::new("pending-crates").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, anon, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| {
+            config.uploader = Uploader::new(LocalStorage::new(root), None);
+            config.new_crate_approval = true;
+        })
+        .with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let json = user
+        .enqueue_publish(PublishBuilder::new("foo_pending"))
+        .good();
+    assert!(json.warnings.other[0].starts_with("new crates have to be approved"));
+    app.run_pending_background_jobs();
+
+    anon.get::<()>("/api/v1/crates/foo_pending")
+        .assert_not_found();
+    anon.get::<()>("/api/v1/crates/foo_pending/1.0.0/download")
+        .assert_not_found();
+    assert_eq!(anon.search("q=foo_pending").meta.total, 0);
+    user.get::<()>("/api/v1/crates/foo_pending")
+        .assert_status(200);
+
+    let crate_to_publish = PublishBuilder::new("foo_pending").version("1.1.0");
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("is waiting for the approval"));
+
+    let json: PendingCratesResponse = admin.get("/api/v1/admin/pending_crates").good();
+    assert_eq!(json.crates.len(), 1);
+    assert_eq!(json.crates[0].name, "foo_pending");
+    assert_eq!(json.crates[0].version, "1.0.0");
+    assert_eq!(json.crates[0].publisher.as_ref().unwrap(), "foo");
+
+    admin
+        .put::<OkBool>("/api/v1/admin/pending_crates/foo_pending/approve", &[])
+        .good();
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("fo/o_/foo_pending");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].vers, "1.0.0");
+    assert_eq!(anon.search("q=foo_pending").meta.total, 1);
+    let json: PendingCratesResponse = admin.get("/api/v1/admin/pending_crates").good();
+    assert!(json.crates.is_empty());
+    admin
+        .put::<OkBool>("/api/v1/admin/pending_crates/foo_pending/approve", &[])
+        .bad_with_status(200);
+    assert_eq!(admin_actions(&app), 1);
+}
+
+#[test]
+fn rejected_crates_are_deleted() {
+    let storage = TempDir::new("pending-crates").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| {
+            config.uploader = Uploader::new(LocalStorage::new(root), None);
+            config.new_crate_approval = true;
+        })
+        .with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    user.enqueue_publish(PublishBuilder::new("foo_unwanted"))
+        .good();
+    admin
+        .put::<OkBool>("/api/v1/admin/pending_crates/foo_unwanted/reject", &[])
+        .good();
+
+    let remaining: i64 = app.db(|conn| {
+        crates::table
+            .filter(crates::name.eq("foo_unwanted"))
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 0);
+    // Unlike deleted crates, the name can be used again right away
+    user.enqueue_publish(PublishBuilder::new("foo_unwanted"))
+        .good();
+    assert_eq!(admin_actions(&app), 1);
+}
+
+#[test]
+fn renamed_crates_keep_resolving() {
+    let storage = TempDir::new("renamed-crates").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, anon, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| {
+            config.uploader = Uploader::new(LocalStorage::new(root), None);
+        })
+        .with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    user.enqueue_publish(PublishBuilder::new("foo_old")).good();
+    app.run_pending_background_jobs();
+
+    let body = json!({ "name": "foo_new" }).to_string();
+    user.put::<()>("/api/v1/admin/crates/foo_old/rename", body.as_bytes())
+        .bad_with_status(200);
+    admin
+        .put::<OkBool>("/api/v1/admin/crates/foo_old/rename", body.as_bytes())
+        .good();
+    assert_eq!(admin_actions(&app), 1);
+
+    anon.get::<()>("/api/v1/crates/foo_old")
+        .assert_status(301)
+        .assert_redirect_ends_with("/api/v1/crates/foo_new");
+    anon.get::<()>("/api/v1/crates/foo_old/1.0.0/download")
+        .assert_status(301)
+        .assert_redirect_ends_with("/api/v1/crates/foo_new/1.0.0/download");
+    // The crate file stays where it was uploaded
+    anon.get::<()>("/api/v1/crates/foo_new/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_old/foo_old-1.0.0.crate");
+
+    let json = user
+        .enqueue_publish(PublishBuilder::new("foo_old").version("1.1.0"))
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("was renamed to `foo_new`"));
+
+    // Crates depending on the old name can still be published, and the
+    // versions published before the rename stay in the index under it
+    let dependency = DependencyBuilder::new("foo_old");
+    user.enqueue_publish(PublishBuilder::new("bar_dependent").dependency(dependency))
+        .good();
+    user.enqueue_publish(PublishBuilder::new("foo_new").version("1.1.0"))
+        .good();
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("fo/o_/foo_old");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].vers, "1.0.0");
+    let crates = app.crates_from_index_head("fo/o_/foo_new");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].vers, "1.1.0");
+    let crates = app.crates_from_index_head("ba/r_/bar_dependent");
+    assert_eq!(crates[0].deps[0].name, "foo_old");
+}
+
+#[test]
+fn rate_limit_overrides_raise_the_publish_burst() {
+    let (app, _, user, token) = TestApp::full()
+        .with_publish_rate_limit(Duration::from_secs(60), 1)
+        .with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let url = format!("/api/v1/admin/rate_limits/{}", user.as_model().gh_login);
+    let body = json!({ "burst": 2, "days": 1 });
+    admin
+        .put::<OkBool>(&url, body.to_string().as_bytes())
+        .good();
+
+    token
+        .enqueue_publish(PublishBuilder::new("rate_override1"))
+        .good();
+    token
+        .enqueue_publish(PublishBuilder::new("rate_override2"))
+        .good();
+    token
+        .enqueue_publish(PublishBuilder::new("rate_override3"))
+        .assert_status(429);
+
+    admin.delete::<OkBool>(&url).good();
+    let overrides = app.db(|conn| {
+        publish_rate_overrides::table
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(overrides, 0);
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[derive(Deserialize)]
+struct CategoryResponse {
+    category: EncodableCategory,
+}
+
+#[test]
+fn synced_categories_cannot_be_managed() {
+    let (app, _) = TestApp::init().empty();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let body = json!({ "slug": "parsing", "name": "Parsing" });
+    let json = admin
+        .post::<CategoryResponse>("/api/v1/admin/categories", body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("REGISTRY_CUSTOM_CATEGORIES"));
+    assert_eq!(admin_actions(&app), 0);
+}
+
+#[test]
+fn categories_can_be_created_renamed_and_merged() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.branding.custom_categories = true)
+        .with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let body = json!({ "slug": "parsing", "name": "Parsing" });
+    admin
+        .post::<CategoryResponse>("/api/v1/admin/categories", body.to_string().as_bytes())
+        .good();
+    let body = json!({ "slug": "parsers", "name": "Parsers", "parent": "parsing" });
+    let json = admin
+        .post::<CategoryResponse>("/api/v1/admin/categories", body.to_string().as_bytes())
+        .good();
+    assert_eq!(json.category.slug, "parsing::parsers");
+    assert_eq!(json.category.category, "Parsers");
+
+    let json = admin
+        .post::<CategoryResponse>("/api/v1/admin/categories", body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("already exists"));
+
+    let krate = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_parser", user.as_model().id).expect_build(conn);
+        Category::update_crate(conn, &krate, &["parsing::parsers"]).unwrap();
+        krate
+    });
+
+    // Renaming a category renames its subcategories
+    let body = json!({ "slug": "parse", "name": "Parse" });
+    let json = admin
+        .put::<CategoryResponse>(
+            "/api/v1/admin/categories/parsing",
+            body.to_string().as_bytes(),
+        )
+        .good();
+    assert_eq!(json.category.slug, "parse");
+    let json: CategoryResponse = anon.get("/api/v1/categories/parsing::parsers").good();
+    assert_eq!(json.category.slug, "parse::parsers");
+    assert_eq!(json.category.crates_cnt, 1);
+
+    // Crates can still be published with the old slugs
+    let invalid = app.db(|conn| Category::update_crate(conn, &krate, &["parsing"]).unwrap());
+    assert!(invalid.is_empty());
+    app.db(|conn| {
+        Category::update_crate(conn, &krate, &["parse::parsers"]).unwrap();
+    });
+
+    let body = json!({ "into": "parse::parsers" });
+    let json = admin
+        .put::<OkBool>(
+            "/api/v1/admin/categories/parse/merge",
+            body.to_string().as_bytes(),
+        )
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("subcategories"));
+
+    let body = json!({ "into": "parse" });
+    admin
+        .put::<OkBool>(
+            "/api/v1/admin/categories/parse::parsers/merge",
+            body.to_string().as_bytes(),
+        )
+        .good();
+    let json: CategoryResponse = anon.get("/api/v1/categories/parsing::parsers").good();
+    assert_eq!(json.category.slug, "parse");
+    assert_eq!(json.category.crates_cnt, 1);
+
+    let (slugs, crate_categories) = app.db(|conn| {
+        let slugs = categories::table
+            .select(categories::slug)
+            .load::<String>(conn)
+            .unwrap();
+        let crate_categories = crates_categories::table
+            .filter(crates_categories::crate_id.eq(krate.id))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap();
+        (slugs, crate_categories)
+    });
+    assert_eq!(slugs, ["parse"]);
+    assert_eq!(crate_categories, 1);
+    assert_eq!(admin_actions(&app), 4);
+}
+
+#[derive(Deserialize)]
+struct KeywordResponse {
+    keyword: EncodableKeyword,
+}
+
+#[derive(Deserialize)]
+struct KeywordAliasesResponse {
+    aliases: Vec<EncodableKeywordAlias>,
+}
+
+#[test]
+fn keyword_aliases_merge_keywords() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo_async_await", user.as_model().id)
+            .keyword("async-await")
+            .expect_build(conn);
+        CrateBuilder::new("foo_async", user.as_model().id)
+            .keyword("async")
+            .expect_build(conn)
+    });
+
+    let url = "/api/v1/admin/keyword_aliases/Async-Await";
+    let body = json!({ "keyword": "async" });
+    admin.put::<OkBool>(url, body.to_string().as_bytes()).good();
+    let body = json!({ "keyword": "async-await" });
+    let json = admin
+        .put::<OkBool>(
+            "/api/v1/admin/keyword_aliases/futures",
+            body.to_string().as_bytes(),
+        )
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("is an alias itself"));
+
+    let json: KeywordAliasesResponse = admin.get("/api/v1/admin/keyword_aliases").good();
+    assert_eq!(json.aliases.len(), 1);
+    assert_eq!(json.aliases[0].alias, "async-await");
+    assert_eq!(json.aliases[0].keyword, "async");
+
+    // Existing crates are moved to the keyword
+    let json: KeywordResponse = anon.get("/api/v1/keywords/async-await").good();
+    assert_eq!(json.keyword.keyword, "async");
+    assert_eq!(json.keyword.crates_cnt, 2);
+    assert_eq!(anon.search("keyword=async-await").crates.len(), 2);
+
+    // and new ones get the keyword instead of the alias
+    let keywords = app.db(|conn| {
+        Keyword::update_crate(conn, &krate, &["async-await", "async"]).unwrap();
+        crates_keywords::table
+            .filter(crates_keywords::crate_id.eq(krate.id))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(keywords, 1);
+
+    admin.delete::<OkBool>(url).good();
+    admin.delete::<OkBool>(url).bad_with_status(200);
+    assert_eq!(admin_actions(&app), 2);
+}
+
+#[derive(Deserialize)]
+struct JobsResponse {
+    queued: HashMap<String, i64>,
+    failed: Vec<EncodableBackgroundJob>,
+    dead_letters: Vec<EncodableDeadLetter>,
+    policies: Vec<EncodableJobPolicy>,
+}
+
+fn enqueue_job(app: &TestApp, job_type: &str) -> i64 {
+    app.db(|conn| {
+        diesel::insert_into(background_jobs::table)
+            .values((
+                background_jobs::job_type.eq(job_type),
+                background_jobs::data.eq(json!({})),
+            ))
+            .returning(background_jobs::id)
+            .get_result(conn)
+            .unwrap()
+    })
+}
+
+/// Records a failed attempt the way swirl does.
+fn fail_job(app: &TestApp, id: i64, times: usize) {
+    app.db(|conn| {
+        for _ in 0..times {
+            diesel::update(background_jobs::table.find(id))
+                .set((
+                    background_jobs::retries.eq(background_jobs::retries + 1),
+                    background_jobs::last_retry.eq(now),
+                ))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+}
+
+#[test]
+fn failed_jobs_are_retried_by_their_policy() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin(&app);
+    user.get::<JobsResponse>("/api/v1/admin/jobs")
+        .bad_with_status(200);
+
+    // swirl runs the job with the lowest id first
+    let digest = enqueue_job(&app, "send_update_digests");
+    let index = enqueue_job(&app, "add_crate");
+    assert!(index < digest);
+
+    fail_job(&app, digest, 1);
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    assert_eq!(json.queued["send_update_digests"], 1);
+    assert_eq!(json.queued["add_crate"], 1);
+    assert!(json.dead_letters.is_empty());
+    let policy = json
+        .policies
+        .iter()
+        .find(|policy| policy.job_type == "send_update_digests")
+        .unwrap();
+    assert_eq!(policy.max_attempts, Some(5));
+    assert_eq!(json.failed.len(), 1);
+    let job = &json.failed[0];
+    assert_eq!(job.id, digest);
+    assert_eq!(job.retries, 1);
+    assert_eq!(
+        job.next_attempt_at - job.created_at,
+        chrono::Duration::seconds(i64::from(policy.backoff_seconds))
+    );
+
+    let url = format!("/api/v1/admin/jobs/{}/retry", digest);
+    admin.put::<OkBool>(&url, &[]).good();
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    assert!(json.failed[0].next_attempt_at < json.failed[0].created_at);
+    let url = format!("/api/v1/admin/jobs/{}/retry", index);
+    admin.put::<OkBool>(&url, &[]).bad_with_status(200);
+
+    // After the last attempt the job is moved to the dead letters
+    fail_job(&app, digest, 4);
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    assert!(json.failed.is_empty());
+    assert_eq!(json.queued.get("send_update_digests"), None);
+    assert_eq!(json.dead_letters.len(), 1);
+    assert_eq!(json.dead_letters[0].job_id, digest);
+    assert_eq!(json.dead_letters[0].retries, 5);
+
+    let url = format!(
+        "/api/v1/admin/jobs/dead_letters/{}/retry",
+        json.dead_letters[0].id
+    );
+    admin.put::<OkBool>(&url, &[]).good();
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    assert_eq!(json.queued["send_update_digests"], 1);
+    assert!(json.dead_letters.is_empty());
+
+    let digest = app.db(|conn| {
+        background_jobs::table
+            .filter(background_jobs::job_type.eq("send_update_digests"))
+            .select(background_jobs::id)
+            .first(conn)
+            .unwrap()
+    });
+    fail_job(&app, digest, 5);
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    let url = format!(
+        "/api/v1/admin/jobs/dead_letters/{}",
+        json.dead_letters[0].id
+    );
+    admin.delete::<OkBool>(&url).good();
+    admin.delete::<OkBool>(&url).bad_with_status(200);
+    let json: JobsResponse = admin.get("/api/v1/admin/jobs").good();
+    assert!(json.dead_letters.is_empty());
+    assert_eq!(admin_actions(&app), 3);
+}
@@ -19,10 +19,11 @@ use crate::util::{Bad, RequestHelper, TestApp};
 use cargo_registry::{
     models::{Crate, CrateOwner, Dependency, NewCategory, NewTeam, NewUser, Team, User, Version},
     schema::crate_owners,
+    storage::S3Storage,
     util::CargoResult,
     views::{
-        EncodableCategory, EncodableCategoryWithSubcategories, EncodableCrate, EncodableKeyword,
-        EncodableOwner, EncodableVersion, GoodCrate,
+        EncodableCategory, EncodableCategoryWithSubcategories, EncodableCrate,
+        EncodableCrateFunding, EncodableKeyword, EncodableOwner, EncodableVersion, GoodCrate,
     },
     App, Config, Env, Replica, Uploader,
 };
@@ -32,6 +33,7 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use conduit_test::MockRequest;
@@ -47,14 +49,18 @@ macro_rules! t {
     };
 }
 
+mod admin;
 mod badge;
 mod builders;
 mod categories;
 mod category;
+mod crate_list;
 mod dump_db;
 mod git;
+mod graphql;
 mod keyword;
 mod krate;
+mod namespace;
 mod owners;
 mod read_only_mode;
 mod record;
@@ -64,7 +70,9 @@ mod team;
 mod token;
 mod user;
 mod util;
+mod v2;
 mod version;
+mod visibility;
 
 #[derive(Deserialize)]
 pub struct CrateList {
@@ -83,6 +91,7 @@ pub struct CrateResponse {
     krate: EncodableCrate,
     versions: Vec<EncodableVersion>,
     keywords: Vec<EncodableKeyword>,
+    funding: Vec<EncodableCrateFunding>,
 }
 #[derive(Deserialize)]
 pub struct VersionResponse {
@@ -119,26 +128,34 @@ fn app() -> (Arc<App>, conduit_middleware::MiddlewareBuilder) {
 }
 
 fn simple_config() -> Config {
-    let uploader = Uploader::S3 {
-        bucket: s3::Bucket::new(
-            String::from("alexcrichton-test"),
-            None,
-            dotenv::var("S3_ACCESS_KEY").unwrap_or_default(),
-            dotenv::var("S3_SECRET_KEY").unwrap_or_default(),
-            // When testing we route all API traffic over HTTP so we can
-            // sniff/record it, but everywhere else we use https
-            "http",
-        ),
-        cdn: None,
-    };
+    let bucket = s3::Bucket::new(
+        String::from("alexcrichton-test"),
+        None,
+        dotenv::var("S3_ACCESS_KEY").unwrap_or_default(),
+        dotenv::var("S3_SECRET_KEY").unwrap_or_default(),
+        // When testing we route all API traffic over HTTP so we can
+        // sniff/record it, but everywhere else we use https
+        "http",
+    );
+    let uploader = Uploader::new(S3Storage::new(bucket), None);
 
     Config {
         uploader,
+        download_signing: None,
+        signed_download_ttl: Duration::from_secs(5 * 60),
+        deleted_name_cooldown: Duration::from_secs(24 * 60 * 60),
+        owner_removal_delay: Duration::from_secs(72 * 60 * 60),
+        // Recorded GitHub responses expect every membership to be checked
+        team_membership_ttl: Duration::from_secs(0),
         session_key: "test this has to be over 32 bytes long".to_string(),
         git_repo_checkout: git::checkout(),
         gh_client_id: dotenv::var("GH_CLIENT_ID").unwrap_or_default(),
         gh_client_secret: dotenv::var("GH_CLIENT_SECRET").unwrap_or_default(),
+        gitlab: None,
+        bitbucket: None,
         db_url: env("TEST_DATABASE_URL"),
+        replica_db_url: None,
+        db_request_budget: None,
         env: Env::Test,
         max_upload_size: 3000,
         max_unpack_size: 2000,
@@ -146,8 +163,22 @@ fn simple_config() -> Config {
         // When testing we route all API traffic over HTTP so we can
         // sniff/record it, but everywhere else we use https
         api_protocol: String::from("http"),
-        publish_rate_limit: Default::default(),
+        rate_limiter: Default::default(),
         blocked_traffic: Default::default(),
+        branding: Default::default(),
+        search_experiment: Default::default(),
+        dependency_validation: Default::default(),
+        tarball_scan: Default::default(),
+        typosquat_check: Default::default(),
+        response_cache: Default::default(),
+        metrics_authorization_token: Some(String::from("metrics token")),
+        cors: Default::default(),
+        download_counting: Default::default(),
+        index_fallback_url: None,
+        index_repository: Some(git::bare()),
+        new_crate_approval: false,
+        crate_namespaces: false,
+        trusted_proxies: vec![String::from("127.0.0.1")],
     }
 }
 
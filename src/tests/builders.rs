@@ -6,7 +6,10 @@ use cargo_registry::{
     util::CargoResult,
     views::krate_publish as u,
 };
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+};
 
 use diesel::prelude::*;
 use flate2::{write::GzEncoder, Compression};
@@ -20,6 +23,7 @@ pub struct VersionBuilder<'a> {
     dependencies: Vec<(i32, Option<&'static str>)>,
     yanked: bool,
     size: i32,
+    rust_version: Option<&'a str>,
 }
 
 impl<'a> VersionBuilder<'a> {
@@ -41,6 +45,7 @@ impl<'a> VersionBuilder<'a> {
             dependencies: Vec::new(),
             yanked: false,
             size: 0,
+            rust_version: None,
         }
     }
 
@@ -50,6 +55,13 @@ impl<'a> VersionBuilder<'a> {
         self
     }
 
+    /// Adds a feature to this version.
+    pub fn feature(mut self, name: &str, enables: &[&str]) -> Self {
+        let enables = enables.iter().map(|s| s.to_string()).collect();
+        self.features.insert(name.to_string(), enables);
+        self
+    }
+
     /// Adds a dependency to this version.
     pub fn dependency(mut self, dependency: &Crate, target: Option<&'static str>) -> Self {
         self.dependencies.push((dependency.id, target));
@@ -67,6 +79,12 @@ impl<'a> VersionBuilder<'a> {
         self
     }
 
+    /// Sets the version's minimum supported Rust version.
+    pub fn rust_version(mut self, rust_version: &'a str) -> Self {
+        self.rust_version = Some(rust_version);
+        self
+    }
+
     fn build(
         self,
         crate_id: i32,
@@ -88,6 +106,7 @@ impl<'a> VersionBuilder<'a> {
             self.license_file,
             self.size,
             published_by,
+            self.rust_version.map(String::from),
         )?
         .save(connection, &[], "someone@example.com")?;
 
@@ -96,6 +115,7 @@ impl<'a> VersionBuilder<'a> {
                 .set(versions::yanked.eq(true))
                 .get_result(connection)?;
         }
+        Crate::update_default_versions(connection, crate_id)?;
 
         let new_deps = self
             .dependencies
@@ -196,6 +216,12 @@ impl<'a> CrateBuilder<'a> {
         self
     }
 
+    /// Sets the crate's `repository` URL.
+    pub fn repository(mut self, repository: &'a str) -> Self {
+        self.krate.repository = Some(repository);
+        self
+    }
+
     /// Sets the crate's `max_upload_size` override value.
     pub fn max_upload_size(mut self, max_upload_size: i32) -> Self {
         self.krate.max_upload_size = Some(max_upload_size);
@@ -256,6 +282,7 @@ impl<'a> CrateBuilder<'a> {
                 .build(krate.id, self.owner_id, connection)?
                 .id;
         }
+        krate = Crate::update_default_versions(connection, krate.id)?;
 
         if let Some(downloads) = self.recent_downloads {
             insert_into(version_downloads::table)
@@ -318,9 +345,12 @@ pub struct PublishBuilder {
     keywords: Vec<String>,
     categories: Vec<String>,
     badges: HashMap<String, HashMap<String, String>>,
+    funding: Option<BTreeMap<String, String>>,
     license: Option<String>,
     license_file: Option<String>,
     authors: Vec<String>,
+    rust_version: Option<String>,
+    features: HashMap<u::EncodableFeatureName, Vec<u::EncodableFeature>>,
 }
 
 impl PublishBuilder {
@@ -338,9 +368,12 @@ impl PublishBuilder {
             keywords: vec![],
             categories: vec![],
             badges: HashMap::new(),
+            funding: None,
             license: Some("MIT".to_string()),
             license_file: None,
             authors: vec!["foo".to_string()],
+            rust_version: None,
+            features: HashMap::new(),
         }
     }
 
@@ -440,6 +473,21 @@ impl PublishBuilder {
         self
     }
 
+    /// Set the funding platforms and URLs of this crate.
+    pub fn funding(mut self, funding: &[(&str, &str)]) -> Self {
+        let funding = funding
+            .iter()
+            .map(|&(platform, url)| (platform.into(), url.into()));
+        self.funding = Some(funding.collect());
+        self
+    }
+
+    /// Set the license of this crate
+    pub fn license(mut self, license: &str) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
     /// Remove the license from this crate. Publish will fail unless license or license file is set.
     pub fn unset_license(mut self) -> Self {
         self.license = None;
@@ -458,6 +506,24 @@ impl PublishBuilder {
         self
     }
 
+    /// Add a feature to this crate, enabling the given features and dependencies
+    pub fn feature(mut self, name: &str, enables: &[&str]) -> Self {
+        self.features.insert(
+            u::EncodableFeatureName(name.into()),
+            enables
+                .iter()
+                .map(|feature| u::EncodableFeature(feature.to_string()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Set the minimum supported Rust version of this crate
+    pub fn rust_version(mut self, rust_version: &str) -> Self {
+        self.rust_version = Some(rust_version.into());
+        self
+    }
+
     /// Remove the authors from this crate. Publish will fail unless authors are reset.
     pub fn unset_authors(mut self) -> Self {
         self.authors = vec![];
@@ -469,7 +535,7 @@ impl PublishBuilder {
         let new_crate = u::EncodableCrateUpload {
             name: u::EncodableCrateName(self.krate_name.clone()),
             vers: u::EncodableCrateVersion(self.version),
-            features: HashMap::new(),
+            features: self.features,
             deps: self.deps,
             authors: self.authors,
             description: self.desc,
@@ -490,7 +556,9 @@ impl PublishBuilder {
             license_file: self.license_file,
             repository: None,
             badges: Some(self.badges),
+            funding: self.funding,
             links: None,
+            rust_version: self.rust_version,
         };
 
         let json = serde_json::to_string(&new_crate).unwrap();
@@ -1,7 +1,14 @@
 use crate::{
     builders::CrateBuilder, new_category, util::MockAnonymousUser, RequestHelper, TestApp,
 };
-use cargo_registry::{models::Category, views::EncodableCategoryWithSubcategories};
+use cargo_registry::{
+    models::Category,
+    schema::crate_download_rollups,
+    views::{EncodableCategory, EncodableCategoryWithSubcategories, EncodableTopCrates},
+};
+
+use chrono::{Datelike, Duration, Utc};
+use diesel::{insert_into, prelude::*};
 
 #[derive(Deserialize)]
 struct CategoryWithSubcategories {
@@ -34,6 +41,70 @@ fn index() {
     assert_eq!(json.categories[0].category, "foo");
 }
 
+#[derive(Deserialize)]
+struct CategorySuggestions {
+    categories: Vec<EncodableCategory>,
+}
+
+#[test]
+fn suggest() {
+    let url = "/api/v1/categories/suggest";
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        for &(name, slug) in &[
+            ("Web programming", "web-programming"),
+            (
+                "Web programming::HTTP client",
+                "web-programming::http-client",
+            ),
+            ("Network programming", "network-programming"),
+            ("Parsing", "parsing"),
+        ] {
+            t!(new_category(name, slug, "Some crates").create_or_update(conn));
+        }
+        CrateBuilder::new("foo_suggest", user.id)
+            .category("network-programming")
+            .expect_build(conn);
+    });
+
+    let suggestions = |query: &str| {
+        let json: CategorySuggestions = anon.get_with_query(url, query).good();
+        json.categories
+            .into_iter()
+            .map(|category| (category.category, category.crates_cnt))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        suggestions("q=PROG"),
+        vec![
+            ("Network programming".into(), 1),
+            ("Web programming".into(), 0),
+            ("Web programming::HTTP client".into(), 0),
+        ]
+    );
+    assert_eq!(
+        suggestions("q=http"),
+        vec![("Web programming::HTTP client".into(), 0)]
+    );
+    // Categories starting with the query come first
+    assert_eq!(
+        suggestions("q=p"),
+        vec![
+            ("Parsing".into(), 0),
+            ("Network programming".into(), 1),
+            ("Web programming".into(), 0),
+            ("Web programming::HTTP client".into(), 0),
+        ]
+    );
+    assert_eq!(suggestions("q=web&limit=1").len(), 1);
+    // Misspelled categories are matched by their trigram similarity
+    assert_eq!(suggestions("q=parsnig"), vec![("Parsing".into(), 0)]);
+    assert!(suggestions("q=").is_empty());
+}
+
 #[test]
 fn show() {
     let (app, anon) = TestApp::init().empty();
@@ -56,6 +127,66 @@ fn show() {
     assert_eq!(json.category.subcategories[0].category, "Baz");
 }
 
+#[test]
+fn top() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let url = "/api/v1/categories/foo-bar/top";
+
+    anon.get::<()>(url).assert_not_found();
+
+    app.db(|conn| {
+        t!(new_category("Foo Bar", "foo-bar", "Foo Bar crates").create_or_update(conn));
+        t!(new_category("Foo Bar::Baz", "foo-bar::baz", "Baz crates").create_or_update(conn));
+        t!(new_category("Foo Barn", "foo-barn", "Foo Barn crates").create_or_update(conn));
+
+        let krate1 = CrateBuilder::new("top_cat_1", user.id).expect_build(conn);
+        let krate2 = CrateBuilder::new("top_cat_2", user.id).expect_build(conn);
+        let other = CrateBuilder::new("other_cat", user.id).expect_build(conn);
+        t!(Category::update_crate(conn, &krate1, &["foo-bar"]));
+        // Crates in both a category and its subcategory are only counted once
+        t!(Category::update_crate(
+            conn,
+            &krate2,
+            &["foo-bar", "foo-bar::baz"]
+        ));
+        t!(Category::update_crate(conn, &other, &["foo-barn"]));
+
+        let today = Utc::now().naive_utc().date();
+        let this_week = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let rollup = |crate_id: i32, downloads: i64| {
+            (
+                crate_download_rollups::crate_id.eq(crate_id),
+                crate_download_rollups::period.eq("week"),
+                crate_download_rollups::period_start.eq(this_week),
+                crate_download_rollups::downloads.eq(downloads),
+            )
+        };
+        insert_into(crate_download_rollups::table)
+            .values(&vec![
+                rollup(krate1.id, 5),
+                rollup(krate2.id, 50),
+                rollup(other.id, 500),
+            ])
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: EncodableTopCrates = anon.get(url).good();
+    let most_downloaded = json
+        .most_downloaded
+        .iter()
+        .map(|top| (top.krate.name.as_str(), top.downloads))
+        .collect::<Vec<_>>();
+    assert_eq!(most_downloaded, [("top_cat_2", 50), ("top_cat_1", 5)]);
+    assert_eq!(json.recently_updated.len(), 2);
+
+    // Subcategories only include their own crates
+    let json: EncodableTopCrates = anon.get("/api/v1/categories/foo-bar::baz/top").good();
+    assert_eq!(json.most_downloaded.len(), 1);
+    assert_eq!(json.most_downloaded[0].krate.name, "top_cat_2");
+}
+
 #[test]
 #[allow(clippy::cognitive_complexity)]
 fn update_crate() {
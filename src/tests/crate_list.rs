@@ -0,0 +1,104 @@
+use crate::{
+    builders::CrateBuilder,
+    util::{MockCookieUser, RequestHelper, Response},
+    OkBool, TestApp,
+};
+use cargo_registry::views::EncodableCrateList;
+
+#[derive(Deserialize)]
+struct ListResponse {
+    list: EncodableCrateList,
+}
+
+#[derive(Deserialize)]
+struct ListsResponse {
+    lists: Vec<EncodableCrateList>,
+}
+
+impl MockCookieUser {
+    fn create_list(&self, name: &str) -> Response<ListResponse> {
+        let body = json!({ "name": name, "description": "my favorites" });
+        self.post("/api/v1/me/lists", body.to_string().as_bytes())
+    }
+
+    fn add_to_list(&self, list_id: i32, krate_name: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/me/lists/{}/crates/{}", list_id, krate_name);
+        self.put(&url, &[])
+    }
+}
+
+#[test]
+fn lists_are_public() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_listed", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("bar_listed", user.as_model().id).expect_build(conn);
+    });
+
+    let list = user.create_list("Web services").good().list;
+    assert_eq!(list.user, "foo");
+    assert!(list.crates.is_empty());
+    let json = user.create_list("web services").bad_with_status(200);
+    assert!(json.errors[0].detail.contains("already have a list"));
+
+    user.add_to_list(list.id, "foo_listed").good();
+    user.add_to_list(list.id, "bar_listed").good();
+    user.add_to_list(list.id, "bar_listed").good();
+    user.add_to_list(list.id, "missing").assert_not_found();
+
+    let json: ListsResponse = anon.get("/api/v1/users/foo/lists").good();
+    assert_eq!(json.lists.len(), 1);
+    assert_eq!(json.lists[0].name, "Web services");
+    assert_eq!(json.lists[0].description, "my favorites");
+    assert_eq!(json.lists[0].crates, ["bar_listed", "foo_listed"]);
+
+    let url = format!("/api/v1/me/lists/{}/crates/bar_listed", list.id);
+    user.delete::<OkBool>(&url).good();
+    let json: ListResponse = anon.get(&format!("/api/v1/lists/{}", list.id)).good();
+    assert_eq!(json.list.crates, ["foo_listed"]);
+
+    user.delete::<OkBool>(&format!("/api/v1/me/lists/{}", list.id))
+        .good();
+    anon.get::<()>(&format!("/api/v1/lists/{}", list.id))
+        .assert_not_found();
+}
+
+#[test]
+fn only_the_curator_can_change_a_list() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("foo_curated", user.as_model().id).expect_build(conn);
+    });
+
+    let list = user.create_list("Mine").good().list;
+    other.add_to_list(list.id, "foo_curated").assert_not_found();
+    other
+        .delete::<OkBool>(&format!("/api/v1/me/lists/{}", list.id))
+        .assert_not_found();
+
+    let json: ListsResponse = other.get("/api/v1/me/lists").good();
+    assert!(json.lists.is_empty());
+}
+
+#[test]
+fn lists_count_their_followers() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+
+    let list = user.create_list("Followed").good().list;
+    assert_eq!(list.followers, 0);
+
+    let url = format!("/api/v1/lists/{}/follow", list.id);
+    user.put::<OkBool>(&url, &[]).good();
+    other.put::<OkBool>(&url, &[]).good();
+    other.put::<OkBool>(&url, &[]).good();
+    anon.put::<OkBool>(&url, &[]).assert_forbidden();
+
+    let json: ListResponse = anon.get(&format!("/api/v1/lists/{}", list.id)).good();
+    assert_eq!(json.list.followers, 2);
+
+    other.delete::<OkBool>(&url).good();
+    let json: ListResponse = anon.get(&format!("/api/v1/lists/{}", list.id)).good();
+    assert_eq!(json.list.followers, 1);
+}
@@ -0,0 +1,142 @@
+use crate::{
+    builders::{CrateBuilder, VersionBuilder},
+    util::{RequestHelper, Response},
+    TestApp,
+};
+use conduit::Method;
+use serde_json::Value;
+
+fn graphql<T: RequestHelper>(client: &T, query: &str) -> Response<Value> {
+    let mut req = client.request_builder(Method::Post, "/api/v1/graphql");
+    req.with_body(json!({ "query": query }).to_string().as_bytes());
+    client.run(req)
+}
+
+#[test]
+fn query_crate_with_versions_and_owners() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_graphql", user.as_model().id)
+            .description("a crate")
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+    });
+
+    let json = graphql(
+        &anon,
+        r#"{ crate(name: "foo_graphql") {
+            name description maxVersion
+            versions { num yanked }
+            owners { login kind }
+        } }"#,
+    )
+    .good();
+
+    let krate = &json["data"]["crate"];
+    assert_eq!(krate["name"], "foo_graphql");
+    assert_eq!(krate["description"], "a crate");
+    assert_eq!(krate["maxVersion"], "1.1.0");
+    assert_eq!(krate["versions"][0]["num"], "1.1.0");
+    assert_eq!(krate["versions"][1]["num"], "1.0.0");
+    assert_eq!(krate["owners"][0]["login"], "foo");
+    assert_eq!(krate["owners"][0]["kind"], "user");
+}
+
+#[test]
+fn query_max_stable_version() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_graphql_pre", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0-pre"))
+            .expect_build(conn);
+    });
+
+    let json = graphql(
+        &anon,
+        r#"{ crate(name: "foo_graphql_pre") { maxVersion maxStableVersion } }"#,
+    )
+    .good();
+
+    let krate = &json["data"]["crate"];
+    assert_eq!(krate["maxVersion"], "1.1.0-pre");
+    assert_eq!(krate["maxStableVersion"], "1.0.0");
+}
+
+#[test]
+fn private_crates_are_hidden() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        use cargo_registry::{models::Visibility, schema::crates};
+        use diesel::prelude::*;
+
+        let krate = CrateBuilder::new("foo_graphql_private", user.as_model().id).expect_build(conn);
+        diesel::update(&krate)
+            .set(crates::visibility.eq(Visibility::Private))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let query = r#"{ crate(name: "foo_graphql_private") { name } }"#;
+    let json = graphql(&anon, query).good();
+    assert_eq!(json["data"]["crate"], Value::Null);
+
+    let json = graphql(&user, query).good();
+    assert_eq!(json["data"]["crate"]["name"], "foo_graphql_private");
+}
+
+#[test]
+fn emails_are_only_visible_to_their_owner() {
+    let (_, anon, user) = TestApp::init().with_user();
+
+    let json = graphql(&user, r#"{ me { login email } }"#).good();
+    assert_eq!(json["data"]["me"]["login"], "foo");
+    assert_eq!(json["data"]["me"]["email"], "something@example.com");
+
+    let json = graphql(&anon, r#"{ user(login: "foo") { login email } }"#).good();
+    assert_eq!(json["data"]["user"]["login"], "foo");
+    assert_eq!(json["data"]["user"]["email"], Value::Null);
+
+    let json = graphql(&anon, r#"{ me { login } }"#).good();
+    assert_eq!(json["data"]["me"], Value::Null);
+}
+
+#[test]
+fn invalid_queries_are_rejected() {
+    let (_, anon) = TestApp::init().empty();
+
+    let mut req = anon.request_builder(Method::Post, "/api/v1/graphql");
+    req.with_body(br#"{ "query": "{ doesNotExist }" }"#);
+    anon.run::<()>(req).assert_status(400);
+}
+
+#[test]
+fn deeply_nested_queries_are_rejected() {
+    let (_, anon) = TestApp::init().empty();
+
+    let query = format!("{}me{}", "{ me ".repeat(9), " }".repeat(9));
+    let json = graphql(&anon, &query).bad_with_status(400);
+    assert_eq!(
+        json.errors[0].detail,
+        "the query is nested more than 8 levels deep"
+    );
+}
+
+#[test]
+fn queries_selecting_too_many_fields_are_rejected() {
+    let (_, anon) = TestApp::init().empty();
+
+    let fields: Vec<_> = (0..201)
+        .map(|i| format!("me{}: me {{ login }}", i))
+        .collect();
+    let query = format!("{{ {} }}", fields.join(" "));
+    let json = graphql(&anon, &query).bad_with_status(400);
+    assert_eq!(
+        json.errors[0].detail,
+        "the query selects more than 200 fields"
+    );
+}
@@ -1,5 +1,12 @@
 use crate::{builders::CrateBuilder, RequestHelper, TestApp};
-use cargo_registry::{models::Keyword, views::EncodableKeyword};
+use cargo_registry::{
+    models::Keyword,
+    schema::{crate_download_rollups, crates},
+    views::{EncodableKeyword, EncodableTopCrates},
+};
+
+use chrono::{Datelike, Duration, Utc};
+use diesel::{insert_into, prelude::*, update};
 
 #[derive(Deserialize)]
 struct KeywordList {
@@ -110,3 +117,130 @@ fn update_crate() {
     assert_eq!(cnt("kw1"), 0);
     assert_eq!(cnt("kw2"), 0);
 }
+
+#[test]
+fn top() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    anon.get::<()>("/api/v1/keywords/top_kw/top")
+        .assert_not_found();
+
+    app.db(|conn| {
+        let today = Utc::now().naive_utc().date();
+        let this_week = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let now = Utc::now().naive_utc();
+
+        let krate1 = CrateBuilder::new("top_kw_1", user.id)
+            .keyword("top_kw")
+            .expect_build(conn);
+        let krate2 = CrateBuilder::new("top_kw_2", user.id)
+            .keyword("top_kw")
+            .expect_build(conn);
+        let krate3 = CrateBuilder::new("top_kw_3", user.id)
+            .keyword("top_kw")
+            .expect_build(conn);
+        let other = CrateBuilder::new("other_kw", user.id)
+            .keyword("other_kw")
+            .expect_build(conn);
+
+        let rollup = |crate_id: i32, weeks_ago: i64, downloads: i64| {
+            (
+                crate_download_rollups::crate_id.eq(crate_id),
+                crate_download_rollups::period.eq("week"),
+                crate_download_rollups::period_start.eq(this_week - Duration::weeks(weeks_ago)),
+                crate_download_rollups::downloads.eq(downloads),
+            )
+        };
+        insert_into(crate_download_rollups::table)
+            .values(&vec![
+                rollup(krate1.id, 0, 10),
+                rollup(krate1.id, 1, 10),
+                rollup(krate2.id, 2, 30),
+                // Too long ago to be counted
+                rollup(krate3.id, 12, 1000),
+                rollup(other.id, 0, 1000),
+            ])
+            .execute(conn)
+            .unwrap();
+
+        update(&krate1)
+            .set(crates::updated_at.eq(now - Duration::days(2)))
+            .execute(conn)
+            .unwrap();
+        update(&krate2)
+            .set(crates::updated_at.eq(now - Duration::days(3)))
+            .execute(conn)
+            .unwrap();
+        update(&krate3)
+            .set(crates::updated_at.eq(now - Duration::days(1)))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: EncodableTopCrates = anon.get("/api/v1/keywords/top_kw/top").good();
+    let most_downloaded = json
+        .most_downloaded
+        .iter()
+        .map(|top| (top.krate.name.as_str(), top.downloads))
+        .collect::<Vec<_>>();
+    assert_eq!(most_downloaded, [("top_kw_2", 30), ("top_kw_1", 20)]);
+    let recently_updated = json
+        .recently_updated
+        .iter()
+        .map(|krate| krate.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(recently_updated, ["top_kw_3", "top_kw_1", "top_kw_2"]);
+}
+
+#[derive(Deserialize)]
+struct KeywordSuggestions {
+    keywords: Vec<EncodableKeyword>,
+}
+
+#[test]
+fn suggest() {
+    let url = "/api/v1/keywords/suggest";
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        Keyword::find_or_create_all(conn, &["serde_unused"]).unwrap();
+        CrateBuilder::new("foo_suggest1", user.id)
+            .keyword("serde")
+            .keyword("serialization")
+            .expect_build(conn);
+        CrateBuilder::new("foo_suggest2", user.id)
+            .keyword("serialization")
+            .keyword("parser")
+            .expect_build(conn);
+    });
+
+    let json: KeywordSuggestions = anon.get_with_query(url, "q=SER").good();
+    let suggestions = json
+        .keywords
+        .iter()
+        .map(|kw| (kw.keyword.as_str(), kw.crates_cnt))
+        .collect::<Vec<_>>();
+    assert_eq!(suggestions, vec![("serialization", 2), ("serde", 1)]);
+
+    let json: KeywordSuggestions = anon.get_with_query(url, "q=ser&limit=1").good();
+    assert_eq!(json.keywords.len(), 1);
+    assert_eq!(json.keywords[0].keyword, "serialization");
+
+    // Misspelled keywords are matched by their trigram similarity
+    let json: KeywordSuggestions = anon.get_with_query(url, "q=serialisation").good();
+    let suggestions = json
+        .keywords
+        .iter()
+        .map(|kw| (kw.keyword.as_str(), kw.crates_cnt))
+        .collect::<Vec<_>>();
+    assert_eq!(suggestions, vec![("serialization", 2)]);
+
+    // `_` is matched literally rather than as a wildcard
+    let json: KeywordSuggestions = anon.get_with_query(url, "q=s_r").good();
+    assert!(json.keywords.is_empty());
+
+    let json: KeywordSuggestions = anon.get(url).good();
+    assert!(json.keywords.is_empty());
+}
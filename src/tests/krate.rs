@@ -1,15 +1,31 @@
 use crate::{
     builders::{CrateBuilder, DependencyBuilder, PublishBuilder, VersionBuilder},
-    new_category, new_dependency, new_user, CrateMeta, CrateResponse, GoodCrate, OkBool,
-    RequestHelper, TestApp,
+    new_category, new_dependency, new_user,
+    util::{MockCookieUser, MockTokenUser, Response},
+    CrateList, CrateMeta, CrateResponse, GoodCrate, OkBool, RequestHelper, TestApp,
 };
 use cargo_registry::{
-    models::{krate::MAX_NAME_LENGTH, Category, Crate},
-    schema::{api_tokens, crates, emails, metadata, versions, versions_published_by},
+    models::{
+        krate::MAX_NAME_LENGTH, Category, Crate, CrateLinks, DeletedCrate, PublishSession,
+        Visibility,
+    },
+    rate_limit::LimitedAction,
+    response_cache::CacheBackend,
+    schema::{
+        api_tokens, crate_download_rollups, crate_owner_actions, crates, deleted_versions,
+        dependencies, emails, metadata, publish_sessions, trending_crates, versions,
+        versions_published_by,
+    },
+    storage::LocalStorage,
+    tasks,
+    uploaders::Uploader,
     views::{
-        EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
-        EncodableVersionDownload,
+        EncodableCategory, EncodableCrate, EncodableCrateFunding, EncodableDependency,
+        EncodableDownloadRollup, EncodableKeyword, EncodablePublishSession,
+        EncodableSeriesAdoption, EncodableVersion, EncodableVersionAdoption,
+        EncodableVersionDownload, PublishDryRun,
     },
+    DependencyValidation, DownloadCounting, TyposquatCheck,
 };
 use std::{
     collections::HashMap,
@@ -19,8 +35,11 @@ use std::{
 };
 
 use chrono::Utc;
+use conduit::Method;
 use diesel::{dsl::*, prelude::*, update};
 use flate2::{write::GzEncoder, Compression};
+use swirl::Job;
+use tempdir::TempDir;
 
 #[derive(Deserialize)]
 struct VersionsList {
@@ -40,6 +59,22 @@ struct RevDeps {
 struct Downloads {
     version_downloads: Vec<EncodableVersionDownload>,
 }
+#[derive(Deserialize)]
+struct DownloadsSummary {
+    weekly: Vec<EncodableDownloadRollup>,
+    monthly: Vec<EncodableDownloadRollup>,
+}
+#[derive(Deserialize)]
+struct PublishSessionResponse {
+    session: EncodablePublishSession,
+}
+#[derive(Deserialize)]
+struct Availability {
+    available: bool,
+    reason: Option<String>,
+    detail: Option<String>,
+    available_at: Option<String>,
+}
 
 #[derive(Deserialize)]
 struct SummaryResponse {
@@ -49,10 +84,40 @@ struct SummaryResponse {
     most_downloaded: Vec<EncodableCrate>,
     most_recently_downloaded: Vec<EncodableCrate>,
     just_updated: Vec<EncodableCrate>,
+    trending: Vec<EncodableCrate>,
     popular_keywords: Vec<EncodableKeyword>,
     popular_categories: Vec<EncodableCategory>,
 }
 
+/// Ranks the crates as trending in the given order, like the
+/// `update_trending_crates` background job does.
+fn set_trending(conn: &PgConnection, krates: &[&Crate]) {
+    let rows = krates
+        .iter()
+        .zip(1..)
+        .map(|(krate, rank)| {
+            (
+                trending_crates::crate_id.eq(krate.id),
+                trending_crates::rank.eq(rank),
+                trending_crates::last_week_downloads.eq(100),
+                trending_crates::previous_week_downloads.eq(0),
+                trending_crates::growth.eq(100.0),
+            )
+        })
+        .collect::<Vec<_>>();
+    insert_into(trending_crates::table)
+        .values(&rows)
+        .execute(conn)
+        .unwrap();
+}
+
+/// The crates listed in the summary are only updated by the `refresh_summary`
+/// background job.
+fn refresh_summary(conn: &PgConnection) {
+    no_arg_sql_function!(refresh_summary_crates, ());
+    select(refresh_summary_crates).execute(conn).unwrap();
+}
+
 impl crate::util::MockAnonymousUser {
     fn reverse_dependencies(&self, krate_name: &str) -> RevDeps {
         let url = format!("/api/v1/crates/{}/reverse_dependencies", krate_name);
@@ -76,6 +141,30 @@ impl crate::util::MockTokenUser {
         self.app().run_pending_background_jobs();
         response
     }
+
+    /// Set an advisory on the specified version of the specified crate and run all pending
+    /// background jobs
+    fn set_advisory(
+        &self,
+        krate_name: &str,
+        version: &str,
+        advisory: &str,
+    ) -> crate::util::Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/{}/advisory", krate_name, version);
+        let body = json!({ "advisory": advisory }).to_string();
+        let response = self.put(&url, body.as_bytes());
+        self.app().run_pending_background_jobs();
+        response
+    }
+
+    /// Clear the advisory on the specified version of the specified crate and run all pending
+    /// background jobs
+    fn clear_advisory(&self, krate_name: &str, version: &str) -> crate::util::Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/{}/advisory", krate_name, version);
+        let response = self.delete(&url);
+        self.app().run_pending_background_jobs();
+        response
+    }
 }
 
 #[test]
@@ -236,6 +325,299 @@ fn exact_match_first_on_queries() {
     assert_eq!(json.crates[2].name, "foo_exact");
 }
 
+#[test]
+fn index_filters() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        new_category("Cat 1", "cat1", "Cat 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        let old = CrateBuilder::new("filter_old", user.id)
+            .description("filter")
+            .keyword("kw1")
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .license(Some("MIT OR Apache-2.0"))
+                    .rust_version("1.31"),
+            )
+            .expect_build(conn);
+
+        let new = CrateBuilder::new("filter_new", user.id)
+            .description("filter")
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .license(Some("MIT"))
+                    .rust_version("1.40.0"),
+            )
+            .expect_build(conn);
+
+        CrateBuilder::new("filter_none", user.id)
+            .description("filter")
+            .keyword("kw1")
+            .version(VersionBuilder::new("1.0.0").license(Some("GPL-3.0")))
+            .expect_build(conn);
+
+        Category::update_crate(conn, &old, &["cat1"]).unwrap();
+        Category::update_crate(conn, &new, &["cat1"]).unwrap();
+    });
+
+    assert_eq!(anon.search("q=filter&category=cat1").meta.total, 2);
+    assert_eq!(
+        anon.search("q=filter&category=cat1&keyword=kw1").meta.total,
+        1
+    );
+    assert_eq!(anon.search("q=filter&license=mit").meta.total, 2);
+    assert_eq!(anon.search("q=filter&license=apache-2.0").meta.total, 1);
+    assert_eq!(anon.search("q=filter&license=GPL-3.0").meta.total, 1);
+    assert_eq!(anon.search("q=filter&rust_version=1.39").meta.total, 1);
+    assert_eq!(anon.search("q=filter&rust_version=1.40").meta.total, 2);
+    assert_eq!(anon.search("rust_version=1.40&license=MIT").meta.total, 2);
+    assert_eq!(anon.search("q=filter&max_rust_version=1.39").meta.total, 1);
+    assert_eq!(
+        anon.search("q=filter&max_rust_version=1.40.0").meta.total,
+        2
+    );
+
+    let json = anon.search("q=filter&rust_version=1.39");
+    assert_eq!(json.crates[0].name, "filter_old");
+
+    let json: serde_json::Value = anon
+        .get_with_query("/api/v1/crates", "q=filter&keyword=kw1&license=MIT")
+        .good();
+    assert_eq!(
+        json["meta"]["filters"],
+        json!({
+            "category": null,
+            "keyword": "kw1",
+            "license": "MIT",
+            "rust_version": null,
+        })
+    );
+
+    let json = anon
+        .get_with_query::<()>("/api/v1/crates", "rust_version=stable")
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("invalid rust_version `stable`"));
+}
+
+#[test]
+fn search_facets() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        new_category("Cat 1", "cat1", "Cat 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        let first = CrateBuilder::new("facet_1", user.id)
+            .keyword("kw1")
+            .keyword("kw2")
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .expect_build(conn);
+        CrateBuilder::new("facet_2", user.id)
+            .keyword("kw1")
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .version(VersionBuilder::new("2.0.0").license(Some("Apache-2.0")))
+            .expect_build(conn);
+        CrateBuilder::new("facet_3", user.id)
+            .version(
+                VersionBuilder::new("1.0.0").license(Some("MIT OR Apache-2.0 WITH LLVM-exception")),
+            )
+            .expect_build(conn);
+        CrateBuilder::new("other", user.id)
+            .keyword("kw3")
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .expect_build(conn);
+
+        Category::update_crate(conn, &first, &["cat1"]).unwrap();
+    });
+
+    let json: serde_json::Value = anon
+        .get_with_query("/api/v1/crates", "q=facet&per_page=1")
+        .good();
+    assert_eq!(json["crates"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        json["meta"]["facets"],
+        json!({
+            "categories": [{ "name": "cat1", "count": 1 }],
+            "keywords": [{ "name": "kw1", "count": 2 }, { "name": "kw2", "count": 1 }],
+            "licenses": [
+                { "name": "MIT", "count": 3 },
+                { "name": "Apache-2.0", "count": 2 },
+                { "name": "LLVM-exception", "count": 1 },
+            ],
+        })
+    );
+
+    let json: serde_json::Value = anon.get("/api/v1/crates").good();
+    assert_eq!(json["meta"]["facets"], serde_json::Value::Null);
+}
+
+#[test]
+fn search_suggestions() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("serde", user.id).expect_build(conn);
+        CrateBuilder::new("serde_json", user.id).expect_build(conn);
+        CrateBuilder::new("rand", user.id).expect_build(conn);
+    });
+
+    let json: serde_json::Value = anon.get_with_query("/api/v1/crates", "q=serdde").good();
+    assert_eq!(json["meta"]["total"], 0);
+    assert_eq!(json["meta"]["suggestions"][0], "serde");
+
+    let json: serde_json::Value = anon.get_with_query("/api/v1/crates", "q=serde").good();
+    let suggestions = json["meta"]["suggestions"].as_array().unwrap();
+    assert!(!suggestions.contains(&json!("serde")));
+
+    let json: serde_json::Value = anon.get("/api/v1/crates").good();
+    assert_eq!(json["meta"]["suggestions"], serde_json::Value::Null);
+}
+
+#[test]
+fn hybrid_search_strategy() {
+    use cargo_registry::search_strategy::{SearchExperiment, SearchStrategy};
+
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| {
+            config.search_experiment = SearchExperiment {
+                default: SearchStrategy::Hybrid,
+                hybrid_percentage: 0,
+            };
+        })
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("tokio", user.id).expect_build(conn);
+        CrateBuilder::new("ranked_quiet", user.id)
+            .description("ranked")
+            .recent_downloads(1000)
+            .expect_build(conn);
+        CrateBuilder::new("ranked_loud", user.id)
+            .description("ranked")
+            .expect_build(conn);
+    });
+
+    // Names with typos are found through their trigram similarity
+    let json: serde_json::Value = anon.get_with_query("/api/v1/crates", "q=tokioo").good();
+    assert_eq!(json["meta"]["search_strategy"], "hybrid");
+    assert_eq!(json["crates"][0]["name"], "tokio");
+
+    // Recent downloads break ties between equally relevant crates
+    let json = anon.search("q=ranked");
+    assert_eq!(json.meta.total, 2);
+    assert_eq!(json.crates[0].name, "ranked_quiet");
+    assert_eq!(json.crates[1].name, "ranked_loud");
+}
+
+#[test]
+fn tsrank_search_strategy_is_the_default() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("tokio", user.id).expect_build(conn);
+    });
+
+    let json: serde_json::Value = anon.get_with_query("/api/v1/crates", "q=tokioo").good();
+    assert_eq!(json["meta"]["search_strategy"], "tsrank");
+    assert_eq!(json["meta"]["total"], 0);
+
+    let json: serde_json::Value = anon.get("/api/v1/crates").good();
+    assert_eq!(json["meta"]["search_strategy"], serde_json::Value::Null);
+}
+
+#[test]
+fn index_bulk_ids() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        for name in &["bulk_a", "bulk-b", "bulk_c"] {
+            CrateBuilder::new(name, user.id).expect_build(conn);
+        }
+    });
+
+    let json = anon.search("ids[]=bulk_a&ids[]=bulk_b&ids[]=missing");
+    assert_eq!(json.meta.total, 2);
+    let mut names = json.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["bulk-b", "bulk_a"]);
+
+    let query = (0..101)
+        .map(|i| format!("ids[]=bulk_{}", i))
+        .collect::<Vec<_>>()
+        .join("&");
+    let json = anon
+        .get_with_query::<()>("/api/v1/crates", &query)
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot request more than 100 crates at once"));
+}
+
+#[test]
+fn index_seek_pagination() {
+    #[derive(Deserialize)]
+    struct SeekList {
+        crates: Vec<EncodableCrate>,
+        meta: SeekMeta,
+    }
+    #[derive(Deserialize)]
+    struct SeekMeta {
+        total: Option<i64>,
+        next_page: Option<String>,
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        for name in &["seek_1", "seek_2", "seek_3"] {
+            CrateBuilder::new(name, user.id).expect_build(conn);
+        }
+    });
+
+    let page1: SeekList = anon
+        .get_with_query("/api/v1/crates", "seek=&per_page=2")
+        .good();
+    let names = page1.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, vec!["seek_1", "seek_2"]);
+    assert_eq!(page1.meta.total, None);
+
+    let next_page = page1.meta.next_page.unwrap();
+    assert!(next_page.starts_with("?seek="));
+    let page2: SeekList = anon
+        .get_with_query("/api/v1/crates", &next_page[1..])
+        .good();
+    let names = page2.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, vec!["seek_3"]);
+    assert_eq!(page2.meta.next_page, None);
+
+    let json = anon
+        .get_with_query::<()>("/api/v1/crates", "q=seek&seek=")
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("seek pagination is only supported when listing crates by name"));
+
+    let json = anon
+        .get_with_query::<()>("/api/v1/crates", "page=2&seek=")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only one of `page` and `seek` can be given"));
+}
+
 #[test]
 fn index_sorting() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -318,6 +700,57 @@ fn index_sorting() {
     assert_eq!(json.crates[3].name, "other_sort");
 }
 
+#[test]
+fn index_sorted_by_trending() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        CrateBuilder::new("a_not_trending", user.id).expect_build(conn);
+        let krate1 = CrateBuilder::new("z_trending_first", user.id).expect_build(conn);
+        let krate2 = CrateBuilder::new("b_trending_second", user.id).expect_build(conn);
+        set_trending(conn, &[&krate1, &krate2]);
+    });
+
+    let json = anon.search("sort=trending");
+    let names = json
+        .crates
+        .iter()
+        .map(|krate| &*krate.name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["z_trending_first", "b_trending_second", "a_not_trending"]
+    );
+}
+
+#[test]
+fn index_sorted_by_popularity() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        // All-time downloads don't matter
+        CrateBuilder::new("a_unpopular", user.id)
+            .downloads(1000)
+            .expect_build(conn);
+        let krate1 = CrateBuilder::new("z_most_popular", user.id).expect_build(conn);
+        let krate2 = CrateBuilder::new("b_popular", user.id).expect_build(conn);
+        for (krate, popularity) in &[(krate1, 5.0), (krate2, 2.5)] {
+            update(crates::table.find(krate.id))
+                .set(crates::popularity.eq(popularity))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let json = anon.search("sort=popularity");
+    let names = json
+        .crates
+        .iter()
+        .map(|krate| &*krate.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["z_most_popular", "b_popular", "a_unpopular"]);
+}
+
 #[test]
 #[allow(clippy::cognitive_complexity)]
 fn exact_match_on_queries_with_sort() {
@@ -567,6 +1000,74 @@ fn show() {
     );
 }
 
+#[test]
+fn show_is_not_modified_until_the_crate_changes() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo_show_etag", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .downloads(20)
+            .expect_build(conn)
+    });
+
+    let url = "/api/v1/crates/foo_show_etag";
+    let response = anon.get::<CrateResponse>(url);
+    response.assert_status(200);
+    let etag = response.header("ETag").to_string();
+
+    let mut req = anon.request_builder(Method::Get, url);
+    req.header("If-None-Match", &etag);
+    anon.run::<()>(req).assert_status(304);
+
+    // Downloads don't touch `updated_at`, but still change the response
+    app.db(|conn| {
+        update(&krate)
+            .set(crates::downloads.eq(21))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let mut req = anon.request_builder(Method::Get, url);
+    req.header("If-None-Match", &etag);
+    let response = anon.run::<CrateResponse>(req);
+    response.assert_status(200);
+    assert_ne!(response.header("ETag"), etag);
+}
+
+#[test]
+fn cached_crates_are_invalidated_by_owner_changes() {
+    let (app, anon, user, token) = TestApp::init()
+        .with_config(|config| {
+            config.response_cache.backend = CacheBackend::Memory { capacity: 10 };
+        })
+        .with_token();
+    let user = user.as_model();
+    app.db_new_user("bar");
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo_cached", user.id)
+            .description("old description")
+            .expect_build(conn)
+    });
+    let json = anon.show_crate("foo_cached");
+    assert_eq!(json.krate.description.unwrap(), "old description");
+
+    app.db(|conn| {
+        update(&krate)
+            .set(crates::description.eq("new description"))
+            .execute(conn)
+            .unwrap();
+    });
+    let json = anon.show_crate("foo_cached");
+    assert_eq!(json.krate.description.unwrap(), "old description");
+
+    token.add_named_owner("foo_cached", "bar").good();
+    let json = anon.show_crate("foo_cached");
+    assert_eq!(json.krate.description.unwrap(), "new description");
+}
+
 #[test]
 fn yanked_versions_are_not_considered_for_max_version() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -721,6 +1222,96 @@ fn new_krate() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+#[test]
+fn new_krate_records_checksum() {
+    let (_, anon, user) = TestApp::full().with_user();
+
+    let crate_to_publish = PublishBuilder::new("foo_new").version("1.0.0");
+    user.enqueue_publish(crate_to_publish).good();
+
+    let json: serde_json::Value = anon.get("/api/v1/crates/foo_new/1.0.0/checksums").good();
+    assert_eq!(json["checksums"]["sha256"].as_str().unwrap().len(), 64);
+    assert!(json["checksums"]["sha512"].is_null());
+}
+
+/// Publishes a crate as if from a CI run, with the given CI metadata headers.
+fn publish_from_ci(
+    user: &MockCookieUser,
+    publish_builder: PublishBuilder,
+    headers: &[(&str, &str)],
+) -> Response<GoodCrate> {
+    let mut request = user.request_builder(Method::Put, "/api/v1/crates/new");
+    for &(name, value) in headers {
+        request.header(name, value);
+    }
+    request.with_body(&publish_builder.body());
+    user.run(request)
+}
+
+#[test]
+fn new_krate_records_ci_provenance() {
+    let storage = TempDir::new("crate-provenance").unwrap();
+    let root = storage.path().to_path_buf();
+    let (_, anon, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_user();
+    let sha = "0123456789ABCDEF0123456789abcdef01234567";
+
+    let headers = [
+        ("X-CI-Provider", "github-actions"),
+        (
+            "X-CI-Run-URL",
+            "https://github.com/foo/foo_ci/actions/runs/1",
+        ),
+        ("X-CI-Commit-SHA", sha),
+    ];
+    publish_from_ci(
+        &user,
+        PublishBuilder::new("foo_ci").version("1.0.0"),
+        &headers,
+    )
+    .good();
+    user.enqueue_publish(PublishBuilder::new("foo_ci").version("1.1.0"))
+        .good();
+
+    let json: serde_json::Value = anon.get("/api/v1/crates/foo_ci/1.0.0/provenance").good();
+    let provenance = &json["provenance"];
+    assert_eq!(provenance["provider"], "github-actions");
+    assert_eq!(
+        provenance["run_url"],
+        "https://github.com/foo/foo_ci/actions/runs/1"
+    );
+    assert_eq!(provenance["commit_sha"], sha.to_lowercase());
+    // The crate has no repository to check the commit against
+    assert!(provenance["verified"].is_null());
+
+    let json: serde_json::Value = anon.get("/api/v1/crates/foo_ci/1.1.0/provenance").good();
+    assert!(json["provenance"].is_null());
+
+    let headers = [("X-CI-Provider", "github-actions")];
+    let json = publish_from_ci(
+        &user,
+        PublishBuilder::new("foo_ci").version("1.2.0"),
+        &headers,
+    )
+    .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("have to be sent together"));
+
+    let headers = [
+        ("X-CI-Provider", "github-actions"),
+        ("X-CI-Commit-SHA", "0123abc"),
+    ];
+    let json = publish_from_ci(
+        &user,
+        PublishBuilder::new("foo_ci").version("1.2.0"),
+        &headers,
+    )
+    .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid commit"));
+}
+
 #[test]
 fn new_krate_with_token() {
     let (_, _, _, token) = TestApp::full().with_token();
@@ -732,29 +1323,162 @@ fn new_krate_with_token() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+/// Uploads a chunk of a publish started at `url`.
+fn upload_chunk(
+    user: &MockCookieUser,
+    url: &str,
+    offset: usize,
+    chunk: &[u8],
+) -> Response<PublishSessionResponse> {
+    let mut request = user.request_builder(Method::Put, &format!("{}/chunk", url));
+    request.with_query(&format!("offset={}", offset));
+    request.with_body(chunk);
+    user.run(request)
+}
+
 #[test]
-fn new_krate_weird_version() {
-    let (_, _, _, token) = TestApp::full().with_token();
+fn new_krate_in_chunks() {
+    let (_, _, user) = TestApp::full().with_user();
 
-    let crate_to_publish = PublishBuilder::new("foo_weird").version("0.0.0-pre");
-    let json: GoodCrate = token.enqueue_publish(crate_to_publish).good();
+    let body = PublishBuilder::new("foo_new").version("1.0.0").body();
+    let start = json!({ "crate": "foo_new", "length": body.len() });
+    let json: PublishSessionResponse = user
+        .post("/api/v1/crates/new/session", start.to_string().as_bytes())
+        .good();
+    assert_eq!(json.session.length, body.len() as i32);
+    assert_eq!(json.session.received, 0);
 
-    assert_eq!(json.krate.name, "foo_weird");
-    assert_eq!(json.krate.max_version, "0.0.0-pre");
+    let url = format!("/api/v1/crates/new/session/{}", json.session.id);
+    let (first, second) = body.split_at(body.len() / 2);
+    let json = upload_chunk(&user, &url, 0, first).good();
+    assert_eq!(json.session.received, first.len() as i32);
+
+    // The response got lost, and the chunk is uploaded again
+    let json = upload_chunk(&user, &url, 0, first).bad_with_status(200);
+    let expected = format!("expected a chunk at offset {}", first.len());
+    assert!(
+        json.errors[0].detail.contains(&expected),
+        "{:?}",
+        json.errors
+    );
+    let json: PublishSessionResponse = user.get(&url).good();
+    assert_eq!(json.session.received, first.len() as i32);
+
+    upload_chunk(&user, &url, first.len(), second).good();
+    let json: GoodCrate = user.post(&format!("{}/finish", url), b"").good();
+    assert_eq!(json.krate.name, "foo_new");
+    assert_eq!(json.krate.max_version, "1.0.0");
+
+    user.get::<()>(&url).assert_not_found();
 }
 
 #[test]
-fn new_with_renamed_dependency() {
-    let (app, _, user, token) = TestApp::full().with_token();
+fn publish_sessions_check_lengths_and_owners() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+
+    let start = |length: i32| {
+        let body = json!({ "crate": "foo_chunks", "length": length });
+        user.post::<PublishSessionResponse>(
+            "/api/v1/crates/new/session",
+            body.to_string().as_bytes(),
+        )
+    };
+    let json = start(0).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("at least 1 byte"));
+    let json = start(i32::max_value()).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("max upload size is"));
+
+    let json = start(10).good();
+    let url = format!("/api/v1/crates/new/session/{}", json.session.id);
+    let json = upload_chunk(&user, &url, 0, &[0; 11]).bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only 10 more bytes are expected"));
+    let json = user
+        .post::<GoodCrate>(&format!("{}/finish", url), b"")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("the upload is incomplete, 0 of 10 bytes were received"));
+
+    other.get::<()>(&url).assert_not_found();
+    upload_chunk(&other, &url, 0, &[0; 10]).assert_not_found();
+
+    upload_chunk(&user, &url, 0, &[0; 10]).good();
+    user.delete::<OkBool>(&url).good();
+    user.get::<()>(&url).assert_not_found();
 
+    // Sessions that haven't received a chunk in a day are cleaned up
+    let id = start(10).good().session.id;
     app.db(|conn| {
-        // Insert a crate directly into the database so that new-krate can depend on it
-        CrateBuilder::new("package-name", user.as_model().id).expect_build(conn);
+        update(publish_sessions::table.find(id))
+            .set(publish_sessions::updated_at.eq(now - 2.days()))
+            .execute(conn)
+            .unwrap();
+        assert_eq!(PublishSession::delete_stale(conn).unwrap(), 1);
     });
+}
 
-    let dependency = DependencyBuilder::new("package-name").rename("my-name");
+#[test]
+fn publish_sessions_are_bound_to_their_crate() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("foo_large", user.as_model().id)
+            .max_upload_size(5000)
+            .expect_build(conn);
+    });
 
-    let crate_to_publish = PublishBuilder::new("new-krate")
+    let start = |user: &MockCookieUser, krate: &str, length: usize| {
+        let body = json!({ "crate": krate, "length": length });
+        user.post::<PublishSessionResponse>(
+            "/api/v1/crates/new/session",
+            body.to_string().as_bytes(),
+        )
+    };
+
+    // The raised limit only applies to users who can publish the crate
+    let json = start(&user, "foo_large", 4000).good();
+    assert_eq!(json.session.crate_name, "foo_large");
+    let json = start(&other, "foo_large", 4000).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("max upload size is: 3000"));
+
+    let body = PublishBuilder::new("foo_small").version("1.0.0").body();
+    let json = start(&user, "foo_large", body.len()).good();
+    let url = format!("/api/v1/crates/new/session/{}", json.session.id);
+    upload_chunk(&user, &url, 0, &body).good();
+    let json = user
+        .post::<GoodCrate>(&format!("{}/finish", url), b"")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains(
+        "the upload is for crate `foo_small`, but the session was started for `foo_large`"
+    ));
+}
+
+#[test]
+fn new_krate_weird_version() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_weird").version("0.0.0-pre");
+    let json: GoodCrate = token.enqueue_publish(crate_to_publish).good();
+
+    assert_eq!(json.krate.name, "foo_weird");
+    assert_eq!(json.krate.max_version, "0.0.0-pre");
+}
+
+#[test]
+fn new_with_renamed_dependency() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        // Insert a crate directly into the database so that new-krate can depend on it
+        CrateBuilder::new("package-name", user.as_model().id).expect_build(conn);
+    });
+
+    let dependency = DependencyBuilder::new("package-name").rename("my-name");
+
+    let crate_to_publish = PublishBuilder::new("new-krate")
         .version("1.0.0")
         .dependency(dependency);
     token.enqueue_publish(crate_to_publish).good();
@@ -769,6 +1493,71 @@ fn new_with_renamed_dependency() {
     assert_eq!(crates[0].deps[0].package.as_ref().unwrap(), "package-name");
 }
 
+/// Publishes a crate as a dry run.
+fn publish_dry_run(
+    token: &MockTokenUser,
+    publish_builder: PublishBuilder,
+) -> Response<PublishDryRun> {
+    let mut request = token.request_builder(Method::Put, "/api/v1/crates/new");
+    request.with_query("dry_run=true");
+    request.with_body(&publish_builder.body());
+    token.run(request)
+}
+
+#[test]
+fn publish_dry_run_persists_nothing() {
+    let storage = TempDir::new("publish-dry-run").unwrap();
+    let root = storage.path().to_path_buf();
+    let (_, anon, _, token) = TestApp::init()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_token();
+
+    let json = publish_dry_run(&token, PublishBuilder::new("fdry").readme("# fdry")).good();
+    assert_eq!(json.krate.name, "fdry");
+    assert_eq!(json.index_entry.name, "fdry");
+    assert_eq!(json.index_entry.vers, "1.0.0");
+    assert_eq!(json.index_entry.cksum.len(), 64);
+    assert_eq!(json.index_entry.yanked, Some(false));
+
+    // Neither the crate nor its file exist, and no jobs were enqueued, which
+    // the test app checks when it is dropped
+    anon.get::<()>("/api/v1/crates/fdry").assert_not_found();
+    assert!(!storage.path().join("crates/fdry/fdry-1.0.0.crate").exists());
+}
+
+#[test]
+fn publish_dry_run_validates_like_publishing() {
+    let storage = TempDir::new("publish-dry-run").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, _, _, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_token();
+
+    let json = publish_dry_run(&token, PublishBuilder::new("fdry").unset_description())
+        .bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("missing or empty metadata fields: description"),
+        "{:?}",
+        json.errors
+    );
+
+    token.enqueue_publish(PublishBuilder::new("fdry")).good();
+    app.run_pending_background_jobs();
+    let json = publish_dry_run(&token, PublishBuilder::new("fdry")).bad_with_status(200);
+    assert!(
+        json.errors[0].detail.contains("already uploaded"),
+        "{:?}",
+        json.errors
+    );
+    let json = publish_dry_run(&token, PublishBuilder::new("fdry").version("1.1.0")).good();
+    assert_eq!(json.index_entry.vers, "1.1.0");
+    assert_eq!(json.krate.max_version, "1.1.0");
+}
+
 #[test]
 fn new_krate_with_dependency() {
     let (app, _, user, token) = TestApp::full().with_token();
@@ -789,6 +1578,97 @@ fn new_krate_with_dependency() {
     token.enqueue_publish(crate_to_publish).good();
 }
 
+#[test]
+fn reject_new_krate_with_unmatched_dependency_requirement() {
+    let (app, _, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo-unmatched", user.as_model().id)
+            .version("1.0.0")
+            .version(VersionBuilder::new("2.0.0").yanked(true))
+            .expect_build(conn);
+    });
+
+    let dependency = DependencyBuilder::new("foo-unmatched").version_req("^2.0");
+    let crate_to_publish = PublishBuilder::new("new_unmatched_dep").dependency(dependency);
+    let json = token.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "no non-yanked version of dependency `foo-unmatched` matches the requirement `^2.0`"
+    );
+}
+
+#[test]
+fn warn_about_unmatched_dependency_requirement() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| config.dependency_validation = DependencyValidation::Warn)
+        .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo-unmatched", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+
+    let dependency = DependencyBuilder::new("foo-unmatched").version_req("^2.0");
+    let crate_to_publish = PublishBuilder::new("new_unmatched_dep").dependency(dependency);
+    let json = token.enqueue_publish(crate_to_publish).good();
+    assert_eq!(
+        json.warnings.other,
+        vec![
+            "no non-yanked version of dependency `foo-unmatched` matches \
+              the requirement `^2.0`"
+                .to_string()
+        ]
+    );
+}
+
+#[test]
+fn warn_about_name_similar_to_popular_crate() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| config.typosquat_check = TyposquatCheck::Warn)
+        .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("serde_json", user.as_model().id)
+            .downloads(1000)
+            .expect_build(conn);
+    });
+
+    let crate_to_publish = PublishBuilder::new("serde_jsno");
+    let json = token.enqueue_publish(crate_to_publish).good();
+    assert_eq!(
+        json.warnings.other,
+        vec![
+            "the name `serde_jsno` is similar to the name of the popular crate(s) `serde_json`"
+                .to_string()
+        ]
+    );
+}
+
+#[test]
+fn reject_name_similar_to_popular_crate_without_approval() {
+    let (app, _, user, token) = TestApp::init()
+        .with_config(|config| config.typosquat_check = TyposquatCheck::Approval)
+        .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("tokio", user.as_model().id)
+            .downloads(1000)
+            .expect_build(conn);
+    });
+
+    let crate_to_publish = PublishBuilder::new("t0kio");
+    let json = token.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("requires the approval of the registry administrators"),
+        "{:?}",
+        json.errors
+    );
+}
+
 #[test]
 fn reject_new_krate_with_non_exact_dependency() {
     let (app, _, user, token) = TestApp::init().with_token();
@@ -914,6 +1794,73 @@ fn valid_feature_names() {
     assert!(Crate::valid_feature("32-column-tables"));
 }
 
+#[test]
+fn new_krate_with_rust_version() {
+    let (_, anon, user) = TestApp::full().with_user();
+
+    let crate_to_publish = PublishBuilder::new("foo_msrv").rust_version("1.40");
+    user.enqueue_publish(crate_to_publish).good();
+
+    let json = anon.show_version("foo_msrv", "1.0.0");
+    assert_eq!(json.version.rust_version, Some("1.40".to_string()));
+
+    let crate_to_publish = PublishBuilder::new("foo_msrv")
+        .version("1.1.0")
+        .rust_version("latest");
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("invalid rust_version `latest`"),
+        "{:?}",
+        json.errors
+    );
+}
+
+#[test]
+fn new_krate_license_is_normalized() {
+    let (_, anon, user) = TestApp::full().with_user();
+
+    let crate_to_publish = PublishBuilder::new("foo_license").license("MIT/Apache-2.0");
+    user.enqueue_publish(crate_to_publish).good();
+
+    let json = anon.show_version("foo_license", "1.0.0");
+    assert_eq!(json.version.license, Some("MIT OR Apache-2.0".to_string()));
+
+    let crate_to_publish = PublishBuilder::new("foo_license")
+        .version("1.1.0")
+        .license("MIT OR");
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("incomplete license expression `MIT OR`"),
+        "{:?}",
+        json.errors
+    );
+}
+
+#[test]
+fn new_krate_with_features() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_features")
+        .feature("default", &["std"])
+        .feature("std", &[]);
+    token.enqueue_publish(crate_to_publish).good();
+    app.run_pending_background_jobs();
+
+    let json = anon.show_version("foo_features", "1.0.0");
+    assert_eq!(
+        json.version.features,
+        json!({ "default": ["std"], "std": [] })
+    );
+
+    let crates = app.crates_from_index_head("fo/o_/foo_features");
+    assert_eq!(crates[0].features["default"], vec!["std".to_string()]);
+    assert!(crates[0].features["std"].is_empty());
+}
+
 #[test]
 fn new_krate_too_big() {
     let (_, _, user) = TestApp::init().with_user();
@@ -931,6 +1878,26 @@ fn new_krate_too_big() {
     );
 }
 
+#[test]
+fn new_krate_with_truncated_tarball() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let mut body = PublishBuilder::new("foo_truncated").body();
+    let length = body.len();
+    body.truncate(length - 10);
+
+    let json = user
+        .put::<()>("/api/v1/crates/new", &body)
+        .bad_with_status(200);
+    assert!(
+        json.errors[0].detail.contains("the upload ended after"),
+        "{:?}",
+        json.errors
+    );
+    user.get::<()>("/api/v1/crates/foo_truncated")
+        .assert_not_found();
+}
+
 #[test]
 fn new_krate_too_big_but_whitelisted() {
     let (app, _, user, token) = TestApp::full().with_token();
@@ -1251,6 +2218,16 @@ fn summary_new_crates() {
             .downloads(1000)
             .expect_build(conn);
 
+        let private = CrateBuilder::new("private_downloads", user.id)
+            .version(VersionBuilder::new("0.1.0"))
+            .downloads(10000)
+            .recent_downloads(100)
+            .expect_build(conn);
+        update(&private)
+            .set(crates::visibility.eq(Visibility::Private))
+            .execute(&*conn)
+            .unwrap();
+
         new_category("Category 1", "cat1", "Category 1 crates")
             .create_or_update(conn)
             .unwrap();
@@ -1269,6 +2246,8 @@ fn summary_new_crates() {
             .set(crates::updated_at.eq(updated))
             .execute(&*conn)
             .unwrap();
+
+        refresh_summary(conn);
     });
 
     let json: SummaryResponse = anon.get("/api/v1/summary").good();
@@ -1287,6 +2266,27 @@ fn summary_new_crates() {
     assert_eq!(json.new_crates.len(), 4);
 }
 
+#[test]
+fn summary_trending_crates() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        let krate1 = CrateBuilder::new("trending_first", user.id).expect_build(conn);
+        let krate2 = CrateBuilder::new("trending_second", user.id).expect_build(conn);
+        CrateBuilder::new("not_trending", user.id).expect_build(conn);
+        set_trending(conn, &[&krate1, &krate2]);
+        refresh_summary(conn);
+    });
+
+    let json: SummaryResponse = anon.get("/api/v1/summary").good();
+    let trending = json
+        .trending
+        .iter()
+        .map(|krate| &*krate.name)
+        .collect::<Vec<_>>();
+    assert_eq!(trending, ["trending_first", "trending_second"]);
+}
+
 #[test]
 fn download() {
     use chrono::{Duration, Utc};
@@ -1340,6 +2340,242 @@ fn download() {
     assert_dl_count("FOO_DOWNLOAD", Some(&query), 2);
 }
 
+#[test]
+fn version_downloads_date_range() {
+    use chrono::Duration;
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_range", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+    anon.get::<()>("/api/v1/crates/foo_range/1.0.0/download")
+        .assert_status(302);
+
+    let url = "/api/v1/crates/foo_range/1.0.0/downloads";
+    let today = Utc::today();
+    let day = |offset: i64| (today + Duration::days(offset)).format("%F");
+
+    let query = format!("from={}&to={}", day(-1), day(0));
+    let downloads: Downloads = anon.get_with_query(url, &query).good();
+    assert_eq!(downloads.version_downloads.len(), 1);
+    assert_eq!(downloads.version_downloads[0].downloads, 1);
+
+    let query = format!("from={}&to={}", day(-10), day(-1));
+    let downloads: Downloads = anon.get_with_query(url, &query).good();
+    assert!(downloads.version_downloads.is_empty());
+
+    let query = format!("from={}&to={}", day(0), day(-1));
+    let json = anon.get_with_query::<()>(url, &query).bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("`from` must not be after `to`"));
+
+    let query = format!("from={}&to={}", day(-400), day(0));
+    let json = anon.get_with_query::<()>(url, &query).bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot request more than 366 days of downloads at once"));
+
+    let json = anon
+        .get_with_query::<()>(url, "from=yesterday")
+        .bad_with_status(400);
+    assert!(json.errors[0].detail.contains("invalid `from` date"));
+}
+
+#[test]
+fn downloads_summary() {
+    use chrono::NaiveDate;
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_summary", user.id).expect_build(conn);
+        let rollup = |period: &str, start: &str, downloads: i64| {
+            (
+                crate_download_rollups::crate_id.eq(krate.id),
+                crate_download_rollups::period.eq(period.to_string()),
+                crate_download_rollups::period_start
+                    .eq(NaiveDate::parse_from_str(start, "%F").unwrap()),
+                crate_download_rollups::downloads.eq(downloads),
+            )
+        };
+        insert_into(crate_download_rollups::table)
+            .values(&vec![
+                rollup("week", "2019-11-04", 8),
+                rollup("week", "2019-10-28", 7),
+                rollup("month", "2019-11-01", 12),
+            ])
+            .execute(conn)
+            .unwrap();
+    });
+
+    let summary: DownloadsSummary = anon
+        .get("/api/v1/crates/foo_summary/downloads/summary")
+        .good();
+    let weekly = summary
+        .weekly
+        .iter()
+        .map(|r| (&*r.period_start, r.downloads))
+        .collect::<Vec<_>>();
+    assert_eq!(weekly, vec![("2019-10-28", 7), ("2019-11-04", 8)]);
+    assert_eq!(summary.monthly.len(), 1);
+    assert_eq!(summary.monthly[0].downloads, 12);
+}
+
+#[test]
+fn download_client_stats() {
+    use conduit::Method;
+
+    #[derive(Deserialize)]
+    struct ClientDownloads {
+        name: String,
+        downloads: i64,
+    }
+    #[derive(Deserialize)]
+    struct ClientStats {
+        cargo_versions: Vec<ClientDownloads>,
+        targets: Vec<ClientDownloads>,
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_clients", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let download = |user_agent: &str| {
+        let mut req =
+            anon.request_builder(Method::Get, "/api/v1/crates/foo_clients/1.0.0/download");
+        req.header("User-Agent", user_agent);
+        anon.run::<()>(req).assert_status(302);
+    };
+    download("cargo 1.39.0 (1c6ec66d5 2019-09-30)");
+    download("cargo 1.39.1 (1c6ec66d5 2019-09-30)");
+    download("cargo 1.40.0 (bc8e4c8be 2019-11-22) x86_64-unknown-linux-gnu");
+    download("Mozilla/5.0");
+
+    let stats: ClientStats = anon
+        .get("/api/v1/crates/foo_clients/downloads/clients")
+        .good();
+    let cargo_versions = stats
+        .cargo_versions
+        .iter()
+        .map(|c| (&*c.name, c.downloads))
+        .collect::<Vec<_>>();
+    assert_eq!(cargo_versions, vec![("1.39", 2), ("1.40", 1)]);
+    let targets = stats
+        .targets
+        .iter()
+        .map(|c| (&*c.name, c.downloads))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        vec![("unknown", 2), ("x86_64-unknown-linux-gnu", 1)]
+    );
+}
+
+#[test]
+fn version_adoption_is_broken_down_by_version_and_series() {
+    #[derive(Deserialize)]
+    struct Adoption {
+        versions: Vec<EncodableVersionAdoption>,
+        series: Vec<EncodableSeriesAdoption>,
+    }
+
+    let (app, anon, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        CrateBuilder::new("foo_adoption", user.id)
+            .version(VersionBuilder::new("0.9.0"))
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .version(VersionBuilder::new("2.0.0"))
+            .expect_build(conn);
+    });
+    let url = "/api/v1/crates/foo_adoption/version_adoption";
+
+    // Nothing is shown before the job runs
+    let adoption: Adoption = anon.get(url).good();
+    assert!(adoption.versions.is_empty());
+
+    for (version, downloads) in &[("0.9.0", 1), ("1.0.0", 2), ("1.1.0", 1)] {
+        for _ in 0..*downloads {
+            let url = format!("/api/v1/crates/foo_adoption/{}/download", version);
+            anon.get::<()>(&url).assert_status(302);
+        }
+    }
+    app.db(|conn| tasks::update_version_adoption().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let adoption: Adoption = anon.get(url).good();
+    let versions = adoption
+        .versions
+        .iter()
+        .map(|v| (&*v.num, v.downloads, v.share))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        versions,
+        vec![("1.1.0", 1, 0.25), ("1.0.0", 2, 0.5), ("0.9.0", 1, 0.25)]
+    );
+    let series = adoption
+        .series
+        .iter()
+        .map(|s| (&*s.series, s.downloads, s.share))
+        .collect::<Vec<_>>();
+    assert_eq!(series, vec![("1", 3, 0.75), ("0.9", 1, 0.25)]);
+}
+
+#[test]
+fn downloads_are_not_counted_by_the_route_when_counted_from_cdn_logs() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.download_counting = DownloadCounting::CdnLogs)
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_cdn_download", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/foo_cdn_download/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_cdn_download/foo_cdn_download-1.0.0.crate");
+    let downloads: Downloads = anon.get("/api/v1/crates/foo_cdn_download/downloads").good();
+    assert!(downloads.version_downloads.is_empty());
+}
+
+#[test]
+fn download_falls_back_to_the_index() {
+    let (app, anon, user) = TestApp::with_proxy()
+        .with_config(|config| config.index_fallback_url = Some("http://index.crates.io".into()))
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("fallback_crate", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    // 1.1.0 is in the index but not in the database yet
+    anon.get::<()>("/api/v1/crates/fallback_crate/1.1.0/download")
+        .assert_redirect_ends_with("/crates/fallback_crate/fallback_crate-1.1.0.crate");
+    anon.get::<()>("/api/v1/crates/fallback_crate/2.0.0/download")
+        .assert_not_found();
+
+    let downloads: Downloads = anon.get("/api/v1/crates/fallback_crate/downloads").good();
+    assert!(downloads.version_downloads.is_empty());
+}
+
 #[test]
 fn download_nonexistent_version_of_existing_crate_404s() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -1391,116 +2627,479 @@ fn dependencies() {
         .bad_with_status(200);
 }
 
-#[test]
-fn diesel_not_found_results_in_404() {
-    let (_, _, user) = TestApp::init().with_user();
+#[test]
+fn diesel_not_found_results_in_404() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    user.get("/api/v1/crates/foo_following/following")
+        .assert_not_found();
+}
+
+#[test]
+fn following() {
+    // TODO: Test anon requests as well?
+    let (app, _, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_following", user.as_model().id).expect_build(conn);
+    });
+
+    let is_following = || -> bool {
+        #[derive(Deserialize)]
+        struct F {
+            following: bool,
+        }
+
+        user.get::<F>("/api/v1/crates/foo_following/following")
+            .good()
+            .following
+    };
+
+    let follow = || {
+        assert!(
+            user.put::<OkBool>("/api/v1/crates/foo_following/follow", b"")
+                .good()
+                .ok
+        );
+    };
+
+    let unfollow = || {
+        assert!(
+            user.delete::<OkBool>("api/v1/crates/foo_following/follow")
+                .good()
+                .ok
+        );
+    };
+
+    assert!(!is_following());
+    follow();
+    follow();
+    assert!(is_following());
+    assert_eq!(user.search("following=1").crates.len(), 1);
+
+    unfollow();
+    unfollow();
+    assert!(!is_following());
+    assert_eq!(user.search("following=1").crates.len(), 0);
+}
+
+#[test]
+fn yank_works_as_intended() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    // Upload a new crate, putting it in the git index
+    let crate_to_publish = PublishBuilder::new("fyk");
+    token.enqueue_publish(crate_to_publish).good();
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("3/f/fyk");
+    assert!(crates.len() == 1);
+    assert!(!crates[0].yanked.unwrap());
+
+    // make sure it's not yanked
+    let json = anon.show_version("fyk", "1.0.0");
+    assert!(!json.version.yanked);
+
+    // yank it
+    token.yank("fyk", "1.0.0").good();
+
+    let crates = app.crates_from_index_head("3/f/fyk");
+    assert!(crates.len() == 1);
+    assert!(crates[0].yanked.unwrap());
+
+    let json = anon.show_version("fyk", "1.0.0");
+    assert!(json.version.yanked);
+
+    // un-yank it
+    token.unyank("fyk", "1.0.0").good();
+
+    let crates = app.crates_from_index_head("3/f/fyk");
+    assert!(crates.len() == 1);
+    assert!(!crates[0].yanked.unwrap());
+
+    let json = anon.show_version("fyk", "1.0.0");
+    assert!(!json.version.yanked);
+}
+
+#[test]
+fn yank_by_a_non_owner_fails() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let another_user = app.db_new_user("bar");
+    let another_user = another_user.as_model();
+    app.db(|conn| {
+        CrateBuilder::new("foo_not", another_user.id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+
+    let json = token.yank("foo_not", "1.0.0").bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "must already be an owner to yank or unyank"
+    );
+}
+
+#[test]
+fn delete_version_within_grace_period() {
+    let storage = TempDir::new("version-delete").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, anon, _, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_token();
+
+    token.enqueue_publish(PublishBuilder::new("fdel")).good();
+    token
+        .enqueue_publish(PublishBuilder::new("fdel").version("1.1.0"))
+        .good();
+    app.run_pending_background_jobs();
+    let crate_file = storage.path().join("crates/fdel/fdel-1.1.0.crate");
+    assert!(crate_file.exists());
+
+    token.delete::<OkBool>("/api/v1/crates/fdel/1.1.0").good();
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("fd/el/fdel");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].vers, "1.0.0");
+    assert!(!crate_file.exists());
+    let json = anon.show_crate("fdel");
+    assert_eq!(json.versions.len(), 1);
+    assert_eq!(json.krate.max_version, "1.0.0");
+    let deleted = app.db(|conn| {
+        deleted_versions::table
+            .select(deleted_versions::num)
+            .load::<String>(conn)
+            .unwrap()
+    });
+    assert_eq!(deleted, vec!["1.1.0"]);
+
+    let json = token
+        .enqueue_publish(PublishBuilder::new("fdel").version("1.1.0"))
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "crate version `1.1.0` was deleted and can't be published again"
+    );
+
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/fdel/1.0.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "the only version of a crate can't be deleted"
+    );
+}
+
+#[test]
+fn delete_version_requirements() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let user = user.as_model();
+    let another_user = app.db_new_user("bar");
+    let another_user = another_user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_del", user.id)
+            .version("1.0.0")
+            .version("1.1.0")
+            .version("1.2.0")
+            .expect_build(conn);
+        let dependency = CrateBuilder::new("foo_del_dep", user.id)
+            .version("1.0.0")
+            .version("1.2.0")
+            .expect_build(conn);
+        // Only a yanked version depends on it, which can still be locked to
+        CrateBuilder::new("bar_del", another_user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .dependency(&dependency, None)
+                    .yanked(true),
+            )
+            .version("1.1.0")
+            .expect_build(conn);
+        CrateBuilder::new("foo_del_other", another_user.id)
+            .version("1.0.0")
+            .version("1.2.0")
+            .expect_build(conn);
+
+        update(versions::table.filter(versions::num.eq("1.0.0")))
+            .set(versions::created_at.eq(now - 4.days()))
+            .execute(conn)
+            .unwrap();
+        update(versions::table.filter(versions::num.eq("1.1.0")))
+            .set(versions::downloads.eq(1000))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_del/1.0.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "versions can only be deleted within 72 hours of being published, \
+         yank the version instead"
+    );
+
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_del/1.1.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "versions with more than 500 downloads can't be deleted, yank the version instead"
+    );
+
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_del_dep/1.2.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "version `1.2.0` can't be deleted because other crates depend on it: bar_del"
+    );
+
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_del_other/1.2.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "must already be an owner to delete a version"
+    );
+}
+
+#[test]
+fn delete_crate_tombstones_the_name() {
+    let storage = TempDir::new("crate-delete").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, anon, _, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_token();
+
+    token.enqueue_publish(PublishBuilder::new("fdelc")).good();
+    app.run_pending_background_jobs();
+    let crate_file = storage.path().join("crates/fdelc/fdelc-1.0.0.crate");
+    assert!(crate_file.exists());
+
+    token.delete::<OkBool>("/api/v1/crates/fdelc").good();
+    app.run_pending_background_jobs();
+
+    anon.get::<()>("/api/v1/crates/fdelc").assert_not_found();
+    assert!(!crate_file.exists());
+
+    let json = token
+        .enqueue_publish(PublishBuilder::new("fdelc"))
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .starts_with("a crate with this name was deleted recently"));
+}
+
+#[test]
+fn deleted_names_can_be_published_again_after_the_cooldown() {
+    let storage = TempDir::new("crate-delete").unwrap();
+    let root = storage.path().to_path_buf();
+    let (app, _, _, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| {
+            config.uploader = Uploader::new(LocalStorage::new(root), None);
+            config.deleted_name_cooldown = Duration::from_secs(0);
+        })
+        .with_token();
+
+    token.enqueue_publish(PublishBuilder::new("fdelr")).good();
+    app.run_pending_background_jobs();
+    token.delete::<OkBool>("/api/v1/crates/fdelr").good();
+    app.run_pending_background_jobs();
 
-    user.get("/api/v1/crates/foo_following/following")
-        .assert_not_found();
+    token.enqueue_publish(PublishBuilder::new("fdelr")).good();
+    app.run_pending_background_jobs();
+    let crates = app.crates_from_index_head("fd/el/fdelr");
+    assert_eq!(crates.len(), 1);
 }
 
 #[test]
-fn following() {
-    // TODO: Test anon requests as well?
-    let (app, _, user) = TestApp::init().with_user();
-
+fn name_availability() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
     app.db(|conn| {
-        CrateBuilder::new("foo_following", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo_taken", user.id).expect_build(conn);
+        let krate = CrateBuilder::new("foo_deleted", user.id).expect_build(conn);
+        DeletedCrate::delete(conn, &krate, user.id, Duration::from_secs(3600)).unwrap();
     });
 
-    let is_following = || -> bool {
-        #[derive(Deserialize)]
-        struct F {
-            following: bool,
-        }
-
-        user.get::<F>("/api/v1/crates/foo_following/following")
+    let availability = |name: &str| -> Availability {
+        anon.get(&format!("/api/v1/crates/{}/availability", name))
             .good()
-            .following
     };
 
-    let follow = || {
-        assert!(
-            user.put::<OkBool>("/api/v1/crates/foo_following/follow", b"")
-                .good()
-                .ok
-        );
-    };
+    let json = availability("foo_free");
+    assert!(json.available);
+    assert_eq!(json.reason, None);
 
-    let unfollow = || {
-        assert!(
-            user.delete::<OkBool>("api/v1/crates/foo_following/follow")
-                .good()
-                .ok
-        );
-    };
+    let json = availability("1foo");
+    assert!(!json.available);
+    assert_eq!(json.reason.as_ref().unwrap(), "invalid");
+    assert_eq!(json.detail.unwrap(), "the name must start with a letter");
 
-    assert!(!is_following());
-    follow();
-    follow();
-    assert!(is_following());
-    assert_eq!(user.search("following=1").crates.len(), 1);
+    let json = availability("foo-taken");
+    assert!(!json.available);
+    assert_eq!(json.reason.unwrap(), "taken");
 
-    unfollow();
-    unfollow();
-    assert!(!is_following());
-    assert_eq!(user.search("following=1").crates.len(), 0);
+    let json = availability("STD");
+    assert!(!json.available);
+    assert_eq!(json.reason.unwrap(), "reserved");
+
+    let json = availability("foo-deleted");
+    assert!(!json.available);
+    assert_eq!(json.reason.unwrap(), "tombstoned");
+    assert!(json.available_at.is_some());
 }
 
 #[test]
-fn yank_works_as_intended() {
-    let (app, anon, _, token) = TestApp::full().with_token();
+fn delete_crate_requirements() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let user = user.as_model();
+    let another_user = app.db_new_user("bar");
+    let another_user = another_user.as_model();
 
-    // Upload a new crate, putting it in the git index
-    let crate_to_publish = PublishBuilder::new("fyk");
-    token.enqueue_publish(crate_to_publish).good();
-    app.run_pending_background_jobs();
+    app.db(|conn| {
+        let dependency = CrateBuilder::new("foo_delc_dep", user.id).expect_build(conn);
+        CrateBuilder::new("bar_delc", another_user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&dependency, None))
+            .expect_build(conn);
+        CrateBuilder::new("foo_delc_popular", user.id)
+            .downloads(1001)
+            .expect_build(conn);
+        CrateBuilder::new("foo_delc_other", another_user.id).expect_build(conn);
+    });
 
-    let crates = app.crates_from_index_head("3/f/fyk");
-    assert!(crates.len() == 1);
-    assert!(!crates[0].yanked.unwrap());
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_delc_dep")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "crates that other crates depend on can't be deleted, yank their versions instead"
+    );
 
-    // make sure it's not yanked
-    let json = anon.show_version("fyk", "1.0.0");
-    assert!(!json.version.yanked);
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_delc_popular")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "crates with more than 1000 downloads can't be deleted, yank their versions instead"
+    );
 
-    // yank it
-    token.yank("fyk", "1.0.0").good();
+    let json = token
+        .delete::<OkBool>("/api/v1/crates/foo_delc_other")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "must already be an owner to delete a crate"
+    );
+}
 
-    let crates = app.crates_from_index_head("3/f/fyk");
-    assert!(crates.len() == 1);
-    assert!(crates[0].yanked.unwrap());
+#[test]
+fn advisory_works_as_intended() {
+    let (app, anon, _, token) = TestApp::full().with_token();
 
-    let json = anon.show_version("fyk", "1.0.0");
-    assert!(json.version.yanked);
+    let crate_to_publish = PublishBuilder::new("fadv");
+    token.enqueue_publish(crate_to_publish).good();
+    app.run_pending_background_jobs();
 
-    // un-yank it
-    token.unyank("fyk", "1.0.0").good();
+    let crates = app.crates_from_index_head("fa/dv/fadv");
+    assert_eq!(crates[0].advisory, None);
 
-    let crates = app.crates_from_index_head("3/f/fyk");
-    assert!(crates.len() == 1);
+    token
+        .set_advisory("fadv", "1.0.0", "known broken on Windows")
+        .good();
+
+    let crates = app.crates_from_index_head("fa/dv/fadv");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(
+        crates[0].advisory.as_ref().map(|s| &**s),
+        Some("known broken on Windows")
+    );
     assert!(!crates[0].yanked.unwrap());
 
-    let json = anon.show_version("fyk", "1.0.0");
+    let json = anon.show_version("fadv", "1.0.0");
+    assert_eq!(
+        json.version.advisory.as_ref().map(|s| &**s),
+        Some("known broken on Windows")
+    );
     assert!(!json.version.yanked);
+
+    token.clear_advisory("fadv", "1.0.0").good();
+
+    let crates = app.crates_from_index_head("fa/dv/fadv");
+    assert_eq!(crates[0].advisory, None);
+    let json = anon.show_version("fadv", "1.0.0");
+    assert_eq!(json.version.advisory, None);
 }
 
 #[test]
-fn yank_by_a_non_owner_fails() {
+fn advisory_by_a_non_owner_fails() {
     let (app, _, _, token) = TestApp::full().with_token();
 
     let another_user = app.db_new_user("bar");
     let another_user = another_user.as_model();
     app.db(|conn| {
-        CrateBuilder::new("foo_not", another_user.id)
+        CrateBuilder::new("foo_advisory_not", another_user.id)
             .version("1.0.0")
             .expect_build(conn);
     });
 
-    let json = token.yank("foo_not", "1.0.0").bad_with_status(200);
+    let json = token
+        .set_advisory("foo_advisory_not", "1.0.0", "superseded by 2.x")
+        .bad_with_status(200);
     assert_eq!(
         json.errors[0].detail,
-        "must already be an owner to yank or unyank"
+        "must already be an owner to change advisories"
+    );
+}
+
+#[test]
+fn empty_advisory_is_rejected() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_advisory_empty", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+
+    let json = token
+        .set_advisory("foo_advisory_empty", "1.0.0", "  ")
+        .bad_with_status(200);
+    assert_eq!(json.errors[0].detail, "advisory message must not be empty");
+}
+
+#[test]
+fn publish_warns_about_dependencies_with_advisories() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo-advised", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+        update(versions::table)
+            .set(versions::advisory.eq("superseded by 2.x"))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let crate_to_publish = PublishBuilder::new("new_advised_dep")
+        .version("1.0.0")
+        .dependency(DependencyBuilder::new("foo-advised"));
+    let json = token.enqueue_publish(crate_to_publish).good();
+    assert_eq!(
+        json.warnings.other,
+        vec!["dependency `foo-advised` resolves to version 1.0.0, \
+             which has an advisory: superseded by 2.x"
+            .to_string()]
     );
 }
 
@@ -1589,6 +3188,35 @@ fn publish_after_yank_max_version() {
     assert_eq!(json.krate.max_version, "2.0.0");
 }
 
+#[test]
+fn max_stable_version_skips_pre_releases_and_yanked_versions() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    token.enqueue_publish(PublishBuilder::new("fstable")).good();
+    let json = token
+        .enqueue_publish(PublishBuilder::new("fstable").version("2.0.0-beta.1"))
+        .good();
+    assert_eq!(json.krate.max_version, "2.0.0-beta.1");
+    assert_eq!(json.krate.max_stable_version.unwrap(), "1.0.0");
+
+    token
+        .enqueue_publish(PublishBuilder::new("fstable").version("1.1.0"))
+        .good();
+    token.yank("fstable", "1.1.0").good();
+    let json = anon.show_crate("fstable");
+    assert_eq!(json.krate.max_version, "2.0.0-beta.1");
+    assert_eq!(json.krate.max_stable_version.unwrap(), "1.0.0");
+
+    token.yank("fstable", "1.0.0").good();
+    let json = anon.search("q=fstable");
+    assert_eq!(json.crates[0].max_version, "2.0.0-beta.1");
+    assert_eq!(json.crates[0].max_stable_version, None);
+
+    token.unyank("fstable", "1.1.0").good();
+    let json = anon.show_crate("fstable");
+    assert_eq!(json.krate.max_stable_version.unwrap(), "1.1.0");
+}
+
 #[test]
 fn publish_after_removing_documentation() {
     let (app, anon, user, token) = TestApp::full().with_token();
@@ -1766,6 +3394,96 @@ fn reverse_dependencies() {
     assert_eq!(deps.meta.total, 0);
 }
 
+#[test]
+fn reverse_dependencies_seek_pagination() {
+    #[derive(Deserialize)]
+    struct SeekRevDeps {
+        versions: Vec<EncodableVersion>,
+        meta: SeekMeta,
+    }
+    #[derive(Deserialize)]
+    struct SeekMeta {
+        total: i64,
+        next_page: Option<String>,
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id).expect_build(conn);
+        for name in &["c2", "c3", "c4"] {
+            CrateBuilder::new(name, user.id)
+                .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+                .expect_build(conn);
+        }
+    });
+
+    let url = "/api/v1/crates/c1/reverse_dependencies";
+    let page1: SeekRevDeps = anon.get_with_query(url, "seek=&per_page=2").good();
+    assert_eq!(page1.meta.total, 3);
+    let mut names = page1.versions.iter().map(|v| &*v.krate).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["c2", "c3"]);
+
+    let next_page = page1.meta.next_page.unwrap();
+    let page2: SeekRevDeps = anon.get_with_query(url, &next_page[1..]).good();
+    assert_eq!(page2.meta.total, 3);
+    assert_eq!(page2.versions.len(), 1);
+    assert_eq!(page2.versions[0].krate, "c4");
+    assert_eq!(page2.meta.next_page, None);
+}
+
+#[test]
+fn reverse_dependencies_matching_version() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id)
+            .version("1.0.0")
+            .version("2.0.0")
+            .expect_build(conn);
+        for (name, req) in &[("c2", "^1.0"), ("c3", "^2.0"), ("c4", ">= 0")] {
+            let dependent = CrateBuilder::new(name, user.id)
+                .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+                .expect_build(conn);
+            update(dependencies::table)
+                .filter(
+                    dependencies::version_id.eq_any(
+                        versions::table
+                            .select(versions::id)
+                            .filter(versions::crate_id.eq(dependent.id)),
+                    ),
+                )
+                .set(dependencies::req.eq(req))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let url = "/api/v1/crates/c1/reverse_dependencies";
+    let deps: RevDeps = anon.get_with_query(url, "matching=1.2.3").good();
+    assert_eq!(deps.meta.total, 2);
+    let mut names = deps.versions.iter().map(|v| &*v.krate).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["c2", "c4"]);
+
+    let deps: RevDeps = anon.get_with_query(url, "matching=2.0.0").good();
+    assert_eq!(deps.meta.total, 2);
+
+    let deps: RevDeps = anon.get_with_query(url, "matching=3.0.0").good();
+    assert_eq!(deps.meta.total, 1);
+    assert_eq!(deps.versions[0].krate, "c4");
+
+    let json = anon
+        .get_with_query::<()>(url, "matching=1.x")
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("invalid version `1.x` in `matching`"));
+}
+
 #[test]
 fn reverse_dependencies_when_old_version_doesnt_depend_but_new_does() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -2241,6 +3959,45 @@ fn publish_rate_limit_doesnt_affect_existing_crates() {
     app.run_pending_background_jobs();
 }
 
+#[test]
+fn searches_are_rate_limited() {
+    let (_, anon) = TestApp::init()
+        .with_rate_limit(LimitedAction::Search, Duration::from_secs(60), 1)
+        .empty();
+
+    let response = anon.get::<CrateList>("/api/v1/crates");
+    assert_eq!(response.header("X-RateLimit-Limit"), "1");
+    assert_eq!(response.header("X-RateLimit-Remaining"), "0");
+    response.good();
+
+    let mut response = anon.get::<CrateList>("/api/v1/crates");
+    let json = response.bad_with_status(429);
+    assert!(json.errors[0].detail.contains("searched too often"));
+    assert_eq!(json.errors[0].code, "rate_limited");
+    assert!(!response.header("Retry-After").is_empty());
+    assert!(!response.header("X-RateLimit-Reset").is_empty());
+}
+
+#[test]
+fn api_tokens_have_separate_rate_limits() {
+    let (app, _, user, token) = TestApp::init()
+        .with_rate_limit(LimitedAction::Mutation, Duration::from_secs(60), 1)
+        .with_token();
+    let other_token = user.db_new_token("other");
+    app.db(|conn| {
+        CrateBuilder::new("foo_rate_limited", user.as_model().id).expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/foo_rate_limited/follow";
+    token.put::<OkBool>(url, &[]).good();
+    token.put::<OkBool>(url, &[]).assert_status(429);
+    other_token.put::<OkBool>(url, &[]).good();
+    // Reads aren't limited
+    token
+        .get::<()>("/api/v1/crates/foo_rate_limited/following")
+        .assert_status(200);
+}
+
 #[test]
 fn pagination_links_included_if_applicable() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -2264,3 +4021,243 @@ fn pagination_links_included_if_applicable() {
     assert_eq!(None, page4.meta.next_page);
     assert_eq!(Some("?page=2&per_page=1".to_string()), page3.meta.prev_page);
 }
+
+#[test]
+fn owners_can_update_metadata_without_publishing() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        CrateBuilder::new("edit_me", user.as_model().id)
+            .description("A crate with a tpyo")
+            .documentation("https://example.com/docs")
+            .expect_build(conn);
+    });
+
+    let body = json!({
+        "crate": {
+            "description": "A crate without a typo",
+            "homepage": "https://example.com",
+            "documentation": null,
+            "keywords": ["fixed"],
+            "categories": ["cat1", "no-such-category"],
+        }
+    });
+    let json: GoodCrate = user
+        .patch("/api/v1/crates/edit_me", body.to_string().as_bytes())
+        .good();
+    assert_eq!(json.warnings.invalid_categories, ["no-such-category"]);
+
+    let json = anon.show_crate("edit_me");
+    assert_eq!(json.krate.description.unwrap(), "A crate without a typo");
+    assert_eq!(json.krate.homepage.unwrap(), "https://example.com");
+    assert_eq!(json.krate.documentation, None);
+    assert_eq!(json.krate.categories.unwrap(), ["cat1"]);
+    assert_eq!(json.keywords[0].keyword, "fixed");
+
+    let detail = app.db(|conn| {
+        crate_owner_actions::table
+            .select(crate_owner_actions::detail)
+            .first::<Option<String>>(conn)
+            .unwrap()
+    });
+    assert_eq!(
+        detail.unwrap(),
+        "description, homepage, documentation, keywords, categories"
+    );
+}
+
+#[test]
+fn metadata_updates_are_validated_like_publishing() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("edit_me", user.as_model().id).expect_build(conn));
+    let url = "/api/v1/crates/edit_me";
+    fn patch<U: RequestHelper>(user: &U, body: serde_json::Value) -> String {
+        let url = "/api/v1/crates/edit_me";
+        let mut json = user
+            .patch::<()>(url, body.to_string().as_bytes())
+            .bad_with_status(200);
+        json.errors.remove(0).detail
+    }
+
+    let detail = patch(
+        &user,
+        json!({ "crate": { "homepage": "ftp://example.com" } }),
+    );
+    assert!(detail.contains("must begin with http:// or https://"));
+    let detail = patch(&user, json!({ "crate": { "description": "" } }));
+    assert!(detail.contains("can't be empty"));
+    let keywords = ["a", "b", "c", "d", "e", "f"];
+    let detail = patch(&user, json!({ "crate": { "keywords": keywords } }));
+    assert!(detail.contains("at most 5 keywords per crate"));
+    let detail = patch(&user, json!({ "crate": {} }));
+    assert_eq!(detail, "no metadata to update");
+
+    let other = app.db_new_user("other");
+    let detail = patch(&other, json!({ "crate": { "description": "Mine now" } }));
+    assert_eq!(detail, "only owners can update the metadata of a crate");
+
+    let body = json!({ "crate": { "description": "Mine now" } });
+    anon.patch::<()>(url, body.to_string().as_bytes())
+        .assert_forbidden();
+
+    let actions = app.db(|conn| {
+        crate_owner_actions::table
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(actions, 0);
+}
+
+#[test]
+fn owners_can_set_project_links() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("linked", user.as_model().id).expect_build(conn));
+    let url = "/api/v1/crates/linked";
+
+    let json = anon.show_crate("linked");
+    assert_eq!(json.krate.project_links, CrateLinks::default());
+
+    let body = json!({
+        "crate": {
+            "links": {
+                "ci": "https://ci.example.com/linked",
+                "changelog": "https://example.com/CHANGELOG.md",
+            }
+        }
+    });
+    let json: GoodCrate = user.patch(url, body.to_string().as_bytes()).good();
+    assert_eq!(
+        json.krate.project_links.ci.unwrap(),
+        "https://ci.example.com/linked"
+    );
+
+    let links = anon.show_crate("linked").krate.project_links;
+    assert_eq!(links.changelog.unwrap(), "https://example.com/CHANGELOG.md");
+    assert_eq!(links.chat, None);
+
+    let body = json!({ "crate": { "links": { "sponsor": "javascript:alert(1)" } } });
+    let json = user
+        .patch::<()>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("must begin with http:// or https://"));
+
+    let body = json!({ "crate": { "links": { "twitter": "https://example.com" } } });
+    let json = user
+        .patch::<()>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("unknown field `twitter`"));
+
+    // The links are replaced as a whole
+    let body = json!({ "crate": { "links": {} } });
+    let json: GoodCrate = user.patch(url, body.to_string().as_bytes()).good();
+    assert_eq!(json.krate.project_links, CrateLinks::default());
+}
+
+#[test]
+fn funding_is_set_when_publishing_and_through_the_api() {
+    let storage = TempDir::new("crate-funding").unwrap();
+    let root = storage.path().to_path_buf();
+    let (_, anon, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.uploader = Uploader::new(LocalStorage::new(root), None))
+        .with_user();
+    let url = "/api/v1/crates/funded";
+
+    let crate_to_publish = PublishBuilder::new("funded").funding(&[
+        ("github", "https://github.com/sponsors/foo"),
+        ("liberapay", "https://liberapay.com/foo"),
+    ]);
+    user.enqueue_publish(crate_to_publish).good();
+    let funding = anon.show_crate("funded").funding;
+    assert_eq!(funding.len(), 2);
+    assert_eq!(funding[0].platform, "github");
+    assert_eq!(funding[0].url, "https://github.com/sponsors/foo");
+    assert_eq!(funding[1].platform, "liberapay");
+
+    // Versions published without a `[funding]` section keep the funding
+    let crate_to_publish = PublishBuilder::new("funded").version("1.1.0");
+    user.enqueue_publish(crate_to_publish).good();
+    assert_eq!(anon.show_crate("funded").funding.len(), 2);
+
+    let body = json!({ "crate": { "funding": { "custom": "https://example.com/donate" } } });
+    user.patch::<GoodCrate>(url, body.to_string().as_bytes())
+        .good();
+    let funding = anon.show_crate("funded").funding;
+    assert_eq!(
+        funding,
+        [EncodableCrateFunding {
+            platform: "custom".into(),
+            url: "https://example.com/donate".into(),
+        }]
+    );
+
+    let body = json!({ "crate": { "funding": { "paypal": "https://example.com" } } });
+    let json = user
+        .patch::<()>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("unknown funding platform `paypal`"));
+
+    let crate_to_publish = PublishBuilder::new("funded")
+        .version("1.2.0")
+        .funding(&[("patreon", "patreon.com/foo")]);
+    let json = user.enqueue_publish(crate_to_publish).bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("must begin with http:// or https://"));
+    assert_eq!(anon.show_crate("funded").funding.len(), 1);
+}
+
+#[test]
+fn repository_verification_is_reset_when_the_repository_changes() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let owner_id = user.as_model().id;
+    app.db(|conn| {
+        CrateBuilder::new("verified_repo", owner_id)
+            .repository("https://github.com/foo/verified_repo")
+            .expect_build(conn);
+        CrateBuilder::new("gitlab_repo", owner_id)
+            .repository("https://gitlab.com/foo/gitlab_repo")
+            .expect_build(conn);
+        CrateBuilder::new("no_repo", owner_id).expect_build(conn);
+    });
+    assert!(!anon.show_crate("verified_repo").krate.repository_verified);
+
+    let verify = |name: &str| format!("/api/v1/crates/{}/repository_verification", name);
+    let json = user
+        .put::<()>(&verify("gitlab_repo"), b"")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only repositories on GitHub"));
+    let json = user.put::<()>(&verify("no_repo"), b"").bad_with_status(200);
+    assert!(json.errors[0].detail.contains("has no repository"));
+    let other = app.db_new_user("other");
+    let json = other
+        .put::<()>(&verify("verified_repo"), b"")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("only owners"));
+
+    // Verifying asks GitHub, so the flag is set directly here
+    app.db(|conn| {
+        update(crates::table.filter(crates::name.eq("verified_repo")))
+            .set(crates::repository_verified.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+    let url = "/api/v1/crates/verified_repo";
+    let body = json!({ "crate": { "description": "still verified" } });
+    let json: GoodCrate = user.patch(url, body.to_string().as_bytes()).good();
+    assert!(json.krate.repository_verified);
+
+    let body = json!({ "crate": { "repository": "https://github.com/spoof/verified_repo" } });
+    let json: GoodCrate = user.patch(url, body.to_string().as_bytes()).good();
+    assert!(!json.krate.repository_verified);
+}
@@ -0,0 +1,123 @@
+use crate::{
+    builders::PublishBuilder,
+    new_team,
+    util::{MockAnonymousUser, MockCookieUser, RequestHelper, Response},
+    OwnerTeamsResponse, TestApp,
+};
+use cargo_registry::{
+    models::{Team, TeamMembership},
+    storage::LocalStorage,
+    uploaders::Uploader,
+    views::EncodableNamespace,
+};
+use std::time::Duration;
+use tempdir::TempDir;
+
+#[derive(Deserialize)]
+struct NamespaceResponse {
+    namespace: EncodableNamespace,
+}
+
+impl MockCookieUser {
+    fn create_namespace(&self, name: &str, team: &str) -> Response<NamespaceResponse> {
+        let url = format!("/api/v1/namespaces/{}", name);
+        let body = json!({ "team": team });
+        self.put(&url, body.to_string().as_bytes())
+    }
+}
+
+/// Records whether the user is a member of the team, so that GitHub isn't
+/// asked during the test.
+fn set_membership(app: &TestApp, team: &Team, user: &MockCookieUser, is_member: bool) {
+    app.db(|conn| {
+        let ttl = Duration::from_secs(60 * 60);
+        TeamMembership::record(conn, team.id, user.as_model().id, is_member, ttl).unwrap();
+    });
+}
+
+fn app_with_namespaces(storage: &TempDir) -> (TestApp, MockAnonymousUser, MockCookieUser) {
+    let root = storage.path().to_path_buf();
+    TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| {
+            config.uploader = Uploader::new(LocalStorage::new(root), None);
+            config.crate_namespaces = true;
+        })
+        .with_user()
+}
+
+#[test]
+fn members_of_the_team_publish_in_the_namespace() {
+    let storage = TempDir::new("namespaces").unwrap();
+    let (app, anon, user) = app_with_namespaces(&storage);
+    let outsider = app.db_new_user("outsider");
+    let team = app.db(|conn| {
+        new_team("github:test-org:core")
+            .create_or_update(conn)
+            .unwrap()
+    });
+    set_membership(&app, &team, &user, true);
+    set_membership(&app, &team, &outsider, false);
+
+    let json = outsider
+        .create_namespace("test-org", "github:test-org:core")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only members of a team can create a namespace for it"
+    );
+    let json = user
+        .create_namespace("test-org", "github:test-org:core")
+        .good();
+    assert_eq!(json.namespace.team, "github:test-org:core");
+    let json = user
+        .create_namespace("Test_Org", "github:test-org:core")
+        .bad_with_status(200);
+    assert_eq!(json.errors[0].detail, "namespace `Test_Org` already exists");
+
+    user.enqueue_publish(PublishBuilder::new("test-org::foo"))
+        .good();
+    app.run_pending_background_jobs();
+    let crates = app.crates_from_index_head("@test-org/3/f/foo");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].name, "test-org::foo");
+
+    let json: OwnerTeamsResponse = anon.get("/api/v1/crates/test-org::foo/owner_team").good();
+    assert_eq!(json.teams.len(), 1);
+    assert_eq!(json.teams[0].login, "github:test-org:core");
+    let json: NamespaceResponse = anon.get("/api/v1/namespaces/test-org").good();
+    assert_eq!(json.namespace.crates, 1);
+
+    let json = outsider
+        .enqueue_publish(PublishBuilder::new("test-org::bar"))
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only members of github:test-org:core can publish new crates in namespace `test-org`"
+    );
+    let json = user
+        .enqueue_publish(PublishBuilder::new("other-org::bar"))
+        .bad_with_status(200);
+    assert_eq!(json.errors[0].detail, "namespace `other-org` doesn't exist");
+}
+
+#[test]
+fn namespaces_are_disabled_by_default() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json = user
+        .create_namespace("test-org", "github:test-org:core")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "namespaces aren't enabled on this registry"
+    );
+    let json = user
+        .enqueue_publish(PublishBuilder::new("test-org::foo"))
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "namespaced crate names aren't enabled on this registry"
+    );
+}
@@ -2,15 +2,22 @@ use crate::{
     add_team_to_crate,
     builders::{CrateBuilder, PublishBuilder},
     new_team,
-    util::{MockCookieUser, MockTokenUser, RequestHelper},
-    TestApp,
+    util::{MockCookieUser, MockTokenUser, RequestHelper, Response},
+    OkBool, TestApp,
 };
 use cargo_registry::{
-    models::Crate,
-    views::{EncodableCrateOwnerInvitation, EncodableOwner, InvitationResponse},
+    models::{Crate, NewUser},
+    schema::{crate_successors, sessions},
+    tasks,
+    views::{
+        EncodableCrateOwnerInvitation, EncodableCrateSuccessor, EncodableOwner,
+        EncodablePendingOwnerRemoval, InvitationResponse,
+    },
 };
 
 use diesel::prelude::*;
+use std::time::Duration;
+use swirl::Job;
 
 #[derive(Deserialize)]
 struct TeamResponse {
@@ -24,6 +31,22 @@ struct UserResponse {
 struct InvitationListResponse {
     crate_owner_invitations: Vec<EncodableCrateOwnerInvitation>,
 }
+#[derive(Deserialize)]
+struct OwnerRemovalsResponse {
+    owner_removals: Vec<EncodablePendingOwnerRemoval>,
+}
+#[derive(Deserialize)]
+struct SuccessorResponse {
+    successor: Option<EncodableCrateSuccessor>,
+}
+#[derive(Deserialize)]
+struct SuccessionsResponse {
+    crate_successions: Vec<EncodableCrateSuccessor>,
+}
+#[derive(Deserialize)]
+struct ModifyOwnersResponse {
+    msg: String,
+}
 
 // Implementing locally for now, unless these are needed elsewhere
 impl MockCookieUser {
@@ -81,6 +104,31 @@ impl MockCookieUser {
     fn list_invitations(&self) -> InvitationListResponse {
         self.get("/api/v1/me/crate_owner_invitations").good()
     }
+
+    /// As the currently logged in user, list the pending owner removals of the named crate.
+    fn list_owner_removals(&self, krate_name: &str) -> Vec<EncodablePendingOwnerRemoval> {
+        let url = format!("/api/v1/crates/{}/owner_removals", krate_name);
+        let json: OwnerRemovalsResponse = self.get(&url).good();
+        json.owner_removals
+    }
+
+    /// As the currently logged in user, confirm a pending owner removal.
+    fn confirm_owner_removal(&self, krate_name: &str, removal_id: i32) -> Response<OkBool> {
+        let url = format!(
+            "/api/v1/crates/{}/owner_removals/{}/confirm",
+            krate_name, removal_id
+        );
+        self.put(&url, &[])
+    }
+
+    /// As the currently logged in user, cancel a pending owner removal.
+    fn cancel_owner_removal(&self, krate_name: &str, removal_id: i32) -> Response<OkBool> {
+        let url = format!(
+            "/api/v1/crates/{}/owner_removals/{}",
+            krate_name, removal_id
+        );
+        self.delete(&url)
+    }
 }
 
 #[test]
@@ -123,6 +171,27 @@ fn create_and_add_owner(
     user
 }
 
+#[test]
+fn users_of_other_identity_providers_can_be_added_as_owners() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("gitlab_owned", user.as_model().id).expect_build(conn));
+    app.db(|conn| {
+        NewUser::new(-1, "gitlab:ferris", None, None, None, "token")
+            .create_or_update_for_provider(conn, "gitlab", "42")
+            .unwrap()
+    });
+
+    // A qualified login isn't mistaken for a GitHub team
+    token.add_named_owner(&krate.name, "gitlab:ferris").good();
+    let json = token
+        .add_named_owner(&krate.name, "gitlab:someone_else")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("could not find user with login `gitlab:someone_else`"));
+}
+
 // Ensures that so long as at least one owner remains associated with the crate,
 // a user can still remove their own login as an owner
 #[test]
@@ -156,6 +225,7 @@ fn owners_can_remove_self() {
     assert!(json.errors[0]
         .detail
         .contains("only owners have permission to modify owners",));
+    assert_eq!(json.errors[0].code, "owner_mismatch");
 }
 
 // Verify consistency when adidng or removing multiple owners in a single request.
@@ -179,11 +249,28 @@ fn modify_multiple_owners() {
         .contains("cannot remove all individual owners of a crate"));
     assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 3);
 
-    // Deleting two owners at once is allowed.
+    // Deleting two owners at once is allowed, once the removals are confirmed.
     let json = token
         .remove_named_owners("owners_multiple", &["user2", "user3"])
         .good();
     assert!(json.ok);
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 3);
+    let removals = user2.list_owner_removals("owners_multiple");
+    assert_eq!(removals.len(), 2);
+    assert_eq!(removals[1].owner, "user3");
+    assert!(
+        user2
+            .confirm_owner_removal("owners_multiple", removals[1].id)
+            .good()
+            .ok
+    );
+    assert_eq!(removals[0].owner, "user2");
+    assert!(
+        user2
+            .confirm_owner_removal("owners_multiple", removals[0].id)
+            .good()
+            .ok
+    );
     assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 1);
 
     // Adding multiple users fails if one of them already is an owner.
@@ -203,6 +290,222 @@ fn modify_multiple_owners() {
     assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 3);
 }
 
+#[test]
+fn removing_owners_needs_confirmation_or_a_delay() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("owners_pending", user.as_model().id).expect_build(conn));
+    let user2 = create_and_add_owner(&app, &token, "user2", &krate);
+    let url = "/api/v1/crates/owners_pending/owners";
+    let body = json!({ "owners": ["user2"] }).to_string();
+
+    // With only two owners, the removal takes effect after a delay.
+    let json: ModifyOwnersResponse = token.delete_with_body(url, body.as_bytes()).good();
+    assert!(json.msg.starts_with("user2 will be removed as an owner on"));
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 2);
+
+    let json = token
+        .remove_named_owner("owners_pending", "user2")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "the removal of `user2` is already pending"
+    );
+
+    let removals = user.list_owner_removals("owners_pending");
+    assert_eq!(removals.len(), 1);
+    assert_eq!(removals[0].owner, "user2");
+    assert_eq!(removals[0].requested_by, "foo");
+    assert!(removals[0].effective_at.is_some());
+
+    // The owner who requested the removal can't confirm it.
+    let json = user
+        .confirm_owner_removal("owners_pending", removals[0].id)
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "removals have to be confirmed by another owner"
+    );
+
+    // Any owner can cancel it.
+    assert!(
+        user2
+            .cancel_owner_removal("owners_pending", removals[0].id)
+            .good()
+            .ok
+    );
+    assert!(user.list_owner_removals("owners_pending").is_empty());
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 2);
+
+    // With more owners, another owner has to confirm the removal instead.
+    let user3 = create_and_add_owner(&app, &token, "user3", &krate);
+    let json: ModifyOwnersResponse = token.delete_with_body(url, body.as_bytes()).good();
+    assert_eq!(
+        json.msg,
+        "the removal of user2 has to be confirmed by another owner"
+    );
+    let removals = user.list_owner_removals("owners_pending");
+    assert!(removals[0].effective_at.is_none());
+    assert!(
+        user3
+            .confirm_owner_removal("owners_pending", removals[0].id)
+            .good()
+            .ok
+    );
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 2);
+
+    // Only owners can see the removals.
+    let url = "/api/v1/crates/owners_pending/owner_removals";
+    let json = user2.get::<()>(url).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only owners can see pending owner removals"
+    );
+}
+
+#[test]
+fn delayed_owner_removals_are_applied_by_a_job() {
+    let (app, _, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_config(|config| config.owner_removal_delay = Duration::from_secs(0))
+        .with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("owners_delayed", user.as_model().id).expect_build(conn));
+    create_and_add_owner(&app, &token, "user2", &krate);
+
+    assert!(
+        token
+            .remove_named_owner("owners_delayed", "user2")
+            .good()
+            .ok
+    );
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 2);
+
+    app.db(|conn| tasks::apply_owner_removals().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+    assert_eq!(app.db(|conn| krate.owners(&conn).unwrap()).len(), 1);
+    assert!(user.list_owner_removals("owners_delayed").is_empty());
+}
+
+#[test]
+fn successors_take_over_crates_of_inactive_owners() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    let krate =
+        app.db(|conn| CrateBuilder::new("succession", user.as_model().id).expect_build(conn));
+    let successor = app.db_new_user("successor");
+
+    let url = "/api/v1/crates/succession/successor";
+    let body = json!({ "successor": { "login": "successor", "inactive_months": 3 } });
+    let json = user
+        .put::<()>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("between 6 and 60 months"));
+
+    let body = json!({ "successor": { "login": "successor", "inactive_months": 6 } });
+    let json: SuccessorResponse = user.put(url, body.to_string().as_bytes()).good();
+    let designated = json.successor.unwrap();
+    assert_eq!(designated.successor, "successor");
+    assert!(!designated.accepted);
+
+    // Only owners can see the successor, and only the successor can accept.
+    let json = successor.get::<()>(url).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only owners can see the successor of a crate"
+    );
+    let accept_url = format!("/api/v1/me/crate_successions/{}", krate.id);
+    let json = user
+        .put::<()>(&accept_url, br#"{"accepted":true}"#)
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("weren't designated"));
+
+    let json: SuccessionsResponse = successor.get("/api/v1/me/crate_successions").good();
+    assert_eq!(json.crate_successions.len(), 1);
+    assert_eq!(json.crate_successions[0].crate_name, "succession");
+    assert!(
+        successor
+            .put::<OkBool>(&accept_url, br#"{"accepted":true}"#)
+            .good()
+            .ok
+    );
+    let json: SuccessorResponse = user.get(url).good();
+    assert!(json.successor.unwrap().accepted);
+
+    let run_job = || {
+        app.db(|conn| tasks::transfer_inactive_crates().enqueue(conn).unwrap());
+        app.run_pending_background_jobs();
+    };
+    let owners = || {
+        app.db(|conn| krate.owners(&conn).unwrap())
+            .iter()
+            .map(|owner| owner.login().to_string())
+            .collect::<Vec<_>>()
+    };
+    let days_ago = |days| chrono::Utc::now().naive_utc() - chrono::Duration::days(days);
+
+    // The owner is active, so nothing happens.
+    run_job();
+    assert_eq!(owners(), ["foo"]);
+
+    // Once the owner is inactive for long enough, they are warned first.
+    app.db(|conn| {
+        diesel::update(crate_successors::table)
+            .set(crate_successors::created_at.eq(days_ago(200)))
+            .execute(conn)
+            .unwrap();
+        diesel::update(sessions::table)
+            .set(sessions::last_used_at.eq(days_ago(200)))
+            .execute(conn)
+            .unwrap();
+    });
+    run_job();
+    assert_eq!(owners(), ["foo"]);
+    let warned_at = app.db(|conn| {
+        crate_successors::table
+            .select(crate_successors::warned_at)
+            .first::<Option<chrono::NaiveDateTime>>(conn)
+            .unwrap()
+    });
+    assert!(warned_at.is_some());
+
+    // The crate is transferred once the warning is old enough.
+    app.db(|conn| {
+        diesel::update(crate_successors::table)
+            .set(crate_successors::warned_at.eq(Some(days_ago(31))))
+            .execute(conn)
+            .unwrap();
+    });
+    run_job();
+    assert_eq!(owners(), ["successor"]);
+    let remaining = app.db(|conn| {
+        crate_successors::table
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 0);
+}
+
+#[test]
+fn owners_cannot_be_successors() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("succession", user.as_model().id).expect_build(conn));
+
+    let url = "/api/v1/crates/succession/successor";
+    let body = json!({ "successor": { "login": "foo", "inactive_months": 12 } });
+    let json = user
+        .put::<()>(url, body.to_string().as_bytes())
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("is already an owner"));
+
+    let json = user.delete::<()>(url).bad_with_status(200);
+    assert_eq!(json.errors[0].detail, "the crate has no successor");
+}
+
 /*  Testing the crate ownership between two crates and one team.
     Given two crates, one crate owned by both a team and a user,
     one only owned by a user, check that the CrateList returned
@@ -2,6 +2,7 @@ use conduit::Method;
 
 use crate::builders::*;
 use crate::util::*;
+use crate::OkBool;
 
 #[test]
 fn user_agent_is_required() {
@@ -71,3 +72,244 @@ fn block_traffic_via_arbitrary_header_and_value() {
     let resp = anon.run::<()>(req);
     resp.assert_status(302);
 }
+
+#[test]
+fn registry_identity_is_configurable() {
+    let (_app, anon) = TestApp::init()
+        .with_config(|config| {
+            config.branding.display_name = "Acme Crates".into();
+            config.branding.support_email = "crates@acme.example".into();
+            config.branding.custom_categories = true;
+        })
+        .empty();
+
+    let json: serde_json::Value = anon.get("/api/v1/registry").good();
+    let registry = &json["registry"];
+    assert_eq!(registry["display_name"], "Acme Crates");
+    assert_eq!(registry["support_email"], "crates@acme.example");
+    assert_eq!(registry["url"], "https://crates.io");
+    assert_eq!(
+        registry["issue_tracker_url"],
+        "https://github.com/rust-lang/crates.io"
+    );
+    assert_eq!(registry["custom_categories"], true);
+}
+
+#[test]
+fn metrics_are_exported_with_the_token() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_metrics", user.as_model().id).expect_build(conn);
+    });
+    anon.get::<()>("/api/v1/crates/foo_metrics/0.99.0/download")
+        .assert_status(302);
+
+    anon.get::<()>("/metrics").assert_forbidden();
+    let mut req = anon.request_builder(Method::Get, "/metrics");
+    req.header("Authorization", "Bearer metrics token");
+    let metrics = anon.run::<()>(req).text();
+    for line in &[
+        "cargo_registry_http_requests_total{method=\"GET\",\
+         route=\"/crates/:crate_id/:version/download\",status=\"302\"} 1",
+        "cargo_registry_http_requests_total{method=\"GET\",route=\"/metrics\",status=\"403\"} 1",
+        "cargo_registry_crate_downloads_total 1",
+    ] {
+        assert!(metrics.lines().any(|l| l == *line), "{}", metrics);
+    }
+
+    let (_, anon) = TestApp::init()
+        .with_config(|config| config.metrics_authorization_token = None)
+        .empty();
+    anon.get::<()>("/metrics").assert_not_found();
+}
+
+#[test]
+fn requests_fail_fast_once_their_database_budget_is_used_up() {
+    let (_app, anon) = TestApp::init()
+        .with_config(|config| config.db_request_budget = Some(std::time::Duration::from_secs(0)))
+        .empty();
+
+    let mut response = anon.get::<()>("/api/v1/crates");
+    let json = response.bad_with_status(503);
+    assert_eq!(
+        json.errors[0].detail,
+        "The service is overloaded. Please try again later."
+    );
+    assert_eq!(response.header("Retry-After"), "5");
+}
+
+#[test]
+fn request_ids_are_returned_and_passed_on_to_background_jobs() {
+    use cargo_registry::schema::background_jobs;
+    use diesel::prelude::*;
+
+    let (app, anon, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_request_id", user.as_model().id).expect_build(conn);
+    });
+
+    let resp = anon.get::<()>("/api/v1/crates/foo_request_id");
+    assert_eq!(resp.header("X-Request-Id").len(), 32);
+
+    let mut req = anon.request_builder(Method::Get, "/api/v1/crates/foo_request_id");
+    req.header("X-Request-Id", "invalid \"id\"");
+    let resp = anon.run::<()>(req);
+    assert_ne!(resp.header("X-Request-Id"), "invalid \"id\"");
+
+    let mut req = user.request_builder(Method::Delete, "/api/v1/crates/foo_request_id/0.99.0/yank");
+    req.header("X-Request-Id", "yank-request-id");
+    let resp = user.run::<OkBool>(req);
+    assert_eq!(resp.header("X-Request-Id"), "yank-request-id");
+
+    let trace_ids = app.db(|conn| {
+        background_jobs::table
+            .select(background_jobs::trace_id)
+            .load::<Option<String>>(conn)
+            .unwrap()
+    });
+    assert_eq!(trace_ids, vec![Some("yank-request-id".to_string())]);
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn readiness_reports_the_index_and_the_background_queue() {
+    use cargo_registry::schema::background_jobs;
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+
+    let (app, anon) = TestApp::init().empty();
+
+    let json: serde_json::Value = anon.get("/healthz").good();
+    assert_eq!(json["status"], "ok");
+
+    let json: serde_json::Value = anon.get("/readyz").good();
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["checks"]["database"]["status"], "ok");
+    assert_eq!(json["checks"]["storage"]["status"], "skipped");
+    assert_eq!(json["checks"]["index"]["status"], "ok");
+    assert_eq!(
+        json["checks"]["background_queue"]["details"]["lag_seconds"],
+        0
+    );
+
+    app.db(|conn| {
+        diesel::insert_into(background_jobs::table)
+            .values((
+                background_jobs::job_type.eq("add_crate"),
+                background_jobs::data.eq(json!({})),
+                background_jobs::retries.eq(1),
+            ))
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(background_jobs::table)
+            .values((
+                background_jobs::job_type.eq("update_downloads"),
+                background_jobs::data.eq(json!({})),
+                background_jobs::created_at.eq(Utc::now().naive_utc() - Duration::hours(1)),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    // The background worker is shared by all servers, so it doesn't make
+    // this one unready
+    let json: serde_json::Value = anon.get("/readyz").good();
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["checks"]["database"]["status"], "ok");
+    assert_eq!(json["checks"]["index"]["status"], "error");
+    assert_eq!(
+        json["checks"]["index"]["error"],
+        "index updates are failing, failed jobs: 1"
+    );
+    assert_eq!(json["checks"]["background_queue"]["status"], "error");
+
+    app.db(|conn| {
+        diesel::delete(background_jobs::table)
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+#[test]
+fn openapi_document_is_served() {
+    let (_app, anon) = TestApp::init().empty();
+
+    let json: serde_json::Value = anon.get("/api/openapi.json").good();
+    assert_eq!(json["openapi"], "3.0.3");
+    let operation = &json["paths"]["/crates/{crate_id}/{version}/download"]["get"];
+    assert_eq!(operation["summary"], "Download a version");
+    assert_eq!(operation["parameters"][1]["name"], "version");
+    let codes = &json["components"]["schemas"]["Errors"]["properties"]["errors"]["items"]
+        ["properties"]["code"]["enum"];
+    assert!(codes.as_array().unwrap().contains(&"owner_mismatch".into()));
+}
+
+#[test]
+fn cors_headers_follow_the_policy_of_the_route() {
+    use cargo_registry::CorsPolicy;
+
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| {
+            config.cors.default = CorsPolicy {
+                allowed_origins: vec!["*".into()],
+                ..CorsPolicy::default()
+            };
+            config.cors.routes = vec![(
+                "/api/v1/me".into(),
+                CorsPolicy {
+                    allowed_origins: vec!["https://app.example".into()],
+                    allowed_methods: vec!["GET".into(), "PUT".into()],
+                    allow_credentials: true,
+                },
+            )];
+        })
+        .with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_cors", user.as_model().id).expect_build(conn);
+    });
+
+    let mut req = anon.request_builder(Method::Get, "/api/v1/crates/foo_cors");
+    req.header("Origin", "https://other.example");
+    let resp = anon.run::<()>(req);
+    resp.assert_status(200);
+    assert_eq!(resp.header("Access-Control-Allow-Origin"), "*");
+    assert!(resp
+        .header("Access-Control-Expose-Headers")
+        .contains("X-Request-Id"));
+
+    let mut req = anon.request_builder(Method::Options, "/api/v1/me/email_notifications");
+    req.header("Origin", "https://app.example");
+    req.header("Access-Control-Request-Method", "PUT");
+    let resp = anon.run::<()>(req);
+    resp.assert_status(204);
+    assert_eq!(
+        resp.header("Access-Control-Allow-Origin"),
+        "https://app.example"
+    );
+    assert_eq!(resp.header("Access-Control-Allow-Methods"), "GET, PUT");
+    assert_eq!(resp.header("Access-Control-Allow-Credentials"), "true");
+    assert_eq!(resp.header("Vary"), "Origin");
+
+    // Other origins and methods aren't allowed, so preflights go unanswered
+    let mut req = anon.request_builder(Method::Options, "/api/v1/me/email_notifications");
+    req.header("Origin", "https://other.example");
+    req.header("Access-Control-Request-Method", "PUT");
+    anon.run::<()>(req).assert_not_found();
+    let mut req = anon.request_builder(Method::Options, "/api/v1/crates/foo_cors");
+    req.header("Origin", "https://other.example");
+    req.header("Access-Control-Request-Method", "DELETE");
+    anon.run::<()>(req).assert_not_found();
+}
+
+#[test]
+fn cors_headers_are_not_sent_by_default() {
+    let (_app, anon) = TestApp::init().empty();
+
+    let mut req = anon.request_builder(Method::Get, "/api/v1/crates");
+    req.header("Origin", "https://other.example");
+    let resp = anon.run::<()>(req);
+    resp.assert_status(200);
+    assert!(resp.maybe_header("Access-Control-Allow-Origin").is_none());
+}
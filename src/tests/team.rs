@@ -5,8 +5,12 @@ use crate::{
     record::GhUser,
     OwnerTeamsResponse, RequestHelper, TestApp,
 };
-use cargo_registry::models::{Crate, NewUser};
-use std::sync::Once;
+use cargo_registry::{
+    models::{Crate, NewUser, TeamMembership},
+    schema::team_memberships,
+    views::EncodableDashboardCrate,
+};
+use std::{sync::Once, time::Duration};
 
 use diesel::*;
 
@@ -379,3 +383,119 @@ fn crates_by_team_id_not_including_deleted_owners() {
     let json = anon.search(&format!("team_id={}", team.id));
     assert_eq!(json.crates.len(), 0);
 }
+
+#[test]
+fn cached_team_memberships_are_used() {
+    // Without the proxy, checking a membership with GitHub would panic
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let member = app.db_new_user("member");
+    let non_member = app.db_new_user("non_member");
+
+    app.db(|conn| {
+        let t = new_team("github:crates-test-org:cached")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_cached_team", user.id).expect_build(conn);
+        add_team_to_crate(&t, &krate, user, conn).unwrap();
+        let ttl = Duration::from_secs(60 * 60);
+        TeamMembership::record(conn, t.id, member.as_model().id, true, ttl).unwrap();
+        TeamMembership::record(conn, t.id, non_member.as_model().id, false, ttl).unwrap();
+    });
+
+    let url = "/api/v1/crates/foo_cached_team/access";
+    member.get::<()>(url).assert_status(200);
+    let json = non_member.get::<()>(url).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only owners can view who has access to a crate"
+    );
+}
+
+#[test]
+fn members_can_resync_a_team() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let member = app.db_new_user("member");
+
+    let team = app.db(|conn| {
+        let t = new_team("github:crates-test-org:resync")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_resync_team", user.id).expect_build(conn);
+        add_team_to_crate(&t, &krate, user, conn).unwrap();
+        let ttl = Duration::from_secs(60 * 60);
+        TeamMembership::record(conn, t.id, member.as_model().id, true, ttl).unwrap();
+        t
+    });
+
+    let url = "/api/v1/teams/github:crates-test-org:resync/resync";
+    anon.post::<()>(url, &[]).assert_forbidden();
+
+    member.post::<()>(url, &[]).assert_status(200);
+    let cached = app.db(|conn| {
+        team_memberships::table
+            .filter(team_memberships::team_id.eq(team.id))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(cached, 0);
+}
+
+#[test]
+fn members_can_list_the_crates_of_a_team() {
+    #[derive(Deserialize)]
+    struct TeamCrates {
+        crates: Vec<EncodableDashboardCrate>,
+        meta: TeamCratesMeta,
+    }
+    #[derive(Deserialize)]
+    struct TeamCratesMeta {
+        downloads: i64,
+        recent_downloads: i64,
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let member = app.db_new_user("member");
+    let non_member = app.db_new_user("non_member");
+
+    app.db(|conn| {
+        let t = new_team("github:crates-test-org:dashboard")
+            .create_or_update(conn)
+            .unwrap();
+        let owned = CrateBuilder::new("foo_team_owned", user.id)
+            .downloads(10)
+            .recent_downloads(3)
+            .expect_build(conn);
+        add_team_to_crate(&t, &owned, user, conn).unwrap();
+        let also_owned = CrateBuilder::new("bar_team_owned", user.id)
+            .downloads(5)
+            .expect_build(conn);
+        add_team_to_crate(&t, &also_owned, user, conn).unwrap();
+        CrateBuilder::new("foo_not_team_owned", user.id).expect_build(conn);
+
+        let ttl = Duration::from_secs(60 * 60);
+        TeamMembership::record(conn, t.id, member.as_model().id, true, ttl).unwrap();
+        TeamMembership::record(conn, t.id, non_member.as_model().id, false, ttl).unwrap();
+    });
+
+    let url = "/api/v1/teams/crates-test-org/dashboard/crates";
+    anon.get::<()>(url).assert_forbidden();
+    let json = non_member.get::<()>(url).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only members of a team can see its crates"
+    );
+
+    let json: TeamCrates = member.get(url).good();
+    let names = json
+        .crates
+        .iter()
+        .map(|krate| &*krate.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["bar_team_owned", "foo_team_owned"]);
+    assert_eq!(json.meta.downloads, 15);
+    assert_eq!(json.meta.recent_downloads, 3);
+}
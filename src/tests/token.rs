@@ -2,7 +2,7 @@ use crate::{user::UserShowPrivateResponse, RequestHelper, TestApp};
 use cargo_registry::{
     models::ApiToken,
     schema::api_tokens,
-    views::{EncodableApiTokenWithToken, EncodableMe},
+    views::{EncodableApiTokenUsage, EncodableApiTokenWithToken, EncodableMe},
 };
 use std::collections::HashSet;
 
@@ -23,6 +23,15 @@ struct NewResponse {
 }
 #[derive(Deserialize)]
 struct RevokedResponse {}
+#[derive(Deserialize)]
+struct UsageResponse {
+    api_token_usage: Vec<EncodableApiTokenUsage>,
+    meta: UsageMeta,
+}
+#[derive(Deserialize)]
+struct UsageMeta {
+    daily_quota: Option<i32>,
+}
 
 macro_rules! assert_contains {
     ($e:expr, $f:expr) => {
@@ -125,6 +134,18 @@ fn create_token_no_name() {
     assert_eq!(json.errors[0].detail, "name must have a value");
 }
 
+#[test]
+fn create_token_with_daily_quota() {
+    let (_, _, user) = TestApp::init().with_user();
+    let with_quota = br#"{ "api_token": { "name": "bar", "daily_quota": 100 } }"#;
+    let json: NewResponse = user.put(URL, with_quota).good();
+    assert_eq!(json.api_token.daily_quota, Some(100));
+
+    let zero_quota = br#"{ "api_token": { "name": "bar", "daily_quota": 0 } }"#;
+    let json = user.put::<()>(URL, zero_quota).bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "daily_quota must be positive");
+}
+
 #[test]
 fn create_token_long_body() {
     let (_, _, user) = TestApp::init().with_user();
@@ -289,3 +310,193 @@ fn using_token_updates_last_used_at() {
     // based on the start of the database transaction so it doesn't work in
     // this test framework.
 }
+
+#[test]
+fn using_token_records_where_it_was_used_from() {
+    #[derive(Deserialize)]
+    struct UsedApiToken {
+        last_used_ip: Option<String>,
+        last_used_user_agent: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct UsedListResponse {
+        api_tokens: Vec<UsedApiToken>,
+    }
+
+    let (_, _, user, token) = TestApp::init().with_token();
+    let json: UsedListResponse = user.get(URL).good();
+    assert_eq!(json.api_tokens[0].last_used_ip, None);
+    assert_eq!(json.api_tokens[0].last_used_user_agent, None);
+
+    token.get::<EncodableMe>("/api/v1/me").good();
+
+    let json: UsedListResponse = user.get(URL).good();
+    assert!(json.api_tokens[0].last_used_ip.is_some());
+    assert_eq!(
+        json.api_tokens[0].last_used_user_agent,
+        Some("conduit-test".into())
+    );
+}
+
+#[test]
+fn token_with_bearer_scheme_gives_access_to_me() {
+    use conduit::Method;
+
+    let (_, _, user, token) = TestApp::init().with_token();
+
+    let mut request = crate::req(Method::Get, "/api/v1/me");
+    let header = format!("Bearer {}", token.as_model().token);
+    request.header("Authorization", &header);
+    let json: UserShowPrivateResponse = token.run(request).good();
+    assert_eq!(json.user.email, user.as_model().email);
+}
+
+#[test]
+fn create_cargo_login_token() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json: NewResponse = user.put("/api/v1/me/tokens/cargo_login", b"").good();
+    assert!(json.api_token.name.starts_with("cargo login"));
+    assert!(!json.api_token.token.is_empty());
+
+    let tokens = app.db(|conn| t!(ApiToken::belonging_to(user.as_model()).load::<ApiToken>(conn)));
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].token, json.api_token.token);
+}
+
+#[test]
+fn cannot_create_cargo_login_token_with_token() {
+    let (_, _, _, token) = TestApp::init().with_token();
+    let json = token
+        .put::<()>("/api/v1/me/tokens/cargo_login", b"")
+        .bad_with_status(400);
+
+    assert_contains!(
+        json.errors[0].detail,
+        "cannot use an API token to create a new API token"
+    );
+}
+
+#[test]
+fn unsigned_secret_scanning_reports_are_rejected() {
+    let (app, anon, _, token) = TestApp::init().with_token();
+    let body = format!(
+        r#"[{{"token":"{}","type":"crates_io_api_token"}}]"#,
+        token.as_model().token
+    );
+
+    anon.post::<()>("/api/github/secret-scanning/verify", body.as_bytes())
+        .assert_forbidden();
+
+    let revoked = app.db(|conn| {
+        t!(api_tokens::table
+            .find(token.as_model().id)
+            .select(api_tokens::revoked)
+            .first::<bool>(conn))
+    });
+    assert!(!revoked);
+}
+
+#[test]
+fn token_usage_is_counted_per_day() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let url = format!("/api/v1/me/tokens/{}/usage", token.as_model().id);
+
+    let json: UsageResponse = user.get(&url).good();
+    assert!(json.api_token_usage.is_empty());
+
+    token.get::<EncodableMe>("/api/v1/me").good();
+    token.get::<EncodableMe>("/api/v1/me").good();
+
+    let json: UsageResponse = user.get(&url).good();
+    assert_eq!(json.api_token_usage.len(), 1);
+    assert_eq!(json.api_token_usage[0].requests, 2);
+    assert_eq!(json.meta.daily_quota, None);
+
+    // The usage of other users' tokens can't be seen
+    let other = app.db_new_user("baz");
+    other.get::<()>(&url).assert_not_found();
+}
+
+#[test]
+fn tokens_over_their_daily_quota_are_refused() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    app.db(|conn| {
+        diesel::update(api_tokens::table.find(token.as_model().id))
+            .set(api_tokens::daily_quota.eq(2))
+            .execute(conn)
+            .unwrap();
+    });
+
+    token.get::<EncodableMe>("/api/v1/me").good();
+    token.get::<EncodableMe>("/api/v1/me").good();
+    let mut response = token.get::<()>("/api/v1/me");
+    let json = response.bad_with_status(429);
+    assert_eq!(json.errors[0].code, "rate_limited");
+    assert!(json.errors[0].detail.contains("2 requests per day"));
+    assert!(response.header("Retry-After").ends_with("00:00:00 GMT"));
+
+    // Refused requests are counted too, and the session isn't limited
+    let url = format!("/api/v1/me/tokens/{}/usage", token.as_model().id);
+    let json: UsageResponse = user.get(&url).good();
+    assert_eq!(json.api_token_usage[0].requests, 3);
+    assert_eq!(json.meta.daily_quota, Some(2));
+}
+
+#[test]
+fn tokens_can_only_be_used_from_allowed_ips() {
+    use conduit::Method;
+
+    let (_, _, user) = TestApp::init().with_user();
+    let body =
+        br#"{ "api_token": { "name": "ci", "allowed_ips": ["192.0.2.17/24", "2001:db8::1"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.allowed_ips,
+        ["192.0.2.0/24", "2001:db8::1/128"]
+    );
+
+    let token = json.api_token.token;
+    let request = |ip: &str| {
+        let mut request = crate::req(Method::Get, "/api/v1/me");
+        request.header("Authorization", &token);
+        request.header("X-Real-Ip", ip);
+        request
+    };
+    let json: UserShowPrivateResponse = user.run(request("192.0.2.200")).good();
+    assert_eq!(json.user.email, user.as_model().email);
+
+    let json = user.run::<()>(request("198.51.100.1")).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "this API token can't be used from 198.51.100.1"
+    );
+    assert_eq!(json.errors[0].code, "unauthorized");
+
+    let invalid = br#"{ "api_token": { "name": "ci", "allowed_ips": ["192.0.2.0/33"] } }"#;
+    let json = user.put::<()>(URL, invalid).bad_with_status(400);
+    assert_eq!(
+        json.errors[0].detail,
+        "invalid IP address or CIDR range: 192.0.2.0/33"
+    );
+}
+
+#[test]
+fn real_ip_header_is_ignored_without_trusted_proxies() {
+    use conduit::Method;
+
+    let (_, _, user) = TestApp::init()
+        .with_config(|config| config.trusted_proxies = Vec::new())
+        .with_user();
+    let body = br#"{ "api_token": { "name": "ci", "allowed_ips": ["192.0.2.0/24"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+
+    let mut request = crate::req(Method::Get, "/api/v1/me");
+    request.header("Authorization", &json.api_token.token);
+    request.header("X-Real-Ip", "192.0.2.200");
+    let json = user.run::<()>(request).bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "this API token can't be used from 127.0.0.1"
+    );
+}
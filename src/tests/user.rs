@@ -5,9 +5,13 @@ use crate::{
     OkBool, TestApp,
 };
 use cargo_registry::{
-    models::{Email, NewUser, User},
-    schema::crate_owners,
-    views::{EncodablePrivateUser, EncodablePublicUser, EncodableVersion, OwnedCrate},
+    identity::OAuthClientConfig,
+    models::{Email, Identity, NewUser, Session, User},
+    schema::{crate_owners, versions},
+    views::{
+        EncodableDashboard, EncodableIdentity, EncodablePrivateUser, EncodablePublicUser,
+        EncodableSession, EncodableVersion, OwnedCrate,
+    },
 };
 
 use diesel::prelude::*;
@@ -27,6 +31,7 @@ pub struct UserShowPublicResponse {
 pub struct UserShowPrivateResponse {
     pub user: EncodablePrivateUser,
     pub owned_crates: Vec<OwnedCrate>,
+    pub update_digest: bool,
 }
 
 #[derive(Deserialize)]
@@ -117,6 +122,217 @@ fn access_token_needs_data() {
     assert!(json.errors[0].detail.contains("invalid state"));
 }
 
+#[test]
+fn login_with_a_disabled_provider_fails() {
+    let (_, anon) = TestApp::init().empty();
+    let json = anon.get::<()>("/authorize_url/gitlab").bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("logging in with `gitlab` isn't supported"));
+}
+
+#[test]
+fn auth_gives_a_token_for_gitlab() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| {
+            config.gitlab = Some(OAuthClientConfig {
+                client_id: "gitlab_client".into(),
+                client_secret: "gitlab_secret".into(),
+                url: "https://gitlab.example.com".into(),
+                redirect_url: None,
+            })
+        })
+        .empty();
+    let json: AuthResponse = anon.get("/authorize_url/gitlab").good();
+    assert!(json
+        .url
+        .starts_with("https://gitlab.example.com/oauth/authorize?"));
+    assert!(json.url.contains(&json.state));
+
+    let json = anon.get::<()>("/authorize/gitlab").bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid state"));
+}
+
+#[test]
+fn provider_users_are_identified_by_their_provider_id() {
+    let (app, _) = TestApp::init().empty();
+    app.db(|conn| {
+        let user = NewUser::new(-1, "gitlab:ferris", None, None, None, "token")
+            .create_or_update_for_provider(conn, "gitlab", "42")
+            .unwrap();
+        assert_eq!(user.provider, "gitlab");
+        assert_eq!(user.provider_user_id.as_ref().map(|s| &s[..]), Some("42"));
+
+        // Renaming the account on GitLab renames the user
+        let renamed = NewUser::new(-1, "gitlab:crab", None, None, None, "token")
+            .create_or_update_for_provider(conn, "gitlab", "42")
+            .unwrap();
+        assert_eq!(renamed.id, user.id);
+        assert_eq!(renamed.gh_login, "gitlab:crab");
+
+        // The same ID with another provider is another user
+        let other = NewUser::new(-1, "bitbucket:ferris", None, None, None, "token")
+            .create_or_update_for_provider(conn, "bitbucket", "42")
+            .unwrap();
+        assert_ne!(other.id, user.id);
+    });
+}
+
+#[derive(Deserialize)]
+struct IdentityList {
+    identities: Vec<EncodableIdentity>,
+}
+
+#[test]
+fn linked_identities_are_listed_and_can_be_unlinked() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user_model = user.as_model();
+    anon.get::<()>("/api/v1/me/identities").assert_forbidden();
+
+    app.db(|conn| {
+        Identity::record(conn, user_model.id, "github", "1", "foo").unwrap();
+        Identity::link(conn, user_model, "gitlab", "42", "ferris").unwrap();
+    });
+
+    let json: IdentityList = user.get("/api/v1/me/identities").good();
+    let providers = json
+        .identities
+        .iter()
+        .map(|identity| (&identity.provider[..], &identity.login[..]))
+        .collect::<Vec<_>>();
+    assert_eq!(providers, [("github", "foo"), ("gitlab", "ferris")]);
+
+    let json = user
+        .delete::<()>("/api/v1/me/identities/github")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("the identity your account signed up with can't be unlinked"));
+
+    let json: OkBool = user.delete("/api/v1/me/identities/gitlab").good();
+    assert!(json.ok);
+    let json: IdentityList = user.get("/api/v1/me/identities").good();
+    assert_eq!(json.identities.len(), 1);
+
+    let json = user
+        .delete::<()>("/api/v1/me/identities/gitlab")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("your account isn't linked to a gitlab account"));
+}
+
+#[test]
+fn identities_can_only_be_linked_to_one_account() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("bar");
+    app.db(|conn| {
+        Identity::link(conn, other.as_model(), "gitlab", "42", "ferris").unwrap();
+
+        let err = Identity::link(conn, user.as_model(), "gitlab", "42", "ferris").unwrap_err();
+        assert_eq!(
+            err.description(),
+            "this gitlab account is already linked to another account"
+        );
+
+        Identity::link(conn, user.as_model(), "gitlab", "43", "crab").unwrap();
+        let err = Identity::link(conn, user.as_model(), "gitlab", "44", "crab2").unwrap_err();
+        assert_eq!(
+            err.description(),
+            "your account is already linked to another gitlab account, unlink it first"
+        );
+
+        // Linking the same identity again just updates its login
+        let identity = Identity::link(conn, user.as_model(), "gitlab", "43", "crab3").unwrap();
+        assert_eq!(identity.login, "crab3");
+    });
+}
+
+#[test]
+fn linking_an_identity_needs_a_valid_state() {
+    let (_, anon, user) = TestApp::init()
+        .with_config(|config| {
+            config.gitlab = Some(OAuthClientConfig {
+                client_id: "gitlab_client".into(),
+                client_secret: "gitlab_secret".into(),
+                url: "https://gitlab.example.com".into(),
+                redirect_url: None,
+            })
+        })
+        .with_user();
+    anon.get::<()>("/api/v1/me/identities/gitlab/authorize_url")
+        .assert_forbidden();
+
+    let json: AuthResponse = user
+        .get("/api/v1/me/identities/gitlab/authorize_url")
+        .good();
+    assert!(json.url.contains(&json.state));
+
+    // The state of logging in can't be used to link an identity
+    let json = user
+        .put::<()>("/api/v1/me/identities/gitlab", b"")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid state"));
+}
+
+#[derive(Deserialize)]
+struct SessionList {
+    sessions: Vec<EncodableSession>,
+}
+
+#[test]
+fn sessions_can_be_listed_and_revoked() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let other = app.db_new_user("bar");
+    anon.get::<()>("/api/v1/me/sessions").assert_forbidden();
+
+    let (laptop, phone, others) = app.db(|conn| {
+        let user_id = user.as_model().id;
+        (
+            Session::create(conn, user_id, Some("127.0.0.1"), Some("Firefox")).unwrap(),
+            Session::create(conn, user_id, Some("127.0.0.2"), None).unwrap(),
+            Session::create(conn, other.as_model().id, None, None).unwrap(),
+        )
+    });
+
+    let json: SessionList = user.get("/api/v1/me/sessions").good();
+    assert_eq!(json.sessions.len(), 2);
+    let listed = json
+        .sessions
+        .iter()
+        .find(|session| session.id == laptop.id)
+        .unwrap();
+    assert_eq!(listed.ip.as_ref().map(|s| &s[..]), Some("127.0.0.1"));
+    assert_eq!(listed.user_agent.as_ref().map(|s| &s[..]), Some("Firefox"));
+    assert!(!listed.current);
+
+    let url = format!("/api/v1/me/sessions/{}", laptop.id);
+    let json: OkBool = user.delete(&url).good();
+    assert!(json.ok);
+    user.delete::<()>(&url).assert_not_found();
+
+    // Sessions of other users can't be revoked
+    let url = format!("/api/v1/me/sessions/{}", others.id);
+    user.delete::<()>(&url).assert_not_found();
+
+    let json: SessionList = user.get("/api/v1/me/sessions").good();
+    let ids = json
+        .sessions
+        .iter()
+        .map(|session| session.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [phone.id]);
+
+    // Revoked sessions can't be used anymore
+    app.db(|conn| {
+        assert!(Session::find_by_token(conn, &laptop.token).is_err());
+        assert_eq!(
+            Session::find_by_token(conn, &phone.token).unwrap().id,
+            phone.id
+        );
+    });
+}
+
 #[test]
 fn me() {
     let url = "/api/v1/me";
@@ -134,6 +350,46 @@ fn me() {
     assert_eq!(json.owned_crates.len(), 1);
 }
 
+#[test]
+fn dashboard() {
+    let url = "/api/v1/me/dashboard";
+    let (app, anon, user, token) = TestApp::init().with_token();
+    anon.get::<()>(url).assert_forbidden();
+
+    let other = app.db_new_user("bar");
+    app.db(|conn| {
+        CrateBuilder::new("foo_dashboard", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .downloads(20)
+            .recent_downloads(5)
+            .expect_build(conn);
+        diesel::update(versions::table.filter(versions::num.eq("1.0.0")))
+            .set(versions::advisory.eq("known broken on Windows"))
+            .execute(conn)
+            .unwrap();
+    });
+    token.add_named_owner("foo_dashboard", "bar").good();
+
+    let json: EncodableDashboard = user.get(url).good();
+    assert_eq!(json.crates.len(), 1);
+    let krate = &json.crates[0];
+    assert_eq!(krate.name, "foo_dashboard");
+    assert_eq!(krate.max_version.as_ref().unwrap(), "1.1.0");
+    assert_eq!(krate.downloads, 20);
+    assert_eq!(krate.recent_downloads, 5);
+    assert_eq!(krate.advisories, 1);
+    assert!(json.crate_owner_invitations.is_empty());
+    assert_eq!(json.email, user.as_model().email);
+    assert!(json.email_verified);
+
+    let json: EncodableDashboard = other.get(url).good();
+    assert!(json.crates.is_empty());
+    assert_eq!(json.crate_owner_invitations.len(), 1);
+    assert_eq!(json.crate_owner_invitations[0].crate_name, "foo_dashboard");
+    assert_eq!(json.crate_owner_invitations[0].invited_by_username, "foo");
+}
+
 #[test]
 fn show() {
     let (app, anon, _) = TestApp::init().with_user();
@@ -296,6 +552,134 @@ fn following() {
         .bad_with_status(200); // TODO: Should be 500
 }
 
+#[test]
+fn following_keywords_and_categories() {
+    use cargo_registry::{models::Category, schema::topic_follows};
+    use chrono::{Duration, Utc};
+
+    #[derive(Deserialize)]
+    struct R {
+        versions: Vec<EncodableVersion>,
+    }
+    #[derive(Deserialize)]
+    struct F {
+        following: bool,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+    app.db(|conn| {
+        t!(crate::new_category("Foo", "foo", "Foo crates").create_or_update(conn));
+        t!(crate::new_category("Foo::Bar", "foo::bar", "Bar crates").create_or_update(conn));
+        CrateBuilder::new("foo_tagged", user_id)
+            .keyword("tagged")
+            .expect_build(conn);
+        let krate = CrateBuilder::new("foo_categorized", user_id).expect_build(conn);
+        t!(Category::update_crate(conn, &krate, &["foo::bar"]));
+        CrateBuilder::new("foo_unrelated", user_id).expect_build(conn);
+    });
+
+    let f: F = user.get("/api/v1/keywords/tagged/following").good();
+    assert!(!f.following);
+    user.put::<OkBool>("/api/v1/keywords/tagged/follow", b"")
+        .good();
+    user.put::<OkBool>("/api/v1/categories/foo/follow", b"")
+        .good();
+    let f: F = user.get("/api/v1/keywords/tagged/following").good();
+    assert!(f.following);
+    let f: F = user.get("/api/v1/categories/foo/following").good();
+    assert!(f.following);
+    user.put::<OkBool>("/api/v1/keywords/missing/follow", b"")
+        .assert_not_found();
+
+    // Only versions published after following the topic are updates
+    let r: R = user.get("/api/v1/me/updates").good();
+    assert_eq!(r.versions.len(), 0);
+
+    app.db(|conn| {
+        diesel::update(topic_follows::table)
+            .set(topic_follows::created_at.eq(Utc::now().naive_utc() - Duration::days(1)))
+            .execute(conn)
+            .unwrap();
+    });
+    let r: R = user.get("/api/v1/me/updates").good();
+    let mut crates = r.versions.iter().map(|v| &*v.krate).collect::<Vec<_>>();
+    crates.sort();
+    assert_eq!(crates, ["foo_categorized", "foo_tagged"]);
+
+    user.delete::<OkBool>("/api/v1/categories/foo/follow")
+        .good();
+    let r: R = user.get("/api/v1/me/updates").good();
+    assert_eq!(r.versions.len(), 1);
+    assert_eq!(r.versions[0].krate, "foo_tagged");
+}
+
+#[test]
+fn update_digest_subscription() {
+    use cargo_registry::schema::update_digest_subscriptions;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    assert!(!user.show_me().update_digest);
+
+    user.put::<OkBool>("/api/v1/me/update_digest", b"").good();
+    user.put::<OkBool>("/api/v1/me/update_digest", b"").good();
+    assert!(user.show_me().update_digest);
+
+    // The link in the digest emails works without signing in
+    let token = app.db(|conn| {
+        update_digest_subscriptions::table
+            .select(update_digest_subscriptions::unsubscribe_token)
+            .first::<String>(conn)
+            .unwrap()
+    });
+    let url = format!("/api/v1/unsubscribe/{}", token);
+    anon.put::<OkBool>(&url, b"").good();
+    assert!(!user.show_me().update_digest);
+    anon.put::<()>(&url, b"").bad_with_status(200);
+
+    user.put::<OkBool>("/api/v1/me/update_digest", b"").good();
+    user.delete::<OkBool>("/api/v1/me/update_digest").good();
+    assert!(!user.show_me().update_digest);
+}
+
+#[test]
+fn unsubscribe_links_turn_off_notifications() {
+    use cargo_registry::email::Unsubscribe;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+    let krate = app.db(|conn| CrateBuilder::new("foo_muted", user_id).expect_build(conn));
+    user.put::<OkBool>("/api/v1/me/update_digest", b"").good();
+
+    let session_key = &app.as_inner().session_key;
+    let digest = Unsubscribe::UpdateDigest { user_id }.token(session_key);
+    anon.get::<OkBool>(&format!("/api/v1/email/unsubscribe/{}", digest))
+        .good();
+    let json = user.show_me();
+    assert!(!json.update_digest);
+    assert!(json.owned_crates[0].email_notifications);
+
+    // Mail clients' one-click unsubscribe buttons use POST
+    let crates = Unsubscribe::CrateNotifications {
+        user_id,
+        crate_ids: vec![krate.id],
+    }
+    .token(session_key);
+    anon.post::<OkBool>(&format!("/api/v1/email/unsubscribe/{}", crates), b"")
+        .good();
+    assert!(!user.show_me().owned_crates[0].email_notifications);
+
+    let forged = format!(
+        "digest.{}.{}",
+        user_id + 1,
+        digest.rsplitn(2, '.').next().unwrap()
+    );
+    let json = anon
+        .get::<()>(&format!("/api/v1/email/unsubscribe/{}", forged))
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("invalid unsubscribe token"));
+}
+
 #[test]
 fn user_total_downloads() {
     use diesel::update;
@@ -348,7 +732,7 @@ fn updating_existing_user_doesnt_change_api_token() {
         t!(NewUser::new(gh_id, "bar", None, None, None, "bar_token").create_or_update(conn));
 
         // Use the original API token to find the now updated user
-        t!(User::find_by_api_token(conn, token))
+        t!(User::find_by_api_token(conn, token, None, None)).unwrap()
     });
 
     assert_eq!("bar", user.gh_login);
@@ -472,6 +856,7 @@ fn test_empty_email_not_added() {
         "{:?}",
         json.errors
     );
+    assert_eq!(json.errors[0].code, "invalid_email");
 
     let json = user
         .update_email_more_control(model.id, None)
@@ -29,6 +29,7 @@ use cargo_registry::{
     git::{Credentials, RepositoryConfig},
     middleware::current_user::AuthenticationSource,
     models::{ApiToken, User},
+    rate_limit::{LimitedAction, RateLimit},
     App, Config,
 };
 use diesel::PgConnection;
@@ -224,6 +225,8 @@ impl TestAppBuilder {
                 connection_pool.clone(),
                 app.config.uploader.clone(),
                 app.http_client().clone(),
+                app.session_key.clone(),
+                app.config.branding.clone(),
             );
 
             Some(
@@ -281,9 +284,14 @@ impl TestAppBuilder {
     }
 
     pub fn with_publish_rate_limit(self, rate: Duration, burst: i32) -> Self {
+        self.with_rate_limit(LimitedAction::PublishNew, rate, burst)
+    }
+
+    pub fn with_rate_limit(self, action: LimitedAction, rate: Duration, burst: i32) -> Self {
         self.with_config(|config| {
-            config.publish_rate_limit.rate = rate;
-            config.publish_rate_limit.burst = burst;
+            config
+                .rate_limiter
+                .set_limit(action, Some(RateLimit { rate, burst }))
         })
     }
 
@@ -347,6 +355,26 @@ pub trait RequestHelper {
         self.run(request)
     }
 
+    /// Issue a PATCH request
+    fn patch<T>(&self, path: &str, body: &[u8]) -> Response<T>
+    where
+        for<'de> T: serde::Deserialize<'de>,
+    {
+        let mut request = self.request_builder(Method::Patch, path);
+        request.with_body(body);
+        self.run(request)
+    }
+
+    /// Issue a POST request
+    fn post<T>(&self, path: &str, body: &[u8]) -> Response<T>
+    where
+        for<'de> T: serde::Deserialize<'de>,
+    {
+        let mut request = self.request_builder(Method::Post, path);
+        request.with_body(body);
+        self.run(request)
+    }
+
     /// Issue a DELETE request
     fn delete<T>(&self, path: &str) -> Response<T>
     where
@@ -548,6 +576,7 @@ impl MockTokenUser {
 #[derive(Deserialize, Debug)]
 pub struct Error {
     pub detail: String,
+    pub code: String,
 }
 
 #[derive(Deserialize)]
@@ -605,6 +634,13 @@ where
         }
     }
 
+    /// Assert the response status code and deserialize the body, for
+    /// responses that have a body whatever their status
+    pub fn with_status(mut self, status: u32) -> T {
+        self.assert_status(status);
+        crate::json(&mut self.response)
+    }
+
     pub fn assert_status(&self, status: u32) -> &Self {
         assert_eq!(status, self.response.status.0);
         self
@@ -614,6 +650,30 @@ where
         assert!(self.response.headers["Location"][0].ends_with(target));
         self
     }
+
+    /// Returns the first value of a response header, panicking if it is missing
+    pub fn header(&self, name: &str) -> &str {
+        &self.response.headers[name][0]
+    }
+
+    /// Returns the first value of a response header, if it is present
+    pub fn maybe_header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Assert that the response is good and return its body as text
+    pub fn text(mut self) -> String {
+        if !crate::ok_resp(&self.response) {
+            panic!("bad response: {:?}", self.response.status);
+        }
+        let mut body = Vec::new();
+        self.response.body.write_body(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    }
 }
 
 impl Response<()> {
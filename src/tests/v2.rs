@@ -0,0 +1,128 @@
+use crate::{
+    builders::{CrateBuilder, VersionBuilder},
+    RequestHelper, TestApp,
+};
+use cargo_registry::views::v2::{
+    EncodableCrate, EncodableOwner, EncodableVersion, Envelope, PageMeta, Problem, ProblemError,
+};
+
+const PROBLEM_JSON: &str = "application/problem+json; charset=utf-8";
+
+#[test]
+fn crates_are_paginated_in_an_envelope() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        let id = user.as_model().id;
+        CrateBuilder::new("v2_a", id)
+            .downloads(10)
+            .expect_build(conn);
+        CrateBuilder::new("v2_b", id)
+            .downloads(30)
+            .expect_build(conn);
+        CrateBuilder::new("v2_c", id)
+            .downloads(20)
+            .expect_build(conn);
+    });
+
+    let json: Envelope<Vec<EncodableCrate>> =
+        anon.get_with_query("/api/v2/crates", "per_page=2").good();
+    let names = json.data.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, ["v2_a", "v2_b"]);
+    assert_eq!(
+        json.meta,
+        Some(PageMeta {
+            total: 3,
+            page: 1,
+            per_page: 2,
+            next_page: Some("?per_page=2&page=2".into()),
+            prev_page: None,
+        })
+    );
+
+    let json: Envelope<Vec<EncodableCrate>> = anon
+        .get_with_query("/api/v2/crates", "per_page=2&page=2&sort=downloads")
+        .good();
+    let names = json.data.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, ["v2_a"]);
+    let meta = json.meta.unwrap();
+    assert_eq!(meta.next_page, None);
+    assert_eq!(
+        meta.prev_page,
+        Some("?per_page=2&page=1&sort=downloads".into())
+    );
+}
+
+#[test]
+fn show_crate_versions_and_owners() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("v2_show", user.as_model().id)
+            .description("A crate")
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .version("2.0.0")
+            .expect_build(conn);
+    });
+
+    let json: Envelope<EncodableCrate> = anon.get("/api/v2/crates/v2_show").good();
+    assert_eq!(json.data.name, "v2_show");
+    assert_eq!(json.data.description, Some("A crate".into()));
+    assert_eq!(json.data.max_version, Some("2.0.0".into()));
+    assert!(json.meta.is_none());
+
+    let json: Envelope<Vec<EncodableVersion>> = anon.get("/api/v2/crates/v2_show/versions").good();
+    let nums = json.data.iter().map(|v| &*v.num).collect::<Vec<_>>();
+    assert_eq!(nums, ["2.0.0", "1.0.0"]);
+    assert_eq!(json.data[1].license, Some("MIT".into()));
+    assert_eq!(json.data[1].published_by, Some("foo".into()));
+    assert_eq!(json.meta.unwrap().total, 2);
+
+    let json: Envelope<Vec<EncodableOwner>> = anon.get("/api/v2/crates/v2_show/owners").good();
+    assert_eq!(json.data.len(), 1);
+    assert_eq!(json.data[0].login, "foo");
+    assert_eq!(json.data[0].kind, "user");
+}
+
+#[test]
+fn errors_are_problems() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("v2_errors", user.as_model().id).expect_build(conn);
+    });
+
+    let response = anon.get::<Problem>("/api/v2/crates/missing");
+    assert_eq!(response.header("Content-Type"), PROBLEM_JSON);
+    let json = response.with_status(404);
+    assert_eq!(json.problem_type, "about:blank");
+    assert_eq!(json.title, "Not Found");
+    assert_eq!(json.status, 404);
+    assert_eq!(json.code, "not_found");
+    assert_eq!(json.errors.len(), 1);
+
+    // Unknown routes too
+    let response = anon.get::<Problem>("/api/v2/missing");
+    assert_eq!(response.header("Content-Type"), PROBLEM_JSON);
+    assert_eq!(response.with_status(404).code, "not_found");
+
+    // Invalid query parameters are pointed at
+    let json = anon
+        .get_with_query::<Problem>("/api/v2/crates", "per_page=1000")
+        .with_status(400);
+    assert_eq!(json.code, "invalid_parameter");
+    assert_eq!(
+        json.errors,
+        [ProblemError {
+            code: "invalid_parameter".into(),
+            detail: "`per_page` must be between 1 and 100".into(),
+            pointer: None,
+            parameter: Some("per_page".into()),
+        }]
+    );
+    let json = anon
+        .get_with_query::<Problem>("/api/v2/crates", "sort=random")
+        .with_status(400);
+    assert_eq!(json.errors[0].parameter, Some("sort".into()));
+    let json = anon
+        .get_with_query::<Problem>("/api/v2/crates/v2_errors/versions", "page=0")
+        .with_status(400);
+    assert_eq!(json.errors[0].parameter, Some("page".into()));
+}
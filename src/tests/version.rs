@@ -2,9 +2,20 @@ use crate::{
     builders::{CrateBuilder, PublishBuilder, VersionBuilder},
     RequestHelper, TestApp, VersionResponse,
 };
-use cargo_registry::{models::Version, schema::versions, views::EncodableVersion};
+use cargo_registry::{
+    models::{BuildVerifier, Version, VersionChecksum, VersionFile},
+    schema::versions,
+    uploaders::CrateFile,
+    views::{
+        EncodableBuildVerification, EncodableDependency, EncodableFileChange, EncodableVersion,
+        EncodableVersionChecksums, EncodableVersionDiff, EncodableVersionFile,
+    },
+};
 
+use chrono::Utc;
+use conduit::Method;
 use diesel::prelude::*;
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
 use serde_json::Value;
 
 #[derive(Deserialize)]
@@ -130,11 +141,63 @@ fn record_rerendered_readme_time() {
         let c = CrateBuilder::new("foo_authors", user.id).expect_build(conn);
         let version = VersionBuilder::new("1.0.0").expect_build(c.id, user.id, conn);
 
-        Version::record_readme_rendering(version.id, conn).unwrap();
-        Version::record_readme_rendering(version.id, conn).unwrap();
+        Version::record_readme_rendering(version.id, None, conn).unwrap();
+        Version::record_readme_rendering(version.id, None, conn).unwrap();
     });
 }
 
+#[test]
+fn readme_is_served_with_etag() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c = CrateBuilder::new("foo_readme_etag", user.id).expect_build(conn);
+        let version = VersionBuilder::new("1.0.0").expect_build(c.id, user.id, conn);
+        Version::record_readme_rendering(version.id, Some("<p>hello</p>"), conn).unwrap();
+        let version = VersionBuilder::new("1.1.0").expect_build(c.id, user.id, conn);
+        Version::record_readme_rendering(version.id, None, conn).unwrap();
+    });
+
+    let url = "/api/v1/crates/foo_readme_etag/1.0.0/readme";
+    let response = anon.get::<()>(url);
+    response.assert_status(200);
+    assert_eq!(response.header("Content-Type"), "text/html; charset=utf-8");
+    let etag = response.header("ETag").to_string();
+
+    let mut req = anon.request_builder(Method::Get, url);
+    req.header("If-None-Match", &etag);
+    anon.run::<()>(req).assert_status(304);
+
+    anon.get::<()>("/api/v1/crates/foo_readme_etag/1.1.0/readme")
+        .assert_redirect_ends_with("/readmes/foo_readme_etag/foo_readme_etag-1.1.0.html");
+}
+
+#[test]
+fn dependencies_are_served_with_last_modified() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_deps_modified", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/foo_deps_modified/1.0.0/dependencies";
+    let response = anon.get::<()>(url);
+    response.assert_status(200);
+    let last_modified = response.header("Last-Modified").to_string();
+
+    let mut req = anon.request_builder(Method::Get, url);
+    req.header("If-Modified-Since", &last_modified);
+    anon.run::<()>(req).assert_status(304);
+
+    let mut req = anon.request_builder(Method::Get, url);
+    req.header("If-Modified-Since", "Mon, 01 Jan 2018 00:00:00 GMT");
+    anon.run::<()>(req).assert_status(200);
+}
+
 #[test]
 fn version_size() {
     let (_, _, user) = TestApp::full().with_user();
@@ -165,3 +228,436 @@ fn version_size() {
         .expect("Could not find v2.0.0");
     assert_eq!(version2.crate_size, Some(91));
 }
+
+#[derive(Deserialize)]
+struct BuildVerifications {
+    reproducible: Option<bool>,
+    verifications: Vec<EncodableBuildVerification>,
+}
+
+/// Records `sha256` as the checksum of the crate file of version 1.0.0 of
+/// the crate.
+fn published_checksum(conn: &PgConnection, crate_id: i32, sha256: &str) {
+    let version_id = versions::table
+        .filter(versions::crate_id.eq(crate_id))
+        .filter(versions::num.eq("1.0.0"))
+        .select(versions::id)
+        .first(conn)
+        .unwrap();
+    t!(VersionChecksum::insert(conn, version_id, sha256));
+}
+
+fn submit_build_verification(
+    anon: &crate::util::MockAnonymousUser,
+    verifier: &BuildVerifier,
+    secret: &str,
+    body: &Value,
+) -> crate::util::Response<crate::OkBool> {
+    let url = "/api/v1/crates/foo_reproducible/1.0.0/build_verification";
+    submit_signed_build_verification(anon, verifier, secret, url, Utc::now().timestamp(), body)
+}
+
+/// Submits a verdict on version 1.0.0 of `foo_reproducible`, signing the
+/// request as if it was sent to `signed_url` at `timestamp`.
+fn submit_signed_build_verification(
+    anon: &crate::util::MockAnonymousUser,
+    verifier: &BuildVerifier,
+    secret: &str,
+    signed_url: &str,
+    timestamp: i64,
+    body: &Value,
+) -> crate::util::Response<crate::OkBool> {
+    let body = body.to_string();
+    let timestamp = timestamp.to_string();
+    let message = BuildVerifier::signed_message("PUT", signed_url, &timestamp, body.as_bytes());
+    let key = PKey::hmac(BuildVerifier::signing_key(secret).as_bytes()).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(&message).unwrap();
+    let signature = hex::encode(signer.sign_to_vec().unwrap());
+
+    let url = "/api/v1/crates/foo_reproducible/1.0.0/build_verification";
+    let mut req = anon.request_builder(Method::Put, url);
+    req.header("X-Verifier", &verifier.name);
+    req.header("X-Verifier-Timestamp", &timestamp);
+    req.header("X-Verifier-Signature", &signature);
+    req.with_body(body.as_bytes());
+    anon.run(req)
+}
+
+#[derive(Deserialize)]
+struct FileList {
+    files: Vec<EncodableVersionFile>,
+    meta: FileListMeta,
+}
+
+#[derive(Deserialize)]
+struct FileListMeta {
+    file_count: usize,
+    total_size: i64,
+}
+
+#[test]
+fn files() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_files", user.id).expect_build(conn);
+        let version = VersionBuilder::new("1.0.0").expect_build(krate.id, user.id, conn);
+        let file = |path: &str, size| CrateFile {
+            path: path.into(),
+            size,
+        };
+        let files = [file("src/lib.rs", 120), file("Cargo.toml", 30)];
+        VersionFile::insert(conn, version.id, &files).unwrap();
+    });
+
+    let json: FileList = anon.get("/api/v1/crates/foo_files/1.0.0/files").good();
+    assert_eq!(
+        json.files,
+        vec![
+            EncodableVersionFile {
+                path: "Cargo.toml".into(),
+                size: 30,
+            },
+            EncodableVersionFile {
+                path: "src/lib.rs".into(),
+                size: 120,
+            },
+        ]
+    );
+    assert_eq!(json.meta.file_count, 2);
+    assert_eq!(json.meta.total_size, 150);
+}
+
+#[derive(Deserialize)]
+struct Diff {
+    diff: EncodableVersionDiff,
+}
+
+#[test]
+fn diff() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let old_dep = CrateBuilder::new("foo_diff_old_dep", user.id).expect_build(conn);
+        let kept_dep = CrateBuilder::new("foo_diff_kept_dep", user.id).expect_build(conn);
+        let new_dep = CrateBuilder::new("foo_diff_new_dep", user.id).expect_build(conn);
+        let krate = CrateBuilder::new("foo_diff", user.id).expect_build(conn);
+        let file = |path: &str, size| CrateFile {
+            path: path.into(),
+            size,
+        };
+
+        let from = VersionBuilder::new("1.0.0")
+            .dependency(&old_dep, None)
+            .dependency(&kept_dep, None)
+            .feature("default", &["std"])
+            .feature("std", &[])
+            .feature("old", &[])
+            .expect_build(krate.id, user.id, conn);
+        let files = [file("Cargo.toml", 30), file("src/lib.rs", 120)];
+        VersionFile::insert(conn, from.id, &files).unwrap();
+
+        let to = VersionBuilder::new("1.1.0")
+            .dependency(&kept_dep, None)
+            .dependency(&new_dep, Some("cfg(unix)"))
+            .feature("default", &["std", "new"])
+            .feature("std", &[])
+            .feature("new", &[])
+            .expect_build(krate.id, user.id, conn);
+        let files = [
+            file("Cargo.toml", 30),
+            file("src/lib.rs", 150),
+            file("src/new.rs", 10),
+        ];
+        VersionFile::insert(conn, to.id, &files).unwrap();
+    });
+
+    let json: Diff = anon.get("/api/v1/crates/foo_diff/1.0.0/diff/1.1.0").good();
+    let diff = json.diff;
+    assert_eq!(diff.from, "1.0.0");
+    assert_eq!(diff.to, "1.1.0");
+
+    let names = |deps: &[EncodableDependency]| {
+        deps.iter()
+            .map(|dep| dep.crate_id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&diff.dependencies.added), vec!["foo_diff_new_dep"]);
+    assert_eq!(
+        diff.dependencies.added[0].target.as_ref().unwrap(),
+        "cfg(unix)"
+    );
+    assert_eq!(names(&diff.dependencies.removed), vec!["foo_diff_old_dep"]);
+    assert!(diff.dependencies.changed.is_empty());
+
+    assert_eq!(diff.features.added.keys().collect::<Vec<_>>(), vec!["new"]);
+    assert_eq!(
+        diff.features.removed.keys().collect::<Vec<_>>(),
+        vec!["old"]
+    );
+    assert_eq!(diff.features.changed.len(), 1);
+    assert_eq!(diff.features.changed["default"].from, vec!["std"]);
+    assert_eq!(diff.features.changed["default"].to, vec!["std", "new"]);
+
+    assert_eq!(
+        diff.files.added,
+        vec![EncodableVersionFile {
+            path: "src/new.rs".into(),
+            size: 10,
+        }]
+    );
+    assert!(diff.files.removed.is_empty());
+    assert_eq!(
+        diff.files.changed,
+        vec![EncodableFileChange {
+            path: "src/lib.rs".into(),
+            from_size: 120,
+            to_size: 150,
+        }]
+    );
+
+    // Going the other way around swaps what was added and removed
+    let json: Diff = anon.get("/api/v1/crates/foo_diff/1.1.0/diff/1.0.0").good();
+    assert_eq!(
+        names(&json.diff.dependencies.added),
+        vec!["foo_diff_old_dep"]
+    );
+    assert_eq!(json.diff.files.removed[0].path, "src/new.rs");
+
+    let json = anon
+        .get::<()>("/api/v1/crates/foo_diff/1.0.0/diff/2.0.0")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "crate `foo_diff` does not have a version `2.0.0`"
+    );
+}
+
+#[derive(Deserialize)]
+struct Checksums {
+    checksums: EncodableVersionChecksums,
+}
+
+#[test]
+fn checksums() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_checksums", user.id).expect_build(conn);
+        let version = VersionBuilder::new("1.0.0").expect_build(krate.id, user.id, conn);
+        VersionChecksum::insert(conn, version.id, "e3b0c442").unwrap();
+        // Published before checksums were recorded
+        VersionBuilder::new("0.9.0").expect_build(krate.id, user.id, conn);
+    });
+
+    let json: Checksums = anon
+        .get("/api/v1/crates/foo_checksums/1.0.0/checksums")
+        .good();
+    assert_eq!(
+        json.checksums,
+        EncodableVersionChecksums {
+            sha256: Some("e3b0c442".into()),
+            sha512: None,
+        }
+    );
+
+    let json: Checksums = anon
+        .get("/api/v1/crates/foo_checksums/0.9.0/checksums")
+        .good();
+    assert_eq!(json.checksums.sha256, None);
+}
+
+#[test]
+fn docs_built_is_exposed() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_docs_built", user.id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+
+    let json = anon.show_version("foo_docs_built", "1.0.0");
+    assert_eq!(json.version.docs_built, None);
+
+    app.db(|conn| {
+        diesel::update(versions::table)
+            .set(versions::docs_built.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = anon.show_version("foo_docs_built", "1.0.0");
+    assert_eq!(json.version.docs_built, Some(true));
+}
+
+#[test]
+fn build_verifications() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let ((first, first_secret), (second, second_secret)) = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_reproducible", user.id)
+            .version("1.0.0")
+            .expect_build(conn);
+        published_checksum(conn, krate.id, "abc");
+        (
+            t!(BuildVerifier::register(conn, "first")),
+            t!(BuildVerifier::register(conn, "second")),
+        )
+    });
+
+    let json = anon.show_version("foo_reproducible", "1.0.0");
+    assert_eq!(json.version.reproducible, None);
+
+    let statement = json!({ "reproducible": true, "checksum": "abc" });
+    submit_build_verification(&anon, &first, &first_secret, &statement).good();
+    let json = anon.show_version("foo_reproducible", "1.0.0");
+    assert_eq!(json.version.reproducible, Some(true));
+
+    // Verdicts about another tarball than the published one are refused
+    let statement = json!({ "reproducible": true, "checksum": "def" });
+    let json =
+        submit_build_verification(&anon, &second, &second_secret, &statement).bad_with_status(400);
+    assert_eq!(
+        json.errors[0].detail,
+        "the checksum doesn't match the published crate file"
+    );
+
+    // A single dissenting verifier is enough to mark the version as not reproducible
+    let statement = json!({
+        "reproducible": false,
+        "checksum": "abc",
+        "log_url": "https://example.com/log",
+    });
+    submit_build_verification(&anon, &second, &second_secret, &statement).good();
+    let json = anon.show_version("foo_reproducible", "1.0.0");
+    assert_eq!(json.version.reproducible, Some(false));
+
+    let url = "/api/v1/crates/foo_reproducible/1.0.0/build_verifications";
+    let json: BuildVerifications = anon.get(url).good();
+    assert_eq!(json.reproducible, Some(false));
+    assert_eq!(json.verifications.len(), 2);
+    assert_eq!(json.verifications[0].verifier, "first");
+    assert!(json.verifications[0].reproducible);
+    assert_eq!(json.verifications[1].verifier, "second");
+    assert_eq!(
+        json.verifications[1].log_url,
+        Some("https://example.com/log".to_string())
+    );
+}
+
+#[test]
+fn build_verification_requires_valid_signature() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let (verifier, secret) = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_reproducible", user.id)
+            .version("1.0.0")
+            .expect_build(conn);
+        published_checksum(conn, krate.id, "abc");
+        t!(BuildVerifier::register(conn, "verifier"))
+    });
+
+    let statement = json!({ "reproducible": true, "checksum": "abc" });
+    submit_build_verification(&anon, &verifier, "wrong secret", &statement).assert_forbidden();
+
+    // Signatures can't be replayed later or for another version
+    let secret = &secret;
+    let url = "/api/v1/crates/foo_reproducible/1.0.0/build_verification";
+    let an_hour_ago = Utc::now().timestamp() - 60 * 60;
+    submit_signed_build_verification(&anon, &verifier, secret, url, an_hour_ago, &statement)
+        .assert_forbidden();
+    let url = "/api/v1/crates/foo_reproducible/2.0.0/build_verification";
+    let now = Utc::now().timestamp();
+    submit_signed_build_verification(&anon, &verifier, secret, url, now, &statement)
+        .assert_forbidden();
+
+    let json = anon.show_version("foo_reproducible", "1.0.0");
+    assert_eq!(json.version.reproducible, None);
+}
+
+#[test]
+fn dependency_graph() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        use cargo_registry::schema::dependencies;
+        use diesel::prelude::*;
+
+        let c = CrateBuilder::new("graph_c", user.id)
+            .version("1.0.0")
+            .version(VersionBuilder::new("2.0.0").yanked(true))
+            .expect_build(conn);
+        // `graph_d` is resolved before `graph_b`, so that `graph_c` is first
+        // reached through an optional dependency
+        let d = CrateBuilder::new("graph_d", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&c, None))
+            .expect_build(conn);
+        let b = CrateBuilder::new("graph_b", user.id)
+            .version("1.0.0")
+            .version(VersionBuilder::new("1.1.0").dependency(&c, None))
+            .expect_build(conn);
+        CrateBuilder::new("graph_a", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .dependency(&b, None)
+                    .dependency(&d, None),
+            )
+            .expect_build(conn);
+        diesel::update(dependencies::table.filter(dependencies::crate_id.eq(d.id)))
+            .set(dependencies::optional.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = "/api/v1/crates/graph_a/1.0.0/dependency_graph";
+    let json: Value = anon.get(url).good();
+    let graph = &json["dependency_graph"];
+    assert_eq!(
+        graph["nodes"],
+        json!([
+            { "id": "graph_a@1.0.0", "name": "graph_a", "version": "1.0.0", "optional": false },
+            { "id": "graph_b@1.1.0", "name": "graph_b", "version": "1.1.0", "optional": false },
+            { "id": "graph_d@1.0.0", "name": "graph_d", "version": "1.0.0", "optional": true },
+            { "id": "graph_c@1.0.0", "name": "graph_c", "version": "1.0.0", "optional": false },
+        ])
+    );
+    assert_eq!(graph["truncated"], false);
+    let edges = graph["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| (edge["from"].as_str().unwrap(), edge["to"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edges,
+        [
+            ("graph_a@1.0.0", "graph_b@1.1.0"),
+            ("graph_a@1.0.0", "graph_d@1.0.0"),
+            ("graph_d@1.0.0", "graph_c@1.0.0"),
+            ("graph_b@1.1.0", "graph_c@1.0.0"),
+        ]
+    );
+    assert_eq!(graph["edges"][1]["optional"], true);
+
+    let json: Value = anon.get_with_query(url, "depth=1").good();
+    assert_eq!(
+        json["dependency_graph"]["nodes"].as_array().unwrap().len(),
+        3
+    );
+
+    let json = anon
+        .get_with_query::<()>(url, "depth=11")
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("depth must be a number between 1 and 10"));
+}
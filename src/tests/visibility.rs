@@ -0,0 +1,316 @@
+use crate::{
+    builders::{CrateBuilder, PublishBuilder, VersionBuilder},
+    util::{MockCookieUser, RequestHelper, Response},
+    OkBool, TestApp,
+};
+use cargo_registry::{
+    response_cache::CacheBackend,
+    uploaders::DownloadSigning,
+    views::{EncodablePublicUser, EncodableVersion},
+};
+use conduit::Method;
+
+#[derive(Deserialize)]
+struct DownloadUrl {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct VersionList {
+    versions: Vec<EncodableVersion>,
+}
+
+#[derive(Deserialize)]
+struct AccessResponse {
+    visibility: String,
+    users: Vec<EncodablePublicUser>,
+}
+
+impl MockCookieUser {
+    fn set_visibility(&self, krate_name: &str, visibility: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/visibility", krate_name);
+        let body = json!({ "visibility": visibility }).to_string();
+        self.put(&url, body.as_bytes())
+    }
+
+    fn grant_access(&self, krate_name: &str, login: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/access", krate_name);
+        let body = json!({ "users": [login] }).to_string();
+        self.put(&url, body.as_bytes())
+    }
+
+    fn revoke_access(&self, krate_name: &str, login: &str) -> Response<OkBool> {
+        let url = format!("/api/v1/crates/{}/access", krate_name);
+        let body = json!({ "users": [login] }).to_string();
+        self.delete_with_body(&url, body.as_bytes())
+    }
+}
+
+#[test]
+fn private_crates_are_hidden_from_anonymous_users() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    let version_id = app.db(|conn| {
+        use cargo_registry::schema::versions;
+        use diesel::prelude::*;
+
+        let krate = CrateBuilder::new("foo_private", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+        versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .select(versions::id)
+            .first::<i32>(conn)
+            .unwrap()
+    });
+
+    assert_eq!(anon.search("q=foo_private").meta.total, 1);
+    user.set_visibility("foo_private", "private").good();
+
+    anon.get::<()>("/api/v1/crates/foo_private")
+        .assert_not_found();
+    anon.get::<()>("/api/v1/crates/foo_private/1.0.0/download")
+        .assert_not_found();
+    for path in &[
+        "versions",
+        "owners",
+        "reverse_dependencies",
+        "downloads",
+        "downloads/summary",
+        "1.0.0",
+        "1.0.0/dependencies",
+        "1.0.0/authors",
+        "1.0.0/downloads",
+    ] {
+        anon.get::<()>(&format!("/api/v1/crates/foo_private/{}", path))
+            .assert_not_found();
+    }
+    assert_eq!(anon.search("q=foo_private").meta.total, 0);
+
+    let version_url = format!("/api/v1/versions/{}", version_id);
+    let versions_url = format!("/api/v1/versions?ids[]={}", version_id);
+    anon.get::<()>(&version_url).assert_not_found();
+    let json: VersionList = anon.get(&versions_url).good();
+    assert!(json.versions.is_empty());
+
+    // Owners can still see their own crate
+    assert_eq!(user.show_crate("foo_private").krate.name, "foo_private");
+    assert_eq!(user.search("q=foo_private").meta.total, 1);
+    user.get::<()>("/api/v1/crates/foo_private/1.0.0/download")
+        .assert_status(302);
+    user.get::<()>(&version_url).good();
+    let json: VersionList = user.get(&versions_url).good();
+    assert_eq!(json.versions.len(), 1);
+}
+
+#[test]
+fn cached_responses_are_invalidated_by_visibility_changes() {
+    use cargo_registry::schema::crates;
+    use diesel::prelude::*;
+
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| {
+            config.response_cache.backend = CacheBackend::Memory { capacity: 10 };
+        })
+        .with_user();
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo_cached_private", user.as_model().id)
+            .description("old description")
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn)
+    });
+    let json = anon.show_crate("foo_cached_private");
+    assert_eq!(json.krate.description.unwrap(), "old description");
+
+    app.db(|conn| {
+        diesel::update(&krate)
+            .set(crates::description.eq("new description"))
+            .execute(conn)
+            .unwrap();
+    });
+    user.set_visibility("foo_cached_private", "private").good();
+    user.set_visibility("foo_cached_private", "public").good();
+    let json = anon.show_crate("foo_cached_private");
+    assert_eq!(json.krate.description.unwrap(), "new description");
+}
+
+#[test]
+fn granted_users_can_access_private_crates() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let reader = app.db_new_user("reader");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_granted", owner.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+    owner.set_visibility("foo_granted", "private").good();
+
+    let download_url = "/api/v1/crates/foo_granted/1.0.0/download";
+    reader.get::<()>(download_url).assert_not_found();
+
+    owner.grant_access("foo_granted", "reader").good();
+    reader.get::<()>(download_url).assert_status(302);
+    assert_eq!(reader.search("q=foo_granted").meta.total, 1);
+
+    let access: AccessResponse = owner.get("/api/v1/crates/foo_granted/access").good();
+    assert_eq!(access.visibility, "private");
+    assert_eq!(access.users.len(), 1);
+    assert_eq!(access.users[0].login, "reader");
+
+    owner.revoke_access("foo_granted", "reader").good();
+    reader.get::<()>(download_url).assert_not_found();
+}
+
+#[test]
+fn only_owners_can_modify_access() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_acl_owners", owner.as_model().id).expect_build(conn);
+    });
+
+    let json = other
+        .set_visibility("foo_acl_owners", "private")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission to modify access to a crate"));
+
+    let json = other
+        .grant_access("foo_acl_owners", "other")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission to modify access to a crate"));
+}
+
+#[test]
+fn private_crate_downloads_are_signed() {
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| {
+            config.download_signing = Some(DownloadSigning::Cdn {
+                key: String::from("secret"),
+            });
+        })
+        .with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_signed", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+        CrateBuilder::new("foo_unsigned", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+    user.set_visibility("foo_signed", "private").good();
+
+    let download_url = |krate_name: &str| {
+        let url = format!("/api/v1/crates/{}/1.0.0/download", krate_name);
+        let mut req = user.request_builder(Method::Get, &url);
+        req.header("Accept", "application/json");
+        user.run::<DownloadUrl>(req).good().url
+    };
+
+    let signed = download_url("foo_signed");
+    assert!(signed.contains("/crates/foo_signed/foo_signed-1.0.0.crate?expires="));
+    assert!(signed.contains("&signature="));
+
+    let unsigned = download_url("foo_unsigned");
+    assert!(unsigned.ends_with("/crates/foo_unsigned/foo_unsigned-1.0.0.crate"));
+}
+
+#[test]
+fn index_files_are_filtered_per_token() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let other = app.db_new_user("other");
+    let in_index = || {
+        let index = app.upstream_repository();
+        let tree = index.head().unwrap().peel_to_tree().unwrap();
+        tree.get_path(std::path::Path::new("fo/o_/foo_index_file"))
+            .is_ok()
+    };
+
+    user.enqueue_publish(PublishBuilder::new("foo_index_file"))
+        .good();
+    app.run_pending_background_jobs();
+
+    let url = "/api/v1/crates/foo_index_file/index";
+    assert!(anon.get::<()>(url).text().contains("\"vers\":\"1.0.0\""));
+
+    user.set_visibility("foo_index_file", "private").good();
+    app.run_pending_background_jobs();
+    assert!(!in_index());
+    anon.get::<()>(url).assert_not_found();
+    other.get::<()>(url).assert_not_found();
+    assert!(token.get::<()>(url).text().contains("\"vers\":\"1.0.0\""));
+
+    // New versions and yanks of private crates stay out of the index as well
+    token
+        .enqueue_publish(PublishBuilder::new("foo_index_file").version("1.1.0"))
+        .good();
+    token
+        .delete::<OkBool>("/api/v1/crates/foo_index_file/1.0.0/yank")
+        .good();
+    app.run_pending_background_jobs();
+    assert!(!in_index());
+    let text = token.get::<()>(url).text();
+    assert!(text.contains("\"vers\":\"1.1.0\""));
+    assert!(text.contains("\"yanked\":true"));
+
+    user.set_visibility("foo_index_file", "public").good();
+    app.run_pending_background_jobs();
+    let crates = app.crates_from_index_head("fo/o_/foo_index_file");
+    assert_eq!(crates.len(), 2);
+    assert_eq!(crates[0].yanked, Some(true));
+    assert_eq!(crates[1].vers, "1.1.0");
+}
+
+#[test]
+fn private_crates_cannot_be_followed_without_access() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_follow_private", user.as_model().id).expect_build(conn);
+    });
+    user.set_visibility("foo_follow_private", "private").good();
+
+    other
+        .put::<()>("/api/v1/crates/foo_follow_private/follow", b"")
+        .assert_not_found();
+    user.put::<OkBool>("/api/v1/crates/foo_follow_private/follow", b"")
+        .good();
+}
+
+#[test]
+fn updates_of_followed_crates_are_hidden_after_losing_access() {
+    #[derive(Deserialize)]
+    struct Updates {
+        versions: Vec<EncodableVersion>,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_followed_private", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+    user.set_visibility("foo_followed_private", "private")
+        .good();
+    user.grant_access("foo_followed_private", "other").good();
+    other
+        .put::<OkBool>("/api/v1/crates/foo_followed_private/follow", b"")
+        .good();
+
+    let json: Updates = other.get("/api/v1/me/updates").good();
+    assert_eq!(json.versions.len(), 1);
+
+    user.revoke_access("foo_followed_private", "other").good();
+    let json: Updates = other.get("/api/v1/me/updates").good();
+    assert!(json.versions.is_empty());
+}
@@ -0,0 +1,153 @@
+//! Detection of new crate names that look like the names of popular crates
+//!
+//! A name is considered similar to a popular crate's name if the two only
+//! differ by a single edit (an insertion, deletion, substitution or swap of
+//! two adjacent characters), or if they look the same once characters that are
+//! easily mistaken for each other (`0` and `o`, `rn` and `m`, ...) and
+//! separators are normalized. What happens to such crates is configured by
+//! `Config::typosquat_check`.
+
+use diesel::prelude::*;
+
+use crate::models::krate::canonical_name;
+use crate::models::{Crate, NewCrateReport, ReportCategory};
+use crate::schema::{approved_crate_names, crates};
+
+/// How many of the most downloaded crates new names are compared against.
+const POPULAR_CRATES: i64 = 1000;
+
+/// Names shorter than this are only compared by their skeleton, since single
+/// edits of short names would match too many unrelated crates.
+const MIN_EDIT_DISTANCE_LENGTH: usize = 5;
+
+/// Returns the names of the popular crates that `name` is similar to.
+pub fn similar_popular_crates(conn: &PgConnection, name: &str) -> QueryResult<Vec<String>> {
+    Ok(crates::table
+        .select(crates::name)
+        .order(crates::downloads.desc())
+        .limit(POPULAR_CRATES)
+        .load::<String>(conn)?
+        .into_iter()
+        .filter(|popular| is_similar(name, popular))
+        .collect())
+}
+
+/// The warning shown to the publisher of a crate with a similar name.
+pub fn warning(name: &str, similar: &[String]) -> String {
+    let similar = similar
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "the name `{}` is similar to the name of the popular crate(s) {}",
+        name, similar
+    )
+}
+
+/// Whether the registry administrators have approved publishing a crate
+/// with this name despite its similarity to a popular crate.
+pub fn is_approved(conn: &PgConnection, name: &str) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        approved_crate_names::table.find(canonical_name(name)),
+    ))
+    .get_result(conn)
+}
+
+/// Adds the crate to the moderation queue.
+pub fn file_report(conn: &PgConnection, krate: &Crate, similar: &[String]) -> QueryResult<()> {
+    NewCrateReport {
+        crate_id: krate.id,
+        reporter_id: None,
+        category: ReportCategory::Typosquat,
+        description: &warning(&krate.name, similar),
+    }
+    .create(conn)?;
+    Ok(())
+}
+
+pub fn is_similar(name: &str, popular: &str) -> bool {
+    let name = canonical_name(name);
+    let popular = canonical_name(popular);
+    if name == popular {
+        return false;
+    }
+
+    if skeleton(&name) == skeleton(&popular) {
+        return true;
+    }
+
+    name.len().min(popular.len()) >= MIN_EDIT_DISTANCE_LENGTH
+        && within_one_edit(name.as_bytes(), popular.as_bytes())
+}
+
+/// Maps characters that look alike to the same character, and drops
+/// separators.
+fn skeleton(name: &str) -> String {
+    name.replace("rn", "m")
+        .replace("vv", "w")
+        .chars()
+        .filter(|&c| c != '_')
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | 'i' => 'l',
+            '3' => 'e',
+            '5' => 's',
+            c => c,
+        })
+        .collect()
+}
+
+/// Whether the optimal string alignment distance of `a` and `b` is at most 1.
+fn within_one_edit(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if b.len() - a.len() > 1 {
+        return false;
+    }
+
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    if a.len() == b.len() {
+        let (a, b) = (&a[prefix..], &b[prefix..]);
+        // A substitution, or a swap of two adjacent characters
+        a.is_empty()
+            || a[1..] == b[1..]
+            || (a.len() >= 2 && a[0] == b[1] && a[1] == b[0] && a[2..] == b[2..])
+    } else {
+        // An insertion into the shorter name
+        a[prefix..] == b[prefix + 1..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_edits_are_similar() {
+        assert!(is_similar("serde_jsno", "serde_json"));
+        assert!(is_similar("serde_jso", "serde_json"));
+        assert!(is_similar("serde_jsonn", "serde_json"));
+        assert!(is_similar("serde_jsom", "serde_json"));
+        assert!(!is_similar("serde_yaml", "serde_json"));
+    }
+
+    #[test]
+    fn short_names_need_to_look_alike() {
+        assert!(!is_similar("rant", "rand"));
+        assert!(is_similar("rnd", "md"));
+        assert!(is_similar("l0g", "log"));
+    }
+
+    #[test]
+    fn homoglyphs_and_separators_are_similar() {
+        assert!(is_similar("t0kio", "tokio"));
+        assert!(is_similar("serdejson", "serde_json"));
+        assert!(is_similar("hyper-rnacros", "hyper_macros"));
+    }
+
+    #[test]
+    fn the_same_crate_is_not_similar() {
+        assert!(!is_similar("serde-json", "serde_json"));
+        assert!(!is_similar("Tokio", "tokio"));
+    }
+}
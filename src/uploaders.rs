@@ -1,54 +1,121 @@
 use conduit::Request;
 use flate2::read::GzDecoder;
 use openssl::hash::{Hasher, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
 use reqwest::header;
 
 use crate::util::LimitErrorReader;
-use crate::util::{human, internal, CargoResult, ChainError, Maximums};
+use crate::util::{CargoResult, ChainError, ErrorCode, Maximums};
 
 use std::env;
-use std::fs::{self, File};
-use std::io::{Cursor, Read};
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::sync::Arc;
+use tempdir::TempDir;
 
+use crate::cdn::CdnBackend;
 use crate::middleware::app::RequestApp;
 use crate::models::Crate;
+use crate::storage::{LocalStorage, StorageBackend};
+use crate::telemetry::{Span, SpanKind};
 
 pub const CACHE_CONTROL_IMMUTABLE: &str = "public,max-age=31536000,immutable";
 
+/// Stores crate files and readmes with a storage backend, and knows where
+/// they are served from.
 #[derive(Clone, Debug)]
-pub enum Uploader {
-    /// For production usage, uploads and redirects to s3.
-    /// For test usage with `TestApp::with_proxy()`, the recording proxy is used.
-    S3 {
-        bucket: s3::Bucket,
-        cdn: Option<String>,
-    },
-
-    /// For development usage only: "uploads" crate files to `dist` and serves them
-    /// from there as well to enable local publishing and download
-    Local,
+pub struct Uploader {
+    backend: Arc<dyn StorageBackend>,
+    /// The host files are served from if they are behind a CDN
+    cdn: Option<String>,
+    /// Purges files from the CDN when they change
+    cdn_invalidation: Option<Arc<dyn CdnBackend>>,
+}
+
+/// A file in an uploaded crate, with its path relative to the crate's root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrateFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// How short-lived download URLs are signed, see `Uploader::signed_crate_location`.
+#[derive(Clone, Debug)]
+pub enum DownloadSigning {
+    /// Presigned URLs of the storage backend, using its credentials. Only S3
+    /// supports them, downloads from other backends aren't signed.
+    S3,
+
+    /// CDN URLs with an `expires` Unix timestamp and a hex encoded HMAC-SHA256
+    /// `signature` of the path and expiry appended to the query string. The
+    /// CDN is expected to verify the signature with the same shared key.
+    Cdn { key: String },
 }
 
 impl Uploader {
+    pub fn new(backend: impl StorageBackend + 'static, cdn: Option<String>) -> Self {
+        Uploader {
+            backend: Arc::new(backend),
+            cdn,
+            cdn_invalidation: None,
+        }
+    }
+
+    /// Purges files from the CDN with `backend` when versions are yanked or
+    /// crates are deleted.
+    pub fn with_cdn_invalidation(mut self, backend: Arc<dyn CdnBackend>) -> Self {
+        self.cdn_invalidation = Some(backend);
+        self
+    }
+
+    /// For development usage only: "uploads" crate files to `local_uploads`
+    /// and serves them from there as well to enable local publishing and
+    /// download.
+    pub fn local() -> Self {
+        let root = env::current_dir().unwrap().join("local_uploads");
+        Uploader::new(LocalStorage::new(root), None)
+    }
+
+    /// Returns the URL files are served from, without a trailing slash, or
+    /// `None` if they are served by the server itself.
+    pub fn public_url(&self) -> Option<String> {
+        match self.cdn {
+            Some(ref cdn) => Some(format!("https://{}", cdn)),
+            None => self.backend.public_url(),
+        }
+    }
+
     /// Returns the URL of an uploaded crate's version archive.
     ///
     /// The function doesn't check for the existence of the file.
     pub fn crate_location(&self, crate_name: &str, version: &str) -> String {
-        match *self {
-            Uploader::S3 {
-                ref bucket,
-                ref cdn,
-                ..
-            } => {
-                let host = match *cdn {
-                    Some(ref s) => s.clone(),
-                    None => bucket.host(),
-                };
-                let path = Uploader::crate_path(crate_name, version);
-                format!("https://{}/{}", host, path)
+        self.location(&Uploader::crate_path(crate_name, version))
+    }
+
+    /// Returns a URL for an uploaded crate's version archive that stops working
+    /// after the Unix timestamp `expires`.
+    ///
+    /// Files served by the server itself can't be signed, so the unsigned
+    /// location is returned for them, as well as for backends that don't
+    /// support presigned URLs.
+    pub fn signed_crate_location(
+        &self,
+        crate_name: &str,
+        version: &str,
+        signing: &DownloadSigning,
+        expires: i64,
+    ) -> String {
+        let path = Uploader::crate_path(crate_name, version);
+        let url = self.location(&path);
+        match signing {
+            DownloadSigning::S3 => self.backend.presigned_url(&path, expires).unwrap_or(url),
+            DownloadSigning::Cdn { key } if self.public_url().is_some() => {
+                let signature = cdn_signature(key, &path, expires);
+                format!("{}?expires={}&signature={}", url, expires, signature)
             }
-            Uploader::Local => format!("/{}", Uploader::crate_path(crate_name, version)),
+            DownloadSigning::Cdn { .. } => url,
         }
     }
 
@@ -56,21 +123,11 @@ impl Uploader {
     ///
     /// The function doesn't check for the existence of the file.
     pub fn readme_location(&self, crate_name: &str, version: &str) -> String {
-        match *self {
-            Uploader::S3 {
-                ref bucket,
-                ref cdn,
-                ..
-            } => {
-                let host = match *cdn {
-                    Some(ref s) => s.clone(),
-                    None => bucket.host(),
-                };
-                let path = Uploader::readme_path(crate_name, version);
-                format!("https://{}/{}", host, path)
-            }
-            Uploader::Local => format!("/{}", Uploader::readme_path(crate_name, version)),
-        }
+        self.location(&Uploader::readme_path(crate_name, version))
+    }
+
+    fn location(&self, path: &str) -> String {
+        format!("{}/{}", self.public_url().unwrap_or_default(), path)
     }
 
     /// Returns the internal path of an uploaded crate's version archive.
@@ -84,59 +141,89 @@ impl Uploader {
         format!("readmes/{}/{}-{}.html", name, name, version)
     }
 
-    /// Uploads a file using the configured uploader (either `S3`, `Local`).
+    /// Returns the internal paths of the files of a version that the CDN
+    /// caches, see `cdn::invalidate_cdn`.
+    pub(crate) fn version_paths(crate_name: &str, version: &str) -> Vec<String> {
+        vec![
+            Uploader::crate_path(crate_name, version),
+            Uploader::readme_path(crate_name, version),
+        ]
+    }
+
+    /// Returns whether files are purged from the CDN when they change.
+    pub(crate) fn invalidates_cdn(&self) -> bool {
+        self.cdn_invalidation.is_some()
+    }
+
+    /// Purges the files at `paths` from the CDN. Does nothing if invalidation
+    /// isn't configured.
+    pub(crate) fn invalidate_cdn(
+        &self,
+        client: &reqwest::Client,
+        paths: &[String],
+    ) -> CargoResult<()> {
+        match self.cdn_invalidation {
+            Some(ref backend) => {
+                let mut span = Span::start("invalidate_cdn", SpanKind::Client);
+                span.set_attribute("invalidate_cdn.paths", paths.len() as u64);
+                span.record(backend.invalidate(client, paths))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that files can be uploaded, for the readiness checks of the
+    /// server.
+    pub(crate) fn check_access(&self, client: &reqwest::Client) -> CargoResult<()> {
+        self.backend.check_access(client)
+    }
+
+    /// Uploads a file using the configured storage backend.
     ///
     /// It returns the path of the uploaded file.
     pub fn upload<R: std::io::Read + Send + 'static>(
         &self,
         client: &reqwest::Client,
         path: &str,
-        mut content: R,
+        content: R,
         content_length: u64,
         content_type: &str,
         extra_headers: Option<header::HeaderMap>,
     ) -> CargoResult<Option<String>> {
-        match *self {
-            Uploader::S3 { ref bucket, .. } => {
-                bucket
-                    .put(
-                        client,
-                        path,
-                        content,
-                        content_length,
-                        content_type,
-                        extra_headers,
-                    )
-                    .map_err(|e| internal(&format_args!("failed to upload to S3: {}", e)))?;
-                Ok(Some(String::from(path)))
-            }
-            Uploader::Local => {
-                let filename = env::current_dir().unwrap().join("local_uploads").join(path);
-                let dir = filename.parent().unwrap();
-                fs::create_dir_all(dir)?;
-                let mut file = File::create(&filename)?;
-                std::io::copy(&mut content, &mut file)?;
-                Ok(filename.to_str().map(String::from))
-            }
-        }
+        let mut span = Span::start("upload", SpanKind::Client);
+        span.set_attribute("upload.path", path);
+        span.set_attribute("upload.content_length", content_length);
+        span.record(self.backend.put(
+            client,
+            path,
+            Box::new(content),
+            content_length,
+            content_type,
+            extra_headers.unwrap_or_default(),
+        ))
     }
 
-    /// Uploads a crate and returns the checksum of the uploaded crate file.
+    /// Uploads a crate and returns the checksum of the uploaded crate file,
+    /// along with the files it contains.
+    ///
+    /// The crate file, which is the next `file_length` bytes of the request
+    /// body, is spooled to a temporary file and hashed on the way, so that
+    /// large crates aren't held in memory. It is only uploaded once its
+    /// contents have been verified.
     pub fn upload_crate(
         &self,
         req: &mut dyn Request,
         krate: &Crate,
         maximums: Maximums,
         vers: &semver::Version,
-    ) -> CargoResult<Vec<u8>> {
+        file_length: u64,
+    ) -> CargoResult<(Vec<u8>, Vec<CrateFile>)> {
         let app = Arc::clone(req.app());
         let path = Uploader::crate_path(&krate.name, &vers.to_string());
-        let mut body = Vec::new();
-        LimitErrorReader::new(req.body(), maximums.max_upload_size).read_to_end(&mut body)?;
-        verify_tarball(krate, vers, &body, maximums.max_unpack_size)?;
-        let checksum = hash(&body);
-        let content_length = body.len() as u64;
-        let content = Cursor::new(body);
+        let spool = TempDir::new("crate-upload")?;
+        let (mut file, checksum, files) =
+            spool_and_verify(req, krate, maximums, vers, file_length, &spool)?;
+        file.seek(SeekFrom::Start(0))?;
         let mut extra_headers = header::HeaderMap::new();
         extra_headers.insert(
             header::CACHE_CONTROL,
@@ -145,12 +232,57 @@ impl Uploader {
         self.upload(
             app.http_client(),
             &path,
-            content,
-            content_length,
+            file,
+            file_length,
             "application/x-tar",
             Some(extra_headers),
         )?;
-        Ok(checksum)
+        Ok((checksum, files))
+    }
+
+    /// Verifies a crate like `upload_crate`, without uploading it. Used for
+    /// dry runs of publishing.
+    pub fn verify_crate(
+        req: &mut dyn Request,
+        krate: &Crate,
+        maximums: Maximums,
+        vers: &semver::Version,
+        file_length: u64,
+    ) -> CargoResult<(Vec<u8>, Vec<CrateFile>)> {
+        let spool = TempDir::new("crate-upload")?;
+        let (_, checksum, files) =
+            spool_and_verify(req, krate, maximums, vers, file_length, &spool)?;
+        Ok((checksum, files))
+    }
+
+    /// Downloads the crate file of an uploaded version.
+    pub(crate) fn download_crate(
+        &self,
+        http_client: &reqwest::Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> CargoResult<Vec<u8>> {
+        let path = Uploader::crate_path(crate_name, vers);
+        let mut span = Span::start("download", SpanKind::Client);
+        span.set_attribute("download.path", path.as_str());
+        span.record(self.backend.get(http_client, &path))
+    }
+
+    /// Deletes the crate file and readme of a deleted version, and purges them
+    /// from the CDN.
+    pub(crate) fn delete_version_files(
+        &self,
+        http_client: &reqwest::Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> CargoResult<()> {
+        let paths = Uploader::version_paths(crate_name, vers);
+        for path in &paths {
+            let mut span = Span::start("delete", SpanKind::Client);
+            span.set_attribute("delete.path", path.as_str());
+            span.record(self.backend.delete(http_client, path))?;
+        }
+        self.invalidate_cdn(http_client, &paths)
     }
 
     pub(crate) fn upload_readme(
@@ -175,12 +307,68 @@ impl Uploader {
     }
 }
 
-fn verify_tarball(
+/// Copies the crate file of `length` bytes from `body` to a new file at
+/// `path`, and returns the file, rewound, along with the SHA256 checksum of
+/// its contents.
+fn spool_crate_file(body: &mut dyn Read, length: u64, path: &Path) -> CargoResult<(File, Vec<u8>)> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    let mut hasher = Hasher::new(MessageDigest::sha256())?;
+    let mut body = body.take(length);
+    let mut buf = [0; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let n = body.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n])?;
+        file.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    if copied != length {
+        return Err(ErrorCode::InvalidUpload.human(&format_args!(
+            "expected a crate file of {} bytes, but the upload ended after {} bytes",
+            length, copied
+        )));
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok((file, hasher.finish()?.to_vec()))
+}
+
+/// Checks that the tarball only contains files of the given crate version, and
+/// returns the regular files it contains.
+/// Spools the crate file in the request body into `spool`, and verifies it.
+/// Returns the spooled file along with its checksum and the files it contains.
+fn spool_and_verify(
+    req: &mut dyn Request,
     krate: &Crate,
+    maximums: Maximums,
     vers: &semver::Version,
-    tarball: &[u8],
+    file_length: u64,
+    spool: &TempDir,
+) -> CargoResult<(File, Vec<u8>, Vec<CrateFile>)> {
+    if file_length > maximums.max_upload_size {
+        return Err(ErrorCode::UploadTooLarge.human(&format_args!(
+            "max upload size is: {}",
+            maximums.max_upload_size
+        )));
+    }
+
+    let (file, checksum) = spool_crate_file(req.body(), file_length, &spool.path().join("crate"))?;
+    let files = verify_tarball(krate, vers, &file, maximums.max_unpack_size)?;
+    Ok((file, checksum, files))
+}
+
+fn verify_tarball<R: Read>(
+    krate: &Crate,
+    vers: &semver::Version,
+    tarball: R,
     max_unpack: u64,
-) -> CargoResult<()> {
+) -> CargoResult<Vec<CrateFile>> {
     // All our data is currently encoded with gzip
     let decoder = GzDecoder::new(tarball);
 
@@ -191,9 +379,11 @@ fn verify_tarball(
     // Use this I/O object now to take a peek inside
     let mut archive = tar::Archive::new(decoder);
     let prefix = format!("{}-{}", krate.name, vers);
+    let mut files = Vec::new();
     for entry in archive.entries()? {
         let entry = entry.chain_error(|| {
-            human("uploaded tarball is malformed or too large when decompressed")
+            ErrorCode::InvalidUpload
+                .human("uploaded tarball is malformed or too large when decompressed")
         })?;
 
         // Verify that all entries actually start with `$name-$vers/`.
@@ -202,7 +392,7 @@ fn verify_tarball(
         // as `bar-0.1.0/` source code, and this could overwrite other crates in
         // the registry!
         if !entry.path()?.starts_with(&prefix) {
-            return Err(human("invalid tarball uploaded"));
+            return Err(ErrorCode::InvalidUpload.human("invalid tarball uploaded"));
         }
 
         // Historical versions of the `tar` crate which Cargo uses internally
@@ -212,14 +402,102 @@ fn verify_tarball(
         // generate a tarball with these file types so this should work for now.
         let entry_type = entry.header().entry_type();
         if entry_type.is_hard_link() || entry_type.is_symlink() {
-            return Err(human("invalid tarball uploaded"));
+            return Err(ErrorCode::InvalidUpload.human("invalid tarball uploaded"));
+        }
+
+        if entry_type.is_file() {
+            let path = entry.path()?;
+            let path = path.strip_prefix(&prefix).unwrap_or(&*path);
+            files.push(CrateFile {
+                path: path.to_string_lossy().into_owned(),
+                size: entry.header().size()?,
+            });
         }
     }
-    Ok(())
+    Ok(files)
+}
+
+/// Signs `/<path>` and the expiry timestamp (separated by a newline) with the
+/// shared CDN key.
+fn cdn_signature(key: &str, path: &str, expires: i64) -> String {
+    let key = PKey::hmac(key.as_bytes()).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer
+        .update(format!("/{}\n{}", path, expires).as_bytes())
+        .unwrap();
+    hex::encode(signer.sign_to_vec().unwrap())
 }
 
-fn hash(data: &[u8]) -> Vec<u8> {
-    let mut hasher = Hasher::new(MessageDigest::sha256()).unwrap();
-    hasher.update(data).unwrap();
-    hasher.finish().unwrap().to_vec()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{GcsStorage, S3Storage};
+
+    fn s3_uploader(cdn: Option<&str>) -> Uploader {
+        let bucket = s3::Bucket::new(
+            String::from("bucket"),
+            None,
+            String::from("access"),
+            String::from("secret"),
+            "https",
+        );
+        Uploader::new(S3Storage::new(bucket), cdn.map(String::from))
+    }
+
+    #[test]
+    fn s3_signed_location_uses_query_string_authentication() {
+        let url = s3_uploader(None).signed_crate_location("foo", "1.0.0", &DownloadSigning::S3, 42);
+        assert!(url.starts_with(
+            "https://bucket.s3.amazonaws.com/crates/foo/foo-1.0.0.crate\
+             ?AWSAccessKeyId=access&Expires=42&Signature="
+        ));
+    }
+
+    #[test]
+    fn cdn_signed_location_depends_on_path_and_expiry() {
+        let uploader = s3_uploader(Some("static.example.com"));
+        let signing = DownloadSigning::Cdn {
+            key: String::from("key"),
+        };
+        let url = uploader.signed_crate_location("foo", "1.0.0", &signing, 42);
+        let expected_signature = cdn_signature("key", "crates/foo/foo-1.0.0.crate", 42);
+        assert_eq!(
+            url,
+            format!(
+                "https://static.example.com/crates/foo/foo-1.0.0.crate\
+                 ?expires=42&signature={}",
+                expected_signature
+            )
+        );
+        assert_ne!(
+            expected_signature,
+            cdn_signature("key", "crates/foo/foo-1.0.0.crate", 43)
+        );
+        assert_ne!(
+            expected_signature,
+            cdn_signature("key", "crates/foo/foo-1.0.1.crate", 42)
+        );
+    }
+
+    #[test]
+    fn local_signed_location_is_unsigned() {
+        let url = Uploader::local().signed_crate_location("foo", "1.0.0", &DownloadSigning::S3, 42);
+        assert_eq!(url, "/crates/foo/foo-1.0.0.crate");
+    }
+
+    #[test]
+    fn backends_without_presigned_urls_fall_back_to_unsigned_locations() {
+        let storage = GcsStorage::new(
+            String::from("bucket"),
+            String::from("access"),
+            String::from("secret"),
+            "https",
+        );
+        let uploader = Uploader::new(storage, None);
+        let url = uploader.signed_crate_location("foo", "1.0.0", &DownloadSigning::S3, 42);
+        assert_eq!(
+            url,
+            "https://bucket.storage.googleapis.com/crates/foo/foo-1.0.0.crate"
+        );
+    }
 }
@@ -6,19 +6,35 @@ use conduit::Response;
 use serde::Serialize;
 
 pub use self::errors::ChainError;
-pub use self::errors::{bad_request, human, internal, internal_error, CargoError, CargoResult};
+pub use self::errors::{
+    bad_request, human, internal, internal_error, CargoError, CargoResult, ErrorCode,
+};
 pub use self::io_util::{read_fill, read_le_u32, LimitErrorReader};
 pub use self::request_helpers::*;
 pub use self::request_proxy::RequestProxy;
 
+pub mod cidr;
 pub mod errors;
 mod io_util;
+pub mod license;
 mod request_helpers;
 mod request_proxy;
 pub mod rfc3339;
 
+/// Escapes the wildcards of `LIKE` patterns in `s`, so that it's matched
+/// literally.
+pub fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 pub fn json_response<T: Serialize>(t: &T) -> Response {
-    let json = serde_json::to_string(t).unwrap();
+    json_body_response(serde_json::to_string(t).unwrap())
+}
+
+/// Returns a response with an already serialized JSON body.
+pub fn json_body_response(json: String) -> Response {
     let mut headers = HashMap::new();
     headers.insert(
         "Content-Type".to_string(),
@@ -0,0 +1,141 @@
+//! IP address ranges in CIDR notation, like `192.0.2.0/24` or `2001:db8::/32`
+//!
+//! API tokens can be restricted to a list of ranges, see
+//! `ApiToken::allowed_ips`. A single address is accepted as the range only
+//! containing it.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = mask_u32(self.prefix_len);
+                u32::from(network) == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = mask_u128(self.prefix_len);
+                u128::from(network) == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether `ip` is in one of the `allowed` ranges. Any address is allowed if
+/// the list is empty, and none if `ip` isn't a valid address.
+pub fn is_allowed(allowed: &[String], ip: &str) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    let ip = match ip.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => return false,
+    };
+    allowed
+        .iter()
+        .filter_map(|range| range.parse::<Cidr>().ok())
+        .any(|range| range.contains(ip))
+}
+
+fn mask_u32(prefix_len: u8) -> u32 {
+    match prefix_len {
+        0 => 0,
+        len => !0 << (32 - u32::from(len)),
+    }
+}
+
+fn mask_u128(prefix_len: u8) -> u128 {
+    match prefix_len {
+        0 => 0,
+        len => !0 << (128 - u32::from(len)),
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parses a range, setting the bits of the address outside of the prefix
+    /// to zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid IP address or CIDR range: {}", s);
+        let (address, prefix_len) = match s.find('/') {
+            Some(idx) => (&s[..idx], Some(&s[(idx + 1)..])),
+            None => (s, None),
+        };
+        let address = address.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+        let max_len = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.trim().parse::<u8>().map_err(|_| invalid())?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+
+        let network = match address {
+            IpAddr::V4(ip) => IpAddr::V4((u32::from(ip) & mask_u32(prefix_len)).into()),
+            IpAddr::V6(ip) => IpAddr::V6((u128::from(ip) & mask_u128(prefix_len)).into()),
+        };
+        Ok(Cidr {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_allowed, Cidr};
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges_are_normalized() {
+        assert_eq!(cidr("192.0.2.0/24").to_string(), "192.0.2.0/24");
+        assert_eq!(cidr("192.0.2.17/24").to_string(), "192.0.2.0/24");
+        assert_eq!(cidr("192.0.2.17").to_string(), "192.0.2.17/32");
+        assert_eq!(cidr("2001:db8::1/32").to_string(), "2001:db8::/32");
+        assert_eq!(cidr("0.0.0.0/0").to_string(), "0.0.0.0/0");
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        for s in &[
+            "",
+            "192.0.2.0/33",
+            "2001:db8::/129",
+            "192.0.2/24",
+            "example.com",
+        ] {
+            assert!(s.parse::<Cidr>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn addresses_in_the_ranges_are_allowed() {
+        let allowed = vec!["192.0.2.0/24".to_string(), "2001:db8::/32".to_string()];
+        assert!(is_allowed(&allowed, "192.0.2.200"));
+        assert!(is_allowed(&allowed, "2001:db8:1::5"));
+        assert!(!is_allowed(&allowed, "192.0.3.1"));
+        assert!(!is_allowed(&allowed, "2001:db9::1"));
+        assert!(!is_allowed(&allowed, "not an address"));
+        assert!(is_allowed(&[], "192.0.3.1"));
+    }
+}
@@ -5,18 +5,165 @@ use std::fmt;
 use chrono::NaiveDateTime;
 use conduit::Response;
 use diesel::result::Error as DieselError;
+use serde::{Serialize, Serializer};
 
 use crate::util::json_response;
 
 #[derive(Serialize)]
 struct StringError {
     detail: String,
+    code: ErrorCode,
 }
 #[derive(Serialize)]
 struct Bad {
     errors: Vec<StringError>,
 }
 
+// =============================================================================
+// Error codes
+
+/// A machine readable identifier of an error, sent as the `code` of the
+/// errors in responses next to the human readable `detail`. Clients can
+/// branch on the code, so the strings must never change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The request is invalid in a way no other code describes
+    InvalidRequest,
+    /// The body of the request isn't the expected JSON
+    InvalidJson,
+    /// A query or path parameter is invalid
+    InvalidParameter,
+    InvalidEmail,
+    /// Publishing needs a verified email address
+    EmailUnverified,
+    /// A version number isn't valid semver
+    InvalidVersion,
+    /// A field of a crate, like its description or a URL, is invalid
+    InvalidField,
+    /// The uploaded crate file or its metadata is invalid
+    InvalidUpload,
+    UploadTooLarge,
+    InvalidDependency,
+    ReservedName,
+    /// Publishing the crate needs the approval of the administrators
+    ApprovalRequired,
+    /// The crate name can't be used yet, after the crate was deleted
+    NameUnavailable,
+    AlreadyExists,
+    /// The request conflicts with the current state of the resource
+    Conflict,
+    /// The crate was locked by the administrators
+    CrateLocked,
+    NotFound,
+    /// The request needs a logged in user
+    Unauthorized,
+    /// The user isn't an owner of the crate, or doesn't have enough rights
+    OwnerMismatch,
+    /// The user acts on behalf of another user
+    UserMismatch,
+    AdminRequired,
+    TeamMembershipRequired,
+    /// GitHub denied access with the token of the user
+    GithubAccessDenied,
+    RateLimited,
+    ReadOnlyMode,
+    ServiceUnavailable,
+}
+
+impl ErrorCode {
+    /// Every code, in the order they are documented in.
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::InvalidRequest,
+        ErrorCode::InvalidJson,
+        ErrorCode::InvalidParameter,
+        ErrorCode::InvalidEmail,
+        ErrorCode::EmailUnverified,
+        ErrorCode::InvalidVersion,
+        ErrorCode::InvalidField,
+        ErrorCode::InvalidUpload,
+        ErrorCode::UploadTooLarge,
+        ErrorCode::InvalidDependency,
+        ErrorCode::ReservedName,
+        ErrorCode::ApprovalRequired,
+        ErrorCode::NameUnavailable,
+        ErrorCode::AlreadyExists,
+        ErrorCode::Conflict,
+        ErrorCode::CrateLocked,
+        ErrorCode::NotFound,
+        ErrorCode::Unauthorized,
+        ErrorCode::OwnerMismatch,
+        ErrorCode::UserMismatch,
+        ErrorCode::AdminRequired,
+        ErrorCode::TeamMembershipRequired,
+        ErrorCode::GithubAccessDenied,
+        ErrorCode::RateLimited,
+        ErrorCode::ReadOnlyMode,
+        ErrorCode::ServiceUnavailable,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::InvalidJson => "invalid_json",
+            ErrorCode::InvalidParameter => "invalid_parameter",
+            ErrorCode::InvalidEmail => "invalid_email",
+            ErrorCode::EmailUnverified => "email_unverified",
+            ErrorCode::InvalidVersion => "invalid_version",
+            ErrorCode::InvalidField => "invalid_field",
+            ErrorCode::InvalidUpload => "invalid_upload",
+            ErrorCode::UploadTooLarge => "upload_too_large",
+            ErrorCode::InvalidDependency => "invalid_dependency",
+            ErrorCode::ReservedName => "reserved_name",
+            ErrorCode::ApprovalRequired => "approval_required",
+            ErrorCode::NameUnavailable => "name_unavailable",
+            ErrorCode::AlreadyExists => "already_exists",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::CrateLocked => "crate_locked",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::OwnerMismatch => "owner_mismatch",
+            ErrorCode::UserMismatch => "user_mismatch",
+            ErrorCode::AdminRequired => "admin_required",
+            ErrorCode::TeamMembershipRequired => "team_membership_required",
+            ErrorCode::GithubAccessDenied => "github_access_denied",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::ReadOnlyMode => "read_only_mode",
+            ErrorCode::ServiceUnavailable => "service_unavailable",
+        }
+    }
+
+    /// Returns a `human` error with this code.
+    pub fn human<S: ToString + ?Sized>(self, error: &S) -> Box<dyn CargoError> {
+        Box::new(ConcreteCargoError {
+            description: error.to_string(),
+            detail: None,
+            cause: None,
+            human: true,
+            code: self,
+        })
+    }
+
+    /// Returns a `bad_request` error with this code.
+    pub fn bad_request<S: ToString + ?Sized>(self, error: &S) -> Box<dyn CargoError> {
+        Box::new(BadRequest {
+            detail: error.to_string(),
+            code: self,
+        })
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
 // =============================================================================
 // CargoError trait
 
@@ -31,6 +178,7 @@ pub trait CargoError: Send + fmt::Display + fmt::Debug + 'static {
             Some(json_response(&Bad {
                 errors: vec![StringError {
                     detail: self.description().to_string(),
+                    code: self.code(),
                 }],
             }))
         } else {
@@ -40,6 +188,10 @@ pub trait CargoError: Send + fmt::Display + fmt::Debug + 'static {
     fn human(&self) -> bool {
         false
     }
+    /// The code of the error in its response, if it is `human`.
+    fn code(&self) -> ErrorCode {
+        ErrorCode::InvalidRequest
+    }
 
     fn get_type_id(&self) -> TypeId {
         TypeId::of::<Self>()
@@ -78,6 +230,9 @@ impl CargoError for Box<dyn CargoError> {
     fn human(&self) -> bool {
         (**self).human()
     }
+    fn code(&self) -> ErrorCode {
+        (**self).code()
+    }
     fn response(&self) -> Option<Response> {
         (**self).response()
     }
@@ -155,6 +310,9 @@ impl<E: CargoError> CargoError for ChainedError<E> {
     fn human(&self) -> bool {
         self.error.human()
     }
+    fn code(&self) -> ErrorCode {
+        self.error.code()
+    }
 }
 
 impl<E: CargoError> fmt::Display for ChainedError<E> {
@@ -186,6 +344,7 @@ struct ConcreteCargoError {
     detail: Option<String>,
     cause: Option<Box<dyn CargoError>>,
     human: bool,
+    code: ErrorCode,
 }
 
 impl fmt::Display for ConcreteCargoError {
@@ -208,6 +367,9 @@ impl CargoError for ConcreteCargoError {
     fn human(&self) -> bool {
         self.human
     }
+    fn code(&self) -> ErrorCode {
+        self.code
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -222,11 +384,16 @@ impl CargoError for NotFound {
         let mut response = json_response(&Bad {
             errors: vec![StringError {
                 detail: "Not Found".to_string(),
+                code: ErrorCode::NotFound,
             }],
         });
         response.status = (404, "Not Found");
         Some(response)
     }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::NotFound
+    }
 }
 
 impl fmt::Display for NotFound {
@@ -247,11 +414,16 @@ impl CargoError for Unauthorized {
         let mut response = json_response(&Bad {
             errors: vec![StringError {
                 detail: "must be logged in to perform that action".to_string(),
+                code: ErrorCode::Unauthorized,
             }],
         });
         response.status = (403, "Forbidden");
         Some(response)
     }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Unauthorized
+    }
 }
 
 impl fmt::Display for Unauthorized {
@@ -261,27 +433,35 @@ impl fmt::Display for Unauthorized {
 }
 
 #[derive(Debug)]
-struct BadRequest(String);
+struct BadRequest {
+    detail: String,
+    code: ErrorCode,
+}
 
 impl CargoError for BadRequest {
     fn description(&self) -> &str {
-        self.0.as_ref()
+        self.detail.as_ref()
     }
 
     fn response(&self) -> Option<Response> {
         let mut response = json_response(&Bad {
             errors: vec![StringError {
-                detail: self.0.clone(),
+                detail: self.detail.clone(),
+                code: self.code,
             }],
         });
         response.status = (400, "Bad Request");
         Some(response)
     }
+
+    fn code(&self) -> ErrorCode {
+        self.code
+    }
 }
 
 impl fmt::Display for BadRequest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.detail.fmt(f)
     }
 }
 
@@ -291,6 +471,7 @@ pub fn internal_error(error: &str, detail: &str) -> Box<dyn CargoError> {
         detail: Some(detail.to_string()),
         cause: None,
         human: false,
+        code: ErrorCode::InvalidRequest,
     })
 }
 
@@ -300,16 +481,14 @@ pub fn internal<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
         detail: None,
         cause: None,
         human: false,
+        code: ErrorCode::InvalidRequest,
     })
 }
 
+/// An error caused by the request, returned to cargo with a 200. Its code is
+/// `invalid_request`, use `ErrorCode::human` for anything more specific.
 pub fn human<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
-    Box::new(ConcreteCargoError {
-        description: error.to_string(),
-        detail: None,
-        cause: None,
-        human: true,
-    })
+    ErrorCode::InvalidRequest.human(error)
 }
 
 /// This is intended to be used for errors being sent back to the Ember
@@ -320,7 +499,7 @@ pub fn human<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
 /// Since this is going back to the UI these errors are treated the same as
 /// `human` errors, other than the HTTP status code.
 pub fn bad_request<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
-    Box::new(BadRequest(error.to_string()))
+    ErrorCode::InvalidRequest.bad_request(error)
 }
 
 #[derive(Debug)]
@@ -368,6 +547,7 @@ impl CargoError for ReadOnlyMode {
                 detail: "Crates.io is currently in read-only mode for maintenance. \
                          Please try again later."
                     .to_string(),
+                code: ErrorCode::ReadOnlyMode,
             }],
         });
         response.status = (503, "Service Unavailable");
@@ -377,6 +557,10 @@ impl CargoError for ReadOnlyMode {
     fn human(&self) -> bool {
         true
     }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::ReadOnlyMode
+    }
 }
 
 impl fmt::Display for ReadOnlyMode {
@@ -387,6 +571,9 @@ impl fmt::Display for ReadOnlyMode {
 
 #[derive(Debug, Clone, Copy)]
 pub struct TooManyRequests {
+    /// What the user did too often, as in "You have {action} in a short
+    /// period of time".
+    pub action: &'static str,
     pub retry_after: NaiveDateTime,
 }
 
@@ -402,11 +589,12 @@ impl CargoError for TooManyRequests {
         let mut response = json_response(&Bad {
             errors: vec![StringError {
                 detail: format!(
-                    "You have published too many crates in a \
-                     short period of time. Please try again after {} or email \
-                     help@crates.io to have your limit increased.",
-                    retry_after
+                    "You have {} in a short period of time. Please try again \
+                     after {} or email help@crates.io to have your limit \
+                     increased.",
+                    self.action, retry_after
                 ),
+                code: ErrorCode::RateLimited,
             }],
         });
         response.status = (429, "TOO MANY REQUESTS");
@@ -419,6 +607,10 @@ impl CargoError for TooManyRequests {
     fn human(&self) -> bool {
         true
     }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::RateLimited
+    }
 }
 
 impl fmt::Display for TooManyRequests {
@@ -426,3 +618,105 @@ impl fmt::Display for TooManyRequests {
         "Too many requests".fmt(f)
     }
 }
+
+/// An API token made more requests today than its daily quota allows.
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaExceeded {
+    pub daily_quota: i32,
+    /// When the quota is reset, at the start of the next day
+    pub retry_after: NaiveDateTime,
+}
+
+impl CargoError for QuotaExceeded {
+    fn description(&self) -> &str {
+        "daily quota exceeded"
+    }
+
+    fn response(&self) -> Option<Response> {
+        const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+        let retry_after = self.retry_after.format(HTTP_DATE_FORMAT);
+
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: format!(
+                    "This API token can only make {} requests per day. Please \
+                     try again after {} or create a token with a higher quota.",
+                    self.daily_quota, retry_after
+                ),
+                code: ErrorCode::RateLimited,
+            }],
+        });
+        response.status = (429, "TOO MANY REQUESTS");
+        response
+            .headers
+            .insert("Retry-After".into(), vec![retry_after.to_string()]);
+        Some(response)
+    }
+
+    fn human(&self) -> bool {
+        true
+    }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::RateLimited
+    }
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "Daily quota exceeded".fmt(f)
+    }
+}
+
+/// No database connection could be checked out in time, because the pool is
+/// exhausted or the database is down, or because the request has used up its
+/// time budget for the database.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseUnavailable;
+
+impl DatabaseUnavailable {
+    /// How long clients are asked to wait before retrying.
+    const RETRY_AFTER_SECONDS: u64 = 5;
+}
+
+impl CargoError for DatabaseUnavailable {
+    fn description(&self) -> &str {
+        "database unavailable"
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: "The service is overloaded. Please try again later.".to_string(),
+                code: ErrorCode::ServiceUnavailable,
+            }],
+        });
+        response.status = (503, "Service Unavailable");
+        response.headers.insert(
+            "Retry-After".into(),
+            vec![Self::RETRY_AFTER_SECONDS.to_string()],
+        );
+        Some(response)
+    }
+
+    fn human(&self) -> bool {
+        true
+    }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::ServiceUnavailable
+    }
+}
+
+impl fmt::Display for DatabaseUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "No database connection available".fmt(f)
+    }
+}
+
+/// Returns the response of an error returned by a middleware, if it has one.
+/// Errors of controllers are turned into their responses by the router.
+pub(crate) fn std_error_response(err: &(dyn Error + Send + 'static)) -> Option<Response> {
+    err.downcast_ref::<CargoErrToStdErr>()
+        .and_then(|err| err.0.response())
+}
@@ -0,0 +1,161 @@
+//! Validation and normalization of SPDX license expressions
+//!
+//! Crates have historically been allowed to separate alternative licenses with
+//! `/` (as in `MIT/Apache-2.0`), and operators are often written in lower case.
+//! Both are accepted, but the expression is stored in its canonical SPDX form
+//! (`MIT OR Apache-2.0`) so that searching and displaying licenses doesn't
+//! have to deal with every spelling.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    License(&'a str),
+    With,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Token::License(id) => f.write_str(id),
+            Token::With => f.write_str("WITH"),
+            Token::And => f.write_str("AND"),
+            Token::Or => f.write_str("OR"),
+            Token::Open => f.write_str("("),
+            Token::Close => f.write_str(")"),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for word in expr.split_whitespace() {
+        let mut rest = word;
+        while !rest.is_empty() {
+            let end = rest.find(|c| c == '(' || c == ')' || c == '/');
+            let (head, tail) = match end {
+                Some(0) => rest.split_at(1),
+                Some(end) => rest.split_at(end),
+                None => (rest, ""),
+            };
+            tokens.push(match head {
+                "(" => Token::Open,
+                ")" => Token::Close,
+                "/" => Token::Or,
+                _ if head.eq_ignore_ascii_case("with") => Token::With,
+                _ if head.eq_ignore_ascii_case("and") => Token::And,
+                _ if head.eq_ignore_ascii_case("or") => Token::Or,
+                _ => Token::License(head),
+            });
+            rest = tail;
+        }
+    }
+    tokens
+}
+
+/// Validates a license expression and returns it in its canonical form.
+///
+/// The returned error describes what is wrong with the expression, and is
+/// meant to be shown to the user as is.
+pub fn normalize(expr: &str) -> Result<String, String> {
+    let tokens = tokenize(expr);
+    let mut normalized = String::new();
+    let mut depth = 0;
+    let mut expect_license = true;
+    let mut previous = None;
+
+    let mut iter = tokens.iter().peekable();
+    while let Some(&token) = iter.next() {
+        match token {
+            Token::License(id) if expect_license => {
+                match iter.peek() {
+                    Some(Token::With) => {
+                        iter.next();
+                        let exception = match iter.next() {
+                            Some(Token::License(exception)) => exception,
+                            _ => {
+                                return Err(format!(
+                                    "expected a license exception after `{} WITH`",
+                                    id
+                                ))
+                            }
+                        };
+                        let with = format!("{} WITH {}", id, exception);
+                        validate(&with)?;
+                        normalized.push_str(&with);
+                    }
+                    _ => {
+                        validate(id)?;
+                        normalized.push_str(id);
+                    }
+                }
+                expect_license = false;
+            }
+            Token::Open if expect_license => {
+                depth += 1;
+                normalized.push('(');
+            }
+            Token::Close if !expect_license && depth > 0 => {
+                depth -= 1;
+                normalized.push(')');
+            }
+            Token::And | Token::Or if !expect_license => {
+                normalized.push_str(&format!(" {} ", token));
+                expect_license = true;
+            }
+            _ => {
+                return Err(match previous {
+                    Some(previous) => format!("unexpected `{}` after `{}`", token, previous),
+                    None => format!("unexpected `{}`", token),
+                });
+            }
+        }
+        previous = Some(token);
+    }
+
+    if expect_license || depth > 0 {
+        return Err(match previous {
+            Some(_) => format!("incomplete license expression `{}`", expr.trim()),
+            None => String::from("empty license expression"),
+        });
+    }
+    Ok(normalized)
+}
+
+fn validate(expr: &str) -> Result<(), String> {
+    license_exprs::validate_license_expr(expr).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn normalizes_valid_expressions() {
+        assert_eq!(normalize("MIT"), Ok("MIT".into()));
+        assert_eq!(normalize("MIT/Apache-2.0"), Ok("MIT OR Apache-2.0".into()));
+        assert_eq!(
+            normalize("MIT or  Apache-2.0"),
+            Ok("MIT OR Apache-2.0".into())
+        );
+        assert_eq!(
+            normalize("( MIT and BSD-3-Clause ) OR GPL-2.0 WITH Classpath-exception-2.0"),
+            Ok("(MIT AND BSD-3-Clause) OR GPL-2.0 WITH Classpath-exception-2.0".into())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(normalize("").is_err());
+        assert!(normalize("MIT OR").is_err());
+        assert!(normalize("MIT Apache-2.0").is_err());
+        assert!(normalize("(MIT OR Apache-2.0").is_err());
+        assert!(normalize("MIT)").is_err());
+        assert!(normalize("Apache-2.0 WITH").is_err());
+        assert!(normalize("MIT OR Foobar").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-//! A helper that wraps a request and can overwrite either the path or the method.
+//! A helper that wraps a request and can overwrite either the path, the method or the body.
 
 use std::{io::Read, net::SocketAddr};
 
@@ -13,6 +13,7 @@ pub struct RequestProxy<'a> {
     other: RequestMutRef<'a>,
     path: Option<&'a str>,
     method: Option<conduit::Method>,
+    body: Option<(&'a mut dyn Read, u64)>,
 }
 
 impl<'a> RequestProxy<'a> {
@@ -22,6 +23,7 @@ impl<'a> RequestProxy<'a> {
             other: req,
             path: Some(path),
             method: None, // Defer to original request
+            body: None,
         }
     }
 
@@ -31,6 +33,21 @@ impl<'a> RequestProxy<'a> {
             other: req,
             path: None, // Defer to original request
             method: Some(method),
+            body: None,
+        }
+    }
+
+    /// Wrap a request and overwrite the body with `body` of `content_length` bytes.
+    pub(crate) fn replace_body(
+        req: RequestMutRef<'a>,
+        body: &'a mut dyn Read,
+        content_length: u64,
+    ) -> Self {
+        RequestProxy {
+            other: req,
+            path: None,
+            method: None,
+            body: Some((body, content_length)),
         }
     }
 }
@@ -45,6 +62,20 @@ impl<'a> Request for RequestProxy<'a> {
         self.path.unwrap_or_else(|| self.other.path())
     }
 
+    fn content_length(&self) -> Option<u64> {
+        match self.body {
+            Some((_, content_length)) => Some(content_length),
+            None => self.other.content_length(),
+        }
+    }
+
+    fn body(&mut self) -> &mut dyn Read {
+        match self.body {
+            Some((ref mut body, _)) => &mut **body,
+            None => self.other.body(),
+        }
+    }
+
     // Pass-through
     fn http_version(&self) -> semver::Version {
         self.other.http_version()
@@ -67,15 +98,9 @@ impl<'a> Request for RequestProxy<'a> {
     fn remote_addr(&self) -> SocketAddr {
         self.other.remote_addr()
     }
-    fn content_length(&self) -> Option<u64> {
-        self.other.content_length()
-    }
     fn headers(&self) -> &dyn conduit::Headers {
         self.other.headers()
     }
-    fn body(&mut self) -> &mut dyn Read {
-        self.other.body()
-    }
     fn extensions(&self) -> &conduit::Extensions {
         self.other.extensions()
     }
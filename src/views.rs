@@ -1,7 +1,9 @@
 use chrono::NaiveDateTime;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::models::DependencyKind;
+use crate::models::{
+    ClaimStatus, CrateLinks, DependencyKind, ReportCategory, ReservedNamePatternKind,
+};
 use crate::util::rfc3339;
 
 #[derive(PartialEq, Debug, Serialize, Deserialize)]
@@ -10,6 +12,16 @@ pub struct EncodableBadge {
     pub attributes: HashMap<String, Option<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableBuildVerification {
+    pub verifier: String,
+    pub reproducible: bool,
+    pub checksum: String,
+    pub log_url: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub updated_at: NaiveDateTime,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableCategory {
     pub id: String,
@@ -64,6 +76,76 @@ pub struct EncodableDependency {
     pub downloads: i32,
 }
 
+/// The transitive dependencies of a version, as returned by the
+/// `dependency_graph` endpoint.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDependencyGraph {
+    pub nodes: Vec<EncodableDependencyGraphNode>,
+    pub edges: Vec<EncodableDependencyGraphEdge>,
+    /// Whether dependencies were left out because the graph got too large.
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDependencyGraphNode {
+    /// `name@version`, used to refer to the node from edges.
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// Whether the version is only depended on through optional dependencies.
+    pub optional: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDependencyGraphEdge {
+    pub from: String,
+    pub to: String,
+    pub req: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDownloadRollup {
+    pub period_start: String,
+    pub downloads: i64,
+}
+
+/// The serialization format for the `VersionAdoption` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersionAdoption {
+    pub num: String,
+    pub downloads: i64,
+    /// The share of the downloads of the crate, between 0 and 1
+    pub share: f64,
+}
+
+/// The adoption of a semver compatible series of versions, like `1` or
+/// `0.3`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableSeriesAdoption {
+    pub series: String,
+    pub downloads: i64,
+    pub share: f64,
+}
+
+/// The most downloaded and most recently updated crates of a category or
+/// keyword.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableTopCrates {
+    pub most_downloaded: Vec<EncodableTopCrate>,
+    pub recently_updated: Vec<EncodableCrate>,
+    /// The start of the first week whose downloads are counted
+    pub downloads_since: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableTopCrate {
+    #[serde(rename = "crate")]
+    pub krate: EncodableCrate,
+    pub downloads: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableVersionDownload {
     pub version: i32,
@@ -71,6 +153,184 @@ pub struct EncodableVersionDownload {
     pub date: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableVersionChecksums {
+    /// Hex encoded, missing for versions that haven't been backfilled yet
+    pub sha256: Option<String>,
+    /// Hex encoded, missing until computed by the `backfill_checksums` job
+    pub sha512: Option<String>,
+}
+
+/// Where a version was published from, see `models::VersionProvenance`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableVersionProvenance {
+    pub provider: String,
+    pub run_url: Option<String>,
+    pub commit_sha: String,
+    /// Whether the commit exists in the repository of the crate, `null` if
+    /// it wasn't or couldn't be checked
+    pub verified: Option<bool>,
+    pub verified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableVersionFile {
+    pub path: String,
+    pub size: i64,
+}
+
+/// The differences between two versions of a crate, see
+/// `controllers::version::diff`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersionDiff {
+    pub from: String,
+    pub to: String,
+    pub dependencies: EncodableDependencyDiff,
+    pub features: EncodableFeatureDiff,
+    pub files: EncodableFileDiff,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDependencyDiff {
+    pub added: Vec<EncodableDependency>,
+    pub removed: Vec<EncodableDependency>,
+    /// Dependencies on the same crate, of the same kind and for the same
+    /// target, whose requirement or features changed
+    pub changed: Vec<EncodableDependencyChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDependencyChange {
+    pub from: EncodableDependency,
+    pub to: EncodableDependency,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableFeatureDiff {
+    pub added: BTreeMap<String, Vec<String>>,
+    pub removed: BTreeMap<String, Vec<String>>,
+    pub changed: BTreeMap<String, EncodableFeatureChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableFeatureChange {
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableFileDiff {
+    pub added: Vec<EncodableVersionFile>,
+    pub removed: Vec<EncodableVersionFile>,
+    pub changed: Vec<EncodableFileChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableFileChange {
+    pub path: String,
+    pub from_size: i64,
+    pub to_size: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableNameClaim {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// The login of the user who wants to take over the crate
+    pub claimant: String,
+    pub reason: String,
+    pub status: ClaimStatus,
+    pub response: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339::option")]
+    pub responded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrateReport {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// The login of the user who filed the report, `None` for reports filed
+    /// by the registry itself
+    pub reporter: Option<String>,
+    pub category: ReportCategory,
+    pub description: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableReservedNamePattern {
+    pub id: i32,
+    pub kind: ReservedNamePatternKind,
+    pub pattern: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableScanFinding {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub version: String,
+    pub path: String,
+    pub kind: String,
+    pub detail: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDownloadAnomaly {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub downloads: i64,
+    pub expected_downloads: i64,
+    pub excluded: bool,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableBackgroundJob {
+    pub id: i64,
+    pub job_type: String,
+    pub data: serde_json::Value,
+    pub retries: i32,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub next_attempt_at: NaiveDateTime,
+    pub trace_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDeadLetter {
+    pub id: i64,
+    pub job_id: i64,
+    pub job_type: String,
+    pub data: serde_json::Value,
+    pub retries: i32,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub dead_at: NaiveDateTime,
+    pub trace_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableJobPolicy {
+    pub job_type: String,
+    pub priority: i32,
+    pub max_attempts: Option<i32>,
+    pub backoff_seconds: i32,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableKeyword {
     pub id: String,
@@ -80,6 +340,46 @@ pub struct EncodableKeyword {
     pub crates_cnt: i32,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodablePublishSession {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    /// The length of the whole publish request body
+    pub length: i32,
+    /// How many bytes were received, which is also the offset of the next chunk
+    pub received: i32,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    /// When the session is deleted unless another chunk is received
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrateList {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    /// The login of the user curating the list
+    pub user: String,
+    /// The names of the crates in the list
+    pub crates: Vec<String>,
+    pub followers: i64,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableKeywordAlias {
+    pub alias: String,
+    pub keyword: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableCrate {
     pub id: String,
@@ -89,6 +389,7 @@ pub struct EncodableCrate {
     pub versions: Option<Vec<i32>>,
     pub keywords: Option<Vec<String>>,
     pub categories: Option<Vec<String>>,
+    /// Deprecated in favor of `project_links`
     pub badges: Option<Vec<EncodableBadge>>,
     #[serde(with = "rfc3339")]
     pub created_at: NaiveDateTime,
@@ -97,10 +398,16 @@ pub struct EncodableCrate {
     pub recent_downloads: Option<i64>,
     // NOTE: Used by shields.io, altering `max_version` requires a PR with shields.io
     pub max_version: String,
+    /// The highest version that isn't a pre-release, if any
+    pub max_stable_version: Option<String>,
     pub description: Option<String>,
     pub homepage: Option<String>,
     pub documentation: Option<String>,
     pub repository: Option<String>,
+    /// Whether an owner proved that they control the repository
+    pub repository_verified: bool,
+    /// The CI, chat, sponsoring and changelog links set by the owners
+    pub project_links: CrateLinks,
     pub links: EncodableCrateLinks,
     pub exact_match: bool,
 }
@@ -125,6 +432,85 @@ pub struct EncodableOwner {
     pub avatar: Option<String>,
 }
 
+/// The serialization format for the `Namespace` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableNamespace {
+    pub name: String,
+    /// The login of the team the namespace belongs to
+    pub team: String,
+    /// The number of public crates in the namespace
+    pub crates: i64,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+/// The serialization format for the `PendingCrate` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodablePendingCrate {
+    pub name: String,
+    /// The version the crate was published with
+    pub version: String,
+    /// The login of the user who published the crate
+    pub publisher: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+/// The serialization format for the `PendingOwnerRemoval` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodablePendingOwnerRemoval {
+    pub id: i32,
+    pub owner: String,
+    pub requested_by: String,
+    #[serde(with = "rfc3339")]
+    pub requested_at: NaiveDateTime,
+    #[serde(with = "rfc3339::option")]
+    pub effective_at: Option<NaiveDateTime>,
+}
+
+/// The serialization format for the `CrateFunding` model.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncodableCrateFunding {
+    pub platform: String,
+    pub url: String,
+}
+
+/// The serialization format for the `CrateSuccessor` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrateSuccessor {
+    pub crate_id: i32,
+    pub crate_name: String,
+    pub successor: String,
+    pub designated_by: String,
+    pub inactive_months: i32,
+    pub accepted: bool,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+/// The serialization format for the `Identity` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableIdentity {
+    pub provider: String,
+    pub login: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+/// The serialization format for the `Session` model.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableSession {
+    pub id: i32,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub last_used_at: NaiveDateTime,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
+
 #[derive(Serialize, Debug)]
 pub struct EncodableTeam {
     pub id: i32,
@@ -147,6 +533,16 @@ pub struct EncodableApiTokenWithToken {
     pub created_at: NaiveDateTime,
     #[serde(with = "rfc3339::option")]
     pub last_used_at: Option<NaiveDateTime>,
+    pub daily_quota: Option<i32>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// The number of requests made with an API token on a day.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct EncodableApiTokenUsage {
+    pub date: String,
+    pub requests: i32,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -156,10 +552,37 @@ pub struct OwnedCrate {
     pub email_notifications: bool,
 }
 
+/// A crate of the current user, as shown on their dashboard.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDashboardCrate {
+    pub id: i32,
+    pub name: String,
+    pub max_version: Option<String>,
+    pub downloads: i32,
+    pub recent_downloads: i64,
+    /// The number of versions that aren't yanked and have an advisory
+    pub advisories: i64,
+    #[serde(with = "rfc3339")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// Everything the dashboard of the current user shows, see `GET /me/dashboard`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableDashboard {
+    pub crates: Vec<EncodableDashboardCrate>,
+    pub crate_owner_invitations: Vec<EncodableCrateOwnerInvitation>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub email_verification_sent: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableMe {
     pub user: EncodablePrivateUser,
     pub owned_crates: Vec<OwnedCrate>,
+    /// Whether the user gets the weekly email with the new releases of the
+    /// crates they follow
+    pub update_digest: bool,
 }
 
 /// The serialization format for the `User` model.
@@ -209,6 +632,10 @@ pub struct EncodableVersion {
     pub links: EncodableVersionLinks,
     pub crate_size: Option<i32>,
     pub published_by: Option<EncodablePublicUser>,
+    pub advisory: Option<String>,
+    pub reproducible: Option<bool>,
+    pub rust_version: Option<String>,
+    pub docs_built: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -225,6 +652,16 @@ pub struct GoodCrate {
     pub warnings: PublishWarnings,
 }
 
+/// The response to a dry run of publishing, see `controllers::krate::publish`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublishDryRun {
+    #[serde(rename = "crate")]
+    pub krate: EncodableCrate,
+    /// The entry the version would get in the index
+    pub index_entry: crate::git::Crate,
+    pub warnings: PublishWarnings,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PublishWarnings {
     pub invalid_categories: Vec<String>,
@@ -235,6 +672,8 @@ pub struct PublishWarnings {
 pub mod krate_publish;
 pub use self::krate_publish::{EncodableCrateDependency, EncodableCrateUpload};
 
+pub mod v2;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -313,6 +752,10 @@ mod tests {
             },
             crate_size: Some(1234),
             published_by: None,
+            advisory: None,
+            reproducible: None,
+            rust_version: None,
+            docs_built: None,
         };
         let json = serde_json::to_string(&ver).unwrap();
         assert!(json
@@ -339,10 +782,13 @@ mod tests {
             downloads: 0,
             recent_downloads: None,
             max_version: "".to_string(),
+            max_stable_version: None,
             description: None,
             homepage: None,
             documentation: None,
             repository: None,
+            repository_verified: false,
+            project_links: CrateLinks::default(),
             links: EncodableCrateLinks {
                 version_downloads: "".to_string(),
                 versions: None,
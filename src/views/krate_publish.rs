@@ -2,7 +2,7 @@
 //! and manages the serialising and deserialising of this information
 //! to and from structs. The serlializing is only utilised in
 //! integration tests.
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
@@ -32,8 +32,14 @@ pub struct EncodableCrateUpload {
     pub license_file: Option<String>,
     pub repository: Option<String>,
     pub badges: Option<HashMap<String, HashMap<String, String>>>,
+    /// The `[funding]` section of the manifest, mapping platforms to URLs.
+    /// Funding set through the API is kept if it is left out.
+    #[serde(default)]
+    pub funding: Option<BTreeMap<String, String>>,
     #[serde(default)]
     pub links: Option<String>,
+    #[serde(default)]
+    pub rust_version: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Hash, Serialize, Debug, Deref)]
@@ -0,0 +1,100 @@
+//! The serialization formats of the `/api/v2` routes, see `controllers::v2`.
+//!
+//! Every successful response is an `Envelope`, with the requested resource
+//! in `data` and, for collections, the pagination in `meta`. Errors are
+//! `Problem`s, as described by RFC 7807.
+
+use chrono::NaiveDateTime;
+
+use crate::util::rfc3339;
+
+/// The body of every successful response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<T> {
+    pub data: T,
+    /// Only present for collections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<PageMeta>,
+}
+
+/// The pagination of a collection. `next_page` and `prev_page` are the query
+/// strings of the neighbouring pages, if any.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PageMeta {
+    pub total: i64,
+    pub page: u32,
+    pub per_page: u32,
+    pub next_page: Option<String>,
+    pub prev_page: Option<String>,
+}
+
+/// An RFC 7807 problem details object, served as `application/problem+json`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Problem {
+    /// Always `about:blank`, so `title` is the reason phrase of `status`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u32,
+    /// A machine readable identifier of the kind of problem, like `not_found`
+    pub code: String,
+    pub detail: String,
+    /// The individual errors, at least one
+    pub errors: Vec<ProblemError>,
+}
+
+/// A single error of a `Problem`, pointing at the part of the request that
+/// caused it when possible.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ProblemError {
+    pub code: String,
+    pub detail: String,
+    /// A JSON pointer into the request body, like `/crate/description`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    /// The name of the offending query parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameter: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrate {
+    pub name: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub documentation: Option<String>,
+    pub repository: Option<String>,
+    pub repository_verified: bool,
+    /// The highest version that isn't yanked, if any
+    pub max_version: Option<String>,
+    pub max_stable_version: Option<String>,
+    pub downloads: i32,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersion {
+    pub num: String,
+    pub yanked: bool,
+    pub license: Option<String>,
+    pub crate_size: Option<i32>,
+    pub rust_version: Option<String>,
+    pub downloads: i32,
+    /// The login of the user who published the version, if known
+    pub published_by: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableOwner {
+    pub login: String,
+    /// `user` or `team`
+    pub kind: String,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub avatar: Option<String>,
+}